@@ -0,0 +1,29 @@
+//! Repeated execution of one small script, with and without the
+//! compilation cache
+//!
+//! `uncached` lexes, parses and compiles on every run; `cached` only
+//! executes after the first.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::Engine;
+
+const SCRIPT: &str = "\
+function area(w, h) { return w * h; }
+let shapes = [[1, 2], [3, 4], [5, 6]];
+let total = 0;
+for (const shape of shapes) {
+    total = total + area(shape[0], shape[1]);
+}
+total";
+
+fn bench_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("repeated_script");
+    let mut uncached = Engine::new();
+    group.bench_function("uncached", |b| b.iter(|| uncached.execute_detailed(SCRIPT).result.unwrap()));
+    let mut cached = Engine::builder().compilation_cache(16).build();
+    group.bench_function("cached", |b| b.iter(|| cached.execute_detailed(SCRIPT).result.unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_cache);
+criterion_main!(benches);
@@ -0,0 +1,32 @@
+//! A million temporary option bags, from the heap and from the frame arena
+//!
+//! Each call builds an object literal that only its own function reads,
+//! the pattern `VMConfig::frame_arena` serves without allocating.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::Engine;
+
+const SCRIPT: &str = "
+function area(i) {
+    let options = { width: i, height: 2, scale: 1 };
+    options.width = options.width * options.scale;
+    return options.width * options.height;
+}
+let total = 0;
+for (let i = 0; i < 1000000; i++) {
+    total = total + area(i);
+}
+total";
+
+fn bench_option_bags(c: &mut Criterion) {
+    let mut group = c.benchmark_group("option_bags");
+    group.sample_size(10);
+    for (name, frame_arena) in [("heap", false), ("frame_arena", true)] {
+        let mut engine = Engine::builder().frame_arena(frame_arena).compilation_cache(1).build();
+        group.bench_function(name, |b| b.iter(|| engine.execute_detailed(SCRIPT).result.unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_option_bags);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+//! Interpreter throughput, with and without the profiler
+//!
+//! `idle` against `counting` shows what counting mode costs; `idle`
+//! across commits shows that a VM without a profiler pays nothing for it.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::{Engine, EngineBuilder, ProfileMode};
+
+const LOOP: &str = "\
+let total = 0;
+for (let i = 0; i < 20000; i++) {
+    total = total + (i % 7) * 3;
+}
+total";
+
+fn run(builder: EngineBuilder) {
+    let mut engine = builder.build();
+    engine.execute_detailed(LOOP).result.unwrap();
+}
+
+fn bench_profiler(c: &mut Criterion) {
+    let mut group = c.benchmark_group("interpreter_loop");
+    group.bench_function("idle", |b| b.iter(|| run(Engine::builder())));
+    group.bench_function("counting", |b| {
+        b.iter(|| run(Engine::builder().profile(ProfileMode::Counting)))
+    });
+    group.bench_function("sampling", |b| {
+        b.iter(|| run(Engine::builder().profile(ProfileMode::Sampling)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_profiler);
+criterion_main!(benches);
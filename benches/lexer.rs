@@ -0,0 +1,36 @@
+//! Lexing throughput on a large generated script
+//!
+//! The script repeats a block of typical code (declarations, calls,
+//! strings, comments) up to about 1 MB.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use v8::Lexer;
+
+const BLOCK: &str = "\
+// Totals the prices of the items in a cart
+function total(items, discount) {
+    let sum = 0;
+    for (const item of items) {
+        sum += item.price * item.quantity;
+    }
+    const label = 'total: ' + sum.toString();
+    console.log(label, \"with discount\", discount);
+    return sum - sum * discount / 100;
+}
+";
+
+fn large_script() -> String {
+    BLOCK.repeat(1024 * 1024 / BLOCK.len())
+}
+
+fn bench_lexer(c: &mut Criterion) {
+    let source = large_script();
+    let mut group = c.benchmark_group("lexer");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.sample_size(20);
+    group.bench_function("tokenize_1mb", |b| b.iter(|| Lexer::new(&source).tokenize().unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexer);
+criterion_main!(benches);
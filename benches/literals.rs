@@ -0,0 +1,29 @@
+//! Building a 500-key object literal in a loop
+//!
+//! `constant` is copied from a template in one instruction; `variable`
+//! has the same keys but reads each value from a variable, so every
+//! property is defined one at a time.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::Engine;
+
+fn script(value: &str) -> String {
+    let properties: Vec<String> = (0..500).map(|i| format!("key{}: {}", i, value)).collect();
+    format!(
+        "let v = 1; let total = 0;\nfor (let i = 0; i < 100; i++) {{\n    let o = {{ {} }};\n    total = total + o.key499;\n}}\ntotal",
+        properties.join(", "),
+    )
+}
+
+fn bench_literals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("object_literal_500_keys");
+    for (name, value) in [("constant", "1"), ("variable", "v")] {
+        let mut engine = Engine::builder().compilation_cache(4).build();
+        let source = script(value);
+        group.bench_function(name, |b| b.iter(|| engine.execute_detailed(&source).result.unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_literals);
+criterion_main!(benches);
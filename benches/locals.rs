@@ -0,0 +1,28 @@
+//! Local variable access in verified and unverified code
+//!
+//! `VM::execute_verified` skips the index checks that `execute_shared`
+//! makes on every access. The loop is almost nothing but local loads,
+//! stores and calls.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::{Engine, VerifiedFunction, VM};
+
+const LOOP: &str = "\
+function step(a, b) { let c = a + b; return c - b; }
+let total = 0;
+for (let i = 0; i < 20000; i++) {
+    total = step(total, i) + 1;
+}
+total";
+
+fn bench_locals(c: &mut Criterion) {
+    let mut group = c.benchmark_group("locals");
+    let function = Engine::new().compile_cached(LOOP).unwrap();
+    let verified = VerifiedFunction::new(function.clone()).unwrap();
+    group.bench_function("verified", |b| b.iter(|| VM::new().execute_verified(&verified).unwrap()));
+    group.bench_function("checked", |b| b.iter(|| VM::new().execute_shared(function.clone()).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_locals);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+//! Constant loads in a loop, with and without hoisting
+//!
+//! Each `','` in the loop makes a new string from the constant pool
+//! unless the optimizer has loaded it once into a local slot before the
+//! loop runs.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::Engine;
+
+const APPEND: &str = "\
+let s = '';
+for (let i = 0; i < 20000; i++) { s = s + ','; }
+s.length";
+
+fn bench_loop_constants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("loop_constants");
+    for (name, optimize) in [("loaded", false), ("hoisted", true)] {
+        let mut engine = Engine::builder().optimize(optimize).build();
+        let function = engine.compile_cached(APPEND).unwrap();
+        group.bench_function(name, |b| b.iter(|| engine.execute_compiled(&function).unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_loop_constants);
+criterion_main!(benches);
@@ -0,0 +1,27 @@
+//! Named property and global reads spread over several functions
+//!
+//! Each function reads `o.length`, `o.value` and the global `limit`, so
+//! the same names are referenced from every function in the program.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::Engine;
+
+const SCRIPT: &str = "
+let limit = 20000;
+let o = { length: 1, value: 2 };
+function a(o) { return o.length + o.value + limit; }
+function b(o) { return o.value - o.length + limit; }
+function c(o) { o.value = o.length; return limit; }
+let total = 0;
+for (let i = 0; i < limit; i++) {
+    total = total + a(o) + b(o) + c(o);
+}
+total";
+
+fn bench_property_access(c: &mut Criterion) {
+    let mut engine = Engine::builder().compilation_cache(4).build();
+    c.bench_function("shared_property_names", |b| b.iter(|| engine.execute_detailed(SCRIPT).result.unwrap()));
+}
+
+criterion_group!(benches, bench_property_access);
+criterion_main!(benches);
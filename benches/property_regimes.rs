@@ -0,0 +1,173 @@
+//! Named property reads under three object layouts, checked against a
+//! saved baseline
+//!
+//! Each regime reads `o.x` from 64 objects in a loop, with the VM's
+//! property caches on (`VMConfig::property_caches`). The objects all
+//! share one layout (monomorphic), come in 4 layouts (polymorphic) or in
+//! 50 (megamorphic); layout `k` has `k` properties ahead of `x`, so each
+//! puts `x` at a different position. Times are divided by that of the
+//! monomorphic loop with the caches switched off, the dictionary-mode
+//! path every read took before the caches, so what is compared is the
+//! caches' effect rather than the speed of the machine.
+//!
+//! The caches are reset before every sample, and after it the harness
+//! checks that the read site ended in the state its regime is named for.
+//!
+//! `cargo bench --bench property_regimes` compares the ratios with
+//! `benches/baselines/property_regimes.json` and fails when the
+//! monomorphic one is over its baseline by more than the tolerance plus
+//! the spread of the samples the baseline was saved from. The tolerance
+//! is saved with the baseline; `PROPERTY_REGIMES_TOLERANCE` (a
+//! percentage) overrides it. The other regimes are only reported.
+//! `-- --save-baseline` records new ratios instead.
+
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use v8::bytecode::BytecodeFunction;
+use v8::{Engine, PropertyCacheStats, VMConfig, VM};
+
+const BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/benches/baselines/property_regimes.json");
+const DEFAULT_TOLERANCE_PERCENT: f64 = 15.0;
+const OBJECTS: usize = 64;
+const ROUNDS: usize = 1500;
+const SAMPLES: usize = 15;
+
+/// Regime names and how many layouts their objects come in
+const REGIMES: [(&str, usize); 3] = [("monomorphic", 1), ("polymorphic", 4), ("megamorphic", 50)];
+
+/// A regime's time relative to the calibration loop, over the samples
+#[derive(Debug, Serialize, Deserialize)]
+struct Measurement {
+    /// Median ratio
+    ratio: f64,
+    /// Interquartile range of the ratios, as a percentage of the median
+    spread_percent: f64,
+}
+
+impl Measurement {
+    fn of(mut ratios: Vec<f64>) -> Self {
+        ratios.sort_by(f64::total_cmp);
+        let ratio = ratios[ratios.len() / 2];
+        let spread = ratios[ratios.len() * 3 / 4] - ratios[ratios.len() / 4];
+        Self { ratio, spread_percent: spread / ratio * 100.0 }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Baseline {
+    /// How far, in percent, the monomorphic ratio may go over its
+    /// baseline (beyond the baseline's spread) before it counts as a
+    /// regression
+    tolerance_percent: f64,
+    regimes: BTreeMap<String, Measurement>,
+}
+
+/// A loop over `OBJECTS` objects in `layouts` layouts, reading `x` from
+/// each
+fn script(layouts: usize) -> String {
+    let makers: String = (0..layouts)
+        .map(|layout| {
+            let pads: String = (0..layout).map(|pad| format!("pad{}: 0, ", pad)).collect();
+            format!("if (layout === {}) {{ return {{ {}x: layout }}; }}\n", layout, pads)
+        })
+        .collect();
+    format!("\
+function make(layout) {{
+{makers}}}
+let items = [];
+for (let i = 0; i < {OBJECTS}; i++) {{ items.push(make(i % {layouts})); }}
+let total = 0;
+for (let r = 0; r < {ROUNDS}; r++) {{
+    for (let j = 0; j < {OBJECTS}; j++) {{ total = total + items[j].x; }}
+}}
+total")
+}
+
+/// Time of running `function` on `vm`, starting from empty caches
+fn time(vm: &mut VM, function: &Arc<BytecodeFunction>) -> f64 {
+    vm.reset_property_caches();
+    let start = Instant::now();
+    vm.execute_shared(function.clone()).expect("the benchmark script runs");
+    start.elapsed().as_secs_f64()
+}
+
+/// Stop the run if the `o.x` site did not end in the state `regime` is
+/// named for, since the numbers would then not measure that regime
+fn check_state(regime: &str, stats: PropertyCacheStats) {
+    let sites = [stats.monomorphic_sites, stats.polymorphic_sites, stats.megamorphic_sites];
+    let expected = match regime {
+        "monomorphic" => [1, 0, 0],
+        "polymorphic" => [0, 1, 0],
+        _ => [0, 0, 1],
+    };
+    assert_eq!(sites, expected, "the {} read site ended in the wrong state: {:?}", regime, stats);
+}
+
+fn measure(tolerance_percent: f64) -> Baseline {
+    let mut engine = Engine::new();
+    let mut compile = |layouts| engine.compile_cached(&script(layouts)).expect("the benchmark script compiles");
+    let mut cached = VM::with_config(VMConfig { property_caches: true, ..VMConfig::default() });
+    // The calibration: same-layout reads in dictionary mode
+    let mut dictionary = VM::new();
+    let calibration = compile(1);
+    let regimes = REGIMES.iter()
+        .map(|&(name, layouts)| {
+            let function = compile(layouts);
+            let ratios = (0..SAMPLES)
+                .map(|_| {
+                    let ratio = time(&mut cached, &function) / time(&mut dictionary, &calibration);
+                    check_state(name, cached.property_cache_stats().expect("the caches are on"));
+                    ratio
+                })
+                .collect();
+            (name.to_string(), Measurement::of(ratios))
+        })
+        .collect();
+    Baseline { tolerance_percent, regimes }
+}
+
+/// `PROPERTY_REGIMES_TOLERANCE`, if set
+fn tolerance_override() -> Option<f64> {
+    let value = std::env::var("PROPERTY_REGIMES_TOLERANCE").ok()?;
+    Some(value.parse().unwrap_or_else(|_| panic!("PROPERTY_REGIMES_TOLERANCE must be a percentage, not '{}'", value)))
+}
+
+fn main() -> ExitCode {
+    if std::env::args().any(|arg| arg == "--save-baseline") {
+        let measured = measure(tolerance_override().unwrap_or(DEFAULT_TOLERANCE_PERCENT));
+        let json = serde_json::to_string_pretty(&measured).expect("baselines serialize") + "\n";
+        std::fs::write(BASELINE_PATH, json).expect("the baseline can be written");
+        println!("Saved {}", BASELINE_PATH);
+        return ExitCode::SUCCESS;
+    }
+    
+    let baseline: Baseline = serde_json::from_str(&std::fs::read_to_string(BASELINE_PATH).expect("a saved baseline"))
+        .expect("the saved baseline is valid");
+    let tolerance = tolerance_override().unwrap_or(baseline.tolerance_percent);
+    let measured = measure(tolerance);
+    let mut regressed = false;
+    for (name, _) in REGIMES {
+        let now = &measured.regimes[name];
+        let Some(before) = baseline.regimes.get(name) else {
+            println!("{:<12} {:>6.3} x calibration, no baseline", name, now.ratio);
+            // Only the monomorphic case is gated; a missing baseline fails it
+            regressed |= name == "monomorphic";
+            continue;
+        };
+        let change = (now.ratio / before.ratio - 1.0) * 100.0;
+        let limit = tolerance + before.spread_percent;
+        println!("{:<12} {:>6.3} x calibration, baseline {:>6.3} ({:+.1}%, limit {:+.1}%)",
+                 name, now.ratio, before.ratio, change, limit);
+        if name == "monomorphic" && change > limit {
+            regressed = true;
+        }
+    }
+    if regressed {
+        eprintln!("Monomorphic property reads regressed by more than the tolerance plus the baseline's spread");
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,22 @@
+//! Engine creation: building the globals versus copying a snapshot
+//!
+//! The `engine` cases create an engine and run an empty script, which is
+//! when it creates its VM; the `vm` cases create just the VM.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use v8::{Engine, Snapshot, VMConfig, VM};
+
+fn bench_startup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("startup");
+    let snapshot = Snapshot::standard().unwrap();
+    group.bench_function("engine/built", |b| b.iter(|| Engine::new().execute("").unwrap()));
+    group.bench_function("engine/snapshot", |b| {
+        b.iter(|| Engine::builder().snapshot(snapshot.clone()).build().execute("").unwrap())
+    });
+    group.bench_function("vm/built", |b| b.iter(VM::new));
+    group.bench_function("vm/snapshot", |b| b.iter(|| VM::with_snapshot(VMConfig::default(), &snapshot)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_startup);
+criterion_main!(benches);
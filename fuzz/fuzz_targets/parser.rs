@@ -0,0 +1,21 @@
+//! Parse arbitrary text: it may fail, but must not panic or hang, and a
+//! program it accepts must pass `ast::validate_source`
+//!
+//! `cargo fuzz run parser corpus/parser seeds/parser ../tests/conformance/cases -- -timeout=10`
+//! (nightly), from `fuzz/`. New inputs go to the first corpus directory;
+//! `seeds/parser` and the conformance cases only seed it. Add anything
+//! this finds to `CRASHERS` in `tests/parser_fuzz_tests.rs`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use v8::ast::validate_source;
+use v8::{Lexer, Parser};
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(source) = std::str::from_utf8(data) else { return };
+    if let Ok(program) = Parser::from_source(Lexer::new(source)).parse() {
+        let violations = validate_source(&program, source);
+        assert!(violations.is_empty(), "invalid AST: {:?}", violations);
+    }
+});
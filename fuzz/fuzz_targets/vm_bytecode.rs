@@ -0,0 +1,125 @@
+//! Run arbitrary bytecode on a VM: it may fail, but must not panic or hang
+//!
+//! `cargo fuzz run vm_bytecode` (nightly). The input is read as a header
+//! byte for the function's shape, then three bytes per instruction: an
+//! opcode and a little-endian operand.
+
+#![no_main]
+
+use std::sync::Arc;
+
+use libfuzzer_sys::fuzz_target;
+use v8::bytecode::{ConstIndex, ConstantValue, JumpOffset, LocalIndex};
+use v8::{Bytecode, BytecodeFunction, VMConfig, VM};
+
+/// Instruction for `opcode`, with `operand` as its index, count or offset
+fn decode(opcode: u8, operand: u16) -> Bytecode {
+    let offset = JumpOffset(operand as i16);
+    let index = ConstIndex(operand);
+    let local = LocalIndex(operand);
+    let small = operand as u8;
+    match opcode % 74 {
+        0 => Bytecode::LdaConst(index),
+        1 => Bytecode::LdaSmi(operand as i16),
+        2 => Bytecode::LdaLocal(local),
+        3 => Bytecode::StaLocal(local),
+        4 => Bytecode::LdaGlobal(index),
+        5 => Bytecode::StaGlobal(index),
+        6 => Bytecode::LdaContextSlot(small, LocalIndex(operand >> 8)),
+        7 => Bytecode::StaContextSlot(small, LocalIndex(operand >> 8)),
+        8 => Bytecode::Push,
+        9 => Bytecode::Pop,
+        10 => Bytecode::Dup,
+        11 => Bytecode::Add,
+        12 => Bytecode::Sub,
+        13 => Bytecode::Mul,
+        14 => Bytecode::Div,
+        15 => Bytecode::Mod,
+        16 => Bytecode::Pow,
+        17 => Bytecode::Inc,
+        18 => Bytecode::Dec,
+        19 => Bytecode::Eq,
+        20 => Bytecode::Ne,
+        21 => Bytecode::StrictEq,
+        22 => Bytecode::StrictNe,
+        23 => Bytecode::Lt,
+        24 => Bytecode::Gt,
+        25 => Bytecode::Le,
+        26 => Bytecode::Ge,
+        27 => Bytecode::InstanceOf,
+        28 => Bytecode::In,
+        29 => Bytecode::LogicalAnd,
+        30 => Bytecode::LogicalOr,
+        31 => Bytecode::LogicalNot,
+        32 => Bytecode::ToBoolean,
+        33 => Bytecode::BitwiseAnd,
+        34 => Bytecode::BitwiseOr,
+        35 => Bytecode::BitwiseXor,
+        36 => Bytecode::LeftShift,
+        37 => Bytecode::RightShift,
+        38 => Bytecode::UnsignedRightShift,
+        39 => Bytecode::BitwiseNot,
+        40 => Bytecode::UnaryPlus,
+        41 => Bytecode::UnaryMinus,
+        42 => Bytecode::TypeOf,
+        43 => Bytecode::LdaThis,
+        44 => Bytecode::LdaNamed(index),
+        45 => Bytecode::StaNamed(index),
+        46 => Bytecode::LdaKeyed,
+        47 => Bytecode::StaKeyed,
+        48 => Bytecode::DeleteNamedProperty(index),
+        49 => Bytecode::DeleteKeyedProperty,
+        50 => Bytecode::Call(small),
+        51 => Bytecode::CallMethod(small),
+        52 => Bytecode::Construct(small),
+        53 => Bytecode::Return,
+        54 => Bytecode::ReturnUndefined,
+        55 => Bytecode::Yield,
+        56 => Bytecode::Jump(offset),
+        57 => Bytecode::JumpIfFalse(offset),
+        58 => Bytecode::JumpIfTrue(offset),
+        59 => Bytecode::JumpIfNullish(offset),
+        60 => Bytecode::GetIterator,
+        61 => Bytecode::IteratorNext(offset),
+        62 => Bytecode::IteratorClose,
+        63 => Bytecode::CreateObject,
+        64 => Bytecode::CreateArray(operand),
+        65 => Bytecode::StaArrayElement(operand),
+        66 => Bytecode::DefineNamedProperty(index),
+        67 => Bytecode::DefineKeyedProperty,
+        68 => Bytecode::CopyDataProperties,
+        69 => Bytecode::CreateClosure(index),
+        70 => Bytecode::CreateFromTemplate(index),
+        71 => Bytecode::LdaGlobalInsideTypeof(index),
+        72 => Bytecode::InitLocal(local),
+        _ => Bytecode::Debugger,
+    }
+}
+
+/// A function whose code is `data`; the nested function, reachable with
+/// `CreateClosure(0)`, runs the same code as a generator or plain function
+fn build(data: &[u8]) -> BytecodeFunction {
+    let (header, code) = data.split_first().map_or((0, data), |(&header, code)| (header, code));
+    let arity = header & 3;
+    let mut function = BytecodeFunction::new("fuzz".to_string(), arity, arity as u16 + (header >> 2 & 7) as u16, 0);
+    function.constants.add_number(1.5);
+    function.constants.add_string("text".to_string());
+    function.add_property_name("length");
+    function.add_property_name("prototype");
+    function.constants.add_regex("a+".to_string(), "g".to_string());
+    function.constants.add_array_template(vec![Some(ConstantValue::Null), None]);
+    for chunk in code.chunks_exact(3) {
+        function.add_instruction(decode(chunk[0], u16::from_le_bytes([chunk[1], chunk[2]])));
+    }
+    function.is_strict = header & 0x20 != 0;
+    
+    let mut nested = function.clone();
+    nested.is_generator = header & 0x40 != 0;
+    function.functions.push(Arc::new(nested));
+    function
+}
+
+fuzz_target!(|data: &[u8]| {
+    let config = VMConfig { max_instructions: Some(20_000), max_call_depth: 500, ..VMConfig::default() };
+    let _ = VM::with_config(config).execute(build(data));
+});
@@ -0,0 +1,4 @@
+a
+++b
+let o = { ...p, [k]: 1, "s": 2, 3: 4 }
+return
@@ -0,0 +1,3 @@
+class A { constructor(v) { this.v = v; } static of(v) { return new A(v); } }
+let g = function* () { yield 1; };
+let h = async (p) => await p;
@@ -0,0 +1,4 @@
+for (let i = 0; i < 3; i++) {
+  if (i % 2) { continue; } else { x = [i, , { k: i }]; }
+}
+for (const v of xs) while (v) break;
@@ -0,0 +1,108 @@
+#ifndef V8RS_H
+#define V8RS_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit by hand. */
+
+#include <stdbool.h>
+#include <stddef.h>
+
+// Which field of a `V8rsValue` holds the value
+typedef enum V8rsValueTag {
+  V8RS_VALUE_TAG_UNDEFINED,
+  V8RS_VALUE_TAG_NULL,
+  // `boolean` holds the value
+  V8RS_VALUE_TAG_BOOLEAN,
+  // `number` holds the value
+  V8RS_VALUE_TAG_NUMBER,
+  // `string` holds the UTF-8 text
+  V8RS_VALUE_TAG_STRING,
+  // `string` holds the object or array serialized as JSON
+  V8RS_VALUE_TAG_JSON,
+} V8rsValueTag;
+
+// Opaque engine handle
+typedef struct V8rsEngine V8rsEngine;
+
+// A JavaScript value crossing the C boundary
+//
+// Primitives are passed by value, symbols as their description, and
+// objects and arrays as JSON text.
+// Fields not selected by `tag` are zero.
+typedef struct V8rsValue {
+  enum V8rsValueTag tag;
+  bool boolean;
+  double number;
+  // NUL-terminated UTF-8, or null
+  char *string;
+} V8rsValue;
+
+// Host function implemented in C
+//
+// Receives the `user_data` it was registered with and `argc` arguments.
+// It writes its result to `out`, which starts out `undefined`, and
+// returns 0. Returning anything else throws: a `String` result becomes
+// the error message. Results tagged `Json` are not accepted.
+typedef int (*V8rsCallback)(void *user_data,
+                            const struct V8rsValue *args,
+                            size_t argc,
+                            struct V8rsValue *out);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Create an engine; returns null if creation fails
+struct V8rsEngine *v8rs_engine_new(void);
+
+// Free an engine; null is ignored
+//
+// # Safety
+// `engine` must be null or come from `v8rs_engine_new` and not have
+// been freed.
+void v8rs_engine_free(struct V8rsEngine *engine);
+
+// Execute `len` bytes of UTF-8 source
+//
+// On success returns 0 and stores the completion value in `out_result`
+// (which may be null to discard it). On failure returns -1, leaves
+// `out_result` `undefined`, and sets the last error message.
+//
+// # Safety
+// `engine` must be a live engine and `source` must point to `len`
+// readable bytes. `out_result` must be null or writable.
+int v8rs_execute(struct V8rsEngine *engine,
+                 const char *source,
+                 size_t len,
+                 struct V8rsValue *out_result);
+
+// Release the string of a value filled in by `v8rs_execute` and reset
+// it to `undefined`; null is ignored
+//
+// # Safety
+// `value` must be null or a value written by `v8rs_execute` whose
+// string has not been freed.
+void v8rs_value_free(struct V8rsValue *value);
+
+// Expose `callback` to scripts as the global function `name`
+//
+// `user_data` is passed back to every call and is never dereferenced
+// by the engine; it must stay valid as long as the engine may call the
+// function. Returns 0, or -1 if the arguments are invalid.
+//
+// # Safety
+// `engine` must be a live engine and `name` a NUL-terminated string.
+int v8rs_register_function(struct V8rsEngine *engine,
+                           const char *name,
+                           V8rsCallback callback,
+                           void *user_data);
+
+// Message of the last failing call on this thread, or null
+//
+// The string is owned by the library; do not free it.
+const char *v8rs_last_error_message(void);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* V8RS_H */
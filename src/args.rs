@@ -0,0 +1,245 @@
+//! Command-line arguments of the `v8` tool
+//!
+//! Flags may come in any order around the script name. At most one flag
+//! picks what to do with the script (`--debug-ast`, `--profile`, ...);
+//! without one the script runs, and without a script either the REPL
+//! starts. `-` names stdin as the script.
+
+/// Largest script read when `--max-source-size` isn't given: 256 MiB
+pub const DEFAULT_MAX_SOURCE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// What to do with the script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Run it, or start the REPL without one
+    Run,
+    /// Print the usage text
+    Help,
+    DebugTokens,
+    DebugAst,
+    DebugBytecode,
+    /// Print the AST as ESTree JSON without running
+    DumpAst,
+    /// Run, writing the bytecode as JSON or text first
+    DumpBytecode { json: bool },
+    /// Print how the script's bytecode differs from `Options::diff_base`'s
+    DiffBytecode,
+    Profile,
+    Timing,
+}
+
+impl Mode {
+    /// Modes that have nothing to work on without a script
+    fn needs_script(self) -> bool {
+        matches!(self, Mode::DumpAst | Mode::DumpBytecode { .. } | Mode::DiffBytecode | Mode::Profile | Mode::Timing)
+    }
+}
+
+/// Code given with `-e` or `-p`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub code: String,
+    /// Print the completion value (`-p`)
+    pub print: bool,
+}
+
+/// Everything the command line asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    pub mode: Mode,
+    /// Script file name; `-` is stdin
+    pub script: Option<String>,
+    /// `-e` and `-p` code, in the order given
+    pub snippets: Vec<Snippet>,
+    pub max_source_size: u64,
+    /// Where `--dump-bytecode` writes, instead of stderr
+    pub output: Option<String>,
+    /// The old side of `--diff-bytecode`: a script, or a JSON dump
+    pub diff_base: Option<String>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Run,
+            script: None,
+            snippets: Vec::new(),
+            max_source_size: DEFAULT_MAX_SOURCE_SIZE,
+            output: None,
+            diff_base: None,
+        }
+    }
+}
+
+impl Options {
+    /// Parse the arguments after the program name
+    ///
+    /// The error is a message for the user.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline_value) = match arg.split_once('=') {
+                Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mode = match flag {
+                "-h" | "--help" => Some(Mode::Help),
+                "--debug-tokens" => Some(Mode::DebugTokens),
+                "--debug-ast" => Some(Mode::DebugAst),
+                "--debug-bytecode" => Some(Mode::DebugBytecode),
+                "--profile" => Some(Mode::Profile),
+                "--timing" => Some(Mode::Timing),
+                "--dump-ast" => match inline_value.as_deref() {
+                    Some("json") => Some(Mode::DumpAst),
+                    _ => return Err("--dump-ast writes json only: use --dump-ast=json".to_string()),
+                },
+                "--dump-bytecode" => match inline_value.as_deref() {
+                    None | Some("text") => Some(Mode::DumpBytecode { json: false }),
+                    Some("json") => Some(Mode::DumpBytecode { json: true }),
+                    Some(format) => return Err(format!("--dump-bytecode writes text or json, not '{}'", format)),
+                },
+                "--diff-bytecode" => {
+                    options.diff_base = Some(inline_value.clone().or_else(|| args.next()).ok_or("--diff-bytecode needs the old script")?);
+                    Some(Mode::DiffBytecode)
+                }
+                _ => None,
+            };
+            if let Some(mode) = mode {
+                options.set_mode(mode, &arg)?;
+                continue;
+            }
+            match flag {
+                "--max-source-size" => {
+                    let value = inline_value.or_else(|| args.next()).unwrap_or_default();
+                    options.max_source_size = value.parse()
+                        .map_err(|_| format!("--max-source-size needs a size in bytes, not '{}'", value))?;
+                }
+                "--output" => {
+                    options.output = Some(args.next().ok_or("--output needs a file name")?);
+                }
+                "-e" | "-p" => {
+                    let code = args.next().ok_or_else(|| format!("{} needs code to run", flag))?;
+                    options.snippets.push(Snippet { code, print: flag == "-p" });
+                }
+                "-" => options.set_script(arg)?,
+                _ if flag.starts_with('-') => return Err(format!("Unknown option '{}' (see --help)", arg)),
+                _ => options.set_script(arg)?,
+            }
+        }
+        options.check()?;
+        Ok(options)
+    }
+    
+    fn set_mode(&mut self, mode: Mode, flag: &str) -> Result<(), String> {
+        if self.mode != Mode::Run && self.mode != mode {
+            return Err(format!("{} cannot be combined with another mode flag", flag));
+        }
+        self.mode = mode;
+        Ok(())
+    }
+    
+    fn set_script(&mut self, script: String) -> Result<(), String> {
+        if let Some(first) = &self.script {
+            return Err(format!("Only one script can be given, not both '{}' and '{}'", first, script));
+        }
+        self.script = Some(script);
+        Ok(())
+    }
+    
+    /// Reject combinations that leave a flag without effect
+    fn check(&self) -> Result<(), String> {
+        if self.output.is_some() && !matches!(self.mode, Mode::DumpBytecode { .. }) {
+            return Err("--output is only used with --dump-bytecode".to_string());
+        }
+        if !self.snippets.is_empty() {
+            if self.mode != Mode::Run {
+                return Err("-e and -p only run code; they take no mode flag".to_string());
+            }
+            if let Some(script) = &self.script {
+                return Err(format!("-e and -p cannot be combined with the script '{}'", script));
+            }
+        }
+        if self.mode.needs_script() && self.script.is_none() {
+            return Err("This mode needs a script: give a file name, or - for stdin (see --help)".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The `--help` text
+pub fn usage(program: &str) -> String {
+    format!("\
+Usage: {program} [options] [file.js | -]
+
+  {program}                        Start the REPL
+  {program} file.js                Run a script and print its completion value
+  {program} -                      Run the script read from stdin
+  {program} -e CODE                Run CODE; repeat to run several in order in one VM
+  {program} -p CODE                Like -e, and print the completion value
+
+Options:
+  --debug-tokens [file.js]     Show tokens (interactive without a file)
+  --debug-ast [file.js]        Show the detailed AST tree (interactive without a file)
+  --debug-bytecode [file.js]   Show compiled bytecode, then run (interactive without a file)
+  --dump-ast=json file.js      Print the AST as ESTree JSON without running it
+  --dump-bytecode[=text|json] file.js
+                               Run, writing bytecode in a stable format to stderr
+  --diff-bytecode OLD file.js  Print how the bytecode of file.js differs from OLD's,
+                               where either may be a --dump-bytecode=json file
+  --output FILE                Write --dump-bytecode output to FILE instead
+  --profile file.js            Run and print executed instructions per line
+  --timing file.js             Run and print time per phase and counters
+  --max-source-size BYTES      Refuse larger scripts (default {DEFAULT_MAX_SOURCE_SIZE})
+  -h, --help                   Show this help
+")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+    
+    #[test]
+    fn test_flags_in_any_order() {
+        let options = parse(&["--output", "out.txt", "script.js", "--dump-bytecode=json", "--max-source-size=10"]).unwrap();
+        assert_eq!(options.mode, Mode::DumpBytecode { json: true });
+        assert_eq!(options.script.as_deref(), Some("script.js"));
+        assert_eq!(options.output.as_deref(), Some("out.txt"));
+        assert_eq!(options.max_source_size, 10);
+        
+        let options = parse(&["-e", "let a = 1", "-p", "a"]).unwrap();
+        assert_eq!(options.snippets, [
+            Snippet { code: "let a = 1".to_string(), print: false },
+            Snippet { code: "a".to_string(), print: true },
+        ]);
+        assert_eq!(parse(&[]).unwrap(), Options::default());
+        assert_eq!(parse(&["--debug-ast", "-"]).unwrap().script.as_deref(), Some("-"));
+        
+        let options = parse(&["--diff-bytecode", "old.js", "new.js"]).unwrap();
+        assert_eq!(options.mode, Mode::DiffBytecode);
+        assert_eq!(options.diff_base.as_deref(), Some("old.js"));
+        assert_eq!(options.script.as_deref(), Some("new.js"));
+    }
+    
+    #[test]
+    fn test_invalid_combinations() {
+        for (args, message) in [
+            (&["--profile", "--timing", "a.js"][..], "--timing cannot be combined with another mode flag"),
+            (&["a.js", "b.js"], "Only one script can be given, not both 'a.js' and 'b.js'"),
+            (&["-e"], "-e needs code to run"),
+            (&["-e", "1", "a.js"], "-e and -p cannot be combined with the script 'a.js'"),
+            (&["--timing", "-p", "1"], "-e and -p only run code; they take no mode flag"),
+            (&["--profile"], "This mode needs a script: give a file name, or - for stdin (see --help)"),
+            (&["--diff-bytecode"], "--diff-bytecode needs the old script"),
+            (&["--diff-bytecode", "old.js"], "This mode needs a script: give a file name, or - for stdin (see --help)"),
+            (&["--output", "x", "a.js"], "--output is only used with --dump-bytecode"),
+            (&["--verbose"], "Unknown option '--verbose' (see --help)"),
+        ] {
+            assert_eq!(parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+}
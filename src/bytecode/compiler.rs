@@ -10,10 +10,23 @@
 //! - **JumpPatch**: Forward jump resolution for control flow
 //! - **LoopContext**: Break/continue handling in loops
 
-use std::collections::HashMap;
-use crate::error::{Error, Result, Span};
-use crate::parser::ast::{Program, Stmt, Expr, BinaryOp, UnaryOp, Literal};
-use super::{BytecodeFunction, Bytecode, ConstIndex, LocalIndex};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::error::{Diagnostic, Error, ErrorCode, Result, Span};
+use crate::lexer::Lexer;
+use crate::parser::{Parser, DEFAULT_MAX_NESTING_DEPTH};
+use crate::parser::ast::{Program, Stmt, Expr, BinaryOp, UnaryOp, PostfixUnaryOp, Literal, PropertyDefinition, PropertyKey, ClassBody};
+use crate::vm::{pure_builtin, Value};
+use super::escape;
+use super::hoist;
+use super::layout::{self, FunctionLayout, ScopeLayout};
+use super::{ArgCount, BytecodeFunction, Bytecode, ConstIndex, ConstantValue, HashableF64, ContextDepth, ElementIndex, JumpOffset, LocalIndex, LocalVariable, SmallInteger, StringTable};
+
+/// Most locals, parameters included, one function can have, as
+/// `BytecodeFunction::locals_count` is a `u16`
+pub const MAX_LOCALS: usize = u16::MAX as usize;
 
 /// Local variable slot assignment
 #[derive(Debug, Clone)]
@@ -22,6 +35,17 @@ pub struct LocalSlot {
     pub index: LocalIndex,
     pub is_parameter: bool,
     pub span: Span,
+    /// Whether any code reads the variable (writes alone don't count)
+    pub is_read: bool,
+    /// Whether the variable was declared with `const`
+    pub is_const: bool,
+    /// Whether the variable was declared with `let`, `const`, `class` or
+    /// `function`, so the function's own code can't reach it by name
+    /// before its declaration
+    pub is_lexical: bool,
+    /// Offset of the first instruction compiled after the declaration;
+    /// `usize::MAX` until the compiler reaches it
+    pub declared_at: usize,
 }
 
 /// Scope for variable resolution and local slot management
@@ -33,6 +57,10 @@ pub struct Scope {
     pub parent: Option<usize>,
     /// Scope type (function, block, etc.)
     pub scope_type: ScopeType,
+    /// First local slot free when the scope was entered
+    pub first_slot: u16,
+    /// Offset of the first instruction compiled in the scope
+    pub start: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -62,124 +90,490 @@ pub struct LoopContext {
     pub continue_patches: Vec<usize>,
 }
 
+/// Where a variable reference resolves to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VariableLocation {
+    /// Slot in the current function's environment
+    Local(LocalIndex),
+    /// Slot in an enclosing function's environment
+    Context(ContextDepth, LocalIndex),
+    /// Global object property
+    Global,
+}
+
+/// What `compile_function` is compiling
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FunctionKind {
+    /// `function` declaration or expression
+    Normal,
+    /// `function*`: calls return a generator instead of running the body
+    Generator,
+    /// `async function`: the body runs as a generator that the VM resumes
+    /// each time an awaited value settles
+    Async,
+    /// A class's constructor: strict, and callable only with `new`
+    ClassConstructor,
+    /// A method in a class body: strict
+    ClassMethod,
+    /// An arrow function: `this` is the one where it was created
+    Arrow,
+    /// `async` arrow function
+    AsyncArrow,
+}
+
+impl FunctionKind {
+    fn plain(is_generator: bool, is_async: bool) -> Self {
+        match (is_generator, is_async) {
+            (true, _) => FunctionKind::Generator,
+            (false, true) => FunctionKind::Async,
+            (false, false) => FunctionKind::Normal,
+        }
+    }
+}
+
 /// Main AST to Bytecode compiler
 pub struct Compiler {
     /// The function being compiled
     function: BytecodeFunction,
+    /// Property names for the whole program, shared with nested compilers
+    names: Rc<RefCell<StringTable>>,
     /// Stack of scopes (for nested scopes)
     scopes: Vec<Scope>,
-    /// Counter for next local variable index
-    next_local_index: LocalIndex,
+    /// First slot after the parameters and hidden locals, where the
+    /// layout of the function's declarations starts
+    next_local_index: u16,
+    /// Variables visible from enclosing functions, innermost last
+    enclosing: Vec<HashMap<String, LocalSlot>>,
+    /// Reads of enclosing functions' variables, reported back to them
+    captured_reads: HashSet<(ContextDepth, String)>,
+    /// Loops being compiled, innermost last
+    loops: Vec<LoopContext>,
+    /// Non-fatal warnings collected while compiling
+    diagnostics: Vec<Diagnostic>,
+    /// Line of the statement being compiled, recorded for instructions
+    /// emitted without a span
+    line: Option<u32>,
+    /// Hidden local holding the completion value of the statements run so
+    /// far, when compiling with `with_completion_values`
+    completion: Option<LocalIndex>,
+    /// Statements and expressions being compiled inside one another,
+    /// counted across nested functions
+    depth: usize,
+    /// Deepest nesting allowed before compiling fails
+    max_depth: usize,
+    /// Variables of this function whose literal never escapes it, so its
+    /// allocation becomes an arena site
+    non_escaping: HashSet<String>,
+    /// Evaluate calls of pure built-ins on constants while compiling (see
+    /// `with_builtin_folding`)
+    fold_builtins: bool,
+    /// Most constant loads to hoist out of the loops of each function; 0
+    /// leaves loops alone (see `with_constant_hoisting`)
+    max_hoisted_constants: usize,
+    /// Whether top-level declarations bind globals, as REPL input's do
+    /// (see `with_top_level_globals`)
+    top_level_globals: bool,
+    /// Globals bound by top-level declarations of this input or earlier
+    /// ones, and whether each is `const`
+    top_level_bindings: HashMap<String, bool>,
+    /// Layouts of the scopes still to be entered, the next one last
+    pending_scopes: Vec<ScopeLayout>,
 }
 
 impl Compiler {
     /// Create a new compiler for a main program
     pub fn new_main(source: &str) -> Self {
         let mut function = BytecodeFunction::new_main();
-        function.debug_info.set_source_code(source.to_string());
+        function.debug_info.set_source_code(Arc::from(source));
         
         Self {
             function,
+            names: Rc::default(),
             scopes: vec![Scope {
                 locals: HashMap::new(),
                 parent: None,
                 scope_type: ScopeType::Function,
+                first_slot: 0,
+                start: 0,
             }],
             next_local_index: 0,
+            enclosing: Vec::new(),
+            captured_reads: HashSet::new(),
+            loops: Vec::new(),
+            diagnostics: Vec::new(),
+            line: None,
+            completion: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            non_escaping: HashSet::new(),
+            fold_builtins: false,
+            max_hoisted_constants: 0,
+            top_level_globals: false,
+            top_level_bindings: HashMap::new(),
+            pending_scopes: Vec::new(),
         }
     }
     
     /// Create a new compiler for a function
     pub fn new_function(name: String, params: &[String], source: &str) -> Self {
+        let params: Vec<(String, Span)> = params.iter().map(|param| (param.clone(), Span::new(0, 0, 1, 1))).collect();
+        Self::new_function_in(name, &params, Arc::from(source))
+    }
+    
+    /// Create a compiler for a function whose program source is already
+    /// retained, sharing it rather than copying it
+    fn new_function_in(name: String, params: &[(String, Span)], source: Arc<str>) -> Self {
         let arity = params.len() as u8;
-        let mut function = BytecodeFunction::new(name, arity, 0, 0);
-        function.debug_info.set_source_code(source.to_string());
+        let mut function = BytecodeFunction::new(name, arity, arity as u16, 0);
+        function.debug_info.set_source_code(source);
         
         // Create function scope with parameters
         let mut scope = Scope {
             locals: HashMap::new(),
             parent: None,
             scope_type: ScopeType::Function,
+            first_slot: 0,
+            start: 0,
         };
         
         let mut next_local = 0;
         // Parameters are local variables 0..arity
-        for (i, param) in params.iter().enumerate() {
+        for (i, (param, span)) in params.iter().enumerate() {
             scope.locals.insert(param.clone(), LocalSlot {
                 name: param.clone(),
-                index: i as LocalIndex,
+                index: LocalIndex(i as u16),
                 is_parameter: true,
-                span: Span::new(0, 0, 1, 1), // TODO: Get real span from AST
+                span: *span,
+                is_read: false,
+                is_const: false,
+                is_lexical: false,
+                declared_at: 0,
             });
-            next_local = i as LocalIndex + 1;
+            next_local = i as u16 + 1;
         }
         
         Self {
             function,
+            names: Rc::default(),
             scopes: vec![scope],
             next_local_index: next_local,
+            enclosing: Vec::new(),
+            captured_reads: HashSet::new(),
+            loops: Vec::new(),
+            diagnostics: Vec::new(),
+            line: None,
+            completion: None,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            non_escaping: HashSet::new(),
+            fold_builtins: false,
+            max_hoisted_constants: 0,
+            top_level_globals: false,
+            top_level_bindings: HashMap::new(),
+            pending_scopes: Vec::new(),
+        }
+    }
+    
+    
+    /// Limit how deeply statements and expressions may nest (by default
+    /// `DEFAULT_MAX_NESTING_DEPTH`, as in the parser)
+    ///
+    /// An AST built some other way than by parsing can be deeper than the
+    /// parser allows, so the compiler enforces the limit again rather than
+    /// overflowing the stack. Chains of binary operators, which the parser
+    /// builds without nesting, compile without nesting too.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+    
+    /// Evaluate calls of pure built-ins on constant arguments while
+    /// compiling, replacing each with its result
+    ///
+    /// `parseInt("42")` compiles to the constant 42, and `"abc".length` to
+    /// 3. Only the built-ins in `vm::pure` qualify, called through the
+    /// global they live on (`parseInt`, `Number.isInteger`) or as a method
+    /// of a string constant, and only when every argument is a constant
+    /// or itself a folded call. A call that fails or returns an object is
+    /// compiled as usual, so `JSON.parse("{")` still throws when it runs,
+    /// not while compiling.
+    ///
+    /// Folding assumes scripts leave the built-ins alone: a program that
+    /// replaces `Number.isInteger` still gets the original for calls
+    /// folded before it did. `EngineBuilder::optimize` turns this on.
+    pub fn with_builtin_folding(mut self) -> Self {
+        self.fold_builtins = true;
+        self
+    }
+    
+    /// Load up to `max_constants` of the primitive constants a function's
+    /// loops use once, before the loops run, into local slots the loops
+    /// read instead
+    ///
+    /// A string constant then becomes one string shared by every
+    /// iteration rather than a new copy each time. Each hoisted constant
+    /// takes a local slot, and a function too close to `MAX_LOCALS` to
+    /// spare `max_constants` of them is compiled as usual. Zero, the
+    /// default, turns hoisting off; `EngineBuilder::optimize` turns it on
+    /// with `DEFAULT_MAX_HOISTED_CONSTANTS`.
+    pub fn with_constant_hoisting(mut self, max_constants: usize) -> Self {
+        self.max_hoisted_constants = max_constants;
+        self
+    }
+    
+    /// Bind top-level declarations to globals instead of locals, as
+    /// input to a REPL needs
+    ///
+    /// `let`, `const`, `var`, `function` and `class` at the top level
+    /// then outlive the program, so later input sees them. `earlier` maps
+    /// the names earlier input declared this way to whether each is
+    /// `const`, so assigning to one is still an error; declaring one again
+    /// is not, and replaces it (the host removes the old binding first,
+    /// see `Engine::eval_repl_input`). Two declarations of a name in one
+    /// input are still an error.
+    pub fn with_top_level_globals(mut self, earlier: &HashMap<String, bool>) -> Self {
+        self.top_level_globals = true;
+        self.top_level_bindings = earlier.clone();
+        self
+    }
+    
+    /// Compile one level deeper with `compile`, failing if that goes past
+    /// `max_depth`
+    fn nested(&mut self, span: Span, compile: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::compile(
+                ErrorCode::TooMuchNesting,
+                format!("Too much nesting: more than {} levels", self.max_depth),
+                Some(span),
+            ));
+        }
+        self.depth += 1;
+        let result = compile(self);
+        self.depth -= 1;
+        result
+    }
+    
+    /// Compile so that the program returns the completion value of its
+    /// last statement, whatever kind of statement that is
+    ///
+    /// This is what `Engine::eval_block` runs. The rules:
+    ///
+    /// - An expression statement completes with its value.
+    /// - A declaration (`let`, `var`, `const`, `function`, `class`)
+    ///   completes with `undefined`.
+    /// - A block completes with the value of the last statement in it
+    ///   that has one; empty statements, `debugger`, `break` and
+    ///   `continue` leave the value before them in place, so `1; {}`
+    ///   completes with 1.
+    /// - An `if` completes with the value of the branch taken, or
+    ///   `undefined` when there is none.
+    /// - A loop completes with the value its body had on the last
+    ///   iteration, or `undefined` if the body never ran. `break` keeps
+    ///   the value reached so far.
+    ///
+    /// Without this, only a program ending in an expression statement
+    /// completes with a value other than `undefined`.
+    pub fn with_completion_values(mut self) -> Self {
+        let index = LocalIndex(self.next_local_index);
+        self.next_local_index += 1;
+        self.function.locals_count = self.next_local_index;
+        self.completion = Some(index);
+        self
+    }
+    
+    /// Record the accumulator as the completion value so far
+    fn update_completion(&mut self) {
+        if let Some(completion) = self.completion {
+            self.emit(Bytecode::StaLocal(completion));
         }
     }
     
+    /// Record `undefined` as the completion value so far
+    fn clear_completion(&mut self) -> Result<()> {
+        if self.completion.is_some() {
+            let undefined_const = self.add_constant(ConstantValue::Undefined)?;
+            self.emit(Bytecode::LdaConst(undefined_const));
+            self.update_completion();
+        }
+        Ok(())
+    }
+    
+    /// Take the layout of the function's declarations from the first
+    /// pass, binding the names of the function scope
+    fn apply_layout(&mut self, layout: FunctionLayout) {
+        self.function.locals_count = self.function.locals_count.max(layout.locals_count);
+        let mut scopes = layout.scopes;
+        scopes.reverse();
+        if let Some(function_scope) = scopes.pop() {
+            self.bind_scope(function_scope);
+        }
+        self.pending_scopes = scopes;
+    }
     
-    /// Enter a new scope
-    fn enter_scope(&mut self, scope_type: ScopeType) {
+    /// Enter a new scope, binding the names its layout declares
+    fn enter_scope(&mut self, scope_type: ScopeType) -> Result<()> {
+        let layout = self.pending_scopes.pop()
+            .ok_or_else(|| Error::internal("Entered a scope the declaration pass did not lay out"))?;
         let parent_index = self.scopes.len() - 1;
         self.scopes.push(Scope {
             locals: HashMap::new(),
             parent: Some(parent_index),
             scope_type,
+            first_slot: layout.first_slot,
+            start: self.function.current_offset(),
         });
+        self.bind_scope(layout);
+        Ok(())
+    }
+    
+    /// Bind the names of the innermost scope before any of its code is
+    /// compiled, so code can refer to ones declared further down
+    fn bind_scope(&mut self, layout: ScopeLayout) {
+        let current = self.scopes.len() - 1;
+        for binding in layout.bindings {
+            let Some(index) = binding.slot else {
+                self.top_level_bindings.insert(binding.name, binding.is_const);
+                continue;
+            };
+            if binding.is_const {
+                self.function.immutable_locals.insert(index);
+            }
+            self.scopes[current].locals.insert(binding.name.clone(), LocalSlot {
+                name: binding.name,
+                index,
+                is_parameter: false,
+                span: binding.span,
+                is_read: false,
+                is_const: binding.is_const,
+                is_lexical: binding.is_lexical,
+                declared_at: usize::MAX,
+            });
+        }
     }
     
     /// Exit the current scope
     fn exit_scope(&mut self) {
         if self.scopes.len() > 1 {
-            self.scopes.pop();
+            if let Some(scope) = self.scopes.pop() {
+                self.warn_unused_locals(&scope);
+                record_locals(&mut self.function, &scope);
+            }
+        }
+    }
+    
+    /// Record the function scope's variables, live to the end of the
+    /// function, and order the symbol table
+    fn finish_locals(&mut self) {
+        record_locals(&mut self.function, &self.scopes[0]);
+        self.function.debug_info.locals.sort_by_key(|local| (local.live.start, local.slot));
+    }
+    
+    /// Warn about variables in `scope` that are never read
+    ///
+    /// Parameters and names starting with an underscore are exempt.
+    fn warn_unused_locals(&mut self, scope: &Scope) {
+        for slot in scope.locals.values() {
+            if !slot.is_read && !slot.is_parameter && !slot.name.starts_with('_') {
+                self.diagnostics.push(Diagnostic::warning(
+                    format!("variable '{}' is declared but never used", slot.name)
+                ).with_span(slot.span));
+            }
+        }
+    }
+    
+    /// Record a read of the variable `name` bound by the `scope`th scope
+    ///
+    /// Slot indices are not unique across scopes, so the read is keyed on
+    /// the scope and the name rather than the index alone.
+    fn mark_read(&mut self, scope: usize, name: &str) {
+        if let Some(slot) = self.scopes[scope].locals.get_mut(name) {
+            slot.is_read = true;
         }
     }
     
     /// Resolve a variable name to a local slot
+    ///
+    /// A lexical declaration not yet compiled is passed over, so a name
+    /// read before it resolves to an outer variable or a global rather
+    /// than to a slot that may still hold another variable's value.
     fn resolve_variable(&self, name: &str) -> Option<&LocalSlot> {
+        self.resolve_scope(name).map(|scope| &self.scopes[scope].locals[name])
+    }
+    
+    /// Position in `scopes` of the scope `resolve_variable` finds `name` in
+    fn resolve_scope(&self, name: &str) -> Option<usize> {
         // Search from current scope up the scope chain
-        for scope in self.scopes.iter().rev() {
-            if let Some(slot) = scope.locals.get(name) {
-                return Some(slot);
-            }
-        }
-        None
+        self.scopes.iter().rposition(|scope| match scope.locals.get(name) {
+            Some(slot) => !(slot.is_lexical && slot.declared_at == usize::MAX),
+            None => false,
+        })
     }
     
-    /// Declare a new local variable in the current scope
-    fn declare_local(&mut self, name: String, span: Span) -> Result<LocalIndex> {
-        let current_scope = self.scopes.last_mut()
-            .ok_or_else(|| Error::runtime("No current scope for variable declaration", Some(span)))?;
-        
-        // Check if variable already exists in current scope
-        if current_scope.locals.contains_key(&name) {
-            return Err(Error::runtime(
-                format!("Variable '{}' already declared in this scope", name),
-                Some(span)
-            ));
+    /// Resolve a variable name to the current function, an enclosing one, or the globals
+    fn resolve_location(&self, name: &str) -> VariableLocation {
+        if let Some(local) = self.resolve_variable(name) {
+            return VariableLocation::Local(local.index);
         }
         
-        let index = self.next_local_index;
-        current_scope.locals.insert(name.clone(), LocalSlot {
-            name: name.clone(),
-            index,
-            is_parameter: false,
-            span,
-        });
-        
-        self.next_local_index += 1;
-        self.function.locals_count = self.next_local_index;
+        for (depth, locals) in self.enclosing.iter().rev().enumerate() {
+            if let Some(slot) = locals.get(name) {
+                return VariableLocation::Context((depth + 1) as ContextDepth, slot.index);
+            }
+        }
         
-        Ok(index)
+        VariableLocation::Global
+    }
+    
+    /// Whether `name` resolves to a variable declared with `const`
+    fn resolves_to_const(&self, name: &str) -> bool {
+        if let Some(local) = self.resolve_variable(name) {
+            return local.is_const;
+        }
+        match self.enclosing.iter().rev().find_map(|locals| locals.get(name)) {
+            Some(slot) => slot.is_const,
+            None => self.top_level_bindings.get(name).copied().unwrap_or(false),
+        }
+    }
+    
+    /// Snapshot of the variables currently in scope, for compiling a nested function
+    fn visible_locals(&self) -> HashMap<String, LocalSlot> {
+        let mut locals = HashMap::new();
+        // Outer scopes first so that inner declarations shadow them
+        for scope in &self.scopes {
+            for (name, slot) in &scope.locals {
+                locals.insert(name.clone(), slot.clone());
+            }
+        }
+        locals
+    }
+    
+    /// The instruction that stores the initial value of the declared
+    /// name `name`, which its scope bound on entry
+    ///
+    /// A name no scope bound is a global, declared at the top level of
+    /// REPL input, and is stored with `InitGlobal`.
+    fn declaration_store(&mut self, name: &str) -> Result<Bytecode> {
+        let offset = self.function.current_offset();
+        let slot = self.scopes.iter_mut().rev().find_map(|scope| scope.locals.get_mut(name));
+        match slot {
+            Some(slot) => {
+                slot.declared_at = slot.declared_at.min(offset);
+                if slot.is_const {
+                    Ok(Bytecode::InitLocal(slot.index))
+                } else {
+                    Ok(Bytecode::StaLocal(slot.index))
+                }
+            }
+            None => {
+                let name_const = self.add_constant_property_name(name)?;
+                Ok(Bytecode::InitGlobal(name_const))
+            }
+        }
     }
     
     /// Add an instruction to the function
     fn emit(&mut self, instruction: Bytecode) {
-        self.function.add_instruction(instruction);
+        self.function.add_instruction_on_line(instruction, self.line);
     }
     
     /// Add an instruction with span information
@@ -187,86 +581,303 @@ impl Compiler {
         self.function.add_instruction_with_span(instruction, span);
     }
     
+    /// Emit a forward jump whose offset is patched once the target is known
+    fn emit_jump(&mut self, jump: fn(JumpOffset) -> Bytecode, span: Span) -> usize {
+        let site = self.function.current_offset();
+        self.emit_with_span(jump(JumpOffset(0)), span);
+        site
+    }
+    
+    /// Emit a jump back to the already emitted instruction at `target`
+    fn emit_loop_jump(&mut self, target: usize, span: Span) -> Result<()> {
+        let offset = JumpOffset::backward(self.function.current_offset(), target)
+            .map_err(|_| Error::compile(ErrorCode::CompilerLimit, "Loop body too large", Some(span)))?;
+        self.emit_with_span(Bytecode::Jump(offset), span);
+        Ok(())
+    }
+    
+    /// Point the jump emitted at `site` to the instruction at `target`
+    ///
+    /// Only `continue` jumps back this way; the target of every other
+    /// patched jump comes after it.
+    fn patch_jump(&mut self, site: usize, target: usize) -> Result<()> {
+        let offset = if target > site {
+            JumpOffset::forward(site, target)
+        } else {
+            JumpOffset::backward(site, target)
+        };
+        let offset = offset.map_err(|_| Error::compile(ErrorCode::CompilerLimit, "Jump target too far away", None))?;
+        let patched = match self.function.get_instruction(site) {
+            Some(Bytecode::JumpIfFalse(_)) => Bytecode::JumpIfFalse(offset),
+            Some(Bytecode::JumpIfTrue(_)) => Bytecode::JumpIfTrue(offset),
+            Some(Bytecode::IteratorNext(_)) => Bytecode::IteratorNext(offset),
+            _ => Bytecode::Jump(offset),
+        };
+        self.function.patch_instruction(site, patched);
+        Ok(())
+    }
+    
+    /// Add a constant to the function's constant pool, or find the one
+    /// already there, failing once the pool has no index left
+    fn add_constant(&mut self, value: ConstantValue) -> Result<ConstIndex> {
+        self.function.constants.try_add_constant(value)
+            .map_err(|_| Error::compile(ErrorCode::CompilerLimit, "Too many constants in one function", None))
+    }
     
-    /// Add a constant to the function's constant pool
-    fn add_constant_number(&mut self, value: f64) -> ConstIndex {
-        self.function.constants.add_number(value)
+    /// Add a number constant to the function's constant pool
+    fn add_constant_number(&mut self, value: f64) -> Result<ConstIndex> {
+        self.add_constant(ConstantValue::Number(HashableF64(value)))
     }
     
     /// Add a string constant to the function's constant pool
-    fn add_constant_string(&mut self, value: String) -> ConstIndex {
-        self.function.constants.add_string(value)
+    fn add_constant_string(&mut self, value: String) -> Result<ConstIndex> {
+        self.add_constant(ConstantValue::String(value))
     }
     
-    /// Add a property name constant to the function's constant pool
-    fn add_constant_property_name(&mut self, name: String) -> ConstIndex {
-        self.function.constants.add_property_name(name)
+    /// Add a property name constant to the function's constant pool,
+    /// interning the name in the program's string table
+    fn add_constant_property_name(&mut self, name: &str) -> Result<ConstIndex> {
+        let id = self.names.borrow_mut().intern(name);
+        self.add_constant(ConstantValue::PropertyName(id))
     }
     
+    /// Hand the program's string table, which nested compilers are done
+    /// with, to the finished function and every function in it
+    fn share_names(&mut self) {
+        let names = Arc::new(self.names.take());
+        self.function.share_names(&names);
+    }
     
     /// Compile a program to bytecode
-    pub fn compile(mut self, program: &Program) -> Result<BytecodeFunction> {
-        // Compile all statements in the program
-        let num_statements = program.statements.len();
-        for (i, stmt) in program.statements.iter().enumerate() {
-            let is_last = i == num_statements - 1;
-            
-            // For the last statement, if it's an expression, keep its value as return value
-            if is_last && matches!(stmt, Stmt::Expression(_)) {
-                // Compile expression without popping the result
-                if let Stmt::Expression(expr) = stmt {
-                    self.compile_expression(expr)?;
-                    // Don't pop - leave value in accumulator for implicit return
-                }
-            } else {
-                self.compile_statement(stmt)?;
-            }
-        }
+    ///
+    /// The program's completion value is left in the accumulator for the
+    /// final `Return`: a trailing expression statement completes with its
+    /// value, anything else (declarations, empty statements, control flow,
+    /// blocks, or an empty program) completes with `undefined`.
+    pub fn compile(self, program: &Program) -> Result<BytecodeFunction> {
+        self.compile_with_diagnostics(program).map(|(function, _)| function)
+    }
+    
+    /// Compile a program, also returning the warnings found along the way
+    ///
+    /// Warnings cover variables that are never read, statements that can
+    /// never run, and `if`/loop conditions whose truthiness is known at
+    /// compile time. They come back in source order.
+    pub fn compile_with_diagnostics(mut self, program: &Program) -> Result<(BytecodeFunction, Vec<Diagnostic>)> {
+        self.function.is_strict = Stmt::has_use_strict_directive(&program.statements);
+        self.non_escaping = escape::non_escaping_locals(&[], &program.statements, self.max_depth);
         
-        // Ensure program ends with a return
-        if let Some(last_instruction) = self.function.bytecode.last() {
-            if !matches!(last_instruction, Bytecode::Return | Bytecode::ReturnUndefined) {
-                // If the last statement was an expression, return its value
-                // Otherwise return undefined
+        // First pass: lay out every declaration of the program
+        let params: Vec<(String, Span)> = self.scopes[0].locals.values().map(|local| (local.name.clone(), local.span)).collect();
+        let layout = layout::function_layout(
+            &params,
+            &program.statements,
+            self.next_local_index,
+            self.top_level_globals,
+            self.depth,
+            self.max_depth,
+        )?;
+        self.apply_layout(layout);
+        
+        // Second pass: compile all statements in the program; each
+        // expression statement leaves its value in the accumulator
+        self.compile_statements(&program.statements)?;
+        
+        // Ensure program ends with a return of its completion value. Only a
+        // trailing expression statement has one; whatever else is left in the
+        // accumulator (e.g. a declaration's initializer) must not leak out.
+        match (self.completion, program.statements.last()) {
+            (Some(completion), _) => {
+                self.emit(Bytecode::LdaLocal(completion));
                 self.emit(Bytecode::Return);
             }
-        } else {
-            self.emit(Bytecode::ReturnUndefined);
+            (None, Some(Stmt::Expression(_))) => self.emit(Bytecode::Return),
+            (None, Some(Stmt::Return { .. })) => {} // Explicit return already emitted
+            (None, _) => self.emit(Bytecode::ReturnUndefined),
         }
         
         // Calculate final stack size
         self.function.calculate_stack_size();
         
+        let scope = self.scopes[0].clone();
+        self.warn_unused_locals(&scope);
+        self.finish_locals();
+        hoist::hoist_loop_constants(&mut self.function, self.max_hoisted_constants);
+        self.share_names();
+        let mut diagnostics = self.diagnostics;
+        diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| span.start));
+        
+        Ok((self.function, diagnostics))
+    }
+    
+    /// Compile a lone expression that reads and writes an existing frame's
+    /// locals, for evaluating it with `VM::eval_in_frame`
+    ///
+    /// `scope_layout` maps the names the expression may use to the
+    /// frame's local slots; other names resolve to globals. The result is
+    /// a function returning the expression's value.
+    pub fn compile_expression_in_scope(source: &str, scope_layout: &[(String, LocalIndex)]) -> Result<BytecodeFunction> {
+        let expr = Parser::from_source(Lexer::new(source)).parse_expression_only()?;
+        let mut compiler = Compiler::new_function("<eval>".to_string(), &[], source);
+        // The frame's environment is the parent of the one the function runs in
+        compiler.enclosing.push(scope_layout.iter().map(|(name, index)| {
+            let slot = LocalSlot {
+                name: name.clone(),
+                index: *index,
+                is_parameter: false,
+                span: Span::new(0, 0, 1, 1),
+                is_read: true,
+                is_const: false,
+                is_lexical: false,
+                declared_at: 0,
+            };
+            (name.clone(), slot)
+        }).collect());
+        compiler.compile_lone_expression(&expr)
+    }
+    
+    /// Compile a lone expression, from `Parser::parse_expression_only`,
+    /// into a main function returning its value
+    ///
+    /// The result runs like a compiled program; names the expression
+    /// doesn't declare itself resolve to globals.
+    pub fn compile_expression_main(self, expr: &Expr) -> Result<BytecodeFunction> {
+        self.compile_lone_expression(expr)
+    }
+    
+    fn compile_lone_expression(mut self, expr: &Expr) -> Result<BytecodeFunction> {
+        let layout = layout::expression_layout(expr, self.next_local_index, self.max_depth)?;
+        self.apply_layout(layout);
+        self.line = Some(expr.span().line);
+        self.compile_expression(expr)?;
+        self.emit(Bytecode::Return);
+        self.function.calculate_stack_size();
+        self.share_names();
         Ok(self.function)
     }
     
-    /// Compile a statement to bytecode
+    /// Compile a statement list, warning about code after an abrupt exit
+    ///
+    /// Function declarations are hoisted: their closures are created
+    /// before the rest of the list runs, so a function can be called
+    /// above its declaration.
+    fn compile_statements(&mut self, statements: &[Stmt]) -> Result<()> {
+        for stmt in statements {
+            if matches!(stmt, Stmt::FunctionDecl { .. }) {
+                self.compile_statement(stmt)?;
+            }
+        }
+        
+        let mut exited_by = None;
+        let mut warned = false;
+        
+        for stmt in statements {
+            if let Some(keyword) = exited_by {
+                // Function declarations and empty statements aren't code that runs
+                if !warned && !matches!(stmt, Stmt::FunctionDecl { .. } | Stmt::Empty { .. }) {
+                    self.diagnostics.push(Diagnostic::warning(
+                        format!("unreachable code after {}", keyword)
+                    ).with_span(stmt.span()));
+                    warned = true;
+                }
+            } else {
+                exited_by = abrupt_exit(stmt);
+            }
+            match stmt {
+                // Compiled above; as a declaration it completes with
+                // `undefined`
+                Stmt::FunctionDecl { .. } => self.clear_completion()?,
+                _ => self.compile_statement(stmt)?,
+            }
+        }
+        Ok(())
+    }
+    
+    /// Warn when an `if` or loop condition is a constant
+    ///
+    /// A literal `true` loop condition is the idiomatic infinite loop and
+    /// is left alone. `span` is where the parser found the condition: a
+    /// bare literal has no span of its own.
+    fn check_constant_condition(&mut self, test: &Expr, is_loop: bool, span: Span) {
+        if is_loop && matches!(test, Expr::Literal(Literal::Boolean(true))) {
+            return;
+        }
+        if let Some(truthy) = constant_truthiness(test) {
+            let message = if truthy { "condition is always truthy" } else { "condition is always falsy" };
+            self.diagnostics.push(Diagnostic::warning(message).with_span(span));
+        }
+    }
+    
+    /// Compile a statement to bytecode, attributing its instructions to
+    /// its line and marking where they start
     fn compile_statement(&mut self, stmt: &Stmt) -> Result<()> {
+        let outer_line = self.line.replace(stmt.span().line);
+        let start = self.function.current_offset();
+        let result = self.nested(stmt.span(), |compiler| compiler.compile_statement_on_line(stmt));
+        self.line = outer_line;
+        // Statements compiling to nothing, such as `;`, have nowhere to stop
+        if self.function.current_offset() > start {
+            self.function.debug_info.add_statement_start(start);
+        }
+        result
+    }
+    
+    /// Compile a statement once its line is recorded
+    fn compile_statement_on_line(&mut self, stmt: &Stmt) -> Result<()> {
         match stmt {
             Stmt::Expression(expr) => {
+                // The value is simply left in the accumulator; expressions
+                // keep the operand stack balanced on their own
                 self.compile_expression(expr)?;
-                // Pop the result since it's not used
-                self.emit(Bytecode::Pop);
+                self.update_completion();
                 Ok(())
             }
             
-            Stmt::VarDecl { name, init, span } => {
-                // Declare the variable in the current scope
-                let local_index = self.declare_local(name.clone(), *span)?;
+            Stmt::VarDecl { name, init, span, .. } => {
+                let store = self.declaration_store(name)?;
                 
                 // If there's an initializer, compile it and store the result
                 if let Some(init_expr) = init {
+                    let allocation = self.function.bytecode.len();
                     self.compile_expression(init_expr)?;
+                    let is_local = !matches!(store, Bytecode::InitGlobal(_));
+                    if is_local && self.non_escaping.contains(name) {
+                        self.mark_arena_site(allocation);
+                    }
                 } else {
                     // Default initialize with undefined
-                    let undefined_const = self.function.constants.add_undefined();
+                    let undefined_const = self.add_constant(ConstantValue::Undefined)?;
                     self.emit(Bytecode::LdaConst(undefined_const));
                 }
                 
                 // Store in the local variable
-                self.emit_with_span(Bytecode::StaLocal(local_index), *span);
+                self.emit_with_span(store, *span);
+                self.clear_completion()?;
+                Ok(())
+            }
+            
+            // Reached ahead of the rest of its statement list (see
+            // `compile_statements`), which also sets the completion value
+            Stmt::FunctionDecl { name, params, body, is_generator, is_async, span } => {
+                let store = self.declaration_store(name)?;
+                self.compile_function(name, params, body, FunctionKind::plain(*is_generator, *is_async), *span)?;
+                self.emit_with_span(store, *span);
+                Ok(())
+            }
+            
+            Stmt::ClassDecl { name, body, span } => {
+                let store = self.declaration_store(name)?;
+                self.compile_class(name, body, *span)?;
+                self.emit_with_span(store, *span);
+                self.clear_completion()?;
+                Ok(())
+            }
+            
+            Stmt::Debugger { span } => {
+                self.emit_with_span(Bytecode::Debugger, *span);
                 Ok(())
             }
+            Stmt::Empty { .. } => Ok(()),
             
             Stmt::Return { value, span } => {
                 if let Some(expr) = value {
@@ -279,36 +890,200 @@ impl Compiler {
             }
             
             Stmt::Block { statements, .. } => {
-                self.enter_scope(ScopeType::Block);
-                for stmt in statements {
-                    self.compile_statement(stmt)?;
-                }
+                self.enter_scope(ScopeType::Block)?;
+                self.compile_statements(statements)?;
                 self.exit_scope();
                 Ok(())
             }
             
-            // TODO: Implement other statements in next tasks
-            _ => {
-                Ok(()) // Placeholder for now
+            Stmt::If { test, test_span, then_stmt, else_stmt, span } => {
+                self.check_constant_condition(test, false, *test_span);
+                self.clear_completion()?;
+                self.compile_expression(test)?;
+                let skip_then = self.emit_jump(Bytecode::JumpIfFalse, *span);
+                self.compile_statement(then_stmt)?;
+                
+                match else_stmt {
+                    Some(else_stmt) => {
+                        let skip_else = self.emit_jump(Bytecode::Jump, *span);
+                        self.patch_jump(skip_then, self.function.current_offset())?;
+                        self.compile_statement(else_stmt)?;
+                        self.patch_jump(skip_else, self.function.current_offset())?;
+                    }
+                    None => self.patch_jump(skip_then, self.function.current_offset())?,
+                }
+                Ok(())
+            }
+            
+            Stmt::While { test, test_span, body, span } => {
+                self.check_constant_condition(test, true, *test_span);
+                self.clear_completion()?;
+                let loop_start = self.function.current_offset();
+                self.compile_expression(test)?;
+                let exit = self.emit_jump(Bytecode::JumpIfFalse, *span);
+                
+                self.compile_loop_body(body, "while")?;
+                self.emit_loop_jump(loop_start, *span)?;
+                
+                let loop_end = self.function.current_offset();
+                self.patch_jump(exit, loop_end)?;
+                self.finish_loop(loop_start, loop_end)
+            }
+            
+            Stmt::For { init, test, test_span, update, body, span } => {
+                let test = test.as_ref().zip(*test_span);
+                self.compile_for(init.as_deref(), test, update.as_ref(), body, *span)
+            }
+            
+            Stmt::Break { span } => {
+                let site = self.emit_jump(Bytecode::Jump, *span);
+                match self.loops.last_mut() {
+                    Some(context) => context.break_patches.push(site),
+                    None => return Err(Error::compile(ErrorCode::IllegalBreak, "Illegal break statement", Some(*span))),
+                }
+                Ok(())
+            }
+            
+            Stmt::Continue { span } => {
+                let site = self.emit_jump(Bytecode::Jump, *span);
+                match self.loops.last_mut() {
+                    Some(context) => context.continue_patches.push(site),
+                    None => return Err(Error::compile(ErrorCode::IllegalContinue, "Illegal continue statement", Some(*span))),
+                }
+                Ok(())
+            }
+            
+            Stmt::ForOf { left, right, body, span } => self.compile_for_of(left, right, body, *span),
+        }
+    }
+    
+    /// Compile a `for (init; test; update)` loop
+    fn compile_for(&mut self, init: Option<&Stmt>, test: Option<(&Expr, Span)>, update: Option<&Expr>, body: &Stmt, span: Span) -> Result<()> {
+        // A `let` in the head is scoped to the loop
+        self.enter_scope(ScopeType::Loop)?;
+        if let Some(init) = init {
+            self.compile_statement(init)?;
+        }
+        self.clear_completion()?;
+        
+        let loop_start = self.function.current_offset();
+        let exit = match test {
+            Some((test, test_span)) => {
+                self.check_constant_condition(test, true, test_span);
+                self.compile_expression(test)?;
+                Some(self.emit_jump(Bytecode::JumpIfFalse, span))
             }
+            None => None,
+        };
+        
+        self.compile_loop_body(body, "for")?;
+        let continue_target = self.function.current_offset();
+        match update {
+            // The update's value is discarded, so `i++` needs no copy
+            Some(Expr::PostfixUnary { op, operand, span }) => {
+                self.compile_update(op, operand, false, *span)?;
+            }
+            Some(update) => self.compile_expression(update)?,
+            None => {}
+        }
+        self.emit_loop_jump(loop_start, span)?;
+        
+        let loop_end = self.function.current_offset();
+        if let Some(exit) = exit {
+            self.patch_jump(exit, loop_end)?;
+        }
+        self.finish_loop(continue_target, loop_end)?;
+        self.exit_scope();
+        Ok(())
+    }
+    
+    /// Compile a `for (left of right)` loop
+    fn compile_for_of(&mut self, left: &Stmt, right: &Expr, body: &Stmt, span: Span) -> Result<()> {
+        // The iterator stays on the stack for the whole loop
+        self.enter_scope(ScopeType::Loop)?;
+        self.clear_completion()?;
+        self.compile_expression(right)?;
+        self.emit_with_span(Bytecode::GetIterator, span);
+        self.emit(Bytecode::Push);
+        
+        let loop_start = self.function.current_offset();
+        let exit = self.emit_jump(Bytecode::IteratorNext, span);
+        match left {
+            Stmt::VarDecl { name, span, .. } => {
+                let store = self.declaration_store(name)?;
+                self.emit_with_span(store, *span);
+            }
+            Stmt::Expression(Expr::Identifier { name, span }) => {
+                self.compile_identifier_store(name, *span)?;
+            }
+            _ => return Err(Error::compile(ErrorCode::InvalidAssignmentTarget, "Invalid left-hand side in for-of loop", Some(span))),
+        }
+        
+        self.compile_loop_body(body, "for_of")?;
+        self.emit_loop_jump(loop_start, span)?;
+        
+        // Both the exhausted iterator and `break` land here, so a
+        // generator left suspended by `break` gets closed; `return`
+        // skips this and leaves it suspended
+        let loop_end = self.function.current_offset();
+        self.patch_jump(exit, loop_end)?;
+        self.emit_with_span(Bytecode::IteratorClose, span);
+        self.finish_loop(loop_start, loop_end)?;
+        self.exit_scope();
+        Ok(())
+    }
+    
+    /// Compile a loop body with its own break/continue context
+    fn compile_loop_body(&mut self, body: &Stmt, label: &str) -> Result<()> {
+        self.loops.push(LoopContext {
+            continue_label: format!("{}_continue", label),
+            break_patches: Vec::new(),
+            continue_patches: Vec::new(),
+        });
+        self.compile_statement(body)
+    }
+    
+    /// Resolve the innermost loop's break and continue jumps
+    fn finish_loop(&mut self, continue_target: usize, loop_end: usize) -> Result<()> {
+        let context = self.loops.pop()
+            .ok_or_else(|| Error::internal("No loop to finish"))?;
+        for site in context.continue_patches {
+            self.patch_jump(site, continue_target)?;
+        }
+        for site in context.break_patches {
+            self.patch_jump(site, loop_end)?;
         }
+        Ok(())
     }
     
     /// Compile an expression to bytecode (result left in accumulator)
     fn compile_expression(&mut self, expr: &Expr) -> Result<()> {
+        self.nested(expr.span(), |compiler| compiler.compile_expression_at_depth(expr))
+    }
+    
+    /// Compile an expression once its nesting is counted
+    fn compile_expression_at_depth(&mut self, expr: &Expr) -> Result<()> {
         match expr {
             Expr::Literal(literal) => {
                 self.compile_literal(literal)
             }
             
             Expr::Identifier { name, span } => {
-                // Try to resolve as local variable first
-                if let Some(local) = self.resolve_variable(name) {
-                    self.emit_with_span(Bytecode::LdaLocal(local.index), *span);
-                } else {
-                    // Global variable access
-                    let name_const = self.add_constant_property_name(name.clone());
-                    self.emit_with_span(Bytecode::LdaGlobal(name_const), *span);
+                match self.resolve_location(name) {
+                    VariableLocation::Local(index) => {
+                        if let Some(scope) = self.resolve_scope(name) {
+                            self.mark_read(scope, name);
+                        }
+                        self.emit_with_span(Bytecode::LdaLocal(index), *span);
+                    }
+                    VariableLocation::Context(depth, index) => {
+                        self.captured_reads.insert((depth, name.clone()));
+                        self.emit_with_span(Bytecode::LdaContextSlot(depth, index), *span);
+                    }
+                    VariableLocation::Global => {
+                        let name_const = self.add_constant_property_name(name)?;
+                        self.emit_with_span(Bytecode::LdaGlobal(name_const), *span);
+                    }
                 }
                 Ok(())
             }
@@ -321,23 +1096,94 @@ impl Compiler {
                 self.compile_unary_operation(op, operand, *span)
             }
             
-            Expr::Assignment { left, right, span } => {
-                self.compile_assignment(left, right, *span)
+            Expr::PostfixUnary { op, operand, span } => {
+                self.compile_update(op, operand, true, *span)
             }
             
-            Expr::Call { callee, args, span } => {
-                self.compile_function_call(callee, args, *span)
+            Expr::Assignment { op, left, right, span } => {
+                self.compile_assignment(op.as_ref(), left, right, *span)
             }
             
-            Expr::Member { object, property, computed, span } => {
-                self.compile_member_access(object, property, *computed, *span)
+            Expr::Call { callee, args, span } => match self.folded_call(callee, args) {
+                Some(value) => self.compile_folded(value),
+                None => self.compile_function_call(callee, args, *span),
+            },
+            
+            Expr::Member { object, property, computed, span } => match self.folded_string_length(object, property, *computed) {
+                Some(value) => self.compile_folded(value),
+                None => self.compile_member_access(object, property, *computed, *span),
+            },
+            
+            Expr::Array { elements, span } => {
+                self.compile_array_literal(elements, *span)
             }
             
-            // TODO: Implement other expressions in next tasks
-            _ => {
-                // Placeholder: load undefined for unimplemented expressions
-                let undefined_const = self.function.constants.add_undefined();
-                self.emit(Bytecode::LdaConst(undefined_const));
+            Expr::Object { properties, span } => {
+                self.compile_object_literal(properties, *span)
+            }
+            
+            Expr::Function { name, params, body, is_generator, is_async, span } => {
+                let name = name.as_deref().unwrap_or("<anonymous>");
+                self.compile_function(name, params, body, FunctionKind::plain(*is_generator, *is_async), *span)
+            }
+            
+            Expr::Arrow { params, body, is_async, span } => {
+                let kind = if *is_async { FunctionKind::AsyncArrow } else { FunctionKind::Arrow };
+                self.compile_function("<anonymous>", params, body, kind, *span)
+            }
+            
+            Expr::Class { name, body, span } => {
+                let Some(name) = name else {
+                    return self.compile_class("<anonymous>", body, *span);
+                };
+                // The name is bound inside the class only
+                self.enter_scope(ScopeType::Block)?;
+                let store = self.declaration_store(name)?;
+                if let Bytecode::StaLocal(_) = store {
+                    self.mark_read(self.scopes.len() - 1, name);
+                }
+                self.compile_class(name, body, *span)?;
+                self.emit_with_span(store, *span);
+                self.exit_scope();
+                Ok(())
+            }
+            
+            Expr::This { span } => {
+                self.emit_with_span(Bytecode::LdaThis, *span);
+                Ok(())
+            }
+            
+            Expr::New { callee, args, span } => {
+                self.compile_expression(callee)?;
+                self.emit(Bytecode::Push);
+                let argc = self.compile_arguments(args, *span)?;
+                // Name the callee in case it turns out not to be a constructor
+                if let Some(name) = callee_name(callee) {
+                    let offset = self.function.current_offset();
+                    self.function.debug_info.callee_names.insert(offset, name);
+                }
+                self.emit_with_span(Bytecode::Construct(argc), *span);
+                Ok(())
+            }
+            
+            Expr::Yield { argument, span } => {
+                match argument {
+                    Some(argument) => self.compile_expression(argument)?,
+                    None => {
+                        let undefined_const = self.add_constant(ConstantValue::Undefined)?;
+                        self.emit(Bytecode::LdaConst(undefined_const));
+                    }
+                }
+                self.emit_with_span(Bytecode::Yield, *span);
+                Ok(())
+            }
+            
+            // The VM drives an async body like a generator: each `Yield`
+            // hands the awaited value to it, and it resumes the frame with
+            // the settled result
+            Expr::Await { argument, span } => {
+                self.compile_expression(argument)?;
+                self.emit_with_span(Bytecode::Yield, *span);
                 Ok(())
             }
         }
@@ -346,43 +1192,181 @@ impl Compiler {
     /// Compile a literal value
     fn compile_literal(&mut self, literal: &Literal) -> Result<()> {
         match literal {
-            Literal::Number(value) => {
-                let const_index = self.add_constant_number(*value);
-                self.emit(Bytecode::LdaConst(const_index));
-            }
+            Literal::Number(value) => match small_integer(*value) {
+                Some(value) => self.emit(Bytecode::LdaSmi(value)),
+                None => {
+                    let const_index = self.add_constant_number(*value)?;
+                    self.emit(Bytecode::LdaConst(const_index));
+                }
+            },
             Literal::String(value) => {
-                let const_index = self.add_constant_string(value.clone());
+                let const_index = self.add_constant_string(value.clone())?;
                 self.emit(Bytecode::LdaConst(const_index));
             }
             Literal::Boolean(value) => {
-                let const_index = self.function.constants.add_boolean(*value);
+                let const_index = self.add_constant(ConstantValue::Boolean(*value))?;
                 self.emit(Bytecode::LdaConst(const_index));
             }
             Literal::Null => {
-                let const_index = self.function.constants.add_null();
+                let const_index = self.add_constant(ConstantValue::Null)?;
                 self.emit(Bytecode::LdaConst(const_index));
             }
             Literal::Undefined => {
-                let const_index = self.function.constants.add_undefined();
+                let const_index = self.add_constant(ConstantValue::Undefined)?;
+                self.emit(Bytecode::LdaConst(const_index));
+            }
+            Literal::RegExp { pattern, flags } => {
+                let const_index = self.add_constant(ConstantValue::Regex { pattern: pattern.clone(), flags: flags.clone() })?;
                 self.emit(Bytecode::LdaConst(const_index));
             }
         }
         Ok(())
     }
     
-    /// Compile binary operations (e.g., +, -, *, /, ==, <, etc.)
-    fn compile_binary_operation(&mut self, op: &BinaryOp, left: &Expr, right: &Expr, _span: Span) -> Result<()> {
-        // Compile left operand (result in accumulator)
-        self.compile_expression(left)?;
+    /// The value of `expr` when it is a constant, including calls folded by
+    /// `with_builtin_folding`
+    fn folded_constant(&self, expr: &Expr) -> Option<Value> {
+        match expr {
+            Expr::Call { callee, args, .. } => self.folded_call(callee, args),
+            Expr::Member { object, property, computed, .. } => self.folded_string_length(object, property, *computed),
+            _ => constant_primitive(expr),
+        }
+    }
+    
+    /// The result of calling a pure built-in on constants, when folding
+    /// and the call qualifies (see `with_builtin_folding`)
+    fn folded_call(&self, callee: &Expr, args: &[Expr]) -> Option<Value> {
+        if !self.fold_builtins {
+            return None;
+        }
+        // A global a REPL declared may not be the built-in any more
+        let is_global = |name: &str| {
+            matches!(self.resolve_location(name), VariableLocation::Global) && !self.top_level_bindings.contains_key(name)
+        };
+        let (path, this) = match callee {
+            Expr::Identifier { name, .. } if is_global(name) => (name.clone(), Value::Undefined),
+            Expr::Member { object, property, computed: false, .. } => {
+                let Expr::Identifier { name: method, .. } = property.as_ref() else {
+                    return None;
+                };
+                match object.as_ref() {
+                    Expr::Identifier { name, .. } if is_global(name) => (format!("{}.{}", name, method), Value::Undefined),
+                    object => match self.folded_constant(object)? {
+                        this @ Value::String(_) => (format!("String.prototype.{}", method), this),
+                        _ => return None,
+                    },
+                }
+            }
+            _ => return None,
+        };
+        let function = pure_builtin(&path)?;
+        let args = args.iter().map(|arg| self.folded_constant(arg)).collect::<Option<Vec<_>>>()?;
+        function(&this, &args).ok().filter(|value| !matches!(value, Value::Object(_) | Value::Function(_)))
+    }
+    
+    /// The `length` of a string constant, when folding
+    fn folded_string_length(&self, object: &Expr, property: &Expr, computed: bool) -> Option<Value> {
+        let is_length = matches!(property, Expr::Identifier { name, .. } if name == "length");
+        if !self.fold_builtins || computed || !is_length {
+            return None;
+        }
+        match self.folded_constant(object)? {
+            Value::String(s) => Some(Value::Number(s.encode_utf16().count() as f64)),
+            _ => None,
+        }
+    }
+    
+    /// Load a value folded at compile time
+    fn compile_folded(&mut self, value: Value) -> Result<()> {
+        let literal = match value {
+            Value::Number(n) => Literal::Number(n),
+            Value::String(s) => Literal::String(s.to_string()),
+            Value::Boolean(b) => Literal::Boolean(b),
+            Value::Null => Literal::Null,
+            Value::Undefined => Literal::Undefined,
+            other => return Err(Error::internal(format!("Cannot load folded value {}", other))),
+        };
+        self.compile_literal(&literal)
+    }
+    
+    /// Compile a chain of `+`, folding constants where JavaScript allows
+    ///
+    /// The chain is flattened, so long ones don't recurse. Leading constants
+    /// are added at compile time when the result is a string: `1 + "2" + 3`
+    /// is "123", while `1 + 2 + "3"` adds the numbers first and is "33".
+    /// Once the running value is known to be a string, each run of
+    /// constants after it is joined into one operand, since concatenating
+    /// them one at a time gives the same string.
+    fn compile_addition(&mut self, left: &Expr, right: &Expr) -> Result<()> {
+        let operands = addition_operands(left, right);
+        let constants: Vec<Option<Value>> = operands.iter().map(|operand| constant_primitive(operand)).collect();
+        let leading = constants.iter().take_while(|constant| constant.is_some()).count();
         
-        // Push left operand to stack
-        self.emit(Bytecode::Push);
+        let prefix = constants[..leading].iter().flatten().cloned().reduce(add_primitives);
+        let (mut next, mut known_string) = match prefix {
+            Some(Value::String(folded)) => {
+                let const_index = self.add_constant_string(folded.to_string())?;
+                self.emit(Bytecode::LdaConst(const_index));
+                (leading, true)
+            }
+            _ => {
+                self.compile_expression(operands[0])?;
+                (1, false)
+            }
+        };
         
-        // Compile right operand (result in accumulator)  
-        self.compile_expression(right)?;
+        while next < operands.len() {
+            self.emit(Bytecode::Push);
+            if known_string && constants[next].is_some() {
+                let run = constants[next..].iter().take_while(|constant| constant.is_some()).count();
+                let joined: String = constants[next..next + run].iter().flatten().map(Value::to_string).collect();
+                let const_index = self.add_constant_string(joined)?;
+                self.emit(Bytecode::LdaConst(const_index));
+                next += run;
+            } else {
+                self.compile_expression(operands[next])?;
+                known_string |= matches!(constants[next], Some(Value::String(_)));
+                next += 1;
+            }
+            self.emit(Bytecode::Add);
+        }
+        Ok(())
+    }
+    
+    /// Compile binary operations (e.g., +, -, *, /, ==, <, etc.)
+    fn compile_binary_operation(&mut self, op: &BinaryOp, left: &Expr, right: &Expr, span: Span) -> Result<()> {
+        if *op == BinaryOp::Add {
+            return self.compile_addition(left, right);
+        }
         
-        // Emit the appropriate bytecode instruction
-        // The operation will be: stack_top op accumulator -> accumulator
+        // `a - b * c - d` nests to the left; walk down that side instead of
+        // recursing, so a long chain compiles like a short one
+        let mut chain = vec![(op, right, span)];
+        let mut leftmost = left;
+        while let Expr::Binary { op, left, right, span } = leftmost {
+            if *op == BinaryOp::Add {
+                break;
+            }
+            chain.push((op, right, *span));
+            leftmost = left;
+        }
+        
+        // Compile the left operand (result in accumulator), then for each
+        // operator push the running value and apply it to the next right
+        // operand
+        self.compile_expression(leftmost)?;
+        for (op, right, span) in chain.into_iter().rev() {
+            self.emit(Bytecode::Push);
+            self.compile_expression(right)?;
+            self.emit_binary_operator(op, span);
+        }
+        Ok(())
+    }
+    
+    /// Emit the instruction for a binary operator
+    ///
+    /// The operation will be: stack_top op accumulator -> accumulator
+    fn emit_binary_operator(&mut self, op: &BinaryOp, span: Span) {
         match op {
             BinaryOp::Add => self.emit(Bytecode::Add),
             BinaryOp::Subtract => self.emit(Bytecode::Sub),
@@ -410,17 +1394,34 @@ impl Compiler {
             BinaryOp::RightShift => self.emit(Bytecode::RightShift),
             BinaryOp::UnsignedRightShift => self.emit(Bytecode::UnsignedRightShift),
             
-            // Not implemented yet
-            BinaryOp::InstanceOf | BinaryOp::In => {
-                return Err(Error::runtime(format!("Operator {:?} not yet implemented", op), None));
-            }
+            BinaryOp::InstanceOf => self.emit(Bytecode::InstanceOf),
+            BinaryOp::In => self.emit_with_span(Bytecode::In, span),
         }
-        
-        Ok(())
     }
     
     /// Compile unary operations (e.g., !, -, +, typeof)
-    fn compile_unary_operation(&mut self, op: &UnaryOp, operand: &Expr, _span: Span) -> Result<()> {
+    fn compile_unary_operation(&mut self, op: &UnaryOp, operand: &Expr, span: Span) -> Result<()> {
+        // `!!x` only coerces, so it needs a single instruction
+        if let (UnaryOp::LogicalNot, Expr::Unary { op: UnaryOp::LogicalNot, operand: inner, .. }) = (op, operand) {
+            self.compile_expression(inner)?;
+            self.emit(Bytecode::ToBoolean);
+            return Ok(());
+        }
+        
+        if *op == UnaryOp::Delete {
+            return self.compile_delete(operand, span);
+        }
+        
+        // `typeof undeclared` is "undefined", not a ReferenceError
+        if let (UnaryOp::TypeOf, Expr::Identifier { name, span }) = (op, operand) {
+            if let VariableLocation::Global = self.resolve_location(name) {
+                let name_const = self.add_constant_property_name(name)?;
+                self.emit_with_span(Bytecode::LdaGlobalInsideTypeof(name_const), *span);
+                self.emit(Bytecode::TypeOf);
+                return Ok(());
+            }
+        }
+        
         // Compile operand (result in accumulator)
         self.compile_expression(operand)?;
         
@@ -432,58 +1433,677 @@ impl Compiler {
             UnaryOp::BitwiseNot => self.emit(Bytecode::BitwiseNot),
             UnaryOp::TypeOf => self.emit(Bytecode::TypeOf),
             
-            // Not implemented yet
-            UnaryOp::Void | UnaryOp::Delete => {
-                return Err(Error::runtime(format!("Operator {:?} not yet implemented", op), None));
-            }
+            UnaryOp::Void => self.compile_literal(&Literal::Undefined)?,
+            
+            UnaryOp::Delete => unreachable!("delete is compiled by compile_delete"),
         }
         
         Ok(())
     }
     
+    /// Compile `delete operand`, leaving the boolean result in the accumulator
+    fn compile_delete(&mut self, operand: &Expr, span: Span) -> Result<()> {
+        match operand {
+            Expr::Member { object, property, computed, .. } => {
+                self.compile_expression(object)?;
+                match (property.as_ref(), *computed) {
+                    (Expr::Identifier { name, .. }, false) => {
+                        let name_const = self.add_constant_property_name(name)?;
+                        self.emit_with_span(Bytecode::DeleteNamedProperty(name_const), span);
+                    }
+                    (key, _) => {
+                        self.emit(Bytecode::Push);
+                        self.compile_expression(key)?;
+                        self.emit_with_span(Bytecode::DeleteKeyedProperty, span);
+                    }
+                }
+            }
+            Expr::Identifier { .. } => {
+                return Err(Error::compile(ErrorCode::UnsupportedSyntax, "Deleting variables is not supported yet", Some(span)));
+            }
+            // Anything that is not a reference is evaluated and deleted trivially
+            other => {
+                self.compile_expression(other)?;
+                let const_index = self.add_constant(ConstantValue::Boolean(true))?;
+                self.emit(Bytecode::LdaConst(const_index));
+            }
+        }
+        Ok(())
+    }
+    
     /// Compile assignment expressions
-    fn compile_assignment(&mut self, left: &Expr, right: &Expr, span: Span) -> Result<()> {
-        // Compile the right-hand side (value to assign)
-        self.compile_expression(right)?;
+    ///
+    /// An assignment is an expression whose value is the assigned value:
+    /// the right-hand side is left in the accumulator and the store
+    /// instructions never modify it, which is what makes `a = b = 0` and
+    /// `(x = 5) + 1` work.
+    ///
+    /// A compound assignment (`op` is `Some`) evaluates its target once:
+    /// `a[i++] += 1` reads and writes the same element, and `f().x += 1`
+    /// calls `f` once.
+    fn compile_assignment(&mut self, op: Option<&BinaryOp>, left: &Expr, right: &Expr, span: Span) -> Result<()> {
+        // Member targets evaluate the object (and key) before the value
+        if let Expr::Member { object, property, computed, .. } = left {
+            self.compile_expression(object)?;
+            self.emit(Bytecode::Push);
+            
+            match (property.as_ref(), *computed) {
+                (Expr::Identifier { name, .. }, false) => {
+                    let name_const = self.add_constant_property_name(name)?;
+                    if let Some(op) = op {
+                        // Load through a copy of the object, keeping the
+                        // original for the store
+                        self.emit(Bytecode::Dup);
+                        self.emit_with_span(Bytecode::LdaNamed(name_const), span);
+                        self.emit(Bytecode::Push);
+                        self.compile_expression(right)?;
+                        self.emit_binary_operator(op, span);
+                    } else {
+                        self.compile_expression(right)?;
+                    }
+                    self.emit_with_span(Bytecode::StaNamed(name_const), span);
+                }
+                (key, _) => {
+                    self.compile_expression(key)?;
+                    self.emit(Bytecode::Push);
+                    if let Some(op) = op {
+                        // Copy the object and key, and pop the key back
+                        // into the accumulator for the load
+                        self.emit(Bytecode::Dup2);
+                        self.emit(Bytecode::Pop);
+                        self.emit_with_span(Bytecode::LdaKeyed, span);
+                        self.emit(Bytecode::Push);
+                        self.compile_expression(right)?;
+                        self.emit_binary_operator(op, span);
+                    } else {
+                        self.compile_expression(right)?;
+                    }
+                    self.emit_with_span(Bytecode::StaKeyed, span);
+                }
+            }
+            return Ok(());
+        }
+        
+        // `x -= 1` (and `x = x - 1`) is a decrement. `x += 1` can't become
+        // an increment: it concatenates when x holds a string.
+        if let Expr::Identifier { name, .. } = left {
+            let is_one = |step: &Expr| matches!(step, Expr::Literal(Literal::Number(n)) if *n == 1.0);
+            let is_decrement = match (op, right) {
+                (Some(BinaryOp::Subtract), step) => is_one(step),
+                (None, Expr::Binary { op: BinaryOp::Subtract, left: operand, right: step, .. }) => {
+                    matches!(operand.as_ref(), Expr::Identifier { name: operand_name, .. } if operand_name == name) && is_one(step)
+                }
+                _ => false,
+            };
+            if is_decrement {
+                self.compile_expression(left)?;
+                self.emit_with_span(Bytecode::Dec, span);
+                self.compile_identifier_store(name, span)?;
+                return Ok(());
+            }
+        }
+        
+        // Compile the right-hand side (value to assign), combined with the
+        // target's current value for a compound assignment
+        if let Some(op) = op {
+            self.compile_expression(left)?;
+            self.emit(Bytecode::Push);
+            self.compile_expression(right)?;
+            self.emit_binary_operator(op, span);
+        } else {
+            self.compile_expression(right)?;
+        }
         
         // Handle different assignment targets
         match left {
             Expr::Identifier { name, .. } => {
                 // Simple variable assignment
-                if let Some(local) = self.resolve_variable(name) {
-                    // Local variable assignment
-                    self.emit_with_span(Bytecode::StaLocal(local.index), span);
-                } else {
-                    // Global variable assignment  
-                    let name_const = self.add_constant_property_name(name.clone());
-                    self.emit_with_span(Bytecode::StaGlobal(name_const), span);
-                }
+                self.compile_identifier_store(name, span)?;
             }
             
-            Expr::Member { .. } => {
-                // Property assignment: obj.prop = value or obj[key] = value
-                // TODO: Implement in member access section
-                return Err(Error::runtime("Member assignment not yet implemented", Some(span)));
+            Expr::Literal(Literal::Undefined) => {
+                // `undefined` is lexed as a literal but is really a global
+                // binding; the VM rejects the write since it is read-only
+                let name_const = self.add_constant_property_name("undefined")?;
+                self.emit_with_span(Bytecode::StaGlobal(name_const), span);
             }
             
             _ => {
-                return Err(Error::runtime("Invalid assignment target", Some(span)));
+                return Err(Error::compile(ErrorCode::InvalidAssignmentTarget, "Invalid assignment target", Some(span)));
             }
         }
         
         Ok(())
     }
     
+    /// Compile `x++` or `x--`
+    ///
+    /// With `keep_old_value` the accumulator ends up holding the variable's
+    /// previous value converted to a number, as the expression's result;
+    /// without it, the new value.
+    fn compile_update(&mut self, op: &PostfixUnaryOp, target: &Expr, keep_old_value: bool, span: Span) -> Result<()> {
+        let name = match target {
+            Expr::Identifier { name, .. } => name,
+            Expr::Member { .. } => {
+                return Err(Error::compile(ErrorCode::UnsupportedSyntax, "Increment and decrement of properties are not supported yet", Some(span)));
+            }
+            _ => {
+                return Err(Error::compile(ErrorCode::InvalidAssignmentTarget, "Invalid left-hand side expression in postfix operation", Some(span)));
+            }
+        };
+        
+        self.compile_expression(target)?;
+        if keep_old_value {
+            self.emit(Bytecode::UnaryPlus);
+            self.emit(Bytecode::Push);
+        }
+        match op {
+            PostfixUnaryOp::Increment => self.emit_with_span(Bytecode::Inc, span),
+            PostfixUnaryOp::Decrement => self.emit_with_span(Bytecode::Dec, span),
+        }
+        self.compile_identifier_store(name, span)?;
+        if keep_old_value {
+            self.emit(Bytecode::Pop);
+        }
+        Ok(())
+    }
+    
+    /// Store the accumulator into the variable `name`, wherever it lives
+    fn compile_identifier_store(&mut self, name: &str, span: Span) -> Result<()> {
+        if self.resolves_to_const(name) {
+            return Err(Error::runtime(ErrorCode::ConstAssignment, "TypeError: Assignment to constant variable.", Some(span)));
+        }
+        match self.resolve_location(name) {
+            VariableLocation::Local(index) => {
+                self.emit_with_span(Bytecode::StaLocal(index), span);
+            }
+            VariableLocation::Context(depth, index) => {
+                self.emit_with_span(Bytecode::StaContextSlot(depth, index), span);
+            }
+            VariableLocation::Global => {
+                let name_const = self.add_constant_property_name(name)?;
+                self.emit_with_span(Bytecode::StaGlobal(name_const), span);
+            }
+        }
+        Ok(())
+    }
+    
     /// Compile function calls
-    fn compile_function_call(&mut self, _callee: &Expr, _args: &[Expr], span: Span) -> Result<()> {
-        // TODO: Implement in function compilation task
-        Err(Error::runtime("Function calls not yet implemented", Some(span)))
+    ///
+    /// Plain calls push the callee and then each argument before `Call`.
+    /// Calls on a member expression also push the receiver first and use
+    /// `CallMethod`, so the callee sees it as `this`.
+    ///
+    /// The order is observable and must survive any later rewriting: in
+    /// `o.m(f(), g())` the object is evaluated once (both the receiver and
+    /// the load use that one value), `m` is looked up before `f()` runs,
+    /// and the arguments run left to right. An argument that throws
+    /// leaves the lookup and every earlier argument done, and the call
+    /// not made. `tests/evaluation_order_tests.rs` pins this down.
+    fn compile_function_call(&mut self, callee: &Expr, args: &[Expr], span: Span) -> Result<()> {
+        let is_method = if let Expr::Member { object, property, computed, .. } = callee {
+            self.compile_expression(object)?;
+            self.emit(Bytecode::Push); // receiver
+            self.emit(Bytecode::Push); // object for the property load
+            self.compile_property_load(property, *computed)?;
+            true
+        } else {
+            self.compile_expression(callee)?;
+            false
+        };
+        self.emit(Bytecode::Push);
+        
+        let argc = self.compile_arguments(args, span)?;
+        // Name the callee in case it turns out not to be a function
+        if let Some(name) = callee_name(callee) {
+            let offset = self.function.current_offset();
+            self.function.debug_info.callee_names.insert(offset, name);
+        }
+        if is_method {
+            self.emit_with_span(Bytecode::CallMethod(argc), span);
+        } else {
+            self.emit_with_span(Bytecode::Call(argc), span);
+        }
+        Ok(())
+    }
+    
+    /// Push each call argument in order, returning the count
+    fn compile_arguments(&mut self, args: &[Expr], span: Span) -> Result<ArgCount> {
+        let argc = ArgCount::try_from(args.len()).map_err(|_| Error::compile(
+            ErrorCode::CompilerLimit,
+            format!("Too many arguments in function call ({})", args.len()),
+            Some(span),
+        ))?;
+        for arg in args {
+            self.compile_expression(arg)?;
+            self.emit(Bytecode::Push);
+        }
+        Ok(argc)
     }
     
     /// Compile member access (obj.prop or obj[key])
-    fn compile_member_access(&mut self, _object: &Expr, _property: &Expr, _computed: bool, span: Span) -> Result<()> {
-        // TODO: Implement in next tasks
-        Err(Error::runtime("Member access not yet implemented", Some(span)))
+    fn compile_member_access(&mut self, object: &Expr, property: &Expr, computed: bool, _span: Span) -> Result<()> {
+        self.compile_expression(object)?;
+        self.emit(Bytecode::Push);
+        self.compile_property_load(property, computed)
+    }
+    
+    /// Load a property of the object on top of the stack into the accumulator
+    fn compile_property_load(&mut self, property: &Expr, computed: bool) -> Result<()> {
+        match (property, computed) {
+            (Expr::Identifier { name, span }, false) => {
+                let name_const = self.add_constant_property_name(name)?;
+                self.emit_with_span(Bytecode::LdaNamed(name_const), *span);
+            }
+            (key, _) => {
+                self.compile_expression(key)?;
+                self.emit_with_span(Bytecode::LdaKeyed, key.span());
+            }
+        }
+        Ok(())
+    }
+    
+    /// Compile an array literal; holes are simply never stored
+    ///
+    /// Constant elements come from a template in one step, leaving only
+    /// the others to be stored.
+    fn compile_array_literal(&mut self, elements: &[Option<Expr>], span: Span) -> Result<()> {
+        let length = ElementIndex::try_from(elements.len())
+            .map_err(|_| Error::compile(ErrorCode::CompilerLimit, "Array literal has too many elements", Some(span)))?;
+        
+        let template: Vec<Option<ConstantValue>> = elements.iter()
+            .map(|element| element.as_ref().and_then(constant_template_value))
+            .collect();
+        let constants = template.iter().flatten().count();
+        if constants == 0 {
+            self.emit_with_span(Bytecode::CreateArray(length), span);
+        } else {
+            let template_const = self.add_constant(ConstantValue::ArrayTemplate(template.clone()))?;
+            self.emit_with_span(Bytecode::CreateFromTemplate(template_const), span);
+            if constants == elements.iter().flatten().count() {
+                return Ok(());
+            }
+        }
+        self.emit(Bytecode::Push);
+        
+        for (index, element) in elements.iter().enumerate() {
+            if let Some(element) = element {
+                if template[index].is_some() {
+                    continue;
+                }
+                self.compile_expression(element)?;
+                self.emit(Bytecode::StaArrayElement(index as ElementIndex));
+            }
+        }
+        
+        // Leave the array in the accumulator
+        self.emit(Bytecode::Pop);
+        Ok(())
+    }
+    
+    /// Compile an object literal
+    ///
+    /// Entries are applied in source order, so a later property or spread
+    /// overwrites an earlier one while the key keeps its first position.
+    /// Constant properties before the first spread or computed key come
+    /// from a template instead; see [`object_template`].
+    fn compile_object_literal(&mut self, properties: &[PropertyDefinition], span: Span) -> Result<()> {
+        let (template, covered) = object_template(properties);
+        if covered.contains(&true) {
+            let template_const = self.add_constant(ConstantValue::ObjectTemplate(template))?;
+            self.emit_with_span(Bytecode::CreateFromTemplate(template_const), span);
+            if covered.len() == properties.len() && !covered.contains(&false) {
+                return Ok(());
+            }
+        } else {
+            self.emit_with_span(Bytecode::CreateObject, span);
+        }
+        self.emit(Bytecode::Push);
+        
+        for (index, definition) in properties.iter().enumerate() {
+            if covered.get(index) == Some(&true) {
+                continue;
+            }
+            match definition {
+                PropertyDefinition::Property(property) => {
+                    let name = match &property.key {
+                        PropertyKey::Computed(key) => {
+                            self.compile_expression(key)?;
+                            self.emit(Bytecode::Push);
+                            None
+                        }
+                        key => static_key(key),
+                    };
+                    
+                    self.compile_expression(&property.value)?;
+                    match name {
+                        Some(name) => {
+                            let name_const = self.add_constant_property_name(&name)?;
+                            self.emit_with_span(Bytecode::DefineNamedProperty(name_const), property.span);
+                        }
+                        None => self.emit_with_span(Bytecode::DefineKeyedProperty, property.span),
+                    }
+                }
+                PropertyDefinition::SpreadProperty { argument, span } => {
+                    self.compile_expression(argument)?;
+                    self.emit_with_span(Bytecode::CopyDataProperties, *span);
+                }
+            }
+        }
+        
+        // Leave the object in the accumulator
+        self.emit(Bytecode::Pop);
+        Ok(())
+    }
+    
+    /// Record the literal allocated at `offset` as an arena site
+    fn mark_arena_site(&mut self, offset: usize) {
+        if matches!(
+            self.function.bytecode.get(offset),
+            Some(Bytecode::CreateObject | Bytecode::CreateArray(_) | Bytecode::CreateFromTemplate(_))
+        ) {
+            self.function.arena_sites.push(offset);
+        }
+    }
+    
+    /// Compile a nested function and emit `CreateClosure` for it
+    fn compile_function(
+        &mut self,
+        name: &str,
+        params: &[(String, Span)],
+        body: &[Stmt],
+        kind: FunctionKind,
+        span: Span,
+    ) -> Result<()> {
+        let source = self.function.debug_info.source_code.clone().unwrap_or_else(|| Arc::from(""));
+        let mut compiler = Compiler::new_function_in(name.to_string(), params, source);
+        compiler.function.debug_info.function_span = Some(span);
+        compiler.function.is_generator = kind == FunctionKind::Generator;
+        compiler.function.is_async = matches!(kind, FunctionKind::Async | FunctionKind::AsyncArrow);
+        compiler.function.is_arrow = matches!(kind, FunctionKind::Arrow | FunctionKind::AsyncArrow);
+        compiler.function.is_class_constructor = kind == FunctionKind::ClassConstructor;
+        compiler.names = self.names.clone();
+        compiler.depth = self.depth;
+        compiler.max_depth = self.max_depth;
+        compiler.fold_builtins = self.fold_builtins;
+        compiler.max_hoisted_constants = self.max_hoisted_constants;
+        compiler.top_level_bindings = self.top_level_bindings.clone();
+        compiler.enclosing = self.enclosing.clone();
+        compiler.enclosing.push(self.visible_locals());
+        // Class bodies are always strict mode code
+        let in_class = matches!(kind, FunctionKind::ClassConstructor | FunctionKind::ClassMethod);
+        compiler.function.is_strict = self.function.is_strict || in_class || Stmt::has_use_strict_directive(body);
+        compiler.non_escaping = escape::non_escaping_locals(params, body, self.max_depth);
+        
+        let layout = layout::function_layout(params, body, compiler.next_local_index, false, compiler.depth, compiler.max_depth)?;
+        compiler.apply_layout(layout);
+        compiler.compile_statements(body)?;
+        compiler.emit(Bytecode::ReturnUndefined);
+        compiler.function.calculate_stack_size();
+        
+        // Hand reads of our variables back to us and pass deeper ones on;
+        // a read marks the scope binding the name as `visible_locals` saw
+        // it when the function was made
+        for (depth, name) in std::mem::take(&mut compiler.captured_reads) {
+            if depth == 1 {
+                if let Some(scope) = self.scopes.iter().rposition(|scope| scope.locals.contains_key(&name)) {
+                    self.mark_read(scope, &name);
+                }
+            } else {
+                self.captured_reads.insert((depth - 1, name));
+            }
+        }
+        let scope = compiler.scopes[0].clone();
+        compiler.warn_unused_locals(&scope);
+        compiler.finish_locals();
+        hoist::hoist_loop_constants(&mut compiler.function, compiler.max_hoisted_constants);
+        self.diagnostics.append(&mut compiler.diagnostics);
+        
+        let index = ConstIndex::try_from(self.function.functions.len())
+            .map_err(|_| Error::compile(ErrorCode::CompilerLimit, "Too many nested functions", Some(span)))?;
+        self.function.functions.push(Arc::new(compiler.function));
+        self.emit_with_span(Bytecode::CreateClosure(index), span);
+        Ok(())
+    }
+    
+    /// Compile a class to its constructor function, left in the accumulator
+    ///
+    /// Desugars to the constructor (an empty one if the class has none)
+    /// with instance methods installed on its `prototype` and static
+    /// methods on the constructor itself. Methods are plain enumerable
+    /// properties for now.
+    fn compile_class(&mut self, name: &str, body: &ClassBody, span: Span) -> Result<()> {
+        let (params, statements) = match &body.constructor {
+            Some(constructor) => (constructor.params.as_slice(), constructor.body.as_slice()),
+            None => (&[][..], &[][..]),
+        };
+        self.compile_function(name, params, statements, FunctionKind::ClassConstructor, span)?;
+        self.emit(Bytecode::Push);
+        
+        let (statics, instance): (Vec<_>, Vec<_>) = body.methods.iter().partition(|method| method.is_static);
+        if !instance.is_empty() {
+            let prototype_const = self.add_constant_property_name("prototype")?;
+            self.emit(Bytecode::Dup);
+            self.emit(Bytecode::LdaNamed(prototype_const));
+            self.emit(Bytecode::Push);
+            for method in instance {
+                self.compile_function(&method.name, &method.params, &method.body, FunctionKind::ClassMethod, method.span)?;
+                let name_const = self.add_constant_property_name(&method.name)?;
+                self.emit_with_span(Bytecode::DefineNamedProperty(name_const), method.span);
+            }
+            self.emit(Bytecode::Pop);
+        }
+        for method in statics {
+            self.compile_function(&method.name, &method.params, &method.body, FunctionKind::ClassMethod, method.span)?;
+            let name_const = self.add_constant_property_name(&method.name)?;
+            self.emit_with_span(Bytecode::DefineNamedProperty(name_const), method.span);
+        }
+        
+        self.emit(Bytecode::Pop);
+        Ok(())
+    }
+}
+
+/// Enter the variables of `scope`, which ends here, in the symbol table
+/// of `function`'s debug info
+fn record_locals(function: &mut BytecodeFunction, scope: &Scope) {
+    let end = function.current_offset();
+    function.debug_info.locals.extend(scope.locals.values().map(|slot| LocalVariable {
+        name: slot.name.clone(),
+        slot: slot.index,
+        is_parameter: slot.is_parameter,
+        span: slot.span,
+        live: slot.declared_at.min(end)..end,
+    }));
+}
+
+/// The keyword that makes control leave a statement list after `stmt`, if any
+fn abrupt_exit(stmt: &Stmt) -> Option<&'static str> {
+    match stmt {
+        Stmt::Return { .. } => Some("return"),
+        Stmt::Break { .. } => Some("break"),
+        Stmt::Continue { .. } => Some("continue"),
+        Stmt::Block { statements, .. } => statements.iter().find_map(abrupt_exit),
+        Stmt::If { then_stmt, else_stmt: Some(else_stmt), .. } => {
+            abrupt_exit(else_stmt).and(abrupt_exit(then_stmt))
+        }
+        _ => None,
+    }
+}
+
+/// How a callee reads in the source, e.g. `obj.method` or `f(...)`
+///
+/// Only simple shapes are described; anything else yields `None`.
+fn callee_name(callee: &Expr) -> Option<String> {
+    match callee {
+        Expr::Identifier { name, .. } => Some(name.clone()),
+        Expr::This { .. } => Some("this".to_string()),
+        Expr::Member { object, property, computed: false, .. } => match property.as_ref() {
+            Expr::Identifier { name, .. } => Some(format!("{}.{}", callee_name(object)?, name)),
+            _ => None,
+        },
+        Expr::Member { object, property, computed: true, .. } => {
+            let key = match property.as_ref() {
+                Expr::Identifier { name, .. } => name.clone(),
+                Expr::Literal(Literal::Number(n)) => n.to_string(),
+                Expr::Literal(Literal::String(s)) => format!("{:?}", s),
+                _ => "...".to_string(),
+            };
+            Some(format!("{}[{}]", callee_name(object)?, key))
+        }
+        Expr::Call { callee, .. } => Some(format!("{}(...)", callee_name(callee)?)),
+        _ => None,
+    }
+}
+
+/// The immediate for a number literal that fits `LdaSmi`
+///
+/// Negative zero is excluded: it would load as positive zero.
+fn small_integer(value: f64) -> Option<SmallInteger> {
+    let fits = value.fract() == 0.0
+        && value >= SmallInteger::MIN as f64
+        && value <= SmallInteger::MAX as f64;
+    if fits && !(value == 0.0 && value.is_sign_negative()) {
+        Some(value as SmallInteger)
+    } else {
+        None
+    }
+}
+
+/// The operands of `left + right` in evaluation order, with the
+/// left-nested `+` chain in `left` flattened without recursion
+fn addition_operands<'a>(left: &'a Expr, right: &'a Expr) -> Vec<&'a Expr> {
+    let mut operands = vec![right];
+    let mut node = left;
+    while let Expr::Binary { op: BinaryOp::Add, left, right, .. } = node {
+        operands.push(right);
+        node = left;
+    }
+    operands.push(node);
+    operands.reverse();
+    operands
+}
+
+/// The value of a primitive literal, a negated number, or a `+` chain of
+/// them
+fn constant_primitive(expr: &Expr) -> Option<Value> {
+    match expr {
+        Expr::Literal(literal) => match literal {
+            Literal::Number(n) => Some(Value::Number(*n)),
+            Literal::String(s) => Some(Value::string(s.clone())),
+            Literal::Boolean(b) => Some(Value::Boolean(*b)),
+            Literal::Null => Some(Value::Null),
+            Literal::Undefined => Some(Value::Undefined),
+            Literal::RegExp { .. } => None,
+        },
+        Expr::Unary { op: UnaryOp::Minus, operand, .. } => match operand.as_ref() {
+            Expr::Literal(Literal::Number(n)) => Some(Value::Number(-n)),
+            _ => None,
+        },
+        Expr::Binary { op: BinaryOp::Add, left, right, .. } => addition_operands(left, right)
+            .into_iter()
+            .map(constant_primitive)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(add_primitives),
+        _ => None,
+    }
+}
+
+/// A literal's value as a template constant: a primitive, or an array or
+/// object literal made only of them
+fn constant_template_value(expr: &Expr) -> Option<ConstantValue> {
+    match expr {
+        Expr::Array { elements, .. } => elements.iter()
+            .map(|element| match element {
+                Some(element) => constant_template_value(element).map(Some),
+                None => Some(None),
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(ConstantValue::ArrayTemplate),
+        Expr::Object { properties, .. } => {
+            let (template, covered) = object_template(properties);
+            let all_constant = covered.len() == properties.len() && !covered.contains(&false);
+            all_constant.then_some(ConstantValue::ObjectTemplate(template))
+        }
+        _ => match constant_primitive(expr)? {
+            Value::Number(n) => Some(ConstantValue::Number(HashableF64(n))),
+            Value::String(s) => Some(ConstantValue::String(s.to_string())),
+            Value::Boolean(b) => Some(ConstantValue::Boolean(b)),
+            Value::Null => Some(ConstantValue::Null),
+            Value::Undefined => Some(ConstantValue::Undefined),
+            _ => None,
+        },
+    }
+}
+
+/// The template for the properties of an object literal up to its first
+/// spread or computed key, and which of those properties it covers
+///
+/// A key whose every definition there is constant takes its last value
+/// from the template. Any other key gets an `undefined` placeholder that
+/// holds its position, and all of its definitions are compiled as usual,
+/// in order, so side effects and the final value are unchanged.
+fn object_template(properties: &[PropertyDefinition]) -> (Vec<(String, ConstantValue)>, Vec<bool>) {
+    let mut template: Vec<(String, ConstantValue)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    // Per template entry: its value, while every definition so far is constant
+    let mut values: Vec<Option<ConstantValue>> = Vec::new();
+    let mut keys = Vec::new();
+    
+    for definition in properties {
+        let PropertyDefinition::Property(property) = definition else { break };
+        let Some(key) = static_key(&property.key) else { break };
+        let value = constant_template_value(&property.value);
+        match positions.get(&key) {
+            Some(&position) => {
+                if values[position].is_some() {
+                    values[position] = value;
+                }
+            }
+            None => {
+                positions.insert(key.clone(), template.len());
+                template.push((key.clone(), ConstantValue::Undefined));
+                values.push(value);
+            }
+        }
+        keys.push(key);
+    }
+    
+    let covered = keys.iter().map(|key| values[positions[key]].is_some()).collect();
+    for ((_, slot), value) in template.iter_mut().zip(values) {
+        if let Some(value) = value {
+            *slot = value;
+        }
+    }
+    (template, covered)
+}
+
+/// A property key known without running anything
+fn static_key(key: &PropertyKey) -> Option<String> {
+    match key {
+        PropertyKey::Identifier(name) | PropertyKey::String(name) => Some(name.clone()),
+        PropertyKey::Number(n) => Some(Value::Number(*n).to_string()),
+        PropertyKey::Computed(_) => None,
+    }
+}
+
+/// `a + b` for primitives, exactly as the `Add` instruction computes it
+fn add_primitives(a: Value, b: Value) -> Value {
+    match (&a, &b) {
+        (Value::String(_), _) | (_, Value::String(_)) => Value::string(format!("{}{}", a, b)),
+        _ => Value::Number(a.to_number() + b.to_number()),
+    }
+}
+
+/// Truthiness of an expression when it can be known without running it
+fn constant_truthiness(expr: &Expr) -> Option<bool> {
+    match expr {
+        // Object values are always truthy
+        Expr::Literal(Literal::RegExp { .. }) | Expr::Object { .. } | Expr::Array { .. } | Expr::Function { .. } => Some(true),
+        Expr::Literal(_) => constant_primitive(expr).map(|value| value.is_truthy()),
+        Expr::Unary { op: UnaryOp::LogicalNot, operand, .. } => constant_truthiness(operand).map(|truthy| !truthy),
+        _ => None,
     }
 }
 
@@ -516,35 +2136,40 @@ mod tests {
     #[test]
     fn test_scope_management() {
         let mut compiler = Compiler::new_main("test");
-        
-        // Declare variable in main scope
         let span = Span::new(0, 0, 1, 1);
-        let index1 = compiler.declare_local("x".to_string(), span).unwrap();
-        assert_eq!(index1, 0);
-        assert!(compiler.resolve_variable("x").is_some());
+        let binding = |name: &str, slot| layout::Binding { name: name.to_string(), slot: Some(LocalIndex(slot)), is_const: false, is_lexical: false, span };
+        compiler.apply_layout(FunctionLayout {
+            scopes: vec![
+                ScopeLayout { first_slot: 0, bindings: vec![binding("x", 0)] },
+                ScopeLayout { first_slot: 1, bindings: vec![binding("y", 1)] },
+            ],
+            locals_count: 2,
+        });
         
-        // Enter block scope
-        compiler.enter_scope(ScopeType::Block);
+        // The main scope's variable is bound from the start
+        assert_eq!(compiler.resolve_variable("x").map(|slot| slot.index), Some(LocalIndex(0)));
         
-        // Declare variable in block scope
-        let index2 = compiler.declare_local("y".to_string(), span).unwrap();
-        assert_eq!(index2, 1);
-        assert!(compiler.resolve_variable("y").is_some());
+        // Enter block scope, which binds its variable
+        compiler.enter_scope(ScopeType::Block).unwrap();
+        assert_eq!(compiler.resolve_variable("y").map(|slot| slot.index), Some(LocalIndex(1)));
         assert!(compiler.resolve_variable("x").is_some()); // Still accessible
         
         // Exit block scope
         compiler.exit_scope();
         assert!(compiler.resolve_variable("x").is_some());
         assert!(compiler.resolve_variable("y").is_none()); // No longer accessible
+        
+        // Every scope was laid out beforehand
+        assert!(compiler.enter_scope(ScopeType::Block).is_err());
     }
     
     #[test]
     fn test_constant_management() {
         let mut compiler = Compiler::new_main("test");
         
-        let _num_const = compiler.add_constant_number(42.0);
-        let _str_const = compiler.add_constant_string("hello".to_string());
-        let _prop_const = compiler.add_constant_property_name("length".to_string());
+        let _num_const = compiler.add_constant_number(42.0).unwrap();
+        let _str_const = compiler.add_constant_string("hello".to_string()).unwrap();
+        let _prop_const = compiler.add_constant_property_name("length").unwrap();
         
         assert_eq!(compiler.function.constants.len(), 3);
         
@@ -552,4 +2177,15 @@ mod tests {
         // (We would need to expose constant pool access for this)
     }
     
+    #[test]
+    fn test_double_negation_compiles_to_to_boolean() {
+        let source = "let x = 'a'; !!x;";
+        let tokens = crate::lexer::Lexer::new(source).tokenize().unwrap();
+        let program = crate::parser::Parser::new(tokens).parse().unwrap();
+        let function = Compiler::new_main(source).compile(&program).unwrap();
+        
+        assert!(function.bytecode.contains(&Bytecode::ToBoolean));
+        assert!(!function.bytecode.contains(&Bytecode::LogicalNot));
+    }
+
 }
\ No newline at end of file
@@ -2,15 +2,63 @@
 //!
 //! This module manages constants referenced by bytecode instructions.
 //! It provides deduplication to save memory and efficient access patterns.
+//!
+//! A pool holds at most [`MAX_CONSTANTS`] entries, the number a
+//! `ConstIndex` can address. Indices are handed out in insertion order and
+//! never change, so tools can rely on them while editing a pool with
+//! [`ConstantPool::replace`].
 
 use std::collections::HashMap;
 use std::fmt;
-use super::instruction::ConstIndex;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, ErrorCode};
+use crate::lexer::{EscapeValidator, UnicodeHelper};
+use super::instruction::{ConstIndex, OperandOutOfRange};
+use super::string_table::NameId;
+
+/// How many constants one pool can hold
+pub const MAX_CONSTANTS: usize = u16::MAX as usize + 1;
 
 /// Wrapper for f64 that implements Hash and Eq for HashMap usage
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "JsonNumber", into = "JsonNumber")]
 pub struct HashableF64(pub f64);
 
+/// How a constant number is written in a bytecode dump: JSON has no NaN
+/// or infinities, so those are written as their JavaScript names
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum JsonNumber {
+    Finite(f64),
+    NonFinite(String),
+}
+
+impl From<HashableF64> for JsonNumber {
+    fn from(number: HashableF64) -> Self {
+        if number.0.is_finite() {
+            JsonNumber::Finite(number.0)
+        } else {
+            JsonNumber::NonFinite(number.to_string())
+        }
+    }
+}
+
+impl TryFrom<JsonNumber> for HashableF64 {
+    type Error = String;
+    
+    fn try_from(number: JsonNumber) -> Result<Self, Self::Error> {
+        match number {
+            JsonNumber::Finite(n) => Ok(HashableF64(n)),
+            JsonNumber::NonFinite(name) => match name.as_str() {
+                "NaN" => Ok(HashableF64(f64::NAN)),
+                "Infinity" => Ok(HashableF64(f64::INFINITY)),
+                "-Infinity" => Ok(HashableF64(f64::NEG_INFINITY)),
+                _ => Err(format!("expected a number, NaN or an infinity, not '{}'", name)),
+            },
+        }
+    }
+}
+
 impl PartialEq for HashableF64 {
     fn eq(&self, other: &Self) -> bool {
         // Handle NaN case - in JS, NaN !== NaN, but for deduplication we treat them as equal
@@ -28,7 +76,7 @@ impl std::hash::Hash for HashableF64 {
     fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
         if self.0.is_nan() {
             // All NaN values hash to the same value for deduplication
-            std::f64::NAN.to_bits().hash(state);
+            f64::NAN.to_bits().hash(state);
         } else {
             self.0.to_bits().hash(state);
         }
@@ -55,7 +103,7 @@ impl std::fmt::Display for HashableF64 {
 }
 
 /// Constant values that can be stored in the constant pool
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ConstantValue {
     /// JavaScript number (always f64 as per spec)
     Number(HashableF64),
@@ -72,28 +120,116 @@ pub enum ConstantValue {
     /// JavaScript undefined
     Undefined,
     
-    /// JavaScript regex pattern (stored as string for now)
-    Regex(String),
+    /// JavaScript regex literal (pattern and flags, uncompiled)
+    Regex { pattern: String, flags: String },
+    
+    /// Property name for fast property access, an index into the
+    /// program's shared [`StringTable`](super::StringTable)
+    PropertyName(NameId),
     
-    /// Property name for fast property access
-    PropertyName(String),
+    /// Object literal made of constants: properties in insertion order
+    ///
+    /// `CreateFromTemplate` turns it into a fresh object each time; values
+    /// are primitives or nested templates.
+    ObjectTemplate(Vec<(String, ConstantValue)>),
+    
+    /// Array literal made of constants, with `None` for holes
+    ArrayTemplate(Vec<Option<ConstantValue>>),
 }
 
 impl fmt::Display for ConstantValue {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ConstantValue::Number(n) => write!(f, "{}", n),
-            ConstantValue::String(s) => write!(f, "\"{}\"", escape_string(s)),
+            ConstantValue::String(s) => write!(f, "\"{}\"", EscapeValidator::escape_string(s)),
             ConstantValue::Boolean(b) => write!(f, "{}", b),
             ConstantValue::Null => write!(f, "null"),
             ConstantValue::Undefined => write!(f, "undefined"),
-            ConstantValue::Regex(pattern) => write!(f, "/{}/", pattern),
-            ConstantValue::PropertyName(name) => write!(f, ".{}", name),
+            ConstantValue::Regex { pattern, flags } => write!(f, "/{}/{}", pattern, flags),
+            ConstantValue::PropertyName(id) => write!(f, ".#{}", id),
+            ConstantValue::ObjectTemplate(properties) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in properties.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    if UnicodeHelper::is_identifier_name(key) {
+                        write!(f, "{}{}: {}", separator, key, value)?;
+                    } else {
+                        write!(f, "{}\"{}\": {}", separator, EscapeValidator::escape_string(key), value)?;
+                    }
+                }
+                if properties.is_empty() { write!(f, "}}") } else { write!(f, " }}") }
+            }
+            ConstantValue::ArrayTemplate(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    if let Some(element) = element {
+                        write!(f, "{}", element)?;
+                    }
+                }
+                // A trailing hole needs its own comma, as in the source
+                if let Some(None) = elements.last() {
+                    write!(f, ",")?;
+                }
+                write!(f, "]")
+            }
         }
     }
 }
 
+/// What sort of constant a [`ConstantValue`] is
+///
+/// Instructions pick constants by kind: `LdaNamed` wants a property name
+/// and `CreateFromTemplate` a template, while `LdaConst` takes any of the
+/// value kinds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConstantKind {
+    Number,
+    String,
+    Boolean,
+    Null,
+    Undefined,
+    Regex,
+    PropertyName,
+    ObjectTemplate,
+    ArrayTemplate,
+}
+
+impl fmt::Display for ConstantKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConstantKind::Number => "number",
+            ConstantKind::String => "string",
+            ConstantKind::Boolean => "boolean",
+            ConstantKind::Null => "null",
+            ConstantKind::Undefined => "undefined",
+            ConstantKind::Regex => "regex",
+            ConstantKind::PropertyName => "property name",
+            ConstantKind::ObjectTemplate => "object template",
+            ConstantKind::ArrayTemplate => "array template",
+        };
+        write!(f, "{}", name)
+    }
+}
+
 impl ConstantValue {
+    /// What sort of constant this is
+    pub fn kind(&self) -> ConstantKind {
+        match self {
+            ConstantValue::Number(_) => ConstantKind::Number,
+            ConstantValue::String(_) => ConstantKind::String,
+            ConstantValue::Boolean(_) => ConstantKind::Boolean,
+            ConstantValue::Null => ConstantKind::Null,
+            ConstantValue::Undefined => ConstantKind::Undefined,
+            ConstantValue::Regex { .. } => ConstantKind::Regex,
+            ConstantValue::PropertyName(_) => ConstantKind::PropertyName,
+            ConstantValue::ObjectTemplate(_) => ConstantKind::ObjectTemplate,
+            ConstantValue::ArrayTemplate(_) => ConstantKind::ArrayTemplate,
+        }
+    }
+    
     /// Get the JavaScript type name for this constant
     pub fn type_name(&self) -> &'static str {
         match self {
@@ -102,7 +238,9 @@ impl ConstantValue {
             ConstantValue::Boolean(_) => "boolean",
             ConstantValue::Null => "object", // typeof null === "object" in JS
             ConstantValue::Undefined => "undefined",
-            ConstantValue::Regex(_) => "object",
+            ConstantValue::Regex { .. } |
+            ConstantValue::ObjectTemplate(_) |
+            ConstantValue::ArrayTemplate(_) => "object",
         }
     }
     
@@ -110,10 +248,15 @@ impl ConstantValue {
     pub fn is_truthy(&self) -> bool {
         match self {
             ConstantValue::Number(n) => !n.0.is_nan() && n.0 != 0.0,
-            ConstantValue::String(s) | ConstantValue::PropertyName(s) => !s.is_empty(),
+            ConstantValue::String(s) => !s.is_empty(),
+            // A key for property access, never loaded as a value
+            ConstantValue::PropertyName(_) => true,
             ConstantValue::Boolean(b) => *b,
             ConstantValue::Null | ConstantValue::Undefined => false,
-            ConstantValue::Regex(_) => true, // objects are always truthy
+            // Objects are always truthy
+            ConstantValue::Regex { .. } |
+            ConstantValue::ObjectTemplate(_) |
+            ConstantValue::ArrayTemplate(_) => true,
         }
     }
 }
@@ -152,19 +295,23 @@ impl ConstantPool {
     
     /// Add a constant to the pool, returns its index
     /// If the constant already exists, returns the existing index (deduplication)
+    ///
+    /// Panics when the pool already holds [`MAX_CONSTANTS`] other
+    /// constants; see [`ConstantPool::try_add_constant`].
     pub fn add_constant(&mut self, value: ConstantValue) -> ConstIndex {
+        self.try_add_constant(value)
+            .unwrap_or_else(|error| panic!("Constant pool overflow: {}", error))
+    }
+    
+    /// Add a constant to the pool, or find the one already there, failing
+    /// when a new constant wouldn't have an index
+    pub fn try_add_constant(&mut self, value: ConstantValue) -> Result<ConstIndex, OperandOutOfRange> {
         // Check if we already have this constant
         if let Some(&index) = self.index_map.get(&value) {
-            return index;
+            return Ok(index);
         }
         
-        // Add new constant
-        let index = self.values.len() as ConstIndex;
-        
-        // Handle potential overflow (should never happen in practice)
-        if self.values.len() >= u16::MAX as usize {
-            panic!("Constant pool overflow: too many constants (max {})", u16::MAX);
-        }
+        let index = ConstIndex::try_from(self.values.len())?;
         
         // Add to string cache if it's a string
         if let ConstantValue::String(ref s) = value {
@@ -174,7 +321,7 @@ impl ConstantPool {
         self.values.push(value.clone());
         self.index_map.insert(value, index);
         
-        index
+        Ok(index)
     }
     
     /// Convenience method to add a number constant
@@ -198,7 +345,10 @@ impl ConstantPool {
     }
     
     /// Convenience method to add a property name
-    pub fn add_property_name(&mut self, name: String) -> ConstIndex {
+    ///
+    /// The name itself goes in the program's [`StringTable`](super::StringTable);
+    /// see `BytecodeFunction::add_property_name`.
+    pub fn add_property_name(&mut self, name: NameId) -> ConstIndex {
         self.add_constant(ConstantValue::PropertyName(name))
     }
     
@@ -207,6 +357,21 @@ impl ConstantPool {
         self.add_constant(ConstantValue::Null)
     }
     
+    /// Add regular expression literal constant
+    pub fn add_regex(&mut self, pattern: String, flags: String) -> ConstIndex {
+        self.add_constant(ConstantValue::Regex { pattern, flags })
+    }
+    
+    /// Add an object literal template
+    pub fn add_object_template(&mut self, properties: Vec<(String, ConstantValue)>) -> ConstIndex {
+        self.add_constant(ConstantValue::ObjectTemplate(properties))
+    }
+    
+    /// Add an array literal template
+    pub fn add_array_template(&mut self, elements: Vec<Option<ConstantValue>>) -> ConstIndex {
+        self.add_constant(ConstantValue::ArrayTemplate(elements))
+    }
+    
     /// Add undefined constant
     pub fn add_undefined(&mut self) -> ConstIndex {
         self.add_constant(ConstantValue::Undefined)
@@ -214,12 +379,45 @@ impl ConstantPool {
     
     /// Get a constant by index
     pub fn get(&self, index: ConstIndex) -> Option<&ConstantValue> {
-        self.values.get(index as usize)
+        self.values.get(index.index())
+    }
+    
+    /// The number constant at `index`
+    pub fn get_number(&self, index: ConstIndex) -> Option<f64> {
+        match self.get(index)? {
+            ConstantValue::Number(n) => Some(n.0),
+            _ => None,
+        }
+    }
+    
+    /// The string constant at `index`
+    pub fn get_string(&self, index: ConstIndex) -> Option<&str> {
+        match self.get(index)? {
+            ConstantValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+    
+    /// The boolean constant at `index`
+    pub fn get_boolean(&self, index: ConstIndex) -> Option<bool> {
+        match self.get(index)? {
+            ConstantValue::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+    
+    /// The property name constant at `index`, an id in the program's
+    /// [`StringTable`](super::StringTable)
+    pub fn get_property_name(&self, index: ConstIndex) -> Option<NameId> {
+        match self.get(index)? {
+            ConstantValue::PropertyName(id) => Some(*id),
+            _ => None,
+        }
     }
     
     /// Get a constant by index, panicking if not found
     pub fn get_unchecked(&self, index: ConstIndex) -> &ConstantValue {
-        &self.values[index as usize]
+        &self.values[index.index()]
     }
     
     /// Get the number of constants in the pool
@@ -237,21 +435,70 @@ impl ConstantPool {
         self.values
             .iter()
             .enumerate()
-            .map(|(i, v)| (i as ConstIndex, v))
+            .map(|(i, v)| (ConstIndex(i as u16), v))
     }
     
     /// Find the index of a constant if it exists
-    pub fn find_constant(&self, value: &ConstantValue) -> Option<ConstIndex> {
+    ///
+    /// After a `replace` made two entries equal, this is the lower index.
+    pub fn find(&self, value: &ConstantValue) -> Option<ConstIndex> {
         self.index_map.get(value).copied()
     }
     
+    /// Find the index of a constant if it exists
+    #[deprecated(note = "renamed to `find`")]
+    pub fn find_constant(&self, value: &ConstantValue) -> Option<ConstIndex> {
+        self.find(value)
+    }
+    
+    /// Put `value` at `index` in place of the constant there, returning
+    /// the old one
+    ///
+    /// Instructions that use the index keep working only if the new value
+    /// has the same [`ConstantKind`], so any other kind is an error, as is
+    /// an index past the end. Other indices don't move. Adding the old
+    /// value again afterwards gives it a new index.
+    pub fn replace(&mut self, index: ConstIndex, value: ConstantValue) -> crate::error::Result<ConstantValue> {
+        let old = self.values.get(index.index()).ok_or_else(|| Error::runtime(
+            ErrorCode::InvalidConstant,
+            format!("No constant #{} to replace in a pool of {}", index, self.len()),
+            None,
+        ))?;
+        if old.kind() != value.kind() {
+            return Err(Error::runtime(
+                ErrorCode::InvalidConstant,
+                format!("Cannot replace {} constant #{} with a {}", old.kind(), index, value.kind()),
+                None,
+            ));
+        }
+        
+        let old = std::mem::replace(&mut self.values[index.index()], value.clone());
+        if self.index_map.get(&old) == Some(&index) {
+            self.index_map.remove(&old);
+            // An equal entry elsewhere takes over deduplication
+            if let Some(position) = self.values.iter().position(|other| *other == old) {
+                self.index_map.insert(old.clone(), ConstIndex(position as u16));
+            }
+        }
+        let first = self.values.iter().position(|other| *other == value).unwrap_or(index.index());
+        self.index_map.insert(value, ConstIndex(first as u16));
+        self.rebuild_string_cache();
+        Ok(old)
+    }
+    
+    fn rebuild_string_cache(&mut self) {
+        self.string_cache = self.index_map.iter()
+            .filter_map(|(value, &index)| match value {
+                ConstantValue::String(s) => Some((s.clone(), index)),
+                _ => None,
+            })
+            .collect();
+    }
+    
     /// Get memory usage statistics
     pub fn memory_stats(&self) -> ConstantPoolStats {
         let values_size = std::mem::size_of_val(&self.values) +
-            self.values.iter().map(|v| match v {
-                ConstantValue::String(s) | ConstantValue::PropertyName(s) | ConstantValue::Regex(s) => s.capacity(),
-                _ => 0,
-            }).sum::<usize>();
+            self.values.iter().map(heap_size).sum::<usize>();
         
         let index_map_size = self.index_map.capacity() * 
             (std::mem::size_of::<ConstantValue>() + std::mem::size_of::<ConstIndex>());
@@ -307,24 +554,46 @@ impl fmt::Display for ConstantPoolStats {
 impl fmt::Display for ConstantPool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "Constants [{}]:", self.len())?;
-        for (i, value) in self.iter() {
-            writeln!(f, "  #{}: {}", i, value)?;
+        write!(f, "{}", self.entries(|_, value| value.to_string()))
+    }
+}
+
+impl ConstantPool {
+    /// The entries one per line as `  #index: value`, each value shown by
+    /// `show`; the disassembler uses it to show property names by name
+    pub fn entries<F: Fn(ConstIndex, &ConstantValue) -> String>(&self, show: F) -> PoolEntries<'_, F> {
+        PoolEntries { pool: self, show }
+    }
+}
+
+/// A pool's entries, as written by [`ConstantPool::entries`]
+pub struct PoolEntries<'a, F> {
+    pool: &'a ConstantPool,
+    show: F,
+}
+
+impl<F: Fn(ConstIndex, &ConstantValue) -> String> fmt::Display for PoolEntries<'_, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, value) in self.pool.iter() {
+            writeln!(f, "  #{}: {}", index, (self.show)(index, value))?;
         }
         Ok(())
     }
 }
 
-/// Helper function to escape string for display
-fn escape_string(s: &str) -> String {
-    s.chars()
-        .map(|c| match c {
-            '\n' => "\\n".to_string(),
-            '\r' => "\\r".to_string(),
-            '\t' => "\\t".to_string(),
-            '\\' => "\\\\".to_string(),
-            '"' => "\\\"".to_string(),
-            c if c.is_control() => format!("\\u{:04x}", c as u32),
-            c => c.to_string(),
-        })
-        .collect()
-}
\ No newline at end of file
+/// Bytes a constant owns on the heap, nested templates included
+fn heap_size(value: &ConstantValue) -> usize {
+    match value {
+        ConstantValue::String(s) => s.capacity(),
+        ConstantValue::Regex { pattern, flags } => pattern.capacity() + flags.capacity(),
+        ConstantValue::ObjectTemplate(properties) => {
+            properties.capacity() * std::mem::size_of::<(String, ConstantValue)>() +
+                properties.iter().map(|(key, value)| key.capacity() + heap_size(value)).sum::<usize>()
+        }
+        ConstantValue::ArrayTemplate(elements) => {
+            elements.capacity() * std::mem::size_of::<Option<ConstantValue>>() +
+                elements.iter().flatten().map(heap_size).sum::<usize>()
+        }
+        _ => 0,
+    }
+}
@@ -0,0 +1,440 @@
+//! Structural diffs between two compiled programs
+//!
+//! [`diff`] lines up the instructions of two functions with a longest
+//! common subsequence over their opcodes and operands, compares the
+//! constant pools entry by entry and the header fields one by one, and
+//! does the same for nested functions paired by `CreateClosure` index.
+//! The result prints like a unified diff, and its edits can be inspected
+//! by tests that check an optimization changes only what it should.
+//!
+//! Instructions are equal when their opcodes and operands are, so a jump
+//! whose relative offset shifts because code between it and its target
+//! went away counts as changed.
+
+use std::fmt;
+use super::disassembler::Disassembler;
+use super::dump::TEXT_OPTIONS;
+use super::function::BytecodeFunction;
+use super::instruction::Bytecode;
+
+/// Unchanged instructions shown around each change
+const CONTEXT_LINES: usize = 2;
+
+/// The differences between two functions and the functions nested in them
+#[derive(Debug, Clone, PartialEq)]
+pub struct BytecodeDiff {
+    /// Where the function sits, as `--dump-bytecode` names it (`main/0/2`)
+    pub path: String,
+    /// Whether the function is in the old program, the new one or both
+    pub presence: Presence,
+    pub fields: Vec<FieldChange>,
+    pub constants: Vec<ConstantChange>,
+    /// Every instruction of both sides, in order
+    pub instructions: Vec<InstructionEdit>,
+    /// Nested functions, in `CreateClosure` index order
+    pub functions: Vec<BytecodeDiff>,
+}
+
+/// Which sides of a diff a function is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Both,
+    OldOnly,
+    NewOnly,
+}
+
+/// A header field that differs, such as `locals`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// A constant pool entry that differs, was added or was removed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConstantChange {
+    pub index: usize,
+    /// The entry as the disassembler shows it, if the old pool has one
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// What happened to one instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    Same,
+    Removed,
+    Added,
+}
+
+/// One instruction of either side, and where it is on each
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstructionEdit {
+    pub kind: EditKind,
+    pub old_offset: Option<usize>,
+    pub new_offset: Option<usize>,
+    pub op: Bytecode,
+    /// The instruction as the text dump shows it
+    pub text: String,
+}
+
+/// Compare `old` with `new`, nested functions included
+pub fn diff(old: &BytecodeFunction, new: &BytecodeFunction) -> BytecodeDiff {
+    diff_functions(Some(old), Some(new), "main".to_string())
+}
+
+fn diff_functions(old: Option<&BytecodeFunction>, new: Option<&BytecodeFunction>, path: String) -> BytecodeDiff {
+    let presence = match (old, new) {
+        (Some(_), Some(_)) => Presence::Both,
+        (Some(_), None) => Presence::OldOnly,
+        _ => Presence::NewOnly,
+    };
+    let fields = match (old, new) {
+        (Some(old), Some(new)) => diff_fields(old, new),
+        _ => Vec::new(),
+    };
+    let nested = |function: Option<&BytecodeFunction>| function.map_or(0, |function| function.functions.len());
+    let functions = (0..nested(old).max(nested(new)))
+        .map(|index| diff_functions(
+            old.and_then(|old| old.functions.get(index)).map(|function| &**function),
+            new.and_then(|new| new.functions.get(index)).map(|function| &**function),
+            format!("{}/{}", path, index),
+        ))
+        .collect();
+    BytecodeDiff {
+        path,
+        presence,
+        fields,
+        constants: diff_constants(old, new),
+        instructions: diff_instructions(old, new),
+        functions,
+    }
+}
+
+fn diff_fields(old: &BytecodeFunction, new: &BytecodeFunction) -> Vec<FieldChange> {
+    let fields = [
+        ("signature", old.signature(), new.signature()),
+        ("arity", old.arity.to_string(), new.arity.to_string()),
+        ("locals", old.locals_count.to_string(), new.locals_count.to_string()),
+        ("max stack", old.max_stack_size.to_string(), new.max_stack_size.to_string()),
+        ("strict", old.is_strict.to_string(), new.is_strict.to_string()),
+    ];
+    fields.into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldChange { field, old, new })
+        .collect()
+}
+
+fn diff_constants(old: Option<&BytecodeFunction>, new: Option<&BytecodeFunction>) -> Vec<ConstantChange> {
+    let entries = |function: Option<&BytecodeFunction>| -> Vec<String> {
+        function.map_or_else(Vec::new, |function| {
+            function.constants.iter()
+                .filter_map(|(index, _)| function.display_constant(index))
+                .collect()
+        })
+    };
+    let (old, new) = (entries(old), entries(new));
+    (0..old.len().max(new.len()))
+        .map(|index| ConstantChange { index, old: old.get(index).cloned(), new: new.get(index).cloned() })
+        .filter(|change| change.old != change.new)
+        .collect()
+}
+
+fn diff_instructions(old: Option<&BytecodeFunction>, new: Option<&BytecodeFunction>) -> Vec<InstructionEdit> {
+    let disassembler = Disassembler::with_options(TEXT_OPTIONS);
+    let old_code = old.map_or(&[][..], |function| &function.bytecode[..]);
+    let new_code = new.map_or(&[][..], |function| &function.bytecode[..]);
+    let edit = |kind, old_offset: Option<usize>, new_offset: Option<usize>| {
+        let (function, offset) = match (old_offset, new_offset) {
+            (_, Some(offset)) => (new, offset),
+            (Some(offset), None) => (old, offset),
+            (None, None) => unreachable!("an instruction is on at least one side"),
+        };
+        let function = function.expect("offsets only come from functions that exist");
+        let op = function.bytecode[offset].clone();
+        let text = disassembler.format_instruction_with_offset(&op, offset, function);
+        InstructionEdit { kind, old_offset, new_offset, op, text }
+    };
+    
+    let mut edits = Vec::with_capacity(old_code.len().max(new_code.len()));
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_end, new_end) in common_subsequence(old_code, new_code).into_iter().chain([(old_code.len(), new_code.len())]) {
+        edits.extend((old_index..old_end).map(|offset| edit(EditKind::Removed, Some(offset), None)));
+        edits.extend((new_index..new_end).map(|offset| edit(EditKind::Added, None, Some(offset))));
+        if old_end < old_code.len() {
+            edits.push(edit(EditKind::Same, Some(old_end), Some(new_end)));
+        }
+        (old_index, new_index) = (old_end + 1, new_end + 1);
+    }
+    edits
+}
+
+/// Offset pairs of a longest common subsequence of `old` and `new`
+///
+/// A plain dynamic program, quadratic in the length of the stretch
+/// between the common prefix and suffix; compiled functions are short.
+fn common_subsequence(old: &[Bytecode], new: &[Bytecode]) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(old, new)| old == new).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let (middle_old, middle_new) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    
+    // lengths[i][j]: the longest common subsequence of middle_old[i..]
+    // and middle_new[j..]
+    let width = middle_new.len() + 1;
+    let mut lengths = vec![0u32; (middle_old.len() + 1) * width];
+    for i in (0..middle_old.len()).rev() {
+        for j in (0..middle_new.len()).rev() {
+            lengths[i * width + j] = if middle_old[i] == middle_new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+            };
+        }
+    }
+    
+    let mut pairs: Vec<_> = (0..prefix).map(|offset| (offset, offset)).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < middle_old.len() && j < middle_new.len() {
+        if middle_old[i] == middle_new[j] {
+            pairs.push((prefix + i, prefix + j));
+            (i, j) = (i + 1, j + 1);
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    pairs
+}
+
+impl BytecodeDiff {
+    /// Whether the two programs compiled to the same code
+    pub fn is_empty(&self) -> bool {
+        self.presence == Presence::Both
+            && self.fields.is_empty()
+            && self.constants.is_empty()
+            && self.changes().next().is_none()
+            && self.functions.iter().all(BytecodeDiff::is_empty)
+    }
+    
+    /// The instructions of this function that were removed or added
+    pub fn changes(&self) -> impl Iterator<Item = &InstructionEdit> {
+        self.instructions.iter().filter(|edit| edit.kind != EditKind::Same)
+    }
+    
+    /// The instructions removed from this function and the functions
+    /// nested in it
+    pub fn removed(&self) -> Vec<&Bytecode> {
+        self.ops_of_kind(EditKind::Removed)
+    }
+    
+    /// The instructions added to this function and the functions nested
+    /// in it
+    pub fn added(&self) -> Vec<&Bytecode> {
+        self.ops_of_kind(EditKind::Added)
+    }
+    
+    fn ops_of_kind(&self, kind: EditKind) -> Vec<&Bytecode> {
+        let mut ops: Vec<_> = self.instructions.iter().filter(|edit| edit.kind == kind).map(|edit| &edit.op).collect();
+        for function in &self.functions {
+            ops.extend(function.ops_of_kind(kind));
+        }
+        ops
+    }
+    
+    /// Ranges of `instructions` to print: every change with up to
+    /// `CONTEXT_LINES` unchanged instructions on each side, merged where
+    /// they touch
+    fn hunks(&self) -> Vec<std::ops::Range<usize>> {
+        let mut hunks: Vec<std::ops::Range<usize>> = Vec::new();
+        for (index, edit) in self.instructions.iter().enumerate() {
+            if edit.kind == EditKind::Same {
+                continue;
+            }
+            let start = index.saturating_sub(CONTEXT_LINES);
+            let end = (index + 1 + CONTEXT_LINES).min(self.instructions.len());
+            match hunks.last_mut() {
+                Some(last) if last.end >= start => last.end = end,
+                _ => hunks.push(start..end),
+            }
+        }
+        hunks
+    }
+}
+
+/// Where a hunk starts on one side and how many instructions it has there,
+/// as `@@ -start,count` shows it
+fn hunk_range(edits: &[InstructionEdit], offset: impl Fn(&InstructionEdit) -> Option<usize>) -> String {
+    let offsets: Vec<_> = edits.iter().filter_map(offset).collect();
+    format!("{},{}", offsets.first().copied().unwrap_or(0), offsets.len())
+}
+
+fn offset_column(offset: Option<usize>) -> String {
+    offset.map_or_else(|| "    ".to_string(), |offset| format!("{:04}", offset))
+}
+
+impl fmt::Display for BytecodeDiff {
+    /// Changes in the style of a unified diff; nothing for functions
+    /// that did not change
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let changed = self.presence != Presence::Both
+            || !self.fields.is_empty()
+            || !self.constants.is_empty()
+            || self.changes().next().is_some();
+        if changed {
+            match self.presence {
+                Presence::Both => writeln!(f, "--- {0}\n+++ {0}", self.path)?,
+                Presence::OldOnly => writeln!(f, "--- {}\n+++ (none)", self.path)?,
+                Presence::NewOnly => writeln!(f, "--- (none)\n+++ {}", self.path)?,
+            }
+            for change in &self.fields {
+                writeln!(f, "-{}: {}\n+{}: {}", change.field, change.old, change.field, change.new)?;
+            }
+            for change in &self.constants {
+                if let Some(old) = &change.old {
+                    writeln!(f, "-#{}: {}", change.index, old)?;
+                }
+                if let Some(new) = &change.new {
+                    writeln!(f, "+#{}: {}", change.index, new)?;
+                }
+            }
+            for hunk in self.hunks() {
+                let edits = &self.instructions[hunk];
+                writeln!(
+                    f,
+                    "@@ -{} +{} @@",
+                    hunk_range(edits, |edit| edit.old_offset),
+                    hunk_range(edits, |edit| edit.new_offset),
+                )?;
+                for edit in edits {
+                    let marker = match edit.kind {
+                        EditKind::Same => ' ',
+                        EditKind::Removed => '-',
+                        EditKind::Added => '+',
+                    };
+                    writeln!(f, "{}{} {}  {}", marker, offset_column(edit.old_offset), offset_column(edit.new_offset), edit.text)?;
+                }
+            }
+        }
+        for function in &self.functions {
+            write!(f, "{}", function)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{ConstIndex, JumpOffset, LocalIndex};
+    
+    fn function(code: &[Bytecode]) -> BytecodeFunction {
+        let mut function = BytecodeFunction::new_main();
+        for op in code {
+            function.add_instruction(op.clone());
+        }
+        function
+    }
+    
+    fn kinds(diff: &BytecodeDiff) -> String {
+        diff.instructions.iter().map(|edit| match edit.kind {
+            EditKind::Same => '=',
+            EditKind::Removed => '-',
+            EditKind::Added => '+',
+        }).collect()
+    }
+    
+    #[test]
+    fn test_identical_functions_have_an_empty_diff() {
+        let code = [Bytecode::LdaSmi(1), Bytecode::Push, Bytecode::Pop, Bytecode::Return];
+        let result = diff(&function(&code), &function(&code));
+        assert!(result.is_empty());
+        assert_eq!(kinds(&result), "====");
+        assert_eq!(result.to_string(), "");
+    }
+    
+    #[test]
+    fn test_deleted_push_pop_pair() {
+        let old = function(&[Bytecode::LdaSmi(1), Bytecode::Push, Bytecode::Pop, Bytecode::StaLocal(LocalIndex(0)), Bytecode::Return]);
+        let new = function(&[Bytecode::LdaSmi(1), Bytecode::StaLocal(LocalIndex(0)), Bytecode::Return]);
+        let result = diff(&old, &new);
+        assert_eq!(kinds(&result), "=--==");
+        assert_eq!(result.removed(), [&Bytecode::Push, &Bytecode::Pop]);
+        assert!(result.added().is_empty());
+        assert_eq!(
+            result.changes().map(|edit| (edit.old_offset, edit.new_offset)).collect::<Vec<_>>(),
+            [(Some(1), None), (Some(2), None)],
+        );
+        assert_eq!(result.to_string(), "\
+--- main
++++ main
+@@ -0,5 +0,3 @@
+ 0000 0000  LdaSmi 1
+-0001       Push
+-0002       Pop
+ 0003 0001  StaLocal 0
+ 0004 0002  Return
+");
+    }
+    
+    #[test]
+    fn test_replaced_instruction_and_changed_constants() {
+        let mut old = function(&[Bytecode::LdaConst(ConstIndex(0)), Bytecode::Return]);
+        old.constants.add_number(2.5);
+        let mut new = function(&[Bytecode::LdaSmi(3), Bytecode::Return]);
+        new.locals_count = 2;
+        
+        let result = diff(&old, &new);
+        assert_eq!(kinds(&result), "-+=");
+        assert_eq!(result.fields, [FieldChange { field: "locals", old: "0".to_string(), new: "2".to_string() }]);
+        assert_eq!(result.constants, [ConstantChange { index: 0, old: Some("2.5".to_string()), new: None }]);
+        let text = result.to_string();
+        assert!(text.contains("-locals: 0\n+locals: 2\n-#0: 2.5\n"), "{}", text);
+        assert!(text.contains("-0000       LdaConst #0 (2.5)\n+     0000  LdaSmi 3\n"), "{}", text);
+    }
+    
+    #[test]
+    fn test_far_apart_changes_get_separate_hunks() {
+        let mut old_code: Vec<_> = (0..12).map(Bytecode::LdaSmi).collect();
+        let mut new_code = old_code.clone();
+        new_code[1] = Bytecode::LdaThis;
+        new_code.remove(10);
+        old_code.push(Bytecode::Return);
+        new_code.push(Bytecode::Return);
+        
+        let text = diff(&function(&old_code), &function(&new_code)).to_string();
+        let hunks: Vec<_> = text.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(hunks, ["@@ -0,4 +0,4 @@", "@@ -8,5 +8,4 @@"], "{}", text);
+    }
+    
+    #[test]
+    fn test_shifted_jump_counts_as_changed() {
+        let old = function(&[Bytecode::JumpIfFalse(JumpOffset(2)), Bytecode::Push, Bytecode::Pop, Bytecode::Return]);
+        let new = function(&[Bytecode::JumpIfFalse(JumpOffset(0)), Bytecode::Return]);
+        assert_eq!(kinds(&diff(&old, &new)), "---+=");
+    }
+    
+    #[test]
+    fn test_nested_functions_are_paired_by_index() {
+        let mut old = function(&[Bytecode::Return]);
+        old.functions.push(function(&[Bytecode::LdaSmi(1), Bytecode::Return]).into());
+        let mut new = function(&[Bytecode::Return]);
+        new.functions.push(function(&[Bytecode::LdaSmi(2), Bytecode::Return]).into());
+        new.functions.push(function(&[Bytecode::Return]).into());
+        
+        let result = diff(&old, &new);
+        assert!(!result.is_empty());
+        assert!(result.changes().next().is_none());
+        assert_eq!(result.functions[0].path, "main/0");
+        assert_eq!(kinds(&result.functions[0]), "-+=");
+        assert_eq!(result.functions[1].presence, Presence::NewOnly);
+        assert_eq!(result.added(), [&Bytecode::LdaSmi(2), &Bytecode::Return]);
+        let text = result.to_string();
+        assert!(text.starts_with("--- main/0\n+++ main/0\n"), "{}", text);
+        assert!(text.contains("--- (none)\n+++ main/1\n@@ -0,0 +0,1 @@\n+     0000  Return\n"), "{}", text);
+    }
+}
@@ -21,6 +21,10 @@ pub struct DisassemblyOptions {
     pub pretty_format: bool,
     /// Show jump targets with labels
     pub show_jump_labels: bool,
+    /// Name the variable each local slot instruction reads or writes
+    pub show_local_names: bool,
+    /// Mark the instructions statements start at with `>`
+    pub show_statement_starts: bool,
 }
 
 impl Default for DisassemblyOptions {
@@ -31,6 +35,8 @@ impl Default for DisassemblyOptions {
             show_analysis: false,
             pretty_format: true,
             show_jump_labels: true,
+            show_local_names: true,
+            show_statement_starts: true,
         }
     }
 }
@@ -60,18 +66,20 @@ impl Disassembler {
         // Function header
         if self.options.pretty_format {
             writeln!(output, "=== {} ===", function.signature()).unwrap();
-            writeln!(output, "Locals: {}, Max stack: {}", 
-                function.locals_count, function.max_stack_size).unwrap();
+            write!(output, "Locals: {}", function.locals_count).unwrap();
+            if !function.immutable_locals.is_empty() {
+                let consts: Vec<_> = function.immutable_locals.iter().map(|index| index.to_string()).collect();
+                write!(output, " (const {})", consts.join(", ")).unwrap();
+            }
+            writeln!(output, ", Max stack: {}", function.max_stack_size).unwrap();
             writeln!(output).unwrap();
         }
         
         // Constant pool (if requested and not empty)
         if self.options.show_constant_values && !function.constants.is_empty() {
             writeln!(output, "Constants:").unwrap();
-            for (index, value) in function.constants.iter() {
-                writeln!(output, "  #{}: {}", index, value).unwrap();
-            }
-            writeln!(output).unwrap();
+            let entries = function.constants.entries(|index, _| function.display_constant(index).unwrap_or_default());
+            writeln!(output, "{}", entries).unwrap();
         }
         
         // Find jump targets for labeling
@@ -111,6 +119,10 @@ impl Disassembler {
         
         // Offset
         write!(line, "  {:4}:", offset).unwrap();
+        if self.options.show_statement_starts {
+            let marker = if function.debug_info.is_statement_start(offset) { '>' } else { ' ' };
+            line.push(marker);
+        }
         
         // Instruction mnemonic and operands  
         let instruction_str = self.format_instruction_with_offset(instruction, offset, function);
@@ -119,6 +131,15 @@ impl Disassembler {
         // Comments and analysis
         let mut comments = Vec::new();
         
+        // Variable the local slot holds here
+        if self.options.show_local_names {
+            if let Bytecode::LdaLocal(slot) | Bytecode::StaLocal(slot) | Bytecode::InitLocal(slot) = instruction {
+                if let Some(name) = function.local_name(*slot, offset) {
+                    comments.push(name.to_string());
+                }
+            }
+        }
+        
         // Line number comment
         if self.options.show_line_numbers {
             if let Some(line_num) = function.debug_info.get_line_number(offset) {
@@ -146,13 +167,14 @@ impl Disassembler {
         line
     }
     
-    /// Format a single instruction with its operands and offset context
-    fn format_instruction_with_offset(&self, instruction: &Bytecode, offset: usize, function: &BytecodeFunction) -> String {
+    /// Format a single instruction with its operands and offset context,
+    /// without the offset and comments `disassemble_instruction` adds
+    pub fn format_instruction_with_offset(&self, instruction: &Bytecode, offset: usize, function: &BytecodeFunction) -> String {
         match instruction {
             // Jump instructions with labels
             Bytecode::Jump(jump_offset) => {
                 if self.options.show_jump_labels {
-                    let target = (offset as i32 + 1 + *jump_offset as i32) as usize;
+                    let target = jump_offset.target(offset).unwrap_or(0);
                     format!("Jump L{} ({})", target, jump_offset)
                 } else {
                     format!("Jump {}", jump_offset)
@@ -161,7 +183,7 @@ impl Disassembler {
             
             Bytecode::JumpIfFalse(jump_offset) => {
                 if self.options.show_jump_labels {
-                    let target = (offset as i32 + 1 + *jump_offset as i32) as usize;
+                    let target = jump_offset.target(offset).unwrap_or(0);
                     format!("JumpIfFalse L{} ({})", target, jump_offset)
                 } else {
                     format!("JumpIfFalse {}", jump_offset)
@@ -170,7 +192,7 @@ impl Disassembler {
             
             Bytecode::JumpIfTrue(jump_offset) => {
                 if self.options.show_jump_labels {
-                    let target = (offset as i32 + 1 + *jump_offset as i32) as usize;
+                    let target = jump_offset.target(offset).unwrap_or(0);
                     format!("JumpIfTrue L{} ({})", target, jump_offset)
                 } else {
                     format!("JumpIfTrue {}", jump_offset)
@@ -179,13 +201,22 @@ impl Disassembler {
             
             Bytecode::JumpIfNullish(jump_offset) => {
                 if self.options.show_jump_labels {
-                    let target = (offset as i32 + 1 + *jump_offset as i32) as usize;
+                    let target = jump_offset.target(offset).unwrap_or(0);
                     format!("JumpIfNullish L{} ({})", target, jump_offset)
                 } else {
                     format!("JumpIfNullish {}", jump_offset)
                 }
             }
             
+            Bytecode::IteratorNext(jump_offset) => {
+                if self.options.show_jump_labels {
+                    let target = jump_offset.target(offset).unwrap_or(0);
+                    format!("IteratorNext L{} ({})", target, jump_offset)
+                } else {
+                    format!("IteratorNext {}", jump_offset)
+                }
+            }
+            
             // For all other instructions, use the original method
             _ => self.format_instruction(instruction, function),
         }
@@ -197,7 +228,7 @@ impl Disassembler {
             // Instructions with constant references
             Bytecode::LdaConst(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
+                    if let Some(value) = function.display_constant(*idx) {
                         format!("LdaConst #{} ({})", idx, value)
                     } else {
                         format!("LdaConst #{} (invalid)", idx)
@@ -209,7 +240,7 @@ impl Disassembler {
             
             Bytecode::LdaGlobal(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
+                    if let Some(value) = function.display_constant(*idx) {
                         format!("LdaGlobal #{} ({})", idx, value)
                     } else {
                         format!("LdaGlobal #{} (invalid)", idx)
@@ -221,7 +252,7 @@ impl Disassembler {
             
             Bytecode::StaGlobal(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
+                    if let Some(value) = function.display_constant(*idx) {
                         format!("StaGlobal #{} ({})", idx, value)
                     } else {
                         format!("StaGlobal #{} (invalid)", idx)
@@ -231,9 +262,33 @@ impl Disassembler {
                 }
             }
             
+            Bytecode::InitGlobal(idx) => {
+                if self.options.show_constant_values {
+                    if let Some(value) = function.display_constant(*idx) {
+                        format!("InitGlobal #{} ({})", idx, value)
+                    } else {
+                        format!("InitGlobal #{} (invalid)", idx)
+                    }
+                } else {
+                    format!("InitGlobal #{}", idx)
+                }
+            }
+            
+            Bytecode::LdaGlobalInsideTypeof(idx) => {
+                if self.options.show_constant_values {
+                    if let Some(value) = function.display_constant(*idx) {
+                        format!("LdaGlobalInsideTypeof #{} ({})", idx, value)
+                    } else {
+                        format!("LdaGlobalInsideTypeof #{} (invalid)", idx)
+                    }
+                } else {
+                    format!("LdaGlobalInsideTypeof #{}", idx)
+                }
+            }
+            
             Bytecode::LdaNamed(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
+                    if let Some(value) = function.display_constant(*idx) {
                         format!("LdaNamed #{} ({})", idx, value)
                     } else {
                         format!("LdaNamed #{} (invalid)", idx)
@@ -245,7 +300,7 @@ impl Disassembler {
             
             Bytecode::StaNamed(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
+                    if let Some(value) = function.display_constant(*idx) {
                         format!("StaNamed #{} ({})", idx, value)
                     } else {
                         format!("StaNamed #{} (invalid)", idx)
@@ -255,27 +310,36 @@ impl Disassembler {
                 }
             }
             
-            Bytecode::CreateArray(idx) => {
+            Bytecode::DefineNamedProperty(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
-                        format!("CreateArray #{} ({})", idx, value)
+                    if let Some(value) = function.display_constant(*idx) {
+                        format!("DefineNamedProperty #{} ({})", idx, value)
                     } else {
-                        format!("CreateArray #{} (invalid)", idx)
+                        format!("DefineNamedProperty #{} (invalid)", idx)
                     }
                 } else {
-                    format!("CreateArray #{}", idx)
+                    format!("DefineNamedProperty #{}", idx)
                 }
             }
             
-            Bytecode::CreateClosure(idx) => {
+            Bytecode::CreateFromTemplate(idx) => {
                 if self.options.show_constant_values {
-                    if let Some(value) = function.constants.get(*idx) {
-                        format!("CreateClosure #{} ({})", idx, value)
+                    if let Some(value) = function.display_constant(*idx) {
+                        format!("CreateFromTemplate #{} ({})", idx, value)
                     } else {
-                        format!("CreateClosure #{} (invalid)", idx)
+                        format!("CreateFromTemplate #{} (invalid)", idx)
                     }
                 } else {
-                    format!("CreateClosure #{}", idx)
+                    format!("CreateFromTemplate #{}", idx)
+                }
+            }
+            
+            Bytecode::CreateClosure(idx) => {
+                match function.functions.get(idx.index()) {
+                    Some(nested) if self.options.show_constant_values => {
+                        format!("CreateClosure #{} ({})", idx, nested.signature())
+                    }
+                    _ => format!("CreateClosure #{}", idx),
                 }
             }
             
@@ -293,10 +357,10 @@ impl Disassembler {
                 Bytecode::Jump(jump_offset) |
                 Bytecode::JumpIfFalse(jump_offset) |
                 Bytecode::JumpIfTrue(jump_offset) |
-                Bytecode::JumpIfNullish(jump_offset) => {
+                Bytecode::JumpIfNullish(jump_offset) |
+                Bytecode::IteratorNext(jump_offset) => {
                     // Jump target is relative to the next instruction
-                    let target = (offset as i32 + 1 + *jump_offset as i32) as usize;
-                    if target <= function.bytecode.len() {
+                    if let Some(target) = jump_offset.target(offset).filter(|&target| target <= function.bytecode.len()) {
                         targets.push(target);
                     }
                 }
@@ -337,6 +401,8 @@ impl Disassembler {
             show_analysis: false,
             pretty_format: false,
             show_jump_labels: false,
+            show_local_names: false,
+            show_statement_starts: false,
         };
         let disassembler = Disassembler::with_options(options);
         disassembler.disassemble_function(function)
@@ -350,6 +416,8 @@ impl Disassembler {
             show_analysis: true,
             pretty_format: true,
             show_jump_labels: true,
+            show_local_names: true,
+            show_statement_starts: true,
         };
         let disassembler = Disassembler::with_options(options);
         disassembler.disassemble_function(function)
@@ -359,7 +427,7 @@ impl Disassembler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::bytecode::{BytecodeFunction, Bytecode};
+    use crate::bytecode::{BytecodeFunction, Bytecode, ConstIndex, JumpOffset};
     
     #[test]
     fn test_simple_disassembly() {
@@ -389,8 +457,8 @@ mod tests {
     fn test_jump_disassembly() {
         let mut function = BytecodeFunction::new_main();
         
-        function.add_instruction(Bytecode::LdaConst(0));
-        function.add_instruction(Bytecode::JumpIfFalse(1)); // Jump forward 1 instruction to instruction 3
+        function.add_instruction(Bytecode::LdaConst(ConstIndex(0)));
+        function.add_instruction(Bytecode::JumpIfFalse(JumpOffset(1))); // Jump forward 1 instruction to instruction 3
         function.add_instruction(Bytecode::Return);
         function.add_instruction(Bytecode::ReturnUndefined); // Jump target should be here (index 3)
         
@@ -0,0 +1,308 @@
+//! Stable bytecode dump formats
+//!
+//! The disassembler's output is meant for people and changes whenever it
+//! can be made clearer. Tools that read compiled code use one of these
+//! two formats instead, each of which changes only with its version:
+//!
+//! - **Text** ([`TEXT_FORMAT_VERSION`]): the lines `format v8-rs bytecode
+//!   text` and `version <n>`, a `name <id> <json>` line per entry of the
+//!   program's property-name table, then every function depth-first from
+//!   `<main>`. A function starts with `function <path>`, where the path
+//!   lists `CreateClosure` indices (`main/0/2`), followed by one
+//!   `<field> <value>` line per property, a `constant` line per constant
+//!   and a `code` line per instruction. Constants are written as in the
+//!   JSON format and instructions as `Bytecode` displays them, so the
+//!   disassembler's layout never leaks in.
+//! - **JSON** ([`DUMP_FORMAT_VERSION`]): a [`BytecodeDump`] written by
+//!   serde_json, with instructions and constants in their typed form and
+//!   the property-name table written once for the whole program.
+//!   [`BytecodeDump::to_function`] reads one back into a function that
+//!   runs, after checking it with the [verifier](super::verifier).
+
+use std::fmt::Write;
+use std::io;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result, Span};
+use super::constant_pool::ConstantValue;
+use super::disassembler::{Disassembler, DisassemblyOptions};
+use super::function::BytecodeFunction;
+use super::instruction::{Bytecode, LocalIndex};
+use super::string_table::StringTable;
+use super::verifier;
+
+/// Version of the JSON format
+pub const DUMP_FORMAT_VERSION: u32 = 3;
+
+/// The `format` field of every JSON dump
+pub const DUMP_FORMAT_NAME: &str = "v8-rs bytecode";
+
+/// Version of the text format
+pub const TEXT_FORMAT_VERSION: u32 = 2;
+
+/// The `format` line of every text dump
+pub const TEXT_FORMAT_NAME: &str = "v8-rs bytecode text";
+
+/// Disassembly options for the instruction text of JSON dumps and of
+/// bytecode diffs, fixed whatever the defaults
+pub(super) const TEXT_OPTIONS: DisassemblyOptions = DisassemblyOptions {
+    show_line_numbers: true,
+    show_constant_values: true,
+    show_analysis: false,
+    pretty_format: true,
+    show_jump_labels: true,
+    show_local_names: false,
+    show_statement_starts: false,
+};
+
+/// Write `function` and the functions nested in it in the text format
+pub fn dump_text(function: &BytecodeFunction) -> String {
+    let mut output = format!("format {}\nversion {}\n", TEXT_FORMAT_NAME, TEXT_FORMAT_VERSION);
+    for (id, name) in function.names.iter() {
+        let json = serde_json::to_string(name).expect("names always serialize");
+        writeln!(output, "name {} {}", id, json).unwrap();
+    }
+    let mut pending = vec![("main".to_string(), function)];
+    while let Some((path, function)) = pending.pop() {
+        output.push('\n');
+        write_text_function(&mut output, &path, function);
+        // Reversed so that the first nested function comes out first
+        for (index, nested) in function.functions.iter().enumerate().rev() {
+            pending.push((format!("{}/{}", path, index), nested));
+        }
+    }
+    output
+}
+
+/// Write one function of a text dump, without the functions nested in it
+fn write_text_function(output: &mut String, path: &str, function: &BytecodeFunction) {
+    let join = |items: Vec<String>| items.join(" ");
+    let flags = [
+        ("generator", function.is_generator),
+        ("async", function.is_async),
+        ("arrow", function.is_arrow),
+        ("strict", function.is_strict),
+        ("class-constructor", function.is_class_constructor),
+    ];
+    
+    writeln!(output, "function {}", path).unwrap();
+    writeln!(output, "signature {}", function.signature()).unwrap();
+    writeln!(output, "arity {}", function.arity).unwrap();
+    writeln!(output, "locals {}", function.locals_count).unwrap();
+    if !function.immutable_locals.is_empty() {
+        writeln!(output, "const-locals {}", join(function.immutable_locals.iter().map(|index| index.to_string()).collect())).unwrap();
+    }
+    if !function.arena_sites.is_empty() {
+        writeln!(output, "arena-sites {}", join(function.arena_sites.iter().map(|site| site.to_string()).collect())).unwrap();
+    }
+    writeln!(output, "max-stack {}", function.max_stack_size).unwrap();
+    let set: Vec<String> = flags.iter().filter(|(_, set)| *set).map(|(flag, _)| flag.to_string()).collect();
+    if !set.is_empty() {
+        writeln!(output, "flags {}", join(set)).unwrap();
+    }
+    for (index, constant) in function.constants.iter() {
+        let json = serde_json::to_string(constant).expect("constants always serialize");
+        writeln!(output, "constant {} {}", index, json).unwrap();
+    }
+    for (offset, op) in function.bytecode.iter().enumerate() {
+        match function.debug_info.get_line_number(offset) {
+            Some(line) => writeln!(output, "code {} {} ; line {}", offset, op, line).unwrap(),
+            None => writeln!(output, "code {} {}", offset, op).unwrap(),
+        }
+    }
+}
+
+/// A compiled program in the JSON format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BytecodeDump {
+    /// Always [`DUMP_FORMAT_NAME`]
+    pub format: String,
+    /// Always [`DUMP_FORMAT_VERSION`] when written by this version
+    pub version: u32,
+    /// The property names that `PropertyName` constants index, shared by
+    /// every function
+    pub names: Vec<String>,
+    /// Top-level code, with every other function nested inside
+    pub main: FunctionDump,
+}
+
+/// One function of a [`BytecodeDump`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FunctionDump {
+    pub name: String,
+    /// The function as the disassembler heads it, such as `function f(arg0)`
+    pub signature: String,
+    pub arity: u8,
+    pub locals_count: u16,
+    /// Locals declared with `const`; absent from dumps of code without any
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub immutable_locals: Vec<u16>,
+    /// Offsets of allocations the frame arena may serve
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub arena_sites: Vec<usize>,
+    pub max_stack_size: usize,
+    pub is_generator: bool,
+    pub is_async: bool,
+    pub is_arrow: bool,
+    pub is_strict: bool,
+    pub is_class_constructor: bool,
+    /// The constant pool, in index order
+    pub constants: Vec<ConstantValue>,
+    pub instructions: Vec<InstructionDump>,
+    /// Nested functions, in `CreateClosure` index order
+    pub functions: Vec<FunctionDump>,
+}
+
+/// One instruction of a [`FunctionDump`], with its debug information
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InstructionDump {
+    pub op: Bytecode,
+    /// The instruction as the text format shows it, such as
+    /// `LdaNamed #0 (.length)`; for reading only
+    pub text: String,
+    pub line: Option<u32>,
+    pub span: Option<Span>,
+}
+
+impl BytecodeDump {
+    /// Describe `function` and the functions nested in it
+    pub fn new(function: &BytecodeFunction) -> Self {
+        Self {
+            format: DUMP_FORMAT_NAME.to_string(),
+            version: DUMP_FORMAT_VERSION,
+            names: function.names.iter().map(|(_, name)| name.to_string()).collect(),
+            main: FunctionDump::new(function, &Disassembler::with_options(TEXT_OPTIONS)),
+        }
+    }
+    
+    /// Write the dump as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("bytecode dumps always serialize")
+    }
+    
+    /// Read a dump written by [`BytecodeDump::to_json`]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let dump: Self = serde_json::from_str(json)
+            .map_err(|error| invalid_dump(error.to_string()))?;
+        if dump.format != DUMP_FORMAT_NAME || dump.version != DUMP_FORMAT_VERSION {
+            return Err(invalid_dump(format!(
+                "expected {} v{}, found {} v{}",
+                DUMP_FORMAT_NAME, DUMP_FORMAT_VERSION, dump.format, dump.version
+            )));
+        }
+        Ok(dump)
+    }
+    
+    /// Rebuild the program the dump describes
+    ///
+    /// Everything the VM needs comes back; the source text and callee
+    /// names used in error messages are not part of the dump. Code that
+    /// fails [`verifier::verify`] is an error.
+    pub fn to_function(&self) -> Result<BytecodeFunction> {
+        let mut names = StringTable::new();
+        for (index, name) in self.names.iter().enumerate() {
+            if names.intern(name).index() != index {
+                return Err(invalid_dump(format!("property name #{} repeats an earlier one", index)));
+            }
+        }
+        let mut function = self.main.to_function(names.len())?;
+        function.share_names(&Arc::new(names));
+        verifier::verify(&function).map_err(|error| error.context("Invalid bytecode dump"))?;
+        Ok(function)
+    }
+}
+
+impl FunctionDump {
+    fn new(function: &BytecodeFunction, disassembler: &Disassembler) -> Self {
+        let instructions = function.bytecode.iter().enumerate().map(|(offset, op)| InstructionDump {
+            op: op.clone(),
+            text: disassembler.format_instruction_with_offset(op, offset, function),
+            line: function.debug_info.get_line_number(offset),
+            span: function.debug_info.get_span(offset).copied(),
+        });
+        Self {
+            name: function.name.clone(),
+            signature: function.signature(),
+            arity: function.arity,
+            locals_count: function.locals_count,
+            immutable_locals: function.immutable_locals.iter().map(|index| index.0).collect(),
+            arena_sites: function.arena_sites.clone(),
+            max_stack_size: function.max_stack_size,
+            is_generator: function.is_generator,
+            is_async: function.is_async,
+            is_arrow: function.is_arrow,
+            is_strict: function.is_strict,
+            is_class_constructor: function.is_class_constructor,
+            constants: function.constants.iter().map(|(_, value)| value.clone()).collect(),
+            instructions: instructions.collect(),
+            functions: function.functions.iter().map(|nested| Self::new(nested, disassembler)).collect(),
+        }
+    }
+    
+    /// Rebuild the function, whose property-name constants index a table
+    /// of `names` entries
+    fn to_function(&self, names: usize) -> Result<BytecodeFunction> {
+        let mut function = BytecodeFunction::new(self.name.clone(), self.arity, self.locals_count, self.max_stack_size);
+        function.immutable_locals = self.immutable_locals.iter().map(|&index| LocalIndex(index)).collect();
+        function.arena_sites = self.arena_sites.clone();
+        function.is_generator = self.is_generator;
+        function.is_async = self.is_async;
+        function.is_arrow = self.is_arrow;
+        function.is_strict = self.is_strict;
+        function.is_class_constructor = self.is_class_constructor;
+        for (index, constant) in self.constants.iter().enumerate() {
+            if let ConstantValue::PropertyName(id) = constant {
+                if id.index() >= names {
+                    return Err(invalid_dump(format!("constant #{} of {} names property #{} of {}", index, self.signature, id, names)));
+                }
+            }
+            // The pool merges equal constants, which would renumber the rest
+            if function.constants.add_constant(constant.clone()).index() != index {
+                return Err(invalid_dump(format!("constant #{} of {} repeats an earlier one", index, self.signature)));
+            }
+        }
+        for instruction in &self.instructions {
+            match instruction.span {
+                Some(span) => function.add_instruction_with_span(instruction.op.clone(), span),
+                None => function.add_instruction_on_line(instruction.op.clone(), instruction.line),
+            }
+        }
+        for nested in &self.functions {
+            function.functions.push(Arc::new(nested.to_function(names)?));
+        }
+        Ok(function)
+    }
+}
+
+fn invalid_dump(message: String) -> Error {
+    Error::from(io::Error::new(io::ErrorKind::InvalidData, message)).context("Invalid bytecode dump")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::ConstIndex;
+    
+    #[test]
+    fn test_non_finite_numbers_round_trip() {
+        let mut function = BytecodeFunction::new_main();
+        for n in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -0.0, 1.5] {
+            function.constants.add_number(n);
+        }
+        function.add_instruction(Bytecode::LdaConst(ConstIndex(1)));
+        function.add_instruction(Bytecode::Return);
+        
+        let json = BytecodeDump::new(&function).to_json();
+        assert!(json.contains("\"Number\": \"-Infinity\""), "{}", json);
+        let restored = BytecodeDump::from_json(&json).unwrap().to_function().unwrap();
+        assert_eq!(restored.constants, function.constants);
+        assert_eq!(restored.bytecode, function.bytecode);
+    }
+    
+    #[test]
+    fn test_other_versions_are_rejected() {
+        let json = BytecodeDump::new(&BytecodeFunction::new_main()).to_json().replace("\"version\": 3", "\"version\": 2");
+        let error = BytecodeDump::from_json(&json).unwrap_err();
+        assert!(format!("{:?}", error).contains("expected v8-rs bytecode v3, found v8-rs bytecode v2"), "{:?}", error);
+    }
+}
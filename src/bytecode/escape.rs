@@ -0,0 +1,271 @@
+//! Escape analysis for object and array literals
+//!
+//! A variable initialized with a literal is *non-escaping* when the
+//! function only ever reads and writes the literal's properties through
+//! it: `opts.x`, `opts[key] = value`, `opts.count++`. Anything else that
+//! could hand the object out — using the variable as a value, calling a
+//! method on it (which passes it as `this`), reassigning it, or naming
+//! it inside a nested function — rules it out.
+//!
+//! The analysis is only a hint. The VM still checks at run time that
+//! nothing else refers to an object before reusing it (see
+//! `vm::arena`), so a miss here costs speed, never correctness.
+
+use std::collections::{HashMap, HashSet};
+use crate::error::Span;
+use crate::parser::ast::{ClassBody, Expr, PropertyDefinition, PropertyKey, Stmt};
+
+/// Names of the variables in `body` whose literal never escapes the
+/// function
+///
+/// Code nested more than `max_depth` levels deep is not analysed, and
+/// then no name qualifies; the compiler rejects such code anyway.
+pub(crate) fn non_escaping_locals(params: &[(String, Span)], body: &[Stmt], max_depth: usize) -> HashSet<String> {
+    let mut analysis = Analysis { max_depth, ..Analysis::default() };
+    for statement in body {
+        analysis.statement(statement, false);
+    }
+    if analysis.too_deep {
+        return HashSet::new();
+    }
+    analysis.declarations.into_iter()
+        .filter(|&(ref name, (count, literal))| count == 1 && literal && !params.iter().any(|(param, _)| param == name))
+        .map(|(name, _)| name)
+        .filter(|name| !analysis.escaped.contains(name))
+        .collect()
+}
+
+/// Declarations and escaping uses seen so far
+#[derive(Default)]
+struct Analysis {
+    /// Per name: how many times this function declares it, and whether
+    /// the last declaration initializes it with a literal
+    declarations: HashMap<String, (usize, bool)>,
+    /// Names used in a way that can let their value out
+    escaped: HashSet<String>,
+    /// Statements and expressions being walked inside one another
+    depth: usize,
+    max_depth: usize,
+    /// Set once `depth` passed `max_depth`
+    too_deep: bool,
+}
+
+impl Analysis {
+    /// Walk a statement; `nested` is whether it is inside a nested function
+    fn statement(&mut self, statement: &Stmt, nested: bool) {
+        if self.depth >= self.max_depth {
+            self.too_deep = true;
+            return;
+        }
+        self.depth += 1;
+        self.statement_at_depth(statement, nested);
+        self.depth -= 1;
+    }
+    
+    fn statement_at_depth(&mut self, statement: &Stmt, nested: bool) {
+        match statement {
+            Stmt::Expression(expr) => self.expr(expr, nested),
+            Stmt::VarDecl { name, init, .. } => {
+                if nested {
+                    self.escaped.insert(name.clone());
+                } else {
+                    let literal = matches!(init, Some(Expr::Object { .. } | Expr::Array { .. }));
+                    let entry = self.declarations.entry(name.clone()).or_default();
+                    *entry = (entry.0 + 1, literal);
+                }
+                if let Some(init) = init {
+                    self.expr(init, nested);
+                }
+            }
+            Stmt::FunctionDecl { name, body, .. } => {
+                self.escaped.insert(name.clone());
+                self.body(body);
+            }
+            Stmt::ClassDecl { name, body, .. } => {
+                self.escaped.insert(name.clone());
+                self.class(body);
+            }
+            Stmt::If { test, then_stmt, else_stmt, .. } => {
+                self.expr(test, nested);
+                self.statement(then_stmt, nested);
+                if let Some(else_stmt) = else_stmt {
+                    self.statement(else_stmt, nested);
+                }
+            }
+            Stmt::While { test, body, .. } => {
+                self.expr(test, nested);
+                self.statement(body, nested);
+            }
+            Stmt::For { init, test, update, body, .. } => {
+                if let Some(init) = init {
+                    self.statement(init, nested);
+                }
+                for expr in [test, update].into_iter().flatten() {
+                    self.expr(expr, nested);
+                }
+                self.statement(body, nested);
+            }
+            Stmt::ForOf { left, right, body, .. } => {
+                self.statement(left, nested);
+                self.expr(right, nested);
+                self.statement(body, nested);
+            }
+            Stmt::Block { statements, .. } => {
+                for statement in statements {
+                    self.statement(statement, nested);
+                }
+            }
+            Stmt::Return { value, .. } => {
+                if let Some(value) = value {
+                    self.expr(value, nested);
+                }
+            }
+            Stmt::Break { .. } | Stmt::Continue { .. } | Stmt::Debugger { .. } | Stmt::Empty { .. } => {}
+        }
+    }
+    
+    /// Walk the body of a nested function, where every name escapes
+    fn body(&mut self, body: &[Stmt]) {
+        for statement in body {
+            self.statement(statement, true);
+        }
+    }
+    
+    fn class(&mut self, body: &ClassBody) {
+        for method in body.constructor.iter().chain(&body.methods) {
+            self.body(&method.body);
+        }
+    }
+    
+    /// Walk an expression whose value may be used for anything
+    fn expr(&mut self, expr: &Expr, nested: bool) {
+        if self.depth >= self.max_depth {
+            self.too_deep = true;
+            return;
+        }
+        self.depth += 1;
+        self.expr_at_depth(expr, nested);
+        self.depth -= 1;
+    }
+    
+    fn expr_at_depth(&mut self, expr: &Expr, nested: bool) {
+        match expr {
+            Expr::Literal(_) | Expr::This { .. } => {}
+            Expr::Identifier { name, .. } => {
+                self.escaped.insert(name.clone());
+            }
+            // Reading or writing a property keeps the object where it is
+            Expr::Member { object, property, computed, .. } => {
+                if nested || !matches!(object.as_ref(), Expr::Identifier { .. }) {
+                    self.expr(object, nested);
+                }
+                if *computed {
+                    self.expr(property, nested);
+                }
+            }
+            // A method call passes the object as `this`
+            Expr::Call { callee, args, .. } => {
+                match callee.as_ref() {
+                    Expr::Member { object, property, computed, .. } => {
+                        self.expr(object, nested);
+                        if *computed {
+                            self.expr(property, nested);
+                        }
+                    }
+                    callee => self.expr(callee, nested),
+                }
+                for arg in args {
+                    self.expr(arg, nested);
+                }
+            }
+            Expr::New { callee, args, .. } => {
+                self.expr(callee, nested);
+                for arg in args {
+                    self.expr(arg, nested);
+                }
+            }
+            // Operator chains nest on the left; walk them without recursing,
+            // as the compiler does
+            Expr::Binary { .. } => {
+                let mut operand = expr;
+                while let Expr::Binary { left, right, .. } = operand {
+                    self.expr(right, nested);
+                    operand = left;
+                }
+                self.expr(operand, nested);
+            }
+            Expr::Assignment { left, right, .. } => {
+                self.expr(left, nested);
+                self.expr(right, nested);
+            }
+            Expr::Unary { operand, .. } | Expr::PostfixUnary { operand, .. } => self.expr(operand, nested),
+            Expr::Object { properties, .. } => {
+                for definition in properties {
+                    match definition {
+                        PropertyDefinition::Property(property) => {
+                            if let PropertyKey::Computed(key) = &property.key {
+                                self.expr(key, nested);
+                            }
+                            self.expr(&property.value, nested);
+                        }
+                        PropertyDefinition::SpreadProperty { argument, .. } => self.expr(argument, nested),
+                    }
+                }
+            }
+            Expr::Array { elements, .. } => {
+                for element in elements.iter().flatten() {
+                    self.expr(element, nested);
+                }
+            }
+            Expr::Yield { argument, .. } => {
+                if let Some(argument) = argument {
+                    self.expr(argument, nested);
+                }
+            }
+            Expr::Await { argument, .. } => self.expr(argument, nested),
+            Expr::Function { body, .. } | Expr::Arrow { body, .. } => self.body(body),
+            Expr::Class { body, .. } => self.class(body),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    fn non_escaping(source: &str) -> Vec<String> {
+        let program = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        let mut names: Vec<_> = non_escaping_locals(&[], &program.statements, 256).into_iter().collect();
+        names.sort();
+        names
+    }
+    
+    #[test]
+    fn test_property_accesses_do_not_escape() {
+        assert_eq!(non_escaping("let o = { a: 1 }; o.a = o.a + 1; o['b'] = 2; o.c++; let n = o.a;"), ["o"]);
+        assert_eq!(non_escaping("const p = [1, 2]; p[0] = p[1];"), ["p"]);
+        assert_eq!(non_escaping("let o = {}; o.self = {}; o.self.x = 1;"), ["o"]);
+    }
+    
+    #[test]
+    fn test_escaping_uses() {
+        for source in [
+            "let o = {}; f(o);",
+            "let o = {}; o.method();",
+            "let o = {}; let p = o;",
+            "let o = {}; g.x = o;",
+            "let o = {}; o = {};",
+            "let o = {}; g.list = [o];",
+            "let o = {}; function f() { return o.x; }",
+            "let o = {}; let f = () => o;",
+            "let o = {}; o.self = o;",
+            "let o = {}; typeof o;",
+            "let o = {}; if (true) { let o = {}; }",
+            "let o = 1;",
+        ] {
+            assert!(non_escaping(source).is_empty(), "{}", source);
+        }
+    }
+}
@@ -3,20 +3,52 @@
 //! This module defines the structure for compiled JavaScript functions,
 //! including bytecode instructions, constant pool, and debug information.
 
+use std::collections::HashMap;
 use std::fmt;
-use crate::error::Span;
-use super::instruction::{Bytecode, LocalIndex};
-use super::constant_pool::ConstantPool;
+use std::ops::Range;
+use std::sync::Arc;
+use crate::error::{Diagnostic, Span};
+use super::instruction::{Bytecode, ConstIndex, LocalIndex};
+use super::constant_pool::{ConstantPool, ConstantValue};
+use super::string_table::{NameId, StringTable};
 
 /// Debug information for mapping bytecode back to source code
 #[derive(Debug, Clone, PartialEq)]
 pub struct DebugInfo {
     /// Maps bytecode instruction index to source code span
     pub source_map: Vec<Option<Span>>,
-    /// Original source code (for error reporting)
-    pub source_code: Option<String>,
+    /// Source of the whole program, shared by every function compiled
+    /// from it. `Arc` rather than `Rc` because compiled code is `Send + Sync`.
+    pub source_code: Option<Arc<str>>,
+    /// Where this function's own text sits in `source_code`; `None` for
+    /// top-level code, which spans all of it
+    pub function_span: Option<Span>,
     /// Line number information for each instruction
     pub line_numbers: Vec<Option<u32>>,
+    /// Source text of the callee at call instructions, for error messages
+    pub callee_names: HashMap<usize, String>,
+    /// Parameters and declared locals, ordered by where their scope starts
+    pub locals: Vec<LocalVariable>,
+    /// Offsets where a statement's code starts, ascending; the debugger
+    /// steps from one to the next
+    pub statement_starts: Vec<usize>,
+}
+
+/// A parameter or declared local and the instructions its name is bound
+/// over
+///
+/// Slots of a block whose scope has ended are handed to later
+/// declarations, so one slot can hold different variables at different
+/// instructions; `live` tells them apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVariable {
+    pub name: String,
+    pub slot: LocalIndex,
+    pub is_parameter: bool,
+    /// The declaration; the whole function for a parameter
+    pub span: Span,
+    /// Instructions from the declaration to the end of its scope
+    pub live: Range<usize>,
 }
 
 impl DebugInfo {
@@ -25,7 +57,11 @@ impl DebugInfo {
         Self {
             source_map: Vec::with_capacity(capacity),
             source_code: None,
+            function_span: None,
             line_numbers: Vec::with_capacity(capacity),
+            callee_names: HashMap::new(),
+            locals: Vec::new(),
+            statement_starts: Vec::new(),
         }
     }
     
@@ -34,7 +70,11 @@ impl DebugInfo {
         Self {
             source_map: Vec::new(),
             source_code: None,
+            function_span: None,
             line_numbers: Vec::new(),
+            callee_names: HashMap::new(),
+            locals: Vec::new(),
+            statement_starts: Vec::new(),
         }
     }
     
@@ -45,8 +85,14 @@ impl DebugInfo {
         self.line_numbers.push(line_number);
     }
     
-    /// Set the original source code
-    pub fn set_source_code(&mut self, source: String) {
+    /// Add debug information for an instruction with no span of its own
+    pub fn add_instruction_on_line(&mut self, line: Option<u32>) {
+        self.source_map.push(None);
+        self.line_numbers.push(line);
+    }
+    
+    /// Set the program source this function was compiled from
+    pub fn set_source_code(&mut self, source: Arc<str>) {
         self.source_code = Some(source);
     }
     
@@ -55,13 +101,90 @@ impl DebugInfo {
         self.source_map.get(instruction_index).and_then(|s| s.as_ref())
     }
     
+    /// Get the callee's source text for a call instruction
+    pub fn get_callee_name(&self, instruction_index: usize) -> Option<&str> {
+        self.callee_names.get(&instruction_index).map(String::as_str)
+    }
+    
     /// Get line number for a bytecode instruction
     pub fn get_line_number(&self, instruction_index: usize) -> Option<u32> {
         self.line_numbers.get(instruction_index).copied().flatten()
     }
+    
+    /// Mark `instruction_index` as where a statement's code starts
+    pub fn add_statement_start(&mut self, instruction_index: usize) {
+        if let Err(position) = self.statement_starts.binary_search(&instruction_index) {
+            self.statement_starts.insert(position, instruction_index);
+        }
+    }
+    
+    /// Whether a statement's code starts at `instruction_index`
+    pub fn is_statement_start(&self, instruction_index: usize) -> bool {
+        self.statement_starts.binary_search(&instruction_index).is_ok()
+    }
+    
+    /// Get the variable in `slot` at instruction `instruction_index`
+    pub fn get_local(&self, slot: LocalIndex, instruction_index: usize) -> Option<&LocalVariable> {
+        self.locals.iter().find(|local| local.slot == slot && local.live.contains(&instruction_index))
+    }
+}
+
+/// A set of local slots, one bit per slot
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LocalSet {
+    words: Vec<u64>,
+}
+
+impl LocalSet {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a slot to the set
+    pub fn insert(&mut self, index: LocalIndex) {
+        let (word, bit) = (index.index() / 64, index.index() % 64);
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        self.words[word] |= 1 << bit;
+    }
+    
+    /// Whether the slot is in the set
+    pub fn contains(&self, index: LocalIndex) -> bool {
+        let (word, bit) = (index.index() / 64, index.index() % 64);
+        self.words.get(word).is_some_and(|word| word & (1 << bit) != 0)
+    }
+    
+    /// Whether the set has no slots
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+    
+    /// The slots in the set, in ascending order
+    pub fn iter(&self) -> impl Iterator<Item = LocalIndex> + '_ {
+        self.words.iter().enumerate().flat_map(|(word, &bits)| {
+            (0..64).filter(move |bit| bits & (1 << bit) != 0)
+                .map(move |bit| LocalIndex((word * 64 + bit) as u16))
+        })
+    }
+}
+
+impl FromIterator<LocalIndex> for LocalSet {
+    fn from_iter<I: IntoIterator<Item = LocalIndex>>(iter: I) -> Self {
+        let mut set = LocalSet::new();
+        for index in iter {
+            set.insert(index);
+        }
+        set
+    }
 }
 
 /// Represents a compiled JavaScript function with bytecode
+///
+/// Compiled code is plain data and is `Send + Sync`: compile once, share
+/// the `Arc<BytecodeFunction>` between threads, and run it on a VM per
+/// thread with `VM::execute_shared`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct BytecodeFunction {
     /// Function name (for debugging and stack traces)
@@ -72,7 +195,18 @@ pub struct BytecodeFunction {
     
     /// Total number of local variable slots needed
     /// This includes parameters, local variables, and temporary slots
-    pub locals_count: LocalIndex,
+    pub locals_count: u16,
+    
+    /// Locals declared with `const`: `InitLocal` sets them once, and any
+    /// other store is an error
+    pub immutable_locals: LocalSet,
+    
+    /// Offsets of the `CreateObject`, `CreateArray` and `CreateFromTemplate`
+    /// instructions whose object never escapes a call, in ascending order
+    ///
+    /// An allocation's position in this list is its site number in the
+    /// frame arena (see `VMConfig::frame_arena`).
+    pub arena_sites: Vec<usize>,
     
     /// Maximum stack depth required during execution
     pub max_stack_size: usize,
@@ -83,9 +217,16 @@ pub struct BytecodeFunction {
     /// Pool of constants referenced by the bytecode
     pub constants: ConstantPool,
     
+    /// Property names referenced by `PropertyName` constants, shared by
+    /// every function compiled from the same program
+    pub names: Arc<StringTable>,
+    
     /// Debug information for mapping back to source
     pub debug_info: DebugInfo,
     
+    /// Nested functions, referenced by index from `CreateClosure`
+    pub functions: Vec<Arc<BytecodeFunction>>,
+    
     /// Whether this function is a generator
     pub is_generator: bool,
     
@@ -94,6 +235,12 @@ pub struct BytecodeFunction {
     
     /// Whether this is an arrow function (affects 'this' binding)
     pub is_arrow: bool,
+    
+    /// Whether this function's code is strict mode code
+    pub is_strict: bool,
+    
+    /// Whether this is a class constructor, which only `new` may call
+    pub is_class_constructor: bool,
 }
 
 impl BytecodeFunction {
@@ -101,20 +248,26 @@ impl BytecodeFunction {
     pub fn new(
         name: String,
         arity: u8,
-        locals_count: LocalIndex,
+        locals_count: u16,
         max_stack_size: usize,
     ) -> Self {
         Self {
             name,
             arity,
             locals_count,
+            immutable_locals: LocalSet::new(),
+            arena_sites: Vec::new(),
             max_stack_size,
             bytecode: Vec::new(),
             constants: ConstantPool::new(),
+            names: Arc::default(),
             debug_info: DebugInfo::empty(),
+            functions: Vec::new(),
             is_generator: false,
             is_async: false,
             is_arrow: false,
+            is_strict: false,
+            is_class_constructor: false,
         }
     }
     
@@ -135,12 +288,54 @@ impl BytecodeFunction {
         self.debug_info.add_instruction(None);
     }
     
+    /// Add a bytecode instruction attributed to a source line only
+    pub fn add_instruction_on_line(&mut self, instruction: Bytecode, line: Option<u32>) {
+        self.bytecode.push(instruction);
+        self.debug_info.add_instruction_on_line(line);
+    }
+    
     /// Add a bytecode instruction with span information
     pub fn add_instruction_with_span(&mut self, instruction: Bytecode, span: Span) {
         self.bytecode.push(instruction);
         self.debug_info.add_instruction(Some(span));
     }
     
+    /// Add a property-name constant, interning the name in this function's
+    /// string table
+    ///
+    /// A table shared with other functions is copied first; the compiler
+    /// interns through its own handle instead.
+    pub fn add_property_name(&mut self, name: &str) -> ConstIndex {
+        let id = Arc::make_mut(&mut self.names).intern(name);
+        self.constants.add_property_name(id)
+    }
+    
+    /// The name with the given id in this function's string table
+    pub fn property_name(&self, id: NameId) -> Option<&str> {
+        self.names.get(id).map(|name| &**name)
+    }
+    
+    /// A constant as the disassembler shows it, with property names
+    /// looked up in the string table
+    pub fn display_constant(&self, idx: ConstIndex) -> Option<String> {
+        Some(match self.constants.get(idx)? {
+            ConstantValue::PropertyName(id) => match self.property_name(*id) {
+                Some(name) => format!(".{}", name),
+                None => format!(".#{} (unknown name)", id),
+            },
+            value => value.to_string(),
+        })
+    }
+    
+    /// Make `names` the string table of this function and every function
+    /// nested in it
+    pub fn share_names(&mut self, names: &Arc<StringTable>) {
+        self.names = names.clone();
+        for function in &mut self.functions {
+            Arc::make_mut(function).share_names(names);
+        }
+    }
+    
     /// Get the current bytecode length (useful for jump targets)
     pub fn current_offset(&self) -> usize {
         self.bytecode.len()
@@ -176,6 +371,12 @@ impl BytecodeFunction {
         self.max_stack_size = max_stack.max(0) as usize;
     }
     
+    /// Whether the body can suspend at `Yield`, as generators and async
+    /// functions do
+    pub fn is_resumable(&self) -> bool {
+        self.is_generator || self.is_async
+    }
+    
     /// Set function flags for special function types
     pub fn set_flags(&mut self, is_generator: bool, is_async: bool, is_arrow: bool) {
         self.is_generator = is_generator;
@@ -183,6 +384,63 @@ impl BytecodeFunction {
         self.is_arrow = is_arrow;
     }
     
+    /// This function's own source text, as `Function.prototype.toString`
+    /// returns it; the whole program for top-level code
+    pub fn source_text(&self) -> Option<&str> {
+        match self.debug_info.function_span {
+            Some(span) => self.source_snippet(span),
+            None => self.debug_info.source_code.as_deref(),
+        }
+    }
+    
+    /// The program source covered by `span`, for error snippets
+    ///
+    /// `None` without retained source or when the span does not fall on
+    /// character boundaries inside it.
+    pub fn source_snippet(&self, span: Span) -> Option<&str> {
+        self.debug_info.source_code.as_deref()?.get(span.start..span.end)
+    }
+    
+    /// Render a diagnostic against the retained program source
+    pub fn format_diagnostic(&self, diagnostic: &Diagnostic) -> String {
+        diagnostic.format_with_source(self.debug_info.source_code.as_deref().unwrap_or(""))
+    }
+    
+    /// Names of the parameters, in order; empty when the function carries
+    /// no symbol table, as functions loaded from a dump don't
+    pub fn parameter_names(&self) -> Vec<&str> {
+        let mut parameters: Vec<_> = self.debug_info.locals.iter().filter(|local| local.is_parameter).collect();
+        parameters.sort_by_key(|local| local.slot);
+        parameters.into_iter().map(|local| local.name.as_str()).collect()
+    }
+    
+    /// Name of the variable held in `slot` at instruction `ip`
+    ///
+    /// `None` for a slot no variable is bound to there, such as the
+    /// hidden one holding completion values, or a block's slot after the
+    /// block ends.
+    pub fn local_name(&self, slot: LocalIndex, ip: usize) -> Option<&str> {
+        self.debug_info.get_local(slot, ip).map(|local| local.name.as_str())
+    }
+    
+    /// The names in scope at instruction `ip` and their slots, in slot
+    /// order, as `Compiler::compile_expression_in_scope` takes them
+    ///
+    /// Where an inner declaration shadows an outer one, the inner slot is
+    /// the one listed.
+    pub fn scope_at(&self, ip: usize) -> Vec<(String, LocalIndex)> {
+        let mut visible: HashMap<&str, &LocalVariable> = HashMap::new();
+        for local in self.debug_info.locals.iter().filter(|local| local.live.contains(&ip)) {
+            let inner = visible.get(local.name.as_str()).is_none_or(|seen| local.live.start >= seen.live.start);
+            if inner {
+                visible.insert(&local.name, local);
+            }
+        }
+        let mut scope: Vec<_> = visible.into_values().map(|local| (local.name.clone(), local.slot)).collect();
+        scope.sort_by_key(|&(_, slot)| slot);
+        scope
+    }
+    
     /// Get a human-readable signature of this function
     pub fn signature(&self) -> String {
         let mut sig = String::new();
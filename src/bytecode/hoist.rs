@@ -0,0 +1,170 @@
+//! Hoisting constant loads out of loops
+//!
+//! `LdaConst` makes a new value from the constant pool each time it runs,
+//! so a string constant in a loop is copied into a fresh string on every
+//! iteration. This pass loads each such constant once, into a local slot
+//! of its own, and has the loop read the slot instead:
+//!
+//! ```text
+//! before                        after
+//!                               0: LdaConst #1 (",")
+//!                               1: InitLocal 2
+//! L4: ...                       L6: ...
+//!     LdaConst #1 (",")             LdaLocal 2
+//!     Add                           Add
+//!     Jump L4                       Jump L6
+//! ```
+//!
+//! Only primitive constants qualify: a regex or template constant makes a
+//! new object each time, which a shared slot would not. The loads go at
+//! the start of the function rather than just before each loop, where
+//! the accumulator may hold a value still to be used; a constant can't
+//! change, so loading it earlier makes no difference. Inserting at the
+//! start moves every instruction by the same amount, so jumps, which are
+//! relative, stay as they are, and only the offsets recorded beside the
+//! code need shifting.
+//!
+//! The slots come after the function's own and are marked immutable, so
+//! `InitLocal` is the only store to them. A function too close to
+//! `MAX_LOCALS` to spare a slot for every constant it may hoist is left
+//! alone.
+
+use std::collections::HashMap;
+use super::compiler::MAX_LOCALS;
+use super::constant_pool::ConstantValue;
+use super::function::BytecodeFunction;
+use super::instruction::{Bytecode, ConstIndex, LocalIndex};
+use super::loops::{find_loops, in_any_loop};
+
+/// How many constants `Compiler::with_constant_hoisting` hoists out of the
+/// loops of one function when not told otherwise
+pub const DEFAULT_MAX_HOISTED_CONSTANTS: usize = 8;
+
+/// Hoist up to `max_constants` constant loads out of the loops of
+/// `function`, returning how many were hoisted
+///
+/// Nested functions are left to their own compilers. Constants are
+/// picked in the order their first load in a loop appears.
+pub(crate) fn hoist_loop_constants(function: &mut BytecodeFunction, max_constants: usize) -> usize {
+    // Close to the budget, the slots are worth more than the loads
+    if function.locals_count as usize + max_constants > MAX_LOCALS {
+        return 0;
+    }
+    let loops = find_loops(function);
+    if loops.is_empty() {
+        return 0;
+    }
+
+    let mut slots: HashMap<ConstIndex, LocalIndex> = HashMap::new();
+    let mut hoisted: Vec<ConstIndex> = Vec::new();
+    for (offset, instruction) in function.bytecode.iter().enumerate() {
+        let Bytecode::LdaConst(index) = *instruction else {
+            continue;
+        };
+        if hoisted.len() == max_constants || slots.contains_key(&index) || !in_any_loop(&loops, offset) {
+            continue;
+        }
+        if is_primitive(function.constants.get(index)) {
+            slots.insert(index, LocalIndex(function.locals_count + hoisted.len() as u16));
+            hoisted.push(index);
+        }
+    }
+    if hoisted.is_empty() {
+        return 0;
+    }
+
+    for (offset, instruction) in function.bytecode.iter_mut().enumerate() {
+        if let Bytecode::LdaConst(index) = *instruction {
+            if let Some(&slot) = slots.get(&index).filter(|_| in_any_loop(&loops, offset)) {
+                *instruction = Bytecode::LdaLocal(slot);
+            }
+        }
+    }
+
+    let mut prologue = Vec::with_capacity(hoisted.len() * 2);
+    for index in &hoisted {
+        let slot = slots[index];
+        prologue.push(Bytecode::LdaConst(*index));
+        prologue.push(Bytecode::InitLocal(slot));
+        function.immutable_locals.insert(slot);
+    }
+    function.locals_count += hoisted.len() as u16;
+    insert_prologue(function, prologue);
+    hoisted.len()
+}
+
+/// Whether loading `constant` makes a value that can be shared, rather
+/// than a new object
+fn is_primitive(constant: Option<&ConstantValue>) -> bool {
+    matches!(
+        constant,
+        Some(ConstantValue::Number(_) | ConstantValue::String(_) | ConstantValue::Boolean(_) | ConstantValue::Null | ConstantValue::Undefined)
+    )
+}
+
+/// Put `prologue` before the first instruction, moving the offsets kept
+/// beside the code to match
+fn insert_prologue(function: &mut BytecodeFunction, prologue: Vec<Bytecode>) {
+    let shift = prologue.len();
+    function.bytecode.splice(0..0, prologue);
+
+    let debug_info = &mut function.debug_info;
+    debug_info.source_map.splice(0..0, std::iter::repeat_n(None, shift));
+    debug_info.line_numbers.splice(0..0, std::iter::repeat_n(None, shift));
+    debug_info.callee_names = std::mem::take(&mut debug_info.callee_names)
+        .into_iter()
+        .map(|(offset, name)| (offset + shift, name))
+        .collect();
+    for offset in &mut debug_info.statement_starts {
+        *offset += shift;
+    }
+    for local in &mut debug_info.locals {
+        // Parameters, live from the start, stay live from the start
+        if local.live.start > 0 {
+            local.live.start += shift;
+        }
+        local.live.end += shift;
+    }
+    for site in &mut function.arena_sites {
+        *site += shift;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::compiler::Compiler;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    fn compile(source: &str) -> BytecodeFunction {
+        let program = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        Compiler::new_main(source).compile(&program).unwrap()
+    }
+    
+    #[test]
+    fn test_functions_near_the_slot_budget_are_left_alone() {
+        let mut function = compile("let s = ''; while (s.length < 3.5) { s = s + 'x'; }");
+        let unchanged = function.clone();
+        function.locals_count = (MAX_LOCALS - 1) as u16;
+        assert_eq!(hoist_loop_constants(&mut function, 2), 0);
+        assert_eq!(function.bytecode, unchanged.bytecode);
+        
+        function.locals_count = unchanged.locals_count;
+        assert_eq!(hoist_loop_constants(&mut function, 2), 2);
+        assert_eq!(function.locals_count, unchanged.locals_count + 2);
+    }
+    
+    #[test]
+    fn test_the_prologue_shifts_recorded_offsets() {
+        let mut function = compile("let s = '';\nwhile (s.length < 3) {\n  s = s + 'x';\n}");
+        let before = function.clone();
+        let hoisted = hoist_loop_constants(&mut function, 8);
+        let shift = hoisted * 2;
+        assert_eq!(function.bytecode.len(), before.bytecode.len() + shift);
+        assert_eq!(function.debug_info.line_numbers.len(), function.bytecode.len());
+        assert_eq!(function.debug_info.line_numbers[shift..], before.debug_info.line_numbers[..]);
+        let starts: Vec<_> = before.debug_info.statement_starts.iter().map(|offset| offset + shift).collect();
+        assert_eq!(function.debug_info.statement_starts, starts);
+    }
+}
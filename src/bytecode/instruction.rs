@@ -5,37 +5,197 @@
 //! while maintaining simplicity for the interpreter.
 
 use std::fmt;
+use serde::{Deserialize, Serialize};
 
-/// Type alias for constant pool indices (supports up to 65536 constants)
-pub type ConstIndex = u16;
+/// Index into a function's constant pool, or for `CreateClosure` into
+/// its nested functions (up to 65536 entries)
+///
+/// Operand kinds are distinct types, so one can't be passed where another
+/// is expected:
+///
+/// ```compile_fail
+/// use v8::bytecode::{Bytecode, ConstIndex};
+///
+/// let name = ConstIndex(3);
+/// let load = Bytecode::LdaLocal(name);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ConstIndex(pub u16);
 
-/// Type alias for local variable indices (supports up to 65536 locals)
-pub type LocalIndex = u16;
+impl ConstIndex {
+    /// The index as a `usize`, for indexing the pool
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<usize> for ConstIndex {
+    type Error = OperandOutOfRange;
+    
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        u16::try_from(index).map(ConstIndex).map_err(|_| OperandOutOfRange::new("constant index", index as i64))
+    }
+}
+
+impl fmt::Display for ConstIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Index of a local variable slot in a function's environment (up to
+/// 65536 locals)
+///
+/// ```compile_fail
+/// use v8::bytecode::{Bytecode, LocalIndex};
+///
+/// let slot = LocalIndex(0);
+/// let load = Bytecode::LdaConst(slot);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct LocalIndex(pub u16);
 
-/// Type alias for jump offsets (±32KB jump range)
-pub type JumpOffset = i16;
+impl LocalIndex {
+    /// The index as a `usize`, for indexing the environment
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl TryFrom<usize> for LocalIndex {
+    type Error = OperandOutOfRange;
+    
+    fn try_from(index: usize) -> Result<Self, Self::Error> {
+        u16::try_from(index).map(LocalIndex).map_err(|_| OperandOutOfRange::new("local index", index as i64))
+    }
+}
+
+impl fmt::Display for LocalIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Jump distance in instructions, counted from the instruction after the
+/// jump (±32K instructions)
+///
+/// ```compile_fail
+/// use v8::bytecode::{Bytecode, JumpOffset, LocalIndex};
+///
+/// let jump = Bytecode::Jump(LocalIndex(2));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct JumpOffset(pub i16);
+
+impl JumpOffset {
+    /// The offset taking a jump at `site` forward to `target`, which is
+    /// after it
+    pub fn forward(site: usize, target: usize) -> Result<Self, OperandOutOfRange> {
+        debug_assert!(target > site, "forward jump from {} to {}", site, target);
+        Self::between(site, target)
+    }
+    
+    /// The offset taking a jump at `site` back to `target`, which is at or
+    /// before it
+    pub fn backward(site: usize, target: usize) -> Result<Self, OperandOutOfRange> {
+        debug_assert!(target <= site, "backward jump from {} to {}", site, target);
+        Self::between(site, target)
+    }
+    
+    fn between(site: usize, target: usize) -> Result<Self, OperandOutOfRange> {
+        let distance = target as i64 - (site as i64 + 1);
+        i16::try_from(distance).map(JumpOffset).map_err(|_| OperandOutOfRange::new("jump offset", distance))
+    }
+    
+    /// Where a jump at `site` lands; `None` before the first instruction
+    pub fn target(self, site: usize) -> Option<usize> {
+        (site + 1).checked_add_signed(self.0 as isize)
+    }
+}
+
+impl fmt::Display for JumpOffset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An operand value too large for its instruction to encode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperandOutOfRange {
+    /// What the operand is, such as "constant index"
+    pub operand: &'static str,
+    pub value: i64,
+}
+
+impl OperandOutOfRange {
+    fn new(operand: &'static str, value: i64) -> Self {
+        Self { operand, value }
+    }
+}
+
+impl fmt::Display for OperandOutOfRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} is out of range", self.operand, self.value)
+    }
+}
+
+impl std::error::Error for OperandOutOfRange {}
 
 /// Type alias for argument count in function calls
 pub type ArgCount = u8;
 
+/// Type alias for the number of environments to walk up to reach a captured variable
+pub type ContextDepth = u8;
+
+/// Type alias for array element indices in literals
+pub type ElementIndex = u16;
+
+/// Type alias for small integer immediates (Smi) loaded without the constant pool
+pub type SmallInteger = i16;
+
 /// Bytecode instructions for stack-based VM with accumulator
-#[derive(Debug, Clone, PartialEq)]
+///
+/// Store instructions (`Sta*`, `Define*`) never modify the accumulator, so
+/// the stored value is still available afterwards; assignment expressions
+/// rely on this for their result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Bytecode {
     // === Load/Store Operations ===
     /// Load constant into accumulator: acc = constants[index]
     LdaConst(ConstIndex),
     
+    /// Load small integer immediate into accumulator: acc = value
+    LdaSmi(SmallInteger),
+    
     /// Load local variable into accumulator: acc = locals[index]
     LdaLocal(LocalIndex),
     
     /// Store accumulator to local variable: locals[index] = acc
     StaLocal(LocalIndex),
     
+    /// Initialize a local variable: locals[index] = acc; the only store
+    /// allowed to a slot in `BytecodeFunction::immutable_locals`
+    InitLocal(LocalIndex),
+    
     /// Load global variable into accumulator: acc = globals[name]
     LdaGlobal(ConstIndex),
     
     /// Store accumulator to global variable: globals[name] = acc
     StaGlobal(ConstIndex),
+    
+    /// Initialize a global declared at the top level of REPL input:
+    /// globals[name] = acc, creating the binding even in strict mode code
+    InitGlobal(ConstIndex),
+    
+    /// Load global variable for `typeof`: acc = globals[name], or
+    /// undefined rather than a ReferenceError when there is none
+    LdaGlobalInsideTypeof(ConstIndex),
+    
+    /// Load captured variable: acc = env.parent^depth[index]
+    LdaContextSlot(ContextDepth, LocalIndex),
+    
+    /// Store captured variable: env.parent^depth[index] = acc
+    StaContextSlot(ContextDepth, LocalIndex),
 
     // === Stack Operations ===
     /// Push accumulator onto stack: push(acc)
@@ -43,6 +203,12 @@ pub enum Bytecode {
     
     /// Pop from stack into accumulator: acc = pop()
     Pop,
+    
+    /// Duplicate the top of the stack: push(top)
+    Dup,
+    
+    /// Duplicate the top two stack entries: push(second); push(top)
+    Dup2,
 
     // === Arithmetic Operations ===
     /// Addition: acc = acc + pop()
@@ -62,6 +228,13 @@ pub enum Bytecode {
     
     /// Exponentiation: acc = acc ** pop()
     Pow,
+    
+    /// Increment: acc = ToNumber(acc) + 1, in double arithmetic like
+    /// `Add`, so it rounds past 2^53 rather than wrapping
+    Inc,
+    
+    /// Decrement: acc = ToNumber(acc) - 1
+    Dec,
 
     // === Comparison Operations ===
     /// Equality: acc = (acc == pop())
@@ -87,6 +260,12 @@ pub enum Bytecode {
     
     /// Greater than or equal: acc = (acc >= pop())
     Ge,
+    
+    /// Prototype chain check: acc = (pop() instanceof acc)
+    InstanceOf,
+    
+    /// Property presence check, own or inherited: acc = (pop() in acc)
+    In,
 
     // === Logical Operations ===
     /// Logical AND: acc = acc && pop()
@@ -97,6 +276,9 @@ pub enum Bytecode {
     
     /// Logical NOT: acc = !acc
     LogicalNot,
+    
+    /// Boolean coercion: acc = ToBoolean(acc), i.e. `!!acc`
+    ToBoolean,
 
     // === Bitwise Operations ===
     /// Bitwise AND: acc = acc & pop()
@@ -129,6 +311,9 @@ pub enum Bytecode {
     
     /// Typeof operation: acc = typeof acc
     TypeOf,
+    
+    /// Load the receiver of the current call: acc = this
+    LdaThis,
 
     // === Property Access ===
     /// Load named property: acc = pop()[constants[index]]
@@ -137,21 +322,39 @@ pub enum Bytecode {
     /// Store named property: pop()[constants[index]] = acc
     StaNamed(ConstIndex),
     
-    /// Load computed property: acc = pop()[pop()]
+    /// Load computed property: acc = pop()[acc]
     LdaKeyed,
     
-    /// Store computed property: pop()[pop()] = acc
+    /// Store computed property: key = pop(); pop()[key] = acc
     StaKeyed,
+    
+    /// Delete named property: acc = delete acc[constants[index]]
+    DeleteNamedProperty(ConstIndex),
+    
+    /// Delete computed property: acc = delete pop()[acc]
+    DeleteKeyedProperty,
 
     // === Function Operations ===
-    /// Function call: acc = call(acc, argc_args_from_stack)
+    /// Function call: pops argc arguments, then the callee; acc = callee(args)
     Call(ArgCount),
     
+    /// Method call: pops argc arguments, the callee, then the receiver;
+    /// acc = callee.call(receiver, args)
+    CallMethod(ArgCount),
+    
+    /// Constructor call: pops argc arguments, then the callee;
+    /// acc = new callee(args)
+    Construct(ArgCount),
+    
     /// Return from function: return acc
     Return,
     
     /// Return undefined from function
     ReturnUndefined,
+    
+    /// Suspend the running generator, handing acc to the caller of `next()`;
+    /// on resumption acc is the value passed to `next()`
+    Yield,
 
     // === Control Flow ===
     /// Unconditional jump: pc += offset
@@ -166,14 +369,40 @@ pub enum Bytecode {
     /// Jump if accumulator is null or undefined: if (acc == null) pc += offset
     JumpIfNullish(JumpOffset),
 
+    // === Iteration ===
+    /// Get an iterator for for-of: acc = acc[Symbol.iterator]()
+    GetIterator,
+    
+    /// Step the iterator on top of the stack (without popping it):
+    /// if done, pc += offset; otherwise acc = the next value
+    IteratorNext(JumpOffset),
+    
+    /// Pop the iterator, finishing a generator that is still suspended
+    IteratorClose,
+
     // === Object Creation ===
     /// Create empty object: acc = {}
     CreateObject,
     
-    /// Create array with initial size: acc = new Array(size)
-    CreateArray(ConstIndex),
+    /// Create array of holes with the given length: acc = new Array(length)
+    CreateArray(ElementIndex),
+    
+    /// Materialize an object or array literal template: acc = fresh copy of constants[index]
+    CreateFromTemplate(ConstIndex),
     
-    /// Create function closure: acc = function(params, bytecode)
+    /// Store into the array on top of the stack (without popping it): top[index] = acc
+    StaArrayElement(ElementIndex),
+    
+    /// Define a property on the object on top of the stack: top[constants[index]] = acc
+    DefineNamedProperty(ConstIndex),
+    
+    /// Define a computed property on the object below the key: key = pop(); top[key] = acc
+    DefineKeyedProperty,
+    
+    /// Copy acc's own enumerable properties onto the object on top of the stack
+    CopyDataProperties,
+    
+    /// Create function closure over the current environment: acc = functions[index]
     CreateClosure(ConstIndex),
 
     // === Debugging and Utilities ===
@@ -189,14 +418,22 @@ impl fmt::Display for Bytecode {
         match self {
             // Load/Store
             Bytecode::LdaConst(idx) => write!(f, "LdaConst #{}", idx),
+            Bytecode::LdaSmi(value) => write!(f, "LdaSmi {}", value),
             Bytecode::LdaLocal(idx) => write!(f, "LdaLocal {}", idx),
             Bytecode::StaLocal(idx) => write!(f, "StaLocal {}", idx),
+            Bytecode::InitLocal(idx) => write!(f, "InitLocal {}", idx),
             Bytecode::LdaGlobal(idx) => write!(f, "LdaGlobal #{}", idx),
             Bytecode::StaGlobal(idx) => write!(f, "StaGlobal #{}", idx),
+            Bytecode::InitGlobal(idx) => write!(f, "InitGlobal #{}", idx),
+            Bytecode::LdaGlobalInsideTypeof(idx) => write!(f, "LdaGlobalInsideTypeof #{}", idx),
+            Bytecode::LdaContextSlot(depth, idx) => write!(f, "LdaContextSlot {} {}", depth, idx),
+            Bytecode::StaContextSlot(depth, idx) => write!(f, "StaContextSlot {} {}", depth, idx),
             
             // Stack
             Bytecode::Push => write!(f, "Push"),
             Bytecode::Pop => write!(f, "Pop"),
+            Bytecode::Dup => write!(f, "Dup"),
+            Bytecode::Dup2 => write!(f, "Dup2"),
             
             // Arithmetic
             Bytecode::Add => write!(f, "Add"),
@@ -205,6 +442,8 @@ impl fmt::Display for Bytecode {
             Bytecode::Div => write!(f, "Div"),
             Bytecode::Mod => write!(f, "Mod"),
             Bytecode::Pow => write!(f, "Pow"),
+            Bytecode::Inc => write!(f, "Inc"),
+            Bytecode::Dec => write!(f, "Dec"),
             
             // Comparison
             Bytecode::Eq => write!(f, "Eq"),
@@ -215,11 +454,14 @@ impl fmt::Display for Bytecode {
             Bytecode::Gt => write!(f, "Gt"),
             Bytecode::Le => write!(f, "Le"),
             Bytecode::Ge => write!(f, "Ge"),
+            Bytecode::InstanceOf => write!(f, "InstanceOf"),
+            Bytecode::In => write!(f, "In"),
             
             // Logical
             Bytecode::LogicalAnd => write!(f, "LogicalAnd"),
             Bytecode::LogicalOr => write!(f, "LogicalOr"),
             Bytecode::LogicalNot => write!(f, "LogicalNot"),
+            Bytecode::ToBoolean => write!(f, "ToBoolean"),
             
             // Bitwise
             Bytecode::BitwiseAnd => write!(f, "BitwiseAnd"),
@@ -234,17 +476,23 @@ impl fmt::Display for Bytecode {
             Bytecode::UnaryPlus => write!(f, "UnaryPlus"),
             Bytecode::UnaryMinus => write!(f, "UnaryMinus"),
             Bytecode::TypeOf => write!(f, "TypeOf"),
+            Bytecode::LdaThis => write!(f, "LdaThis"),
             
             // Property access
             Bytecode::LdaNamed(idx) => write!(f, "LdaNamed #{}", idx),
             Bytecode::StaNamed(idx) => write!(f, "StaNamed #{}", idx),
             Bytecode::LdaKeyed => write!(f, "LdaKeyed"),
             Bytecode::StaKeyed => write!(f, "StaKeyed"),
+            Bytecode::DeleteNamedProperty(idx) => write!(f, "DeleteNamedProperty #{}", idx),
+            Bytecode::DeleteKeyedProperty => write!(f, "DeleteKeyedProperty"),
             
             // Functions
             Bytecode::Call(argc) => write!(f, "Call {}", argc),
+            Bytecode::CallMethod(argc) => write!(f, "CallMethod {}", argc),
+            Bytecode::Construct(argc) => write!(f, "Construct {}", argc),
             Bytecode::Return => write!(f, "Return"),
             Bytecode::ReturnUndefined => write!(f, "ReturnUndefined"),
+            Bytecode::Yield => write!(f, "Yield"),
             
             // Control flow
             Bytecode::Jump(offset) => write!(f, "Jump {}", offset),
@@ -252,9 +500,19 @@ impl fmt::Display for Bytecode {
             Bytecode::JumpIfTrue(offset) => write!(f, "JumpIfTrue {}", offset),
             Bytecode::JumpIfNullish(offset) => write!(f, "JumpIfNullish {}", offset),
             
+            // Iteration
+            Bytecode::GetIterator => write!(f, "GetIterator"),
+            Bytecode::IteratorNext(offset) => write!(f, "IteratorNext {}", offset),
+            Bytecode::IteratorClose => write!(f, "IteratorClose"),
+            
             // Object creation
             Bytecode::CreateObject => write!(f, "CreateObject"),
-            Bytecode::CreateArray(size) => write!(f, "CreateArray #{}", size),
+            Bytecode::CreateArray(length) => write!(f, "CreateArray {}", length),
+            Bytecode::CreateFromTemplate(idx) => write!(f, "CreateFromTemplate #{}", idx),
+            Bytecode::StaArrayElement(index) => write!(f, "StaArrayElement {}", index),
+            Bytecode::DefineNamedProperty(idx) => write!(f, "DefineNamedProperty #{}", idx),
+            Bytecode::DefineKeyedProperty => write!(f, "DefineKeyedProperty"),
+            Bytecode::CopyDataProperties => write!(f, "CopyDataProperties"),
             Bytecode::CreateClosure(idx) => write!(f, "CreateClosure #{}", idx),
             
             // Debug
@@ -272,20 +530,47 @@ impl Bytecode {
             Bytecode::JumpIfFalse(_) |
             Bytecode::JumpIfTrue(_) |
             Bytecode::JumpIfNullish(_) |
+            Bytecode::IteratorNext(_) |
             Bytecode::Call(_) |
+            Bytecode::CallMethod(_) |
+            Bytecode::Construct(_) |
             Bytecode::Return |
-            Bytecode::ReturnUndefined
+            Bytecode::ReturnUndefined |
+            Bytecode::Yield
         )
     }
     
+    /// The offset of a jump, conditional or not, including the exit of
+    /// `IteratorNext`
+    pub fn jump_offset(&self) -> Option<JumpOffset> {
+        match self {
+            Bytecode::Jump(offset) |
+            Bytecode::JumpIfFalse(offset) |
+            Bytecode::JumpIfTrue(offset) |
+            Bytecode::JumpIfNullish(offset) |
+            Bytecode::IteratorNext(offset) => Some(*offset),
+            _ => None,
+        }
+    }
+    
     /// Returns true if this instruction modifies the accumulator
     pub fn modifies_accumulator(&self) -> bool {
         !matches!(self,
             Bytecode::StaLocal(_) |
+            Bytecode::InitLocal(_) |
             Bytecode::StaGlobal(_) |
+            Bytecode::InitGlobal(_) |
+            Bytecode::StaContextSlot(_, _) |
+            Bytecode::StaArrayElement(_) |
+            Bytecode::DefineNamedProperty(_) |
+            Bytecode::DefineKeyedProperty |
+            Bytecode::CopyDataProperties |
             Bytecode::Push |
+            Bytecode::Dup |
+            Bytecode::Dup2 |
             Bytecode::StaNamed(_) |
             Bytecode::StaKeyed |
+            Bytecode::IteratorClose |
             Bytecode::Nop |
             Bytecode::Debugger
         )
@@ -298,13 +583,21 @@ impl Bytecode {
             Bytecode::Pow | Bytecode::Eq | Bytecode::Ne | Bytecode::StrictEq | Bytecode::StrictNe |
             Bytecode::Lt | Bytecode::Gt | Bytecode::Le | Bytecode::Ge | Bytecode::LogicalAnd |
             Bytecode::LogicalOr | Bytecode::BitwiseAnd | Bytecode::BitwiseOr | Bytecode::BitwiseXor |
-            Bytecode::LeftShift | Bytecode::RightShift | Bytecode::UnsignedRightShift => 1,
+            Bytecode::LeftShift | Bytecode::RightShift | Bytecode::UnsignedRightShift |
+            Bytecode::InstanceOf | Bytecode::In => 1,
             
             Bytecode::LdaNamed(_) | Bytecode::LdaKeyed => 1,
+            Bytecode::DeleteKeyedProperty => 1,
             Bytecode::StaNamed(_) => 1,
             Bytecode::StaKeyed => 2,
+            Bytecode::DefineKeyedProperty => 1,
+            
+            Bytecode::Pop => 1,
+            Bytecode::IteratorClose => 1,
             
             Bytecode::Call(argc) => *argc as usize + 1, // args + function
+            Bytecode::CallMethod(argc) => *argc as usize + 2, // args + function + receiver
+            Bytecode::Construct(argc) => *argc as usize + 1, // args + constructor
             
             _ => 0,
         }
@@ -313,7 +606,8 @@ impl Bytecode {
     /// Returns the number of stack items this instruction pushes
     pub fn stack_push_count(&self) -> usize {
         match self {
-            Bytecode::Push => 1,
+            Bytecode::Push | Bytecode::Dup => 1,
+            Bytecode::Dup2 => 2,
             _ => 0,
         }
     }
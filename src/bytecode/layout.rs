@@ -0,0 +1,484 @@
+//! Declaration collection, the compiler's first pass over a function
+//!
+//! Before emitting any code for a function, the compiler walks its body
+//! to find every declaration and give it a local slot:
+//!
+//! - `var` is hoisted to the function scope from wherever it appears, and
+//!   shares the slot of a parameter of the same name;
+//! - `let`, `const`, `function` and `class` belong to the block, loop
+//!   head or function body they appear in, and the name of a named class
+//!   expression to a scope around the class alone.
+//!
+//! Nested functions are left out; each gets its own layout when it is
+//! compiled. A scope's bindings take their slots when it is entered, and
+//! its slots go back to later siblings when it ends, so `locals_count` is
+//! the most slots in use at once. Two exceptions keep slots for the rest
+//! of the function: a scope that creates a closure, since closures share
+//! their creator's locals, and a `const`, whose slot only `InitLocal` may
+//! store to and so never takes one that other code used.
+//!
+//! The emission pass enters the same scopes in the same order, binding
+//! all of a scope's names on entry, so code can refer to a variable or
+//! function declared further down. Only `var`s and functions can be
+//! reached that way from the function's own code, though: a `let`,
+//! `const` or `class` name resolves as if undeclared until its
+//! declaration, since its slot may still hold a sibling scope's value.
+//! Closures see every name of the scopes around them.
+
+use crate::error::{Error, ErrorCode, Result, Span};
+use crate::parser::ast::{Expr, PropertyDefinition, PropertyKey, Stmt, VarKind};
+use super::LocalIndex;
+
+/// The scopes of one function and their slots
+#[derive(Debug)]
+pub(crate) struct FunctionLayout {
+    /// Scopes in the order the compiler enters them, the function scope
+    /// first
+    pub scopes: Vec<ScopeLayout>,
+    /// Slots the function needs, parameters included
+    pub locals_count: u16,
+}
+
+/// The bindings of one scope
+#[derive(Debug)]
+pub(crate) struct ScopeLayout {
+    /// First slot free when the scope is entered
+    pub first_slot: u16,
+    /// Names declared in the scope, in source order
+    pub bindings: Vec<Binding>,
+}
+
+/// A name declared in a scope
+#[derive(Debug)]
+pub(crate) struct Binding {
+    pub name: String,
+    /// The local slot, or `None` for a top-level declaration that binds a
+    /// global (see `Compiler::with_top_level_globals`)
+    pub slot: Option<LocalIndex>,
+    pub is_const: bool,
+    /// Declared with `let`, `const`, `class` or `function` rather than
+    /// `var`
+    pub is_lexical: bool,
+    /// The first declaration of the name
+    pub span: Span,
+}
+
+/// Lay out a function with `params` in its first slots and `body`
+///
+/// Each parameter comes with the span duplicate declaration errors point
+/// at for it. Slots from `first_slot` on are free. With `globals`, the names the
+/// function scope declares bind globals instead of slots. `depth` is how
+/// deeply the function itself is nested, counted as the compiler does.
+pub(crate) fn function_layout(
+    params: &[(String, Span)],
+    body: &[Stmt],
+    first_slot: u16,
+    globals: bool,
+    depth: usize,
+    max_depth: usize,
+) -> Result<FunctionLayout> {
+    let mut walk = Walk::new(params, depth, max_depth);
+    for statement in body {
+        walk.statement(statement)?;
+    }
+    walk.finish(first_slot, globals)
+}
+
+/// Lay out a lone expression compiled as a function of its own
+pub(crate) fn expression_layout(expr: &Expr, first_slot: u16, max_depth: usize) -> Result<FunctionLayout> {
+    let mut walk = Walk::new(&[], 0, max_depth);
+    walk.expr(expr)?;
+    walk.finish(first_slot, false)
+}
+
+/// The error for a second declaration of `name` at `span`, with a note at
+/// the `first`
+fn duplicate_declaration(name: &str, span: Span, first: Span) -> Error {
+    Error::compile(ErrorCode::DuplicateDeclaration, format!("Variable '{}' already declared in this scope", name), Some(span))
+        .with_note(format!("'{}' first declared here", name), first)
+}
+
+/// A declaration found by the walk
+#[derive(Debug, Clone)]
+struct Declaration {
+    name: String,
+    is_const: bool,
+    is_lexical: bool,
+    span: Span,
+}
+
+/// A scope found by the walk
+#[derive(Debug)]
+struct ScopeNode {
+    parent: Option<usize>,
+    /// Lexical declarations, in source order
+    declarations: Vec<Declaration>,
+    /// Whether the scope, or one inside it, creates a closure or holds a
+    /// `const`, so that its slots stay taken when it ends
+    keeps_slots: bool,
+}
+
+/// The first pass over one function
+struct Walk<'a> {
+    params: &'a [(String, Span)],
+    /// Scopes in the order they are entered
+    scopes: Vec<ScopeNode>,
+    /// The scope being walked
+    current: usize,
+    /// `var` declarations and the scope each appears in
+    vars: Vec<(Declaration, usize)>,
+    /// Statements and expressions being walked inside one another
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a> Walk<'a> {
+    fn new(params: &'a [(String, Span)], depth: usize, max_depth: usize) -> Self {
+        Self {
+            params,
+            scopes: vec![ScopeNode { parent: None, declarations: Vec::new(), keeps_slots: false }],
+            current: 0,
+            vars: Vec::new(),
+            depth,
+            max_depth,
+        }
+    }
+
+    /// Walk one level deeper with `walk`, failing as the compiler would if
+    /// that goes past `max_depth`
+    fn nested(&mut self, span: Span, walk: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        if self.depth >= self.max_depth {
+            return Err(Error::compile(
+                ErrorCode::TooMuchNesting,
+                format!("Too much nesting: more than {} levels", self.max_depth),
+                Some(span),
+            ));
+        }
+        self.depth += 1;
+        let result = walk(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Walk `walk` in a new scope inside the current one
+    fn scope(&mut self, walk: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        let parent = self.current;
+        self.scopes.push(ScopeNode { parent: Some(parent), declarations: Vec::new(), keeps_slots: false });
+        self.current = self.scopes.len() - 1;
+        let result = walk(self);
+        if self.scopes[self.current].keeps_slots {
+            self.scopes[parent].keeps_slots = true;
+        }
+        self.current = parent;
+        result
+    }
+
+    /// Declare `name` in the current scope
+    fn declare_lexical(&mut self, name: &str, is_const: bool, span: Span) -> Result<()> {
+        let scope = &mut self.scopes[self.current];
+        let parameter = self.params.iter().find(|(param, _)| param == name).filter(|_| self.current == 0);
+        let earlier = scope.declarations.iter().find(|declaration| declaration.name == name);
+        if let Some(first) = parameter.map(|(_, span)| *span).or(earlier.map(|declaration| declaration.span)) {
+            return Err(duplicate_declaration(name, span, first));
+        }
+        scope.declarations.push(Declaration { name: name.to_string(), is_const, is_lexical: true, span });
+        scope.keeps_slots |= is_const;
+        Ok(())
+    }
+
+    /// Note that the current scope creates a closure
+    fn closure(&mut self) {
+        self.scopes[self.current].keeps_slots = true;
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> Result<()> {
+        self.nested(statement.span(), |walk| walk.statement_at_depth(statement))
+    }
+
+    fn statement_at_depth(&mut self, statement: &Stmt) -> Result<()> {
+        match statement {
+            Stmt::Expression(expr) => self.expr(expr),
+            Stmt::VarDecl { kind, name, init, span } => {
+                self.declaration(*kind, name, *span)?;
+                match init {
+                    Some(init) => self.expr(init),
+                    None => Ok(()),
+                }
+            }
+            Stmt::FunctionDecl { name, span, .. } | Stmt::ClassDecl { name, span, .. } => {
+                self.declare_lexical(name, false, *span)?;
+                self.closure();
+                Ok(())
+            }
+            Stmt::If { test, then_stmt, else_stmt, .. } => {
+                self.expr(test)?;
+                self.statement(then_stmt)?;
+                match else_stmt {
+                    Some(else_stmt) => self.statement(else_stmt),
+                    None => Ok(()),
+                }
+            }
+            Stmt::While { test, body, .. } => {
+                self.expr(test)?;
+                self.statement(body)
+            }
+            // In the order the compiler visits them: init, test, body, update
+            Stmt::For { init, test, update, body, .. } => self.scope(|walk| {
+                if let Some(init) = init {
+                    walk.statement(init)?;
+                }
+                if let Some(test) = test {
+                    walk.expr(test)?;
+                }
+                walk.statement(body)?;
+                match update {
+                    Some(update) => walk.expr(update),
+                    None => Ok(()),
+                }
+            }),
+            Stmt::ForOf { left, right, body, .. } => self.scope(|walk| {
+                walk.expr(right)?;
+                if let Stmt::VarDecl { kind, name, span, .. } = left.as_ref() {
+                    walk.declaration(*kind, name, *span)?;
+                }
+                walk.statement(body)
+            }),
+            Stmt::Block { statements, .. } => self.scope(|walk| {
+                for statement in statements {
+                    walk.statement(statement)?;
+                }
+                Ok(())
+            }),
+            Stmt::Return { value: Some(value), .. } => self.expr(value),
+            Stmt::Return { value: None, .. }
+            | Stmt::Break { .. }
+            | Stmt::Continue { .. }
+            | Stmt::Debugger { .. }
+            | Stmt::Empty { .. } => Ok(()),
+        }
+    }
+
+    /// Record a `var`, `let` or `const` declaration
+    fn declaration(&mut self, kind: VarKind, name: &str, span: Span) -> Result<()> {
+        match kind {
+            VarKind::Var => {
+                let declaration = Declaration { name: name.to_string(), is_const: false, is_lexical: false, span };
+                self.vars.push((declaration, self.current));
+                Ok(())
+            }
+            VarKind::Let => self.declare_lexical(name, false, span),
+            VarKind::Const => self.declare_lexical(name, true, span),
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<()> {
+        self.nested(expr.span(), |walk| walk.expr_at_depth(expr))
+    }
+
+    /// Walk the parts of `expr` the compiler compiles, in its order
+    fn expr_at_depth(&mut self, expr: &Expr) -> Result<()> {
+        match expr {
+            Expr::Literal(_) | Expr::Identifier { .. } | Expr::This { .. } => Ok(()),
+            // Operator chains nest on the left; walk them without
+            // recursing, as the compiler does
+            Expr::Binary { .. } => {
+                let mut operands = Vec::new();
+                let mut operand = expr;
+                while let Expr::Binary { left, right, .. } = operand {
+                    operands.push(right.as_ref());
+                    operand = left;
+                }
+                self.expr(operand)?;
+                for operand in operands.into_iter().rev() {
+                    self.expr(operand)?;
+                }
+                Ok(())
+            }
+            Expr::Unary { operand, .. } | Expr::PostfixUnary { operand, .. } => self.expr(operand),
+            Expr::Assignment { left, right, .. } => {
+                self.expr(left)?;
+                self.expr(right)
+            }
+            Expr::Member { object, property, computed, .. } => {
+                self.expr(object)?;
+                if *computed {
+                    self.expr(property)?;
+                }
+                Ok(())
+            }
+            Expr::Call { callee: target, args, .. } | Expr::New { callee: target, args, .. } => {
+                self.expr(target)?;
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                Ok(())
+            }
+            Expr::Object { properties, .. } => {
+                for definition in properties {
+                    match definition {
+                        PropertyDefinition::Property(property) => {
+                            if let PropertyKey::Computed(key) = &property.key {
+                                self.expr(key)?;
+                            }
+                            self.expr(&property.value)?;
+                        }
+                        PropertyDefinition::SpreadProperty { argument, .. } => self.expr(argument)?,
+                    }
+                }
+                Ok(())
+            }
+            Expr::Array { elements, .. } => {
+                for element in elements.iter().flatten() {
+                    self.expr(element)?;
+                }
+                Ok(())
+            }
+            Expr::Yield { argument: Some(argument), .. } | Expr::Await { argument, .. } => self.expr(argument),
+            Expr::Yield { argument: None, .. } => Ok(()),
+            Expr::Function { .. } | Expr::Arrow { .. } | Expr::Class { name: None, .. } => {
+                self.closure();
+                Ok(())
+            }
+            // The name is bound inside the class only
+            Expr::Class { name: Some(name), span, .. } => self.scope(|walk| {
+                walk.declare_lexical(name, false, *span)?;
+                walk.closure();
+                Ok(())
+            }),
+        }
+    }
+
+    /// Check the `var`s against the lexical declarations and give every
+    /// binding its slot
+    fn finish(mut self, first_slot: u16, globals: bool) -> Result<FunctionLayout> {
+        // A `var` may not share its name with a lexical declaration of
+        // any scope it is hoisted out of, or of the function scope
+        for (var, scope) in &self.vars {
+            let mut scope = Some(*scope);
+            while let Some(index) = scope {
+                let node = &self.scopes[index];
+                if let Some(lexical) = node.declarations.iter().find(|declaration| declaration.name == var.name) {
+                    let (first, later) = if lexical.span.start > var.span.start {
+                        (var.span, lexical.span)
+                    } else {
+                        (lexical.span, var.span)
+                    };
+                    return Err(duplicate_declaration(&var.name, later, first));
+                }
+                scope = node.parent;
+            }
+        }
+
+        // The function scope holds its lexical declarations and every
+        // `var` not named after a parameter, each name once
+        let mut hoisted: Vec<Declaration> = Vec::new();
+        for (var, _) in self.vars.drain(..) {
+            let is_parameter = self.params.iter().any(|(param, _)| *param == var.name);
+            if !is_parameter && !hoisted.iter().any(|declaration| declaration.name == var.name) {
+                hoisted.push(var);
+            }
+        }
+        self.scopes[0].declarations.append(&mut hoisted);
+        self.scopes[0].declarations.sort_by_key(|declaration| declaration.span.start);
+
+        let mut slots = Slots { next: first_slot, count: first_slot };
+        let mut scopes = Vec::with_capacity(self.scopes.len());
+        // Scopes still open, as (index, first slot), innermost last
+        let mut open: Vec<(usize, u16)> = Vec::new();
+        for (index, node) in self.scopes.iter().enumerate() {
+            while let Some(&(top, first)) = open.last() {
+                if Some(top) == node.parent {
+                    break;
+                }
+                if !self.scopes[top].keeps_slots {
+                    slots.next = first;
+                }
+                open.pop();
+            }
+            let first = slots.next;
+            let mut bindings = Vec::with_capacity(node.declarations.len());
+            for declaration in &node.declarations {
+                let slot = if globals && index == 0 {
+                    None
+                } else {
+                    Some(slots.allocate(declaration)?)
+                };
+                bindings.push(Binding {
+                    name: declaration.name.clone(),
+                    slot,
+                    is_const: declaration.is_const,
+                    is_lexical: declaration.is_lexical,
+                    span: declaration.span,
+                });
+            }
+            scopes.push(ScopeLayout { first_slot: first, bindings });
+            open.push((index, first));
+        }
+        Ok(FunctionLayout { scopes, locals_count: slots.count })
+    }
+}
+
+/// Slot allocation while laying out the scopes
+struct Slots {
+    /// First slot not in use
+    next: u16,
+    /// Slots used so far
+    count: u16,
+}
+
+impl Slots {
+    fn allocate(&mut self, declaration: &Declaration) -> Result<LocalIndex> {
+        // Never a slot a scope that ended has used
+        if declaration.is_const {
+            self.next = self.count;
+        }
+        let slot = LocalIndex(self.next);
+        self.next = self.next.checked_add(1)
+            .ok_or_else(|| Error::compile(ErrorCode::CompilerLimit, "Too many local variables", Some(declaration.span)))?;
+        self.count = self.count.max(self.next);
+        Ok(slot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn layout(source: &str) -> Result<FunctionLayout> {
+        let program = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        function_layout(&[], &program.statements, 0, false, 0, 256)
+    }
+
+    /// Each scope's bindings as `name@slot`
+    fn slots(source: &str) -> Vec<Vec<String>> {
+        layout(source).unwrap().scopes.iter()
+            .map(|scope| scope.bindings.iter().map(|binding| format!("{}@{}", binding.name, binding.slot.unwrap().0)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_vars_are_hoisted_to_the_function_scope() {
+        assert_eq!(slots("let a; { var b; { var c; } } var b;"), [vec!["a@0", "b@1", "c@2"], vec![], vec![]]);
+        assert_eq!(slots("for (var i = 0; ;) { let j; }"), [vec!["i@0"], vec![], vec!["j@1"]]);
+    }
+
+    #[test]
+    fn test_sibling_scopes_share_slots() {
+        assert_eq!(slots("{ let a; let b; } { let c; }"), [vec![], vec!["a@0", "b@1"], vec!["c@0"]]);
+        assert_eq!(layout("{ let a; let b; } { let c; }").unwrap().locals_count, 2);
+        // A closure or a `const` keeps the scope's slots
+        assert_eq!(slots("{ let a; f(() => a); } { let b; }"), [vec![], vec!["a@0"], vec!["b@1"]]);
+        assert_eq!(slots("{ let a; } { let b; { const c = 1; } } { let d; }"), [vec![], vec!["a@0"], vec!["b@0"], vec!["c@1"], vec!["d@2"]]);
+    }
+
+    #[test]
+    fn test_var_conflicting_with_lexical_declaration() {
+        let error = layout("{ let x; { var x; } }").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::DuplicateDeclaration);
+        assert_eq!(error.span().unwrap().start, 11);
+        assert!(layout("let x; var x;").is_err());
+        assert!(layout("var x; var x; { let y; } { var y; }").is_ok());
+    }
+}
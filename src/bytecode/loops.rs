@@ -0,0 +1,63 @@
+//! Loops in compiled code, found from their back edges
+//!
+//! The compiler emits structured control flow: the only jumps that go
+//! backward are the ones that run a loop again, whether at the end of its
+//! body or from a `continue`. So a backward jump at `site` to `header`
+//! closes a loop whose code is every instruction from `header` to
+//! `site`, and a forward jump out of that range leaves it. Loops are
+//! either disjoint or nested, never partly overlapping.
+
+use std::ops::RangeInclusive;
+use super::function::BytecodeFunction;
+
+/// A loop: the instructions from its header to its last back edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Loop {
+    /// The first instruction of the loop, where each iteration starts
+    pub header: usize,
+    /// The last backward jump to the header
+    pub back_edge: usize,
+}
+
+impl Loop {
+    /// The instructions of the loop, header and back edge included
+    pub fn body(&self) -> RangeInclusive<usize> {
+        self.header..=self.back_edge
+    }
+
+    /// Whether the instruction at `offset` is part of the loop
+    pub fn contains(&self, offset: usize) -> bool {
+        self.body().contains(&offset)
+    }
+}
+
+/// The loops of `function`, ordered by header, outer loops before the
+/// loops nested in them
+///
+/// Several back edges to one header make one loop: a `continue` that
+/// jumps back to the test, or nested `do`-`while` loops whose bodies start
+/// together, which then count as the outer one. Nested functions are not
+/// searched.
+pub fn find_loops(function: &BytecodeFunction) -> Vec<Loop> {
+    let mut loops: Vec<Loop> = Vec::new();
+    for (site, instruction) in function.bytecode.iter().enumerate() {
+        let Some(header) = instruction.jump_offset().and_then(|offset| offset.target(site)) else {
+            continue;
+        };
+        if header > site {
+            continue;
+        }
+        match loops.iter_mut().find(|found| found.header == header) {
+            Some(found) => found.back_edge = found.back_edge.max(site),
+            None => loops.push(Loop { header, back_edge: site }),
+        }
+    }
+    // An inner loop's back edge comes before its outer loop's
+    loops.sort_by_key(|found| found.header);
+    loops
+}
+
+/// Whether the instruction at `offset` is inside any of `loops`
+pub fn in_any_loop(loops: &[Loop], offset: usize) -> bool {
+    loops.iter().any(|found| found.contains(offset))
+}
@@ -15,14 +15,23 @@
 //!
 //! Instructions are designed to be compact while maintaining readability:
 //! - Most instructions are 1-3 bytes
-//! - Operands use typed indices (ConstIndex, LocalIndex, etc.)
-//! - Jump offsets are signed 16-bit for ±32KB range
+//! - Operands are newtypes (ConstIndex, LocalIndex, JumpOffset), so one kind
+//!   of index cannot be passed for another
+//! - Jump offsets are signed 16-bit for a ±32K instruction range
 
 pub mod instruction;
 pub mod function;
 pub mod constant_pool;
+pub mod string_table;
 pub mod disassembler;
+pub mod dump;
+pub mod diff;
 pub mod compiler;
+pub mod verifier;
+pub mod loops;
+mod escape;
+mod hoist;
+mod layout;
 
 #[cfg(test)]
 mod tests;
@@ -31,5 +40,11 @@ mod tests;
 pub use instruction::*;
 pub use function::*;
 pub use constant_pool::*;
+pub use string_table::*;
 pub use disassembler::*;
-pub use compiler::*;
\ No newline at end of file
+pub use dump::*;
+pub use diff::{diff, BytecodeDiff, ConstantChange, EditKind, FieldChange, InstructionEdit, Presence};
+pub use compiler::*;
+pub use verifier::*;
+pub use loops::{find_loops, Loop};
+pub use hoist::DEFAULT_MAX_HOISTED_CONSTANTS;
\ No newline at end of file
@@ -0,0 +1,94 @@
+//! Program-wide table of property names
+//!
+//! Every function compiled from one program shares a single table, so a
+//! name such as `length` is stored once however many functions use it.
+//! Property-name constants hold a [`NameId`] into the table. The VM
+//! resolves a table into interned atoms the first time it runs code from
+//! it, after which a `NameId` is a property key with no hashing of text.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+
+/// Index of a name in a [`StringTable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NameId(pub u32);
+
+impl NameId {
+    /// Get the index as a usize for table access
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for NameId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Interned names, in the order they were first added
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StringTable {
+    names: Vec<Arc<str>>,
+    index: HashMap<Arc<str>, NameId>,
+}
+
+impl StringTable {
+    /// Create an empty table
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// The id of `name`, adding it if it is new
+    pub fn intern(&mut self, name: &str) -> NameId {
+        if let Some(&id) = self.index.get(name) {
+            return id;
+        }
+        let id = NameId(self.names.len() as u32);
+        let name: Arc<str> = Arc::from(name);
+        self.names.push(name.clone());
+        self.index.insert(name, id);
+        id
+    }
+    
+    /// The name with the given id
+    pub fn get(&self, id: NameId) -> Option<&Arc<str>> {
+        self.names.get(id.index())
+    }
+    
+    /// Number of names in the table
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    
+    /// Check if the table is empty
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    
+    /// Iterate over all names with their ids
+    pub fn iter(&self) -> impl Iterator<Item = (NameId, &str)> {
+        self.names.iter().enumerate().map(|(i, name)| (NameId(i as u32), &**name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_intern_reuses_ids() {
+        let mut table = StringTable::new();
+        let length = table.intern("length");
+        let next = table.intern("next");
+        assert_eq!(table.intern("length"), length);
+        assert_ne!(length, next);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get(next).map(|name| &**name), Some("next"));
+        assert_eq!(table.get(NameId(2)), None);
+        assert_eq!(table.iter().map(|(_, name)| name).collect::<Vec<_>>(), ["length", "next"]);
+    }
+}
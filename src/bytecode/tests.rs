@@ -5,36 +5,47 @@
 
 use super::*;
 
+/// Compile `source` as a script, for tests that look at the bytecode
+#[cfg(test)]
+fn compile(source: &str) -> BytecodeFunction {
+    let program = crate::parser::Parser::from_source(crate::lexer::Lexer::new(source)).parse().unwrap();
+    Compiler::new_main(source).compile(&program).unwrap()
+}
+
 #[cfg(test)]
 mod instruction_tests {
     use super::*;
     
     #[test]
     fn test_instruction_display() {
-        let instr = Bytecode::LdaConst(42);
+        let instr = Bytecode::LdaConst(ConstIndex(42));
         assert_eq!(format!("{}", instr), "LdaConst #42");
         
         let instr = Bytecode::Add;
         assert_eq!(format!("{}", instr), "Add");
         
-        let instr = Bytecode::Jump(-5);
+        let instr = Bytecode::Jump(JumpOffset(-5));
         assert_eq!(format!("{}", instr), "Jump -5");
+        
+        assert_eq!(format!("{}", Bytecode::LdaSmi(-7)), "LdaSmi -7");
+        assert_eq!(format!("{}", Bytecode::Inc), "Inc");
+        assert_eq!(format!("{}", Bytecode::Dec), "Dec");
     }
     
     #[test]
     fn test_instruction_analysis() {
         // Test control flow detection
-        assert!(Bytecode::Jump(0).is_control_flow());
-        assert!(Bytecode::JumpIfFalse(0).is_control_flow());
+        assert!(Bytecode::Jump(JumpOffset(0)).is_control_flow());
+        assert!(Bytecode::JumpIfFalse(JumpOffset(0)).is_control_flow());
         assert!(Bytecode::Call(2).is_control_flow());
         assert!(Bytecode::Return.is_control_flow());
         assert!(!Bytecode::Add.is_control_flow());
-        assert!(!Bytecode::LdaConst(0).is_control_flow());
+        assert!(!Bytecode::LdaConst(ConstIndex(0)).is_control_flow());
         
         // Test accumulator modification
-        assert!(Bytecode::LdaConst(0).modifies_accumulator());
+        assert!(Bytecode::LdaConst(ConstIndex(0)).modifies_accumulator());
         assert!(Bytecode::Add.modifies_accumulator());
-        assert!(!Bytecode::StaLocal(0).modifies_accumulator());
+        assert!(!Bytecode::StaLocal(LocalIndex(0)).modifies_accumulator());
         assert!(!Bytecode::Push.modifies_accumulator());
         
         // Test stack effects
@@ -42,7 +53,49 @@ mod instruction_tests {
         assert_eq!(Bytecode::Sub.stack_pop_count(), 1);
         assert_eq!(Bytecode::Call(3).stack_pop_count(), 4); // 3 args + function
         assert_eq!(Bytecode::Push.stack_push_count(), 1);
-        assert_eq!(Bytecode::LdaConst(0).stack_pop_count(), 0);
+        assert_eq!(Bytecode::LdaConst(ConstIndex(0)).stack_pop_count(), 0);
+    }
+    
+    #[test]
+    fn test_stores_preserve_accumulator() {
+        // Assignment expressions rely on the stored value staying in acc
+        let stores = [
+            Bytecode::StaLocal(LocalIndex(0)),
+            Bytecode::StaGlobal(ConstIndex(0)),
+            Bytecode::StaContextSlot(1, LocalIndex(0)),
+            Bytecode::StaNamed(ConstIndex(0)),
+            Bytecode::StaKeyed,
+            Bytecode::StaArrayElement(0),
+            Bytecode::DefineNamedProperty(ConstIndex(0)),
+            Bytecode::DefineKeyedProperty,
+        ];
+        for store in &stores {
+            assert!(!store.modifies_accumulator(), "{} must not modify the accumulator", store);
+        }
+    }
+    
+    #[test]
+    fn test_operand_ranges() {
+        assert_eq!(ConstIndex::try_from(65535), Ok(ConstIndex(u16::MAX)));
+        let error = ConstIndex::try_from(65536).unwrap_err();
+        assert_eq!(error.to_string(), "constant index 65536 is out of range");
+        assert_eq!(LocalIndex::try_from(7).map(LocalIndex::index), Ok(7));
+        assert!(LocalIndex::try_from(usize::MAX).is_err());
+    }
+    
+    #[test]
+    fn test_jump_offsets() {
+        // Offsets count from the instruction after the jump
+        assert_eq!(JumpOffset::forward(3, 4), Ok(JumpOffset(0)));
+        assert_eq!(JumpOffset::forward(3, 10), Ok(JumpOffset(6)));
+        assert_eq!(JumpOffset::backward(10, 2), Ok(JumpOffset(-9)));
+        assert_eq!(JumpOffset::backward(5, 5), Ok(JumpOffset(-1)));
+        assert_eq!(JumpOffset(6).target(3), Some(10));
+        assert_eq!(JumpOffset(-9).target(10), Some(2));
+        assert_eq!(JumpOffset(-5).target(1), None);
+        
+        let error = JumpOffset::forward(0, 40_000).unwrap_err();
+        assert_eq!(error.to_string(), "jump offset 39999 is out of range");
     }
 }
 
@@ -61,10 +114,10 @@ mod constant_pool_tests {
         let idx_null = pool.add_null();
         
         // Check indices are sequential
-        assert_eq!(idx_num, 0);
-        assert_eq!(idx_str, 1);
-        assert_eq!(idx_bool, 2);
-        assert_eq!(idx_null, 3);
+        assert_eq!(idx_num, ConstIndex(0));
+        assert_eq!(idx_str, ConstIndex(1));
+        assert_eq!(idx_bool, ConstIndex(2));
+        assert_eq!(idx_null, ConstIndex(3));
         
         // Check values can be retrieved
         assert!(matches!(pool.get(idx_num), Some(ConstantValue::Number(_))));
@@ -113,6 +166,102 @@ mod constant_pool_tests {
         assert_eq!(ConstantValue::Boolean(true).type_name(), "boolean");
         assert_eq!(ConstantValue::Null.type_name(), "object"); // JS quirk: typeof null === "object"
         assert_eq!(ConstantValue::Undefined.type_name(), "undefined");
+        
+        let template = ConstantValue::ObjectTemplate(vec![
+            ("a b".to_string(), ConstantValue::ArrayTemplate(vec![None])),
+        ]);
+        assert_eq!(template.type_name(), "object");
+        assert!(template.is_truthy());
+        assert_eq!(template.to_string(), r#"{ "a b": [,] }"#);
+        assert_eq!(ConstantValue::ObjectTemplate(Vec::new()).to_string(), "{}");
+    }
+    
+    #[test]
+    fn test_iteration_order_is_insertion_order() {
+        let mut pool = ConstantPool::new();
+        let values = [
+            ConstantValue::String("b".to_string()),
+            ConstantValue::Number(HashableF64(2.0)),
+            ConstantValue::String("a".to_string()),
+            ConstantValue::Undefined,
+        ];
+        for value in &values {
+            pool.add_constant(value.clone());
+        }
+        // Adding again, or more, doesn't reorder anything
+        pool.add_string("a".to_string());
+        pool.add_boolean(false);
+        let entries: Vec<_> = pool.iter().map(|(index, value)| (index.0, value.kind())).collect();
+        assert_eq!(entries, [
+            (0, ConstantKind::String), (1, ConstantKind::Number), (2, ConstantKind::String),
+            (3, ConstantKind::Undefined), (4, ConstantKind::Boolean),
+        ]);
+        assert_eq!(pool.iter().map(|(_, value)| value.clone()).take(4).collect::<Vec<_>>(), values);
+        assert_eq!(pool.find(&ConstantValue::String("a".to_string())), Some(ConstIndex(2)));
+        assert_eq!(pool.find(&ConstantValue::Null), None);
+        assert_eq!(pool.to_string(), "Constants [5]:\n  #0: \"b\"\n  #1: 2\n  #2: \"a\"\n  #3: undefined\n  #4: false\n");
+    }
+    
+    #[test]
+    fn test_typed_getters() {
+        let mut pool = ConstantPool::new();
+        let number = pool.add_number(1.5);
+        let string = pool.add_string("s".to_string());
+        let boolean = pool.add_boolean(true);
+        let name = pool.add_property_name(NameId(0));
+        assert_eq!(pool.get_number(number), Some(1.5));
+        assert_eq!(pool.get_string(string), Some("s"));
+        assert_eq!(pool.get_boolean(boolean), Some(true));
+        assert_eq!(pool.get_property_name(name), Some(NameId(0)));
+        assert_eq!(pool.get_number(string), None);
+        assert_eq!(pool.get_string(name), None);
+        assert_eq!(pool.get_number(ConstIndex(9)), None);
+    }
+    
+    #[test]
+    fn test_replace() {
+        let mut pool = ConstantPool::new();
+        let one = pool.add_number(1.0);
+        let two = pool.add_number(2.0);
+        let text = pool.add_string("x".to_string());
+        
+        assert_eq!(pool.replace(one, ConstantValue::Number(HashableF64(10.0))).unwrap(), ConstantValue::Number(HashableF64(1.0)));
+        assert_eq!(pool.get_number(one), Some(10.0));
+        assert_eq!(pool.find(&ConstantValue::Number(HashableF64(10.0))), Some(one));
+        assert_eq!(pool.find(&ConstantValue::Number(HashableF64(1.0))), None);
+        // The old value is new again; the rest keep their indices
+        assert_eq!(pool.add_number(1.0), ConstIndex(3));
+        assert_eq!(pool.get_number(two), Some(2.0));
+        
+        // Replacing with an existing value leaves two equal entries;
+        // lookups find the lower index
+        pool.replace(two, ConstantValue::Number(HashableF64(10.0))).unwrap();
+        assert_eq!(pool.add_number(10.0), one);
+        pool.replace(one, ConstantValue::Number(HashableF64(5.0))).unwrap();
+        assert_eq!(pool.add_number(10.0), two);
+        
+        pool.replace(text, ConstantValue::String("y".to_string())).unwrap();
+        assert_eq!(pool.add_string("y".to_string()), text);
+        assert_eq!(pool.add_string("x".to_string()), ConstIndex(4));
+        
+        let error = pool.replace(text, ConstantValue::Number(HashableF64(0.0))).unwrap_err();
+        assert_eq!(error.to_string(), "Runtime error: Cannot replace string constant #2 with a number");
+        let error = pool.replace(ConstIndex(40), ConstantValue::Null).unwrap_err();
+        assert_eq!(error.to_string(), "Runtime error: No constant #40 to replace in a pool of 5");
+    }
+    
+    #[test]
+    fn test_capacity_boundary() {
+        let mut pool = ConstantPool::with_capacity(MAX_CONSTANTS);
+        for n in 0..MAX_CONSTANTS {
+            pool.add_number(n as f64);
+        }
+        assert_eq!(pool.len(), MAX_CONSTANTS);
+        assert_eq!(pool.get_number(ConstIndex(u16::MAX)), Some(65535.0));
+        // Existing constants are still found; new ones have no index left
+        assert_eq!(pool.try_add_constant(ConstantValue::Number(HashableF64(7.0))), Ok(ConstIndex(7)));
+        assert!(pool.try_add_constant(ConstantValue::Null).is_err());
+        assert_eq!(pool.len(), MAX_CONSTANTS);
     }
     
     #[test]
@@ -165,14 +314,14 @@ mod bytecode_function_tests {
     fn test_instruction_addition() {
         let mut func = BytecodeFunction::new_main();
         
-        func.add_instruction(Bytecode::LdaConst(0));
+        func.add_instruction(Bytecode::LdaConst(ConstIndex(0)));
         func.add_instruction(Bytecode::Return);
         
         assert_eq!(func.bytecode.len(), 2);
         assert_eq!(func.current_offset(), 2);
         
         // Test getting instructions
-        assert!(matches!(func.get_instruction(0), Some(Bytecode::LdaConst(0))));
+        assert!(matches!(func.get_instruction(0), Some(Bytecode::LdaConst(ConstIndex(0)))));
         assert!(matches!(func.get_instruction(1), Some(Bytecode::Return)));
         assert!(func.get_instruction(2).is_none());
     }
@@ -181,13 +330,13 @@ mod bytecode_function_tests {
     fn test_instruction_patching() {
         let mut func = BytecodeFunction::new_main();
         
-        func.add_instruction(Bytecode::Jump(0)); // Placeholder
+        func.add_instruction(Bytecode::Jump(JumpOffset(0))); // Placeholder
         func.add_instruction(Bytecode::Return);
         
         // Patch the jump instruction
-        func.patch_instruction(0, Bytecode::Jump(5));
+        func.patch_instruction(0, Bytecode::Jump(JumpOffset(5)));
         
-        assert!(matches!(func.get_instruction(0), Some(Bytecode::Jump(5))));
+        assert!(matches!(func.get_instruction(0), Some(Bytecode::Jump(JumpOffset(5)))));
     }
     
     #[test]
@@ -225,12 +374,12 @@ mod bytecode_function_tests {
     fn test_function_stats() {
         let mut func = BytecodeFunction::new("test".to_string(), 1, 3, 0);
         
-        func.add_instruction(Bytecode::LdaConst(0));
-        func.add_instruction(Bytecode::JumpIfFalse(2));
+        func.add_instruction(Bytecode::LdaConst(ConstIndex(0)));
+        func.add_instruction(Bytecode::JumpIfFalse(JumpOffset(2)));
         func.add_instruction(Bytecode::Return);
         
         let const_idx = func.constants.add_number(42.0);
-        assert_eq!(const_idx, 0);
+        assert_eq!(const_idx, ConstIndex(0));
         
         let stats = func.stats();
         
@@ -258,8 +407,8 @@ mod integration_tests {
         // Add           // a + b
         // Return        // return result
         
-        func.add_instruction(Bytecode::LdaLocal(0));
-        func.add_instruction(Bytecode::LdaLocal(1));
+        func.add_instruction(Bytecode::LdaLocal(LocalIndex(0)));
+        func.add_instruction(Bytecode::LdaLocal(LocalIndex(1)));
         func.add_instruction(Bytecode::Add);
         func.add_instruction(Bytecode::Return);
         
@@ -299,4 +448,422 @@ mod integration_tests {
         assert!(disassembly.contains("Hello, "));
         assert!(disassembly.contains("World!"));
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod expression_main_tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::vm::{Value, VM};
+    
+    fn run_expression(source: &str) -> Value {
+        let expr = Parser::from_source(Lexer::new(source)).parse_expression_only().unwrap();
+        let function = Compiler::new_main(source).compile_expression_main(&expr).unwrap();
+        VM::new().execute(function).unwrap()
+    }
+    
+    #[test]
+    fn test_expression_with_function_expression_runs() {
+        assert_eq!(run_expression("(function (n) { let twice = n * 2; return twice; })(21)"), Value::Number(42.0));
+        assert_eq!(run_expression("((a) => (b) => a + b)(1)(2)"), Value::Number(3.0));
+        assert_eq!(run_expression("{ total: [1, 2].length }.total"), Value::Number(2.0));
+    }
+}
+
+#[cfg(test)]
+mod compiler_diagnostics_tests {
+    use super::*;
+    use crate::error::{Diagnostic, Span};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    fn warnings(source: &str) -> Vec<Diagnostic> {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let (_, diagnostics) = Compiler::new_main(source).compile_with_diagnostics(&program).unwrap();
+        diagnostics
+    }
+    
+    fn messages(source: &str) -> Vec<String> {
+        warnings(source).into_iter().map(|d| d.message).collect()
+    }
+    
+    #[test]
+    fn test_unused_variable() {
+        let diagnostics = warnings("let used = 1; let unused = 2; used;");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "variable 'unused' is declared but never used");
+        let span = diagnostics[0].span.unwrap();
+        assert_eq!((span.start, span.end, span.line), (14, 17, 1));
+    }
+    
+    #[test]
+    fn test_writes_alone_are_not_uses() {
+        assert_eq!(messages("let x = 1; x = 2;"), vec!["variable 'x' is declared but never used"]);
+        assert!(messages("let x = 1; x = x + 1;").is_empty());
+    }
+    
+    #[test]
+    fn test_reads_from_closures_and_blocks_count() {
+        assert!(messages("let x = 1; let f = function () { return function () { return x; }; }; f;").is_empty());
+        assert_eq!(messages("{ let inner = 1; }"), vec!["variable 'inner' is declared but never used"]);
+        // A read counts for the variable it resolves to, not one it shadows
+        assert_eq!(messages("let x = 1; { let x = 2; x; }"), vec!["variable 'x' is declared but never used"]);
+        assert_eq!(messages("let x = 1; { let x = 2; let f = () => x; f; }"), vec!["variable 'x' is declared but never used"]);
+        // Parameters and underscore names are exempt
+        assert!(messages("function f(a, b) { let _ignored = 1; } f;").is_empty());
+    }
+    
+    #[test]
+    fn test_unreachable_code_after_return() {
+        let diagnostics = warnings("function f() {\n  return 1;\n  g();\n  h();\n}\nf;");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unreachable code after return");
+        assert_eq!(diagnostics[0].span.unwrap().line, 3);
+        
+        assert_eq!(messages("while (x) { break; y(); }"), vec!["unreachable code after break"]);
+        assert_eq!(messages("function f() { if (a) { return; } else { return 2; } z(); } f;"),
+                   vec!["unreachable code after return"]);
+    }
+    
+    #[test]
+    fn test_reachable_code_is_not_flagged() {
+        assert!(messages("function f() { if (a) { return 1; } return 2; } f;").is_empty());
+        assert!(messages("function f() { return 1; function _g() {} } f;").is_empty());
+    }
+    
+    #[test]
+    fn test_constant_conditions() {
+        let diagnostics = warnings("if (0) { a(); }");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "condition is always falsy");
+        assert_eq!(diagnostics[0].span, Some(Span::new(4, 5, 1, 5)));
+        
+        let diagnostics = warnings("if ({}) { a(); }");
+        assert_eq!(diagnostics[0].message, "condition is always truthy");
+        assert_eq!(diagnostics[0].span, Some(Span::new(4, 6, 1, 5)));
+        let diagnostics = warnings("x = 1;\nwhile (!'') { a(); }\nfor (; 'go' ;) {}");
+        let spans: Vec<_> = diagnostics.iter().map(|diagnostic| diagnostic.span).collect();
+        assert_eq!(spans, vec![Some(Span::new(14, 17, 2, 8)), Some(Span::new(35, 39, 3, 8))]);
+        
+        // `while (true)` is deliberate, and variable conditions are unknown
+        assert!(messages("while (true) { break; }").is_empty());
+        assert!(messages("if (a) { b(); }").is_empty());
+    }
+}
+
+#[cfg(test)]
+mod compiler_selection_tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    #[test]
+    fn test_small_integers_skip_the_constant_pool() {
+        let function = compile("0; 1; 32767; -2;");
+        assert!(function.constants.is_empty());
+        assert_eq!(function.bytecode[..5], [
+            Bytecode::LdaSmi(0), Bytecode::LdaSmi(1), Bytecode::LdaSmi(32767),
+            Bytecode::LdaSmi(2), Bytecode::UnaryMinus,
+        ]);
+        
+        // Out-of-range and fractional numbers still use the pool
+        let function = compile("32768; 1.5;");
+        assert_eq!(function.constants.len(), 2);
+        assert!(function.bytecode.iter().all(|instr| !matches!(instr, Bytecode::LdaSmi(_))));
+    }
+    
+    #[test]
+    fn test_too_many_constants_is_a_compile_error() {
+        let source: String = (0..MAX_CONSTANTS + 1).map(|n| format!("{}.5;", n)).collect();
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let program = Parser::new(tokens).parse().unwrap();
+        let error = Compiler::new_main(&source).compile(&program).unwrap_err();
+        assert_eq!(error.code(), crate::error::ErrorCode::CompilerLimit);
+    }
+    
+    #[test]
+    fn test_string_literal_concatenation_is_folded() {
+        let function = compile("'a' + 'b' + 'c';");
+        assert_eq!(function.bytecode, vec![Bytecode::LdaConst(ConstIndex(0)), Bytecode::Return]);
+        assert_eq!(function.constants.get(ConstIndex(0)), Some(&ConstantValue::String("abc".to_string())));
+        
+        // Variables are added at runtime
+        assert!(compile("let x = 1; 'a' + x;").bytecode.contains(&Bytecode::Add));
+    }
+    
+    #[test]
+    fn test_addition_folding_follows_evaluation_order() {
+        let folded = |source: &str| {
+            let function = compile(source);
+            assert_eq!(function.bytecode, vec![Bytecode::LdaConst(ConstIndex(0)), Bytecode::Return], "{}", source);
+            function.constants.get(ConstIndex(0)).cloned()
+        };
+        assert_eq!(folded("1 + '2' + 3;"), Some(ConstantValue::String("123".to_string())));
+        // The numbers are added before the string joins in
+        assert_eq!(folded("1 + 2 + '3';"), Some(ConstantValue::String("33".to_string())));
+        assert_eq!(folded("'a' + (1 + 2) + -1 + true + null;"), Some(ConstantValue::String("a3-1truenull".to_string())));
+        
+        // Numeric chains are left alone
+        assert!(compile("1 + 2;").bytecode.contains(&Bytecode::Add));
+        
+        // After a string, runs of constants join into one operand; before
+        // one, a variable might be a number, so nothing joins
+        let function = compile("let x = 1; x + 1 + 2 + 'a' + 3 + 'b';");
+        let adds = function.bytecode.iter().filter(|instr| **instr == Bytecode::Add).count();
+        assert_eq!(adds, 4);
+        assert!(function.constants.iter().any(|(_, c)| *c == ConstantValue::String("3b".to_string())));
+    }
+    
+    #[test]
+    fn test_long_literal_chains_fold_to_one_constant() {
+        let source: Vec<String> = (0..1000).map(|i| format!("'{}'", i % 10)).collect();
+        let function = compile(&format!("{};", source.join(" + ")));
+        let expected: String = (0..1000).map(|i| char::from(b'0' + (i % 10) as u8)).collect();
+        assert_eq!(function.bytecode, vec![Bytecode::LdaConst(ConstIndex(0)), Bytecode::Return]);
+        assert_eq!(function.constants.len(), 1);
+        assert_eq!(function.constants.get(ConstIndex(0)), Some(&ConstantValue::String(expected)));
+    }
+    
+    #[test]
+    fn test_updates_of_variables_use_inc_and_dec() {
+        // A discarded `i++` in a for update is load, step, store
+        let function = compile("for (let i = 0; i < 3; i++) {}");
+        let window = [Bytecode::LdaLocal(LocalIndex(0)), Bytecode::Inc, Bytecode::StaLocal(LocalIndex(0))];
+        assert!(function.bytecode.windows(3).any(|w| w == window));
+        
+        let function = compile("let x = 5; x -= 1; x;");
+        let window = [Bytecode::LdaLocal(LocalIndex(0)), Bytecode::Dec, Bytecode::StaLocal(LocalIndex(0))];
+        assert!(function.bytecode.windows(3).any(|w| w == window));
+        
+        // `+= 1` may concatenate, so it stays an addition
+        assert!(compile("let s = 'a'; s += 1; s;").bytecode.contains(&Bytecode::Add));
+    }
+    
+    #[test]
+    fn test_method_calls_evaluate_in_source_order() {
+        // The object once, kept for `this` and the lookup; the method
+        // before any argument; the arguments left to right
+        let function = compile("o.m(a, b);");
+        let names: Vec<String> = function.bytecode.iter().map(|instr| instr.to_string()).collect();
+        assert_eq!(names, [
+            "LdaGlobal #0", "Push", "Push", "LdaNamed #1", "Push",
+            "LdaGlobal #2", "Push", "LdaGlobal #3", "Push", "CallMethod 2", "Return",
+        ]);
+        
+        // A computed key is evaluated after the object, before the arguments
+        let function = compile("o[k](a);");
+        let names: Vec<String> = function.bytecode.iter().map(|instr| instr.to_string()).collect();
+        assert_eq!(names, [
+            "LdaGlobal #0", "Push", "Push", "LdaGlobal #1", "LdaKeyed", "Push",
+            "LdaGlobal #2", "Push", "CallMethod 1", "Return",
+        ]);
+    }
+    
+    #[test]
+    fn test_constant_literals_use_templates() {
+        let function = compile("({a: 1, b: [true, , 'x'], c: {d: null}, 2: -3});");
+        assert_eq!(function.bytecode, vec![Bytecode::CreateFromTemplate(ConstIndex(0)), Bytecode::Return]);
+        assert_eq!(
+            function.constants.get(ConstIndex(0)).unwrap().to_string(),
+            r#"{ a: 1, b: [true, , "x"], c: { d: null }, "2": -3 }"#,
+        );
+        
+        let function = compile("[1, 'a' + 'b', [], {}];");
+        assert_eq!(function.bytecode, vec![Bytecode::CreateFromTemplate(ConstIndex(0)), Bytecode::Return]);
+        assert_eq!(function.constants.get(ConstIndex(0)).unwrap().to_string(), r#"[1, "ab", [], {}]"#);
+        
+        // Nothing constant, nothing to template
+        let function = compile("let x = 1; [x]; ({x}); ({}); [];");
+        assert!(!function.bytecode.iter().any(|instr| matches!(instr, Bytecode::CreateFromTemplate(_))));
+    }
+    
+    #[test]
+    fn test_mixed_literals_store_only_the_rest() {
+        let function = compile("let x = 1; [1, x, 3];");
+        assert_eq!(function.constants.get(ConstIndex(0)).unwrap().to_string(), "[1, , 3]");
+        let stores: Vec<_> = function.bytecode.iter()
+            .filter(|instr| matches!(instr, Bytecode::StaArrayElement(_)))
+            .collect();
+        assert_eq!(stores, vec![&Bytecode::StaArrayElement(1)]);
+        
+        // A placeholder keeps `x` first; the template stops at the spread
+        let function = compile("let x = 1; ({x, y: 2, ...x, z: 3});");
+        assert_eq!(function.constants.get(ConstIndex(0)).unwrap().to_string(), "{ x: undefined, y: 2 }");
+        let defines = function.bytecode.iter()
+            .filter(|instr| matches!(instr, Bytecode::DefineNamedProperty(_)))
+            .count();
+        assert_eq!(defines, 2);
+        
+        // A key defined by anything but constants is left to run in order
+        let function = compile("let x = 1; ({a: x, b: 2, a: 3});");
+        assert_eq!(function.constants.get(ConstIndex(0)).unwrap().to_string(), "{ a: undefined, b: 2 }");
+        let function = compile("({a: 1, b: 2, a: 3});");
+        assert_eq!(function.constants.get(ConstIndex(0)).unwrap().to_string(), "{ a: 3, b: 2 }");
+    }
+}
+
+#[cfg(test)]
+mod source_text_tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::error::{Diagnostic, Span};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    
+    #[test]
+    fn test_function_source_text() {
+        let source = "let a = 1;\nfunction add(x, y) { return x + y; }\nlet f = async (z) => z * 2;\nclass C { m() { return 1; } }\n";
+        let main = compile(source);
+        assert_eq!(main.source_text(), Some(source));
+        
+        let texts: Vec<_> = main.functions.iter().map(|function| function.source_text().unwrap()).collect();
+        assert_eq!(texts, vec![
+            "function add(x, y) { return x + y; }",
+            "async (z) => z * 2",
+            "class C { m() { return 1; } }",
+            "m() { return 1; }",
+        ]);
+        
+        assert_eq!(main.source_snippet(Span::new(4, 5, 1, 5)), Some("a"));
+        assert_eq!(main.source_snippet(Span::new(0, source.len() + 1, 1, 1)), None);
+        assert_eq!(BytecodeFunction::new_main().source_text(), None);
+    }
+    
+    #[test]
+    fn test_nested_functions_share_one_source() {
+        let mut source = String::new();
+        for i in 0..100 {
+            source.push_str(&format!("function f{}() {{ ", i));
+        }
+        source.push_str("return 1;");
+        source.push_str(&" } ".repeat(100));
+        
+        let main = compile(&source);
+        let shared = main.debug_info.source_code.clone().unwrap();
+        // Main, one hundred nested functions and `shared` itself
+        assert_eq!(Arc::strong_count(&shared), 102);
+        
+        let mut function = &main;
+        for i in 0..100 {
+            function = &function.functions[0];
+            assert!(Arc::ptr_eq(function.debug_info.source_code.as_ref().unwrap(), &shared));
+            assert!(function.source_text().unwrap().starts_with(&format!("function f{}() {{", i)));
+        }
+        assert_eq!(function.source_text(), Some("function f99() { return 1; }"));
+    }
+    
+    #[test]
+    fn test_const_locals_in_the_disassembly() {
+        let function = compile("let a = 1; const b = 2; const c = a + b;");
+        assert_eq!(function.immutable_locals.iter().collect::<Vec<_>>(), [LocalIndex(1), LocalIndex(2)]);
+        let text = Disassembler::new().disassemble_function(&function);
+        assert!(text.contains("Locals: 3 (const 1, 2), Max stack:"), "{}", text);
+        assert!(text.contains("InitLocal 2"), "{}", text);
+    }
+    
+    #[test]
+    fn test_nested_functions_share_property_names() {
+        let main = compile("let o = { length: 1 }; function f() { return o.length; } function g() { o.next = f; return o.length; }");
+        let (f, g) = (&main.functions[0], &main.functions[1]);
+        assert!(Arc::ptr_eq(&main.names, &f.names) && Arc::ptr_eq(&main.names, &g.names));
+        // `length` is stored once for both functions, then `next`
+        assert_eq!(main.names.iter().map(|(_, name)| name).collect::<Vec<_>>(), ["length", "next"]);
+        
+        let length_in = |function: &BytecodeFunction| function.constants.iter()
+            .find_map(|(_, value)| match value {
+                ConstantValue::PropertyName(id) if function.property_name(*id) == Some("length") => Some(*id),
+                _ => None,
+            });
+        assert_eq!(length_in(f), length_in(g));
+        assert!(length_in(f).is_some());
+        assert!(Disassembler::new().disassemble_function(f).contains("LdaNamed #0 (.length)"));
+    }
+    
+    #[test]
+    fn test_diagnostics_underline_one_line_of_a_function() {
+        let source = "function unused() {\n  return 1;\n}\n";
+        let program = Parser::from_source(Lexer::new(source)).parse().unwrap();
+        let (main, diagnostics) = Compiler::new_main(source).compile_with_diagnostics(&program).unwrap();
+        let diagnostic: &Diagnostic = &diagnostics[0];
+        let rendered = main.format_diagnostic(diagnostic);
+        assert!(rendered.contains("1 | function unused() {"));
+        assert_eq!(rendered.matches('^').count(), "function unused() {".len());
+    }
+}
+
+#[cfg(test)]
+mod symbol_table_tests {
+    use super::*;
+    use crate::{Engine, Value};
+    
+    /// Offsets of the instructions equal to `instruction`
+    fn offsets_of(function: &BytecodeFunction, instruction: Bytecode) -> Vec<usize> {
+        function.bytecode.iter().enumerate()
+            .filter(|&(_, at)| *at == instruction)
+            .map(|(offset, _)| offset)
+            .collect()
+    }
+    
+    #[test]
+    fn test_sibling_blocks_share_a_slot_under_their_own_names() {
+        let function = compile("let x = 1;\n{ let a = x + 1; x = a; }\n{ let b = x * 2; x = b; }\nx;");
+        assert_eq!(function.locals_count, 2);
+        let stores = offsets_of(&function, Bytecode::StaLocal(LocalIndex(1)));
+        assert_eq!(stores.len(), 2);
+        assert_eq!(function.local_name(LocalIndex(1), stores[0]), Some("a"));
+        assert_eq!(function.local_name(LocalIndex(1), stores[1]), Some("b"));
+        assert_eq!(function.local_name(LocalIndex(0), stores[1]), Some("x"));
+        // Past the second block the slot holds no variable
+        let last = function.bytecode.len() - 1;
+        assert_eq!(function.local_name(LocalIndex(1), last), None);
+        assert_eq!(function.scope_at(stores[1]), [("x".to_string(), LocalIndex(0)), ("b".to_string(), LocalIndex(1))]);
+        assert_eq!(function.scope_at(last), [("x".to_string(), LocalIndex(0))]);
+        
+        let text = Disassembler::new().disassemble_function(&function);
+        let annotated: Vec<_> = text.lines().filter(|line| line.contains("StaLocal 1")).collect();
+        assert!(annotated[0].contains("; a, line 2") && annotated[1].contains("; b, line 3"), "{}", text);
+    }
+    
+    #[test]
+    fn test_inner_declarations_shadow_outer_ones() {
+        let function = compile("let x = 1; { let x = 2; x; } x;");
+        let loads = offsets_of(&function, Bytecode::LdaLocal(LocalIndex(1)));
+        assert_eq!(function.scope_at(loads[0]), [("x".to_string(), LocalIndex(1))]);
+        assert_eq!(function.local_name(LocalIndex(0), loads[0]), Some("x"));
+    }
+    
+    #[test]
+    fn test_captured_and_const_slots_are_not_reused() {
+        // The closure shares the block's slot, so `b` must not take it
+        let source = "let f; { let a = 1; f = () => a; } { let b = 2; b; } f();";
+        let function = compile(source);
+        let stores = offsets_of(&function, Bytecode::StaLocal(LocalIndex(1)));
+        assert_eq!(function.local_name(LocalIndex(1), stores[0]), Some("a"));
+        assert_eq!(function.locals_count, 3);
+        assert_eq!(Engine::new().execute(source).unwrap(), Value::Number(1.0));
+        
+        // Only `InitLocal` stores to a `const`, so it never takes a slot
+        // something else stored to
+        let source = "{ let a = 1; a; } const c = 2; { let b = 3; b; } c;";
+        let function = compile(source);
+        let consts: Vec<_> = function.immutable_locals.iter().collect();
+        assert_eq!(consts.len(), 1);
+        assert!(!function.bytecode.contains(&Bytecode::StaLocal(consts[0])));
+        assert_eq!(Engine::new().execute(source).unwrap(), Value::Number(2.0));
+    }
+    
+    #[test]
+    fn test_parameters_are_named_in_order() {
+        let main = compile("function f(p, q) { let r = p + q; return r; }");
+        let f = &main.functions[0];
+        assert_eq!(f.parameter_names(), ["p", "q"]);
+        let p = f.debug_info.locals.iter().find(|local| local.name == "p").unwrap();
+        assert!(p.is_parameter);
+        assert_eq!((p.span.start, p.span.end), (11, 12));
+        assert_eq!(p.live, 0..f.bytecode.len());
+        assert_eq!(f.local_name(LocalIndex(2), f.bytecode.len() - 1), Some("r"));
+        assert!(main.parameter_names().is_empty());
+    }
+}
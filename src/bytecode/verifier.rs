@@ -0,0 +1,192 @@
+//! Static checks on compiled code
+//!
+//! The compiler only emits code that passes [`verify`]. Code from
+//! elsewhere, such as a [`BytecodeDump`](super::BytecodeDump) read from
+//! disk or a function built by hand, may not, and running it could break
+//! the guarantees the language makes. The verifier checks each function
+//! and the functions nested in it for:
+//!
+//! - local slots outside `locals_count`, including immutable ones
+//! - stores other than `InitLocal` to a slot in `immutable_locals`
+//! - `StaContextSlot` stores to an immutable slot of an enclosing function
+//! - arena sites out of order or on anything but an allocation
+//! - instructions that would take more operands off the stack than the
+//!   function pushed, or paths that meet with different stack depths
+//!
+//! Context slots deeper than the enclosing functions are left alone: they
+//! belong to whatever environment the code is run in.
+//!
+//! A [`VerifiedFunction`] carries the result to the VM, which then reads
+//! and writes locals without an error path for indices out of range.
+
+use std::ops::Deref;
+use std::sync::Arc;
+use crate::error::{Error, ErrorCode, Result};
+use super::function::BytecodeFunction;
+use super::instruction::{Bytecode, JumpOffset};
+
+/// A function that passed [`verify`], together with the functions nested
+/// in it
+///
+/// The function is shared and can no longer change, so its local indices
+/// stay in range for as long as this exists. `VM::execute_verified` runs
+/// it, and the closures it creates, on the VM's locals path that panics
+/// instead of returning errors.
+#[derive(Debug, Clone)]
+pub struct VerifiedFunction(Arc<BytecodeFunction>);
+
+impl VerifiedFunction {
+    /// Verify `function`
+    pub fn new(function: Arc<BytecodeFunction>) -> Result<Self> {
+        verify(&function)?;
+        Ok(Self(function))
+    }
+    
+    /// The verified function
+    pub fn function(&self) -> &Arc<BytecodeFunction> {
+        &self.0
+    }
+}
+
+impl Deref for VerifiedFunction {
+    type Target = BytecodeFunction;
+    
+    fn deref(&self) -> &BytecodeFunction {
+        &self.0
+    }
+}
+
+/// Check `function` and every function nested in it
+pub fn verify(function: &BytecodeFunction) -> Result<()> {
+    verify_nested(function, "main".to_string(), &mut Vec::new())
+}
+
+/// Check `function`, whose enclosing functions are `ancestors`,
+/// innermost last
+fn verify_nested<'a>(function: &'a BytecodeFunction, path: String, ancestors: &mut Vec<&'a BytecodeFunction>) -> Result<()> {
+    if let Some(index) = function.immutable_locals.iter().find(|index| index.0 >= function.locals_count) {
+        return Err(Error::runtime(ErrorCode::InvalidBytecode, format!(
+            "Invalid bytecode in {}: immutable local {} is outside its {} locals",
+            path, index, function.locals_count
+        ), None));
+    }
+    
+    if let Some(pair) = function.arena_sites.windows(2).find(|pair| pair[0] >= pair[1]) {
+        return Err(Error::runtime(ErrorCode::InvalidBytecode, format!(
+            "Invalid bytecode in {}: arena site {} does not follow {}",
+            path, pair[1], pair[0]
+        ), None));
+    }
+    if let Some(&site) = function.arena_sites.iter().find(|&&site| !matches!(
+        function.bytecode.get(site),
+        Some(Bytecode::CreateObject | Bytecode::CreateArray(_) | Bytecode::CreateFromTemplate(_))
+    )) {
+        return Err(Error::runtime(ErrorCode::InvalidBytecode, format!("Invalid bytecode in {}: arena site {} is not an allocation", path, site), None));
+    }
+    
+    for (offset, instruction) in function.bytecode.iter().enumerate() {
+        let invalid = |message: String| Error::runtime(
+            ErrorCode::InvalidBytecode,
+            format!("Invalid bytecode in {} at offset {}: {}", path, offset, message),
+            function.debug_info.get_span(offset).copied(),
+        );
+        match instruction {
+            Bytecode::LdaLocal(index) | Bytecode::StaLocal(index) | Bytecode::InitLocal(index) => {
+                if index.0 >= function.locals_count {
+                    return Err(invalid(format!("local {} is outside its {} locals", index, function.locals_count)));
+                }
+                if matches!(instruction, Bytecode::StaLocal(_)) && function.immutable_locals.contains(*index) {
+                    return Err(invalid(format!("StaLocal to immutable local {}", index)));
+                }
+            }
+            Bytecode::LdaContextSlot(depth, index) | Bytecode::StaContextSlot(depth, index) => {
+                let Some(owner) = context_owner(function, ancestors, *depth as usize) else {
+                    continue;
+                };
+                if index.0 >= owner.locals_count {
+                    return Err(invalid(format!("context slot {} {} is outside its {} locals", depth, index, owner.locals_count)));
+                }
+                if matches!(instruction, Bytecode::StaContextSlot(..)) && owner.immutable_locals.contains(*index) {
+                    return Err(invalid(format!("StaContextSlot to immutable local {} of {}", index, owner.name)));
+                }
+            }
+            _ => {}
+        }
+    }
+    
+    check_stack_balance(function, &path)?;
+    
+    ancestors.push(function);
+    for (index, nested) in function.functions.iter().enumerate() {
+        verify_nested(nested, format!("{}/{}", path, index), ancestors)?;
+    }
+    ancestors.pop();
+    Ok(())
+}
+
+/// Check that every instruction finds the operands it reads on the stack,
+/// and that each instruction is reached with one stack depth
+///
+/// Walks every path from the entry, tracking the depth of the function's
+/// own part of the operand stack. A function's code starts with an empty
+/// stack, and a generator resumes after `Yield` with the stack it had.
+fn check_stack_balance(function: &BytecodeFunction, path: &str) -> Result<()> {
+    let code = &function.bytecode;
+    let mut depths: Vec<Option<usize>> = vec![None; code.len()];
+    let mut pending = vec![(0, 0)];
+    while let Some((offset, depth)) = pending.pop() {
+        // Running off the end returns, whatever is on the stack
+        let Some(instruction) = code.get(offset) else {
+            continue;
+        };
+        let invalid = |message: String| Error::runtime(
+            ErrorCode::InvalidBytecode,
+            format!("Invalid bytecode in {} at offset {}: {}", path, offset, message),
+            function.debug_info.get_span(offset).copied(),
+        );
+        match depths[offset] {
+            Some(known) if known == depth => continue,
+            Some(known) => return Err(invalid(format!(
+                "reached with stack depths {} and {}", known.min(depth), known.max(depth)
+            ))),
+            None => depths[offset] = Some(depth),
+        }
+        
+        let needed = instruction.stack_pop_count() + stack_peek_count(instruction);
+        if depth < needed {
+            return Err(invalid(format!("the stack is {} deep, but {} needs {}", depth, instruction, needed)));
+        }
+        let after = depth - instruction.stack_pop_count() + instruction.stack_push_count();
+        let target = |jump: &JumpOffset| jump.target(offset)
+            .filter(|&target| target <= code.len())
+            .ok_or_else(|| invalid(format!("{} jumps outside the function", instruction)));
+        match instruction {
+            Bytecode::Return | Bytecode::ReturnUndefined => {}
+            Bytecode::Jump(jump) => pending.push((target(jump)?, after)),
+            Bytecode::JumpIfFalse(jump) | Bytecode::JumpIfTrue(jump) | Bytecode::JumpIfNullish(jump) | Bytecode::IteratorNext(jump) => {
+                pending.push((target(jump)?, after));
+                pending.push((offset + 1, after));
+            }
+            _ => pending.push((offset + 1, after)),
+        }
+    }
+    Ok(())
+}
+
+/// Stack operands an instruction reads without popping them
+fn stack_peek_count(instruction: &Bytecode) -> usize {
+    match instruction {
+        Bytecode::Dup | Bytecode::IteratorNext(_) | Bytecode::StaArrayElement(_) |
+        Bytecode::DefineNamedProperty(_) | Bytecode::DefineKeyedProperty | Bytecode::CopyDataProperties => 1,
+        Bytecode::Dup2 => 2,
+        _ => 0,
+    }
+}
+
+/// The function whose environment is `depth` levels up from `function`'s
+fn context_owner<'a>(function: &'a BytecodeFunction, ancestors: &[&'a BytecodeFunction], depth: usize) -> Option<&'a BytecodeFunction> {
+    match depth {
+        0 => Some(function),
+        _ => ancestors.len().checked_sub(depth).map(|index| ancestors[index]),
+    }
+}
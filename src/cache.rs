@@ -0,0 +1,156 @@
+//! Compilation Cache
+//!
+//! Maps source text to its compiled main function so an engine that runs
+//! the same script repeatedly lexes, parses and compiles it once. Entries
+//! are keyed by a hash of the source; the source itself is kept to tell
+//! a hash collision from a hit. When full, the least recently used entry
+//! is evicted.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use crate::bytecode::VerifiedFunction;
+use crate::error::Diagnostic;
+
+/// Hit and miss counts of an engine's compilation cache
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups answered from the cache
+    pub hits: u64,
+    /// Lookups that had to compile
+    pub misses: u64,
+    /// Entries currently stored
+    pub entries: usize,
+}
+
+/// A compiled program and the warnings produced while compiling it
+#[derive(Debug, Clone)]
+pub(crate) struct CompiledScript {
+    pub function: VerifiedFunction,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    source: String,
+    script: CompiledScript,
+    last_used: u64,
+}
+
+/// Bounded LRU cache of compiled scripts
+#[derive(Debug, Default)]
+pub(crate) struct CompilationCache {
+    capacity: usize,
+    entries: HashMap<u64, Entry>,
+    /// Incremented on every access; orders entries by recency
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl CompilationCache {
+    /// A cache holding up to `capacity` scripts; zero disables caching
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, ..Self::default() }
+    }
+    
+    /// Whether lookups can ever hit
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+    
+    /// The compiled form of `source`, counting a hit or a miss
+    pub fn get(&mut self, source: &str) -> Option<CompiledScript> {
+        if !self.is_enabled() {
+            return None;
+        }
+        self.clock += 1;
+        match self.entries.get_mut(&key(source)) {
+            Some(entry) if entry.source == source => {
+                entry.last_used = self.clock;
+                self.hits += 1;
+                Some(entry.script.clone())
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+    
+    /// Store the compiled form of `source`, evicting the least recently
+    /// used entry when full
+    pub fn insert(&mut self, source: &str, script: CompiledScript) {
+        if !self.is_enabled() {
+            return;
+        }
+        let key = key(source);
+        if self.entries.len() >= self.capacity && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.clock += 1;
+        self.entries.insert(key, Entry { source: source.to_string(), script, last_used: self.clock });
+    }
+    
+    /// Drop every entry; the counters are kept
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+    
+    /// Counts so far, and the current number of entries
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits, misses: self.misses, entries: self.entries.len() }
+    }
+}
+
+fn key(source: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    source.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use crate::bytecode::BytecodeFunction;
+    
+    fn script(name: &str) -> CompiledScript {
+        CompiledScript {
+            function: VerifiedFunction::new(Arc::new(BytecodeFunction::new(name.to_string(), 0, 0, 0))).unwrap(),
+            diagnostics: Vec::new(),
+        }
+    }
+    
+    #[test]
+    fn test_least_recently_used_entry_is_evicted() {
+        let mut cache = CompilationCache::new(2);
+        cache.insert("a", script("a"));
+        cache.insert("b", script("b"));
+        // Touch `a`, so `b` is the oldest when `c` arrives
+        assert_eq!(cache.get("a").unwrap().function.name, "a");
+        cache.insert("c", script("c"));
+        
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("c").is_some());
+        assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1, entries: 2 });
+        
+        cache.clear();
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.stats().entries, 0);
+    }
+    
+    #[test]
+    fn test_disabled_cache_stores_nothing() {
+        let mut cache = CompilationCache::new(0);
+        cache.insert("a", script("a"));
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+}
@@ -0,0 +1,192 @@
+//! What an engine supports
+//!
+//! `Engine::capabilities` reports the syntax features this build
+//! implements, the limits the engine was configured with and the crate
+//! version, so a host can decide which scripts to feed it. The feature
+//! list is kept honest by the conformance suite: fixtures name the feature
+//! they exercise with a `// feature:` directive, and a test checks that a
+//! feature is reported exactly when none of its fixtures are skipped.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::bytecode::{MAX_CONSTANTS, MAX_LOCALS};
+use crate::lexer::FeatureFlags;
+use crate::vm::value::MAX_DENSE_ARRAY_LENGTH;
+use crate::vm::VMConfig;
+
+/// A piece of syntax a script may depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Feature {
+    /// `x => x`, sync and `async`
+    ArrowFunctions,
+    /// `async function` and `await`
+    AsyncFunctions,
+    /// `let` and `const` scoped to their block
+    BlockScoping,
+    /// `class` declarations and expressions
+    Classes,
+    /// `a ? b : c`
+    ConditionalOperator,
+    /// `function f(a = 1)`
+    DefaultParameters,
+    /// `let [a, b] = ...` and `let { a } = ...`
+    Destructuring,
+    /// `do ... while`
+    DoWhile,
+    /// `**` and `**=`
+    Exponentiation,
+    /// `for (x of iterable)`
+    ForOf,
+    /// Calls of function declarations placed later in their scope
+    FunctionHoisting,
+    /// `function*` and `yield`
+    Generators,
+    /// Labeled statements, `break label` and `continue label`
+    Labels,
+    /// `a ?? b`
+    NullishCoalescing,
+    /// `1_000_000`; depends on `FeatureFlags::numeric_separators`
+    NumericSeparators,
+    /// `{ ...object }`
+    ObjectSpread,
+    /// `a?.b`
+    OptionalChaining,
+    /// `function f(...rest)`
+    RestParameters,
+    /// `switch`
+    Switch,
+    /// Template literals, `` `a${b}` ``
+    TemplateLiterals,
+    /// Trailing commas in literals, parameter lists and calls
+    TrailingCommas,
+    /// `try`, `catch`, `finally` and `throw`
+    TryCatch,
+}
+
+impl Feature {
+    /// Every feature, implemented or not
+    pub const ALL: [Feature; 22] = [
+        Feature::ArrowFunctions, Feature::AsyncFunctions, Feature::BlockScoping, Feature::Classes,
+        Feature::ConditionalOperator, Feature::DefaultParameters, Feature::Destructuring, Feature::DoWhile,
+        Feature::Exponentiation, Feature::ForOf, Feature::FunctionHoisting, Feature::Generators,
+        Feature::Labels, Feature::NullishCoalescing, Feature::NumericSeparators, Feature::ObjectSpread,
+        Feature::OptionalChaining, Feature::RestParameters, Feature::Switch, Feature::TemplateLiterals,
+        Feature::TrailingCommas, Feature::TryCatch,
+    ];
+    
+    /// The features this build implements whatever the configuration;
+    /// update it together with the conformance fixtures of a feature
+    const IMPLEMENTED: [Feature; 10] = [
+        Feature::ArrowFunctions, Feature::AsyncFunctions, Feature::BlockScoping, Feature::Classes,
+        Feature::Exponentiation, Feature::ForOf, Feature::FunctionHoisting, Feature::Generators,
+        Feature::ObjectSpread, Feature::TrailingCommas,
+    ];
+    
+    /// The kebab-case name, as in `// feature:` directives and JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::ArrowFunctions => "arrow-functions",
+            Feature::AsyncFunctions => "async-functions",
+            Feature::BlockScoping => "block-scoping",
+            Feature::Classes => "classes",
+            Feature::ConditionalOperator => "conditional-operator",
+            Feature::DefaultParameters => "default-parameters",
+            Feature::Destructuring => "destructuring",
+            Feature::DoWhile => "do-while",
+            Feature::Exponentiation => "exponentiation",
+            Feature::ForOf => "for-of",
+            Feature::FunctionHoisting => "function-hoisting",
+            Feature::Generators => "generators",
+            Feature::Labels => "labels",
+            Feature::NullishCoalescing => "nullish-coalescing",
+            Feature::NumericSeparators => "numeric-separators",
+            Feature::ObjectSpread => "object-spread",
+            Feature::OptionalChaining => "optional-chaining",
+            Feature::RestParameters => "rest-parameters",
+            Feature::Switch => "switch",
+            Feature::TemplateLiterals => "template-literals",
+            Feature::TrailingCommas => "trailing-commas",
+            Feature::TryCatch => "try-catch",
+        }
+    }
+    
+    /// The feature with this kebab-case name
+    pub fn from_name(name: &str) -> Option<Feature> {
+        Feature::ALL.into_iter().find(|feature| feature.name() == name)
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Limits an engine runs scripts under
+///
+/// Each is read from the engine's configuration or from the constant the
+/// compiler or VM checks against, never from a copy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Limits {
+    /// Constants in one function's pool
+    pub max_constants: usize,
+    /// Local variables and parameters of one function
+    pub max_locals: usize,
+    /// Active calls (`VMConfig::max_call_depth`)
+    pub max_call_depth: usize,
+    /// Values on the operand stack (`VMConfig::max_stack_size`)
+    pub max_stack_size: usize,
+    /// Instructions one execution may run (`VMConfig::max_instructions`)
+    pub max_instructions: Option<u64>,
+    /// Bytes live strings and objects may take (`VMConfig::max_heap_bytes`)
+    pub max_heap_bytes: Option<usize>,
+    /// How deeply statements and expressions may nest
+    pub max_nesting_depth: usize,
+    /// Length of an array
+    pub max_array_length: usize,
+    /// Length of a string; `None` as strings are bounded only by memory
+    pub max_string_length: Option<usize>,
+}
+
+/// What an engine supports (see the module docs)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// The crate version, `VERSION`
+    pub version: String,
+    /// The implemented syntax features
+    pub features: BTreeSet<Feature>,
+    /// The configured limits
+    pub limits: Limits,
+}
+
+impl Capabilities {
+    /// Capabilities of an engine with this configuration
+    pub(crate) fn new(vm_config: &VMConfig, max_nesting_depth: usize, lexer_features: FeatureFlags) -> Self {
+        let mut features: BTreeSet<Feature> = Feature::IMPLEMENTED.into_iter().collect();
+        if lexer_features.numeric_separators {
+            features.insert(Feature::NumericSeparators);
+        }
+        Self {
+            version: crate::VERSION.to_string(),
+            features,
+            limits: Limits {
+                max_constants: MAX_CONSTANTS,
+                max_locals: MAX_LOCALS,
+                max_call_depth: vm_config.max_call_depth,
+                max_stack_size: vm_config.max_stack_size,
+                max_instructions: vm_config.max_instructions,
+                max_heap_bytes: vm_config.max_heap_bytes,
+                max_nesting_depth,
+                max_array_length: MAX_DENSE_ARRAY_LENGTH,
+                max_string_length: None,
+            },
+        }
+    }
+    
+    /// Whether scripts may use `feature`
+    pub fn supports(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+}
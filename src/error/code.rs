@@ -0,0 +1,145 @@
+//! Stable error codes
+//!
+//! Every error the engine reports carries an `ErrorCode`, so hosts and
+//! tests can match on what went wrong without depending on the wording
+//! of messages, and hosts that localize messages have something to key
+//! on. Codes never change meaning once released; a retired code is not
+//! reused. They are grouped by where the error is raised:
+//!
+//! - `E1xxx` lexer
+//! - `E2xxx` parser
+//! - `E3xxx` compiler
+//! - `E4xxx` runtime
+//! - `E5xxx` host and embedding APIs
+//! - `E9xxx` engine bugs
+//!
+//! `specs/ERROR_CODES.md` lists them all; it is generated from this table
+//! (see `tests/error_code_tests.rs`).
+
+use std::fmt;
+
+macro_rules! error_codes {
+    ($($name:ident = $code:literal, $summary:literal;)*) => {
+        /// What went wrong, as a stable code (see the module docs)
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum ErrorCode {
+            $(
+                #[doc = $summary]
+                $name,
+            )*
+        }
+        
+        impl ErrorCode {
+            /// Every code, in table order
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$name),*];
+            
+            /// The code as shown in output, such as `E1001`
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$name => $code,)*
+                }
+            }
+            
+            /// The variant name, such as `UnexpectedCharacter`
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$name => stringify!($name),)*
+                }
+            }
+            
+            /// A one-line description of the errors that carry this code
+            pub fn summary(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$name => $summary,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    UnexpectedCharacter = "E1001", "A character that cannot start a token";
+    InvalidNumber = "E1002", "A malformed numeric literal";
+    UnterminatedString = "E1003", "A string literal without its closing quote";
+    InvalidEscape = "E1004", "A malformed escape sequence in a string literal";
+    UnterminatedRegExp = "E1005", "A regular expression literal without its closing slash";
+    InvalidRegExpFlags = "E1006", "Unknown or repeated regular expression flags";
+    UnterminatedComment = "E1007", "A block comment without its closing `*/`";
+    BidiControl = "E1008", "A bidirectional control character outside a string, with the reject policy";
+    DisabledSyntax = "E1009", "Syntax whose feature flag is off";
+    UnterminatedTemplate = "E1010", "A template literal without its closing backtick";
+    
+    UnexpectedToken = "E2001", "A token that cannot appear here";
+    ExpectedToken = "E2002", "A required token, such as a closing parenthesis, is missing";
+    MissingSemicolon = "E2003", "Two statements on one line without a `;` between them";
+    UnexpectedEnd = "E2004", "The input ends in the middle of a construct";
+    TooMuchNesting = "E2005", "Nesting deeper than the configured maximum depth";
+    MissingInitializer = "E2006", "A `const` declaration without an initializer";
+    DuplicateConstructor = "E2007", "A class with more than one constructor";
+    LineBreakBeforeArrow = "E2008", "A line break between arrow function parameters and `=>`";
+    AmbiguousExponentiation = "E2009", "A unary operator directly before `**`";
+    UnsupportedSyntax = "E2010", "Valid JavaScript the engine does not support yet";
+    InvalidEstree = "E2011", "ESTree input that is not valid JSON or has the wrong shape";
+    UnsupportedEstree = "E2012", "An ESTree node the engine does not support";
+    DuplicateParameter = "E2013", "A parameter name repeated where that is not allowed, such as in strict mode code";
+    
+    DuplicateDeclaration = "E3001", "A variable declared twice in one scope";
+    IllegalBreak = "E3002", "`break` outside a loop";
+    IllegalContinue = "E3003", "`continue` outside a loop";
+    InvalidAssignmentTarget = "E3004", "Assignment to something that is not a variable or property";
+    CompilerLimit = "E3005", "Code too large for the bytecode format, such as too many locals";
+    
+    UnknownGlobal = "E4001", "ReferenceError: read of a global that does not exist";
+    NotCallable = "E4002", "TypeError: call of a value that is not a function";
+    NullishPropertyAccess = "E4003", "TypeError: property read or write on `null` or `undefined`";
+    StackOverflow = "E4004", "RangeError: the call stack grew past its limit";
+    InstructionLimit = "E4005", "RangeError: a run executed more instructions than allowed";
+    ConstAssignment = "E4006", "TypeError: assignment to a `const` variable";
+    ReadOnlyProperty = "E4007", "TypeError: write to or redefinition of a read-only property";
+    NotIterable = "E4008", "TypeError: iteration over a value that is not iterable";
+    NotConstructor = "E4009", "TypeError: `new` on a non-constructor, or a constructor called without `new`";
+    InvalidArgument = "E4010", "TypeError: a built-in called with an argument of the wrong type";
+    NotObjectCoercible = "E4011", "TypeError: `null` or `undefined` where an object is needed";
+    IncompatibleReceiver = "E4012", "TypeError: a built-in method called on the wrong kind of `this`";
+    InvalidInstanceof = "E4013", "TypeError: `instanceof` with a right-hand side it cannot use";
+    InvalidInOperand = "E4014", "TypeError: `in` with a right-hand side that is not an object";
+    CannotDelete = "E4015", "TypeError: `delete` of a non-configurable property";
+    NotPrimitive = "E4016", "TypeError: an object with no primitive value";
+    InvalidPrototype = "E4017", "TypeError: a prototype that is not an object or null, or would form a cycle";
+    GeneratorRunning = "E4018", "TypeError: a generator resumed while it is running";
+    InvalidArrayLength = "E4019", "RangeError: an array length that is negative, fractional or too large";
+    CircularJson = "E4020", "TypeError: `JSON.stringify` of a circular structure";
+    JsonNesting = "E4021", "RangeError: JSON nested deeper than supported";
+    JsonSyntax = "E4022", "SyntaxError: `JSON.parse` of malformed JSON";
+    UnsupportedBuiltin = "E4023", "A built-in option or argument the engine does not support yet";
+    SymbolConversion = "E4024", "TypeError: a symbol used where a string is needed";
+    InvalidIterator = "E4025", "TypeError: an iterator, or a result of its `next`, that is not an object";
+    AllocationLimit = "E4026", "RangeError: script values would take more memory than allowed";
+    JoinNesting = "E4027", "RangeError: arrays converted to a string nested deeper than supported";
+    
+    HostError = "E5001", "An error raised by a host function or hook";
+    InvalidBytecode = "E5002", "Bytecode that fails verification";
+    InvalidConstant = "E5003", "A constant pool edit that does not fit the pool";
+    SnapshotCapture = "E5004", "A global that cannot be captured in a snapshot";
+    InvalidSnapshot = "E5005", "Snapshot bytes that cannot be restored";
+    Io = "E5006", "A failed read or write, or invalid data from outside the engine";
+    InvalidFfiArgument = "E5007", "A null or invalid argument passed through the C API";
+    InvalidFrame = "E5008", "A debugger request for a frame that does not exist";
+    NotCloneable = "E5009", "A function, symbol or engine-bound object passed to `Value::deep_clone_detached`";
+    NativeFunctionPanicked = "E5010", "A host or native function that panicked instead of returning an error";
+    
+    Internal = "E9001", "A broken engine invariant, such as a stack underflow: an engine bug";
+}
+
+impl ErrorCode {
+    /// The code whose text is `code`, such as `E1001`
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|candidate| candidate.as_str() == code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -1,6 +1,6 @@
 //! Diagnostic reporting with colored output
 
-use super::{Error, Span};
+use super::{Error, ErrorCode, Note, Span};
 use std::fmt;
 
 /// Diagnostic severity levels
@@ -15,30 +15,43 @@ pub enum Severity {
 #[derive(Debug, Clone)]
 pub struct Diagnostic {
     pub severity: Severity,
+    /// The code of the error this reports, shown as `error[E1001]`
+    pub code: Option<ErrorCode>,
     pub message: String,
     pub span: Option<Span>,
     pub source_name: Option<String>,
+    /// Other places the message refers to, shown after it
+    pub notes: Vec<Note>,
 }
 
 impl Diagnostic {
     pub fn error(message: impl Into<String>) -> Self {
         Self {
             severity: Severity::Error,
+            code: None,
             message: message.into(),
             span: None,
             source_name: None,
+            notes: Vec::new(),
         }
     }
     
     pub fn warning(message: impl Into<String>) -> Self {
         Self {
             severity: Severity::Warning,
+            code: None,
             message: message.into(),
             span: None,
             source_name: None,
+            notes: Vec::new(),
         }
     }
     
+    pub fn with_code(mut self, code: ErrorCode) -> Self {
+        self.code = Some(code);
+        self
+    }
+    
     pub fn with_span(mut self, span: Span) -> Self {
         self.span = Some(span);
         self
@@ -49,6 +62,11 @@ impl Diagnostic {
         self
     }
     
+    pub fn with_note(mut self, note: Note) -> Self {
+        self.notes.push(note);
+        self
+    }
+    
     /// Format diagnostic with colored output
     pub fn format_with_source(&self, source: &str) -> String {
         let mut output = String::new();
@@ -60,92 +78,144 @@ impl Diagnostic {
         let bold = "\x1b[1m";
         let reset = "\x1b[0m";
         
-        let (color, severity_text) = match self.severity {
-            Severity::Error => (red, "error"),
-            Severity::Warning => (yellow, "warning"),
-            Severity::Info => (blue, "info"),
+        let color = match self.severity {
+            Severity::Error => red,
+            Severity::Warning => yellow,
+            Severity::Info => blue,
         };
         
         // Header line
-        output.push_str(&format!("{}{}:{} {}{}\n", 
-            bold, color, severity_text, self.message, reset));
+        output.push_str(&format!("{}{}{}:{} {}{}\n", 
+            bold, color, self.heading(), reset, self.message, reset));
         
         if let Some(span) = self.span {
-            // Source name and location
-            if let Some(ref source_name) = self.source_name {
-                output.push_str(&format!("  --> {}:{}:{}\n", 
-                    source_name, span.line, span.column));
-            } else {
-                output.push_str(&format!("  --> line {}:{}\n", 
-                    span.line, span.column));
+            self.push_snippet(&mut output, source, span, color);
+        }
+        
+        for note in &self.notes {
+            output.push_str(&format!("{}{}note:{} {}\n", bold, blue, reset, note.message));
+            self.push_snippet(&mut output, source, note.span, blue);
+        }
+        
+        output
+    }
+    
+    /// Show where `span` is: its location, source line and `^` markers
+    fn push_snippet(&self, output: &mut String, source: &str, span: Span, color: &str) {
+        let reset = "\x1b[0m";
+        
+        // Source name and location
+        if let Some(ref source_name) = self.source_name {
+            output.push_str(&format!("  --> {}:{}:{}\n", 
+                source_name, span.line, span.column));
+        } else {
+            output.push_str(&format!("  --> line {}:{}\n", 
+                span.line, span.column));
+        }
+        
+        // Extract the relevant line from source
+        if let Some(line_text) = get_line_from_source(source, span.line as usize) {
+            let line_num_width = format!("{}", span.line).len();
+            
+            // Line number and source
+            output.push_str(&format!("{:width$} | \n", "", width = line_num_width));
+            output.push_str(&format!("{} | {}\n", span.line, line_text));
+            
+            // Error indicator
+            let spaces = " ".repeat(line_num_width);
+            let mut indicator = String::new();
+            indicator.push_str(&format!("{} | ", spaces));
+            
+            // Add spaces to align with error column
+            for _ in 0..span.column.saturating_sub(1) {
+                indicator.push(' ');
             }
             
-            // Extract the relevant line from source
-            if let Some(line_text) = get_line_from_source(source, span.line as usize) {
-                let line_num_width = format!("{}", span.line).len();
-                
-                // Line number and source
-                output.push_str(&format!("{:width$} | \n", "", width = line_num_width));
-                output.push_str(&format!("{} | {}\n", span.line, line_text));
-                
-                // Error indicator
-                let spaces = " ".repeat(line_num_width);
-                let mut indicator = String::new();
-                indicator.push_str(&format!("{} | ", spaces));
-                
-                // Add spaces to align with error column
-                for _ in 0..span.column.saturating_sub(1) {
-                    indicator.push(' ');
-                }
-                
-                // Add error markers
-                let error_len = if span.len() > 0 { span.len() } else { 1 };
-                for _ in 0..error_len {
-                    indicator.push_str(&format!("{}^{}", color, reset));
-                }
-                
-                output.push_str(&indicator);
-                output.push('\n');
+            // Add error markers, stopping at the end of the first line
+            // for spans such as whole functions that run on past it
+            let error_len = source
+                .get(span.start..span.end)
+                .map_or(span.len(), |text| text.lines().next().unwrap_or("").chars().count())
+                .max(1);
+            for _ in 0..error_len {
+                indicator.push_str(&format!("{}^{}", color, reset));
             }
+            
+            output.push_str(&indicator);
+            output.push('\n');
+        }
+    }
+    
+    /// The severity with the code, if any: `error[E1001]`
+    fn heading(&self) -> String {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Info => "info",
+        };
+        match self.code {
+            Some(code) => format!("{}[{}]", severity, code),
+            None => severity.to_string(),
         }
-        
-        output
     }
 }
 
 impl From<Error> for Diagnostic {
     fn from(error: Error) -> Self {
-        match error {
+        let code = error.code();
+        let diagnostic = match error {
             Error::Lexer { message, span, .. } => {
                 Diagnostic::error(message).with_span(span)
             },
             Error::Parser { message, span, .. } => {
                 Diagnostic::error(message).with_span(span)
             },
-            Error::Runtime { message, span } => {
+            Error::Compile { message, span, .. } => {
                 let mut diag = Diagnostic::error(message);
                 if let Some(span) = span {
                     diag = diag.with_span(span);
                 }
                 diag
             },
+            Error::Runtime { kind, span } => {
+                let mut diag = Diagnostic::error(kind.to_string());
+                if let Some(span) = span {
+                    diag = diag.with_span(span);
+                }
+                diag
+            },
             Error::Io(io_error) => {
                 Diagnostic::error(format!("IO error: {}", io_error))
             },
-        }
+            Error::Context { message, source } => {
+                let inner = Diagnostic::from(*source);
+                let mut diag = Diagnostic::error(format!("{}: {}", message, inner.message));
+                if let Some(span) = inner.span {
+                    diag = diag.with_span(span);
+                }
+                diag.notes = inner.notes;
+                diag
+            },
+            Error::Noted { note, source } => Diagnostic::from(*source).with_note(note),
+        };
+        diagnostic.with_code(code)
     }
 }
 
+/// One line per message, each ending in where it points, without the
+/// source snippets of `format_with_source`
 impl fmt::Display for Diagnostic {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}: {}", 
-            match self.severity {
-                Severity::Error => "error",
-                Severity::Warning => "warning", 
-                Severity::Info => "info",
-            },
-            self.message
-        )
+        write!(f, "{}: {}", self.heading(), self.message)?;
+        match (&self.source_name, self.span) {
+            (Some(source_name), Some(span)) => write!(f, " ({}, line {}, column {})", source_name, span.line, span.column)?,
+            (None, Some(span)) => write!(f, " (line {}, column {})", span.line, span.column)?,
+            (_, None) => {}
+        }
+        for note in &self.notes {
+            write!(f, "\nnote: {} (line {}, column {})", note.message, note.span.line, note.span.column)?;
+        }
+        Ok(())
     }
 }
 
@@ -1,11 +1,17 @@
 //! Error handling and diagnostics for the JavaScript engine
 
+pub mod code;
 pub mod diagnostic;
+pub(crate) mod suggest;
 
+pub use code::ErrorCode;
 pub use diagnostic::Diagnostic;
 
+use std::fmt;
+use serde::{Deserialize, Serialize};
+
 /// Source position information
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Span {
     pub start: usize,
     pub end: usize,
@@ -32,6 +38,7 @@ impl Span {
 pub enum Error {
     #[error("Lexer error: {message} at line {line}, column {column}")]
     Lexer {
+        code: ErrorCode,
         message: String,
         line: u32,
         column: u32,
@@ -40,25 +47,155 @@ pub enum Error {
     
     #[error("Parser error: {message} at line {line}, column {column}")]
     Parser {
+        code: ErrorCode,
         message: String,
         line: u32,
         column: u32,
         span: Span,
     },
     
-    #[error("Runtime error: {message}")]
-    Runtime {
+    /// A program that parsed but can't be compiled, such as one that
+    /// declares a name twice (the `E3xxx` codes)
+    #[error("Compile error: {message}")]
+    Compile {
+        code: ErrorCode,
         message: String,
         span: Option<Span>,
     },
     
+    #[error("Runtime error: {kind}")]
+    Runtime {
+        kind: RuntimeErrorKind,
+        span: Option<Span>,
+    },
+    
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
+    
+    /// An error annotated with what was being done when it happened
+    #[error("{message}")]
+    Context {
+        message: String,
+        #[source]
+        source: Box<Error>,
+    },
+    
+    /// An error with a note pointing at a second place in the source
+    #[error("{source}")]
+    Noted {
+        note: Note,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+/// A second place in the source an error refers to, such as where a name
+/// declared twice was first declared
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub message: String,
+    pub span: Span,
+}
+
+/// What went wrong at runtime, for hosts that match on errors
+///
+/// Suggestions are boxed rather than `String`s: every `Result` in the
+/// engine carries this type, and deeply nested compilation in a debug
+/// build has little stack to spare.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RuntimeErrorKind {
+    /// Read of a global binding that doesn't exist; `suggestion` is a
+    /// global with a similar name
+    UnknownGlobal { name: String, suggestion: Option<Box<str>> },
+    /// Call of a value that isn't a function; for a method that isn't
+    /// there, `suggestion` is a similar key of the receiver
+    NotCallable { callee: String, type_of: String, suggestion: Option<Box<str>> },
+    /// Property read or write on `null` or `undefined`
+    NullishPropertyAccess { base: String, property: String, is_write: bool },
+    /// The call stack grew past its limit
+    StackOverflow { depth: usize },
+    /// A run executed more instructions than `VMConfig::max_instructions`
+    InstructionLimit { limit: u64 },
+    /// Script values would take more memory than
+    /// `VMConfig::max_heap_bytes`
+    AllocationLimit { limit: usize },
+    /// A host or native function panicked; `message` is the panic's, when
+    /// it was a string
+    NativeFunctionPanicked { name: String, message: Option<String> },
+    /// A broken VM invariant, such as a stack underflow: an engine bug
+    Internal { message: String },
+    /// Any other error, described by its code and message
+    Custom { code: ErrorCode, message: String },
+}
+
+impl RuntimeErrorKind {
+    /// The stable code for this kind of error
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            RuntimeErrorKind::UnknownGlobal { .. } => ErrorCode::UnknownGlobal,
+            RuntimeErrorKind::NotCallable { .. } => ErrorCode::NotCallable,
+            RuntimeErrorKind::NullishPropertyAccess { .. } => ErrorCode::NullishPropertyAccess,
+            RuntimeErrorKind::StackOverflow { .. } => ErrorCode::StackOverflow,
+            RuntimeErrorKind::InstructionLimit { .. } => ErrorCode::InstructionLimit,
+            RuntimeErrorKind::AllocationLimit { .. } => ErrorCode::AllocationLimit,
+            RuntimeErrorKind::NativeFunctionPanicked { .. } => ErrorCode::NativeFunctionPanicked,
+            RuntimeErrorKind::Internal { .. } => ErrorCode::Internal,
+            RuntimeErrorKind::Custom { code, .. } => *code,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeErrorKind::UnknownGlobal { name, suggestion } => {
+                write!(f, "ReferenceError: {} is not defined", name)?;
+                write_suggestion(f, suggestion)
+            }
+            RuntimeErrorKind::NotCallable { callee, suggestion, .. } => {
+                write!(f, "TypeError: {} is not a function", callee)?;
+                write_suggestion(f, suggestion)
+            }
+            RuntimeErrorKind::NullishPropertyAccess { base, property, is_write: false } => {
+                write!(f, "TypeError: Cannot read properties of {} (reading '{}')", base, property)
+            }
+            RuntimeErrorKind::NullishPropertyAccess { base, property, is_write: true } => {
+                write!(f, "TypeError: Cannot set properties of {} (setting '{}')", base, property)
+            }
+            RuntimeErrorKind::StackOverflow { .. } => write!(f, "RangeError: Maximum call stack size exceeded"),
+            RuntimeErrorKind::InstructionLimit { limit } => write!(f, "RangeError: Instruction limit of {} exceeded", limit),
+            RuntimeErrorKind::AllocationLimit { limit } => write!(f, "RangeError: Allocation limit of {} bytes exceeded", limit),
+            RuntimeErrorKind::NativeFunctionPanicked { name, message } => {
+                let name = if name.is_empty() { "native function" } else { name.as_str() };
+                write!(f, "Error: {} panicked", name)?;
+                match message {
+                    Some(message) => write!(f, ": {}", message),
+                    None => Ok(()),
+                }
+            }
+            RuntimeErrorKind::Internal { message } | RuntimeErrorKind::Custom { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+/// The message a panic was raised with, if it was a string
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> Option<&str> {
+    payload.downcast_ref::<&str>().copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+}
+
+/// Append "(did you mean 'x'?)" when there is a suggestion
+fn write_suggestion(f: &mut fmt::Formatter<'_>, suggestion: &Option<Box<str>>) -> fmt::Result {
+    match suggestion {
+        Some(name) => write!(f, " (did you mean '{}'?)", name),
+        None => Ok(()),
+    }
 }
 
 impl Error {
-    pub fn lexer(message: impl Into<String>, span: Span) -> Self {
+    pub fn lexer(code: ErrorCode, message: impl Into<String>, span: Span) -> Self {
         Self::Lexer {
+            code,
             message: message.into(),
             line: span.line,
             column: span.column,
@@ -66,8 +203,9 @@ impl Error {
         }
     }
     
-    pub fn parser(message: impl Into<String>, span: Span) -> Self {
+    pub fn parser(code: ErrorCode, message: impl Into<String>, span: Span) -> Self {
         Self::Parser {
+            code,
             message: message.into(),
             line: span.line,
             column: span.column,
@@ -75,10 +213,81 @@ impl Error {
         }
     }
     
-    pub fn runtime(message: impl Into<String>, span: Option<Span>) -> Self {
-        Self::Runtime {
+    pub fn compile(code: ErrorCode, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self::Compile { code, message: message.into(), span }
+    }
+    
+    pub fn runtime(code: ErrorCode, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self::with_kind(RuntimeErrorKind::Custom { code, message: message.into() }, span)
+    }
+    
+    /// An error raised by a host function or hook (`ErrorCode::HostError`)
+    pub fn host(message: impl Into<String>) -> Self {
+        Self::runtime(ErrorCode::HostError, message, None)
+    }
+    
+    pub fn with_kind(kind: RuntimeErrorKind, span: Option<Span>) -> Self {
+        Self::Runtime { kind, span }
+    }
+    
+    /// A broken VM invariant (see `RuntimeErrorKind::Internal`)
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::with_kind(RuntimeErrorKind::Internal { message: message.into() }, None)
+    }
+    
+    /// Wrap this error with a description of what was being done
+    ///
+    /// The original error stays reachable through `source()`.
+    pub fn context(self, message: impl Into<String>) -> Self {
+        Self::Context {
             message: message.into(),
-            span,
+            source: Box::new(self),
+        }
+    }
+    
+    /// Attach a note pointing at `span`, such as the first declaration of
+    /// a name this error reports declared again
+    pub fn with_note(self, message: impl Into<String>, span: Span) -> Self {
+        Self::Noted {
+            note: Note { message: message.into(), span },
+            source: Box::new(self),
+        }
+    }
+    
+    /// The error underneath any context or notes, for matching on its
+    /// variant
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } | Error::Noted { source, .. } => source.inner(),
+            error => error,
+        }
+    }
+    
+    /// The structured cause of a runtime error, looking through context
+    pub fn kind(&self) -> Option<&RuntimeErrorKind> {
+        match self {
+            Error::Runtime { kind, .. } => Some(kind),
+            Error::Context { source, .. } | Error::Noted { source, .. } => source.kind(),
+            _ => None,
+        }
+    }
+    
+    /// The note attached with `with_note`, looking through context
+    pub fn note(&self) -> Option<&Note> {
+        match self {
+            Error::Noted { note, .. } => Some(note),
+            Error::Context { source, .. } => source.note(),
+            _ => None,
+        }
+    }
+    
+    /// The stable code for this error, looking through context
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Error::Lexer { code, .. } | Error::Parser { code, .. } | Error::Compile { code, .. } => *code,
+            Error::Runtime { kind, .. } => kind.code(),
+            Error::Io(_) => ErrorCode::Io,
+            Error::Context { source, .. } | Error::Noted { source, .. } => source.code(),
         }
     }
     
@@ -86,8 +295,9 @@ impl Error {
         match self {
             Error::Lexer { span, .. } => Some(*span),
             Error::Parser { span, .. } => Some(*span),
-            Error::Runtime { span, .. } => *span,
+            Error::Compile { span, .. } | Error::Runtime { span, .. } => *span,
             Error::Io(_) => None,
+            Error::Context { source, .. } | Error::Noted { source, .. } => source.span(),
         }
     }
 }
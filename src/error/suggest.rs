@@ -0,0 +1,73 @@
+//! "Did you mean" suggestions for misspelled names
+//!
+//! A name is suggested when it is within a few edits of the one that
+//! wasn't found: one edit for short names, a third of the length for
+//! longer ones. Names under three characters get no suggestion, since
+//! nearly everything is close to them. Ties go to the candidate seen
+//! first.
+
+/// Candidates looked at per suggestion; beyond this a large object or
+/// global scope isn't worth scanning just to improve an error message
+pub(crate) const MAX_CANDIDATES: usize = 1000;
+
+/// Number of single-character insertions, deletions and substitutions
+/// that turn `a` into `b` (Levenshtein distance)
+pub(crate) fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// The candidate closest to `name`, if any is close enough to be a typo
+///
+/// `name` itself is never suggested. At most [`MAX_CANDIDATES`] are
+/// compared.
+pub(crate) fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<Box<str>> {
+    let length = name.chars().count();
+    if length < 3 {
+        return None;
+    }
+    let limit = (length / 3).max(1);
+    let mut best: Option<(usize, &str)> = None;
+    for candidate in candidates.into_iter().take(MAX_CANDIDATES) {
+        if candidate == name || candidate.len().abs_diff(name.len()) > limit {
+            continue;
+        }
+        let distance = edit_distance(name, candidate);
+        if distance <= limit && best.is_none_or(|(closest, _)| distance < closest) {
+            best = Some((distance, candidate));
+        }
+    }
+    best.map(|(_, candidate)| candidate.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("consle", "console"), 1);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+    
+    #[test]
+    fn test_closest() {
+        assert_eq!(closest("consle", ["Math", "console", "JSON"]), Some("console".into()));
+        assert_eq!(closest("lenght", ["length", "push"]), Some("length".into()));
+        assert_eq!(closest("x", ["y", "xy"]), None);
+        assert_eq!(closest("count", ["count"]), None);
+        assert_eq!(closest("width", ["height", "depth"]), None);
+    }
+}
@@ -0,0 +1,463 @@
+//! C Interface
+//!
+//! `extern "C"` entry points for embedding the engine from C, enabled by
+//! the `ffi` cargo feature. The matching header is `include/v8rs.h`,
+//! generated with `cbindgen --config cbindgen.toml --output include/v8rs.h`.
+//!
+//! Ownership and lifetime rules:
+//! - An engine from `v8rs_engine_new` belongs to the caller until it is
+//!   passed to `v8rs_engine_free`. Engines are not thread-safe: use each
+//!   one only on the thread that created it.
+//! - A `V8rsValue` filled in by `v8rs_execute` owns its `string`, which
+//!   the caller releases with `v8rs_value_free`.
+//! - Arguments passed to a host callback, strings included, are borrowed
+//!   for the duration of the call only. A string the callback stores in
+//!   its result stays owned by the callback; the engine copies it.
+//! - The message from `v8rs_last_error_message` is owned by the library
+//!   and valid until the next failing call on the same thread.
+//!
+//! No panic crosses the boundary: a panic inside the engine is reported
+//! as an error, and the engine it happened in should be freed.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::rc::Rc;
+
+use crate::vm::value::ObjectData;
+use crate::{Engine, Error, ErrorCode, Result, Value};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Opaque engine handle
+pub struct V8rsEngine {
+    engine: Engine,
+}
+
+/// Which field of a `V8rsValue` holds the value
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum V8rsValueTag {
+    Undefined,
+    Null,
+    /// `boolean` holds the value
+    Boolean,
+    /// `number` holds the value
+    Number,
+    /// `string` holds the UTF-8 text
+    String,
+    /// `string` holds the object or array serialized as JSON
+    Json,
+}
+
+/// A JavaScript value crossing the C boundary
+///
+/// Primitives are passed by value, symbols as their description, and
+/// objects and arrays as JSON text.
+/// Fields not selected by `tag` are zero.
+#[repr(C)]
+#[derive(Debug)]
+pub struct V8rsValue {
+    pub tag: V8rsValueTag,
+    pub boolean: bool,
+    pub number: f64,
+    /// NUL-terminated UTF-8, or null
+    pub string: *mut c_char,
+}
+
+/// Host function implemented in C
+///
+/// Receives the `user_data` it was registered with and `argc` arguments.
+/// It writes its result to `out`, which starts out `undefined`, and
+/// returns 0. Returning anything else throws: a `String` result becomes
+/// the error message. Results tagged `Json` are not accepted.
+pub type V8rsCallback = Option<
+    unsafe extern "C" fn(user_data: *mut c_void, args: *const V8rsValue, argc: usize, out: *mut V8rsValue) -> c_int,
+>;
+
+impl V8rsValue {
+    fn undefined() -> Self {
+        Self { tag: V8rsValueTag::Undefined, boolean: false, number: 0.0, string: ptr::null_mut() }
+    }
+    
+    /// Convert a value, allocating its string with `CString`
+    fn from_value(value: &Value) -> Result<Self> {
+        let mut result = Self::undefined();
+        match value {
+            Value::Undefined => {}
+            Value::Null => result.tag = V8rsValueTag::Null,
+            Value::Boolean(b) => {
+                result.tag = V8rsValueTag::Boolean;
+                result.boolean = *b;
+            }
+            Value::Number(n) => {
+                result.tag = V8rsValueTag::Number;
+                result.number = *n;
+            }
+            Value::String(s) => {
+                result.tag = V8rsValueTag::String;
+                result.string = c_string(s)?.into_raw();
+            }
+            Value::Symbol(symbol) => {
+                // C has no handle to keep a symbol's identity in, so it
+                // crosses as its description, `Symbol(...)`
+                result.tag = V8rsValueTag::String;
+                result.string = c_string(&symbol.to_string())?.into_raw();
+            }
+            Value::Object(_) | Value::Function(_) => {
+                // Functions have no JSON form and stay `undefined`
+                if let Some(json) = to_json(value, &mut Vec::new())? {
+                    result.tag = V8rsValueTag::Json;
+                    result.string = c_string(&json)?.into_raw();
+                }
+            }
+        }
+        Ok(result)
+    }
+    
+    /// Copy a value written by a host callback
+    ///
+    /// # Safety
+    /// A `String` value's `string` must be null or NUL-terminated.
+    unsafe fn to_value(&self) -> Result<Value> {
+        Ok(match self.tag {
+            V8rsValueTag::Undefined => Value::Undefined,
+            V8rsValueTag::Null => Value::Null,
+            V8rsValueTag::Boolean => Value::Boolean(self.boolean),
+            V8rsValueTag::Number => Value::Number(self.number),
+            V8rsValueTag::String => Value::string(borrowed_str(self.string)?),
+            V8rsValueTag::Json => {
+                return Err(Error::runtime(ErrorCode::InvalidFfiArgument, "Host functions cannot return JSON values", None));
+            }
+        })
+    }
+}
+
+/// Serialize an object like `JSON.stringify`
+///
+/// Returns `None` for values with no JSON form (functions, symbols,
+/// `undefined`).
+fn to_json(value: &Value, seen: &mut Vec<*const ObjectData>) -> Result<Option<String>> {
+    let data = match value {
+        Value::Undefined | Value::Function(_) | Value::Symbol(_) => return Ok(None),
+        Value::Null => return Ok(Some("null".to_string())),
+        Value::Boolean(b) => return Ok(Some(b.to_string())),
+        Value::Number(n) if !n.is_finite() => return Ok(Some("null".to_string())),
+        Value::Number(_) => return Ok(Some(value.to_string())),
+        Value::String(s) => return Ok(Some(json_quote(s))),
+        Value::Object(data) => data,
+    };
+    
+    let ptr = Rc::as_ptr(data);
+    if seen.contains(&ptr) {
+        return Err(Error::runtime(ErrorCode::CircularJson, "TypeError: Converting circular structure to JSON", None));
+    }
+    seen.push(ptr);
+    let json = match data.array_elements() {
+        Some(elements) => {
+            let elements = elements.borrow().clone();
+            let mut parts = Vec::with_capacity(elements.len());
+            for element in &elements {
+                let element = element.clone().unwrap_or_default();
+                parts.push(to_json(&element, seen)?.unwrap_or_else(|| "null".to_string()));
+            }
+            format!("[{}]", parts.join(","))
+        }
+        None => {
+            let properties = data.properties.borrow().clone();
+            let mut parts = Vec::new();
+            for (key, value) in properties.enumerable() {
+                if let Some(json) = to_json(value, seen)? {
+                    parts.push(format!("{}:{}", json_quote(key), json));
+                }
+            }
+            format!("{{{}}}", parts.join(","))
+        }
+    };
+    seen.pop();
+    Ok(Some(json))
+}
+
+/// Quote a string as a JSON string literal
+fn json_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{c}' => quoted.push_str("\\f"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn c_string(s: &str) -> Result<CString> {
+    CString::new(s).map_err(|_| Error::runtime(ErrorCode::InvalidFfiArgument, "String contains a NUL character", None))
+}
+
+/// Borrow a NUL-terminated UTF-8 string
+///
+/// # Safety
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn borrowed_str<'a>(s: *const c_char) -> Result<&'a str> {
+    if s.is_null() {
+        return Err(Error::runtime(ErrorCode::InvalidFfiArgument, "Unexpected null string", None));
+    }
+    CStr::from_ptr(s).to_str().map_err(|_| Error::runtime(ErrorCode::InvalidFfiArgument, "String is not valid UTF-8", None))
+}
+
+fn set_last_error(message: &str) {
+    // Interior NULs would truncate the message anyway
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let detail = crate::error::panic_message(payload).unwrap_or("unknown cause");
+    format!("Internal error: engine panicked: {}", detail)
+}
+
+/// Run `f`, turning errors and panics into -1 and the last error message
+fn guard(f: impl FnOnce() -> Result<()>) -> c_int {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(error)) => {
+            set_last_error(&error.to_string());
+            -1
+        }
+        Err(payload) => {
+            set_last_error(&panic_message(payload.as_ref()));
+            -1
+        }
+    }
+}
+
+/// Create an engine; returns null if creation fails
+#[no_mangle]
+pub extern "C" fn v8rs_engine_new() -> *mut V8rsEngine {
+    panic::catch_unwind(|| Box::into_raw(Box::new(V8rsEngine { engine: Engine::new() })))
+        .unwrap_or_else(|payload| {
+            set_last_error(&panic_message(payload.as_ref()));
+            ptr::null_mut()
+        })
+}
+
+/// Free an engine; null is ignored
+///
+/// # Safety
+/// `engine` must be null or come from `v8rs_engine_new` and not have
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn v8rs_engine_free(engine: *mut V8rsEngine) {
+    if engine.is_null() {
+        return;
+    }
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(engine))));
+}
+
+/// Execute `len` bytes of UTF-8 source
+///
+/// On success returns 0 and stores the completion value in `out_result`
+/// (which may be null to discard it). On failure returns -1, leaves
+/// `out_result` `undefined`, and sets the last error message.
+///
+/// # Safety
+/// `engine` must be a live engine and `source` must point to `len`
+/// readable bytes. `out_result` must be null or writable.
+#[no_mangle]
+pub unsafe extern "C" fn v8rs_execute(
+    engine: *mut V8rsEngine,
+    source: *const c_char,
+    len: usize,
+    out_result: *mut V8rsValue,
+) -> c_int {
+    if !out_result.is_null() {
+        out_result.write(V8rsValue::undefined());
+    }
+    guard(|| {
+        let engine = engine.as_mut().ok_or_else(|| Error::runtime(ErrorCode::InvalidFfiArgument, "Engine is null", None))?;
+        if source.is_null() {
+            return Err(Error::runtime(ErrorCode::InvalidFfiArgument, "Source is null", None));
+        }
+        let bytes = std::slice::from_raw_parts(source.cast::<u8>(), len);
+        let source = std::str::from_utf8(bytes).map_err(|_| Error::runtime(ErrorCode::InvalidFfiArgument, "Source is not valid UTF-8", None))?;
+        
+        let value = engine.engine.execute_detailed(source).result?;
+        let value = V8rsValue::from_value(&value)?;
+        match out_result.as_mut() {
+            Some(out) => *out = value,
+            None => v8rs_value_free(&mut { value }),
+        }
+        Ok(())
+    })
+}
+
+/// Release the string of a value filled in by `v8rs_execute` and reset
+/// it to `undefined`; null is ignored
+///
+/// # Safety
+/// `value` must be null or a value written by `v8rs_execute` whose
+/// string has not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn v8rs_value_free(value: *mut V8rsValue) {
+    let Some(value) = value.as_mut() else {
+        return;
+    };
+    if !value.string.is_null() {
+        drop(CString::from_raw(value.string));
+    }
+    *value = V8rsValue::undefined();
+}
+
+/// Expose `callback` to scripts as the global function `name`
+///
+/// `user_data` is passed back to every call and is never dereferenced
+/// by the engine; it must stay valid as long as the engine may call the
+/// function. Returns 0, or -1 if the arguments are invalid.
+///
+/// # Safety
+/// `engine` must be a live engine and `name` a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn v8rs_register_function(
+    engine: *mut V8rsEngine,
+    name: *const c_char,
+    callback: V8rsCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    guard(|| {
+        let engine = engine.as_mut().ok_or_else(|| Error::runtime(ErrorCode::InvalidFfiArgument, "Engine is null", None))?;
+        let name = borrowed_str(name)?;
+        let callback = callback.ok_or_else(|| Error::runtime(ErrorCode::InvalidFfiArgument, "Callback is null", None))?;
+        
+        let function_name = name.to_string();
+        engine.engine.register_function(name, move |cx| {
+            call_host(callback, user_data, &function_name, cx.args)
+        });
+        Ok(())
+    })
+}
+
+/// Call a C callback with converted arguments
+fn call_host(
+    callback: unsafe extern "C" fn(*mut c_void, *const V8rsValue, usize, *mut V8rsValue) -> c_int,
+    user_data: *mut c_void,
+    name: &str,
+    args: &[Value],
+) -> Result<Value> {
+    let mut c_args = Vec::with_capacity(args.len());
+    for arg in args {
+        c_args.push(V8rsValue::from_value(arg)?);
+    }
+    let mut out = V8rsValue::undefined();
+    
+    // Safety: the callback's contract is documented on `V8rsCallback`
+    let status = unsafe { callback(user_data, c_args.as_ptr(), c_args.len(), &mut out) };
+    let result = if status == 0 {
+        unsafe { out.to_value() }
+    } else {
+        let message = match out.tag {
+            V8rsValueTag::String => unsafe { borrowed_str(out.string) }.unwrap_or_default().to_string(),
+            _ => format!("Host function '{}' failed with status {}", name, status),
+        };
+        Err(Error::host(message))
+    };
+    
+    for mut arg in c_args {
+        // Safety: every argument string was allocated by `from_value`
+        unsafe { v8rs_value_free(&mut arg) };
+    }
+    result
+}
+
+/// Message of the last failing call on this thread, or null
+///
+/// The string is owned by the library; do not free it.
+#[no_mangle]
+pub extern "C" fn v8rs_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn run(engine: *mut V8rsEngine, source: &str) -> (c_int, V8rsValue) {
+        let mut out = V8rsValue::undefined();
+        let status = unsafe { v8rs_execute(engine, source.as_ptr().cast(), source.len(), &mut out) };
+        (status, out)
+    }
+    
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(v8rs_last_error_message()) }.to_str().unwrap().to_string()
+    }
+    
+    #[test]
+    fn test_primitive_and_json_results() {
+        let engine = v8rs_engine_new();
+        
+        let (status, out) = run(engine, "1 + 2");
+        assert_eq!((status, out.tag, out.number), (0, V8rsValueTag::Number, 3.0));
+        
+        let (_, mut out) = run(engine, "'a' + 'b'");
+        assert_eq!(out.tag, V8rsValueTag::String);
+        assert_eq!(unsafe { CStr::from_ptr(out.string) }.to_str().unwrap(), "ab");
+        unsafe { v8rs_value_free(&mut out) };
+        assert!(out.string.is_null());
+        
+        let (_, mut out) = run(engine, "({ a: [1, 'x\"', null, undefined], b: undefined, c: { d: true } })");
+        assert_eq!(out.tag, V8rsValueTag::Json);
+        assert_eq!(
+            unsafe { CStr::from_ptr(out.string) }.to_str().unwrap(),
+            r#"{"a":[1,"x\"",null,null],"c":{"d":true}}"#
+        );
+        unsafe { v8rs_value_free(&mut out) };
+        
+        unsafe { v8rs_engine_free(engine) };
+    }
+    
+    #[test]
+    fn test_errors_are_reported() {
+        let engine = v8rs_engine_new();
+        
+        let (status, out) = run(engine, "let a = []; a[0] = a; a");
+        assert_eq!((status, out.tag), (-1, V8rsValueTag::Undefined));
+        assert!(last_error().contains("circular"));
+        
+        let (status, _) = run(engine, "null.x");
+        assert_eq!(status, -1);
+        assert!(last_error().contains("Cannot read properties of null"));
+        
+        let status = unsafe { v8rs_execute(ptr::null_mut(), ptr::null(), 0, ptr::null_mut()) };
+        assert_eq!(status, -1);
+        assert_eq!(last_error(), "Runtime error: Engine is null");
+        
+        unsafe { v8rs_engine_free(engine) };
+    }
+    
+    #[test]
+    fn test_host_function_panics_are_caught() {
+        let engine = v8rs_engine_new();
+        unsafe { &mut *engine }.engine.register_function("boom", |_| panic!("kaboom"));
+        
+        // The engine turns the panic into an error before it reaches the
+        // boundary, and stays usable
+        let (status, _) = run(engine, "boom()");
+        assert_eq!(status, -1);
+        assert_eq!(last_error(), "Runtime error: Error: boom panicked: kaboom");
+        let (status, out) = run(engine, "1 + 1");
+        assert_eq!((status, out.number), (0, 2.0));
+        
+        unsafe { v8rs_engine_free(engine) };
+    }
+}
@@ -0,0 +1,126 @@
+//! Lexer context flags set by the parser, and the template literal state
+//! the lexer tracks itself
+
+use crate::error::Span;
+
+/// Syntactic context the lexer cannot work out from characters alone
+///
+/// A live lexer reads these before scanning each token; the parser sets
+/// them through `TokenSource` when it knows which grammar it expects next.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LexerContext {
+    /// A `/` here starts a regular expression literal, not a division
+    pub expect_regex_allowed: bool,
+    /// Scanning inside a template literal's `${ ... }` substitution
+    ///
+    /// The lexer works this out from its `TemplateModes` and sets it
+    /// before each token, for extension scanners to read; whatever the
+    /// parser passes is overwritten.
+    pub in_template_substitution: bool,
+}
+
+impl LexerContext {
+    /// Context for an operand position, where `/` starts a regex literal
+    pub fn regex_allowed() -> Self {
+        Self { expect_regex_allowed: true, ..Self::default() }
+    }
+}
+
+/// One level of template literal nesting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemplateMode {
+    /// Inside the text of a template literal opened by the backtick at
+    /// `backtick`
+    Template { backtick: Span },
+    /// Inside a `${ ... }` substitution, with `braces` braces opened in it
+    /// and not yet closed
+    Substitution { braces: u32 },
+}
+
+/// Where the lexer is in nested template literals
+///
+/// A `}` is a plain `RightBrace` unless it closes a substitution, and
+/// only counting the braces opened inside each substitution tells the
+/// two apart: in `` `a${ {b: `${c}`} }d` `` the first `}` closes the
+/// inner substitution, the second the object literal, and the third the
+/// outer substitution. The stack holds a `Template` for each literal
+/// being scanned and a `Substitution` above it while inside its `${`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TemplateModes {
+    stack: Vec<TemplateMode>,
+}
+
+impl TemplateModes {
+    /// Open a template literal at its backtick
+    pub fn push_template(&mut self, backtick: Span) {
+        self.stack.push(TemplateMode::Template { backtick });
+    }
+    
+    /// Close the innermost template literal at its closing backtick,
+    /// returning the span of its opening one
+    pub fn pop_template(&mut self) -> Option<Span> {
+        match self.stack.last() {
+            Some(&TemplateMode::Template { backtick }) => {
+                self.stack.pop();
+                Some(backtick)
+            }
+            _ => None,
+        }
+    }
+    
+    /// Start a substitution at `${` in the innermost template literal
+    pub fn enter_substitution(&mut self) {
+        self.stack.push(TemplateMode::Substitution { braces: 0 });
+    }
+    
+    /// End the innermost substitution at its closing `}`, back in the
+    /// text of its template literal
+    pub fn exit_substitution(&mut self) {
+        if self.closes_substitution() {
+            self.stack.pop();
+        }
+    }
+    
+    /// Count a `{` opened inside the innermost substitution
+    pub fn open_brace(&mut self) {
+        if let Some(TemplateMode::Substitution { braces }) = self.stack.last_mut() {
+            *braces += 1;
+        }
+    }
+    
+    /// Count a `}` that closes a brace opened inside the innermost
+    /// substitution
+    pub fn close_brace(&mut self) {
+        if let Some(TemplateMode::Substitution { braces }) = self.stack.last_mut() {
+            *braces = braces.saturating_sub(1);
+        }
+    }
+    
+    /// Whether a `}` here closes a substitution rather than a brace
+    pub fn closes_substitution(&self) -> bool {
+        matches!(self.stack.last(), Some(TemplateMode::Substitution { braces: 0 }))
+    }
+    
+    /// Whether scanning is inside a substitution
+    pub fn in_substitution(&self) -> bool {
+        matches!(self.stack.last(), Some(TemplateMode::Substitution { .. }))
+    }
+    
+    /// The span of the opening backtick of the innermost template literal
+    pub fn innermost_template(&self) -> Option<Span> {
+        self.stack.iter().rev().find_map(|mode| match mode {
+            TemplateMode::Template { backtick } => Some(*backtick),
+            TemplateMode::Substitution { .. } => None,
+        })
+    }
+    
+    /// How many template literals are open
+    pub fn depth(&self) -> usize {
+        self.stack.iter().filter(|mode| matches!(mode, TemplateMode::Template { .. })).count()
+    }
+    
+    /// The modes, outermost first
+    pub fn modes(&self) -> &[TemplateMode] {
+        &self.stack
+    }
+}
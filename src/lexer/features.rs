@@ -0,0 +1,35 @@
+//! Syntax features the lexer can be told to reject
+
+/// Which optional pieces of syntax the lexer accepts
+///
+/// Every feature is on by default. Turning one off makes the lexer reject
+/// the syntax with an error naming the flag, for embedders that want
+/// scripts limited to what an older runtime understands. BigInt literals,
+/// optional chaining, nullish coalescing and top-level await get flags of
+/// their own as the lexer learns them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureFlags {
+    /// `_` between the digits of a number literal, as in `1_000_000`
+    pub numeric_separators: bool,
+    /// A `#!` line at the start of the source, skipped like a comment
+    pub hashbang: bool,
+}
+
+impl FeatureFlags {
+    /// Every feature enabled
+    pub const ALL: Self = Self { numeric_separators: true, hashbang: true };
+    
+    /// Every feature disabled
+    pub const NONE: Self = Self { numeric_separators: false, hashbang: false };
+}
+
+impl Default for FeatureFlags {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// Message for syntax whose feature flag is off
+pub(crate) fn disabled(syntax: &str, flag: &str) -> String {
+    format!("{} are disabled: feature '{}' is off in FeatureFlags", syntax, flag)
+}
@@ -1,7 +1,11 @@
 //! JavaScript lexer implementation
 
-use super::token::{Token, TokenKind, Keyword};
-use crate::error::{Error, Result, Span};
+use super::token::{Token, TokenKind, Keyword, ContextualKeyword, Trivia, TriviaKind};
+use super::context::{LexerContext, TemplateModes};
+use super::features::{self, FeatureFlags};
+use super::scanner::Scanner;
+use super::unicode::{BidiPolicy, UnicodeHelper};
+use crate::error::{Diagnostic, Error, ErrorCode, Result, Span};
 
 /// JavaScript lexer
 pub struct Lexer<'a> {
@@ -12,91 +16,333 @@ pub struct Lexer<'a> {
     start_line: u32,
     start_column: u32,
     token_start: usize,
+    context: LexerContext,
+    /// Template literals being scanned (see `TemplateModes`)
+    templates: TemplateModes,
+    /// The template modes before each token that changed them, by the
+    /// token's start, so `rewind_to` can restore them
+    template_history: Vec<(usize, TemplateModes)>,
+    features: FeatureFlags,
+    bidi_policy: BidiPolicy,
+    diagnostics: Vec<Diagnostic>,
+    /// Whether to record `trivia`
+    track_trivia: bool,
+    trivia: Vec<Trivia>,
+    /// End of the last trivia recorded, so that trivia skipped again
+    /// after `rewind_to` isn't recorded twice
+    trivia_end: usize,
+    /// Extension scanners, tried in order before the built-in ones
+    scanners: Vec<Box<dyn Scanner + 'a>>,
 }
 
 impl<'a> Lexer<'a> {
     /// Create a new lexer for the given source
+    ///
+    /// A byte order mark at the very start is skipped, and so is a `#!`
+    /// line right after it; the line break ending that line still counts,
+    /// so the code after a shebang starts on line 2.
     pub fn new(source: &'a str) -> Self {
         Self {
             source,
-            current: 0,
+            current: start_offset(source, true),
             line: 1,
             column: 1,
             start_line: 1,
             start_column: 1,
             token_start: 0,
+            context: LexerContext::default(),
+            templates: TemplateModes::default(),
+            template_history: Vec::new(),
+            features: FeatureFlags::default(),
+            bidi_policy: BidiPolicy::default(),
+            diagnostics: Vec::new(),
+            track_trivia: false,
+            trivia: Vec::new(),
+            trivia_end: 0,
+            scanners: Vec::new(),
         }
     }
     
+    /// Add an extension scanner (see `Scanner`)
+    ///
+    /// It runs after the scanners added before it and before all
+    /// built-in scanning.
+    pub fn with_scanner(mut self, scanner: Box<dyn Scanner + 'a>) -> Self {
+        self.scanners.push(scanner);
+        self
+    }
+    
+    /// Accept only the optional syntax enabled in `features`
+    ///
+    /// Call it before scanning: with `hashbang` off, a `#!` line at the
+    /// start is an error instead of being skipped.
+    pub fn with_features(mut self, features: FeatureFlags) -> Self {
+        if self.current == start_offset(self.source, true) {
+            self.current = start_offset(self.source, features.hashbang);
+        }
+        self.features = features;
+        self
+    }
+    
+    /// Record whitespace, comments and a leading `#!` line as `Trivia`
+    /// while scanning (off by default)
+    pub fn with_trivia(mut self, enabled: bool) -> Self {
+        self.track_trivia = enabled;
+        self
+    }
+    
+    /// Trivia recorded so far, in source order
+    pub fn trivia(&self) -> &[Trivia] {
+        &self.trivia
+    }
+    
+    /// Take the trivia recorded so far
+    pub fn take_trivia(&mut self) -> Vec<Trivia> {
+        std::mem::take(&mut self.trivia)
+    }
+    
+    /// Choose how bidirectional control characters outside strings are handled
+    pub fn set_bidi_policy(&mut self, policy: BidiPolicy) {
+        self.bidi_policy = policy;
+    }
+    
+    /// Warnings recorded so far
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+    
+    /// Take the warnings recorded so far
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+    
     /// Tokenize the entire source and return a vector of tokens
+    ///
+    /// Without a parser to say where an operand is expected, `/` is read
+    /// as the start of a regular expression literal only when the previous
+    /// token cannot end an expression. Parsing from a live lexer (see
+    /// `TokenSource`) resolves the cases this guess gets wrong.
     pub fn tokenize(&mut self) -> Result<Vec<Token>> {
-        let mut tokens = Vec::new();
+        let mut tokens: Vec<Token> = Vec::new();
         
-        while !self.is_at_end() {
-            // Skip whitespace but track position
-            if self.current_char().is_whitespace() {
-                if self.current_char() == '\n' {
-                    self.line += 1;
-                    self.column = 1;
-                } else {
-                    self.column += 1;
-                }
-                self.advance();
-                continue;
-            }
-            
-            // Skip comments
-            if self.current_char() == '/' && self.peek() == Some('/') {
-                self.skip_line_comment();
-                continue;
-            }
-            
-            if self.current_char() == '/' && self.peek() == Some('*') {
-                self.skip_block_comment()?;
-                continue;
+        loop {
+            self.context.expect_regex_allowed = regex_allowed_after(tokens.last().map(|token| &token.kind));
+            let token = self.next_token()?;
+            let is_eof = matches!(token.kind, TokenKind::Eof);
+            tokens.push(token);
+            if is_eof {
+                break;
             }
-            
+        }
+        
+        Ok(tokens)
+    }
+    
+    /// Scan the next token under the current context
+    ///
+    /// Returns an `Eof` token once the source is exhausted.
+    pub fn next_token(&mut self) -> Result<Token> {
+        self.context.in_template_substitution = self.templates.in_substitution();
+        let newline_before = self.skip_trivia()?;
+        
+        let mut token = if self.is_at_end() {
+            let eof_span = Span::new(self.current, self.current, self.line, self.column);
+            Token::new(TokenKind::Eof, eof_span)
+        } else {
             // Start of a new token
             self.start_line = self.line;
             self.start_column = self.column;
             self.token_start = self.current;
             
-            let token = self.scan_token()?;
-            tokens.push(token);
+            self.scan_token()?
+        };
+        token.newline_before = newline_before;
+        Ok(token)
+    }
+    
+    /// Current lexer context
+    pub fn context(&self) -> LexerContext {
+        self.context
+    }
+    
+    /// Replace the context consulted when scanning the next token
+    pub fn set_context(&mut self, context: LexerContext) {
+        self.context = context;
+    }
+    
+    /// Where scanning is in nested template literals
+    pub fn template_modes(&self) -> &TemplateModes {
+        &self.templates
+    }
+    
+    /// Move back to the start of a previously scanned token
+    ///
+    /// The template modes go back to what they were there, too.
+    pub fn rewind_to(&mut self, span: Span) {
+        self.current = span.start;
+        self.line = span.line;
+        self.column = span.column;
+        if let Some(index) = self.template_history.iter().position(|(start, _)| *start >= span.start) {
+            self.templates = self.template_history[index].1.clone();
+            self.template_history.truncate(index);
         }
+    }
+    
+    /// Change the template modes, remembering how they were before the
+    /// current token
+    fn change_templates(&mut self, change: impl FnOnce(&mut TemplateModes)) {
+        if self.template_history.last().is_none_or(|(start, _)| *start != self.token_start) {
+            self.template_history.push((self.token_start, self.templates.clone()));
+        }
+        change(&mut self.templates);
+    }
+    
+    /// Skip whitespace and comments, tracking position
+    ///
+    /// Returns whether a line terminator was skipped, including one
+    /// inside a block comment.
+    fn skip_trivia(&mut self) -> Result<bool> {
+        let start = self.current;
+        if self.track_trivia {
+            self.record_hashbang();
+        }
+        while !self.is_at_end() {
+            let (piece_start, line, column) = (self.current, self.line, self.column);
+            let kind = if self.current_char().is_whitespace() {
+                self.advance();
+                TriviaKind::Whitespace
+            } else if self.current_char() == '/' && self.peek() == Some('/') {
+                self.skip_line_comment()?;
+                TriviaKind::LineComment
+            } else if self.current_char() == '/' && self.peek() == Some('*') {
+                self.skip_block_comment()?;
+                TriviaKind::BlockComment
+            } else if UnicodeHelper::is_bidi_control(self.current_char()) {
+                self.check_bidi_control()?;
+                self.advance();
+                TriviaKind::Whitespace
+            } else if self.current_char() == UnicodeHelper::BOM {
+                self.diagnostics.push(Diagnostic::warning(
+                    "Byte order mark inside the source is treated as whitespace"
+                ).with_span(self.char_span()));
+                self.advance();
+                TriviaKind::Whitespace
+            } else {
+                break;
+            };
+            self.record_trivia(kind, Span::new(piece_start, self.current, line, column));
+        }
+        Ok(self.source[start..self.current].contains(['\n', '\r', '\u{2028}', '\u{2029}']))
+    }
+    
+    /// Record the `#!` line skipped at the start, once scanning is past it
+    fn record_hashbang(&mut self) {
+        let bom_len = start_offset(self.source, false);
+        let end = start_offset(self.source, self.features.hashbang);
+        if end > bom_len && self.current == end {
+            self.record_trivia(TriviaKind::Hashbang, Span::new(bom_len, end, 1, 1));
+        }
+    }
+    
+    /// Record trivia when tracking it, merging a whitespace run into the
+    /// one right before it
+    fn record_trivia(&mut self, kind: TriviaKind, span: Span) {
+        if !self.track_trivia || span.start < self.trivia_end {
+            return;
+        }
+        self.trivia_end = span.end;
+        match self.trivia.last_mut() {
+            Some(last) if kind == TriviaKind::Whitespace && last.kind == kind && last.span.end == span.start => {
+                last.span.end = span.end;
+            }
+            _ => self.trivia.push(Trivia { kind, span }),
+        }
+    }
+    
+    /// Let the first extension scanner that claims the input scan a token
+    fn scan_extension_token(&mut self) -> Option<Token> {
+        let rest = &self.source[self.current..];
+        let (kind, length) = self.scanners.iter()
+            .filter_map(|scanner| scanner.scan(rest, &self.context))
+            .find(|&(_, length)| length > 0 && rest.is_char_boundary(length))?;
         
-        // Add EOF token
-        let eof_span = Span::new(self.current, self.current, self.line, self.column);
-        tokens.push(Token::new(TokenKind::Eof, eof_span, String::new()));
-        
-        Ok(tokens)
+        let start_pos = self.current;
+        // Step through the characters so lines and columns stay right
+        while self.current < start_pos + length {
+            self.advance();
+        }
+        let span = self.make_span(start_pos, self.current);
+        Some(Token::new(kind, span))
     }
     
     /// Scan a single token
     fn scan_token(&mut self) -> Result<Token> {
+        if let Some(token) = self.scan_extension_token() {
+            return Ok(token);
+        }
+        
         let start_pos = self.current;
+        if !self.features.hashbang && start_pos == start_offset(self.source, false) && self.source[start_pos..].starts_with("#!") {
+            self.advance();
+            self.advance();
+            return Err(Error::lexer(
+                ErrorCode::DisabledSyntax,
+                features::disabled("Hashbang comments", "hashbang"),
+                self.make_span(start_pos, self.current),
+            ));
+        }
         let c = self.advance();
         
         let kind = match c {
             // Numbers
-            '0'..='9' => self.scan_number()?,
+            '0'..='9' => self.scan_number(c)?,
+            '.' if self.current_char().is_ascii_digit() => self.scan_number(c)?,
             
             // Strings
             '"' | '\'' => self.scan_string(c)?,
+            '`' => {
+                let backtick = self.make_span(start_pos, self.current);
+                self.change_templates(|templates| templates.push_template(backtick));
+                self.scan_template_text(true)?
+            }
             
             // Identifiers and keywords
             'a'..='z' | 'A'..='Z' | '_' | '$' => self.scan_identifier(),
+            c if UnicodeHelper::is_identifier_start(c) => self.scan_identifier(),
             
             // Single character tokens that we'll handle in the next task
             '(' => TokenKind::LeftParen,
             ')' => TokenKind::RightParen,
-            '{' => TokenKind::LeftBrace,
-            '}' => TokenKind::RightBrace,
+            '{' => {
+                if self.templates.in_substitution() {
+                    self.change_templates(TemplateModes::open_brace);
+                }
+                TokenKind::LeftBrace
+            }
+            // The `}` closing a substitution resumes the template's text
+            '}' if self.templates.closes_substitution() => {
+                self.change_templates(TemplateModes::exit_substitution);
+                self.scan_template_text(false)?
+            }
+            '}' => {
+                if self.templates.in_substitution() {
+                    self.change_templates(TemplateModes::close_brace);
+                }
+                TokenKind::RightBrace
+            }
             '[' => TokenKind::LeftBracket,
             ']' => TokenKind::RightBracket,
             ',' => TokenKind::Comma,
             ';' => TokenKind::Semicolon,
-            '.' => TokenKind::Dot,
+            '.' => {
+                if self.current_char() == '.' && self.peek() == Some('.') {
+                    self.advance();
+                    self.advance();
+                    TokenKind::Ellipsis
+                } else {
+                    TokenKind::Dot
+                }
+            }
             ':' => TokenKind::Colon,
             '?' => TokenKind::Question,
             '~' => TokenKind::Tilde,
@@ -105,7 +351,8 @@ impl<'a> Lexer<'a> {
             '+' => self.scan_plus_operator()?,
             '-' => self.scan_minus_operator()?,
             '*' => self.scan_star_operator()?,
-            '/' => TokenKind::Slash, // Already handled division
+            '/' if self.context.expect_regex_allowed => self.scan_regex()?,
+            '/' => self.scan_slash_operator()?,
             '%' => self.scan_percent_operator()?,
             '=' => self.scan_equal_operator()?,
             '!' => self.scan_bang_operator()?,
@@ -117,6 +364,7 @@ impl<'a> Lexer<'a> {
             
             _ => {
                 return Err(Error::lexer(
+                    ErrorCode::UnexpectedCharacter,
                     format!("Unexpected character: '{}'", c),
                     self.make_span(self.token_start, self.current),
                 ));
@@ -124,63 +372,142 @@ impl<'a> Lexer<'a> {
         };
         
         let end = self.current;
-        let span = self.make_span(start_pos, end);
-        let text = self.safe_slice(start_pos, end);
-        
-        Ok(Token::new(kind, span, text))
+        Ok(Token::new(kind, self.make_span(start_pos, end)))
     }
     
-    /// Scan a number literal
-    fn scan_number(&mut self) -> Result<TokenKind> {
-        // Scan integer part
-        while self.current_char().is_ascii_digit() {
-            self.advance();
+    /// Scan a number literal whose first character, a digit or the `.`
+    /// of `.5`, is already consumed
+    ///
+    /// Handles `0x`/`0o`/`0b` prefixes and `_` separators. A literal must
+    /// not run straight into an identifier or another `.5`-style fraction:
+    /// `3in` and `1.2.3` are errors, not two tokens.
+    fn scan_number(&mut self, first: char) -> Result<TokenKind> {
+        if first == '0' {
+            let radix = match self.current_char() {
+                'x' | 'X' => Some(16),
+                'o' | 'O' => Some(8),
+                'b' | 'B' => Some(2),
+                _ => None,
+            };
+            if let Some(radix) = radix {
+                let prefix = self.advance();
+                let digits_start = self.current;
+                self.scan_digits(radix, false)?;
+                if self.current == digits_start {
+                    return Err(self.number_error(&format!("missing digits after '0{}'", prefix)));
+                }
+                self.check_number_end()?;
+                let value = self.source[digits_start..self.current].chars()
+                    .filter_map(|c| c.to_digit(radix))
+                    .fold(0.0, |value, digit| value * radix as f64 + digit as f64);
+                return Ok(TokenKind::Number(value));
+            }
         }
         
-        // Check for decimal point
-        if self.current_char() == '.' && self.peek().map(|c| c.is_ascii_digit()).unwrap_or(false) {
-            self.advance(); // consume '.'
-            
-            // Scan fractional part
-            while self.current_char().is_ascii_digit() {
+        if first == '.' {
+            self.scan_digits(10, false)?;
+        } else {
+            // Integer part, then an optional fraction: `1.` and `1.5` both count
+            self.scan_digits(10, true)?;
+            if self.current_char() == '.' {
                 self.advance();
+                self.scan_digits(10, false)?;
             }
         }
         
-        // Check for exponent
         if matches!(self.current_char(), 'e' | 'E') {
-            self.advance(); // consume 'e' or 'E'
-            
-            // Optional sign
+            self.advance();
             if matches!(self.current_char(), '+' | '-') {
                 self.advance();
             }
-            
-            // Exponent digits
-            if !self.current_char().is_ascii_digit() {
-                return Err(Error::lexer(
-                    "Invalid number: expected digits after exponent".to_string(),
-                    self.make_span(self.current, self.current + 1),
-                ));
-            }
-            
-            while self.current_char().is_ascii_digit() {
-                self.advance();
+            let digits_start = self.current;
+            self.scan_digits(10, false)?;
+            if self.current == digits_start {
+                return Err(self.number_error("missing digits in exponent"));
             }
         }
+        self.check_number_end()?;
         
-        // Parse the number
-        let number_text = self.safe_slice(self.token_start, self.current);
-        
+        let number_text: String = self.safe_slice(self.token_start, self.current)
+            .chars()
+            .filter(|&c| c != '_')
+            .collect();
         match number_text.parse::<f64>() {
             Ok(value) => Ok(TokenKind::Number(value)),
-            Err(_) => Err(Error::lexer(
-                format!("Invalid number: {}", number_text),
-                self.make_span(self.token_start, self.current),
-            )),
+            Err(_) => Err(self.number_error("not a number")),
+        }
+    }
+    
+    /// Scan a run of digits in `radix`, with single `_` separators
+    /// between them
+    ///
+    /// `after_digit` is whether the character before the run is a digit
+    /// the run continues.
+    fn scan_digits(&mut self, radix: u32, mut after_digit: bool) -> Result<()> {
+        loop {
+            let c = self.current_char();
+            if c.is_digit(radix) {
+                self.advance();
+                after_digit = true;
+            } else if c == '_' {
+                if !self.features.numeric_separators {
+                    let (message, span) = self.malformed_number(&features::disabled("numeric separators", "numeric_separators"));
+                    return Err(Error::lexer(ErrorCode::DisabledSyntax, message, span));
+                }
+                let next_is_digit = self.peek().is_some_and(|next| next.is_digit(radix));
+                if !after_digit || !next_is_digit {
+                    return Err(self.number_error("numeric separators are only allowed between digits"));
+                }
+                self.advance();
+                after_digit = false;
+            } else {
+                return Ok(());
+            }
         }
     }
     
+    /// Reject a literal followed directly by an identifier or a fraction
+    fn check_number_end(&mut self) -> Result<()> {
+        let c = self.current_char();
+        if self.is_at_end() {
+            return Ok(());
+        }
+        if c.is_ascii_digit() {
+            return Err(self.number_error(&format!("'{}' is not a valid digit here", c)));
+        }
+        if c == '\\' || UnicodeHelper::is_identifier_start(c) {
+            return Err(self.number_error("identifier starts immediately after numeric literal"));
+        }
+        if c == '.' && self.peek().is_some_and(|next| next.is_ascii_digit()) {
+            return Err(self.number_error("unexpected '.' after numeric literal"));
+        }
+        Ok(())
+    }
+    
+    /// An error for the number literal being scanned, spanning the rest of
+    /// the malformed token
+    fn number_error(&mut self, problem: &str) -> Error {
+        let (message, span) = self.malformed_number(problem);
+        Error::lexer(ErrorCode::InvalidNumber, message, span)
+    }
+    
+    /// Skip the rest of the malformed number being scanned, returning the
+    /// message and span of its error
+    fn malformed_number(&mut self, problem: &str) -> (String, Span) {
+        loop {
+            let c = self.current_char();
+            let continues = !self.is_at_end()
+                && (UnicodeHelper::is_identifier_part(c)
+                    || (c == '.' && self.peek().is_some_and(|next| next.is_ascii_digit())));
+            if !continues {
+                break;
+            }
+            self.advance();
+        }
+        let message = format!("Invalid number '{}': {}", self.safe_slice(self.token_start, self.current), problem);
+        (message, self.make_span(self.token_start, self.current))
+    }
+    
     /// Scan a string literal
     fn scan_string(&mut self, quote: char) -> Result<TokenKind> {
         let mut value = String::new();
@@ -192,80 +519,21 @@ impl<'a> Lexer<'a> {
                 
                 if self.is_at_end() {
                     return Err(Error::lexer(
+                        ErrorCode::UnterminatedString,
                         "Unterminated string: unexpected end of input after escape".to_string(),
                         self.make_span(self.current - 1, self.current),
                     ));
                 }
                 
-                let escaped = match self.advance() {
-                    'n' => '\n',
-                    't' => '\t',
-                    'r' => '\r',
-                    '\\' => '\\',
-                    '\'' => '\'',
-                    '"' => '"',
-                    '0' => '\0',
-                    'x' => {
-                        // Hexadecimal escape sequence \xHH
-                        let hex = self.scan_hex_escape(2)?;
-                        char::from(hex as u8)
-                    },
-                    'u' => {
-                        // Unicode escape sequence \uHHHH
-                        if self.current_char() == '{' {
-                            // \u{HHHHHH}
-                            self.advance(); // consume '{'
-                            let mut hex_digits = String::new();
-                            while self.current_char() != '}' && !self.is_at_end() {
-                                if self.current_char().is_ascii_hexdigit() {
-                                    hex_digits.push(self.advance());
-                                } else {
-                                    return Err(Error::lexer(
-                                        "Invalid unicode escape sequence".to_string(),
-                                        self.make_span(self.current, self.current + 1),
-                                    ));
-                                }
-                            }
-                            if self.current_char() == '}' {
-                                self.advance(); // consume '}'
-                            }
-                            
-                            let code_point = u32::from_str_radix(&hex_digits, 16)
-                                .map_err(|_| Error::lexer(
-                                    "Invalid unicode escape sequence".to_string(),
-                                    self.make_span(self.current - hex_digits.len(), self.current),
-                                ))?;
-                                
-                            char::from_u32(code_point).ok_or_else(|| Error::lexer(
-                                "Invalid unicode code point".to_string(),
-                                self.make_span(self.current - hex_digits.len(), self.current),
-                            ))?
-                        } else {
-                            // \uHHHH
-                            let hex = self.scan_hex_escape(4)?;
-                            char::from_u32(hex).ok_or_else(|| Error::lexer(
-                                "Invalid unicode escape sequence".to_string(),
-                                self.make_span(self.current - 4, self.current),
-                            ))?
-                        }
-                    },
-                    c => c, // For any other character, include it literally
-                };
-                
-                value.push(escaped);
+                value.extend(self.scan_escape()?);
             } else {
-                if self.current_char() == '\n' {
-                    self.line += 1;
-                    self.column = 1;
-                } else {
-                    self.column += 1;
-                }
                 value.push(self.advance());
             }
         }
         
         if self.is_at_end() {
             return Err(Error::lexer(
+                ErrorCode::UnterminatedString,
                 "Unterminated string literal".to_string(),
                 self.make_span(self.token_start, self.current),
             ));
@@ -277,13 +545,197 @@ impl<'a> Lexer<'a> {
         Ok(TokenKind::String(value))
     }
     
+    /// Read an escape sequence after its backslash, returning the character
+    /// it stands for, or `None` for a line continuation
+    ///
+    /// String and template literals share this, so both accept exactly the
+    /// same escapes.
+    fn scan_escape(&mut self) -> Result<Option<char>> {
+        Ok(Some(match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{C}',
+            'v' => '\u{B}',
+            '\\' => '\\',
+            '\'' => '\'',
+            '"' => '"',
+            '0' => '\0',
+            // A backslash before a line break continues the literal on
+            // the next line
+            '\r' => {
+                if self.current_char() == '\n' {
+                    self.advance();
+                }
+                return Ok(None);
+            }
+            '\n' | '\u{2028}' | '\u{2029}' => return Ok(None),
+            'x' => {
+                // Hexadecimal escape sequence \xHH
+                let hex = self.scan_hex_escape(2)?;
+                char::from(hex as u8)
+            },
+            'u' => {
+                // Unicode escape sequence \uHHHH
+                if self.current_char() == '{' {
+                    // \u{HHHHHH}
+                    self.advance(); // consume '{'
+                    let mut hex_digits = String::new();
+                    while self.current_char() != '}' && !self.is_at_end() {
+                        if self.current_char().is_ascii_hexdigit() {
+                            hex_digits.push(self.advance());
+                        } else {
+                            return Err(Error::lexer(
+                                ErrorCode::InvalidEscape,
+                                "Invalid unicode escape sequence".to_string(),
+                                self.make_span(self.current, self.current + 1),
+                            ));
+                        }
+                    }
+                    if self.is_at_end() {
+                        return Err(Error::lexer(
+                            ErrorCode::InvalidEscape,
+                            "Invalid unicode escape sequence: missing '}'".to_string(),
+                            self.make_span(self.current - hex_digits.len(), self.current),
+                        ));
+                    }
+                    self.advance(); // consume '}'
+                    
+                    let code_point = u32::from_str_radix(&hex_digits, 16)
+                        .map_err(|_| Error::lexer(
+                            ErrorCode::InvalidEscape,
+                            "Invalid unicode escape sequence".to_string(),
+                            self.make_span(self.current - hex_digits.len(), self.current),
+                        ))?;
+                    
+                    char::from_u32(code_point).ok_or_else(|| Error::lexer(
+                        ErrorCode::InvalidEscape,
+                        "Invalid unicode code point".to_string(),
+                        self.make_span(self.current - hex_digits.len(), self.current),
+                    ))?
+                } else {
+                    // \uHHHH
+                    let hex = self.scan_hex_escape(4)?;
+                    char::from_u32(hex).ok_or_else(|| Error::lexer(
+                        ErrorCode::InvalidEscape,
+                        "Invalid unicode escape sequence".to_string(),
+                        self.make_span(self.current - 4, self.current),
+                    ))?
+                }
+            },
+            c => c, // For any other character, include it literally
+        }))
+    }
+    
+    /// Scan template literal text up to and including the next `${` or
+    /// the closing backtick
+    ///
+    /// `first` is whether the text follows the opening backtick rather
+    /// than the `}` of a substitution. The value is cooked: escapes are
+    /// resolved and line breaks become `\n`.
+    fn scan_template_text(&mut self, first: bool) -> Result<TokenKind> {
+        let mut value = String::new();
+        loop {
+            if self.is_at_end() {
+                // Point at the literal's own backtick, however far back it is
+                let backtick = self.templates.innermost_template()
+                    .unwrap_or_else(|| self.make_span(self.token_start, self.current));
+                return Err(Error::lexer(ErrorCode::UnterminatedTemplate, "Unterminated template literal".to_string(), backtick));
+            }
+            match self.advance() {
+                '`' => {
+                    self.change_templates(|templates| {
+                        templates.pop_template();
+                    });
+                    return Ok(if first { TokenKind::NoSubstitutionTemplate(value) } else { TokenKind::TemplateTail(value) });
+                }
+                '$' if self.current_char() == '{' => {
+                    self.advance();
+                    self.change_templates(TemplateModes::enter_substitution);
+                    return Ok(if first { TokenKind::TemplateHead(value) } else { TokenKind::TemplateMiddle(value) });
+                }
+                '\\' if !self.is_at_end() => value.extend(self.scan_escape()?),
+                '\r' => {
+                    if self.current_char() == '\n' {
+                        self.advance();
+                    }
+                    value.push('\n');
+                }
+                c => value.push(c),
+            }
+        }
+    }
+    
+    /// Scan a regular expression literal after its opening `/`
+    fn scan_regex(&mut self) -> Result<TokenKind> {
+        let mut pattern = String::new();
+        let mut in_class = false;
+        
+        loop {
+            if self.is_at_end() || self.current_char() == '\n' {
+                return Err(Error::lexer(
+                    ErrorCode::UnterminatedRegExp,
+                    "Unterminated regular expression literal".to_string(),
+                    self.make_span(self.token_start, self.current),
+                ));
+            }
+            
+            let c = self.advance();
+            match c {
+                '/' if !in_class => break,
+                '\\' => {
+                    pattern.push(c);
+                    if !self.is_at_end() && self.current_char() != '\n' {
+                        pattern.push(self.advance());
+                    }
+                    continue;
+                }
+                '[' => in_class = true,
+                ']' => in_class = false,
+                _ => {}
+            }
+            pattern.push(c);
+        }
+        
+        let flags_start = self.current;
+        let mut flags = String::new();
+        while !self.is_at_end() && UnicodeHelper::is_identifier_part(self.current_char()) {
+            let flag = self.advance();
+            if !"dgimsuyv".contains(flag) || flags.contains(flag) {
+                return Err(Error::lexer(
+                    ErrorCode::InvalidRegExpFlags,
+                    format!("Invalid regular expression flags '{}'", self.safe_slice(flags_start, self.current)),
+                    self.make_span(self.token_start, self.current),
+                ));
+            }
+            flags.push(flag);
+        }
+        
+        Ok(TokenKind::RegExp { pattern, flags })
+    }
+    
     /// Scan an identifier or keyword
     fn scan_identifier(&mut self) -> TokenKind {
-        while self.is_identifier_continue(self.current_char()) {
+        while UnicodeHelper::is_identifier_part(self.current_char()) {
             self.advance();
         }
         
-        let text = self.safe_slice(self.token_start, self.current);
+        let raw = self.safe_slice(self.token_start, self.current);
+        if raw.chars().any(UnicodeHelper::is_zero_width_joiner) {
+            self.diagnostics.push(Diagnostic::warning(format!(
+                "Identifier '{}' contains an invisible zero-width joiner", raw
+            )).with_span(self.make_span(self.token_start, self.current)));
+        }
+        
+        // Canonically equivalent spellings name the same binding
+        let text = UnicodeHelper::normalize_identifier(&raw);
+        
+        // Contextual keywords stay identifiers; the token carries the
+        // classification so the parser can recognize them where they matter
+        if ContextualKeyword::from_str(&text).is_some() {
+            return TokenKind::Identifier(text);
+        }
         
         // Check if it's a keyword
         if let Some(keyword) = Keyword::from_str(&text) {
@@ -299,17 +751,6 @@ impl<'a> Lexer<'a> {
         }
     }
     
-    /// Check if a character can start an identifier
-    #[allow(dead_code)]
-    fn is_identifier_start(&self, c: char) -> bool {
-        c.is_alphabetic() || c == '_' || c == '$' || unicode_xid::UnicodeXID::is_xid_start(c)
-    }
-    
-    /// Check if a character can continue an identifier
-    fn is_identifier_continue(&self, c: char) -> bool {
-        c.is_alphanumeric() || c == '_' || c == '$' || unicode_xid::UnicodeXID::is_xid_continue(c)
-    }
-    
     /// Scan hexadecimal escape sequence
     fn scan_hex_escape(&mut self, digits: usize) -> Result<u32> {
         let mut hex_string = String::new();
@@ -317,6 +758,7 @@ impl<'a> Lexer<'a> {
         for _ in 0..digits {
             if self.is_at_end() || !self.current_char().is_ascii_hexdigit() {
                 return Err(Error::lexer(
+                    ErrorCode::InvalidEscape,
                     format!("Invalid hex escape sequence: expected {} hex digits", digits),
                     self.make_span(self.current, self.current + 1),
                 ));
@@ -325,20 +767,26 @@ impl<'a> Lexer<'a> {
         }
         
         u32::from_str_radix(&hex_string, 16).map_err(|_| Error::lexer(
+            ErrorCode::InvalidEscape,
             "Invalid hex escape sequence".to_string(),
             self.make_span(self.current - digits, self.current),
         ))
     }
     
     /// Skip line comment (// ...)
-    fn skip_line_comment(&mut self) {
-        while !self.is_at_end() && self.current_char() != '\n' {
+    fn skip_line_comment(&mut self) -> Result<()> {
+        while !self.is_at_end() && !matches!(self.current_char(), '\n' | '\r' | '\u{2028}' | '\u{2029}') {
+            self.check_bidi_control()?;
             self.advance();
         }
+        Ok(())
     }
     
     /// Skip block comment (/* ... */)
+    ///
+    /// An unterminated comment is reported at its `/*`.
     fn skip_block_comment(&mut self) -> Result<()> {
+        let start = Span::new(self.current, self.current, self.line, self.column);
         self.advance(); // consume '/'
         self.advance(); // consume '*'
         
@@ -349,21 +797,41 @@ impl<'a> Lexer<'a> {
                 return Ok(());
             }
             
-            if self.current_char() == '\n' {
-                self.line += 1;
-                self.column = 1;
-            } else {
-                self.column += 1;
-            }
+            self.check_bidi_control()?;
             self.advance();
         }
         
         Err(Error::lexer(
+            ErrorCode::UnterminatedComment,
             "Unterminated block comment".to_string(),
-            self.make_span(self.current, self.current),
+            Span::new(start.start, self.current, start.line, start.column),
         ))
     }
     
+    /// Apply the bidi policy if the current character is a bidi control
+    fn check_bidi_control(&mut self) -> Result<()> {
+        let c = self.current_char();
+        if !UnicodeHelper::is_bidi_control(c) {
+            return Ok(());
+        }
+        
+        let message = format!(
+            "Bidirectional control character U+{:04X} outside a string literal", c as u32
+        );
+        match self.bidi_policy {
+            BidiPolicy::Reject => Err(Error::lexer(ErrorCode::BidiControl, message, self.char_span())),
+            BidiPolicy::Warn => {
+                self.diagnostics.push(Diagnostic::warning(message).with_span(self.char_span()));
+                Ok(())
+            }
+        }
+    }
+    
+    /// Span of the current character
+    fn char_span(&self) -> Span {
+        Span::new(self.current, self.current + self.current_char().len_utf8(), self.line, self.column)
+    }
+    
     /// Get the current character
     fn current_char(&self) -> char {
         if self.is_at_end() {
@@ -383,77 +851,45 @@ impl<'a> Lexer<'a> {
         }
     }
     
-    /// Get character at specific byte position
+    /// Get the character starting at a byte position
+    ///
+    /// `'\0'` past the end or inside a multi-byte character, which a
+    /// well-formed lexer never asks for.
     fn char_at_byte_pos(&self, byte_pos: usize) -> char {
-        if byte_pos >= self.source.len() {
-            return '\0';
-        }
-        
-        // Find the character that starts at or contains this byte position
-        let mut current_byte = 0;
-        for ch in self.source.chars() {
-            if current_byte == byte_pos {
-                return ch;
-            }
-            current_byte += ch.len_utf8();
-            if current_byte > byte_pos {
-                // We're in the middle of a multi-byte character
-                // This shouldn't happen in a well-formed lexer
-                return '\0';
-            }
-        }
-        '\0'
+        self.source.get(byte_pos..)
+            .and_then(|rest| rest.chars().next())
+            .unwrap_or('\0')
     }
     
-    /// Safely slice string respecting UTF-8 character boundaries
+    /// Copy the source between two byte positions, with an end inside a
+    /// multi-byte character moved past it
     fn safe_slice(&self, start: usize, end: usize) -> String {
-        if start >= self.source.len() {
-            return String::new();
+        let mut end = end.min(self.source.len());
+        while !self.source.is_char_boundary(end) {
+            end += 1;
         }
-        
-        let end = end.min(self.source.len());
-        
-        // Find the actual character boundaries
-        let mut current_byte = 0;
-        let mut start_char_pos = 0;
-        let mut end_char_pos = 0;
-        let mut char_count = 0;
-        
-        for ch in self.source.chars() {
-            if current_byte == start {
-                start_char_pos = char_count;
-            }
-            if current_byte == end {
-                end_char_pos = char_count;
-                break;
-            }
-            current_byte += ch.len_utf8();
-            char_count += 1;
-            
-            // If we've passed the end position, use this character boundary
-            if current_byte >= end {
-                end_char_pos = char_count;
-                break;
-            }
-        }
-        
-        // If we didn't find the exact end, use the last character
-        if current_byte < end {
-            end_char_pos = char_count;
-        }
-        
-        // Now we can safely slice using character positions
-        self.source.chars()
-            .skip(start_char_pos)
-            .take(end_char_pos - start_char_pos)
-            .collect()
+        self.source.get(start..end).unwrap_or_default().to_string()
     }
     
     /// Advance to the next character
+    ///
+    /// This is the only place the line and column move, so every
+    /// character is counted exactly once, whether it is in a token, a
+    /// comment or whitespace. `\r\n` counts as one line break.
     fn advance(&mut self) -> char {
         let c = self.current_char();
         self.current += c.len_utf8();
-        self.column += 1;
+        let is_line_break = match c {
+            '\n' | '\u{2028}' | '\u{2029}' => true,
+            '\r' => self.current_char() != '\n',
+            _ => false,
+        };
+        if is_line_break {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         c
     }
     
@@ -532,6 +968,16 @@ impl<'a> Lexer<'a> {
         }
     }
     
+    /// Scan / or /=
+    fn scan_slash_operator(&mut self) -> Result<TokenKind> {
+        if self.current_char() == '=' {
+            self.advance();
+            Ok(TokenKind::SlashEqual)
+        } else {
+            Ok(TokenKind::Slash)
+        }
+    }
+    
     /// Scan % or %=
     fn scan_percent_operator(&mut self) -> Result<TokenKind> {
         if self.current_char() == '=' {
@@ -625,4 +1071,33 @@ impl<'a> Lexer<'a> {
             Ok(TokenKind::Pipe)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Where scanning starts: after a byte order mark and, when `hashbang`
+/// is allowed, after a `#!` line
+fn start_offset(source: &str, hashbang: bool) -> usize {
+    let bom_len = if source.starts_with(UnicodeHelper::BOM) { UnicodeHelper::BOM.len_utf8() } else { 0 };
+    let rest = &source[bom_len..];
+    if hashbang && rest.starts_with("#!") {
+        bom_len + rest.find(['\n', '\r', '\u{2028}', '\u{2029}']).unwrap_or(rest.len())
+    } else {
+        bom_len
+    }
+}
+
+/// Whether a `/` following `previous` starts a regular expression literal
+///
+/// Tokens that can end an expression make the `/` a division operator.
+fn regex_allowed_after(previous: Option<&TokenKind>) -> bool {
+    !matches!(
+        previous,
+        Some(
+            TokenKind::Number(_) | TokenKind::String(_) | TokenKind::Boolean(_) |
+            TokenKind::Null | TokenKind::Undefined | TokenKind::RegExp { .. } |
+            TokenKind::NoSubstitutionTemplate(_) | TokenKind::TemplateTail(_) |
+            TokenKind::Identifier(_) | TokenKind::Keyword(Keyword::This) | TokenKind::Keyword(Keyword::Super) |
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace |
+            TokenKind::PlusPlus | TokenKind::MinusMinus
+        )
+    )
+}
@@ -4,10 +4,24 @@
 //! Supports the core JavaScript token types needed for our engine.
 
 pub mod token;
+pub mod context;
+pub mod features;
+pub mod source;
+pub mod scanner;
+pub mod unicode;
+pub mod utils;
+#[allow(clippy::module_inception)]
 pub mod lexer;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
-pub use token::{Token, TokenKind, Keyword};
-pub use lexer::Lexer;
\ No newline at end of file
+pub use token::{Token, TokenKind, Keyword, ContextualKeyword, Trivia, TriviaKind};
+pub use lexer::Lexer;
+pub use context::{LexerContext, TemplateMode, TemplateModes};
+pub use features::FeatureFlags;
+pub use source::TokenSource;
+pub use scanner::Scanner;
+pub use unicode::{BidiPolicy, UnicodeHelper};
+pub use utils::EscapeValidator;
\ No newline at end of file
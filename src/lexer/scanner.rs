@@ -0,0 +1,23 @@
+//! Extension Scanners
+//!
+//! A `Scanner` lets an embedder add token syntax (a pipeline operator,
+//! decorators, JSX-like tags) without changing the built-in lexer. Each
+//! scanner registered with `Lexer::with_scanner` is offered the source at
+//! every token start, after whitespace and comments are skipped, and
+//! before any built-in scanning. Scanners are tried in registration order
+//! and the first to claim the input wins; when none does, the built-in
+//! rules apply as usual.
+
+use super::context::LexerContext;
+use super::token::TokenKind;
+
+/// A pluggable token scanner
+pub trait Scanner {
+    /// Try to scan one token at the start of `rest`, the unscanned source
+    ///
+    /// Returns the token kind and its length in bytes, or `None` to leave
+    /// the input to the next scanner. A length of zero, or one that does
+    /// not end on a character boundary, is treated as `None`. `context`
+    /// is what the parser told the lexer about the current position.
+    fn scan(&self, rest: &str, context: &LexerContext) -> Option<(TokenKind, usize)>;
+}
@@ -0,0 +1,68 @@
+//! Token sources the parser can pull from
+//!
+//! The parser consumes tokens one at a time through `TokenSource`. A
+//! pre-lexed token vector ignores context and replays what it holds; a live
+//! `Lexer` scans each token on demand and can re-scan one under a different
+//! context, which is how the parser resolves `/` between division and a
+//! regular expression literal.
+
+use super::context::LexerContext;
+use super::lexer::Lexer;
+use super::token::{Token, TokenKind};
+use crate::error::{Error, ErrorCode, Result, Span};
+
+/// A stream of tokens that the parser drives
+pub trait TokenSource {
+    /// Produce the next token, scanned under `context` where that matters
+    fn next_token(&mut self, context: &LexerContext) -> Result<Token>;
+    
+    /// Scan again from the start of `token` under `context`
+    ///
+    /// Everything produced after `token` is discarded; the next
+    /// `next_token` call continues after the re-scanned token. Returns
+    /// `None` when the source cannot re-scan (its tokens are fixed).
+    fn rescan(&mut self, token: &Token, context: &LexerContext) -> Result<Option<Token>>;
+}
+
+impl TokenSource for std::vec::IntoIter<Token> {
+    fn next_token(&mut self, _context: &LexerContext) -> Result<Token> {
+        self.next().ok_or_else(|| Error::parser(
+            ErrorCode::UnexpectedEnd,
+            "Unexpected end of token stream".to_string(),
+            Span::new(0, 0, 1, 1),
+        ))
+    }
+    
+    fn rescan(&mut self, _token: &Token, _context: &LexerContext) -> Result<Option<Token>> {
+        Ok(None)
+    }
+}
+
+impl TokenSource for Lexer<'_> {
+    fn next_token(&mut self, context: &LexerContext) -> Result<Token> {
+        self.set_context(*context);
+        Lexer::next_token(self)
+    }
+    
+    fn rescan(&mut self, token: &Token, context: &LexerContext) -> Result<Option<Token>> {
+        if matches!(token.kind, TokenKind::Eof) {
+            return Ok(None);
+        }
+        self.rewind_to(token.span);
+        self.set_context(*context);
+        // Rewinding skips no trivia, so the line break before the token is carried over
+        let mut rescanned = Lexer::next_token(self)?;
+        rescanned.newline_before = token.newline_before;
+        Ok(Some(rescanned))
+    }
+}
+
+impl<S: TokenSource + ?Sized> TokenSource for &mut S {
+    fn next_token(&mut self, context: &LexerContext) -> Result<Token> {
+        (**self).next_token(context)
+    }
+    
+    fn rescan(&mut self, token: &Token, context: &LexerContext) -> Result<Option<Token>> {
+        (**self).rescan(token, context)
+    }
+}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
-    use crate::lexer::{Lexer, TokenKind, Keyword};
+    use crate::lexer::{Lexer, TokenKind, Keyword, ContextualKeyword};
+    use crate::error::ErrorCode;
     
     #[test]
     fn test_basic_tokenization() {
@@ -93,6 +94,42 @@ btn.addEventListener("click", () => {
         assert!(has_cafe, "Should handle Unicode identifiers");
     }
     
+    #[test]
+    fn test_identifiers_follow_xid_properties() {
+        // Not alphabetic, but Other_ID_Start
+        let tokens = Lexer::new("\u{2118}x").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("\u{2118}x".to_string()));
+        
+        // Superscript digits are alphanumeric but don't continue a name, and
+        // circled letters are alphabetic but don't start one
+        for source in ["a\u{00B2}", "\u{24B6}"] {
+            let kinds: Vec<TokenKind> = Lexer::new(source).tokenize()
+                .map(|tokens| tokens.into_iter().map(|token| token.kind).collect())
+                .unwrap_or_default();
+            assert!(!matches!(kinds.as_slice(), [TokenKind::Identifier(_), TokenKind::Eof]), "{:?}: {:?}", source, kinds);
+        }
+    }
+    
+    #[test]
+    fn test_string_and_template_escapes_agree() {
+        let string = |source: &str| match Lexer::new(source).tokenize().map(|tokens| tokens[0].kind.clone()) {
+            Ok(TokenKind::String(value) | TokenKind::NoSubstitutionTemplate(value)) => Ok(value),
+            Ok(other) => panic!("{}: {:?}", source, other),
+            Err(error) => Err(error.code()),
+        };
+        for (body, expected) in [
+            ("\\b\\f\\v\\0", Ok("\u{8}\u{C}\u{B}\0".to_string())),
+            ("a\\\nb\\\r\nc\\\u{2028}d", Ok("abcd".to_string())),
+            ("\\x41\\u0042\\u{43}\\q", Ok("ABCq".to_string())),
+            ("\\x4", Err(ErrorCode::InvalidEscape)),
+            ("\\u{41", Err(ErrorCode::InvalidEscape)),
+            ("\\u{110000}", Err(ErrorCode::InvalidEscape)),
+        ] {
+            assert_eq!(string(&format!("'{}'", body)), expected, "string {:?}", body);
+            assert_eq!(string(&format!("`{}`", body)), expected, "template {:?}", body);
+        }
+    }
+    
     #[test]
     fn test_unicode_strings() {
         // Test with Unicode content in strings
@@ -165,4 +202,471 @@ function test() {
         assert_eq!(tokens2.len(), 1);
         matches!(&tokens2[0].kind, TokenKind::Eof);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_contextual_keywords_are_identifiers() {
+        let mut lexer = Lexer::new("of async from let");
+        let tokens = lexer.tokenize().unwrap();
+        
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("of".to_string()));
+        assert_eq!(tokens[0].contextual_keyword, Some(ContextualKeyword::Of));
+        assert_eq!(tokens[1].kind, TokenKind::Identifier("async".to_string()));
+        assert_eq!(tokens[1].contextual_keyword, Some(ContextualKeyword::Async));
+        assert_eq!(tokens[2].kind, TokenKind::Identifier("from".to_string()));
+        assert_eq!(tokens[2].contextual_keyword, Some(ContextualKeyword::From));
+        
+        // Reserved words are unaffected
+        assert_eq!(tokens[3].kind, TokenKind::Keyword(Keyword::Let));
+        assert_eq!(tokens[3].contextual_keyword, None);
+    }
+    
+    #[test]
+    fn test_ellipsis_token() {
+        let mut lexer = Lexer::new("{ ...a, b: c.d }");
+        let kinds: Vec<TokenKind> = lexer.tokenize().unwrap().into_iter().map(|t| t.kind).collect();
+        
+        assert_eq!(kinds[1], TokenKind::Ellipsis);
+        assert_eq!(kinds[2], TokenKind::Identifier("a".to_string()));
+        assert_eq!(kinds[7], TokenKind::Dot);
+    }
+    
+    #[test]
+    fn test_slash_after_operand_is_division() {
+        let mut lexer = Lexer::new("a = b / c /= d");
+        let kinds: Vec<TokenKind> = lexer.tokenize().unwrap().into_iter().map(|t| t.kind).collect();
+        
+        assert_eq!(kinds[3], TokenKind::Slash);
+        assert_eq!(kinds[5], TokenKind::SlashEqual);
+    }
+    
+    #[test]
+    fn test_regex_literal_token() {
+        let source = "x = /a[/]b\\/c/gi.test(s)";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        
+        assert_eq!(tokens[2].kind, TokenKind::RegExp {
+            pattern: "a[/]b\\/c".to_string(),
+            flags: "gi".to_string(),
+        });
+        assert_eq!(tokens[2].text_in(source), "/a[/]b\\/c/gi");
+        assert_eq!(tokens[3].kind, TokenKind::Dot);
+    }
+    
+    #[test]
+    fn test_regex_literal_errors() {
+        assert!(Lexer::new("x = /abc\ny/").tokenize().is_err());
+        assert!(Lexer::new("x = /abc/gg").tokenize().is_err());
+        assert!(Lexer::new("x = /abc/q").tokenize().is_err());
+    }
+    
+    #[test]
+    fn test_live_lexer_rescans_slash_as_regex() {
+        use crate::lexer::{LexerContext, TokenSource};
+        
+        let mut lexer = Lexer::new("/c/g + 1");
+        let slash = TokenSource::next_token(&mut lexer, &LexerContext::default()).unwrap();
+        assert_eq!(slash.kind, TokenKind::Slash);
+        
+        let regex = lexer.rescan(&slash, &LexerContext::regex_allowed()).unwrap().unwrap();
+        assert_eq!(regex.kind, TokenKind::RegExp { pattern: "c".to_string(), flags: "g".to_string() });
+        
+        let next = TokenSource::next_token(&mut lexer, &LexerContext::default()).unwrap();
+        assert_eq!(next.kind, TokenKind::Plus);
+    }
+    
+    #[test]
+    fn test_leading_bom_is_ignored() {
+        let tokens = Lexer::new("\u{FEFF}let x = 1;").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Keyword(Keyword::Let));
+        assert_eq!(tokens[0].span.start, 3);
+        assert_eq!(tokens[0].span.column, 1);
+    }
+    
+    #[test]
+    fn test_leading_hashbang_is_ignored() {
+        let tokens = Lexer::new("#!/usr/bin/env v8\nlet x = 1;").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Keyword(Keyword::Let));
+        assert_eq!(tokens[0].span.line, 2);
+        
+        // After a BOM, and as the whole source
+        let tokens = Lexer::new("\u{FEFF}#!v8\nx").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("x".to_string()));
+        assert_eq!(Lexer::new("#!v8").tokenize().unwrap()[0].kind, TokenKind::Eof);
+        
+        // Anywhere else, `#` is still an error
+        assert!(Lexer::new(" #!v8\nx").tokenize().is_err());
+        assert!(Lexer::new("x\n#!v8").tokenize().is_err());
+        assert!(Lexer::new("let a = #b;").tokenize().is_err());
+    }
+    
+    #[test]
+    fn test_trivia_is_recorded_when_tracking_is_on() {
+        use crate::lexer::TriviaKind::*;
+        
+        let source = "\u{FEFF}#!/usr/bin/env v8\n// note\nlet /* a */  x;";
+        let mut lexer = Lexer::new(source).with_trivia(true);
+        lexer.tokenize().unwrap();
+        let trivia: Vec<_> = lexer.trivia().iter()
+            .map(|trivia| (trivia.kind, &source[trivia.span.start..trivia.span.end], trivia.span.line))
+            .collect();
+        assert_eq!(trivia, [
+            (Hashbang, "#!/usr/bin/env v8", 1),
+            (Whitespace, "\n", 1),
+            (LineComment, "// note", 2),
+            (Whitespace, "\n", 2),
+            (Whitespace, " ", 3),
+            (BlockComment, "/* a */", 3),
+            (Whitespace, "  ", 3),
+        ]);
+        
+        // Off by default
+        let mut lexer = Lexer::new(source);
+        lexer.tokenize().unwrap();
+        assert!(lexer.trivia().is_empty());
+    }
+    
+    #[test]
+    fn test_feature_flags_gate_syntax() {
+        use crate::lexer::FeatureFlags;
+        
+        let lex = |source: &str, features: FeatureFlags| Lexer::new(source).with_features(features).tokenize();
+        let no_separators = FeatureFlags { numeric_separators: false, ..FeatureFlags::ALL };
+        let no_hashbang = FeatureFlags { hashbang: false, ..FeatureFlags::ALL };
+        
+        // Numeric separators: accepted when on, named in the error when off
+        assert_eq!(lex("1_000", FeatureFlags::ALL).unwrap()[0].kind, TokenKind::Number(1000.0));
+        let error = lex("1_000", no_separators).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::DisabledSyntax);
+        assert!(error.to_string().contains("Invalid number '1_000': numeric separators are disabled: feature 'numeric_separators'"),
+                "{}", error);
+        assert_eq!(lex("1000", no_separators).unwrap()[0].kind, TokenKind::Number(1000.0));
+        assert!(lex("1_000", no_hashbang).is_ok());
+        
+        // Hashbang: skipped when on, an error over the `#!` when off
+        assert_eq!(lex("#!v8\nx", FeatureFlags::ALL).unwrap()[0].kind, TokenKind::Identifier("x".to_string()));
+        for source in ["#!v8\nx", "\u{FEFF}#!v8\nx"] {
+            let error = lex(source, no_hashbang).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::DisabledSyntax);
+            assert!(error.to_string().contains("Hashbang comments are disabled: feature 'hashbang'"), "{}", error);
+            let span = error.span().unwrap();
+            assert_eq!(&source[span.start..span.end], "#!");
+        }
+        assert!(lex("#!v8\nx", no_separators).is_ok());
+        
+        // Switching back on restores the default behaviour
+        assert!(lex("#!v8\n1_000", FeatureFlags::NONE).is_err());
+        assert!(lex("#!v8\n1_000", FeatureFlags::default()).is_ok());
+    }
+    
+    #[test]
+    fn test_bidi_controls_outside_strings() {
+        use crate::lexer::BidiPolicy;
+        use crate::error::Error;
+        
+        // Rejected by default, in code and in comments alike
+        for source in ["let x\u{202E} = 1;", "// admin \u{2066}check\nx", "/* \u{202A} */ x"] {
+            let error = Lexer::new(source).tokenize().unwrap_err();
+            assert!(matches!(&error, Error::Lexer { message, .. } if message.contains("Bidirectional control")),
+                    "{:?}: {}", source, error);
+        }
+        
+        // Warned about and skipped when configured
+        let mut lexer = Lexer::new("let x\u{202E} = 1; // \u{2069}");
+        lexer.set_bidi_policy(BidiPolicy::Warn);
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(lexer.diagnostics().len(), 2);
+        assert_eq!(lexer.diagnostics()[0].span.unwrap().start, 5);
+        
+        // String literals may contain them
+        let mut lexer = Lexer::new("let s = \"\u{202E}abc\";");
+        assert!(lexer.tokenize().is_ok());
+        assert!(lexer.diagnostics().is_empty());
+    }
+    
+    #[test]
+    fn test_zero_width_joiner_in_identifier_warns() {
+        let mut lexer = Lexer::new("let a\u{200D}b = 1; let ab = 2;");
+        let tokens = lexer.tokenize().unwrap();
+        
+        assert_eq!(tokens[1].kind, TokenKind::Identifier("a\u{200D}b".to_string()));
+        assert_eq!(lexer.diagnostics().len(), 1);
+        assert!(lexer.diagnostics()[0].message.contains("zero-width joiner"));
+    }
+    
+    #[test]
+    fn test_identifiers_are_nfc_normalized() {
+        let composed = Lexer::new("café").tokenize().unwrap();
+        let decomposed = Lexer::new("cafe\u{0301}").tokenize().unwrap();
+        
+        assert_eq!(composed[0].kind, decomposed[0].kind);
+        // The token text keeps the source spelling
+        assert_eq!(decomposed[0].text_in("cafe\u{0301}"), "cafe\u{0301}");
+        
+        // Non-ASCII identifiers may start with a letter outside ASCII
+        let tokens = Lexer::new("été").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("été".to_string()));
+    }
+    
+    #[test]
+    fn test_newline_before_tokens() {
+        let source = "a b\nc /* x\n */ d // e\nf\r\ng";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let flags: Vec<(&str, bool)> = tokens.iter()
+            .map(|token| (token.text_in(source), token.newline_before))
+            .collect();
+        assert_eq!(flags, [("a", false), ("b", false), ("c", true), ("d", true), ("f", true), ("g", true), ("", false)]);
+    }
+    
+    #[test]
+    fn test_block_comments_with_stars_and_lines() {
+        let source = "/** doc * with ** stars **/ a /*\n*\n**/ b";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let names: Vec<&str> = tokens.iter().map(|token| token.text_in(source)).collect();
+        assert_eq!(names, vec!["a", "b", ""]);
+        // Lines inside the comment count once each
+        assert_eq!((tokens[1].span.line, tokens[1].span.column), (3, 5));
+    }
+    
+    #[test]
+    fn test_positions_after_whitespace_and_comments() {
+        let tokens = Lexer::new("a  b\n  c // x\n/* y */ d\r\ne").tokenize().unwrap();
+        let positions: Vec<(u32, u32)> = tokens.iter()
+            .take(5)
+            .map(|token| (token.span.line, token.span.column))
+            .collect();
+        assert_eq!(positions, vec![(1, 1), (1, 4), (2, 3), (3, 9), (4, 1)]);
+    }
+    
+    #[test]
+    fn test_unterminated_block_comment_points_at_its_start() {
+        let error = Lexer::new("let a = 1;\n  /* open\n\n never closed").tokenize().unwrap_err();
+        let span = error.span().unwrap();
+        assert_eq!(error.code(), ErrorCode::UnterminatedComment);
+        assert_eq!((span.line, span.column, span.start), (2, 3, 13));
+    }
+    
+    #[test]
+    fn test_comment_openers_inside_comments_and_strings() {
+        // `/*` in a line comment ends with the line
+        let tokens = Lexer::new("// /*\nx").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Identifier("x".to_string()));
+        
+        // ...and in a string it is text
+        let tokens = Lexer::new("'/*' + x // */").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::String("/*".to_string()));
+        assert_eq!(tokens[2].kind, TokenKind::Identifier("x".to_string()));
+    }
+    
+    /// Scans a `|>` pipeline operator, but not where a regex may start
+    struct PipelineScanner;
+    
+    impl crate::lexer::Scanner for PipelineScanner {
+        fn scan(&self, rest: &str, context: &crate::lexer::LexerContext) -> Option<(TokenKind, usize)> {
+            if context.expect_regex_allowed || !rest.starts_with("|>") {
+                return None;
+            }
+            Some((TokenKind::Extension("|>".to_string()), 2))
+        }
+    }
+    
+    #[test]
+    fn test_extension_scanner_runs_before_builtin_scanning() {
+        let source = "a |> f || b\n|>c";
+        let tokens = Lexer::new(source)
+            .with_scanner(Box::new(PipelineScanner))
+            .tokenize()
+            .unwrap();
+        let kinds: Vec<TokenKind> = tokens.iter().map(|token| token.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier("a".to_string()),
+                TokenKind::Extension("|>".to_string()),
+                TokenKind::Identifier("f".to_string()),
+                TokenKind::PipePipe,
+                TokenKind::Identifier("b".to_string()),
+                TokenKind::Extension("|>".to_string()),
+                TokenKind::Identifier("c".to_string()),
+                TokenKind::Eof,
+            ]
+        );
+        assert_eq!(tokens[1].text_in(source), "|>");
+        assert_eq!((tokens[5].span.line, tokens[5].span.column), (2, 1));
+        assert!(tokens[5].newline_before);
+        assert_eq!((tokens[6].span.line, tokens[6].span.column), (2, 3));
+        
+        // Without the scanner the same source is `|` then `>`
+        let tokens = Lexer::new("a |> f").tokenize().unwrap();
+        assert_eq!(tokens[1].kind, TokenKind::Pipe);
+        assert_eq!(tokens[2].kind, TokenKind::Greater);
+    }
+    
+    #[test]
+    fn test_extension_scanner_sees_lexer_context() {
+        use crate::lexer::{LexerContext, TokenSource};
+        
+        let mut lexer = Lexer::new("|> |>").with_scanner(Box::new(PipelineScanner));
+        let token = TokenSource::next_token(&mut lexer, &LexerContext::regex_allowed()).unwrap();
+        assert_eq!(token.kind, TokenKind::Pipe);
+        let token = TokenSource::next_token(&mut lexer, &LexerContext::regex_allowed()).unwrap();
+        assert_eq!(token.kind, TokenKind::Greater);
+        let token = TokenSource::next_token(&mut lexer, &LexerContext::default()).unwrap();
+        assert_eq!(token.kind, TokenKind::Extension("|>".to_string()));
+    }
+    
+    #[test]
+    fn test_number_literal_forms() {
+        let cases = [
+            ("1.e3", 1000.0),
+            ("1.", 1.0),
+            (".5", 0.5),
+            ("2.5E-1", 0.25),
+            ("0x1F", 31.0),
+            ("0o17", 15.0),
+            ("0B101", 5.0),
+            ("1_000_000", 1_000_000.0),
+            ("0xFF_FF", 65535.0),
+            ("1e1_0", 1e10),
+        ];
+        for (source, expected) in cases {
+            let tokens = Lexer::new(source).tokenize().unwrap_or_else(|error| panic!("{}: {}", source, error));
+            assert_eq!(tokens[0].kind, TokenKind::Number(expected), "{}", source);
+            assert_eq!(tokens.len(), 2, "{}", source);
+        }
+        
+        // Separated by a space, a number and an identifier are two tokens
+        let tokens = Lexer::new("123 abc").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Number(123.0));
+        assert_eq!(tokens[1].kind, TokenKind::Identifier("abc".to_string()));
+        // ...and so are a literal ending in `.` and a member access
+        let kinds: Vec<TokenKind> = Lexer::new("1..a 1.5.b").tokenize().unwrap()
+            .into_iter()
+            .map(|token| token.kind)
+            .collect();
+        assert_eq!(kinds[..3], [TokenKind::Number(1.0), TokenKind::Dot, TokenKind::Identifier("a".to_string())]);
+        assert_eq!(kinds[3..6], [TokenKind::Number(1.5), TokenKind::Dot, TokenKind::Identifier("b".to_string())]);
+    }
+    
+    #[test]
+    fn test_malformed_number_literals() {
+        let cases = [
+            ("123abc", "123abc", "identifier starts immediately after numeric literal"),
+            ("1.toString()", "1.toString", "identifier starts immediately after numeric literal"),
+            ("3in x", "3in", "identifier starts immediately after numeric literal"),
+            ("0x", "0x", "missing digits after '0x'"),
+            ("0b;", "0b", "missing digits after '0b'"),
+            ("0xg1", "0xg1", "missing digits after '0x'"),
+            ("0b102", "0b102", "'2' is not a valid digit here"),
+            ("1e", "1e", "missing digits in exponent"),
+            ("1e+", "1e+", "missing digits in exponent"),
+            ("1.5Ex", "1.5Ex", "missing digits in exponent"),
+            ("1.2.3", "1.2.3", "unexpected '.' after numeric literal"),
+            ("1__2", "1__2", "numeric separators are only allowed between digits"),
+            ("1_", "1_", "numeric separators are only allowed between digits"),
+            ("0x_1", "0x_1", "numeric separators are only allowed between digits"),
+        ];
+        for (source, token, problem) in cases {
+            let error = Lexer::new(source).tokenize().unwrap_err();
+            let message = format!("Invalid number '{}': {}", token, problem);
+            assert_eq!(error.code(), ErrorCode::InvalidNumber, "{}", source);
+            assert!(error.to_string().contains(&message), "{}: {}", source, error);
+            // The span covers the whole malformed token
+            let span = error.span().unwrap();
+            assert_eq!((span.start, span.end), (0, token.len()), "{}", source);
+        }
+    }
+    
+    /// Token kinds of `source`, without the final `Eof`
+    fn kinds(source: &str) -> Vec<TokenKind> {
+        let mut tokens: Vec<TokenKind> = Lexer::new(source).tokenize().unwrap().into_iter().map(|token| token.kind).collect();
+        tokens.pop();
+        tokens
+    }
+    
+    #[test]
+    fn test_template_literals_with_nested_substitutions() {
+        use TokenKind::*;
+        
+        assert_eq!(kinds("`plain \\` text\n`"), vec![NoSubstitutionTemplate("plain ` text\n".to_string())]);
+        
+        // The `}` of the object literal does not end the outer substitution
+        assert_eq!(kinds("`a${ {b: `${c}`} }d`"), vec![
+            TemplateHead("a".to_string()),
+            LeftBrace,
+            Identifier("b".to_string()),
+            Colon,
+            TemplateHead("".to_string()),
+            Identifier("c".to_string()),
+            TemplateTail("".to_string()),
+            RightBrace,
+            TemplateTail("d".to_string()),
+        ]);
+        
+        // Two levels deep, with blocks inside a substitution
+        let tokens = kinds("`x${ `y${ (() => { return {z: 1}; })() }-${2}` }!` / 2");
+        assert_eq!(tokens[..2], [TemplateHead("x".to_string()), TemplateHead("y".to_string())]);
+        let texts: Vec<&TokenKind> = tokens.iter()
+            .filter(|kind| matches!(kind, TemplateMiddle(_) | TemplateTail(_)))
+            .collect();
+        assert_eq!(texts, [&TemplateMiddle("-".to_string()), &TemplateTail("".to_string()), &TemplateTail("!".to_string())]);
+        // A template ends an expression, so `/` after it divides
+        assert_eq!(tokens[tokens.len() - 2..], [Slash, Number(2.0)]);
+    }
+    
+    #[test]
+    fn test_template_modes_track_nesting() {
+        use crate::lexer::{LexerContext, TemplateMode, TokenSource};
+        
+        let mut lexer = Lexer::new("`a${ {b: `${c}`} }d`");
+        let mut depths = Vec::new();
+        let mut in_substitution = Vec::new();
+        loop {
+            let token = TokenSource::next_token(&mut lexer, &LexerContext::default()).unwrap();
+            if token.kind == TokenKind::Eof {
+                break;
+            }
+            depths.push(lexer.template_modes().depth());
+            in_substitution.push(lexer.context().in_template_substitution);
+            if token.kind == TokenKind::LeftBrace {
+                assert_eq!(lexer.template_modes().modes()[1], TemplateMode::Substitution { braces: 1 });
+            }
+        }
+        assert_eq!(depths, [1, 1, 1, 1, 2, 2, 1, 1, 0]);
+        // Whether each token was scanned inside a substitution
+        assert_eq!(in_substitution, [false, true, true, true, true, true, true, true, true]);
+    }
+    
+    #[test]
+    fn test_unterminated_template_points_at_its_backtick() {
+        let error = Lexer::new("`done`\n  + `a${ `open\nmore").tokenize().unwrap_err();
+        let span = error.span().unwrap();
+        assert_eq!(error.code(), ErrorCode::UnterminatedTemplate);
+        assert_eq!((span.line, span.column, span.start, span.end), (2, 10, 16, 17));
+        
+        // Even when the text runs on after a substitution
+        let error = Lexer::new("x = `a${b} and\nmore").tokenize().unwrap_err();
+        let span = error.span().unwrap();
+        assert_eq!((span.line, span.column, span.start), (1, 5, 4));
+    }
+    
+    #[test]
+    fn test_rescanning_restores_template_modes() {
+        use crate::lexer::{LexerContext, TokenSource};
+        
+        // Read as a division, the `}` after the `/` ends the substitution;
+        // read as a regex, it is the regex's
+        let mut lexer = Lexer::new("`${ /}/ }`");
+        let context = LexerContext::default();
+        assert_eq!(TokenSource::next_token(&mut lexer, &context).unwrap().kind, TokenKind::TemplateHead("".to_string()));
+        let slash = TokenSource::next_token(&mut lexer, &context).unwrap();
+        assert_eq!(slash.kind, TokenKind::Slash);
+        assert_eq!(TokenSource::next_token(&mut lexer, &context).unwrap().kind, TokenKind::TemplateTail("/ }".to_string()));
+        assert_eq!(lexer.template_modes().depth(), 0);
+        
+        let regex = lexer.rescan(&slash, &LexerContext::regex_allowed()).unwrap().unwrap();
+        assert_eq!(regex.kind, TokenKind::RegExp { pattern: "}".to_string(), flags: "".to_string() });
+        assert_eq!(lexer.template_modes().depth(), 1);
+        assert_eq!(TokenSource::next_token(&mut lexer, &context).unwrap().kind, TokenKind::TemplateTail("".to_string()));
+    }
+}
@@ -4,19 +4,66 @@ use crate::error::Span;
 use std::fmt;
 
 /// A JavaScript token with position information
+///
+/// The token doesn't copy its source text; `text_in` slices it from the
+/// source on demand. Where the value differs from that spelling, like a
+/// string literal with escapes, the kind carries the cooked value.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
-    pub text: String,
+    /// Contextual keyword classification for identifier tokens
+    /// (e.g. `of`, `async`), which stay ordinary identifiers elsewhere
+    pub contextual_keyword: Option<ContextualKeyword>,
+    /// Whether a line break separates this token from the previous one,
+    /// which is what automatic semicolon insertion looks at
+    pub newline_before: bool,
 }
 
 impl Token {
-    pub fn new(kind: TokenKind, span: Span, text: String) -> Self {
-        Self { kind, span, text }
+    pub fn new(kind: TokenKind, span: Span) -> Self {
+        let contextual_keyword = match &kind {
+            TokenKind::Identifier(name) => ContextualKeyword::from_str(name),
+            _ => None,
+        };
+        Self { kind, span, contextual_keyword, newline_before: false }
+    }
+    
+    /// The token as spelled in `source`, the text it was scanned from
+    ///
+    /// Empty for the end-of-input token, and for a span that isn't in
+    /// `source`.
+    pub fn text_in<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.span.start..self.span.end).unwrap_or_default()
+    }
+    
+    /// Check if this token is the given contextual keyword
+    pub fn is_contextual(&self, keyword: ContextualKeyword) -> bool {
+        self.contextual_keyword == Some(keyword)
     }
 }
 
+/// Source text between tokens, recorded when the lexer tracks trivia
+/// (see `Lexer::with_trivia`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+/// What a piece of trivia is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    /// A run of whitespace and line terminators
+    Whitespace,
+    /// `// ...`, without the line terminator ending it
+    LineComment,
+    /// `/* ... */`
+    BlockComment,
+    /// The `#!` line at the start of a script
+    Hashbang,
+}
+
 /// JavaScript token types
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
@@ -26,6 +73,17 @@ pub enum TokenKind {
     Boolean(bool),
     Null,
     Undefined,
+    /// Regular expression literal `/pattern/flags`
+    RegExp { pattern: String, flags: String },
+    /// Template literal without substitutions, `` `text` ``; template
+    /// text is cooked, with escapes resolved
+    NoSubstitutionTemplate(String),
+    /// Start of a template literal up to its first substitution, `` `text${ ``
+    TemplateHead(String),
+    /// Template text between two substitutions, `}text${`
+    TemplateMiddle(String),
+    /// End of a template literal after its last substitution, `` }text` ``
+    TemplateTail(String),
     
     // Identifiers and keywords
     Identifier(String),
@@ -85,13 +143,16 @@ pub enum TokenKind {
     Semicolon,      // ;
     Comma,          // ,
     Dot,            // .
+    Ellipsis,       // ...
     Colon,          // :
     Question,       // ?
     Arrow,          // =>
     
     // Special tokens
     Eof,
-    Newline,
+    /// Token produced by an extension `Scanner`, identified by a name of
+    /// the scanner's choosing; the built-in parser rejects these
+    Extension(String),
     
     // Error token
     Error(String),
@@ -159,10 +220,12 @@ pub enum Keyword {
     Delete,
     Void,
     Yield,
+    Debugger,
 }
 
 impl Keyword {
     /// Parse a keyword from a string
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Option<Self> {
         match s {
             "function" => Some(Keyword::Function),
@@ -208,6 +271,7 @@ impl Keyword {
             "delete" => Some(Keyword::Delete),
             "void" => Some(Keyword::Void),
             "yield" => Some(Keyword::Yield),
+            "debugger" => Some(Keyword::Debugger),
             _ => None,
         }
     }
@@ -259,6 +323,53 @@ impl Keyword {
             Keyword::Delete => "delete",
             Keyword::Void => "void",
             Keyword::Yield => "yield",
+            Keyword::Debugger => "debugger",
+        }
+    }
+}
+
+/// Words that only act as keywords in specific syntactic positions
+///
+/// The lexer emits these as `TokenKind::Identifier` so that `let of = 1`
+/// stays valid; the parser checks `Token::contextual_keyword` where the
+/// word has special meaning (e.g. `of` in for-of).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ContextualKeyword {
+    Async,
+    Await,
+    Of,
+    From,
+    As,
+    Get,
+    Set,
+}
+
+impl ContextualKeyword {
+    /// Parse a contextual keyword from a string
+    #[allow(clippy::should_implement_trait)]
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "async" => Some(ContextualKeyword::Async),
+            "await" => Some(ContextualKeyword::Await),
+            "of" => Some(ContextualKeyword::Of),
+            "from" => Some(ContextualKeyword::From),
+            "as" => Some(ContextualKeyword::As),
+            "get" => Some(ContextualKeyword::Get),
+            "set" => Some(ContextualKeyword::Set),
+            _ => None,
+        }
+    }
+    
+    /// Get the string representation of a contextual keyword
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContextualKeyword::Async => "async",
+            ContextualKeyword::Await => "await",
+            ContextualKeyword::Of => "of",
+            ContextualKeyword::From => "from",
+            ContextualKeyword::As => "as",
+            ContextualKeyword::Get => "get",
+            ContextualKeyword::Set => "set",
         }
     }
 }
@@ -271,6 +382,11 @@ impl fmt::Display for TokenKind {
             TokenKind::Boolean(b) => write!(f, "{}", b),
             TokenKind::Null => write!(f, "null"),
             TokenKind::Undefined => write!(f, "undefined"),
+            TokenKind::RegExp { pattern, flags } => write!(f, "/{}/{}", pattern, flags),
+            TokenKind::NoSubstitutionTemplate(text) => write!(f, "`{}`", text),
+            TokenKind::TemplateHead(text) => write!(f, "`{}${{", text),
+            TokenKind::TemplateMiddle(text) => write!(f, "}}{}${{", text),
+            TokenKind::TemplateTail(text) => write!(f, "}}{}`", text),
             TokenKind::Identifier(name) => write!(f, "{}", name),
             TokenKind::Keyword(kw) => write!(f, "{}", kw.as_str()),
             TokenKind::Plus => write!(f, "+"),
@@ -314,11 +430,12 @@ impl fmt::Display for TokenKind {
             TokenKind::Semicolon => write!(f, ";"),
             TokenKind::Comma => write!(f, ","),
             TokenKind::Dot => write!(f, "."),
+            TokenKind::Ellipsis => write!(f, "..."),
             TokenKind::Colon => write!(f, ":"),
             TokenKind::Question => write!(f, "?"),
             TokenKind::Arrow => write!(f, "=>"),
             TokenKind::Eof => write!(f, "EOF"),
-            TokenKind::Newline => write!(f, "\\n"),
+            TokenKind::Extension(name) => write!(f, "{}", name),
             TokenKind::Error(msg) => write!(f, "ERROR({})", msg),
         }
     }
@@ -0,0 +1,109 @@
+//! Unicode hygiene for source text
+//!
+//! Decides which characters make up identifiers, classifies the invisible
+//! characters that make source text read differently from how it lexes,
+//! and normalizes identifiers so that canonically equivalent spellings
+//! name the same binding.
+//!
+//! The lexer and everything that prints names back out (the value
+//! inspector, the constant pool's display) classify identifiers here, so
+//! a key printed without quotes always lexes as one identifier.
+
+use unicode_normalization::{is_nfc, UnicodeNormalization};
+use unicode_xid::UnicodeXID;
+
+/// How the lexer treats bidirectional control characters outside strings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BidiPolicy {
+    /// Fail with a lexer error
+    #[default]
+    Reject,
+    /// Record a warning and skip the character
+    Warn,
+}
+
+/// Unicode character classification and identifier normalization
+pub struct UnicodeHelper;
+
+impl UnicodeHelper {
+    /// The byte order mark, ignorable at the start of a source
+    pub const BOM: char = '\u{FEFF}';
+    
+    /// Bidirectional embedding, override and isolate controls
+    /// (U+202A–U+202E, U+2066–U+2069)
+    pub fn is_bidi_control(c: char) -> bool {
+        matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+    }
+    
+    /// Zero-width non-joiner and joiner, which are legal identifier parts
+    pub fn is_zero_width_joiner(c: char) -> bool {
+        matches!(c, '\u{200C}' | '\u{200D}')
+    }
+    
+    /// Whether `c` can start an identifier: `$`, `_` or a character with
+    /// the XID_Start property
+    ///
+    /// Letters in the wider sense of `char::is_alphabetic` are not enough:
+    /// combining marks and circled letters are alphabetic but can't start
+    /// an identifier, while `℘` can though it isn't alphabetic.
+    pub fn is_identifier_start(c: char) -> bool {
+        c == '$' || c == '_' || c.is_xid_start()
+    }
+    
+    /// Whether `c` can continue an identifier: `$`, a zero-width joiner
+    /// or non-joiner, or a character with the XID_Continue property
+    ///
+    /// Digits other than decimal ones, like `²`, are not identifier parts.
+    pub fn is_identifier_part(c: char) -> bool {
+        c == '$' || c.is_xid_continue() || Self::is_zero_width_joiner(c)
+    }
+    
+    /// Whether `name` lexes as exactly one identifier (or keyword) written
+    /// without escapes
+    pub fn is_identifier_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars.next().is_some_and(Self::is_identifier_start) && chars.all(Self::is_identifier_part)
+    }
+    
+    /// Normalize an identifier to NFC
+    pub fn normalize_identifier(name: &str) -> String {
+        if is_nfc(name) {
+            return name.to_string();
+        }
+        name.nfc().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_normalize_identifier() {
+        assert_eq!(UnicodeHelper::normalize_identifier("cafe\u{0301}"), "café");
+        assert_eq!(UnicodeHelper::normalize_identifier("café"), "café");
+        assert_eq!(UnicodeHelper::normalize_identifier("n\u{0303}o\u{0308}"), "ñö");
+        // Marks without a composition stay as written
+        assert_eq!(UnicodeHelper::normalize_identifier("x\u{0301}"), "x\u{0301}");
+        // Beyond Latin: Greek with a combining acute, and Hangul jamo
+        assert_eq!(UnicodeHelper::normalize_identifier("\u{03B1}\u{0301}"), "\u{03AC}");
+        assert_eq!(UnicodeHelper::normalize_identifier("\u{1100}\u{1161}"), "\u{AC00}");
+    }
+    
+    #[test]
+    fn test_classification() {
+        assert!(UnicodeHelper::is_bidi_control('\u{202E}'));
+        assert!(UnicodeHelper::is_bidi_control('\u{2066}'));
+        assert!(!UnicodeHelper::is_bidi_control('\u{200D}'));
+        assert!(UnicodeHelper::is_zero_width_joiner('\u{200D}'));
+    }
+    
+    #[test]
+    fn test_identifier_characters() {
+        assert!(UnicodeHelper::is_identifier_name("$_café\u{200C}1"));
+        assert!(UnicodeHelper::is_identifier_name("\u{2118}"));
+        for name in ["", "1a", "a-b", "a\u{00B2}", "\u{24B6}", "\u{0345}a", "\u{200D}a"] {
+            assert!(!UnicodeHelper::is_identifier_name(name), "{:?}", name);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Escaping for printing strings and names as source text
+//!
+//! Debug output and anything generated from an AST go through here, so
+//! quotes, backslashes, line breaks and invisible characters can't garble
+//! what is printed.
+
+use std::fmt::Write;
+
+use super::unicode::UnicodeHelper;
+
+/// Escapes string values and identifier names for printing
+pub struct EscapeValidator;
+
+impl EscapeValidator {
+    /// The contents of a double-quoted string literal whose value is `s`
+    pub fn escape_string(s: &str) -> String {
+        Self::escape_quoted(s, '"')
+    }
+    
+    /// The contents of a string literal delimited by `quote` whose value is `s`
+    pub fn escape_quoted(s: &str, quote: char) -> String {
+        let mut escaped = String::with_capacity(s.len());
+        for c in s.chars() {
+            match c {
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                '\t' => escaped.push_str("\\t"),
+                '\\' => escaped.push_str("\\\\"),
+                c if c == quote => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                c if Self::is_invisible(c) => push_unicode_escape(&mut escaped, c),
+                c => escaped.push(c),
+            }
+        }
+        escaped
+    }
+    
+    /// `name` as identifier source, with characters that would print
+    /// invisibly written as `\u` escapes
+    pub fn escape_identifier(name: &str) -> String {
+        let mut escaped = String::with_capacity(name.len());
+        for c in name.chars() {
+            if Self::is_invisible(c) || UnicodeHelper::is_zero_width_joiner(c) || c.is_whitespace() {
+                push_unicode_escape(&mut escaped, c);
+            } else {
+                escaped.push(c);
+            }
+        }
+        escaped
+    }
+    
+    /// Control characters, line and paragraph separators, bidi controls
+    /// and the byte order mark
+    fn is_invisible(c: char) -> bool {
+        c.is_control()
+            || matches!(c, '\u{2028}' | '\u{2029}' | UnicodeHelper::BOM)
+            || UnicodeHelper::is_bidi_control(c)
+    }
+}
+
+/// Append `\uXXXX`, or `\u{X}` beyond the Basic Multilingual Plane
+fn push_unicode_escape(out: &mut String, c: char) {
+    let code = c as u32;
+    let _ = if code > 0xFFFF {
+        write!(out, "\\u{{{:x}}}", code)
+    } else {
+        write!(out, "\\u{:04x}", code)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_escape_string() {
+        assert_eq!(EscapeValidator::escape_string("plain"), "plain");
+        assert_eq!(EscapeValidator::escape_string("say \"hi\"\\\n"), "say \\\"hi\\\"\\\\\\n");
+        assert_eq!(EscapeValidator::escape_string("a\u{0}b\u{202E}c\u{2028}"), "a\\u0000b\\u202ec\\u2028");
+        // Printable non-ASCII stays as written
+        assert_eq!(EscapeValidator::escape_string("café ✓"), "café ✓");
+    }
+    
+    #[test]
+    fn test_escape_identifier() {
+        assert_eq!(EscapeValidator::escape_identifier("$valid_name1"), "$valid_name1");
+        assert_eq!(EscapeValidator::escape_identifier("a\u{200D}b"), "a\\u200db");
+        assert_eq!(EscapeValidator::escape_identifier("ünïcode"), "ünïcode");
+    }
+}
@@ -11,116 +11,1139 @@ pub mod lexer;
 pub mod parser;
 pub mod bytecode;
 pub mod vm;
+pub mod repl;
+pub mod stdio;
+mod cache;
+mod capabilities;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
 // Re-exports for convenience
-pub use error::{Error, Result};
-pub use lexer::{Lexer, Token, TokenKind};
+pub use error::{Diagnostic, Error, ErrorCode, Note, Result, RuntimeErrorKind};
+pub use lexer::{FeatureFlags, Lexer, LexerContext, Token, TokenKind, TokenSource};
 pub use parser::{Parser, ast};
-pub use bytecode::{BytecodeFunction, Disassembler, ConstantPool, Bytecode, Compiler};
-pub use vm::{VM, Value};
+pub use bytecode::{BytecodeFunction, Disassembler, ConstantPool, Bytecode, Compiler, VerifiedFunction};
+pub use vm::{VM, VMConfig, VmMetrics, PropertyCacheStats, Value, CallContext, Local, Persistent, PersistentRoots, GlobalSlot, ProfileMode, ProfileReport, Breakpoint, BreakpointId, DebugContext, DebugHook, NanOrigin, PauseReason, StepMode, CaseMapper, Snapshot, Detached, DetachedObject, DetachedValue};
+pub use cache::CacheStats;
+pub use capabilities::{Capabilities, Feature, Limits};
+pub use stdio::{CapturedOutput, EngineIo, SharedIo};
+use cache::{CompilationCache, CompiledScript};
 use ast::PrettyPrint;
+use vm::{ConsoleOutput, FunctionRef, HostFunction};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 /// Engine version
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
 /// Engine entry point
+///
+/// An engine owns its VMs and is confined to one thread. To run one
+/// program on many threads, compile it once with `compile_cached` and
+/// execute the shared function on an engine (or `VM`) per thread.
 pub struct Engine {
-    /// Enable detailed AST debugging output
+    /// Print the AST of each program before compiling it
     pub ast_debug_mode: bool,
-    /// Enable bytecode debugging output
+    /// Print the bytecode of each program before running it; tracing the
+    /// run is `VMConfig::trace_execution`
     pub bytecode_debug_mode: bool,
+    /// Let scripts overwrite engine-installed built-ins like `console`
+    pub writable_builtins: bool,
+    /// Host-defined globals installed before each execution
+    host_globals: Vec<(String, GlobalSlot)>,
+    /// Keep one VM across executions (see `new_persistent`)
+    persistent: bool,
+    /// The shared VM, once a persistent engine has run something
+    vm: Option<VM>,
+    /// Result history, when persistent
+    history: ResultHistory,
+    /// Roots of the engine's `Persistent`s, shared by its VMs
+    persistents: PersistentRoots,
+    /// Names REPL input has declared at the top level, and whether each
+    /// is `const` (see `eval_repl_input`)
+    top_level: HashMap<String, bool>,
+    /// Receives errors thrown by scheduled callbacks
+    uncaught_handler: Option<Box<dyn FnMut(Error)>>,
+    /// Receives the reasons of promises rejected with no handler
+    rejection_handler: Option<Box<dyn FnMut(Value)>>,
+    /// Receives `console` output in place of the streams
+    console_output: Option<ConsoleOutput>,
+    /// Where the engine and its VMs print and read
+    io: SharedIo,
+    /// Seed for `Math.random`; `None` seeds each VM from OS entropy
+    random_seed: Option<u64>,
+    /// Profile each VM by source line (see `profile_report`)
+    profile_mode: Option<ProfileMode>,
+    /// Limits of each VM the engine creates
+    vm_config: VMConfig,
+    /// How deeply statements and expressions may nest, for both the
+    /// parser and the compiler
+    max_nesting_depth: usize,
+    /// Optional syntax the lexer accepts
+    lexer_features: FeatureFlags,
+    /// Compiled scripts by source; disabled unless the builder sized it
+    cache: CompilationCache,
+    /// Collect `ExecutionMetrics` for each execution
+    metrics_enabled: bool,
+    /// Metrics of the most recent execution, when enabled
+    last_metrics: Option<ExecutionMetrics>,
+    /// Globals each VM starts from, instead of running the setup code of
+    /// the built-ins
+    snapshot: Option<Arc<Snapshot>>,
+    /// Run the compiler's optional optimizations (see
+    /// `EngineBuilder::optimize`)
+    optimize: bool,
+    /// Most constants to hoist out of the loops of one function when
+    /// optimizing (see `EngineBuilder::max_hoisted_constants`)
+    max_hoisted_constants: usize,
 }
 
 impl Engine {
-    /// Create a new engine instance
+    /// Create a new engine instance, as `Engine::builder().build()` does
     pub fn new() -> Self {
-        Self {
-            ast_debug_mode: false,
-            bytecode_debug_mode: false,
-        }
+        Self::builder().build()
     }
     
-    /// Create a new engine instance with AST debug mode enabled
+    /// Create an engine that prints the AST of each program
+    /// (`EngineBuilder::ast_debug`)
     pub fn new_with_ast_debug() -> Self {
-        Self {
-            ast_debug_mode: true,
-            bytecode_debug_mode: false,
-        }
+        Self::builder().ast_debug(true).build()
     }
     
-    /// Create a new engine instance with bytecode debug mode enabled
+    /// Create an engine that prints the bytecode of each program
+    /// (`EngineBuilder::bytecode_debug`)
     pub fn new_with_bytecode_debug() -> Self {
-        Self {
-            ast_debug_mode: false,
-            bytecode_debug_mode: true,
-        }
+        Self::builder().bytecode_debug(true).build()
     }
     
-    /// Create a new engine instance with all debug modes enabled
+    /// Create an engine that prints the AST and bytecode of each program
+    /// and traces its execution (`EngineBuilder::ast_debug`,
+    /// `bytecode_debug` and `trace_execution`)
     pub fn new_with_all_debug() -> Self {
-        Self {
-            ast_debug_mode: true,
-            bytecode_debug_mode: true,
+        Self::builder().ast_debug(true).bytecode_debug(true).trace_execution(true).build()
+    }
+    
+    /// Configure an engine before creating it
+    pub fn builder() -> EngineBuilder {
+        EngineBuilder::default()
+    }
+    
+    /// Create an engine whose executions share one VM
+    ///
+    /// Globals created by one `execute` stay visible to the next, and
+    /// each non-undefined result is stored in `_` and in `_1`, `_2`, ...,
+    /// as in Node's REPL. Once a script assigns `_` itself, `_` is left
+    /// alone; the numbered history keeps growing.
+    pub fn new_persistent() -> Self {
+        Self::builder().persistent(true).build()
+    }
+    
+    /// Create an engine whose VMs copy their globals from the snapshot
+    /// embedded in the engine (see `Snapshot::standard`) rather than
+    /// building them
+    ///
+    /// Fails only if the embedded snapshot is stale.
+    pub fn from_snapshot() -> Result<Self> {
+        Ok(Self::builder().snapshot(Snapshot::standard()?).build())
+    }
+    
+    /// Whether executions share one VM (see `new_persistent`)
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+    
+    /// Results stored in `_1`, `_2`, ... so far, oldest first
+    pub fn result_history(&self) -> Vec<Value> {
+        self.history.results.iter().map(Persistent::get).collect()
+    }
+    
+    /// Hold `value` across executions and event loop pumps (see
+    /// `vm::handles`)
+    ///
+    /// The handle is a root in every VM the engine makes, shared or not.
+    pub fn persistent(&self, value: Value) -> Persistent {
+        Persistent::new(&self.persistents, value)
+    }
+    
+    /// Define a global binding visible to scripts
+    ///
+    /// The slot's flags decide whether scripts may overwrite it; use
+    /// `GlobalSlot::read_only` for constants the host relies on.
+    pub fn define_global(&mut self, name: impl Into<String>, slot: GlobalSlot) {
+        match &mut self.vm {
+            Some(vm) if self.persistent => vm.define_global(name, slot),
+            _ => self.host_globals.push((name.into(), slot)),
+        }
+    }
+    
+    /// Remove a global binding, returning it
+    ///
+    /// Built-ins can be removed too. A persistent engine removes it from
+    /// its VM; any other engine can only remove bindings from
+    /// `define_global`.
+    pub fn remove_global(&mut self, name: &str) -> Option<GlobalSlot> {
+        if self.persistent {
+            if self.vm.is_none() {
+                self.vm = Some(self.create_vm());
+            }
+            return self.vm.as_mut().and_then(|vm| vm.remove_global(name));
+        }
+        let index = self.host_globals.iter().rposition(|(defined, _)| defined == name)?;
+        let (_, slot) = self.host_globals.remove(index);
+        self.host_globals.retain(|(defined, _)| defined != name);
+        Some(slot)
+    }
+    
+    /// Bring a value copied out of an engine with
+    /// `Value::deep_clone_detached` into this one
+    ///
+    /// The value may come from any engine, on any thread. Its objects are
+    /// new, so adopting the same copy twice gives two unrelated values.
+    pub fn adopt(&self, detached: &DetachedValue) -> Value {
+        detached.attach()
+    }
+    
+    /// Expose a Rust callback to scripts as a global function
+    ///
+    /// The callback gets the arguments, `this` and the VM through a
+    /// [`CallContext`], as built-in functions do. An error returned by the callback stops the script and comes back
+    /// from `execute`. A panic does too, as
+    /// `RuntimeErrorKind::NativeFunctionPanicked`, but callbacks should
+    /// return errors rather than rely on that: the panic hook still runs,
+    /// and a panic that aborts can't be caught. Scripts may overwrite the
+    /// binding, like any other global.
+    ///
+    /// A value the callback captures is not a root: capture a
+    /// `Persistent` for it instead (see `Engine::persistent`).
+    pub fn register_function(
+        &mut self,
+        name: impl Into<String>,
+        callback: impl Fn(&mut CallContext) -> Result<Value> + 'static,
+    ) {
+        let name = name.into();
+        let function = HostFunction::new(name.clone(), callback);
+        let value = Value::Function(FunctionRef::Host(Rc::new(function)));
+        self.define_global(name, GlobalSlot::new(value));
+    }
+    
+    /// Pump the event loop: run queued microtasks and the timers due at
+    /// `now_ms`, a host-chosen time in milliseconds
+    ///
+    /// Returns whether callbacks are still waiting. Errors thrown by
+    /// callbacks go to the uncaught-exception handler instead of stopping
+    /// the pump, and promises still rejected without a handler once it is
+    /// done go to the unhandled-rejection handler. A persistent engine
+    /// pumps its shared VM; otherwise this pumps the most recent execution
+    /// that left work behind.
+    pub fn run_pending(&mut self, now_ms: u64) -> Result<bool> {
+        let Some(vm) = &mut self.vm else {
+            return Ok(false);
+        };
+        let handler = &mut self.uncaught_handler;
+        let io = &self.io;
+        let mut on_uncaught = |error: Error| match handler {
+            Some(handler) => handler(error),
+            None => {
+                let mut diagnostic = Diagnostic::from(error);
+                diagnostic.message = format!("Uncaught {}", diagnostic.message);
+                let _ = writeln!(io.borrow_mut().stderr, "{}", diagnostic);
+            }
+        };
+        let pending = vm.run_pending(now_ms, &mut on_uncaught);
+        for reason in vm.take_unhandled_rejections() {
+            match &mut self.rejection_handler {
+                Some(handler) => handler(reason),
+                None => {
+                    let _ = writeln!(self.io.borrow_mut().stderr, "Warning: Unhandled promise rejection: {}", reason.inspect());
+                }
+            }
+        }
+        Ok(pending)
+    }
+    
+    /// Line and function hits of the most recent execution, including
+    /// callbacks pumped since; `None` unless built with profiling
+    ///
+    /// A persistent engine reports on everything its VM has run.
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.vm.as_ref().and_then(VM::profile_report)
+    }
+    
+    /// Handle errors thrown by timer and microtask callbacks
+    ///
+    /// Without a handler they are printed to the engine's stderr.
+    pub fn set_uncaught_exception_handler(&mut self, handler: impl FnMut(Error) + 'static) {
+        self.uncaught_handler = Some(Box::new(handler));
+    }
+    
+    /// Handle promises rejected with no handler, reported by `run_pending`
+    /// once per promise with the rejection reason
+    ///
+    /// Without a handler a warning is printed to the engine's stderr.
+    pub fn set_unhandled_rejection_handler(&mut self, handler: impl FnMut(Value) + 'static) {
+        self.rejection_handler = Some(Box::new(handler));
+    }
+    
+    /// Receive each line scripts write through `console` instead of
+    /// printing it to the engine's streams
+    ///
+    /// Applies to the VMs created from now on and to the engine's current
+    /// VM, if it kept one.
+    pub fn set_console_handler(&mut self, handler: impl FnMut(&str) + 'static) {
+        let output: ConsoleOutput = Rc::new(RefCell::new(handler));
+        if let Some(vm) = &mut self.vm {
+            vm.set_console_output(Some(output.clone()));
         }
+        self.console_output = Some(output);
+    }
+    
+    /// Print and read through `io` instead of the process's streams
+    ///
+    /// Everything the engine prints goes there, as does what scripts
+    /// print, and `readLine()` and the REPL read from it (see `stdio`).
+    /// Applies to the VMs created from now on and to the engine's current
+    /// VM, if it kept one.
+    pub fn set_io(&mut self, io: EngineIo) {
+        self.io = io.shared();
+        if let Some(vm) = &mut self.vm {
+            vm.set_io(self.io.clone());
+        }
+    }
+    
+    /// The streams the engine prints to and reads from
+    pub fn io(&self) -> SharedIo {
+        self.io.clone()
     }
     
     /// Execute JavaScript source code
+    ///
+    /// Returns the program's completion value. This is deterministic:
+    /// a program ending in an expression statement completes with that
+    /// expression's value; a program ending in a declaration, an empty
+    /// statement, a block, or control flow (and an empty program) completes
+    /// with `undefined`, regardless of what earlier statements evaluated.
     pub fn execute(&mut self, source: &str) -> Result<Value> {
+        let result = self.execute_detailed(source).result?;
+        
+        // Print the result if it's not undefined (for REPL)
+        if !matches!(result, Value::Undefined) {
+            writeln!(self.io.borrow_mut().stdout, "{}", result)?;
+        }
+        
+        Ok(result)
+    }
+    
+    /// Run a block of statements and return its completion value
+    ///
+    /// Unlike `execute`, any statement can supply the value, as in a
+    /// do-expression: `if (admin) { "Admin" } else { "User" }` evaluates
+    /// to one of the two strings. `Compiler::with_completion_values` has
+    /// the rules. Nothing is printed, and the compilation cache is not
+    /// used because the same source compiles differently here.
+    pub fn eval_block(&mut self, source: &str) -> Result<Value> {
+        let program = Parser::from_source(Lexer::new(source).with_features(self.lexer_features)).with_max_depth(self.max_nesting_depth).parse()?;
+        let (function, _) = self.compiler(source)
+            .with_completion_values()
+            .compile_with_diagnostics(&program)?;
+        self.execute_compiled(&Arc::new(function))
+    }
+    
+    /// Evaluate a single expression and return its value
+    ///
+    /// The source is parsed with `Parser::parse_expression_only`, so it
+    /// must be exactly one expression: `{ a: 1 }` is an object, and a
+    /// trailing `;` or a second statement is a syntax error. Like
+    /// `execute`, it runs in a fresh VM unless the engine is persistent.
+    pub fn eval_expression(&mut self, source: &str) -> Result<Value> {
+        let expr = Parser::from_source(Lexer::new(source).with_features(self.lexer_features))
+            .with_max_depth(self.max_nesting_depth)
+            .parse_expression_only()?;
+        let function = self.compiler(source).compile_expression_main(&expr)?;
+        self.execute_compiled(&Arc::new(function))
+    }
+    
+    /// Run one input of a REPL session
+    ///
+    /// Top-level declarations bind globals (see
+    /// `Compiler::with_top_level_globals`), so later input sees them. As
+    /// in Node, declaring a name again in later input replaces its
+    /// binding, value and `const`-ness alike, while declaring it twice in
+    /// one input is an error. Meant for persistent engines.
+    pub fn eval_repl_input(&mut self, source: &str) -> Result<Value> {
+        let program = Parser::from_source(Lexer::new(source).with_features(self.lexer_features)).with_max_depth(self.max_nesting_depth).parse()?;
+        let function = self.compiler(source)
+            .with_top_level_globals(&self.top_level)
+            .compile(&program)?;
+        for (name, is_const, is_hoisted) in top_level_declarations(&program) {
+            self.remove_global(&name);
+            // `var` and function names are bound before the input runs, as
+            // a script's would be; `let`, `const` and class names only when
+            // their declaration runs, so reading one earlier is the same
+            // ReferenceError it is in a script
+            if self.persistent && is_hoisted {
+                self.define_global(name.clone(), GlobalSlot::new(Value::Undefined));
+            }
+            self.top_level.insert(name, is_const);
+        }
+        self.execute_compiled(&Arc::new(function))
+    }
+    
+    /// Execute a script file
+    ///
+    /// The file may start with a byte order mark and a `#!` line.
+    pub fn execute_file(&mut self, path: impl AsRef<Path>) -> Result<Value> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| Error::from(e).context(format!("Cannot read '{}'", path.display())))?;
+        self.execute(&source)
+    }
+    
+    /// Execute JavaScript source code, reporting every phase
+    ///
+    /// Unlike `execute`, a failure doesn't discard earlier work: the report
+    /// says which phase failed and keeps the artifacts of the phases that
+    /// completed, along with how long each phase took.
+    pub fn execute_detailed(&mut self, source: &str) -> ExecutionReport {
         // Execution pipeline:
         // 1. Parse source to AST ✓
         // 2. Compile AST to bytecode ✓ 
         // 3. Execute bytecode in interpreter ✓
         // 4. Profile and JIT compile hot functions (Phase 5 - TODO)
+        let mut report = ExecutionReport::new();
+        let bytecode_function = match self.compile_source(source, &mut report) {
+            Some(function) => function,
+            None => {
+                self.record_metrics(report.timings, 0, None, VmMetrics::default());
+                return report;
+            }
+        };
+        
+        // Step 4: Execute bytecode in VM
+        report.phase = Phase::Execute;
+        let execute_start = Instant::now();
+        let (result, vm_metrics) = self.run_main(|vm| vm.execute_verified(&bytecode_function));
+        report.result = result;
+        report.timings.execute = execute_start.elapsed();
+        let statements = report.ast.as_ref().map_or(0, |ast| ast.statements.len());
+        self.record_metrics(report.timings, statements, Some(&bytecode_function), vm_metrics);
+        report.bytecode = Some(bytecode_function.function().clone());
+        report
+    }
+    
+    /// Compile `source`, or take it from the compilation cache
+    ///
+    /// Without a cache (see `EngineBuilder::compilation_cache`) this
+    /// compiles every time. The function can be run any number of times
+    /// with `execute_compiled`.
+    pub fn compile_cached(&mut self, source: &str) -> Result<Arc<BytecodeFunction>> {
+        let mut report = ExecutionReport::new();
+        match self.compile_source(source, &mut report) {
+            Some(function) => Ok(function.function().clone()),
+            None => Err(report.result.expect_err("a failed compilation reports its error")),
+        }
+    }
+    
+    /// Run a function from `compile_cached`
+    ///
+    /// Like `execute_detailed(..).result`, the function runs in a fresh VM
+    /// unless the engine is persistent. The function may have been built
+    /// by hand, so the VM checks every local it accesses.
+    pub fn execute_compiled(&mut self, function: &Arc<BytecodeFunction>) -> Result<Value> {
+        let execute_start = Instant::now();
+        let (result, vm_metrics) = self.run_main(|vm| vm.execute_shared(function.clone()));
+        let timings = PhaseTimings { execute: execute_start.elapsed(), ..PhaseTimings::default() };
+        self.record_metrics(timings, 0, Some(function), vm_metrics);
+        result
+    }
+    
+    /// Run `source` with `bindings` as globals that shadow any of the
+    /// same name, for this run only
+    ///
+    /// Assignments to the bindings change them, not the globals they
+    /// shadow, and are dropped with them; globals the script creates or
+    /// assigns otherwise stay, as with `execute`. The bindings are gone
+    /// once it returns, whether or not the script succeeded, so callbacks
+    /// it leaves for `run_pending` see the real globals. Nothing is
+    /// printed.
+    pub fn eval_with_globals(
+        &mut self,
+        source: &str,
+        bindings: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Value> {
+        let slots = bindings.into_iter().map(|(name, value)| (name, GlobalSlot::new(value)));
+        self.eval_with_global_slots(source, slots)
+    }
+    
+    /// `eval_with_globals` with the bindings' flags given: a
+    /// `GlobalSlot::read_only` binding ignores assignments in sloppy mode
+    /// code and rejects them in strict mode code, as a read-only global
+    /// does
+    pub fn eval_with_global_slots(
+        &mut self,
+        source: &str,
+        bindings: impl IntoIterator<Item = (String, GlobalSlot)>,
+    ) -> Result<Value> {
+        let function = self.compile_cached(source)?;
+        let layer = bindings.into_iter().collect();
+        let execute_start = Instant::now();
+        let (result, vm_metrics) = self.run_main(|vm| {
+            vm.push_global_layer(layer);
+            let result = vm.execute_shared(function.clone());
+            vm.pop_global_layer();
+            result
+        });
+        let timings = PhaseTimings { execute: execute_start.elapsed(), ..PhaseTimings::default() };
+        self.record_metrics(timings, 0, Some(&function), vm_metrics);
+        result
+    }
+    
+    /// Metrics of the most recent execution; `None` unless the engine was
+    /// built with `EngineBuilder::metrics`
+    ///
+    /// Callbacks pumped by `run_pending` afterwards are not included.
+    pub fn last_metrics(&self) -> Option<&ExecutionMetrics> {
+        self.last_metrics.as_ref()
+    }
+    
+    /// Drop every compiled script from the cache
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+    
+    /// Hits and misses of the compilation cache so far
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+    
+    /// The syntax features this build implements, the limits this engine
+    /// was configured with and the crate version
+    pub fn capabilities(&self) -> Capabilities {
+        Capabilities::new(&self.vm_config, self.max_nesting_depth, self.lexer_features)
+    }
+    
+    /// Steps 1-3 of the pipeline, recording their artifacts in `report`
+    ///
+    /// Returns `None` once `report.result` holds the error that stopped
+    /// compilation. A cache hit skips all three steps, so its report has
+    /// no tokens or AST, only the diagnostics of the original compilation.
+    /// The compiled code is verified before it is cached.
+    fn compile_source(&mut self, source: &str, report: &mut ExecutionReport) -> Option<VerifiedFunction> {
+        if let Some(script) = self.cache.get(source) {
+            report.phase = Phase::Compile;
+            report.diagnostics = script.diagnostics;
+            self.print_bytecode(&script.function);
+            return Some(script.function);
+        }
+        
+        // Steps 1-2: Parse, pulling tokens from a live lexer so the parser
+        // can tell it where a regex literal may start. Lexing happens on
+        // demand, so its time is measured inside the token source.
+        let mut lexer = RecordingLexer::new(source, self.lexer_features);
+        let parse_start = Instant::now();
+        let parsed = Parser::from_source(&mut lexer).with_max_depth(self.max_nesting_depth).parse();
+        let parse_elapsed = parse_start.elapsed();
         
-        // Step 1: Tokenize the source code
-        let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize()?;
+        // A parse error still leaves the tokens to report if the rest of the
+        // source lexes cleanly
+        let lexed = match parsed.as_ref().map_err(Error::inner) {
+            Err(Error::Lexer { .. }) => false,
+            Err(Error::Parser { .. }) => lexer.finish().is_ok(),
+            _ => true,
+        };
+        report.timings.lex = lexer.elapsed;
+        report.timings.parse = parse_elapsed.saturating_sub(lexer.elapsed);
+        report.diagnostics = lexer.lexer.take_diagnostics();
+        if lexed {
+            report.tokens = Some(lexer.tokens);
+        }
+        if !matches!(parsed.as_ref().map_err(Error::inner), Err(Error::Lexer { .. })) {
+            report.phase = Phase::Parse;
+        }
         
-        // Step 2: Parse tokens into AST
-        let mut parser = Parser::new(tokens);
-        let ast = parser.parse()?;
+        let ast = match parsed {
+            Ok(ast) => ast,
+            Err(error) => {
+                report.result = Err(error);
+                return None;
+            }
+        };
         
         // Display the parsed AST if requested
         if self.ast_debug_mode {
-            println!("AST (detailed tree):");
-            println!("{}", ast.pretty_print(0));
-            println!();
+            let _ = writeln!(self.io.borrow_mut().stdout, "AST (detailed tree):\n{}\n", ast.pretty_print(0));
         }
         
         // Step 3: Compile AST to bytecode
-        let bytecode_function = self.compile_to_bytecode(&ast, source)?;
+        report.phase = Phase::Compile;
+        let compile_start = Instant::now();
+        let compiled = self.compile_to_bytecode(&ast, source);
+        report.timings.compile = compile_start.elapsed();
+        report.ast = Some(ast);
+        
+        let bytecode_function = match compiled.and_then(|(function, diagnostics)| {
+            Ok((VerifiedFunction::new(Arc::new(function))?, diagnostics))
+        }) {
+            Ok((function, mut diagnostics)) => {
+                report.diagnostics.append(&mut diagnostics);
+                function
+            }
+            Err(error) => {
+                report.result = Err(error);
+                return None;
+            }
+        };
         
-        // Display the bytecode if requested
+        self.cache.insert(source, CompiledScript {
+            function: bytecode_function.clone(),
+            diagnostics: report.diagnostics.clone(),
+        });
+        self.print_bytecode(&bytecode_function);
+        Some(bytecode_function)
+    }
+    
+    /// Display the bytecode if requested
+    fn print_bytecode(&self, function: &BytecodeFunction) {
         if self.bytecode_debug_mode {
-            println!("Bytecode:");
-            println!("{}", Disassembler::quick_disassemble(&bytecode_function));
-            println!();
+            let _ = writeln!(self.io.borrow_mut().stdout, "Bytecode:\n{}\n", Disassembler::quick_disassemble(function));
         }
-        
-        // Step 4: Execute bytecode in VM
-        let mut vm = if self.bytecode_debug_mode {
-            VM::new_with_debug()
-        } else {
-            VM::new()
+    }
+    
+    /// Run a compiled program in the shared VM or a fresh one, returning
+    /// the VM's counters for the run along with its result
+    ///
+    /// `execute` starts the program in the VM it is given.
+    fn run_main(&mut self, execute: impl FnOnce(&mut VM) -> Result<Value>) -> (Result<Value>, VmMetrics) {
+        let reused = if self.persistent { self.vm.take() } else { None };
+        let mut vm = match reused {
+            Some(vm) => vm,
+            None => self.create_vm(),
         };
         
-        let result = vm.execute(bytecode_function)?;
+        // Leave out whatever a shared VM counted since its last run
+        vm.take_metrics();
+        if self.persistent {
+            vm.push_global_layer(self.history.layer(&vm));
+        }
+        let result = execute(&mut vm);
+        let vm_metrics = vm.take_metrics();
         
-        // Print the result if it's not undefined (for REPL)
-        if !matches!(result, Value::Undefined) {
-            println!("{}", result);
+        if self.persistent {
+            self.history.record(&mut vm, result.as_ref().ok());
+            self.vm = Some(vm);
+        } else if vm.has_pending_tasks() || vm.has_unhandled_rejections() || self.profile_mode.is_some() {
+            // Keep the VM alive for `run_pending` and `profile_report`
+            self.vm = Some(vm);
         }
         
-        Ok(result)
+        (result, vm_metrics)
+    }
+    
+    /// Store the metrics of an execution, if they are being collected
+    fn record_metrics(
+        &mut self,
+        timings: PhaseTimings,
+        statements: usize,
+        function: Option<&BytecodeFunction>,
+        vm_metrics: VmMetrics,
+    ) {
+        if !self.metrics_enabled {
+            return;
+        }
+        let mut metrics = ExecutionMetrics {
+            timings,
+            statements,
+            instructions_executed: vm_metrics.instructions_executed,
+            peak_stack_depth: vm_metrics.peak_stack_depth,
+            ..ExecutionMetrics::default()
+        };
+        // Count the code of every function in the program
+        let mut pending: Vec<&BytecodeFunction> = function.into_iter().collect();
+        while let Some(function) = pending.pop() {
+            metrics.instructions_compiled += function.bytecode.len();
+            metrics.constants += function.constants.len();
+            pending.extend(function.functions.iter().map(|nested| &**nested));
+        }
+        self.last_metrics = Some(metrics);
+    }
+    
+    /// A VM configured with this engine's flags and host globals
+    fn create_vm(&self) -> VM {
+        let mut vm = match &self.snapshot {
+            Some(snapshot) => VM::with_snapshot(self.vm_config, snapshot),
+            None => VM::with_config(self.vm_config),
+        };
+        if self.writable_builtins {
+            vm.set_builtins_writable(true);
+        }
+        if let Some(seed) = self.random_seed {
+            vm.set_random_seed(seed);
+        }
+        if let Some(mode) = self.profile_mode {
+            vm.enable_profiling(mode);
+        }
+        if self.metrics_enabled {
+            vm.enable_metrics();
+        }
+        vm.set_console_output(self.console_output.clone());
+        vm.set_io(self.io.clone());
+        vm.set_persistent_roots(self.persistents.clone());
+        for (name, slot) in &self.host_globals {
+            vm.define_global(name.clone(), slot.clone());
+        }
+        vm
     }
     
     /// Compile AST to bytecode using the real compiler
-    fn compile_to_bytecode(&self, ast: &ast::Program, source: &str) -> Result<BytecodeFunction> {
-        // Create a compiler for the main program
-        let compiler = Compiler::new_main(source);
+    fn compile_to_bytecode(&self, ast: &ast::Program, source: &str) -> Result<(BytecodeFunction, Vec<Diagnostic>)> {
+        self.compiler(source).compile_with_diagnostics(ast)
+    }
+    
+    /// A compiler for the main program `source`, with the engine's limit
+    /// and optimizations
+    fn compiler(&self, source: &str) -> Compiler {
+        let compiler = Compiler::new_main(source).with_max_depth(self.max_nesting_depth);
+        if !self.optimize {
+            return compiler;
+        }
+        let compiler = compiler.with_constant_hoisting(self.max_hoisted_constants);
+        // Folding assumes the built-ins are the engine's own
+        if self.writable_builtins {
+            compiler
+        } else {
+            compiler.with_builtin_folding()
+        }
+    }
+}
+
+/// Options for creating an `Engine`, from `Engine::builder`
+#[derive(Debug, Default, Clone)]
+pub struct EngineBuilder {
+    ast_debug_mode: bool,
+    bytecode_debug_mode: bool,
+    trace_execution: bool,
+    writable_builtins: bool,
+    persistent: bool,
+    random_seed: Option<u64>,
+    profile_mode: Option<ProfileMode>,
+    vm_config: VMConfig,
+    proto_setter: bool,
+    frame_arena: bool,
+    max_nesting_depth: Option<usize>,
+    lexer_features: FeatureFlags,
+    cache_capacity: usize,
+    metrics: bool,
+    snapshot: Option<Arc<Snapshot>>,
+    optimize: bool,
+    max_hoisted_constants: Option<usize>,
+}
+
+impl EngineBuilder {
+    /// Print the AST of each program to the engine's stdout before
+    /// compiling it
+    pub fn ast_debug(mut self, enabled: bool) -> Self {
+        self.ast_debug_mode = enabled;
+        self
+    }
+    
+    /// Print the bytecode of each program to the engine's stdout before
+    /// running it
+    ///
+    /// Only the listing is printed; the run itself is unchanged.
+    pub fn bytecode_debug(mut self, enabled: bool) -> Self {
+        self.bytecode_debug_mode = enabled;
+        self
+    }
+    
+    /// Trace every instruction each VM runs to the engine's stderr (see
+    /// `VMConfig::trace_execution`)
+    ///
+    /// What scripts print and return stays the same, but they run many
+    /// times slower.
+    pub fn trace_execution(mut self, enabled: bool) -> Self {
+        self.trace_execution = enabled;
+        self
+    }
+    
+    /// Let scripts overwrite engine-installed built-ins
+    pub fn writable_builtins(mut self, enabled: bool) -> Self {
+        self.writable_builtins = enabled;
+        self
+    }
+    
+    /// Share one VM across executions (see `Engine::new_persistent`)
+    pub fn persistent(mut self, enabled: bool) -> Self {
+        self.persistent = enabled;
+        self
+    }
+    
+    /// Seed `Math.random`, making its sequence the same on every run and
+    /// platform
+    ///
+    /// Each VM the engine creates starts from this seed, so executions of
+    /// a non-persistent engine all see the same sequence.
+    pub fn random_seed(mut self, seed: u64) -> Self {
+        self.random_seed = Some(seed);
+        self
+    }
+    
+    /// Profile every execution by source line (see
+    /// `Engine::profile_report`)
+    pub fn profile(mut self, mode: ProfileMode) -> Self {
+        self.profile_mode = Some(mode);
+        self
+    }
+    
+    /// Limits such as the maximum call depth
+    pub fn vm_config(mut self, config: VMConfig) -> Self {
+        self.vm_config = config;
+        self
+    }
+    
+    /// Let `obj.__proto__`, written as a dotted member, read and change
+    /// the prototype of `obj` (see `VMConfig::proto_setter`)
+    ///
+    /// Off by default, which keeps `__proto__` an ordinary key. Computed
+    /// writes such as `obj["__proto__"] = value` and keys parsed by
+    /// `JSON.parse` stay ordinary properties even when this is on.
+    pub fn proto_setter(mut self, enabled: bool) -> Self {
+        self.proto_setter = enabled;
+        self
+    }
+    
+    /// Reuse the objects of literals that never leave the call that made
+    /// them, such as option bags, instead of allocating each one afresh
+    /// (see `VMConfig::frame_arena`)
+    ///
+    /// Off by default while it is new.
+    pub fn frame_arena(mut self, enabled: bool) -> Self {
+        self.frame_arena = enabled;
+        self
+    }
+    
+    /// Keep the compiled form of up to `capacity` distinct scripts, so
+    /// running the same source again skips lexing, parsing and compiling
+    ///
+    /// The least recently used script is dropped when the cache is full.
+    /// Zero, the default, disables the cache.
+    pub fn compilation_cache(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+    
+    /// Collect timings and counters for each execution (see
+    /// `Engine::last_metrics`)
+    ///
+    /// Off by default; the VM then skips its counters entirely.
+    pub fn metrics(mut self, enabled: bool) -> Self {
+        self.metrics = enabled;
+        self
+    }
+    
+    /// Start each VM from the globals in `snapshot` (see
+    /// `Engine::from_snapshot`)
+    ///
+    /// With `writable_builtins`, the engine-installed built-ins are still
+    /// rebuilt on top, as that setting needs fresh copies of them.
+    pub fn snapshot(mut self, snapshot: Arc<Snapshot>) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+    
+    /// Run the compiler's optional optimizations:
+    ///
+    /// - evaluating calls of pure built-ins on constant arguments while
+    ///   compiling, so `parseInt("42")` becomes the constant 42 (see
+    ///   `Compiler::with_builtin_folding`). It assumes scripts don't
+    ///   replace built-in methods, and is skipped with `writable_builtins`;
+    /// - loading the constants a loop uses once, before it runs, rather
+    ///   than on every iteration (see `Compiler::with_constant_hoisting`
+    ///   and `max_hoisted_constants`).
+    ///
+    /// Off by default.
+    pub fn optimize(mut self, enabled: bool) -> Self {
+        self.optimize = enabled;
+        self
+    }
+    
+    /// Hoist at most `max` constants out of the loops of each function
+    /// when optimizing (by default `DEFAULT_MAX_HOISTED_CONSTANTS`)
+    ///
+    /// Each takes a local slot for the whole call. Zero turns hoisting
+    /// off.
+    pub fn max_hoisted_constants(mut self, max: usize) -> Self {
+        self.max_hoisted_constants = Some(max);
+        self
+    }
+    
+    /// Limit how deeply statements and expressions may nest
+    ///
+    /// The parser and the compiler both recurse once per level, so this
+    /// one value bounds the native stack either can use; deeper scripts
+    /// fail with a clean error. Defaults to
+    /// `parser::DEFAULT_MAX_NESTING_DEPTH`.
+    pub fn max_nesting_depth(mut self, depth: usize) -> Self {
+        self.max_nesting_depth = Some(depth);
+        self
+    }
+    
+    /// Accept only the optional syntax enabled in `features`
+    ///
+    /// Everything is enabled by default. Scripts using a disabled feature
+    /// fail to compile with an error naming its flag.
+    pub fn lexer_features(mut self, features: FeatureFlags) -> Self {
+        self.lexer_features = features;
+        self
+    }
+    
+    /// Create the engine
+    pub fn build(self) -> Engine {
+        Engine {
+            ast_debug_mode: self.ast_debug_mode,
+            bytecode_debug_mode: self.bytecode_debug_mode,
+            writable_builtins: self.writable_builtins,
+            host_globals: Vec::new(),
+            persistent: self.persistent,
+            vm: None,
+            history: ResultHistory::default(),
+            persistents: PersistentRoots::default(),
+            top_level: HashMap::new(),
+            uncaught_handler: None,
+            rejection_handler: None,
+            console_output: None,
+            io: EngineIo::process().shared(),
+            random_seed: self.random_seed,
+            profile_mode: self.profile_mode,
+            vm_config: VMConfig {
+                proto_setter: self.proto_setter || self.vm_config.proto_setter,
+                frame_arena: self.frame_arena || self.vm_config.frame_arena,
+                trace_execution: self.trace_execution || self.vm_config.trace_execution,
+                ..self.vm_config
+            },
+            max_nesting_depth: self.max_nesting_depth.unwrap_or(parser::DEFAULT_MAX_NESTING_DEPTH),
+            lexer_features: self.lexer_features,
+            cache: CompilationCache::new(self.cache_capacity),
+            metrics_enabled: self.metrics,
+            snapshot: self.snapshot,
+            optimize: self.optimize,
+            max_hoisted_constants: self.max_hoisted_constants.unwrap_or(bytecode::DEFAULT_MAX_HOISTED_CONSTANTS),
+            last_metrics: None,
+        }
+    }
+}
+
+/// The names `program` declares at its top level, whether each is
+/// `const`, and whether it is hoisted (`var` and function declarations)
+fn top_level_declarations(program: &ast::Program) -> Vec<(String, bool, bool)> {
+    program.statements.iter()
+        .filter_map(|stmt| match stmt {
+            ast::Stmt::VarDecl { kind, name, .. } => Some((name.clone(), *kind == ast::VarKind::Const, *kind == ast::VarKind::Var)),
+            ast::Stmt::FunctionDecl { name, .. } => Some((name.clone(), false, true)),
+            ast::Stmt::ClassDecl { name, .. } => Some((name.clone(), false, false)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Results of a persistent engine, readable as `_` and `_1`, `_2`, ...
+///
+/// The names are not globals: each run of the engine gets them in a
+/// global layer (see `VM::push_global_layer`) that is popped when the run
+/// returns, so callbacks left for `run_pending` don't see them. `_` is
+/// writable and the numbered results read-only. A real global of the same
+/// name, declared or assigned by a script, takes precedence over each.
+#[derive(Debug, Default)]
+struct ResultHistory {
+    /// Held between runs, when the layer with them is not pushed
+    results: Vec<Persistent>,
+}
+
+impl ResultHistory {
+    /// The bindings for a run on `vm`, before any layer is pushed
+    fn layer(&self, vm: &VM) -> HashMap<String, GlobalSlot> {
+        let numbered = self.results.iter().enumerate()
+            .map(|(index, value)| (format!("_{}", index + 1), GlobalSlot::read_only(value.get())));
+        let last = self.results.last()
+            .map(|value| ("_".to_string(), GlobalSlot::new(value.get())));
+        numbered.chain(last)
+            .filter(|(name, _)| vm.get_global(name).is_none())
+            .collect()
+    }
+    
+    /// Pop the layer of a run, and store its result if it has a
+    /// non-undefined one
+    ///
+    /// A script assigning `_` writes the layer's binding, even with the
+    /// value it held; that value is moved to a real global, which `_`
+    /// then stays.
+    fn record(&mut self, vm: &mut VM, result: Option<&Value>) {
+        let assigned = vm.global_layer_written("_");
+        let mut layer = vm.pop_global_layer().unwrap_or_default();
+        if let Some(slot) = layer.remove("_").filter(|_| assigned) {
+            vm.define_global("_", slot);
+        }
+        
+        if let Some(value) = result.filter(|value| !matches!(value, Value::Undefined)) {
+            self.results.push(vm.persistent(value.clone()));
+        }
+    }
+}
+
+/// Stage of the execution pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Lex,
+    Parse,
+    Compile,
+    Execute,
+}
+
+/// Wall-clock time spent in each phase
+///
+/// Lexing runs on demand while parsing, so `lex` is the time spent inside
+/// the lexer and `parse` the remainder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    pub lex: Duration,
+    pub parse: Duration,
+    pub compile: Duration,
+    pub execute: Duration,
+}
+
+/// Timings and counters of one execution, from `Engine::last_metrics`
+///
+/// `statements` counts the program's top-level statements; it is zero
+/// for a program taken from the compilation cache or run with
+/// `execute_compiled`, whose earlier phases did not run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutionMetrics {
+    pub timings: PhaseTimings,
+    /// Top-level statements in the program
+    pub statements: usize,
+    /// Instructions in the main function and every nested function
+    pub instructions_compiled: usize,
+    /// Constant pool entries across the same functions
+    pub constants: usize,
+    /// Instructions the VM ran
+    pub instructions_executed: u64,
+    /// Most values the operand stack held at once
+    pub peak_stack_depth: usize,
+}
+
+impl fmt::Display for ExecutionMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let timings = &self.timings;
+        let total = timings.lex + timings.parse + timings.compile + timings.execute;
+        writeln!(f, "{:<24}{:>14}", "phase", "time")?;
+        for (phase, time) in [
+            ("lex", timings.lex),
+            ("parse", timings.parse),
+            ("compile", timings.compile),
+            ("execute", timings.execute),
+            ("total", total),
+        ] {
+            writeln!(f, "  {:<22}{:>11.3} ms", phase, time.as_secs_f64() * 1000.0)?;
+        }
+        writeln!(f, "{:<24}{:>14}", "counter", "value")?;
+        for (counter, value) in [
+            ("statements", self.statements as u64),
+            ("instructions compiled", self.instructions_compiled as u64),
+            ("constants", self.constants as u64),
+            ("instructions executed", self.instructions_executed),
+            ("peak stack depth", self.peak_stack_depth as u64),
+        ] {
+            writeln!(f, "  {:<22}{:>14}", counter, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Outcome of `Engine::execute_detailed`
+///
+/// Each artifact is present exactly when its phase completed; a program
+/// taken from the compilation cache has bytecode but no tokens or AST.
+#[derive(Debug)]
+pub struct ExecutionReport {
+    /// Phase that produced `result`: the one that failed, or `Execute`
+    pub phase: Phase,
+    /// Every token in the source
+    pub tokens: Option<Vec<Token>>,
+    /// The parsed program
+    pub ast: Option<ast::Program>,
+    /// The compiled main function
+    pub bytecode: Option<Arc<BytecodeFunction>>,
+    /// Completion value, or the error that stopped the pipeline
+    pub result: Result<Value>,
+    /// Non-fatal warnings from the phases that ran
+    pub diagnostics: Vec<Diagnostic>,
+    pub timings: PhaseTimings,
+}
+
+impl ExecutionReport {
+    /// A report for a pipeline that has not started
+    fn new() -> Self {
+        Self {
+            phase: Phase::Lex,
+            tokens: None,
+            ast: None,
+            bytecode: None,
+            result: Ok(Value::Undefined),
+            diagnostics: Vec::new(),
+            timings: PhaseTimings::default(),
+        }
+    }
+}
+
+/// Live lexer that records the tokens it hands out and its scanning time
+struct RecordingLexer<'a> {
+    lexer: Lexer<'a>,
+    tokens: Vec<Token>,
+    elapsed: Duration,
+}
+
+impl<'a> RecordingLexer<'a> {
+    fn new(source: &'a str, features: FeatureFlags) -> Self {
+        Self { lexer: Lexer::new(source).with_features(features), tokens: Vec::new(), elapsed: Duration::ZERO }
+    }
+    
+    /// Lex whatever the parser didn't reach
+    fn finish(&mut self) -> Result<()> {
+        while !self.tokens.last().is_some_and(|token| matches!(token.kind, TokenKind::Eof)) {
+            self.next_token(&LexerContext::default())?;
+        }
+        Ok(())
+    }
+}
+
+impl TokenSource for RecordingLexer<'_> {
+    fn next_token(&mut self, context: &LexerContext) -> Result<Token> {
+        let start = Instant::now();
+        let token = TokenSource::next_token(&mut self.lexer, context);
+        self.elapsed += start.elapsed();
+        
+        let token = token?;
+        self.tokens.push(token.clone());
+        Ok(token)
+    }
+    
+    fn rescan(&mut self, token: &Token, context: &LexerContext) -> Result<Option<Token>> {
+        let start = Instant::now();
+        let rescanned = self.lexer.rescan(token, context);
+        self.elapsed += start.elapsed();
         
-        // Compile the AST to bytecode
-        compiler.compile(ast)
+        let rescanned = rescanned?;
+        if let Some(rescanned) = &rescanned {
+            if let Some(index) = self.tokens.iter().rposition(|t| t.span.start == token.span.start) {
+                self.tokens.truncate(index);
+            }
+            self.tokens.push(rescanned.clone());
+        }
+        Ok(rescanned)
     }
 }
 
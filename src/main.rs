@@ -1,62 +1,106 @@
-use v8::{Engine, Result, Lexer, Parser, ast::PrettyPrint};
+use v8::{Diagnostic, Engine, Error, ProfileMode, Result, Lexer, Parser, Disassembler, Value, ast::PrettyPrint};
+use v8::bytecode::{self, dump_text, BytecodeDump, BytecodeFunction};
+use v8::parser::estree;
+use v8::repl::Repl;
 use std::env;
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, ErrorKind, Read};
+use std::sync::Arc;
 
-fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+mod args;
+mod output;
+
+use args::{Mode, Options, Snippet};
+use output::{err, errln, out, outln};
+
+fn main() {
+    let status = match run() {
+        Ok(()) => 0,
+        Err(error) => {
+            errln!("{}", Diagnostic::from(error));
+            1
+        }
+    };
+    output::exit(status)
+}
+
+fn run() -> Result<()> {
+    let mut arguments = env::args();
+    let program = arguments.next().unwrap_or_else(|| "v8".to_string());
+    let options = match Options::parse(arguments) {
+        Ok(options) => options,
+        Err(message) => {
+            errln!("{}", message);
+            output::exit(1);
+        }
+    };
+    let max_size = options.max_source_size;
+    let script = options.script.as_deref();
     
-    match args.len() {
-        1 => {
-            // Start REPL mode
-            repl_mode()
-        },
-        2 => {
-            // Check if we should debug tokenization, AST, or bytecode
-            let filename = &args[1];
-            if filename == "--debug-tokens" {
-                debug_tokens_mode()
-            } else if filename == "--debug-ast" {
-                debug_ast_mode()
-            } else if filename == "--debug-bytecode" {
-                debug_bytecode_mode()
-            } else {
-                execute_file(filename)
-            }
-        },
-        3 => {
-            if &args[1] == "--debug-tokens" {
-                let filename = &args[2];
-                debug_tokens_for_file(filename)
-            } else if &args[1] == "--debug-ast" {
-                let filename = &args[2];
-                debug_ast_for_file(filename)
-            } else if &args[1] == "--debug-bytecode" {
-                let filename = &args[2];
-                debug_bytecode_for_file(filename)
-            } else {
-                eprintln!("Usage: {} [file.js] or {} --debug-tokens [file.js] or {} --debug-ast [file.js] or {} --debug-bytecode [file.js]", args[0], args[0], args[0], args[0]);
-                std::process::exit(1);
-            }
-        },
-        _ => {
-            eprintln!("Usage: {} [file.js]", args[0]);
-            eprintln!("  {} - Start REPL", args[0]);
-            eprintln!("  {} file.js - Execute JavaScript file", args[0]);
-            eprintln!("  {} --debug-tokens [file.js] - Show tokens for input", args[0]);
-            eprintln!("  {} --debug-ast [file.js] - Show detailed AST tree", args[0]);
-            eprintln!("  {} --debug-bytecode [file.js] - Show compiled bytecode", args[0]);
-            std::process::exit(1);
+    match (options.mode, script) {
+        (Mode::Help, _) => {
+            out!("{}", args::usage(&program));
+            Ok(())
+        }
+        (Mode::Run, _) if !options.snippets.is_empty() => run_snippets(&options.snippets),
+        (Mode::Run, Some(filename)) => execute_file(filename, max_size),
+        (Mode::Run, None) => repl_mode(),
+        (Mode::DebugTokens, Some(filename)) => debug_tokens_for_file(filename, max_size),
+        (Mode::DebugTokens, None) => debug_tokens_mode(),
+        (Mode::DebugAst, Some(filename)) => debug_ast_for_file(filename, max_size),
+        (Mode::DebugAst, None) => debug_ast_mode(),
+        (Mode::DebugBytecode, Some(filename)) => debug_bytecode_for_file(filename, max_size),
+        (Mode::DebugBytecode, None) => debug_bytecode_mode(),
+        (Mode::DumpAst, Some(filename)) => dump_ast_file(filename, max_size),
+        (Mode::DumpBytecode { json }, Some(filename)) => dump_bytecode_file(filename, max_size, json, options.output.as_deref()),
+        (Mode::DiffBytecode, Some(filename)) => {
+            let base = options.diff_base.as_deref().expect("--diff-bytecode names the old script");
+            diff_bytecode_files(base, filename, max_size)
+        }
+        (Mode::Profile, Some(filename)) => profile_file(filename, max_size),
+        (Mode::Timing, Some(filename)) => timing_file(filename, max_size),
+        // `Options::parse` requires a script for the rest
+        (_, None) => unreachable!("no script for {:?}", options.mode),
+    }
+}
+
+/// Read a script, refusing one of more than `max_size` bytes
+///
+/// The size a file reports is checked before anything is read, and the
+/// read itself stops just past the limit, so an oversized script from a
+/// pipe or a growing file is never held in memory either. The file name
+/// `-` reads stdin to its end.
+fn read_script(filename: &str, max_size: u64) -> Result<String> {
+    let cannot_read = |error: io::Error| Error::from(error).context(format!("Cannot read '{}'", filename));
+    let too_large = |size: String| cannot_read(io::Error::new(
+        ErrorKind::FileTooLarge,
+        format!("the script is {} bytes, over the limit of {} (see --max-source-size)", size, max_size),
+    ));
+    
+    let mut source = String::new();
+    if filename == "-" {
+        io::stdin().lock().take(max_size + 1).read_to_string(&mut source).map_err(cannot_read)?;
+    } else {
+        let file = File::open(filename).map_err(cannot_read)?;
+        let size = file.metadata().map_err(cannot_read)?.len();
+        if size > max_size {
+            return Err(too_large(size.to_string()));
         }
+        source.reserve(size as usize);
+        file.take(max_size + 1).read_to_string(&mut source).map_err(cannot_read)?;
+    }
+    if source.len() as u64 > max_size {
+        return Err(too_large(format!("more than {}", max_size)));
     }
+    Ok(source)
 }
 
 fn debug_tokens_mode() -> Result<()> {
-    println!("Enter JavaScript code to see tokens (Ctrl+D to exit):");
+    outln!("Enter JavaScript code to see tokens (Ctrl+D to exit):");
     
     loop {
-        print!("tokens> ");
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
+        out!("tokens> ");
+        output::flush_stdout();
         
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
@@ -69,14 +113,14 @@ fn debug_tokens_mode() -> Result<()> {
                 match lexer.tokenize() {
                     Ok(tokens) => {
                         for token in tokens {
-                            println!("{:?}", token);
+                            outln!("{:?} {:?}", token.text_in(input), token);
                         }
                     },
-                    Err(e) => eprintln!("Lexer error: {}", e),
+                    Err(e) => errln!("{}", Diagnostic::from(e)),
                 }
             },
             Err(e) => {
-                eprintln!("Error reading input: {}", e);
+                errln!("Error reading input: {}", e);
                 break;
             }
         }
@@ -84,94 +128,119 @@ fn debug_tokens_mode() -> Result<()> {
     Ok(())
 }
 
-fn debug_tokens_for_file(filename: &str) -> Result<()> {
-    let source = fs::read_to_string(filename)?;
-    println!("Tokens for file '{}':", filename);
-    println!("Source: {}", source);
-    println!();
+fn debug_tokens_for_file(filename: &str, max_size: u64) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    outln!("Tokens for file '{}':", filename);
+    outln!("Source: {}", source);
+    outln!();
     
     let mut lexer = Lexer::new(&source);
     match lexer.tokenize() {
         Ok(tokens) => {
             for (i, token) in tokens.iter().enumerate() {
-                println!("{}: {:?}", i, token);
+                outln!("{}: {:?} {:?}", i, token.text_in(&source), token);
             }
         },
-        Err(e) => eprintln!("Lexer error: {}", e),
+        Err(e) => errln!("{}", Diagnostic::from(e)),
     }
     Ok(())
 }
 
 fn repl_mode() -> Result<()> {
-    println!("V8-like JavaScript Engine v{}", v8::VERSION);
-    println!("Type 'exit' or '.exit' to quit, '.help' for help");
-    
-    let mut engine = Engine::new();
+    outln!("V8-like JavaScript Engine v{}", v8::VERSION);
+    outln!("Type 'exit' or '.exit' to quit, '.help' for help");
     
-    loop {
-        print!("js> ");
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
-        
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(0) => {
-                // EOF (Ctrl+D) - graceful exit
-                println!("\nGoodbye!");
-                break;
-            }
-            Ok(_) => {
-                // Successfully read input
-                let input = input.trim();
-                
-                // Handle REPL commands
-                match input {
-                    "exit" | ".exit" => break,
-                    ".help" => {
-                        println!("Available commands:");
-                        println!("  .exit   - Exit the REPL");
-                        println!("  .help   - Show this help message");
-                        println!("  <expr>  - Evaluate JavaScript expression");
-                        continue;
-                    }
-                    "" => continue,
-                    _ => {
-                        // Execute JavaScript code
-                        match engine.execute(input) {
-                            Ok(_result) => {
-                                // Success - result already printed by engine
-                            },
-                            Err(e) => {
-                                eprintln!("Error: {}", e);
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                eprintln!("Error reading input: {}", e);
-                break;
+    let mut repl = Repl::new();
+    route_output(repl.engine_mut());
+    if let Err(e) = repl.run("js> ") {
+        errln!("Error reading input: {}", e);
+    }
+    Ok(())
+}
+
+fn execute_file(filename: &str, max_size: u64) -> Result<()> {
+    run_to_completion(&mut Engine::new(), filename, max_size)
+}
+
+/// Send everything a script prints, and reports of errors after it
+/// finishes, through the panic-free output
+fn route_output(engine: &mut Engine) {
+    engine.set_io(output::engine_io());
+}
+
+/// Print a completion value the way `Engine::execute` does
+fn print_result(result: &Value) {
+    if !matches!(result, Value::Undefined) {
+        outln!("{}", result);
+    }
+}
+
+fn profile_file(filename: &str, max_size: u64) -> Result<()> {
+    let mut engine = Engine::builder().profile(ProfileMode::Counting).build();
+    run_to_completion(&mut engine, filename, max_size)?;
+    if let Some(report) = engine.profile_report() {
+        err!("{}", report);
+    }
+    Ok(())
+}
+
+fn timing_file(filename: &str, max_size: u64) -> Result<()> {
+    let mut engine = Engine::builder().metrics(true).build();
+    let result = run_to_completion(&mut engine, filename, max_size);
+    if let Some(metrics) = engine.last_metrics() {
+        err!("{}", metrics);
+    }
+    result
+}
+
+fn run_to_completion(engine: &mut Engine, filename: &str, max_size: u64) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    run_source(engine, &source)
+}
+
+/// Run a script and then its timers
+fn run_source(engine: &mut Engine, source: &str) -> Result<()> {
+    route_output(engine);
+    print_result(&engine.execute_detailed(source).result?);
+    run_timers(engine)
+}
+
+/// Run `-e` and `-p` code in order in one VM, then the timers
+///
+/// Each snippet runs like a REPL input, so declarations in one are seen
+/// by the next. Unlike a script's, the completion value is printed only
+/// for `-p`, and then even when it is `undefined`; as in Node, strings
+/// are printed as they are and other values inspected.
+fn run_snippets(snippets: &[Snippet]) -> Result<()> {
+    let mut engine = Engine::new_persistent();
+    route_output(&mut engine);
+    for snippet in snippets {
+        let result = engine.eval_repl_input(&snippet.code)?;
+        if snippet.print {
+            match &result {
+                Value::String(s) => outln!("{}", s),
+                value => outln!("{}", value.inspect()),
             }
         }
     }
-    
-    Ok(())
+    run_timers(&mut engine)
 }
 
-fn execute_file(filename: &str) -> Result<()> {
-    let source = fs::read_to_string(filename)?;
-    let mut engine = Engine::new();
-    engine.execute(&source)?;
+/// Pump timers in real time until nothing is scheduled
+fn run_timers(engine: &mut Engine) -> Result<()> {
+    let start = std::time::Instant::now();
+    while engine.run_pending(start.elapsed().as_millis() as u64)? {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
     Ok(())
 }
 
 fn debug_ast_mode() -> Result<()> {
-    println!("Enter JavaScript code to see AST tree (Ctrl+D to exit):");
+    outln!("Enter JavaScript code to see AST tree (Ctrl+D to exit):");
     
     loop {
-        print!("ast> ");
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
+        out!("ast> ");
+        output::flush_stdout();
         
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
@@ -186,16 +255,16 @@ fn debug_ast_mode() -> Result<()> {
                         let mut parser = Parser::new(tokens);
                         match parser.parse() {
                             Ok(ast) => {
-                                println!("{}", ast.pretty_print(0));
+                                outln!("{}", ast.pretty_print(0));
                             },
-                            Err(e) => eprintln!("Parser error: {}", e),
+                            Err(e) => errln!("{}", Diagnostic::from(e)),
                         }
                     },
-                    Err(e) => eprintln!("Lexer error: {}", e),
+                    Err(e) => errln!("{}", Diagnostic::from(e)),
                 }
             },
             Err(e) => {
-                eprintln!("Error reading input: {}", e);
+                errln!("Error reading input: {}", e);
                 break;
             }
         }
@@ -203,11 +272,11 @@ fn debug_ast_mode() -> Result<()> {
     Ok(())
 }
 
-fn debug_ast_for_file(filename: &str) -> Result<()> {
-    let source = fs::read_to_string(filename)?;
-    println!("AST tree for file '{}':", filename);
-    println!("Source: {}", source);
-    println!();
+fn debug_ast_for_file(filename: &str, max_size: u64) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    outln!("AST tree for file '{}':", filename);
+    outln!("Source: {}", source);
+    outln!();
     
     let mut lexer = Lexer::new(&source);
     match lexer.tokenize() {
@@ -215,23 +284,30 @@ fn debug_ast_for_file(filename: &str) -> Result<()> {
             let mut parser = Parser::new(tokens);
             match parser.parse() {
                 Ok(ast) => {
-                    println!("{}", ast.pretty_print(0));
+                    outln!("{}", ast.pretty_print(0));
                 },
-                Err(e) => eprintln!("Parser error: {}", e),
+                Err(e) => errln!("{}", Diagnostic::from(e)),
             }
         },
-        Err(e) => eprintln!("Lexer error: {}", e),
+        Err(e) => errln!("{}", Diagnostic::from(e)),
     }
     Ok(())
 }
 
+/// Print the AST of a script as ESTree JSON
+fn dump_ast_file(filename: &str, max_size: u64) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    let program = Parser::new(Lexer::new(&source).tokenize()?).parse()?;
+    outln!("{}", estree::from_program(&program, &source).to_json());
+    Ok(())
+}
+
 fn debug_bytecode_mode() -> Result<()> {
-    println!("Enter JavaScript code to see bytecode (Ctrl+D to exit):");
+    outln!("Enter JavaScript code to see bytecode (Ctrl+D to exit):");
     
     loop {
-        print!("bytecode> ");
-        use std::io::{self, Write};
-        io::stdout().flush().unwrap();
+        out!("bytecode> ");
+        output::flush_stdout();
         
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
@@ -240,16 +316,12 @@ fn debug_bytecode_mode() -> Result<()> {
                 let input = input.trim();
                 if input.is_empty() { continue; }
                 
-                let mut engine = Engine::new_with_bytecode_debug();
-                match engine.execute(input) {
-                    Ok(_result) => {
-                        // Success - bytecode already printed by engine
-                    },
-                    Err(e) => eprintln!("Error: {}", e),
+                if let Err(e) = run_with_bytecode(input) {
+                    errln!("{}", Diagnostic::from(e));
                 }
             },
             Err(e) => {
-                eprintln!("Error reading input: {}", e);
+                errln!("Error reading input: {}", e);
                 break;
             }
         }
@@ -257,13 +329,66 @@ fn debug_bytecode_mode() -> Result<()> {
     Ok(())
 }
 
-fn debug_bytecode_for_file(filename: &str) -> Result<()> {
-    let source = fs::read_to_string(filename)?;
-    println!("Bytecode for file '{}':", filename);
-    println!("Source: {}", source);
-    println!();
+fn debug_bytecode_for_file(filename: &str, max_size: u64) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    outln!("Bytecode for file '{}':", filename);
+    outln!("Source: {}", source);
+    outln!();
     
-    let mut engine = Engine::new_with_bytecode_debug();
-    engine.execute(&source)?;
+    run_with_bytecode(&source)
+}
+
+/// Write the bytecode for a script in a stable format, then run it
+///
+/// The dump goes to stderr or a file so stdout carries only what the
+/// script prints.
+fn dump_bytecode_file(filename: &str, max_size: u64, json: bool, output: Option<&str>) -> Result<()> {
+    let source = read_script(filename, max_size)?;
+    let mut engine = Engine::new();
+    let function = engine.compile_cached(&source)?;
+    let text = if json {
+        BytecodeDump::new(&function).to_json() + "\n"
+    } else {
+        dump_text(&function)
+    };
+    match output {
+        Some(path) => fs::write(path, text).map_err(|error| Error::from(error).context(format!("Cannot write '{}'", path)))?,
+        None => err!("{}", text),
+    }
+    // Compiled scripts are cached, so this runs what was dumped
+    run_source(&mut engine, &source)
+}
+
+/// Print how the bytecode of `filename` differs from that of `base`
+fn diff_bytecode_files(base: &str, filename: &str, max_size: u64) -> Result<()> {
+    let (old, new) = (load_bytecode(base, max_size)?, load_bytecode(filename, max_size)?);
+    let difference = bytecode::diff(&old, &new);
+    if difference.is_empty() {
+        outln!("No differences");
+    } else {
+        out!("{}", difference);
+    }
+    Ok(())
+}
+
+/// Compile a script, or read back a `--dump-bytecode=json` file
+fn load_bytecode(filename: &str, max_size: u64) -> Result<Arc<BytecodeFunction>> {
+    let source = read_script(filename, max_size)?;
+    if filename.ends_with(".json") {
+        Ok(Arc::new(BytecodeDump::from_json(&source)?.to_function()?))
+    } else {
+        Engine::new().compile_cached(&source)
+    }
+}
+
+/// Print the bytecode for `source`, then run it
+fn run_with_bytecode(source: &str) -> Result<()> {
+    let mut engine = Engine::new();
+    route_output(&mut engine);
+    let function = engine.compile_cached(source)?;
+    outln!("Bytecode:");
+    outln!("{}", Disassembler::quick_disassemble(&function));
+    outln!();
+    print_result(&engine.execute_compiled(&function)?);
     Ok(())
 }
\ No newline at end of file
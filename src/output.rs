@@ -0,0 +1,129 @@
+//! Panic-free printing for the command-line tool
+//!
+//! `println!` panics when stdout is a pipe whose reader has gone away, as
+//! in `v8 script.js | head`. Everything the CLI prints goes through the
+//! macros here instead: a closed stdout ends the process quietly with
+//! status 0, like standard Unix tools, and any other write error is
+//! reported on stderr with status 1. Engines get the same behaviour
+//! through [`engine_io`].
+
+use std::fmt;
+use std::io::{self, ErrorKind, Write};
+use std::process;
+
+use v8::EngineIo;
+
+/// `print!` through [`write_stdout`]
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!($($arg)*))
+    };
+}
+
+/// `println!` through [`write_stdout`]
+macro_rules! outln {
+    () => {
+        $crate::output::write_stdout(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::write_stdout(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// `eprint!` through [`write_stderr`]
+macro_rules! err {
+    ($($arg:tt)*) => {
+        $crate::output::write_stderr(format_args!($($arg)*))
+    };
+}
+
+/// `eprintln!` through [`write_stderr`]
+macro_rules! errln {
+    ($($arg:tt)*) => {
+        $crate::output::write_stderr(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+pub(crate) use {err, errln, out, outln};
+
+/// Write to stdout, exiting if it cannot be written
+pub fn write_stdout(args: fmt::Arguments) {
+    if let Err(error) = io::stdout().lock().write_fmt(args) {
+        stdout_failed(error);
+    }
+}
+
+/// Flush stdout, exiting if it cannot be written
+///
+/// Needed after a prompt without a newline and before `process::exit`,
+/// which does not flush.
+pub fn flush_stdout() {
+    if let Err(error) = io::stdout().flush() {
+        stdout_failed(error);
+    }
+}
+
+/// Write to stderr, exiting with status 1 if it cannot be written
+pub fn write_stderr(args: fmt::Arguments) {
+    // With stderr gone as well there is nowhere left to report to
+    if io::stderr().write_fmt(args).is_err() {
+        process::exit(1);
+    }
+}
+
+/// Streams for an engine that write like the macros here
+pub fn engine_io() -> EngineIo {
+    EngineIo {
+        stdout: Box::new(Stdout),
+        stderr: Box::new(Stderr),
+        ..EngineIo::process()
+    }
+}
+
+/// Stdout through [`write_stdout`]
+struct Stdout;
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Err(error) = io::stdout().lock().write_all(buf) {
+            stdout_failed(error);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        flush_stdout();
+        Ok(())
+    }
+}
+
+/// Stderr through [`write_stderr`]
+struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if io::stderr().write_all(buf).is_err() {
+            process::exit(1);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Flush stdout and exit with `status`
+pub fn exit(status: i32) -> ! {
+    flush_stdout();
+    process::exit(status)
+}
+
+fn stdout_failed(error: io::Error) -> ! {
+    if error.kind() == ErrorKind::BrokenPipe {
+        // Whoever was reading has all the output they wanted
+        process::exit(0);
+    }
+    write_stderr(format_args!("v8: cannot write to stdout: {}\n", error));
+    process::exit(1)
+}
@@ -5,7 +5,14 @@
 
 use std::fmt;
 use crate::error::Span;
-use super::nodes::{Program, Stmt, Expr, Property, PropertyKey};
+use crate::lexer::EscapeValidator;
+use super::literals::Literal;
+use super::nodes::{Program, Stmt, VarKind, Expr, Property, PropertyDefinition, PropertyKey, ClassBody, ClassMethod};
+
+/// Parameter names, escaped and comma separated
+fn param_list(params: &[(String, Span)]) -> String {
+    params.iter().map(|(p, _)| EscapeValidator::escape_identifier(p)).collect::<Vec<_>>().join(", ")
+}
 
 impl Stmt {
     pub fn span(&self) -> Span {
@@ -13,15 +20,40 @@ impl Stmt {
             Stmt::Expression(expr) => expr.span(),
             Stmt::VarDecl { span, .. } => *span,
             Stmt::FunctionDecl { span, .. } => *span,
+            Stmt::ClassDecl { span, .. } => *span,
             Stmt::If { span, .. } => *span,
             Stmt::While { span, .. } => *span,
             Stmt::For { span, .. } => *span,
+            Stmt::ForOf { span, .. } => *span,
             Stmt::Block { span, .. } => *span,
             Stmt::Return { span, .. } => *span,
             Stmt::Break { span } => *span,
             Stmt::Continue { span } => *span,
+            Stmt::Debugger { span } => *span,
+            Stmt::Empty { span } => *span,
+        }
+    }
+    
+    /// The text of a directive, a string literal statement such as
+    /// `"use strict"` in the prologue of a program or function body
+    pub fn directive(&self) -> Option<&str> {
+        match self {
+            Stmt::Expression(Expr::Literal(Literal::String(text))) => Some(text),
+            _ => None,
         }
     }
+    
+    pub fn is_use_strict_directive(&self) -> bool {
+        self.directive() == Some("use strict")
+    }
+    
+    /// Whether the directive prologue of a program or function body, its
+    /// leading directives, holds a `"use strict"`
+    pub fn has_use_strict_directive(statements: &[Stmt]) -> bool {
+        statements.iter()
+            .take_while(|statement| statement.directive().is_some())
+            .any(Stmt::is_use_strict_directive)
+    }
 }
 
 impl Expr {
@@ -38,7 +70,12 @@ impl Expr {
             Expr::Object { span, .. } => *span,
             Expr::Array { span, .. } => *span,
             Expr::Function { span, .. } => *span,
+            Expr::Arrow { span, .. } => *span,
             Expr::This { span } => *span,
+            Expr::Yield { span, .. } => *span,
+            Expr::Await { span, .. } => *span,
+            Expr::New { span, .. } => *span,
+            Expr::Class { span, .. } => *span,
         }
     }
 }
@@ -61,15 +98,16 @@ impl fmt::Display for Stmt {
             Stmt::Expression(expr) => write!(f, "ExpressionStatement({})", expr),
             Stmt::VarDecl { name, init, .. } => {
                 if let Some(init) = init {
-                    write!(f, "VarDeclaration({} = {})", name, init)
+                    write!(f, "VarDeclaration({} = {})", EscapeValidator::escape_identifier(name), init)
                 } else {
-                    write!(f, "VarDeclaration({})", name)
+                    write!(f, "VarDeclaration({})", EscapeValidator::escape_identifier(name))
                 }
             }
             Stmt::FunctionDecl { name, params, body, .. } => {
                 write!(f, "FunctionDeclaration({} ({}) {{ {} statements }})", 
-                       name, params.join(", "), body.len())
+                       EscapeValidator::escape_identifier(name), param_list(params), body.len())
             }
+            Stmt::ClassDecl { name, body, .. } => write!(f, "ClassDeclaration({} {})", EscapeValidator::escape_identifier(name), body),
             Stmt::If { test, then_stmt, else_stmt, .. } => {
                 if let Some(else_stmt) = else_stmt {
                     write!(f, "IfStatement({} then {} else {})", test, then_stmt, else_stmt)
@@ -86,6 +124,9 @@ impl fmt::Display for Stmt {
                 let update_str = if let Some(update) = update { format!("{}", update) } else { "".to_string() };
                 write!(f, "ForStatement({};{};{} do {})", init_str, test_str, update_str, body)
             }
+            Stmt::ForOf { left, right, body, .. } => {
+                write!(f, "ForOfStatement({} of {} do {})", left, right, body)
+            }
             Stmt::Block { statements, .. } => {
                 write!(f, "BlockStatement({{ {} statements }})", statements.len())
             }
@@ -98,6 +139,18 @@ impl fmt::Display for Stmt {
             }
             Stmt::Break { .. } => write!(f, "BreakStatement"),
             Stmt::Continue { .. } => write!(f, "ContinueStatement"),
+            Stmt::Debugger { .. } => write!(f, "DebuggerStatement"),
+            Stmt::Empty { .. } => write!(f, "EmptyStatement"),
+        }
+    }
+}
+
+impl fmt::Display for VarKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarKind::Var => write!(f, "var"),
+            VarKind::Let => write!(f, "let"),
+            VarKind::Const => write!(f, "const"),
         }
     }
 }
@@ -106,7 +159,7 @@ impl fmt::Display for Expr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Expr::Literal(lit) => write!(f, "{}", lit),
-            Expr::Identifier { name, .. } => write!(f, "{}", name),
+            Expr::Identifier { name, .. } => write!(f, "{}", EscapeValidator::escape_identifier(name)),
             Expr::Binary { op, left, right, .. } => {
                 write!(f, "({} {} {})", left, op, right)
             }
@@ -116,18 +169,25 @@ impl fmt::Display for Expr {
             Expr::PostfixUnary { op, operand, .. } => {
                 write!(f, "({}{})", operand, op)
             }
-            Expr::Assignment { left, right, .. } => {
-                write!(f, "({} = {})", left, right)
-            }
+            Expr::Assignment { op, left, right, .. } => match op {
+                Some(op) => write!(f, "({} {}= {})", left, op, right),
+                None => write!(f, "({} = {})", left, right),
+            },
             Expr::Call { callee, args, .. } => {
                 write!(f, "{}({})", callee, 
                        args.iter().map(|a| format!("{}", a)).collect::<Vec<_>>().join(", "))
             }
             Expr::Member { object, property, computed, .. } => {
-                if *computed {
-                    write!(f, "{}[{}]", object, property)
+                // Objects that would read differently before `.` or `[`
+                // are parenthesized
+                if matches!(**object, Expr::Literal(Literal::Number(_)) | Expr::Object { .. } | Expr::Function { .. } | Expr::Arrow { .. } | Expr::Class { .. }) {
+                    write!(f, "({})", object)?;
                 } else {
-                    write!(f, "{}.{}", object, property)
+                    write!(f, "{}", object)?;
+                }
+                match &**property {
+                    Expr::Identifier { name, .. } if !*computed => write!(f, ".{}", EscapeValidator::escape_identifier(name)),
+                    property => write!(f, "[{}]", property),
                 }
             }
             Expr::Object { properties, .. } => {
@@ -140,12 +200,55 @@ impl fmt::Display for Expr {
                            if let Some(e) = e { format!("{}", e) } else { "".to_string() }
                        ).collect::<Vec<_>>().join(", "))
             }
-            Expr::Function { name, params, body, .. } => {
-                let name_str = if let Some(name) = name { format!(" {}", name) } else { "".to_string() };
-                write!(f, "function{}({}) {{ {} statements }}", 
-                       name_str, params.join(", "), body.len())
+            Expr::Function { name, params, body, is_generator, is_async, .. } => {
+                let name_str = if let Some(name) = name { format!(" {}", EscapeValidator::escape_identifier(name)) } else { "".to_string() };
+                let prefix = if *is_async { "async " } else { "" };
+                let star = if *is_generator { "*" } else { "" };
+                write!(f, "{}function{}{}({}) {{ {} statements }}", 
+                       prefix, star, name_str, param_list(params), body.len())
+            }
+            Expr::Arrow { params, body, is_async, .. } => {
+                let prefix = if *is_async { "async " } else { "" };
+                write!(f, "{}({}) => {{ {} statements }}", prefix, param_list(params), body.len())
             }
             Expr::This { .. } => write!(f, "this"),
+            Expr::Yield { argument: Some(argument), .. } => write!(f, "(yield {})", argument),
+            Expr::Yield { argument: None, .. } => write!(f, "(yield)"),
+            Expr::Await { argument, .. } => write!(f, "(await {})", argument),
+            Expr::New { callee, args, .. } => {
+                write!(f, "new {}({})", callee, 
+                       args.iter().map(|a| format!("{}", a)).collect::<Vec<_>>().join(", "))
+            }
+            Expr::Class { name, body, .. } => {
+                let name_str = if let Some(name) = name { format!(" {}", EscapeValidator::escape_identifier(name)) } else { "".to_string() };
+                write!(f, "class{} {}", name_str, body)
+            }
+        }
+    }
+}
+
+impl fmt::Display for ClassBody {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let members: Vec<String> = self.constructor.iter().chain(&self.methods)
+            .map(|method| method.to_string())
+            .collect();
+        write!(f, "{{ {} }}", members.join("; "))
+    }
+}
+
+impl fmt::Display for ClassMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let static_str = if self.is_static { "static " } else { "" };
+        write!(f, "{}{}({}) {{ {} statements }}", 
+               static_str, EscapeValidator::escape_identifier(&self.name), param_list(&self.params), self.body.len())
+    }
+}
+
+impl fmt::Display for PropertyDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PropertyDefinition::Property(property) => write!(f, "{}", property),
+            PropertyDefinition::SpreadProperty { argument, .. } => write!(f, "...{}", argument),
         }
     }
 }
@@ -159,8 +262,8 @@ impl fmt::Display for Property {
 impl fmt::Display for PropertyKey {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            PropertyKey::Identifier(name) => write!(f, "{}", name),
-            PropertyKey::String(s) => write!(f, "\"{}\"", s),
+            PropertyKey::Identifier(name) => write!(f, "{}", EscapeValidator::escape_identifier(name)),
+            PropertyKey::String(s) => write!(f, "\"{}\"", EscapeValidator::escape_string(s)),
             PropertyKey::Number(n) => write!(f, "{}", n),
             PropertyKey::Computed(expr) => write!(f, "[{}]", expr),
         }
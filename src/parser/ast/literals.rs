@@ -4,6 +4,8 @@
 
 use std::fmt;
 
+use crate::lexer::EscapeValidator;
+
 /// Literal values
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
@@ -12,16 +14,18 @@ pub enum Literal {
     Boolean(bool),
     Null,
     Undefined,
+    RegExp { pattern: String, flags: String },
 }
 
 impl fmt::Display for Literal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Literal::Number(n) => write!(f, "{}", n),
-            Literal::String(s) => write!(f, "\"{}\"", s),
+            Literal::String(s) => write!(f, "\"{}\"", EscapeValidator::escape_string(s)),
             Literal::Boolean(b) => write!(f, "{}", b),
             Literal::Null => write!(f, "null"),
             Literal::Undefined => write!(f, "undefined"),
+            Literal::RegExp { pattern, flags } => write!(f, "/{}/{}", pattern, flags),
         }
     }
 }
\ No newline at end of file
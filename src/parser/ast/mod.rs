@@ -1,20 +1,23 @@
 //! Abstract Syntax Tree definitions for JavaScript
 //! 
 //! This module provides a decomposed AST structure organized into logical components:
-//! - `nodes`: Core AST node definitions (Program, Stmt, Expr, Property, PropertyKey)
+//! - `nodes`: Core AST node definitions (Program, Stmt, Expr, Property, PropertyDefinition, PropertyKey)
 //! - `literals`: Literal value types (Literal enum)
 //! - `operators`: JavaScript operators (BinaryOp, UnaryOp, PostfixUnaryOp)
 //! - `impls`: Method implementations (span access, Display formatting)
 //! - `pretty`: Pretty printing functionality (PrettyPrint trait)
+//! - `validity`: Invariant checks for parsed or rewritten trees (`validate`)
 
 pub mod nodes;
 pub mod literals;
 pub mod operators;
 pub mod impls;
 pub mod pretty;
+pub mod validity;
 
 // Re-export all public types for backward compatibility
-pub use nodes::{Program, Stmt, Expr, Property, PropertyKey};
+pub use nodes::{Program, Stmt, VarKind, Expr, Property, PropertyDefinition, PropertyKey, ClassBody, ClassMethod};
 pub use literals::Literal;
 pub use operators::{BinaryOp, UnaryOp, PostfixUnaryOp};
-pub use pretty::PrettyPrint;
\ No newline at end of file
+pub use pretty::PrettyPrint;
+pub use validity::{validate, validate_source, Violation, ViolationKind};
\ No newline at end of file
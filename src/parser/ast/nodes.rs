@@ -18,34 +18,56 @@ pub struct Program {
 pub enum Stmt {
     Expression(Expr),
     VarDecl {
+        kind: VarKind,
         name: String,
         init: Option<Expr>,
         span: Span,
     },
     FunctionDecl {
         name: String,
-        params: Vec<String>,
+        /// Parameter names, each with its span
+        params: Vec<(String, Span)>,
         body: Vec<Stmt>,
+        /// `function*`
+        is_generator: bool,
+        /// `async function`
+        is_async: bool,
+        span: Span,
+    },
+    ClassDecl {
+        name: String,
+        body: ClassBody,
         span: Span,
     },
     If {
         test: Expr,
+        /// Where `test` is; a literal has no span of its own
+        test_span: Span,
         then_stmt: Box<Stmt>,
         else_stmt: Option<Box<Stmt>>,
         span: Span,
     },
     While {
         test: Expr,
+        test_span: Span,
         body: Box<Stmt>,
         span: Span,
     },
     For {
         init: Option<Box<Stmt>>,
         test: Option<Expr>,
+        /// Where `test` is, when there is one
+        test_span: Option<Span>,
         update: Option<Expr>,
         body: Box<Stmt>,
         span: Span,
     },
+    ForOf {
+        left: Box<Stmt>,
+        right: Expr,
+        body: Box<Stmt>,
+        span: Span,
+    },
     Block {
         statements: Vec<Stmt>,
         span: Span,
@@ -60,6 +82,21 @@ pub enum Stmt {
     Continue {
         span: Span,
     },
+    Debugger {
+        span: Span,
+    },
+    Empty {
+        span: Span,
+    },
+}
+
+/// The keyword a variable was declared with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VarKind {
+    Var,
+    Let,
+    /// Assigned once, by its declaration
+    Const,
 }
 
 /// JavaScript expression
@@ -87,6 +124,7 @@ pub enum Expr {
         span: Span,
     },
     Assignment {
+        op: Option<BinaryOp>, // Some(Add) for a += b
         left: Box<Expr>,
         right: Box<Expr>,
         span: Span,
@@ -103,7 +141,7 @@ pub enum Expr {
         span: Span,
     },
     Object {
-        properties: Vec<Property>,
+        properties: Vec<PropertyDefinition>,
         span: Span,
     },
     Array {
@@ -112,13 +150,81 @@ pub enum Expr {
     },
     Function {
         name: Option<String>,
-        params: Vec<String>,
+        /// Parameter names, each with its span
+        params: Vec<(String, Span)>,
+        body: Vec<Stmt>,
+        /// `function*`
+        is_generator: bool,
+        /// `async function`
+        is_async: bool,
+        span: Span,
+    },
+    /// `(params) => body`; an expression body is a single `return`
+    Arrow {
+        /// Parameter names, each with its span
+        params: Vec<(String, Span)>,
         body: Vec<Stmt>,
+        /// `async (params) => body`
+        is_async: bool,
+        span: Span,
+    },
+    /// `yield argument` inside a generator
+    Yield {
+        argument: Option<Box<Expr>>,
+        span: Span,
+    },
+    /// `await argument` inside an async function
+    Await {
+        argument: Box<Expr>,
         span: Span,
     },
     This {
         span: Span,
     },
+    /// `new callee(args)`; the argument list may be omitted
+    New {
+        callee: Box<Expr>,
+        args: Vec<Expr>,
+        span: Span,
+    },
+    Class {
+        name: Option<String>,
+        body: ClassBody,
+        span: Span,
+    },
+}
+
+/// Members of a class declaration or expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassBody {
+    /// The `constructor(...) { ... }` method, if the class has one
+    pub constructor: Option<ClassMethod>,
+    /// All other methods, in source order
+    pub methods: Vec<ClassMethod>,
+}
+
+/// Method in a class body
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClassMethod {
+    pub name: String,
+    /// Parameter names, each with its span
+    pub params: Vec<(String, Span)>,
+    pub body: Vec<Stmt>,
+    /// `static name() {}` lives on the class itself, not its prototype
+    pub is_static: bool,
+    pub span: Span,
+}
+
+/// Entry of an object literal
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyDefinition {
+    /// `key: value`
+    Property(Property),
+    /// `...argument`: copies the argument's own enumerable properties
+    SpreadProperty {
+        argument: Expr,
+        span: Span,
+    },
 }
 
 /// Object property
@@ -3,8 +3,14 @@
 //! This module provides detailed tree-like formatting for AST nodes,
 //! useful for debugging and visualization.
 
-use super::nodes::{Program, Stmt, Expr, Property, PropertyKey};
+use super::nodes::{Program, Stmt, Expr, Property, PropertyDefinition, PropertyKey, ClassBody, ClassMethod};
 use super::literals::Literal;
+use crate::lexer::EscapeValidator;
+
+/// `s` in double quotes, escaped
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", EscapeValidator::escape_string(s))
+}
 
 // PrettyPrint trait for detailed tree-like AST representation
 pub trait PrettyPrint {
@@ -31,9 +37,10 @@ impl PrettyPrint for Stmt {
                         expr.pretty_print(indent + 1),
                         "  ".repeat(indent))
             }
-            Stmt::VarDecl { name, init, span } => {
-                let mut result = format!("VarDeclaration {{\n{}name: \"{}\",\n", 
-                                        "  ".repeat(indent + 1), name);
+            Stmt::VarDecl { kind, name, init, span } => {
+                let mut result = format!("VarDeclaration {{\n{}kind: {},\n{}name: {},\n", 
+                                        "  ".repeat(indent + 1), kind,
+                                        "  ".repeat(indent + 1), quoted(name));
                 if let Some(init) = init {
                     result.push_str(&format!("{}init: {},\n", 
                                            "  ".repeat(indent + 1), 
@@ -45,11 +52,17 @@ impl PrettyPrint for Stmt {
                                "  ".repeat(indent + 1), span, "  ".repeat(indent)));
                 result
             }
-            Stmt::FunctionDecl { name, params, body, span } => {
-                let mut result = format!("FunctionDeclaration {{\n{}name: \"{}\",\n{}params: [{}],\n{}body: [\n", 
-                                        "  ".repeat(indent + 1), name,
-                                        "  ".repeat(indent + 1), params.iter().map(|p| format!("\"{}\"", p)).collect::<Vec<_>>().join(", "),
-                                        "  ".repeat(indent + 1));
+            Stmt::FunctionDecl { name, params, body, is_generator, is_async, span } => {
+                let mut result = format!("FunctionDeclaration {{\n{}name: {},\n", "  ".repeat(indent + 1), quoted(name));
+                if *is_generator {
+                    result.push_str(&format!("{}generator: true,\n", "  ".repeat(indent + 1)));
+                }
+                if *is_async {
+                    result.push_str(&format!("{}async: true,\n", "  ".repeat(indent + 1)));
+                }
+                result.push_str(&format!("{}params: [{}],\n{}body: [\n", 
+                                        "  ".repeat(indent + 1), params.iter().map(|(p, _)| quoted(p)).collect::<Vec<_>>().join(", "),
+                                        "  ".repeat(indent + 1)));
                 for stmt in body {
                     result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), stmt.pretty_print(indent + 2)));
                 }
@@ -57,7 +70,13 @@ impl PrettyPrint for Stmt {
                                "  ".repeat(indent + 1), "  ".repeat(indent + 1), span, "  ".repeat(indent)));
                 result
             }
-            Stmt::If { test, then_stmt, else_stmt, span } => {
+            Stmt::ClassDecl { name, body, span } => {
+                format!("ClassDeclaration {{\n{}name: {},\n{}body: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), quoted(name),
+                        "  ".repeat(indent + 1), body.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
+            Stmt::If { test, then_stmt, else_stmt, span, .. } => {
                 let mut result = format!("IfStatement {{\n{}test: {},\n{}then_stmt: {},\n", 
                                         "  ".repeat(indent + 1), test.pretty_print(indent + 1),
                                         "  ".repeat(indent + 1), then_stmt.pretty_print(indent + 1));
@@ -71,14 +90,14 @@ impl PrettyPrint for Stmt {
                                "  ".repeat(indent + 1), span, "  ".repeat(indent)));
                 result
             }
-            Stmt::While { test, body, span } => {
+            Stmt::While { test, body, span, .. } => {
                 format!("WhileStatement {{\n{}test: {},\n{}body: {},\n{}span: {:?}\n{}}}", 
                         "  ".repeat(indent + 1), test.pretty_print(indent + 1),
                         "  ".repeat(indent + 1), body.pretty_print(indent + 1),
                         "  ".repeat(indent + 1), span, "  ".repeat(indent))
             }
-            Stmt::For { init, test, update, body, span } => {
-                let mut result = format!("ForStatement {{\n", );
+            Stmt::For { init, test, update, body, span, .. } => {
+                let mut result = "ForStatement {\n".to_string();
                 if let Some(init) = init {
                     result.push_str(&format!("{}init: Some({}),\n", 
                                            "  ".repeat(indent + 1), init.pretty_print(indent + 1)));
@@ -102,6 +121,13 @@ impl PrettyPrint for Stmt {
                                "  ".repeat(indent + 1), span, "  ".repeat(indent)));
                 result
             }
+            Stmt::ForOf { left, right, body, span } => {
+                format!("ForOfStatement {{\n{}left: {},\n{}right: {},\n{}body: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), left.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), right.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), body.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
             Stmt::Block { statements, span } => {
                 let mut result = format!("BlockStatement {{\n{}statements: [\n", "  ".repeat(indent + 1));
                 for stmt in statements {
@@ -112,7 +138,7 @@ impl PrettyPrint for Stmt {
                 result
             }
             Stmt::Return { value, span } => {
-                let mut result = format!("ReturnStatement {{\n", );
+                let mut result = "ReturnStatement {\n".to_string();
                 if let Some(value) = value {
                     result.push_str(&format!("{}value: Some({}),\n", 
                                            "  ".repeat(indent + 1), value.pretty_print(indent + 1)));
@@ -131,6 +157,14 @@ impl PrettyPrint for Stmt {
                 format!("ContinueStatement {{\n{}span: {:?}\n{}}}", 
                         "  ".repeat(indent + 1), span, "  ".repeat(indent))
             }
+            Stmt::Debugger { span } => {
+                format!("DebuggerStatement {{\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
+            Stmt::Empty { span } => {
+                format!("EmptyStatement {{\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
         }
     }
 }
@@ -142,8 +176,8 @@ impl PrettyPrint for Expr {
                 format!("Literal({})", lit.pretty_print(indent))
             }
             Expr::Identifier { name, span } => {
-                format!("Identifier {{\n{}name: \"{}\",\n{}span: {:?}\n{}}}", 
-                        "  ".repeat(indent + 1), name, "  ".repeat(indent + 1), span, "  ".repeat(indent))
+                format!("Identifier {{\n{}name: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), quoted(name), "  ".repeat(indent + 1), span, "  ".repeat(indent))
             }
             Expr::Binary { op, left, right, span } => {
                 format!("BinaryExpression {{\n{}op: {:?},\n{}left: {},\n{}right: {},\n{}span: {:?}\n{}}}", 
@@ -164,8 +198,9 @@ impl PrettyPrint for Expr {
                         "  ".repeat(indent + 1), operand.pretty_print(indent + 1),
                         "  ".repeat(indent + 1), span, "  ".repeat(indent))
             }
-            Expr::Assignment { left, right, span } => {
-                format!("AssignmentExpression {{\n{}left: {},\n{}right: {},\n{}span: {:?}\n{}}}", 
+            Expr::Assignment { op, left, right, span } => {
+                format!("AssignmentExpression {{\n{}op: {:?},\n{}left: {},\n{}right: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), op,
                         "  ".repeat(indent + 1), left.pretty_print(indent + 1),
                         "  ".repeat(indent + 1), right.pretty_print(indent + 1),
                         "  ".repeat(indent + 1), span, "  ".repeat(indent))
@@ -210,15 +245,36 @@ impl PrettyPrint for Expr {
                                "  ".repeat(indent + 1), "  ".repeat(indent + 1), span, "  ".repeat(indent)));
                 result
             }
-            Expr::Function { name, params, body, span } => {
-                let mut result = format!("FunctionExpression {{\n", );
+            Expr::Function { name, params, body, is_generator, is_async, span } => {
+                let mut result = "FunctionExpression {\n".to_string();
                 if let Some(name) = name {
-                    result.push_str(&format!("{}name: Some(\"{}\"),\n", "  ".repeat(indent + 1), name));
+                    result.push_str(&format!("{}name: Some({}),\n", "  ".repeat(indent + 1), quoted(name)));
                 } else {
                     result.push_str(&format!("{}name: None,\n", "  ".repeat(indent + 1)));
                 }
+                if *is_generator {
+                    result.push_str(&format!("{}generator: true,\n", "  ".repeat(indent + 1)));
+                }
+                if *is_async {
+                    result.push_str(&format!("{}async: true,\n", "  ".repeat(indent + 1)));
+                }
                 result.push_str(&format!("{}params: [{}],\n{}body: [\n", 
-                                        "  ".repeat(indent + 1), params.iter().map(|p| format!("\"{}\"", p)).collect::<Vec<_>>().join(", "),
+                                        "  ".repeat(indent + 1), params.iter().map(|(p, _)| quoted(p)).collect::<Vec<_>>().join(", "),
+                                        "  ".repeat(indent + 1)));
+                for stmt in body {
+                    result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), stmt.pretty_print(indent + 2)));
+                }
+                result.push_str(&format!("{}],\n{}span: {:?}\n{}}}", 
+                               "  ".repeat(indent + 1), "  ".repeat(indent + 1), span, "  ".repeat(indent)));
+                result
+            }
+            Expr::Arrow { params, body, is_async, span } => {
+                let mut result = "ArrowFunctionExpression {\n".to_string();
+                if *is_async {
+                    result.push_str(&format!("{}async: true,\n", "  ".repeat(indent + 1)));
+                }
+                result.push_str(&format!("{}params: [{}],\n{}body: [\n", 
+                                        "  ".repeat(indent + 1), params.iter().map(|(p, _)| quoted(p)).collect::<Vec<_>>().join(", "),
                                         "  ".repeat(indent + 1)));
                 for stmt in body {
                     result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), stmt.pretty_print(indent + 2)));
@@ -231,6 +287,41 @@ impl PrettyPrint for Expr {
                 format!("ThisExpression {{\n{}span: {:?}\n{}}}", 
                         "  ".repeat(indent + 1), span, "  ".repeat(indent))
             }
+            Expr::Yield { argument, span } => {
+                let argument = match argument {
+                    Some(argument) => format!("Some({})", argument.pretty_print(indent + 1)),
+                    None => "None".to_string(),
+                };
+                format!("YieldExpression {{\n{}argument: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), argument,
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
+            Expr::Await { argument, span } => {
+                format!("AwaitExpression {{\n{}argument: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), argument.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
+            Expr::New { callee, args, span } => {
+                let mut result = format!("NewExpression {{\n{}callee: {},\n{}args: [\n", 
+                                        "  ".repeat(indent + 1), callee.pretty_print(indent + 1),
+                                        "  ".repeat(indent + 1));
+                for arg in args {
+                    result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), arg.pretty_print(indent + 2)));
+                }
+                result.push_str(&format!("{}],\n{}span: {:?}\n{}}}", 
+                               "  ".repeat(indent + 1), "  ".repeat(indent + 1), span, "  ".repeat(indent)));
+                result
+            }
+            Expr::Class { name, body, span } => {
+                let name = match name {
+                    Some(name) => format!("Some({})", quoted(name)),
+                    None => "None".to_string(),
+                };
+                format!("ClassExpression {{\n{}name: {},\n{}body: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), name,
+                        "  ".repeat(indent + 1), body.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
         }
     }
 }
@@ -239,10 +330,60 @@ impl PrettyPrint for Literal {
     fn pretty_print(&self, _indent: usize) -> String {
         match self {
             Literal::Number(n) => format!("Number({})", n),
-            Literal::String(s) => format!("String(\"{}\")", s),
+            Literal::String(s) => format!("String({})", quoted(s)),
             Literal::Boolean(b) => format!("Boolean({})", b),
             Literal::Null => "Null".to_string(),
             Literal::Undefined => "Undefined".to_string(),
+            Literal::RegExp { pattern, flags } => format!("RegExp(/{}/{})", pattern, flags),
+        }
+    }
+}
+
+impl PrettyPrint for ClassBody {
+    fn pretty_print(&self, indent: usize) -> String {
+        let mut result = "ClassBody {\n".to_string();
+        if let Some(constructor) = &self.constructor {
+            result.push_str(&format!("{}constructor: Some({}),\n", 
+                                   "  ".repeat(indent + 1), constructor.pretty_print(indent + 1)));
+        } else {
+            result.push_str(&format!("{}constructor: None,\n", "  ".repeat(indent + 1)));
+        }
+        result.push_str(&format!("{}methods: [\n", "  ".repeat(indent + 1)));
+        for method in &self.methods {
+            result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), method.pretty_print(indent + 2)));
+        }
+        result.push_str(&format!("{}]\n{}}}", "  ".repeat(indent + 1), "  ".repeat(indent)));
+        result
+    }
+}
+
+impl PrettyPrint for ClassMethod {
+    fn pretty_print(&self, indent: usize) -> String {
+        let mut result = format!("ClassMethod {{\n{}name: {},\n", "  ".repeat(indent + 1), quoted(&self.name));
+        if self.is_static {
+            result.push_str(&format!("{}static: true,\n", "  ".repeat(indent + 1)));
+        }
+        result.push_str(&format!("{}params: [{}],\n{}body: [\n", 
+                                "  ".repeat(indent + 1), self.params.iter().map(|(p, _)| quoted(p)).collect::<Vec<_>>().join(", "),
+                                "  ".repeat(indent + 1)));
+        for stmt in &self.body {
+            result.push_str(&format!("{}{},\n", "  ".repeat(indent + 2), stmt.pretty_print(indent + 2)));
+        }
+        result.push_str(&format!("{}],\n{}span: {:?}\n{}}}", 
+                       "  ".repeat(indent + 1), "  ".repeat(indent + 1), self.span, "  ".repeat(indent)));
+        result
+    }
+}
+
+impl PrettyPrint for PropertyDefinition {
+    fn pretty_print(&self, indent: usize) -> String {
+        match self {
+            PropertyDefinition::Property(property) => property.pretty_print(indent),
+            PropertyDefinition::SpreadProperty { argument, span } => {
+                format!("SpreadProperty {{\n{}argument: {},\n{}span: {:?}\n{}}}", 
+                        "  ".repeat(indent + 1), argument.pretty_print(indent + 1),
+                        "  ".repeat(indent + 1), span, "  ".repeat(indent))
+            }
         }
     }
 }
@@ -259,8 +400,8 @@ impl PrettyPrint for Property {
 impl PrettyPrint for PropertyKey {
     fn pretty_print(&self, _indent: usize) -> String {
         match self {
-            PropertyKey::Identifier(name) => format!("Identifier(\"{}\")", name),
-            PropertyKey::String(s) => format!("String(\"{}\")", s),
+            PropertyKey::Identifier(name) => format!("Identifier({})", quoted(name)),
+            PropertyKey::String(s) => format!("String({})", quoted(s)),
             PropertyKey::Number(n) => format!("Number({})", n),
             PropertyKey::Computed(expr) => format!("Computed({})", expr.pretty_print(0)),
         }
@@ -0,0 +1,380 @@
+//! AST validity checks
+//!
+//! `validate` walks a program and reports every place it breaks an
+//! invariant the compiler relies on. The parser must only ever produce
+//! valid trees, which the parser fuzzer checks; anything that builds or
+//! rewrites an AST can run the same check on its output.
+//!
+//! Most node spans cover only the node's first token or its operator, so
+//! nesting is checked as far as that allows: a statement's parts come
+//! after its keyword, a function's, class's or method's parts lie within
+//! its span (the only spans that cover the whole node), and a statement
+//! list runs in source order. Literals carry no span and are skipped.
+
+use std::fmt;
+use crate::error::Span;
+use super::nodes::{ClassBody, ClassMethod, Expr, Program, PropertyDefinition, PropertyKey, Stmt, VarKind};
+
+/// A broken invariant, at the node that breaks it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub kind: ViolationKind,
+    pub span: Span,
+}
+
+/// What a `Violation` breaks
+#[derive(Debug, Clone, PartialEq)]
+pub enum ViolationKind {
+    /// A span that ends before it starts
+    InvertedSpan,
+    /// A span past the end of the source (`validate_source` only)
+    OutOfBounds { source_len: usize },
+    /// A node outside the span of the node containing it
+    Escapes { parent: Span },
+    /// A statement that does not follow the one before it in the source
+    OutOfOrder { previous: Span },
+    /// An identifier, binding, function, class or parameter named by the
+    /// empty string
+    EmptyName,
+    /// A `for` or for-of head the parser can't have made
+    MalformedFor(&'static str),
+    /// A `const` declaration without an initializer outside a for-of head
+    ConstWithoutInitializer,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let span = self.span;
+        match &self.kind {
+            ViolationKind::InvertedSpan => write!(f, "span {}..{} ends before it starts", span.start, span.end),
+            ViolationKind::OutOfBounds { source_len } => {
+                write!(f, "span {}..{} is past the end of the {}-byte source", span.start, span.end, source_len)
+            }
+            ViolationKind::Escapes { parent } => write!(
+                f,
+                "span {}..{} is outside its parent's span {}..{}",
+                span.start, span.end, parent.start, parent.end,
+            ),
+            ViolationKind::OutOfOrder { previous } => write!(
+                f,
+                "statement at {} does not follow the statement at {}..{}",
+                span.start, previous.start, previous.end,
+            ),
+            ViolationKind::EmptyName => write!(f, "empty name at {}", span.start),
+            ViolationKind::MalformedFor(problem) => write!(f, "malformed for at {}: {}", span.start, problem),
+            ViolationKind::ConstWithoutInitializer => write!(f, "const without an initializer at {}", span.start),
+        }
+    }
+}
+
+/// Every invariant `program` breaks, in tree order; empty when it is valid
+pub fn validate(program: &Program) -> Vec<Violation> {
+    let mut validator = Validator { source_len: None, violations: Vec::new() };
+    validator.statements(&program.statements, Region::ALL);
+    validator.violations
+}
+
+/// `validate`, also checking that every span lies within `source`, the
+/// text `program` was parsed from
+pub fn validate_source(program: &Program, source: &str) -> Vec<Violation> {
+    let mut validator = Validator { source_len: Some(source.len()), violations: Vec::new() };
+    validator.statements(&program.statements, Region::ALL);
+    validator.violations
+}
+
+/// Where the nodes inside another must be: from `start`, and up to `end`
+/// when the outer node's span covers all of it
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    start: usize,
+    end: Option<usize>,
+    /// The outer node's span, for reporting
+    parent: Span,
+}
+
+impl Region {
+    const ALL: Region = Region { start: 0, end: None, parent: Span { start: 0, end: 0, line: 1, column: 1 } };
+
+    /// Nodes after the first token of a node spanning `span`
+    fn after(span: Span) -> Region {
+        Region { start: span.start, end: None, parent: span }
+    }
+
+    /// Nodes within a node spanning `span`
+    fn within(span: Span) -> Region {
+        Region { start: span.start, end: Some(span.end), parent: span }
+    }
+
+    fn contains(&self, span: Span) -> bool {
+        span.start >= self.start && self.end.is_none_or(|end| span.end <= end)
+    }
+}
+
+struct Validator {
+    source_len: Option<usize>,
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    fn report(&mut self, kind: ViolationKind, span: Span) {
+        self.violations.push(Violation { kind, span });
+    }
+
+    /// Check `span` on its own and against the region it must lie in
+    fn span(&mut self, span: Span, region: Region) {
+        if span.start > span.end {
+            self.report(ViolationKind::InvertedSpan, span);
+            return;
+        }
+        if let Some(source_len) = self.source_len.filter(|&len| span.end > len) {
+            self.report(ViolationKind::OutOfBounds { source_len }, span);
+        }
+        if !region.contains(span) {
+            self.report(ViolationKind::Escapes { parent: region.parent }, span);
+        }
+    }
+
+    fn name(&mut self, name: &str, span: Span) {
+        if name.is_empty() {
+            self.report(ViolationKind::EmptyName, span);
+        }
+    }
+
+    /// The parameters of a function spanning `function`
+    fn params(&mut self, params: &[(String, Span)], function: Span) {
+        for (param, span) in params {
+            self.span(*span, Region::within(function));
+            self.name(param, *span);
+        }
+    }
+
+    /// A statement list: each statement in `region`, and after the one
+    /// before it
+    fn statements(&mut self, statements: &[Stmt], region: Region) {
+        let mut previous: Option<Span> = None;
+        for stmt in statements {
+            self.statement(stmt, region);
+            let Some(span) = stmt_span(stmt) else { continue };
+            if let Some(previous) = previous.filter(|previous| span.start < previous.end || span.start <= previous.start) {
+                self.report(ViolationKind::OutOfOrder { previous }, span);
+            }
+            previous = Some(span);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Stmt, region: Region) {
+        match stmt {
+            Stmt::Expression(expr) => self.expression(expr, region),
+            Stmt::VarDecl { kind, name, init, span } => {
+                self.span(*span, region);
+                self.name(name, *span);
+                if *kind == VarKind::Const && init.is_none() {
+                    self.report(ViolationKind::ConstWithoutInitializer, *span);
+                }
+                if let Some(init) = init {
+                    self.expression(init, Region::after(*span));
+                }
+            }
+            Stmt::FunctionDecl { name, params, body, span, .. } => {
+                self.span(*span, region);
+                self.name(name, *span);
+                self.params(params, *span);
+                self.statements(body, Region::within(*span));
+            }
+            Stmt::ClassDecl { name, body, span } => {
+                self.span(*span, region);
+                self.name(name, *span);
+                self.class_body(body, Region::within(*span));
+            }
+            Stmt::If { test, then_stmt, else_stmt, span, .. } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                self.expression(test, inner);
+                self.statement(then_stmt, inner);
+                if let Some(else_stmt) = else_stmt {
+                    self.statement(else_stmt, inner);
+                }
+            }
+            Stmt::While { test, body, span, .. } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                self.expression(test, inner);
+                self.statement(body, inner);
+            }
+            Stmt::For { init, test, update, body, span, .. } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                if let Some(init) = init {
+                    if !matches!(**init, Stmt::VarDecl { .. } | Stmt::Expression(_)) {
+                        self.report(ViolationKind::MalformedFor("the initializer is neither a declaration nor an expression"), *span);
+                    }
+                    self.statement(init, inner);
+                }
+                if let Some(test) = test {
+                    self.expression(test, inner);
+                }
+                if let Some(update) = update {
+                    self.expression(update, inner);
+                }
+                self.statement(body, inner);
+            }
+            Stmt::ForOf { left, right, body, span } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                match &**left {
+                    // `for (const x of xs)` is the one const without an initializer
+                    Stmt::VarDecl { name, init: None, span: left_span, .. } => {
+                        self.span(*left_span, inner);
+                        self.name(name, *left_span);
+                    }
+                    Stmt::Expression(target @ Expr::Identifier { .. }) => self.expression(target, inner),
+                    _ => self.report(
+                        ViolationKind::MalformedFor("the left side is neither a declaration without an initializer nor a name"),
+                        *span,
+                    ),
+                }
+                self.expression(right, inner);
+                self.statement(body, inner);
+            }
+            Stmt::Block { statements, span } => {
+                self.span(*span, region);
+                self.statements(statements, Region::after(*span));
+            }
+            Stmt::Return { value, span } => {
+                self.span(*span, region);
+                if let Some(value) = value {
+                    self.expression(value, Region::after(*span));
+                }
+            }
+            Stmt::Break { span } | Stmt::Continue { span } | Stmt::Debugger { span } | Stmt::Empty { span } => {
+                self.span(*span, region);
+            }
+        }
+    }
+
+    fn class_body(&mut self, body: &ClassBody, region: Region) {
+        for method in body.constructor.iter().chain(&body.methods) {
+            self.method(method, region);
+        }
+    }
+
+    fn method(&mut self, method: &ClassMethod, region: Region) {
+        // Method names may be any string, the empty one included
+        self.span(method.span, region);
+        self.params(&method.params, method.span);
+        self.statements(&method.body, Region::within(method.span));
+    }
+
+    /// An expression in `region`; binary operators, assignments, calls
+    /// and member accesses are spanned by a token after their first
+    /// operand, so their operands are only checked against `region`
+    fn expression(&mut self, expr: &Expr, region: Region) {
+        match expr {
+            Expr::Literal(_) => {}
+            Expr::Identifier { name, span } => {
+                self.span(*span, region);
+                self.name(name, *span);
+            }
+            Expr::Binary { left, right, span, .. } | Expr::Assignment { left, right, span, .. } => {
+                self.span(*span, region);
+                self.expression(left, region);
+                self.expression(right, region);
+            }
+            Expr::Unary { operand, span, .. } => {
+                self.span(*span, region);
+                self.expression(operand, Region::after(*span));
+            }
+            Expr::PostfixUnary { operand, span, .. } => {
+                self.span(*span, region);
+                self.expression(operand, region);
+            }
+            Expr::Call { callee, args, span } => {
+                self.span(*span, region);
+                self.expression(callee, region);
+                for arg in args {
+                    self.expression(arg, region);
+                }
+            }
+            Expr::Member { object, property, span, .. } => {
+                self.span(*span, region);
+                self.expression(object, region);
+                self.expression(property, region);
+            }
+            Expr::Object { properties, span } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                for definition in properties {
+                    match definition {
+                        PropertyDefinition::Property(property) => {
+                            self.span(property.span, inner);
+                            match &property.key {
+                                PropertyKey::Identifier(name) => self.name(name, property.span),
+                                PropertyKey::Computed(key) => self.expression(key, inner),
+                                PropertyKey::String(_) | PropertyKey::Number(_) => {}
+                            }
+                            self.expression(&property.value, inner);
+                        }
+                        PropertyDefinition::SpreadProperty { argument, span } => {
+                            self.span(*span, inner);
+                            self.expression(argument, Region::after(*span));
+                        }
+                    }
+                }
+            }
+            Expr::Array { elements, span } => {
+                self.span(*span, region);
+                for element in elements.iter().flatten() {
+                    self.expression(element, Region::after(*span));
+                }
+            }
+            Expr::Function { name, params, body, span, .. } => {
+                self.span(*span, region);
+                if let Some(name) = name {
+                    self.name(name, *span);
+                }
+                self.params(params, *span);
+                self.statements(body, Region::within(*span));
+            }
+            Expr::Arrow { params, body, span, .. } => {
+                self.span(*span, region);
+                self.params(params, *span);
+                self.statements(body, Region::within(*span));
+            }
+            Expr::Yield { argument, span } => {
+                self.span(*span, region);
+                if let Some(argument) = argument {
+                    self.expression(argument, Region::after(*span));
+                }
+            }
+            Expr::Await { argument, span } => {
+                self.span(*span, region);
+                self.expression(argument, Region::after(*span));
+            }
+            Expr::This { span } => self.span(*span, region),
+            Expr::New { callee, args, span } => {
+                self.span(*span, region);
+                let inner = Region::after(*span);
+                self.expression(callee, inner);
+                for arg in args {
+                    self.expression(arg, inner);
+                }
+            }
+            Expr::Class { name, body, span } => {
+                self.span(*span, region);
+                if let Some(name) = name {
+                    self.name(name, *span);
+                }
+                self.class_body(body, Region::within(*span));
+            }
+        }
+    }
+}
+
+/// A statement's span, if it has a real one: expression statements of a
+/// bare literal don't
+fn stmt_span(stmt: &Stmt) -> Option<Span> {
+    match stmt {
+        Stmt::Expression(Expr::Literal(_)) => None,
+        stmt => Some(stmt.span()),
+    }
+}
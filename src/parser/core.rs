@@ -4,8 +4,8 @@
 //! for the specialized parsing agents.
 
 use super::ast::*;
-use crate::error::{Error, Result, Span};
-use crate::lexer::{Token, TokenKind};
+use crate::error::{Error, ErrorCode, Result, Span};
+use crate::lexer::{Token, TokenKind, Keyword, ContextualKeyword, LexerContext, TokenSource};
 
 /// Operator precedence for Pratt parsing
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -14,13 +14,18 @@ pub enum Precedence {
     Assignment = 1,  // =
     Or = 2,         // ||
     And = 3,        // &&
-    Equality = 4,   // == !=
-    Comparison = 5, // < > <= >=
-    Term = 6,       // + -
-    Factor = 7,     // * /
-    Unary = 8,      // ! -
-    Call = 9,       // . ()
-    Primary = 10,
+    BitwiseOr = 4,  // |
+    BitwiseXor = 5, // ^
+    BitwiseAnd = 6, // &
+    Equality = 7,   // == !=
+    Comparison = 8, // < > <= >=
+    Shift = 9,      // << >> >>>
+    Term = 10,      // + -
+    Factor = 11,    // * /
+    Exponent = 12,  // ** (right-associative)
+    Unary = 13,     // ! -
+    Call = 14,      // . ()
+    Primary = 15,
 }
 
 impl Precedence {
@@ -29,11 +34,16 @@ impl Precedence {
             Precedence::None => Precedence::Assignment,
             Precedence::Assignment => Precedence::Or,
             Precedence::Or => Precedence::And,
-            Precedence::And => Precedence::Equality,
+            Precedence::And => Precedence::BitwiseOr,
+            Precedence::BitwiseOr => Precedence::BitwiseXor,
+            Precedence::BitwiseXor => Precedence::BitwiseAnd,
+            Precedence::BitwiseAnd => Precedence::Equality,
             Precedence::Equality => Precedence::Comparison,
-            Precedence::Comparison => Precedence::Term,
+            Precedence::Comparison => Precedence::Shift,
+            Precedence::Shift => Precedence::Term,
             Precedence::Term => Precedence::Factor,
-            Precedence::Factor => Precedence::Unary,
+            Precedence::Factor => Precedence::Exponent,
+            Precedence::Exponent => Precedence::Unary,
             Precedence::Unary => Precedence::Call,
             Precedence::Call => Precedence::Primary,
             Precedence::Primary => Precedence::Primary,
@@ -41,18 +51,181 @@ impl Precedence {
     }
 }
 
+/// A saved parser position, returned by `ParserCore::checkpoint`
+///
+/// Holds the token index and the flags that change how tokens are read, so
+/// rewinding is as cheap as taking it.
+#[derive(Debug, Clone, Copy)]
+pub struct ParserState {
+    current: usize,
+    context: LexerContext,
+    in_generator: bool,
+    in_async: bool,
+    strict: bool,
+}
+
 /// Core parser functionality for token management and coordination
-pub struct ParserCore {
+///
+/// Tokens are pulled from a `TokenSource` one at a time and buffered, so
+/// the current token is always available and earlier tokens stay
+/// addressable by index.
+pub struct ParserCore<'a> {
     pub tokens: Vec<Token>,
     pub current: usize,
+    /// Context passed to the source with each pull
+    pub context: LexerContext,
+    /// Whether the innermost enclosing function is a generator, so
+    /// `yield` is an expression
+    pub in_generator: bool,
+    /// Whether the innermost enclosing function is async, so `await` is
+    /// an expression
+    pub in_async: bool,
+    /// Whether the code being parsed is strict mode code: inside a class,
+    /// or after a `"use strict"` directive of the program or an enclosing
+    /// function
+    pub strict: bool,
+    source: Box<dyn TokenSource + 'a>,
+    /// First lexer error hit while pulling; it outranks any parse error
+    source_error: Option<Error>,
+    /// Statements and expressions being parsed inside one another
+    depth: usize,
+    /// Deepest nesting allowed before parsing fails, so that deeply nested
+    /// input is an error rather than a stack overflow
+    pub max_depth: usize,
 }
 
-impl ParserCore {
+impl<'a> ParserCore<'a> {
     /// Create a new parser core from a vector of tokens
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self {
-            tokens,
+        Self::from_source(tokens.into_iter())
+    }
+    
+    /// Create a new parser core pulling tokens from `source`
+    pub fn from_source(source: impl TokenSource + 'a) -> Self {
+        let mut core = Self {
+            tokens: Vec::new(),
             current: 0,
+            context: LexerContext::default(),
+            in_generator: false,
+            in_async: false,
+            strict: false,
+            source: Box::new(source),
+            source_error: None,
+            depth: 0,
+            max_depth: super::DEFAULT_MAX_NESTING_DEPTH,
+        };
+        core.fill(0);
+        core
+    }
+    
+    /// Take the lexer error that stopped the token stream, if any
+    pub fn take_source_error(&mut self) -> Option<Error> {
+        self.source_error.take()
+    }
+    
+    /// Pull tokens until `tokens[index]` exists or the stream has ended
+    fn fill(&mut self, index: usize) {
+        while self.tokens.len() <= index {
+            if self.tokens.last().is_some_and(|token| matches!(token.kind, TokenKind::Eof)) {
+                return;
+            }
+            let token = match self.source.next_token(&self.context) {
+                Ok(token) => token,
+                Err(error) => {
+                    // End the stream where lexing failed; `take_source_error`
+                    // reports the real cause
+                    let span = error.span().unwrap_or_else(|| Span::new(0, 0, 1, 1));
+                    self.source_error.get_or_insert(error);
+                    Token::new(TokenKind::Eof, span)
+                }
+            };
+            self.tokens.push(token);
+        }
+    }
+    
+    /// Look `offset` tokens past the current one, pulling as needed
+    pub fn peek_nth(&mut self, offset: usize) -> Option<&Token> {
+        self.fill(self.current + offset);
+        self.tokens.get(self.current + offset)
+    }
+    
+    /// The kind of the token `n` places past the current one; `Eof` past
+    /// the end of input
+    pub fn lookahead(&mut self, n: usize) -> &TokenKind {
+        self.fill(self.current + n);
+        let index = (self.current + n).min(self.tokens.len() - 1);
+        &self.tokens[index].kind
+    }
+    
+    /// Save the current position for a later `rewind`
+    pub fn checkpoint(&self) -> ParserState {
+        ParserState {
+            current: self.current,
+            context: self.context,
+            in_generator: self.in_generator,
+            in_async: self.in_async,
+            strict: self.strict,
+        }
+    }
+    
+    /// Go back to a position saved by `checkpoint`
+    ///
+    /// Tokens read since stay buffered and are handed out again. A token
+    /// re-scanned in the meantime keeps its new reading.
+    pub fn rewind(&mut self, state: ParserState) {
+        self.current = state.current;
+        self.context = state.context;
+        self.in_generator = state.in_generator;
+        self.in_async = state.in_async;
+        self.strict = state.strict;
+    }
+    
+    /// Run `parse` speculatively: on success keep what it consumed, on
+    /// error rewind to where it started
+    ///
+    /// The error of an abandoned attempt is dropped, so whatever the
+    /// caller parses instead reports its own. Lexer errors are not
+    /// speculative and still end the token stream.
+    pub fn try_parse<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Option<T> {
+        let state = self.checkpoint();
+        match parse(self) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                self.rewind(state);
+                None
+            }
+        }
+    }
+    
+    /// Parse one level deeper with `parse`, failing if that goes past
+    /// `max_depth`
+    pub fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth >= self.max_depth {
+            return Err(Error::parser(
+                ErrorCode::TooMuchNesting,
+                format!("Too much nesting: more than {} levels", self.max_depth),
+                self.peek().span,
+            ));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+    
+    /// Re-scan the current token under `context`
+    ///
+    /// Buffered lookahead past the current token is discarded. Returns
+    /// `false` when the source's tokens are fixed and cannot be re-scanned.
+    pub fn rescan_current(&mut self, context: LexerContext) -> Result<bool> {
+        let token = self.peek().clone();
+        match self.source.rescan(&token, &context)? {
+            Some(rescanned) => {
+                self.tokens.truncate(self.current);
+                self.tokens.push(rescanned);
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
     
@@ -77,10 +250,17 @@ impl ParserCore {
         &self.tokens[self.current.saturating_sub(1)]
     }
     
+    /// Span from the start of `start` to the end of the previous token,
+    /// covering everything parsed since `start` was peeked
+    pub fn span_from(&self, start: Span) -> Span {
+        Span::new(start.start, self.previous().span.end.max(start.end), start.line, start.column)
+    }
+    
     /// Advance to the next token and return the current one
     pub fn advance(&mut self) -> &Token {
         if !self.is_at_end() {
             self.current += 1;
+            self.fill(self.current);
         }
         self.previous()
     }
@@ -100,7 +280,35 @@ impl ParserCore {
         if self.is_at_end() {
             false
         } else {
-            std::mem::discriminant(&self.peek().kind) == std::mem::discriminant(token_type)
+            match (&self.peek().kind, token_type) {
+                // Keywords share a variant, so compare which keyword it is
+                (TokenKind::Keyword(actual), TokenKind::Keyword(expected)) => actual == expected,
+                (actual, expected) => std::mem::discriminant(actual) == std::mem::discriminant(expected),
+            }
+        }
+    }
+    
+    /// Check if the current token is the given contextual keyword
+    pub fn peek_is_contextual(&self, keyword: ContextualKeyword) -> bool {
+        !self.is_at_end() && self.peek().is_contextual(keyword)
+    }
+    
+    /// Whether the current token starts an async function: `async`, then
+    /// `function` on the same line
+    pub fn at_async_function(&mut self) -> bool {
+        self.peek_is_contextual(ContextualKeyword::Async)
+            && self.peek_nth(1).is_some_and(|token| {
+                token.kind == TokenKind::Keyword(Keyword::Function) && !token.newline_before
+            })
+    }
+    
+    /// Consume the current token if it is the given contextual keyword
+    pub fn eat_contextual(&mut self, keyword: ContextualKeyword) -> bool {
+        if self.peek_is_contextual(keyword) {
+            self.advance();
+            true
+        } else {
+            false
         }
     }
     
@@ -111,6 +319,7 @@ impl ParserCore {
             Ok(())
         } else {
             Err(Error::parser(
+                ErrorCode::ExpectedToken,
                 message.to_string(),
                 self.peek().span,
             ))
@@ -125,19 +334,43 @@ impl ParserCore {
             Ok(name)
         } else {
             Err(Error::parser(
+                ErrorCode::ExpectedToken,
                 message.to_string(),
                 self.peek().span,
             ))
         }
     }
     
-    /// Consume semicolon or newline (both are valid statement terminators)
-    pub fn consume_semicolon_or_newline(&mut self) {
-        if matches!(self.peek().kind, TokenKind::Semicolon | TokenKind::Newline | TokenKind::Eof | TokenKind::RightBrace) {
-            // Only consume if it's not a closing brace (which ends the block)
-            if !matches!(self.peek().kind, TokenKind::RightBrace) {
-                self.advance();
-            }
+    /// Consume a property name after `.`, where reserved words are allowed
+    /// (`gen.return`, `obj.new`)
+    pub fn consume_property_name(&mut self, message: &str) -> Result<String> {
+        if let TokenKind::Keyword(keyword) = self.peek().kind {
+            self.advance();
+            return Ok(keyword.as_str().to_string());
+        }
+        self.consume_identifier(message)
+    }
+    
+    /// Whether a statement may end before the current token without a `;`
+    ///
+    /// Automatic semicolon insertion applies before a `}`, at the end of
+    /// input, and before a token on a new line.
+    pub fn can_insert_semicolon(&self) -> bool {
+        matches!(self.peek().kind, TokenKind::Eof | TokenKind::RightBrace) || self.peek().newline_before
+    }
+    
+    /// Consume the `;` ending a statement, or check that one can be inserted
+    ///
+    /// A closing brace is left for the enclosing block to consume.
+    pub fn consume_statement_end(&mut self) -> Result<()> {
+        if self.match_token(&TokenKind::Semicolon) || self.can_insert_semicolon() {
+            Ok(())
+        } else {
+            Err(Error::parser(
+                ErrorCode::MissingSemicolon,
+                format!("Unexpected token: {} (expected ';' or a line break)", self.peek().kind),
+                self.peek().span,
+            ))
         }
     }
     
@@ -146,12 +379,20 @@ impl ParserCore {
         match token {
             TokenKind::PipePipe => Precedence::Or,
             TokenKind::AmpAmp => Precedence::And,
+            TokenKind::Pipe => Precedence::BitwiseOr,
+            TokenKind::Caret => Precedence::BitwiseXor,
+            TokenKind::Amp => Precedence::BitwiseAnd,
             TokenKind::EqualEqual | TokenKind::BangEqual |
             TokenKind::EqualEqualEqual | TokenKind::BangEqualEqual => Precedence::Equality,
             TokenKind::Less | TokenKind::Greater |
-            TokenKind::LessEqual | TokenKind::GreaterEqual => Precedence::Comparison,
+            TokenKind::LessEqual | TokenKind::GreaterEqual |
+            TokenKind::Keyword(Keyword::Instanceof) => Precedence::Comparison,
+            TokenKind::Keyword(Keyword::In) => Precedence::Comparison,
+            TokenKind::LessLess | TokenKind::GreaterGreater |
+            TokenKind::GreaterGreaterGreater => Precedence::Shift,
             TokenKind::Plus | TokenKind::Minus => Precedence::Term,
             TokenKind::Star | TokenKind::Slash | TokenKind::Percent => Precedence::Factor,
+            TokenKind::StarStar => Precedence::Exponent,
             _ => Precedence::None,
         }
     }
@@ -164,6 +405,7 @@ impl ParserCore {
             TokenKind::Star => Ok(BinaryOp::Multiply),
             TokenKind::Slash => Ok(BinaryOp::Divide),
             TokenKind::Percent => Ok(BinaryOp::Modulo),
+            TokenKind::StarStar => Ok(BinaryOp::Power),
             TokenKind::EqualEqual => Ok(BinaryOp::Equal),
             TokenKind::BangEqual => Ok(BinaryOp::NotEqual),
             TokenKind::EqualEqualEqual => Ok(BinaryOp::StrictEqual),
@@ -174,7 +416,16 @@ impl ParserCore {
             TokenKind::GreaterEqual => Ok(BinaryOp::GreaterEqual),
             TokenKind::AmpAmp => Ok(BinaryOp::LogicalAnd),
             TokenKind::PipePipe => Ok(BinaryOp::LogicalOr),
+            TokenKind::Amp => Ok(BinaryOp::BitwiseAnd),
+            TokenKind::Pipe => Ok(BinaryOp::BitwiseOr),
+            TokenKind::Caret => Ok(BinaryOp::BitwiseXor),
+            TokenKind::LessLess => Ok(BinaryOp::LeftShift),
+            TokenKind::GreaterGreater => Ok(BinaryOp::RightShift),
+            TokenKind::GreaterGreaterGreater => Ok(BinaryOp::UnsignedRightShift),
+            TokenKind::Keyword(Keyword::Instanceof) => Ok(BinaryOp::InstanceOf),
+            TokenKind::Keyword(Keyword::In) => Ok(BinaryOp::In),
             _ => Err(Error::parser(
+                ErrorCode::UnexpectedToken,
                 format!("Invalid binary operator: {}", token),
                 Span::new(0, 0, 1, 1), // TODO: use actual span
             )),
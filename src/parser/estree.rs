@@ -0,0 +1,1086 @@
+//! ESTree JSON for programs
+//!
+//! [`Node`] has the shape of the ESTree specification
+//! (<https://github.com/estree/estree>) that JavaScript tools such as
+//! ESLint and AST explorers read: a `type` string per node, `start` and
+//! `end` offsets in UTF-16 code units, and a `loc` with 1-based lines and
+//! 0-based columns. [`from_program`] converts a parsed [`Program`] and
+//! [`to_program`] converts back, so a transform written against ESTree
+//! can be run here.
+//!
+//! The AST records less than ESTree describes, and the conversion is
+//! explicit about it:
+//!
+//! - Literals have no position, so their nodes leave `start`, `end` and
+//!   `loc` out. Every other node covers the span the parser recorded and
+//!   all of its children.
+//! - Arrow functions always have a block body.
+//! - Node types and forms the AST can't hold, such as template literals,
+//!   `super` classes or several declarators, are errors in [`to_program`].
+
+use serde::{Deserialize, Serialize};
+use crate::error::{Error, ErrorCode, Result, Span};
+use super::ast::{
+    BinaryOp, ClassBody, ClassMethod, Expr, Literal, PostfixUnaryOp, Program, Property,
+    PropertyDefinition, PropertyKey, Stmt, UnaryOp, VarKind,
+};
+
+/// A node of an ESTree AST
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    #[serde(flatten)]
+    pub kind: NodeKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub loc: Option<SourceLocation>,
+}
+
+/// Where a node starts and ends in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceLocation {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// A 1-based line and a 0-based column, counted in UTF-16 code units
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub line: u32,
+    pub column: u32,
+}
+
+/// The value of a `Literal` node
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LiteralValue {
+    Boolean(bool),
+    #[serde(serialize_with = "serialize_number")]
+    Number(f64),
+    String(String),
+    /// `null`, and regular expressions, whose value JSON can't hold
+    Null,
+}
+
+/// Write integral numbers without a fraction, as JavaScript tools do
+fn serialize_number<S: serde::Serializer>(n: &f64, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER && !(*n == 0.0 && n.is_sign_negative()) {
+        serializer.serialize_i64(*n as i64)
+    } else {
+        serializer.serialize_f64(*n)
+    }
+}
+
+/// The `regex` field of a regular expression `Literal`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegexLiteral {
+    pub pattern: String,
+    pub flags: String,
+}
+
+/// The node types this crate's AST can represent, with their ESTree fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum NodeKind {
+    Program {
+        body: Vec<Node>,
+        #[serde(rename = "sourceType")]
+        source_type: String,
+    },
+    
+    // Statements
+    ExpressionStatement {
+        expression: Box<Node>,
+    },
+    VariableDeclaration {
+        declarations: Vec<Node>,
+        kind: String,
+    },
+    VariableDeclarator {
+        id: Box<Node>,
+        init: Option<Box<Node>>,
+    },
+    FunctionDeclaration {
+        id: Option<Box<Node>>,
+        params: Vec<Node>,
+        body: Box<Node>,
+        generator: bool,
+        #[serde(rename = "async")]
+        is_async: bool,
+    },
+    ClassDeclaration {
+        id: Option<Box<Node>>,
+        #[serde(rename = "superClass")]
+        super_class: Option<Box<Node>>,
+        body: Box<Node>,
+    },
+    IfStatement {
+        test: Box<Node>,
+        consequent: Box<Node>,
+        alternate: Option<Box<Node>>,
+    },
+    WhileStatement {
+        test: Box<Node>,
+        body: Box<Node>,
+    },
+    ForStatement {
+        init: Option<Box<Node>>,
+        test: Option<Box<Node>>,
+        update: Option<Box<Node>>,
+        body: Box<Node>,
+    },
+    ForOfStatement {
+        left: Box<Node>,
+        right: Box<Node>,
+        body: Box<Node>,
+        #[serde(rename = "await")]
+        is_await: bool,
+    },
+    BlockStatement {
+        body: Vec<Node>,
+    },
+    ReturnStatement {
+        argument: Option<Box<Node>>,
+    },
+    BreakStatement {
+        label: Option<Box<Node>>,
+    },
+    ContinueStatement {
+        label: Option<Box<Node>>,
+    },
+    DebuggerStatement {},
+    EmptyStatement {},
+    
+    // Expressions
+    Literal {
+        value: LiteralValue,
+        raw: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        regex: Option<RegexLiteral>,
+    },
+    Identifier {
+        name: String,
+    },
+    ThisExpression {},
+    BinaryExpression {
+        operator: String,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    LogicalExpression {
+        operator: String,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    UnaryExpression {
+        operator: String,
+        prefix: bool,
+        argument: Box<Node>,
+    },
+    UpdateExpression {
+        operator: String,
+        prefix: bool,
+        argument: Box<Node>,
+    },
+    AssignmentExpression {
+        operator: String,
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    CallExpression {
+        callee: Box<Node>,
+        arguments: Vec<Node>,
+        optional: bool,
+    },
+    NewExpression {
+        callee: Box<Node>,
+        arguments: Vec<Node>,
+    },
+    MemberExpression {
+        object: Box<Node>,
+        property: Box<Node>,
+        computed: bool,
+        optional: bool,
+    },
+    ObjectExpression {
+        properties: Vec<Node>,
+    },
+    Property {
+        key: Box<Node>,
+        value: Box<Node>,
+        kind: String,
+        method: bool,
+        shorthand: bool,
+        computed: bool,
+    },
+    SpreadElement {
+        argument: Box<Node>,
+    },
+    ArrayExpression {
+        elements: Vec<Option<Node>>,
+    },
+    FunctionExpression {
+        id: Option<Box<Node>>,
+        params: Vec<Node>,
+        body: Box<Node>,
+        generator: bool,
+        #[serde(rename = "async")]
+        is_async: bool,
+    },
+    ArrowFunctionExpression {
+        id: Option<Box<Node>>,
+        params: Vec<Node>,
+        body: Box<Node>,
+        expression: bool,
+        generator: bool,
+        #[serde(rename = "async")]
+        is_async: bool,
+    },
+    YieldExpression {
+        argument: Option<Box<Node>>,
+        delegate: bool,
+    },
+    AwaitExpression {
+        argument: Box<Node>,
+    },
+    ClassExpression {
+        id: Option<Box<Node>>,
+        #[serde(rename = "superClass")]
+        super_class: Option<Box<Node>>,
+        body: Box<Node>,
+    },
+    ClassBody {
+        body: Vec<Node>,
+    },
+    MethodDefinition {
+        key: Box<Node>,
+        value: Box<Node>,
+        kind: String,
+        computed: bool,
+        #[serde(rename = "static")]
+        is_static: bool,
+    },
+}
+
+impl NodeKind {
+    /// The ESTree `type` of the node
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            NodeKind::Program { .. } => "Program",
+            NodeKind::ExpressionStatement { .. } => "ExpressionStatement",
+            NodeKind::VariableDeclaration { .. } => "VariableDeclaration",
+            NodeKind::VariableDeclarator { .. } => "VariableDeclarator",
+            NodeKind::FunctionDeclaration { .. } => "FunctionDeclaration",
+            NodeKind::ClassDeclaration { .. } => "ClassDeclaration",
+            NodeKind::IfStatement { .. } => "IfStatement",
+            NodeKind::WhileStatement { .. } => "WhileStatement",
+            NodeKind::ForStatement { .. } => "ForStatement",
+            NodeKind::ForOfStatement { .. } => "ForOfStatement",
+            NodeKind::BlockStatement { .. } => "BlockStatement",
+            NodeKind::ReturnStatement { .. } => "ReturnStatement",
+            NodeKind::BreakStatement { .. } => "BreakStatement",
+            NodeKind::ContinueStatement { .. } => "ContinueStatement",
+            NodeKind::DebuggerStatement {} => "DebuggerStatement",
+            NodeKind::EmptyStatement {} => "EmptyStatement",
+            NodeKind::Literal { .. } => "Literal",
+            NodeKind::Identifier { .. } => "Identifier",
+            NodeKind::ThisExpression {} => "ThisExpression",
+            NodeKind::BinaryExpression { .. } => "BinaryExpression",
+            NodeKind::LogicalExpression { .. } => "LogicalExpression",
+            NodeKind::UnaryExpression { .. } => "UnaryExpression",
+            NodeKind::UpdateExpression { .. } => "UpdateExpression",
+            NodeKind::AssignmentExpression { .. } => "AssignmentExpression",
+            NodeKind::CallExpression { .. } => "CallExpression",
+            NodeKind::NewExpression { .. } => "NewExpression",
+            NodeKind::MemberExpression { .. } => "MemberExpression",
+            NodeKind::ObjectExpression { .. } => "ObjectExpression",
+            NodeKind::Property { .. } => "Property",
+            NodeKind::SpreadElement { .. } => "SpreadElement",
+            NodeKind::ArrayExpression { .. } => "ArrayExpression",
+            NodeKind::FunctionExpression { .. } => "FunctionExpression",
+            NodeKind::ArrowFunctionExpression { .. } => "ArrowFunctionExpression",
+            NodeKind::YieldExpression { .. } => "YieldExpression",
+            NodeKind::AwaitExpression { .. } => "AwaitExpression",
+            NodeKind::ClassExpression { .. } => "ClassExpression",
+            NodeKind::ClassBody { .. } => "ClassBody",
+            NodeKind::MethodDefinition { .. } => "MethodDefinition",
+        }
+    }
+    
+    /// The node's children, in source order
+    pub fn children(&self) -> Vec<&Node> {
+        fn all<'a>(nodes: impl IntoIterator<Item = &'a Node>) -> Vec<&'a Node> {
+            nodes.into_iter().collect()
+        }
+        match self {
+            NodeKind::Program { body, .. } |
+            NodeKind::BlockStatement { body } |
+            NodeKind::ClassBody { body } => all(body),
+            NodeKind::VariableDeclaration { declarations, .. } => all(declarations),
+            NodeKind::ObjectExpression { properties } => all(properties),
+            NodeKind::ArrayExpression { elements } => all(elements.iter().flatten()),
+            NodeKind::ExpressionStatement { expression: node } |
+            NodeKind::SpreadElement { argument: node } |
+            NodeKind::AwaitExpression { argument: node } |
+            NodeKind::UnaryExpression { argument: node, .. } |
+            NodeKind::UpdateExpression { argument: node, .. } => vec![&**node],
+            NodeKind::ReturnStatement { argument: node } |
+            NodeKind::YieldExpression { argument: node, .. } |
+            NodeKind::BreakStatement { label: node } |
+            NodeKind::ContinueStatement { label: node } => all(node.as_deref()),
+            NodeKind::VariableDeclarator { id, init } => all([&**id].into_iter().chain(init.as_deref())),
+            NodeKind::FunctionDeclaration { id, params, body, .. } |
+            NodeKind::FunctionExpression { id, params, body, .. } |
+            NodeKind::ArrowFunctionExpression { id, params, body, .. } => {
+                all(id.as_deref().into_iter().chain(params).chain([&**body]))
+            }
+            NodeKind::ClassDeclaration { id, super_class, body } |
+            NodeKind::ClassExpression { id, super_class, body } => {
+                all(id.as_deref().into_iter().chain(super_class.as_deref()).chain([&**body]))
+            }
+            NodeKind::IfStatement { test, consequent, alternate } => {
+                all([&**test, &**consequent].into_iter().chain(alternate.as_deref()))
+            }
+            NodeKind::WhileStatement { test, body } => vec![&**test, &**body],
+            NodeKind::ForStatement { init, test, update, body } => {
+                all(init.as_deref().into_iter().chain(test.as_deref()).chain(update.as_deref()).chain([&**body]))
+            }
+            NodeKind::ForOfStatement { left, right, body, .. } => vec![&**left, &**right, &**body],
+            NodeKind::BinaryExpression { left, right, .. } |
+            NodeKind::LogicalExpression { left, right, .. } |
+            NodeKind::AssignmentExpression { left, right, .. } => vec![&**left, &**right],
+            NodeKind::CallExpression { callee, arguments, .. } |
+            NodeKind::NewExpression { callee, arguments } => all([&**callee].into_iter().chain(arguments)),
+            NodeKind::MemberExpression { object, property, .. } => vec![&**object, &**property],
+            NodeKind::Property { key, value, .. } |
+            NodeKind::MethodDefinition { key, value, .. } => vec![&**key, &**value],
+            NodeKind::DebuggerStatement {} |
+            NodeKind::EmptyStatement {} |
+            NodeKind::Literal { .. } |
+            NodeKind::Identifier { .. } |
+            NodeKind::ThisExpression {} => Vec::new(),
+        }
+    }
+}
+
+impl Node {
+    /// Write the node as pretty-printed JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("ESTree nodes always serialize")
+    }
+    
+    /// Read a node from ESTree JSON
+    ///
+    /// Fields this crate doesn't use, such as `range` or `comments`, are
+    /// ignored; node types it has no variant for are errors.
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|error| {
+            let span = Span::new(0, 0, error.line() as u32, error.column() as u32);
+            Error::parser(ErrorCode::InvalidEstree, format!("Invalid ESTree JSON: {}", error), span)
+        })
+    }
+}
+
+/// Convert `program`, parsed from `source`, to ESTree
+pub fn from_program(program: &Program, source: &str) -> Node {
+    let converter = Converter { positions: Positions::new(source) };
+    let body = program.statements.iter().map(|statement| converter.statement(statement)).collect();
+    // The program covers its whole source, comments and all
+    let whole = Span::new(0, source.len(), 1, 1);
+    converter.node(NodeKind::Program { body, source_type: "script".to_string() }, Some(whole))
+}
+
+/// Convert an ESTree `Program` to this crate's AST
+///
+/// Spans are rebuilt from `start`, `end` and `loc` as given.
+pub fn to_program(node: &Node) -> Result<Program> {
+    match &node.kind {
+        NodeKind::Program { body, .. } => Ok(Program { statements: statements(body)? }),
+        _ => Err(unexpected(node, "a Program")),
+    }
+}
+
+/// Offsets and line starts of a source, for turning byte offsets into
+/// ESTree positions
+struct Positions {
+    /// The UTF-16 offset of every byte offset, and of the end
+    utf16: Vec<usize>,
+    /// UTF-16 offsets where lines start
+    line_starts: Vec<usize>,
+}
+
+impl Positions {
+    fn new(source: &str) -> Self {
+        let mut utf16 = Vec::with_capacity(source.len() + 1);
+        let mut line_starts = vec![0];
+        let mut offset = 0;
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            utf16.extend(std::iter::repeat_n(offset, c.len_utf8()));
+            offset += c.len_utf16();
+            let line_break = match c {
+                '\n' | '\u{2028}' | '\u{2029}' => true,
+                '\r' => chars.peek() != Some(&'\n'),
+                _ => false,
+            };
+            if line_break {
+                line_starts.push(offset);
+            }
+        }
+        utf16.push(offset);
+        Self { utf16, line_starts }
+    }
+    
+    fn utf16_offset(&self, byte_offset: usize) -> usize {
+        self.utf16[byte_offset.min(self.utf16.len() - 1)]
+    }
+    
+    fn position(&self, utf16_offset: usize) -> Position {
+        let line = self.line_starts.partition_point(|&start| start <= utf16_offset) - 1;
+        Position { line: line as u32 + 1, column: (utf16_offset - self.line_starts[line]) as u32 }
+    }
+}
+
+
+/// Compound assignment operators and the binary operator each applies
+const COMPOUND_ASSIGNMENTS: [(BinaryOp, &str); 5] = [
+    (BinaryOp::Add, "+="),
+    (BinaryOp::Subtract, "-="),
+    (BinaryOp::Multiply, "*="),
+    (BinaryOp::Divide, "/="),
+    (BinaryOp::Modulo, "%="),
+];
+
+const BINARY_OPERATORS: [BinaryOp; 24] = [
+    BinaryOp::Add, BinaryOp::Subtract, BinaryOp::Multiply, BinaryOp::Divide, BinaryOp::Modulo,
+    BinaryOp::Power, BinaryOp::Equal, BinaryOp::NotEqual, BinaryOp::StrictEqual,
+    BinaryOp::StrictNotEqual, BinaryOp::Less, BinaryOp::Greater, BinaryOp::LessEqual,
+    BinaryOp::GreaterEqual, BinaryOp::LogicalAnd, BinaryOp::LogicalOr, BinaryOp::BitwiseAnd,
+    BinaryOp::BitwiseOr, BinaryOp::BitwiseXor, BinaryOp::LeftShift, BinaryOp::RightShift,
+    BinaryOp::UnsignedRightShift, BinaryOp::InstanceOf, BinaryOp::In,
+];
+
+const UNARY_OPERATORS: [UnaryOp; 7] = [
+    UnaryOp::Plus, UnaryOp::Minus, UnaryOp::LogicalNot, UnaryOp::BitwiseNot,
+    UnaryOp::TypeOf, UnaryOp::Void, UnaryOp::Delete,
+];
+
+/// The ESTree operator of `op`, without the space `Display` adds to words
+fn unary_operator(op: UnaryOp) -> String {
+    op.to_string().trim_end().to_string()
+}
+
+struct Converter {
+    positions: Positions,
+}
+
+impl Converter {
+    /// A node covering `span` and all of its children
+    fn node(&self, kind: NodeKind, span: Option<Span>) -> Node {
+        let own = span.map(|span| (self.positions.utf16_offset(span.start), self.positions.utf16_offset(span.end)));
+        let range = kind.children().into_iter()
+            .filter_map(|child| child.start.zip(child.end))
+            .chain(own)
+            .reduce(|(start, end), (child_start, child_end)| (start.min(child_start), end.max(child_end)));
+        Node {
+            start: range.map(|(start, _)| start),
+            end: range.map(|(_, end)| end),
+            loc: range.map(|(start, end)| SourceLocation {
+                start: self.positions.position(start),
+                end: self.positions.position(end),
+            }),
+            kind,
+        }
+    }
+    
+    fn boxed(&self, kind: NodeKind, span: Option<Span>) -> Box<Node> {
+        Box::new(self.node(kind, span))
+    }
+    
+    fn identifier(&self, name: &str, span: Option<Span>) -> Box<Node> {
+        self.boxed(NodeKind::Identifier { name: name.to_string() }, span)
+    }
+    
+    fn params(&self, params: &[(String, Span)]) -> Vec<Node> {
+        params.iter().map(|(name, span)| *self.identifier(name, Some(*span))).collect()
+    }
+    
+    fn block(&self, statements: &[Stmt], span: Option<Span>) -> Box<Node> {
+        let body = statements.iter().map(|statement| self.statement(statement)).collect();
+        self.boxed(NodeKind::BlockStatement { body }, span)
+    }
+    
+    fn expression(&self, expr: &Expr) -> Box<Node> {
+        Box::new(self.expression_node(expr))
+    }
+    
+    fn optional(&self, expr: Option<&Expr>) -> Option<Box<Node>> {
+        expr.map(|expr| self.expression(expr))
+    }
+    
+    fn statement(&self, statement: &Stmt) -> Node {
+        let kind = match statement {
+            // The statement's span is its expression's
+            Stmt::Expression(expr) => return self.node(NodeKind::ExpressionStatement { expression: self.expression(expr) }, None),
+            Stmt::Block { statements, span } => return *self.block(statements, Some(*span)),
+            Stmt::VarDecl { .. } => return self.for_head(statement),
+            Stmt::FunctionDecl { name, params, body, is_generator, is_async, .. } => NodeKind::FunctionDeclaration {
+                id: Some(self.identifier(name, None)),
+                params: self.params(params),
+                body: self.block(body, None),
+                generator: *is_generator,
+                is_async: *is_async,
+            },
+            Stmt::ClassDecl { name, body, .. } => NodeKind::ClassDeclaration {
+                id: Some(self.identifier(name, None)),
+                super_class: None,
+                body: self.class_body(body),
+            },
+            Stmt::If { test, then_stmt, else_stmt, .. } => NodeKind::IfStatement {
+                test: self.expression(test),
+                consequent: Box::new(self.statement(then_stmt)),
+                alternate: else_stmt.as_ref().map(|statement| Box::new(self.statement(statement))),
+            },
+            Stmt::While { test, body, .. } => NodeKind::WhileStatement {
+                test: self.expression(test),
+                body: Box::new(self.statement(body)),
+            },
+            Stmt::For { init, test, update, body, .. } => NodeKind::ForStatement {
+                init: init.as_ref().map(|init| Box::new(self.for_head(init))),
+                test: self.optional(test.as_ref()),
+                update: self.optional(update.as_ref()),
+                body: Box::new(self.statement(body)),
+            },
+            Stmt::ForOf { left, right, body, .. } => NodeKind::ForOfStatement {
+                left: Box::new(self.for_head(left)),
+                right: self.expression(right),
+                body: Box::new(self.statement(body)),
+                is_await: false,
+            },
+            Stmt::Return { value, .. } => NodeKind::ReturnStatement { argument: self.optional(value.as_ref()) },
+            Stmt::Break { .. } => NodeKind::BreakStatement { label: None },
+            Stmt::Continue { .. } => NodeKind::ContinueStatement { label: None },
+            Stmt::Debugger { .. } => NodeKind::DebuggerStatement {},
+            Stmt::Empty { .. } => NodeKind::EmptyStatement {},
+        };
+        self.node(kind, Some(statement.span()))
+    }
+    
+    /// A declaration, or the bare expression ESTree puts in a `for` head
+    fn for_head(&self, statement: &Stmt) -> Node {
+        match statement {
+            Stmt::VarDecl { kind, name, init, span } => {
+                let declarator = NodeKind::VariableDeclarator {
+                    id: self.identifier(name, None),
+                    init: self.optional(init.as_ref()),
+                };
+                let declaration = NodeKind::VariableDeclaration {
+                    declarations: vec![self.node(declarator, None)],
+                    kind: kind.to_string(),
+                };
+                self.node(declaration, Some(*span))
+            }
+            Stmt::Expression(expr) => self.expression_node(expr),
+            _ => self.statement(statement),
+        }
+    }
+    
+    fn class_body(&self, body: &ClassBody) -> Box<Node> {
+        let mut methods: Vec<_> = body.constructor.iter().chain(&body.methods).collect();
+        methods.sort_by_key(|method| method.span.start);
+        let body = methods.into_iter().map(|method| self.method(method, body.constructor.as_ref() == Some(method))).collect();
+        self.boxed(NodeKind::ClassBody { body }, None)
+    }
+    
+    fn method(&self, method: &ClassMethod, is_constructor: bool) -> Node {
+        let value = NodeKind::FunctionExpression {
+            id: None,
+            params: self.params(&method.params),
+            body: self.block(&method.body, None),
+            generator: false,
+            is_async: false,
+        };
+        let kind = NodeKind::MethodDefinition {
+            key: self.identifier(&method.name, None),
+            value: self.boxed(value, None),
+            kind: if is_constructor { "constructor" } else { "method" }.to_string(),
+            computed: false,
+            is_static: method.is_static,
+        };
+        self.node(kind, Some(method.span))
+    }
+    
+    fn literal(&self, literal: &Literal) -> Node {
+        let (value, regex) = match literal {
+            Literal::Number(n) if n.is_finite() => (LiteralValue::Number(*n), None),
+            // JSON has no such numbers; `raw` still says which
+            Literal::Number(_) | Literal::Null => (LiteralValue::Null, None),
+            Literal::String(s) => (LiteralValue::String(s.clone()), None),
+            Literal::Boolean(b) => (LiteralValue::Boolean(*b), None),
+            // `undefined` is an identifier to ESTree
+            Literal::Undefined => return *self.identifier("undefined", None),
+            Literal::RegExp { pattern, flags } => {
+                (LiteralValue::Null, Some(RegexLiteral { pattern: pattern.clone(), flags: flags.clone() }))
+            }
+        };
+        let raw = match literal {
+            Literal::Number(n) if n.is_infinite() => "Infinity".to_string(),
+            _ => literal.to_string(),
+        };
+        self.node(NodeKind::Literal { value, raw, regex }, None)
+    }
+    
+    fn property_key(&self, key: &PropertyKey) -> Box<Node> {
+        match key {
+            PropertyKey::Identifier(name) => self.identifier(name, None),
+            PropertyKey::String(s) => Box::new(self.literal(&Literal::String(s.clone()))),
+            PropertyKey::Number(n) => Box::new(self.literal(&Literal::Number(*n))),
+            PropertyKey::Computed(expr) => self.expression(expr),
+        }
+    }
+    
+    fn property(&self, definition: &PropertyDefinition) -> Node {
+        let Property { key, value, span } = match definition {
+            PropertyDefinition::Property(property) => property,
+            PropertyDefinition::SpreadProperty { argument, span } => {
+                return self.node(NodeKind::SpreadElement { argument: self.expression(argument) }, Some(*span));
+            }
+        };
+        // The parser gives a shorthand's value the key's span, and starts
+        // a method's function at its key
+        let shorthand = matches!(value, Expr::Identifier { span: value_span, .. } if value_span == span);
+        let method = matches!(value, Expr::Function { span: value_span, .. } if value_span.start == span.start);
+        let value = match value {
+            Expr::Function { params, body, span, .. } if method => self.boxed(NodeKind::FunctionExpression {
+                id: None,
+                params: self.params(params),
+                body: self.block(body, None),
+                generator: false,
+                is_async: false,
+            }, Some(*span)),
+            _ => self.expression(value),
+        };
+        let kind = NodeKind::Property {
+            key: self.property_key(key),
+            value,
+            kind: "init".to_string(),
+            method,
+            shorthand,
+            computed: matches!(key, PropertyKey::Computed(_)),
+        };
+        self.node(kind, Some(*span))
+    }
+    
+    fn expression_node(&self, expr: &Expr) -> Node {
+        let kind = match expr {
+            Expr::Literal(literal) => return self.literal(literal),
+            Expr::Identifier { name, .. } => NodeKind::Identifier { name: name.clone() },
+            Expr::This { .. } => NodeKind::ThisExpression {},
+            Expr::Binary { op, left, right, .. } => {
+                let (operator, left, right) = (op.to_string(), self.expression(left), self.expression(right));
+                match op {
+                    BinaryOp::LogicalAnd | BinaryOp::LogicalOr => NodeKind::LogicalExpression { operator, left, right },
+                    _ => NodeKind::BinaryExpression { operator, left, right },
+                }
+            }
+            Expr::Unary { op, operand, .. } => NodeKind::UnaryExpression {
+                operator: unary_operator(*op),
+                prefix: true,
+                argument: self.expression(operand),
+            },
+            Expr::PostfixUnary { op, operand, .. } => NodeKind::UpdateExpression {
+                operator: op.to_string(),
+                prefix: false,
+                argument: self.expression(operand),
+            },
+            Expr::Assignment { op, left, right, .. } => {
+                let operator = match op {
+                    Some(op) => COMPOUND_ASSIGNMENTS.iter()
+                        .find(|(compound, _)| compound == op)
+                        .map_or("=", |(_, operator)| *operator),
+                    None => "=",
+                };
+                NodeKind::AssignmentExpression {
+                    operator: operator.to_string(),
+                    left: self.expression(left),
+                    right: self.expression(right),
+                }
+            }
+            Expr::Call { callee, args, .. } => NodeKind::CallExpression {
+                callee: self.expression(callee),
+                arguments: args.iter().map(|arg| self.expression_node(arg)).collect(),
+                optional: false,
+            },
+            Expr::New { callee, args, .. } => NodeKind::NewExpression {
+                callee: self.expression(callee),
+                arguments: args.iter().map(|arg| self.expression_node(arg)).collect(),
+            },
+            Expr::Member { object, property, computed, .. } => NodeKind::MemberExpression {
+                object: self.expression(object),
+                property: self.expression(property),
+                computed: *computed,
+                optional: false,
+            },
+            Expr::Object { properties, .. } => NodeKind::ObjectExpression {
+                properties: properties.iter().map(|property| self.property(property)).collect(),
+            },
+            Expr::Array { elements, .. } => NodeKind::ArrayExpression {
+                elements: elements.iter().map(|element| element.as_ref().map(|expr| self.expression_node(expr))).collect(),
+            },
+            Expr::Function { name, params, body, is_generator, is_async, .. } => NodeKind::FunctionExpression {
+                id: name.as_ref().map(|name| self.identifier(name, None)),
+                params: self.params(params),
+                body: self.block(body, None),
+                generator: *is_generator,
+                is_async: *is_async,
+            },
+            Expr::Arrow { params, body, is_async, .. } => NodeKind::ArrowFunctionExpression {
+                id: None,
+                params: self.params(params),
+                body: self.block(body, None),
+                expression: false,
+                generator: false,
+                is_async: *is_async,
+            },
+            Expr::Yield { argument, .. } => NodeKind::YieldExpression {
+                argument: self.optional(argument.as_deref()),
+                delegate: false,
+            },
+            Expr::Await { argument, .. } => NodeKind::AwaitExpression { argument: self.expression(argument) },
+            Expr::Class { name, body, .. } => NodeKind::ClassExpression {
+                id: name.as_ref().map(|name| self.identifier(name, None)),
+                super_class: None,
+                body: self.class_body(body),
+            },
+        };
+        self.node(kind, Some(expr.span()))
+    }
+}
+
+/// The span of a node read from JSON, as far as it says
+fn span(node: &Node) -> Span {
+    let start = node.start.unwrap_or(0);
+    let (line, column) = node.loc.map_or((1, 1), |loc| (loc.start.line, loc.start.column + 1));
+    Span::new(start, node.end.unwrap_or(start).max(start), line, column)
+}
+
+fn unexpected(node: &Node, expected: &str) -> Error {
+    Error::parser(ErrorCode::InvalidEstree, format!("Expected {} in ESTree input, found {}", expected, node.kind.type_name()), span(node))
+}
+
+fn unsupported(node: &Node, what: &str) -> Error {
+    Error::parser(ErrorCode::UnsupportedEstree, format!("Unsupported ESTree {}: {}", node.kind.type_name(), what), span(node))
+}
+
+fn statements(nodes: &[Node]) -> Result<Vec<Stmt>> {
+    nodes.iter().map(statement).collect()
+}
+
+fn block(node: &Node) -> Result<Vec<Stmt>> {
+    match &node.kind {
+        NodeKind::BlockStatement { body } => statements(body),
+        _ => Err(unexpected(node, "a BlockStatement")),
+    }
+}
+
+fn identifier_name(node: &Node) -> Result<String> {
+    match &node.kind {
+        NodeKind::Identifier { name } => Ok(name.clone()),
+        _ => Err(unexpected(node, "an Identifier")),
+    }
+}
+
+fn optional_name(node: Option<&Node>) -> Result<Option<String>> {
+    node.map(identifier_name).transpose()
+}
+
+/// Parameters, which may only be plain identifiers
+fn params(nodes: &[Node]) -> Result<Vec<(String, Span)>> {
+    nodes.iter().map(|node| Ok((identifier_name(node)?, span(node)))).collect()
+}
+
+fn expression_box(node: &Node) -> Result<Box<Expr>> {
+    expression(node).map(Box::new)
+}
+
+fn optional_expression(node: Option<&Node>) -> Result<Option<Expr>> {
+    node.map(expression).transpose()
+}
+
+fn statement(node: &Node) -> Result<Stmt> {
+    let span = span(node);
+    Ok(match &node.kind {
+        NodeKind::ExpressionStatement { expression: expr } => Stmt::Expression(expression(expr)?),
+        NodeKind::VariableDeclaration { declarations, kind } => {
+            let kind = match kind.as_str() {
+                "var" => VarKind::Var,
+                "let" => VarKind::Let,
+                "const" => VarKind::Const,
+                _ => return Err(unsupported(node, &format!("kind {}", kind))),
+            };
+            let [declarator] = declarations.as_slice() else {
+                return Err(unsupported(node, "only one declarator per declaration"));
+            };
+            let NodeKind::VariableDeclarator { id, init } = &declarator.kind else {
+                return Err(unexpected(declarator, "a VariableDeclarator"));
+            };
+            Stmt::VarDecl { kind, name: identifier_name(id)?, init: optional_expression(init.as_deref())?, span }
+        }
+        NodeKind::FunctionDeclaration { id: Some(id), params: names, body, generator, is_async } => Stmt::FunctionDecl {
+            name: identifier_name(id)?,
+            params: params(names)?,
+            body: block(body)?,
+            is_generator: *generator,
+            is_async: *is_async,
+            span,
+        },
+        NodeKind::ClassDeclaration { id: Some(id), super_class, body } => Stmt::ClassDecl {
+            name: identifier_name(id)?,
+            body: class_body(node, super_class.as_deref(), body)?,
+            span,
+        },
+        NodeKind::FunctionDeclaration { id: None, .. } | NodeKind::ClassDeclaration { id: None, .. } => {
+            return Err(unsupported(node, "declarations need a name"));
+        }
+        NodeKind::IfStatement { test, consequent, alternate } => Stmt::If {
+            test: expression(test)?,
+            test_span: self::span(test),
+            then_stmt: Box::new(statement(consequent)?),
+            else_stmt: alternate.as_deref().map(statement).transpose()?.map(Box::new),
+            span,
+        },
+        NodeKind::WhileStatement { test, body } => Stmt::While {
+            test: expression(test)?,
+            test_span: self::span(test),
+            body: Box::new(statement(body)?),
+            span,
+        },
+        NodeKind::ForStatement { init, test, update, body } => Stmt::For {
+            init: init.as_deref().map(for_head).transpose()?.map(Box::new),
+            test: optional_expression(test.as_deref())?,
+            test_span: test.as_deref().map(self::span),
+            update: optional_expression(update.as_deref())?,
+            body: Box::new(statement(body)?),
+            span,
+        },
+        NodeKind::ForOfStatement { is_await: true, .. } => return Err(unsupported(node, "for await")),
+        NodeKind::ForOfStatement { left, right, body, .. } => Stmt::ForOf {
+            left: Box::new(for_head(left)?),
+            right: expression(right)?,
+            body: Box::new(statement(body)?),
+            span,
+        },
+        NodeKind::BlockStatement { body } => Stmt::Block { statements: statements(body)?, span },
+        NodeKind::ReturnStatement { argument } => Stmt::Return { value: optional_expression(argument.as_deref())?, span },
+        NodeKind::BreakStatement { label: None } => Stmt::Break { span },
+        NodeKind::ContinueStatement { label: None } => Stmt::Continue { span },
+        NodeKind::BreakStatement { .. } | NodeKind::ContinueStatement { .. } => return Err(unsupported(node, "labels")),
+        NodeKind::DebuggerStatement {} => Stmt::Debugger { span },
+        NodeKind::EmptyStatement {} => Stmt::Empty { span },
+        _ => return Err(unexpected(node, "a statement")),
+    })
+}
+
+fn for_head(node: &Node) -> Result<Stmt> {
+    match &node.kind {
+        NodeKind::VariableDeclaration { .. } => statement(node),
+        _ => expression(node).map(Stmt::Expression),
+    }
+}
+
+fn class_body(class: &Node, super_class: Option<&Node>, body: &Node) -> Result<ClassBody> {
+    if super_class.is_some() {
+        return Err(unsupported(class, "superClass"));
+    }
+    let NodeKind::ClassBody { body: members } = &body.kind else {
+        return Err(unexpected(body, "a ClassBody"));
+    };
+    let mut constructor = None;
+    let mut methods = Vec::new();
+    for member in members {
+        let NodeKind::MethodDefinition { key, value, kind, computed, is_static } = &member.kind else {
+            return Err(unexpected(member, "a MethodDefinition"));
+        };
+        if *computed {
+            return Err(unsupported(member, "computed method names"));
+        }
+        let NodeKind::FunctionExpression { params: names, body, generator: false, is_async: false, .. } = &value.kind else {
+            return Err(unsupported(value, "only plain functions can be class methods"));
+        };
+        let method = ClassMethod {
+            name: identifier_name(key)?,
+            params: params(names)?,
+            body: block(body)?,
+            is_static: *is_static,
+            span: span(member),
+        };
+        match kind.as_str() {
+            "constructor" if constructor.is_none() => constructor = Some(method),
+            "method" => methods.push(method),
+            _ => return Err(unsupported(member, &format!("{} methods", kind))),
+        }
+    }
+    Ok(ClassBody { constructor, methods })
+}
+
+fn literal(node: &Node, value: &LiteralValue, raw: &str, regex: Option<&RegexLiteral>) -> Result<Literal> {
+    if let Some(RegexLiteral { pattern, flags }) = regex {
+        return Ok(Literal::RegExp { pattern: pattern.clone(), flags: flags.clone() });
+    }
+    Ok(match value {
+        LiteralValue::Boolean(b) => Literal::Boolean(*b),
+        LiteralValue::Number(n) => Literal::Number(*n),
+        LiteralValue::String(s) => Literal::String(s.clone()),
+        LiteralValue::Null if raw == "null" => Literal::Null,
+        // Numbers JSON can't hold
+        LiteralValue::Null => Literal::Number(raw.parse().map_err(|_| unsupported(node, &format!("value of {}", raw)))?),
+    })
+}
+
+fn property_key(key: &Node, computed: bool) -> Result<PropertyKey> {
+    if computed {
+        return expression(key).map(PropertyKey::Computed);
+    }
+    Ok(match &key.kind {
+        NodeKind::Identifier { name } => PropertyKey::Identifier(name.clone()),
+        NodeKind::Literal { value: LiteralValue::String(s), .. } => PropertyKey::String(s.clone()),
+        NodeKind::Literal { value: LiteralValue::Number(n), .. } => PropertyKey::Number(*n),
+        _ => return Err(unexpected(key, "an identifier, string or number key")),
+    })
+}
+
+fn property(node: &Node) -> Result<PropertyDefinition> {
+    let span = span(node);
+    let (key, value, kind, method, computed) = match &node.kind {
+        NodeKind::SpreadElement { argument } => {
+            return Ok(PropertyDefinition::SpreadProperty { argument: expression(argument)?, span });
+        }
+        NodeKind::Property { key, value, kind, method, computed, .. } => (key, value, kind, *method, *computed),
+        _ => return Err(unexpected(node, "a Property")),
+    };
+    if kind != "init" {
+        return Err(unsupported(node, &format!("{} properties", kind)));
+    }
+    let key = property_key(key, computed)?;
+    let value = match (&value.kind, &key) {
+        // A method is named after its key
+        (NodeKind::FunctionExpression { id: None, .. }, PropertyKey::Identifier(name) | PropertyKey::String(name)) if method => {
+            match expression(value)? {
+                Expr::Function { params, body, is_generator, is_async, span, .. } => {
+                    Expr::Function { name: Some(name.clone()), params, body, is_generator, is_async, span }
+                }
+                _ => unreachable!("a FunctionExpression is a function"),
+            }
+        }
+        _ => expression(value)?,
+    };
+    Ok(PropertyDefinition::Property(Property { key, value, span }))
+}
+
+fn binary_op(node: &Node, operator: &str) -> Result<BinaryOp> {
+    BINARY_OPERATORS.into_iter()
+        .find(|op| op.to_string() == operator)
+        .ok_or_else(|| unsupported(node, &format!("operator {}", operator)))
+}
+
+fn expression(node: &Node) -> Result<Expr> {
+    let span = span(node);
+    Ok(match &node.kind {
+        NodeKind::Literal { value, raw, regex } => Expr::Literal(literal(node, value, raw, regex.as_ref())?),
+        NodeKind::Identifier { name } if name == "undefined" => Expr::Literal(Literal::Undefined),
+        NodeKind::Identifier { name } => Expr::Identifier { name: name.clone(), span },
+        NodeKind::ThisExpression {} => Expr::This { span },
+        NodeKind::BinaryExpression { operator, left, right } | NodeKind::LogicalExpression { operator, left, right } => {
+            Expr::Binary { op: binary_op(node, operator)?, left: expression_box(left)?, right: expression_box(right)?, span }
+        }
+        NodeKind::UnaryExpression { operator, prefix: true, argument } => {
+            let op = UNARY_OPERATORS.into_iter()
+                .find(|&op| unary_operator(op) == *operator)
+                .ok_or_else(|| unsupported(node, &format!("operator {}", operator)))?;
+            Expr::Unary { op, operand: expression_box(argument)?, span }
+        }
+        NodeKind::UpdateExpression { operator, prefix: false, argument } => {
+            let op = match operator.as_str() {
+                "++" => PostfixUnaryOp::Increment,
+                "--" => PostfixUnaryOp::Decrement,
+                _ => return Err(unsupported(node, &format!("operator {}", operator))),
+            };
+            Expr::PostfixUnary { op, operand: expression_box(argument)?, span }
+        }
+        NodeKind::UnaryExpression { .. } | NodeKind::UpdateExpression { .. } => {
+            return Err(unsupported(node, "prefix must be true for unary and false for update operators"));
+        }
+        NodeKind::AssignmentExpression { operator, left, right } => {
+            let op = match operator.as_str() {
+                "=" => None,
+                _ => Some(COMPOUND_ASSIGNMENTS.iter()
+                    .find(|(_, compound)| compound == operator)
+                    .map(|(op, _)| *op)
+                    .ok_or_else(|| unsupported(node, &format!("operator {}", operator)))?),
+            };
+            Expr::Assignment { op, left: expression_box(left)?, right: expression_box(right)?, span }
+        }
+        NodeKind::CallExpression { optional: true, .. } | NodeKind::MemberExpression { optional: true, .. } => {
+            return Err(unsupported(node, "optional chaining"));
+        }
+        NodeKind::CallExpression { callee, arguments, .. } => Expr::Call {
+            callee: expression_box(callee)?,
+            args: arguments.iter().map(expression).collect::<Result<_>>()?,
+            span,
+        },
+        NodeKind::NewExpression { callee, arguments } => Expr::New {
+            callee: expression_box(callee)?,
+            args: arguments.iter().map(expression).collect::<Result<_>>()?,
+            span,
+        },
+        NodeKind::MemberExpression { object, property, computed, .. } => {
+            let property = if *computed {
+                expression(property)?
+            } else {
+                // `o.undefined` names a property, not the value
+                Expr::Identifier { name: identifier_name(property)?, span: self::span(property) }
+            };
+            Expr::Member { object: expression_box(object)?, property: Box::new(property), computed: *computed, span }
+        }
+        NodeKind::ObjectExpression { properties } => Expr::Object {
+            properties: properties.iter().map(property).collect::<Result<_>>()?,
+            span,
+        },
+        NodeKind::ArrayExpression { elements } => Expr::Array {
+            elements: elements.iter().map(|element| element.as_ref().map(expression).transpose()).collect::<Result<_>>()?,
+            span,
+        },
+        NodeKind::FunctionExpression { id, params: names, body, generator, is_async } => Expr::Function {
+            name: optional_name(id.as_deref())?,
+            params: params(names)?,
+            body: block(body)?,
+            is_generator: *generator,
+            is_async: *is_async,
+            span,
+        },
+        NodeKind::ArrowFunctionExpression { generator: true, .. } => return Err(unsupported(node, "generator arrows")),
+        NodeKind::ArrowFunctionExpression { params: names, body, is_async, .. } => {
+            let body = match &body.kind {
+                NodeKind::BlockStatement { .. } => block(body)?,
+                // An expression body is a single `return`, as the parser has it
+                _ => vec![Stmt::Return { value: Some(expression(body)?), span: self::span(body) }],
+            };
+            Expr::Arrow { params: params(names)?, body, is_async: *is_async, span }
+        }
+        NodeKind::YieldExpression { delegate: true, .. } => return Err(unsupported(node, "yield*")),
+        NodeKind::YieldExpression { argument, .. } => Expr::Yield {
+            argument: optional_expression(argument.as_deref())?.map(Box::new),
+            span,
+        },
+        NodeKind::AwaitExpression { argument } => Expr::Await { argument: expression_box(argument)?, span },
+        NodeKind::ClassExpression { id, super_class, body } => Expr::Class {
+            name: optional_name(id.as_deref())?,
+            body: class_body(node, super_class.as_deref(), body)?,
+            span,
+        },
+        _ => return Err(unexpected(node, "an expression")),
+    })
+}
@@ -5,11 +5,12 @@
 
 use super::ast::*;
 use super::core::{ParserCore, Precedence};
-use super::statements::ExpressionParser as ExpressionParserTrait;
-use crate::error::{Error, Result};
-use crate::lexer::{TokenKind, Keyword};
+use super::statements::{check_unique_parameters, ExpressionParser as ExpressionParserTrait, StatementParser};
+use crate::error::{Error, ErrorCode, Result, Span};
+use crate::lexer::{TokenKind, Keyword, ContextualKeyword, LexerContext};
 
 /// Expression parser for handling all expression types
+#[derive(Default)]
 pub struct ExpressionParser;
 
 impl ExpressionParser {
@@ -19,7 +20,24 @@ impl ExpressionParser {
     }
     
     /// Parse assignment expressions (right-associative)
+    ///
+    /// Every operand that is a full expression (parenthesized, an
+    /// argument, an element, a property value) starts here, so this is
+    /// where expression nesting is counted.
     fn parse_assignment(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        core.nested(|core| self.parse_assignment_at_depth(core))
+    }
+    
+    /// Parse an assignment expression once its nesting is counted
+    fn parse_assignment_at_depth(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        if core.in_generator && core.check(&TokenKind::Keyword(Keyword::Yield)) {
+            return self.parse_yield(core);
+        }
+        
+        if let Some(arrow) = self.try_parse_arrow(core)? {
+            return Ok(arrow);
+        }
+        
         let expr = self.parse_precedence(core, Precedence::Or)?;
         
         if core.match_token(&TokenKind::Equal) {
@@ -27,6 +45,7 @@ impl ExpressionParser {
             let right = self.parse_assignment(core)?; // Right associative
             
             return Ok(Expr::Assignment {
+                op: None,
                 left: Box::new(expr),
                 right: Box::new(right),
                 span: start_span,
@@ -38,17 +57,10 @@ impl ExpressionParser {
             let op_token = core.advance().clone();
             let right = self.parse_assignment(core)?; // Right associative
             
-            // Transform a += b into a = a + b
-            let binary_expr = Expr::Binary {
-                op: compound_op,
-                left: Box::new(expr.clone()),
-                right: Box::new(right),
-                span: op_token.span,
-            };
-            
             return Ok(Expr::Assignment {
+                op: Some(compound_op),
                 left: Box::new(expr),
-                right: Box::new(binary_expr),
+                right: Box::new(right),
                 span: op_token.span,
             });
         }
@@ -56,10 +68,91 @@ impl ExpressionParser {
         Ok(expr)
     }
     
+    /// Parse an arrow function if one starts at the current token
+    ///
+    /// `x =>` is recognized by lookahead alone. A parenthesized parameter
+    /// list reads the same as a parenthesized expression up to the `=>`
+    /// after it, so that head is parsed speculatively and abandoned when
+    /// no `=>` follows. Once the head is taken the body is parsed for
+    /// real, and its errors are reported as they are.
+    fn try_parse_arrow(&mut self, core: &mut ParserCore) -> Result<Option<Expr>> {
+        let span = core.peek().span;
+        let head = if matches!(core.lookahead(0), TokenKind::Identifier(_)) && matches!(core.lookahead(1), TokenKind::Arrow) {
+            let name = core.consume_identifier("Expected parameter name")?;
+            core.advance(); // consume '=>'
+            Some((vec![(name, core.previous().span)], false))
+        } else if core.check(&TokenKind::LeftParen) || core.peek_is_contextual(ContextualKeyword::Async) {
+            core.try_parse(Self::parse_arrow_head)
+        } else {
+            None
+        };
+        let Some((params, is_async)) = head else {
+            return Ok(None);
+        };
+        check_unique_parameters(&params)?;
+        
+        let body = StatementParser::new().parse_arrow_body(core, self, is_async)?;
+        Ok(Some(Expr::Arrow { params, body, is_async, span: core.span_from(span) }))
+    }
+    
+    /// Parse `(params) =>`, `async (params) =>` or `async x =>`, returning
+    /// the parameters and whether the function is async
+    fn parse_arrow_head(core: &mut ParserCore) -> Result<(Vec<(String, Span)>, bool)> {
+        let is_async = core.peek_is_contextual(ContextualKeyword::Async)
+            && core.peek_nth(1).is_some_and(|token| !token.newline_before);
+        if is_async {
+            core.advance();
+        }
+        let params = if core.match_token(&TokenKind::LeftParen) {
+            StatementParser::new().parse_parameters(core)?
+        } else {
+            let name = core.consume_identifier("Expected arrow function parameters")?;
+            vec![(name, core.previous().span)]
+        };
+        if core.peek().newline_before {
+            return Err(Error::parser(ErrorCode::LineBreakBeforeArrow, "Unexpected line break before '=>'", core.peek().span));
+        }
+        core.consume(&TokenKind::Arrow, "Expected '=>' after arrow function parameters")?;
+        Ok((params, is_async))
+    }
+    
+    /// Parse `yield` or `yield expr` inside a generator
+    ///
+    /// The operand is optional: `yield` alone is followed by a closing
+    /// token, a separator, or a line break.
+    fn parse_yield(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        let token = core.advance().clone();
+        let next = core.peek();
+        let has_argument = next.span.line == token.span.line && !matches!(
+            next.kind,
+            TokenKind::RightParen | TokenKind::RightBracket | TokenKind::RightBrace
+                | TokenKind::Comma | TokenKind::Semicolon | TokenKind::Colon | TokenKind::Eof
+        );
+        if has_argument && core.check(&TokenKind::Star) {
+            return Err(Error::parser(ErrorCode::UnsupportedSyntax, "yield* delegation is not supported", core.peek().span));
+        }
+        
+        let argument = if has_argument {
+            Some(Box::new(self.parse_assignment(core)?))
+        } else {
+            None
+        };
+        Ok(Expr::Yield { argument, span: token.span })
+    }
+    
     /// Parse expression with given minimum precedence
     fn parse_precedence(&mut self, core: &mut ParserCore, precedence: Precedence) -> Result<Expr> {
+        let start = core.peek().span.start;
         let mut left = self.parse_unary(core)?;
         
+        // `-2 ** 2` is a SyntaxError: a unary expression can only be the
+        // base of `**` inside parentheses, which `(-2)` starting at `(` shows
+        let bare_unary = matches!(&left, Expr::Unary { span, .. } | Expr::Await { span, .. } if span.start == start);
+        if bare_unary && core.check(&TokenKind::StarStar) {
+            let power = self.parse_binary(core, left)?;
+            return Err(unary_base_error(&power));
+        }
+        
         while !core.is_at_end() && precedence <= core.get_precedence(&core.peek().kind) {
             left = self.parse_binary(core, left)?;
         }
@@ -68,47 +161,49 @@ impl ExpressionParser {
     }
     
     /// Parse unary expression
+    ///
+    /// A prefix operator applies to a whole unary expression, calls and
+    /// member accesses included: `typeof f()` is `typeof (f())` and
+    /// `!x.y` is `!(x.y)`.
     fn parse_unary(&mut self, core: &mut ParserCore) -> Result<Expr> {
-        match &core.peek().kind {
-            TokenKind::Minus => {
-                let span = core.peek().span;
-                core.advance();
-                let operand = Box::new(self.parse_unary(core)?);
-                Ok(Expr::Unary {
-                    op: UnaryOp::Minus,
-                    operand,
-                    span,
-                })
-            }
-            TokenKind::Plus => {
-                let span = core.peek().span;
-                core.advance();
-                let operand = Box::new(self.parse_unary(core)?);
-                Ok(Expr::Unary {
-                    op: UnaryOp::Plus,
-                    operand,
-                    span,
-                })
-            }
-            TokenKind::Bang => {
-                let span = core.peek().span;
-                core.advance();
-                let operand = Box::new(self.parse_unary(core)?);
-                Ok(Expr::Unary {
-                    op: UnaryOp::LogicalNot,
-                    operand,
-                    span,
-                })
+        let op = match &core.peek().kind {
+            TokenKind::Minus => UnaryOp::Minus,
+            TokenKind::Plus => UnaryOp::Plus,
+            TokenKind::Bang => UnaryOp::LogicalNot,
+            TokenKind::Tilde => UnaryOp::BitwiseNot,
+            TokenKind::Keyword(Keyword::Typeof) => UnaryOp::TypeOf,
+            TokenKind::Keyword(Keyword::Void) => UnaryOp::Void,
+            TokenKind::Keyword(Keyword::Delete) => UnaryOp::Delete,
+            _ if core.in_async && core.peek_is_contextual(ContextualKeyword::Await) => {
+                let span = core.advance().span;
+                let argument = Box::new(self.parse_operand(core)?);
+                return Ok(Expr::Await { argument, span });
             }
-            _ => self.parse_primary(core),
-        }
+            _ => return self.parse_primary(core),
+        };
+        let span = core.advance().span;
+        let operand = Box::new(self.parse_operand(core)?);
+        Ok(Expr::Unary { op, operand, span })
+    }
+    
+    /// Parse the operand of a prefix operator, one level deeper
+    fn parse_operand(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        core.nested(|core| self.parse_unary(core))
     }
     
     /// Parse binary expression
     fn parse_binary(&mut self, core: &mut ParserCore, left: Expr) -> Result<Expr> {
         let op_token = core.advance().clone();
         let precedence = core.get_precedence(&op_token.kind);
-        let right = self.parse_precedence(core, precedence.next())?;
+        // `**` is right-associative: 2 ** 3 ** 2 is 2 ** (3 ** 2)
+        let right_precedence = if precedence == Precedence::Exponent {
+            precedence
+        } else {
+            precedence.next()
+        };
+        // Only a right-associative operand nests; anything else returns
+        // before the next operator is read
+        let right = core.nested(|core| self.parse_precedence(core, right_precedence))?;
         
         let op = core.token_to_binary_op(&op_token.kind)?;
         
@@ -122,14 +217,43 @@ impl ExpressionParser {
     
     /// Parse primary expression (literals, identifiers, parenthesized expressions)
     fn parse_primary(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        let expr = self.parse_primary_atom(core)?;
+        
+        // Handle postfix expressions (calls, member access)
+        self.parse_postfix(core, expr)
+    }
+    
+    /// Parse a primary expression without any postfix operators
+    fn parse_primary_atom(&mut self, core: &mut ParserCore) -> Result<Expr> {
+        // An operand is expected here, so `/` or `/=` opens a regex literal;
+        // a live lexer re-scans it as one
+        if matches!(core.peek().kind, TokenKind::Slash | TokenKind::SlashEqual) {
+            core.rescan_current(LexerContext::regex_allowed())?;
+        }
+        
+        if core.at_async_function() {
+            let span = core.advance().span;
+            core.advance(); // consume 'function'
+            return self.parse_function_expression(core, span, true);
+        }
+        
+        // `advance` stays on the last token at the end, so it would be
+        // read again, and `new` would loop reading itself
+        if core.is_at_end() {
+            return Err(Error::parser(ErrorCode::UnexpectedEnd, "Unexpected end of input", core.peek().span));
+        }
         let token = core.advance();
         
-        let mut expr = match &token.kind {
+        match &token.kind {
             TokenKind::Number(n) => Ok(Expr::Literal(Literal::Number(*n))),
             TokenKind::String(s) => Ok(Expr::Literal(Literal::String(s.clone()))),
             TokenKind::Boolean(b) => Ok(Expr::Literal(Literal::Boolean(*b))),
             TokenKind::Null => Ok(Expr::Literal(Literal::Null)),
             TokenKind::Undefined => Ok(Expr::Literal(Literal::Undefined)),
+            TokenKind::RegExp { pattern, flags } => Ok(Expr::Literal(Literal::RegExp {
+                pattern: pattern.clone(),
+                flags: flags.clone(),
+            })),
             TokenKind::Keyword(Keyword::True) => Ok(Expr::Literal(Literal::Boolean(true))),
             TokenKind::Keyword(Keyword::False) => Ok(Expr::Literal(Literal::Boolean(false))),
             TokenKind::Keyword(Keyword::Null) => Ok(Expr::Literal(Literal::Null)),
@@ -143,16 +267,192 @@ impl ExpressionParser {
                 core.consume(&TokenKind::RightParen, "Expected ')'")?;
                 Ok(expr)
             }
+            TokenKind::LeftBracket => {
+                let span = token.span;
+                self.parse_array_literal(core, span)
+            }
+            TokenKind::LeftBrace => {
+                let span = token.span;
+                self.parse_object_literal(core, span)
+            }
+            TokenKind::Keyword(Keyword::Function) => {
+                let span = token.span;
+                self.parse_function_expression(core, span, false)
+            }
+            TokenKind::Keyword(Keyword::This) => Ok(Expr::This { span: token.span }),
+            TokenKind::Keyword(Keyword::New) => {
+                let span = token.span;
+                self.parse_new_expression(core, span)
+            }
+            TokenKind::Keyword(Keyword::Class) => {
+                let span = token.span;
+                self.parse_class_expression(core, span)
+            }
+            TokenKind::NoSubstitutionTemplate(_) | TokenKind::TemplateHead(_) => Err(Error::parser(
+                ErrorCode::UnsupportedSyntax,
+                "Template literals are not supported yet",
+                token.span,
+            )),
             _ => Err(Error::parser(
+                ErrorCode::UnexpectedToken,
                 format!("Unexpected token: {}", token.kind),
                 token.span,
             )),
-        }?;
+        }
+    }
+    
+    /// Parse a `new` expression after the keyword: `new Callee(args)`
+    ///
+    /// The callee is a member expression, so in `new a.B(1)` the argument
+    /// list belongs to `new`; without one (`new B`) there are no arguments.
+    fn parse_new_expression(&mut self, core: &mut ParserCore, span: Span) -> Result<Expr> {
+        let mut callee = if core.check(&TokenKind::Keyword(Keyword::New)) {
+            let span = core.advance().span;
+            core.nested(|core| self.parse_new_expression(core, span))?
+        } else {
+            self.parse_primary_atom(core)?
+        };
+        while matches!(core.peek().kind, TokenKind::Dot | TokenKind::LeftBracket) {
+            callee = self.parse_member_access(core, callee)?;
+        }
         
-        // Handle postfix expressions (calls, member access)
-        expr = self.parse_postfix(core, expr)?;
+        let args = if core.match_token(&TokenKind::LeftParen) {
+            self.parse_arguments(core)?
+        } else {
+            Vec::new()
+        };
+        Ok(Expr::New { callee: Box::new(callee), args, span })
+    }
+    
+    /// Parse a class expression after the `class` keyword; the name is optional
+    fn parse_class_expression(&mut self, core: &mut ParserCore, span: Span) -> Result<Expr> {
+        let name = if let TokenKind::Identifier(name) = &core.peek().kind {
+            let name = name.clone();
+            core.advance();
+            Some(name)
+        } else {
+            None
+        };
         
-        Ok(expr)
+        let body = StatementParser::new().parse_class_tail(core, self)?;
+        Ok(Expr::Class { name, body, span: core.span_from(span) })
+    }
+    
+    /// Parse array literal after the opening '[': `[a, , b]`
+    ///
+    /// Elisions produce holes (`None`); a single trailing comma is ignored.
+    fn parse_array_literal(&mut self, core: &mut ParserCore, span: Span) -> Result<Expr> {
+        let mut elements = Vec::new();
+        
+        while !core.check(&TokenKind::RightBracket) && !core.is_at_end() {
+            if core.match_token(&TokenKind::Comma) {
+                elements.push(None);
+                continue;
+            }
+            
+            elements.push(Some(self.parse_expression(core)?));
+            if !core.check(&TokenKind::RightBracket) {
+                core.consume(&TokenKind::Comma, "Expected ',' between array elements")?;
+            }
+        }
+        
+        core.consume(&TokenKind::RightBracket, "Expected ']' after array elements")?;
+        
+        Ok(Expr::Array { elements, span })
+    }
+    
+    /// Parse object literal after the opening '{'; a trailing comma is
+    /// allowed
+    fn parse_object_literal(&mut self, core: &mut ParserCore, span: Span) -> Result<Expr> {
+        let mut properties = Vec::new();
+        
+        while !core.check(&TokenKind::RightBrace) && !core.is_at_end() {
+            properties.push(self.parse_property_definition(core)?);
+            if !core.check(&TokenKind::RightBrace) {
+                core.consume(&TokenKind::Comma, "Expected ',' between object properties")?;
+            }
+        }
+        
+        core.consume(&TokenKind::RightBrace, "Expected '}' after object properties")?;
+        
+        Ok(Expr::Object { properties, span })
+    }
+    
+    /// Parse one object literal entry: `key: value`, shorthand `key`,
+    /// method `key() {}`, or spread `...expr`
+    fn parse_property_definition(&mut self, core: &mut ParserCore) -> Result<PropertyDefinition> {
+        let token = core.advance().clone();
+        let span = token.span;
+        
+        let key = match &token.kind {
+            TokenKind::Ellipsis => {
+                let argument = self.parse_expression(core)?;
+                return Ok(PropertyDefinition::SpreadProperty { argument, span });
+            }
+            TokenKind::Identifier(name) => {
+                if core.check(&TokenKind::Comma) || core.check(&TokenKind::RightBrace) {
+                    // Shorthand: `{ name }` is `{ name: name }`
+                    return Ok(PropertyDefinition::Property(Property {
+                        key: PropertyKey::Identifier(name.clone()),
+                        value: Expr::Identifier { name: name.clone(), span },
+                        span,
+                    }));
+                }
+                PropertyKey::Identifier(name.clone())
+            }
+            TokenKind::String(s) => PropertyKey::String(s.clone()),
+            TokenKind::Number(n) => PropertyKey::Number(*n),
+            TokenKind::LeftBracket => {
+                let expr = self.parse_expression(core)?;
+                core.consume(&TokenKind::RightBracket, "Expected ']' after computed property name")?;
+                PropertyKey::Computed(expr)
+            }
+            // Reserved words are valid property names, spelled as their kind displays
+            TokenKind::Keyword(_) | TokenKind::Boolean(_) | TokenKind::Null | TokenKind::Undefined => {
+                PropertyKey::Identifier(token.kind.to_string())
+            }
+            _ => {
+                return Err(Error::parser(
+                    ErrorCode::UnexpectedToken,
+                    format!("Unexpected token in object literal: {}", token.kind),
+                    span,
+                ));
+            }
+        };
+        
+        let value = if core.check(&TokenKind::LeftParen) {
+            // Method shorthand: `key(params) { body }`
+            let name = match &key {
+                PropertyKey::Identifier(name) | PropertyKey::String(name) => Some(name.clone()),
+                _ => None,
+            };
+            let (params, body) = StatementParser::new().parse_method_rest(core, self)?;
+            Expr::Function { name, params, body, is_generator: false, is_async: false, span: core.span_from(span) }
+        } else {
+            core.consume(&TokenKind::Colon, "Expected ':' after property name")?;
+            self.parse_expression(core)?
+        };
+        
+        Ok(PropertyDefinition::Property(Property { key, value, span }))
+    }
+    
+    /// Parse function expression after the 'function' keyword
+    fn parse_function_expression(&mut self, core: &mut ParserCore, span: Span, is_async: bool) -> Result<Expr> {
+        let is_generator = core.match_token(&TokenKind::Star);
+        if is_async && is_generator {
+            return Err(Error::parser(ErrorCode::UnsupportedSyntax, "Async generators are not supported", core.previous().span));
+        }
+        let name = if let TokenKind::Identifier(name) = &core.peek().kind {
+            let name = name.clone();
+            core.advance();
+            Some(name)
+        } else {
+            None
+        };
+        
+        let (params, body) = StatementParser::new().parse_function_rest(core, self, is_generator, is_async)?;
+        
+        Ok(Expr::Function { name, params, body, is_generator, is_async, span: core.span_from(span) })
     }
     
     /// Parse postfix expressions (calls, member access)
@@ -163,16 +463,7 @@ impl ExpressionParser {
                     // Function call: func(args)
                     let start_span = core.peek().span;
                     core.advance(); // consume '('
-                    
-                    let mut args = Vec::new();
-                    while !core.check(&TokenKind::RightParen) && !core.is_at_end() {
-                        args.push(self.parse_expression(core)?);
-                        if !core.check(&TokenKind::RightParen) {
-                            core.consume(&TokenKind::Comma, "Expected ',' between arguments")?;
-                        }
-                    }
-                    
-                    core.consume(&TokenKind::RightParen, "Expected ')' after arguments")?;
+                    let args = self.parse_arguments(core)?;
                     
                     expr = Expr::Call {
                         callee: Box::new(expr),
@@ -180,36 +471,11 @@ impl ExpressionParser {
                         span: start_span,
                     };
                 }
-                TokenKind::Dot => {
-                    // Member access: obj.prop
-                    let start_span = core.peek().span;
-                    core.advance(); // consume '.'
-                    let property_name = core.consume_identifier("Expected property name after '.'")?;
-                    
-                    expr = Expr::Member {
-                        object: Box::new(expr),
-                        property: Box::new(Expr::Identifier { 
-                            name: property_name, 
-                            span: start_span 
-                        }),
-                        computed: false,
-                        span: start_span,
-                    };
-                }
-                TokenKind::LeftBracket => {
-                    // Computed member access: obj[key]
-                    let start_span = core.peek().span;
-                    core.advance(); // consume '['
-                    let property = self.parse_expression(core)?;
-                    core.consume(&TokenKind::RightBracket, "Expected ']'")?;
-                    
-                    expr = Expr::Member {
-                        object: Box::new(expr),
-                        property: Box::new(property),
-                        computed: true,
-                        span: start_span,
-                    };
+                TokenKind::Dot | TokenKind::LeftBracket => {
+                    expr = self.parse_member_access(core, expr)?;
                 }
+                // A line break before `++`/`--` ends the statement instead
+                TokenKind::PlusPlus | TokenKind::MinusMinus if core.peek().newline_before => break,
                 TokenKind::PlusPlus => {
                     // Postfix increment: expr++
                     let span = core.peek().span;
@@ -237,6 +503,52 @@ impl ExpressionParser {
         }
         Ok(expr)
     }
+    
+    /// Parse call arguments after the opening '(' up to and including ')',
+    /// allowing a trailing comma (ES2017)
+    fn parse_arguments(&mut self, core: &mut ParserCore) -> Result<Vec<Expr>> {
+        let mut args = Vec::new();
+        while !core.check(&TokenKind::RightParen) && !core.is_at_end() {
+            args.push(self.parse_expression(core)?);
+            if !core.check(&TokenKind::RightParen) {
+                core.consume(&TokenKind::Comma, "Expected ',' between arguments")?;
+            }
+        }
+        
+        core.consume(&TokenKind::RightParen, "Expected ')' after arguments")?;
+        Ok(args)
+    }
+    
+    /// Parse one member access on `object`: `.prop` or `[key]`
+    fn parse_member_access(&mut self, core: &mut ParserCore, object: Expr) -> Result<Expr> {
+        let start_span = core.peek().span;
+        if core.match_token(&TokenKind::Dot) {
+            // Member access: obj.prop
+            let property_name = core.consume_property_name("Expected property name after '.'")?;
+            
+            Ok(Expr::Member {
+                object: Box::new(object),
+                property: Box::new(Expr::Identifier { 
+                    name: property_name, 
+                    span: start_span 
+                }),
+                computed: false,
+                span: start_span,
+            })
+        } else {
+            // Computed member access: obj[key]
+            core.consume(&TokenKind::LeftBracket, "Expected '.' or '['")?;
+            let property = self.parse_expression(core)?;
+            core.consume(&TokenKind::RightBracket, "Expected ']'")?;
+            
+            Ok(Expr::Member {
+                object: Box::new(object),
+                property: Box::new(property),
+                computed: true,
+                span: start_span,
+            })
+        }
+    }
 }
 
 impl ExpressionParserTrait for ExpressionParser {
@@ -244,4 +556,24 @@ impl ExpressionParserTrait for ExpressionParser {
     fn parse_expression(&mut self, core: &mut ParserCore) -> Result<Expr> {
         self.parse_assignment(core)
     }
-}
\ No newline at end of file
+}
+/// The error for a unary expression used as the base of `**`, suggesting
+/// the two parenthesizations that say what was meant
+fn unary_base_error(power: &Expr) -> Error {
+    let Expr::Binary { left, right, .. } = power else {
+        unreachable!("called with the `**` expression just parsed");
+    };
+    let (op, operand, span) = match &**left {
+        Expr::Unary { op, operand, span } => (op.to_string(), operand, *span),
+        Expr::Await { argument, span } => ("await ".to_string(), argument, *span),
+        _ => unreachable!("called only for a unary base"),
+    };
+    Error::parser(
+        ErrorCode::AmbiguousExponentiation,
+        format!(
+            "Unary operator used immediately before exponentiation expression; \
+             write ({op}{operand}) ** {right} or {op}({operand} ** {right})",
+        ),
+        span,
+    )
+}
@@ -8,15 +8,27 @@
 //! - `statements`: Statement and declaration parsing
 //! - `expressions`: Expression parsing with Pratt parser
 //! - `parser`: Main orchestration layer
+//! - `estree`: Conversion to and from ESTree JSON
 
 pub mod ast;
 pub mod core;
+pub mod estree;
 pub mod expressions;
+#[allow(clippy::module_inception)]
 pub mod parser;
 pub mod statements;
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests;
 
+/// How deeply statements and expressions may nest, in both the parser and
+/// the compiler, unless configured otherwise
+///
+/// Each level costs some native stack in both, so this keeps pathological
+/// input, such as thousands of nested parentheses, from overflowing it.
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 256;
+
 pub use ast::*;
+pub use core::ParserState;
 pub use parser::Parser;
\ No newline at end of file
@@ -3,44 +3,100 @@
 //! Orchestrates the three specialized parsing agents: ParserCore, StatementParser, and ExpressionParser.
 
 use super::ast::*;
-use super::core::ParserCore;
-use super::statements::StatementParser;
+use super::core::{ParserCore, ParserState};
+use super::statements::{note_directive, ExpressionParser as _, StatementParser};
 use super::expressions::ExpressionParser;
-use crate::error::Result;
-use crate::lexer::{Token, TokenKind};
+use crate::error::{Error, ErrorCode, Result};
+use crate::lexer::{Token, TokenSource};
 
 /// JavaScript parser - orchestrates the three specialized parsing agents
-pub struct Parser {
-    core: ParserCore,
+pub struct Parser<'a> {
+    core: ParserCore<'a>,
     statement_parser: StatementParser,
     expression_parser: ExpressionParser,
 }
 
-impl Parser {
+impl<'a> Parser<'a> {
     /// Create a new parser from a vector of tokens
     pub fn new(tokens: Vec<Token>) -> Self {
+        Self::from_source(tokens.into_iter())
+    }
+    
+    /// Create a new parser that pulls tokens from `source`
+    ///
+    /// With a live `Lexer` as the source the parser tells the lexer where
+    /// a regular expression literal may start, instead of relying on the
+    /// previous-token guess that `Lexer::tokenize` makes.
+    pub fn from_source(source: impl TokenSource + 'a) -> Self {
         Self {
-            core: ParserCore::new(tokens),
+            core: ParserCore::from_source(source),
             statement_parser: StatementParser::new(),
             expression_parser: ExpressionParser::new(),
         }
     }
     
+    /// Limit how deeply statements and expressions may nest (by default
+    /// `DEFAULT_MAX_NESTING_DEPTH`); deeper input is a parse error
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.core.max_depth = max_depth;
+        self
+    }
+    
     /// Parse the tokens into an AST
     pub fn parse(&mut self) -> Result<Program> {
+        let result = self.parse_program();
+        
+        // A lexer error ends the token stream early, so whatever the parser
+        // made of the truncated input is secondary to it
+        match self.core.take_source_error() {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+    
+    /// Parse the tokens as one expression, such as `a + b` or `{ x: 1 }`
+    ///
+    /// Unlike `parse`, which reads statements, the whole input must be a
+    /// single expression: anything after it, a `;` included, is an error.
+    /// There is no automatic semicolon insertion, since there are no
+    /// statements to end, so `a\nb` is an error here where a program
+    /// would read two statements. Input starting with `{` is an object
+    /// literal rather than a block, and `function f() {}` a function
+    /// expression rather than a declaration.
+    pub fn parse_expression_only(&mut self) -> Result<Expr> {
+        let result = self.expression_parser.parse_expression(&mut self.core).and_then(|expr| {
+            if self.core.is_at_end() {
+                return Ok(expr);
+            }
+            let token = self.core.peek();
+            Err(Error::parser(ErrorCode::UnexpectedToken, format!("Expected a single expression, found {}", token.kind), token.span))
+        });
+        match self.core.take_source_error() {
+            Some(error) => Err(error),
+            None => result,
+        }
+    }
+    
+    /// Save the current position for a later `rewind`
+    pub fn checkpoint(&self) -> ParserState {
+        self.core.checkpoint()
+    }
+    
+    /// Go back to a position saved by `checkpoint`
+    pub fn rewind(&mut self, state: ParserState) {
+        self.core.rewind(state);
+    }
+    
+    fn parse_program(&mut self) -> Result<Program> {
         let mut statements = Vec::new();
+        let mut prologue = true;
         
         while !self.core.is_at_end() {
-            // Skip newlines
-            if matches!(self.core.peek().kind, TokenKind::Newline) {
-                self.core.advance();
-                continue;
-            }
-            
             let stmt = self.statement_parser.parse_statement(
                 &mut self.core, 
                 &mut self.expression_parser
             )?;
+            note_directive(&mut self.core, &stmt, &mut prologue);
             statements.push(stmt);
         }
         
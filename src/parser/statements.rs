@@ -5,15 +5,19 @@
 
 use super::ast::*;
 use super::core::ParserCore;
-use crate::error::{Error, Result};
-use crate::lexer::{TokenKind, Keyword};
+use crate::error::{Error, ErrorCode, Result, Span};
+use crate::lexer::{Token, TokenKind, Keyword, ContextualKeyword};
 
 /// Trait for expression parsing capability
 pub trait ExpressionParser {
     fn parse_expression(&mut self, core: &mut ParserCore) -> Result<Expr>;
 }
 
+/// The parameters, each with its span, and the body of a function
+type FunctionParts = (Vec<(String, Span)>, Vec<Stmt>);
+
 /// Statement parser for handling all statement types
+#[derive(Default)]
 pub struct StatementParser;
 
 impl StatementParser {
@@ -28,16 +32,36 @@ impl StatementParser {
         core: &mut ParserCore, 
         expr_parser: &mut E
     ) -> Result<Stmt> {
+        core.nested(|core| self.parse_statement_at_depth(core, expr_parser))
+    }
+    
+    /// Parse a statement once its nesting is counted
+    fn parse_statement_at_depth<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<Stmt> {
+        if core.at_async_function() {
+            return self.parse_function_declaration(core, expr_parser);
+        }
         match &core.peek().kind {
             TokenKind::Keyword(Keyword::Let) => self.parse_let_declaration(core, expr_parser),
             TokenKind::Keyword(Keyword::Var) => self.parse_var_declaration(core, expr_parser),
             TokenKind::Keyword(Keyword::Const) => self.parse_const_declaration(core, expr_parser),
             TokenKind::Keyword(Keyword::Function) => self.parse_function_declaration(core, expr_parser),
+            TokenKind::Keyword(Keyword::Class) => self.parse_class_declaration(core, expr_parser),
             TokenKind::Keyword(Keyword::If) => self.parse_if_statement(core, expr_parser),
             TokenKind::Keyword(Keyword::While) => self.parse_while_statement(core, expr_parser),
             TokenKind::Keyword(Keyword::For) => self.parse_for_statement(core, expr_parser),
             TokenKind::Keyword(Keyword::Return) => self.parse_return_statement(core, expr_parser),
+            TokenKind::Keyword(Keyword::Break) => self.parse_jump_statement(core, |span| Stmt::Break { span }),
+            TokenKind::Keyword(Keyword::Continue) => self.parse_jump_statement(core, |span| Stmt::Continue { span }),
+            TokenKind::Keyword(Keyword::Debugger) => self.parse_jump_statement(core, |span| Stmt::Debugger { span }),
             TokenKind::LeftBrace => self.parse_block_statement(core, expr_parser),
+            TokenKind::Semicolon => {
+                let span = core.advance().span;
+                Ok(Stmt::Empty { span })
+            }
             _ => self.parse_expression_statement(core, expr_parser),
         }
     }
@@ -59,9 +83,10 @@ impl StatementParser {
             None
         };
         
-        core.consume_semicolon_or_newline();
+        core.consume_statement_end()?;
         
         Ok(Stmt::VarDecl { 
+            kind: VarKind::Let,
             name, 
             init, 
             span: start_span 
@@ -85,9 +110,10 @@ impl StatementParser {
             None
         };
         
-        core.consume_semicolon_or_newline();
+        core.consume_statement_end()?;
         
         Ok(Stmt::VarDecl { 
+            kind: VarKind::Var,
             name, 
             init, 
             span: start_span 
@@ -107,6 +133,7 @@ impl StatementParser {
         
         if !core.match_token(&TokenKind::Equal) {
             return Err(Error::parser(
+                ErrorCode::MissingInitializer,
                 "Missing initializer in const declaration".to_string(),
                 core.peek().span,
             ));
@@ -114,42 +141,269 @@ impl StatementParser {
         
         let init = Some(expr_parser.parse_expression(core)?);
         
-        core.consume_semicolon_or_newline();
+        core.consume_statement_end()?;
         
         Ok(Stmt::VarDecl { 
+            kind: VarKind::Const,
             name, 
             init, 
             span: start_span 
         })
     }
     
-    /// Parse function declaration: `function name(params) { body }`
+    /// Parse function declaration: `function name(params) { body }`,
+    /// `function* name(params) { body }` for a generator, or
+    /// `async function name(params) { body }`
     fn parse_function_declaration<E: ExpressionParser>(
         &mut self, 
         core: &mut ParserCore, 
         expr_parser: &mut E
     ) -> Result<Stmt> {
         let start_span = core.peek().span;
-        core.advance(); // consume 'function'
+        let is_async = core.eat_contextual(ContextualKeyword::Async);
+        core.consume(&TokenKind::Keyword(Keyword::Function), "Expected 'function' after 'async'")?;
+        let is_generator = core.match_token(&TokenKind::Star);
+        if is_async && is_generator {
+            return Err(Error::parser(ErrorCode::UnsupportedSyntax, "Async generators are not supported", core.previous().span));
+        }
         
         let name = core.consume_identifier("Expected function name")?;
+        let (params, body) = self.parse_function_rest(core, expr_parser, is_generator, is_async)?;
         
+        Ok(Stmt::FunctionDecl { name, params, body, is_generator, is_async, span: core.span_from(start_span) })
+    }
+    
+    /// Parse the parameter list and body of a function: `(params) { body }`
+    ///
+    /// Shared by function declarations and function expressions. Inside the
+    /// body `yield` is an expression exactly when `is_generator`, and
+    /// `await` exactly when `is_async`. Parameter names may repeat only in
+    /// sloppy mode code.
+    pub(crate) fn parse_function_rest<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E,
+        is_generator: bool,
+        is_async: bool,
+    ) -> Result<FunctionParts> {
+        let enclosing_generator = std::mem::replace(&mut core.in_generator, is_generator);
+        let enclosing_async = std::mem::replace(&mut core.in_async, is_async);
+        let enclosing_strict = core.strict;
+        let result = self.parse_function_parts(core, expr_parser, false);
+        core.in_generator = enclosing_generator;
+        core.in_async = enclosing_async;
+        core.strict = enclosing_strict;
+        result
+    }
+    
+    /// Parse the parameter list and body of a method: `(params) { body }`
+    ///
+    /// Like `parse_function_rest` for a plain function, except that
+    /// parameter names may never repeat.
+    pub(crate) fn parse_method_rest<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E,
+    ) -> Result<FunctionParts> {
+        let enclosing_generator = std::mem::replace(&mut core.in_generator, false);
+        let enclosing_async = std::mem::replace(&mut core.in_async, false);
+        let enclosing_strict = core.strict;
+        let result = self.parse_function_parts(core, expr_parser, true);
+        core.in_generator = enclosing_generator;
+        core.in_async = enclosing_async;
+        core.strict = enclosing_strict;
+        result
+    }
+    
+    fn parse_function_parts<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E,
+        unique_params: bool,
+    ) -> Result<FunctionParts> {
         core.consume(&TokenKind::LeftParen, "Expected '(' after function name")?;
+        let params = self.parse_parameters(core)?;
+        
+        core.consume(&TokenKind::LeftBrace, "Expected '{' to start function body")?;
+        let body = self.parse_function_body(core, expr_parser)?;
+        
+        // A "use strict" directive in the body applies to the parameters too
+        if unique_params || core.strict {
+            check_unique_parameters(&params)?;
+        }
+        Ok((params, body))
+    }
+    
+    /// Parse parameter names after the opening '(' up to and including ')',
+    /// allowing a trailing comma (ES2017); each comes with its span
+    pub(crate) fn parse_parameters(&mut self, core: &mut ParserCore) -> Result<Vec<(String, Span)>> {
         let mut params = Vec::new();
         
         while !core.check(&TokenKind::RightParen) && !core.is_at_end() {
-            params.push(core.consume_identifier("Expected parameter name")?);
+            let name = core.consume_identifier("Expected parameter name")?;
+            params.push((name, core.previous().span));
             if !core.check(&TokenKind::RightParen) {
                 core.consume(&TokenKind::Comma, "Expected ',' between parameters")?;
             }
         }
         
         core.consume(&TokenKind::RightParen, "Expected ')' after parameters")?;
+        Ok(params)
+    }
+    
+    /// Parse a function body after the opening '{' up to and including '}'
+    ///
+    /// A `"use strict"` directive in its prologue makes the rest of the
+    /// body strict mode code; the caller restores the enclosing mode.
+    fn parse_function_body<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<Vec<Stmt>> {
+        let mut statements = Vec::new();
+        let mut prologue = true;
         
-        core.consume(&TokenKind::LeftBrace, "Expected '{' to start function body")?;
-        let body = self.parse_block_statement_body(core, expr_parser)?;
+        while !core.check(&TokenKind::RightBrace) && !core.is_at_end() {
+            let statement = self.parse_statement(core, expr_parser)?;
+            note_directive(core, &statement, &mut prologue);
+            statements.push(statement);
+        }
+        
+        core.consume(&TokenKind::RightBrace, "Expected '}'")?;
+        Ok(statements)
+    }
+    
+    /// Parse the body of an arrow function after `=>`: a block, or an
+    /// expression that becomes a single `return`
+    ///
+    /// `yield` is never an expression inside, and `await` is exactly when
+    /// `is_async`.
+    pub(crate) fn parse_arrow_body<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E,
+        is_async: bool,
+    ) -> Result<Vec<Stmt>> {
+        let enclosing_generator = std::mem::replace(&mut core.in_generator, false);
+        let enclosing_async = std::mem::replace(&mut core.in_async, is_async);
+        let enclosing_strict = core.strict;
+        let result = if core.match_token(&TokenKind::LeftBrace) {
+            self.parse_function_body(core, expr_parser)
+        } else {
+            let span = core.peek().span;
+            expr_parser.parse_expression(core).map(|value| vec![Stmt::Return { value: Some(value), span }])
+        };
+        core.in_generator = enclosing_generator;
+        core.in_async = enclosing_async;
+        core.strict = enclosing_strict;
+        result
+    }
+    
+    /// Parse class declaration: `class Name { members }`
+    fn parse_class_declaration<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<Stmt> {
+        let start_span = core.peek().span;
+        core.advance(); // consume 'class'
+        
+        let name = core.consume_identifier("Expected class name")?;
+        let body = self.parse_class_tail(core, expr_parser)?;
+        
+        Ok(Stmt::ClassDecl { name, body, span: core.span_from(start_span) })
+    }
+    
+    /// Parse the rest of a class after its name: `{ members }`
+    ///
+    /// Shared by class declarations and class expressions. Members are
+    /// methods, optionally `static`; `extends`, generator methods,
+    /// accessors and fields are rejected.
+    pub(crate) fn parse_class_tail<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<ClassBody> {
+        if core.check(&TokenKind::Keyword(Keyword::Extends)) {
+            return Err(Error::parser(ErrorCode::UnsupportedSyntax, "Class inheritance with 'extends' is not supported yet", core.peek().span));
+        }
+        core.consume(&TokenKind::LeftBrace, "Expected '{' to start class body")?;
+        
+        // Class bodies are always strict mode code
+        let enclosing_strict = std::mem::replace(&mut core.strict, true);
+        let result = self.parse_class_members(core, expr_parser);
+        core.strict = enclosing_strict;
+        result
+    }
+    
+    /// Parse the members of a class body after its '{' up to and
+    /// including the '}'
+    fn parse_class_members<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<ClassBody> {
+        let mut constructor = None;
+        let mut methods = Vec::new();
+        while !core.check(&TokenKind::RightBrace) && !core.is_at_end() {
+            if core.match_token(&TokenKind::Semicolon) {
+                continue;
+            }
+            let method = self.parse_class_method(core, expr_parser)?;
+            if method.name == "constructor" && !method.is_static {
+                if constructor.is_some() {
+                    return Err(Error::parser(ErrorCode::DuplicateConstructor, "A class may only have one constructor", method.span));
+                }
+                constructor = Some(method);
+            } else {
+                methods.push(method);
+            }
+        }
+        
+        core.consume(&TokenKind::RightBrace, "Expected '}' after class body")?;
+        Ok(ClassBody { constructor, methods })
+    }
+    
+    /// Parse one class member: `[static] name(params) { body }`
+    fn parse_class_method<E: ExpressionParser>(
+        &mut self, 
+        core: &mut ParserCore, 
+        expr_parser: &mut E
+    ) -> Result<ClassMethod> {
+        let span = core.peek().span;
+        // `static` is a modifier unless it names the method itself
+        let is_static = core.check(&TokenKind::Keyword(Keyword::Static))
+            && !core.peek_nth(1).is_some_and(|token| token.kind == TokenKind::LeftParen);
+        if is_static {
+            core.advance();
+        }
+        
+        let token = core.advance().clone();
+        let name = match &token.kind {
+            TokenKind::Identifier(name) | TokenKind::String(name) => name.clone(),
+            // Reserved words are valid method names, spelled as their kind displays
+            TokenKind::Keyword(_) | TokenKind::Boolean(_) | TokenKind::Null | TokenKind::Undefined => token.kind.to_string(),
+            TokenKind::Star => {
+                return Err(Error::parser(ErrorCode::UnsupportedSyntax, "Generator methods are not supported", token.span));
+            }
+            _ => {
+                return Err(Error::parser(
+                    ErrorCode::UnexpectedToken,
+                    format!("Unexpected token in class body: {}", token.kind),
+                    token.span,
+                ));
+            }
+        };
+        if !core.check(&TokenKind::LeftParen) {
+            return Err(Error::parser(
+                ErrorCode::UnsupportedSyntax,
+                format!("Expected '(' after method name '{}' (class fields and accessors are not supported)", name),
+                core.peek().span,
+            ));
+        }
         
-        Ok(Stmt::FunctionDecl { name, params, body, span: start_span })
+        let (params, body) = self.parse_method_rest(core, expr_parser)?;
+        Ok(ClassMethod { name, params, body, is_static, span: core.span_from(span) })
     }
     
     /// Parse if statement: `if (test) then_stmt else else_stmt`
@@ -162,7 +416,9 @@ impl StatementParser {
         core.advance(); // consume 'if'
         
         core.consume(&TokenKind::LeftParen, "Expected '(' after 'if'")?;
+        let test_start = core.peek().span;
         let test = expr_parser.parse_expression(core)?;
+        let test_span = core.span_from(test_start);
         core.consume(&TokenKind::RightParen, "Expected ')' after if condition")?;
         
         let then_stmt = Box::new(self.parse_statement(core, expr_parser)?);
@@ -173,7 +429,7 @@ impl StatementParser {
             None
         };
         
-        Ok(Stmt::If { test, then_stmt, else_stmt, span: start_span })
+        Ok(Stmt::If { test, test_span, then_stmt, else_stmt, span: start_span })
     }
     
     /// Parse while statement: `while (test) body`
@@ -186,12 +442,14 @@ impl StatementParser {
         core.advance(); // consume 'while'
         
         core.consume(&TokenKind::LeftParen, "Expected '(' after 'while'")?;
+        let test_start = core.peek().span;
         let test = expr_parser.parse_expression(core)?;
+        let test_span = core.span_from(test_start);
         core.consume(&TokenKind::RightParen, "Expected ')' after while condition")?;
         
         let body = Box::new(self.parse_statement(core, expr_parser)?);
         
-        Ok(Stmt::While { test, body, span: start_span })
+        Ok(Stmt::While { test, test_span, body, span: start_span })
     }
     
     /// Parse for statement: `for (init; test; update) body`
@@ -205,10 +463,20 @@ impl StatementParser {
         
         core.consume(&TokenKind::LeftParen, "Expected '(' after 'for'")?;
         
+        if let Some(left) = self.try_parse_for_of_head(core)? {
+            let right = expr_parser.parse_expression(core)?;
+            core.consume(&TokenKind::RightParen, "Expected ')' after for-of iterable")?;
+            let body = Box::new(self.parse_statement(core, expr_parser)?);
+            return Ok(Stmt::ForOf { left, right, body, span: start_span });
+        }
+        
         // Parse init (can be a variable declaration or expression, or empty)
         let init = if core.match_token(&TokenKind::Semicolon) {
             None // Empty init
-        } else if matches!(core.peek().kind, TokenKind::Keyword(Keyword::Var) | TokenKind::Keyword(Keyword::Let)) {
+        } else if matches!(
+            core.peek().kind,
+            TokenKind::Keyword(Keyword::Var) | TokenKind::Keyword(Keyword::Let) | TokenKind::Keyword(Keyword::Const)
+        ) {
             // Variable declaration
             Some(Box::new(self.parse_statement(core, expr_parser)?))
         } else {
@@ -219,11 +487,13 @@ impl StatementParser {
         };
         
         // Parse test condition (optional)
+        let test_start = core.peek().span;
         let test = if core.check(&TokenKind::Semicolon) {
             None
         } else {
             Some(expr_parser.parse_expression(core)?)
         };
+        let test_span = test.as_ref().map(|_| core.span_from(test_start));
         core.consume(&TokenKind::Semicolon, "Expected ';' after for loop condition")?;
         
         // Parse update expression (optional)
@@ -238,7 +508,45 @@ impl StatementParser {
         // Parse body
         let body = Box::new(self.parse_statement(core, expr_parser)?);
         
-        Ok(Stmt::For { init, test, update, body, span: start_span })
+        Ok(Stmt::For { init, test, test_span, update, body, span: start_span })
+    }
+    
+    /// Parse the `let x of` / `x of` head of a for-of loop, consuming through `of`
+    ///
+    /// Returns `None` without consuming anything when the loop is a
+    /// classic three-clause `for`.
+    fn try_parse_for_of_head(&mut self, core: &mut ParserCore) -> Result<Option<Box<Stmt>>> {
+        let kind = match core.peek().kind {
+            TokenKind::Keyword(Keyword::Let) => Some(VarKind::Let),
+            TokenKind::Keyword(Keyword::Var) => Some(VarKind::Var),
+            TokenKind::Keyword(Keyword::Const) => Some(VarKind::Const),
+            _ => None,
+        };
+        let is_declaration = kind.is_some();
+        let name_offset = if is_declaration { 1 } else { 0 };
+        
+        let name = match core.peek_nth(name_offset) {
+            Some(Token { kind: TokenKind::Identifier(name), .. }) => name.clone(),
+            _ => return Ok(None),
+        };
+        let is_for_of = core.peek_nth(name_offset + 1)
+            .is_some_and(|token| token.is_contextual(ContextualKeyword::Of));
+        if !is_for_of {
+            return Ok(None);
+        }
+        
+        let start_span = core.peek().span;
+        let left = if let Some(kind) = kind {
+            core.advance(); // consume 'let' / 'var' / 'const'
+            core.advance(); // consume the binding name
+            Stmt::VarDecl { kind, name, init: None, span: start_span }
+        } else {
+            core.advance();
+            Stmt::Expression(Expr::Identifier { name, span: start_span })
+        };
+        
+        core.eat_contextual(ContextualKeyword::Of);
+        Ok(Some(Box::new(left)))
     }
     
     /// Parse return statement: `return expr?;`
@@ -250,17 +558,25 @@ impl StatementParser {
         let start_span = core.peek().span;
         core.advance(); // consume 'return'
         
-        let value = if matches!(core.peek().kind, TokenKind::Semicolon | TokenKind::Newline | TokenKind::Eof | TokenKind::RightBrace) {
+        // `return` takes no value from the next line
+        let value = if core.check(&TokenKind::Semicolon) || core.can_insert_semicolon() {
             None
         } else {
             Some(expr_parser.parse_expression(core)?)
         };
         
-        core.consume_semicolon_or_newline();
+        core.consume_statement_end()?;
         
         Ok(Stmt::Return { value, span: start_span })
     }
     
+    /// Parse a keyword-only statement: `break;`, `continue;` or `debugger;`
+    fn parse_jump_statement(&mut self, core: &mut ParserCore, make: fn(Span) -> Stmt) -> Result<Stmt> {
+        let span = core.advance().span; // consume the keyword
+        core.consume_statement_end()?;
+        Ok(make(span))
+    }
+    
     /// Parse block statement: `{ statements }`
     fn parse_block_statement<E: ExpressionParser>(
         &mut self, 
@@ -284,17 +600,6 @@ impl StatementParser {
         let mut statements = Vec::new();
         
         while !core.check(&TokenKind::RightBrace) && !core.is_at_end() {
-            // Skip newlines and whitespace
-            if matches!(core.peek().kind, TokenKind::Newline) {
-                core.advance();
-                continue;
-            }
-            
-            // If we hit a closing brace, break out of the loop
-            if core.check(&TokenKind::RightBrace) {
-                break;
-            }
-            
             statements.push(self.parse_statement(core, expr_parser)?);
         }
         
@@ -309,7 +614,32 @@ impl StatementParser {
         expr_parser: &mut E
     ) -> Result<Stmt> {
         let expr = expr_parser.parse_expression(core)?;
-        core.consume_semicolon_or_newline();
+        core.consume_statement_end()?;
         Ok(Stmt::Expression(expr))
     }
-}
\ No newline at end of file
+}
+
+/// Track a directive prologue, the string statements that start a program
+/// or function body: while `prologue` holds, a `"use strict"` statement
+/// makes the code after it strict, and any other kind of statement ends it
+pub(crate) fn note_directive(core: &mut ParserCore, statement: &Stmt, prologue: &mut bool) {
+    *prologue = *prologue && statement.directive().is_some();
+    if *prologue && statement.is_use_strict_directive() {
+        core.strict = true;
+    }
+}
+
+/// Reject a parameter list that names a parameter twice, as strict mode
+/// code, arrow functions and methods must not
+pub(crate) fn check_unique_parameters(params: &[(String, Span)]) -> Result<()> {
+    for (index, (name, span)) in params.iter().enumerate() {
+        if let Some((_, first)) = params[..index].iter().find(|(earlier, _)| earlier == name) {
+            return Err(Error::parser(
+                ErrorCode::DuplicateParameter,
+                format!("Duplicate parameter name '{}' not allowed in this context", name),
+                *span,
+            ).with_note(format!("'{}' first declared here", name), *first));
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
 #[cfg(test)]
 mod tests {
     use crate::lexer::Lexer;
-    use crate::error::Result;
-    use crate::parser::{Parser, Program, Stmt, Expr, BinaryOp};
+    use crate::error::{ErrorCode, Result, Span};
+    use crate::parser::{Parser, Program, Stmt, VarKind, Expr, BinaryOp, UnaryOp};
     
     fn parse_source(source: &str) -> Result<Program> {
         let mut lexer = Lexer::new(source);
@@ -11,6 +11,10 @@ mod tests {
         parser.parse()
     }
     
+    fn names(params: &[(String, Span)]) -> Vec<&str> {
+        params.iter().map(|(name, _)| name.as_str()).collect()
+    }
+    
     #[test]
     fn test_function_declaration() {
         let source = "function add(a, b) { return a + b; }";
@@ -20,7 +24,10 @@ mod tests {
         match &program.statements[0] {
             Stmt::FunctionDecl { name, params, body, .. } => {
                 assert_eq!(name, "add");
-                assert_eq!(params, &["a", "b"]);
+                assert_eq!(names(params), ["a", "b"]);
+                // Each parameter keeps the span of its name
+                let columns: Vec<_> = params.iter().map(|(_, span)| (span.column, span.len())).collect();
+                assert_eq!(columns, [(14, 1), (17, 1)]);
                 assert_eq!(body.len(), 1);
                 
                 // Check that body contains a return statement
@@ -125,7 +132,8 @@ mod tests {
         
         // Test let declaration
         match &program.statements[0] {
-            Stmt::VarDecl { name, init, .. } => {
+            Stmt::VarDecl { kind, name, init, .. } => {
+                assert_eq!(*kind, VarKind::Let);
                 assert_eq!(name, "x");
                 assert!(init.is_some());
             }
@@ -134,7 +142,8 @@ mod tests {
         
         // Test const declaration  
         match &program.statements[1] {
-            Stmt::VarDecl { name, init, .. } => {
+            Stmt::VarDecl { kind, name, init, .. } => {
+                assert_eq!(*kind, VarKind::Const);
                 assert_eq!(name, "y");
                 assert!(init.is_some());
             }
@@ -143,7 +152,8 @@ mod tests {
         
         // Test var declaration without initializer
         match &program.statements[2] {
-            Stmt::VarDecl { name, init, .. } => {
+            Stmt::VarDecl { kind, name, init, .. } => {
+                assert_eq!(*kind, VarKind::Var);
                 assert_eq!(name, "z");
                 assert!(init.is_none());
             }
@@ -173,6 +183,18 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_compound_assignment_keeps_its_target_once() {
+        let program = parse_source("a[i] += 1;").unwrap();
+        match &program.statements[0] {
+            Stmt::Expression(Expr::Assignment { op: Some(BinaryOp::Add), left, right, .. }) => {
+                assert!(matches!(left.as_ref(), Expr::Member { computed: true, .. }));
+                assert!(matches!(right.as_ref(), Expr::Literal(_)));
+            }
+            other => panic!("Expected compound assignment, got {:?}", other),
+        }
+    }
+    
     #[test]
     fn test_binary_expressions() {
         let source = "x + y * z;";
@@ -277,4 +299,554 @@ mod tests {
             _ => panic!("Expected variable declaration"),
         }
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_contextual_keywords_as_identifiers() {
+        let program = parse_source("let of = 1; let async = of; from + async").unwrap();
+        assert_eq!(program.statements.len(), 3);
+        
+        match &program.statements[0] {
+            Stmt::VarDecl { name, .. } => assert_eq!(name, "of"),
+            _ => panic!("Expected variable declaration"),
+        }
+        match &program.statements[1] {
+            Stmt::VarDecl { name, init: Some(Expr::Identifier { name: init, .. }), .. } => {
+                assert_eq!(name, "async");
+                assert_eq!(init, "of");
+            }
+            _ => panic!("Expected variable declaration"),
+        }
+    }
+    
+    #[test]
+    fn test_for_of_statement() {
+        let program = parse_source("for (let item of items) { item; }").unwrap();
+        
+        match &program.statements[0] {
+            Stmt::ForOf { left, right, .. } => {
+                assert!(matches!(left.as_ref(), Stmt::VarDecl { name, init: None, .. } if name == "item"));
+                assert!(matches!(right, Expr::Identifier { name, .. } if name == "items"));
+            }
+            _ => panic!("Expected for-of statement"),
+        }
+        
+        // `of` as the loop variable and as the iterable still parses
+        let program = parse_source("for (of of of) {}").unwrap();
+        match &program.statements[0] {
+            Stmt::ForOf { left, right, .. } => {
+                assert!(matches!(left.as_ref(), Stmt::Expression(Expr::Identifier { name, .. }) if name == "of"));
+                assert!(matches!(right, Expr::Identifier { name, .. } if name == "of"));
+            }
+            _ => panic!("Expected for-of statement"),
+        }
+        
+        // Classic for loops are unaffected
+        let program = parse_source("for (let i = 0; i < 3; i = i + 1) {}").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::For { .. }));
+    }
+    
+    #[test]
+    fn test_array_literal_with_holes() {
+        let program = parse_source("[1, , 2, ];").unwrap();
+        match &program.statements[0] {
+            Stmt::Expression(Expr::Array { elements, .. }) => {
+                assert_eq!(elements.len(), 3);
+                assert!(elements[0].is_some());
+                assert!(elements[1].is_none());
+                assert!(elements[2].is_some());
+            }
+            _ => panic!("Expected array literal"),
+        }
+    }
+    
+    #[test]
+    fn test_unary_base_of_exponent_is_rejected() {
+        let error = parse_source("let x = -2 ** 2;").unwrap_err();
+        assert_eq!(error.to_string(), "Parser error: Unary operator used immediately before exponentiation \
+                                       expression; write (-2) ** 2 or -(2 ** 2) at line 1, column 9");
+        let error = parse_source("a * !b ** c;").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::AmbiguousExponentiation);
+        assert!(error.to_string().contains("write (!b) ** c or !(b ** c)"), "{}", error);
+        for source in ["async function f() { await x ** 2; }", "~1 ** 2;", "2 ** -3 ** 2;"] {
+            assert_eq!(parse_source(source).unwrap_err().code(), ErrorCode::AmbiguousExponentiation, "{}", source);
+        }
+        
+        // Parentheses, a unary exponent and a unary whole are all fine
+        for source in ["(-2) ** 2;", "-(2 ** 2);", "2 ** -3;", "-2 * 2;", "2 ** 3 ** -2;"] {
+            assert!(parse_source(source).is_ok(), "{} should parse", source);
+        }
+    }
+    
+    #[test]
+    fn test_prefix_operators_apply_to_calls_and_members() {
+        let operand = |source: &str| match parse_source(source).unwrap().statements.remove(0) {
+            Stmt::Expression(Expr::Unary { operand, .. }) => *operand,
+            other => panic!("{}: expected a unary expression, got {:?}", source, other),
+        };
+        assert!(matches!(operand("typeof x.y;"), Expr::Member { .. }));
+        assert!(matches!(operand("typeof f();"), Expr::Call { .. }));
+        assert!(matches!(operand("!x.y.z();"), Expr::Call { .. }));
+        assert!(matches!(operand("void 0;"), Expr::Literal(_)));
+        assert!(matches!(operand("!function () {}();"), Expr::Call { .. }));
+        assert!(matches!(operand("!!~s.indexOf(q);"), Expr::Unary { op: UnaryOp::LogicalNot, .. }));
+        assert!(matches!(operand("typeof typeof x;"), Expr::Unary { op: UnaryOp::TypeOf, .. }));
+        
+        // ...and bind tighter than any binary operator
+        let program = parse_source("typeof x === 'undefined';").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::Expression(Expr::Binary { op: BinaryOp::StrictEqual, left, .. })
+            if matches!(left.as_ref(), Expr::Unary { op: UnaryOp::TypeOf, .. })));
+        let error = parse_source("typeof x ** 2;").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::AmbiguousExponentiation);
+        assert!(error.to_string().contains("write (typeof x) ** 2 or typeof (x ** 2)"), "{}", error);
+    }
+    
+    #[test]
+    fn test_trailing_commas() {
+        let array_lengths = [("[1, 2, 3,];", 3), ("[1,,];", 2), ("[,];", 1), ("[,,];", 2), ("[];", 0)];
+        for (source, length) in array_lengths {
+            match &parse_source(source).unwrap().statements[0] {
+                Stmt::Expression(Expr::Array { elements, .. }) => assert_eq!(elements.len(), length, "{}", source),
+                other => panic!("Expected array literal for {}, got {:?}", source, other),
+            }
+        }
+        
+        let program = parse_source("let o = { a: 1, b, ...c, };").unwrap();
+        assert!(matches!(&program.statements[0],
+            Stmt::VarDecl { init: Some(Expr::Object { properties, .. }), .. } if properties.len() == 3));
+        
+        let program = parse_source("f(1, 2,); new C(1,);").unwrap();
+        assert_eq!(program.statements[0].to_string(), "ExpressionStatement(f(1, 2))");
+        assert!(matches!(&program.statements[1], Stmt::Expression(Expr::New { args, .. }) if args.len() == 1));
+        
+        let program = parse_source("function f(a, b,) {} let g = function (a,) {}; let h = (a, b,) => a; class K { m(a,) {} }").unwrap();
+        assert!(matches!(&program.statements[0], Stmt::FunctionDecl { params, .. } if params.len() == 2));
+        assert!(matches!(&program.statements[1],
+            Stmt::VarDecl { init: Some(Expr::Function { params, .. }), .. } if params.len() == 1));
+        assert!(matches!(&program.statements[2],
+            Stmt::VarDecl { init: Some(Expr::Arrow { params, .. }), .. } if params.len() == 2));
+        assert!(matches!(&program.statements[3],
+            Stmt::ClassDecl { body, .. } if body.methods[0].params.len() == 1));
+        
+        // A comma needs something before it everywhere but array literals
+        for source in ["f(,);", "f(1,,);", "function f(,) {}", "function f(a,,) {}", "let o = {,};", "let o = { a: 1,, };", "let h = (,) => 1;"] {
+            assert!(parse_source(source).is_err(), "{} should not parse", source);
+        }
+    }
+    
+    #[test]
+    fn test_function_expression() {
+        let program = parse_source("let f = function (a) { return a; };").unwrap();
+        match &program.statements[0] {
+            Stmt::VarDecl { init: Some(Expr::Function { name, params, body, .. }), .. } => {
+                assert!(name.is_none());
+                assert_eq!(names(params), ["a"]);
+                assert_eq!(body.len(), 1);
+            }
+            _ => panic!("Expected function expression initializer"),
+        }
+    }
+    
+    #[test]
+    fn test_object_literal_with_spread() {
+        use crate::parser::ast::{PropertyDefinition, PropertyKey};
+        
+        let program = parse_source("let o = { a: 1, ...rest, [k]: 2, b, 'c': 3 };").unwrap();
+        match &program.statements[0] {
+            Stmt::VarDecl { init: Some(Expr::Object { properties, .. }), .. } => {
+                assert_eq!(properties.len(), 5);
+                assert!(matches!(&properties[0], PropertyDefinition::Property(p) if p.key == PropertyKey::Identifier("a".to_string())));
+                assert!(matches!(&properties[1], PropertyDefinition::SpreadProperty { argument: Expr::Identifier { name, .. }, .. } if name == "rest"));
+                assert!(matches!(&properties[2], PropertyDefinition::Property(p) if matches!(p.key, PropertyKey::Computed(_))));
+                assert!(matches!(&properties[3], PropertyDefinition::Property(p) if matches!(&p.value, Expr::Identifier { name, .. } if name == "b")));
+                assert!(matches!(&properties[4], PropertyDefinition::Property(p) if p.key == PropertyKey::String("c".to_string())));
+            }
+            _ => panic!("Expected object literal initializer"),
+        }
+    }
+    
+    fn parse_live(source: &str) -> Result<Program> {
+        Parser::from_source(Lexer::new(source)).parse()
+    }
+    
+    #[test]
+    fn test_division_chain_vs_regex_literal() {
+        use crate::parser::ast::Literal;
+        
+        for parse in [parse_source, parse_live] {
+            let division = parse("a = b / c / d").unwrap();
+            match &division.statements[0] {
+                Stmt::Expression(Expr::Assignment { right, .. }) => {
+                    assert!(matches!(right.as_ref(), Expr::Binary { op: BinaryOp::Divide, left, .. }
+                        if matches!(left.as_ref(), Expr::Binary { op: BinaryOp::Divide, .. })));
+                }
+                _ => panic!("Expected assignment of a division chain"),
+            }
+            
+            let regex = parse("a = /c/ .test(s)").unwrap();
+            match &regex.statements[0] {
+                Stmt::Expression(Expr::Assignment { right, .. }) => match right.as_ref() {
+                    Expr::Call { callee, .. } => match callee.as_ref() {
+                        Expr::Member { object, .. } => assert_eq!(
+                            object.as_ref(),
+                            &Expr::Literal(Literal::RegExp { pattern: "c".to_string(), flags: String::new() })
+                        ),
+                        _ => panic!("Expected member call on the regex"),
+                    },
+                    _ => panic!("Expected call expression"),
+                },
+                _ => panic!("Expected assignment"),
+            }
+        }
+    }
+    
+    #[test]
+    fn test_live_lexer_resolves_regex_after_paren() {
+        // The previous-token guess reads `/` after `)` as division; only the
+        // parser knows an `if` body expects an operand here
+        let source = "if (x) /=re/.test(s);";
+        assert!(parse_source(source).is_err());
+        
+        let program = parse_live(source).unwrap();
+        assert!(matches!(&program.statements[0], Stmt::If { .. }));
+    }
+    
+    #[test]
+    fn test_live_lexer_reports_lexer_errors() {
+        let error = parse_live("let a = 1;\nlet b = \"open").unwrap_err();
+        assert!(matches!(error, crate::error::Error::Lexer { .. }));
+    }
+    
+    #[test]
+    fn test_keyword_after_if_body_is_not_else() {
+        let program = parse_source("function f(n) { if (n) return 1; return 0; }").unwrap();
+        match &program.statements[0] {
+            Stmt::FunctionDecl { body, .. } => {
+                assert_eq!(body.len(), 2);
+                assert!(matches!(&body[0], Stmt::If { else_stmt: None, .. }));
+                assert!(matches!(&body[1], Stmt::Return { .. }));
+            }
+            _ => panic!("Expected function declaration"),
+        }
+    }
+    
+    #[test]
+    fn test_generator_function_and_yield() {
+        let program = parse_source("function* g() { yield 1; let x = yield; yield\n2; }").unwrap();
+        match &program.statements[0] {
+            Stmt::FunctionDecl { is_generator, body, .. } => {
+                assert!(*is_generator);
+                assert!(matches!(&body[0], Stmt::Expression(Expr::Yield { argument: Some(_), .. })));
+                assert!(matches!(&body[1], Stmt::VarDecl { init: Some(Expr::Yield { argument: None, .. }), .. }));
+                // A line break ends a bare `yield`
+                assert!(matches!(&body[2], Stmt::Expression(Expr::Yield { argument: None, .. })));
+            }
+            _ => panic!("Expected generator declaration"),
+        }
+        
+        let program = parse_source("let g = function* () {}; function f() {}").unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDecl { init: Some(Expr::Function { is_generator: true, .. }), .. }
+        ));
+        assert!(matches!(&program.statements[1], Stmt::FunctionDecl { is_generator: false, .. }));
+        
+        // Delegation is not supported, and nested functions are not generators
+        assert!(parse_source("function* g() { yield* other(); }").is_err());
+        assert!(parse_source("function* g() { function f() { yield 1; } }").is_err());
+    }
+    
+    #[test]
+    fn test_async_function_and_await() {
+        let program = parse_source("async function f() { let x = await g(); await x + 1; }").unwrap();
+        match &program.statements[0] {
+            Stmt::FunctionDecl { is_async, is_generator: false, body, .. } => {
+                assert!(*is_async);
+                assert!(matches!(&body[0], Stmt::VarDecl { init: Some(Expr::Await { .. }), .. }));
+                // `await` binds tighter than `+`
+                assert!(matches!(
+                    &body[1],
+                    Stmt::Expression(Expr::Binary { left, .. }) if matches!(**left, Expr::Await { .. })
+                ));
+            }
+            _ => panic!("Expected async function declaration"),
+        }
+        
+        let program = parse_source("let f = async function () {}; async\nfunction g() {}").unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDecl { init: Some(Expr::Function { is_async: true, .. }), .. }
+        ));
+        // A line break after `async` makes it an identifier
+        assert!(matches!(&program.statements[1], Stmt::Expression(Expr::Identifier { .. })));
+        assert!(matches!(&program.statements[2], Stmt::FunctionDecl { is_async: false, .. }));
+        
+        // Outside async functions `await` is an identifier, and async
+        // generators are not supported
+        assert!(parse_source("let await = 1;").is_ok());
+        assert!(parse_source("async function f() { function g() { await 1; } }").is_err());
+        assert!(parse_source("async function* g() {}").is_err());
+    }
+    
+    #[test]
+    fn test_reserved_words_as_member_names() {
+        let program = parse_source("g.return(1);").unwrap();
+        match &program.statements[0] {
+            Stmt::Expression(Expr::Call { callee, .. }) => {
+                assert!(matches!(callee.as_ref(), Expr::Member { property, .. }
+                    if matches!(property.as_ref(), Expr::Identifier { name, .. } if name == "return")));
+            }
+            _ => panic!("Expected method call"),
+        }
+    }
+    
+    #[test]
+    fn test_class_declaration() {
+        let source = "class Point { constructor(x) { this.x = x; } norm() { return this.x; } static of(x) { return new Point(x); } }";
+        let program = parse_source(source).unwrap();
+        match &program.statements[0] {
+            Stmt::ClassDecl { name, body, .. } => {
+                assert_eq!(name, "Point");
+                assert_eq!(names(&body.constructor.as_ref().unwrap().params), ["x"]);
+                let methods: Vec<(&str, bool)> = body.methods.iter()
+                    .map(|method| (method.name.as_str(), method.is_static))
+                    .collect();
+                assert_eq!(methods, vec![("norm", false), ("of", true)]);
+            }
+            _ => panic!("Expected class declaration"),
+        }
+        
+        // Unsupported class features are rejected
+        assert!(parse_source("class A extends B {}").is_err());
+        assert!(parse_source("class A { constructor() {} constructor() {} }").is_err());
+        assert!(parse_source("class A { x = 1; }").is_err());
+        assert!(parse_source("class A { *gen() {} }").is_err());
+    }
+    
+    #[test]
+    fn test_new_and_class_expressions() {
+        let program = parse_source("let C = class {}; new a.B(1).c; new B; x instanceof C;").unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::VarDecl { init: Some(Expr::Class { name: None, .. }), .. }
+        ));
+        // The member access after the arguments applies to the new object
+        match &program.statements[1] {
+            Stmt::Expression(Expr::Member { object, .. }) => match object.as_ref() {
+                Expr::New { callee, args, .. } => {
+                    assert!(matches!(callee.as_ref(), Expr::Member { .. }));
+                    assert_eq!(args.len(), 1);
+                }
+                _ => panic!("Expected new expression"),
+            },
+            _ => panic!("Expected member expression"),
+        }
+        assert!(matches!(&program.statements[2], Stmt::Expression(Expr::New { args, .. }) if args.is_empty()));
+        assert!(matches!(
+            &program.statements[3],
+            Stmt::Expression(Expr::Binary { op: BinaryOp::InstanceOf, .. })
+        ));
+    }
+    
+    #[test]
+    fn test_delete_and_in() {
+        let program = parse_source("delete o.a; 'a' in o === false;").unwrap();
+        assert!(matches!(
+            &program.statements[0],
+            Stmt::Expression(Expr::Unary { op: UnaryOp::Delete, operand, .. }) if matches!(operand.as_ref(), Expr::Member { .. })
+        ));
+        // `in` binds tighter than equality
+        match &program.statements[1] {
+            Stmt::Expression(Expr::Binary { op: BinaryOp::StrictEqual, left, .. }) => {
+                assert!(matches!(left.as_ref(), Expr::Binary { op: BinaryOp::In, .. }));
+            }
+            other => panic!("Expected equality, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_error_line_after_long_block_comment() {
+        let comment_lines = vec![" * filler"; 98].join("\n");
+        let source = format!("/*\n{}\n */\nlet = 1;", comment_lines);
+        let error = parse_source(&source).unwrap_err();
+        assert_eq!(error.span().map(|span| span.line), Some(101));
+        
+        // A `/*` in a line comment does not swallow the next line
+        assert!(parse_source("let a = 1; // /*\nlet b = a;").unwrap().statements.len() == 2);
+    }
+    
+    #[test]
+    fn test_debugger_statement() {
+        let program = parse_source("debugger\nx;\n debugger;").unwrap();
+        assert!(matches!(program.statements[0], Stmt::Debugger { .. }));
+        assert!(matches!(program.statements[1], Stmt::Expression(_)));
+        assert!(matches!(program.statements[2], Stmt::Debugger { span } if span.line == 3));
+    }
+    
+    #[test]
+    fn test_arrow_functions() {
+        let program = parse_source("let f = (a, b) => a + b; g(x => { return x; }); h(async () => await p);").unwrap();
+        match &program.statements[0] {
+            Stmt::VarDecl { init: Some(Expr::Arrow { params, body, is_async: false, .. }), .. } => {
+                assert_eq!(names(params), ["a", "b"]);
+                assert!(matches!(&body[..], [Stmt::Return { value: Some(Expr::Binary { op: BinaryOp::Add, .. }), .. }]));
+            }
+            other => panic!("Expected arrow function, got {:?}", other),
+        }
+        assert_eq!(program.statements[1].to_string(), "ExpressionStatement(g((x) => { 1 statements }))");
+        assert_eq!(program.statements[2].to_string(), "ExpressionStatement(h(async () => { 1 statements }))");
+        
+        // Without `=>` the same tokens are ordinary expressions
+        let program = parse_source("(a); async(b); async;").unwrap();
+        assert_eq!(program.statements[0].to_string(), "ExpressionStatement(a)");
+        assert_eq!(program.statements[1].to_string(), "ExpressionStatement(async(b))");
+        assert_eq!(program.statements[2].to_string(), "ExpressionStatement(async)");
+    }
+    
+    #[test]
+    fn test_abandoned_arrow_attempt_does_not_leak_errors() {
+        // The speculative parameter list fails at `1`; the error is the
+        // parenthesized expression's
+        let error = parse_source("let x = (a, 1);").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ExpectedToken);
+        assert!(!error.to_string().contains("parameter"), "{}", error);
+        
+        // Once `=>` is seen, errors in the body are reported as they are
+        let error = parse_source("let f = (a) => { let = 1; };").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::ExpectedToken);
+        assert_eq!(error.span().map(|span| span.column), Some(22), "{}", error);
+        
+        // `=>` must be on the line of its parameters
+        assert!(parse_source("let f = (a)\n=> a;").is_err());
+    }
+    
+    #[test]
+    fn test_duplicate_parameters_depend_on_strictness() {
+        // Sloppy mode functions may repeat a name; the last one wins
+        assert!(parse_source("function f(a, a) {}").is_ok());
+        assert!(parse_source("let f = function (a, b, a) {};").is_ok());
+        // A directive applies to the whole function, parameters included,
+        // and to the functions inside it
+        for source in [
+            "'use strict'; function f(a, a) {}",
+            "function f(a, a) { 'use strict'; }",
+            "function f() { 'use strict'; return function (b, b) {}; }",
+            "function f() { 'a'; 'use strict'; function g(c, c) {} }",
+            "class C { m(a, a) {} }",
+            "let f = (a, a) => a;",
+            "let o = { m(a, a) {} };",
+        ] {
+            assert_eq!(parse_source(source).unwrap_err().code(), ErrorCode::DuplicateParameter, "{}", source);
+        }
+        // Strictness ends with the function or class that set it, and a
+        // directive after the prologue is just a string
+        assert!(parse_source("function f() { 'use strict'; } function g(a, a) {}").is_ok());
+        assert!(parse_source("class C {} function g(a, a) {}").is_ok());
+        assert!(parse_source("function f(a, a) { f(); 'use strict'; }").is_ok());
+        
+        // The error is at the second name, with a note at the first
+        let error = parse_source("let f = (x, y, x) => y;").unwrap_err();
+        assert_eq!(error.span().map(|span| span.column), Some(16));
+        assert_eq!(error.note().map(|note| note.span.column), Some(10));
+    }
+    
+    #[test]
+    fn test_checkpoint_and_rewind() {
+        use crate::lexer::TokenKind;
+        use crate::parser::core::ParserCore;
+        
+        let mut core = ParserCore::new(Lexer::new("a b c").tokenize().unwrap());
+        assert_eq!(core.lookahead(2), &TokenKind::Identifier("c".to_string()));
+        assert_eq!(core.lookahead(10), &TokenKind::Eof);
+        
+        let state = core.checkpoint();
+        core.advance();
+        core.in_async = true;
+        core.rewind(state);
+        assert_eq!(core.consume_identifier("name").unwrap(), "a");
+        assert!(!core.in_async);
+        
+        // A failed attempt leaves the position untouched
+        let failed = core.try_parse(|core| {
+            core.advance();
+            core.consume(&TokenKind::Semicolon, "no semicolon")
+        });
+        assert!(failed.is_none());
+        assert_eq!(core.lookahead(0), &TokenKind::Identifier("b".to_string()));
+        assert_eq!(core.try_parse(|core| core.consume_identifier("name")), Some("b".to_string()));
+        assert_eq!(core.lookahead(0), &TokenKind::Identifier("c".to_string()));
+    }
+    
+    #[test]
+    fn test_expression_only_rejects_trailing_tokens() {
+        let parse = |source: &str| Parser::from_source(Lexer::new(source)).parse_expression_only();
+        
+        assert!(matches!(parse("1 + 2").unwrap(), Expr::Binary { op: BinaryOp::Add, .. }));
+        // A line break ends nothing, so a continued expression still parses
+        assert!(parse("a\n+ b").is_ok());
+        for (source, found) in [("1 + 2 3", "3"), ("a\nb", "b"), ("x;", ";"), ("f() {}", "{")] {
+            let error = parse(source).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::UnexpectedToken, "{}", source);
+            assert!(error.to_string().contains(&format!("Expected a single expression, found {}", found)), "{}: {}", source, error);
+        }
+        
+        // `{` starts an object and `function` an expression, unlike in a program
+        assert!(matches!(parse("{ a: 1 }").unwrap(), Expr::Object { .. }));
+        assert!(matches!(parse("function () { return 1; }").unwrap(), Expr::Function { .. }));
+        assert!(matches!(parse_source("{ a; }").unwrap().statements[0], Stmt::Block { .. }));
+    }
+    
+    #[test]
+    fn test_validate_accepts_parsed_programs() {
+        use crate::parser::ast::validate_source;
+        
+        let source = "function f(a) { for (let i = 0; i < a; i++) { g(i); } }\nclass C { m() { return () => this; } }\nfor (const x of [1]) {}";
+        let program = parse_source(source).unwrap();
+        assert_eq!(validate_source(&program, source), vec![]);
+    }
+    
+    #[test]
+    fn test_validate_reports_broken_trees() {
+        use crate::error::Span;
+        use crate::parser::ast::{validate, validate_source, ViolationKind};
+        
+        let span = |start, end| Span::new(start, end, 1, start as u32 + 1);
+        let kinds = |program: &Program| validate(program).into_iter().map(|violation| violation.kind).collect::<Vec<_>>();
+        
+        // Statements out of order, and a body statement outside its function
+        let program = Program { statements: vec![
+            Stmt::Break { span: span(10, 15) },
+            Stmt::Continue { span: span(2, 10) },
+            Stmt::FunctionDecl {
+                name: "f".to_string(),
+                params: vec![(String::new(), span(25, 26))],
+                body: vec![Stmt::Empty { span: span(40, 41) }],
+                is_generator: false,
+                is_async: false,
+                span: span(20, 30),
+            },
+        ] };
+        assert_eq!(kinds(&program), vec![
+            ViolationKind::OutOfOrder { previous: span(10, 15) },
+            ViolationKind::EmptyName,
+            ViolationKind::Escapes { parent: span(20, 30) },
+        ]);
+        
+        // A for-of over a declaration with an initializer, and a const without one
+        let declaration = |kind, init| Stmt::VarDecl { kind, name: "x".to_string(), init, span: span(5, 8) };
+        let program = Program { statements: vec![
+            Stmt::ForOf {
+                left: Box::new(declaration(VarKind::Let, Some(Expr::Identifier { name: "y".to_string(), span: span(9, 10) }))),
+                right: Expr::Identifier { name: "xs".to_string(), span: span(14, 16) },
+                body: Box::new(Stmt::Empty { span: span(17, 18) }),
+                span: span(0, 3),
+            },
+            Stmt::Block { statements: vec![declaration(VarKind::Const, None)], span: span(3, 4) },
+        ] };
+        assert!(matches!(kinds(&program)[..], [ViolationKind::MalformedFor(_), ViolationKind::ConstWithoutInitializer]));
+        
+        // Spans past the end of the source, or ending before they start
+        let program = Program { statements: vec![Stmt::Debugger { span: span(0, 8) }, Stmt::Empty { span: span(12, 11) }] };
+        let kinds: Vec<_> = validate_source(&program, "debug").into_iter().map(|violation| violation.kind).collect();
+        assert_eq!(kinds, vec![ViolationKind::OutOfBounds { source_len: 5 }, ViolationKind::InvertedSpan]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Read-Eval-Print Loop
+//!
+//! The line handling behind the interactive shell, kept free of terminal
+//! I/O so it can be driven programmatically. `Repl::run` reads and
+//! prints through the engine's streams (see `stdio`).
+
+use std::io::Write;
+
+use crate::{Diagnostic, Engine, Lexer, Parser, Result, Value};
+
+/// What the shell should do after a line of input
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplOutput {
+    /// Print this text to stdout
+    Print(String),
+    /// Print this text to stderr
+    Error(String),
+    /// Nothing to show
+    Silent,
+    /// Leave the REPL
+    Exit,
+}
+
+/// Interactive session over a persistent engine
+///
+/// Results are echoed with `Value::inspect` and kept in `_`, `_1`, `_2`, ...
+pub struct Repl {
+    engine: Engine,
+}
+
+impl Repl {
+    /// Create a REPL with a fresh persistent engine
+    pub fn new() -> Self {
+        Self { engine: Engine::new_persistent() }
+    }
+    
+    /// The engine evaluating the input
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+    
+    /// The engine evaluating the input, for installing handlers
+    pub fn engine_mut(&mut self) -> &mut Engine {
+        &mut self.engine
+    }
+    
+    /// Prompt for lines on the engine's stdin until it ends or the user
+    /// exits, printing results to its stdout and errors to its stderr
+    pub fn run(&mut self, prompt: &str) -> Result<()> {
+        let io = self.engine.io();
+        loop {
+            write!(io.borrow_mut().stdout, "{}", prompt)?;
+            io.borrow_mut().stdout.flush()?;
+            let Some(line) = io.borrow_mut().read_line()? else {
+                writeln!(io.borrow_mut().stdout, "\nGoodbye!")?;
+                return Ok(());
+            };
+            match self.eval_line(&line) {
+                ReplOutput::Print(text) => writeln!(io.borrow_mut().stdout, "{}", text)?,
+                ReplOutput::Error(text) => writeln!(io.borrow_mut().stderr, "{}", text)?,
+                ReplOutput::Silent => {}
+                ReplOutput::Exit => return Ok(()),
+            }
+        }
+    }
+    
+    /// Handle one line: a meta-command or JavaScript source
+    pub fn eval_line(&mut self, line: &str) -> ReplOutput {
+        match line.trim() {
+            "" => ReplOutput::Silent,
+            "exit" | ".exit" => ReplOutput::Exit,
+            ".help" => ReplOutput::Print(
+                [
+                    "Available commands:",
+                    "  .exit     - Exit the REPL",
+                    "  .help     - Show this help message",
+                    "  .results  - List the stored results _1, _2, ...",
+                    "  <expr>    - Evaluate JavaScript expression",
+                ]
+                .join("\n"),
+            ),
+            ".results" => {
+                let history = self.engine.result_history();
+                if history.is_empty() {
+                    return ReplOutput::Print("No results yet".to_string());
+                }
+                let lines: Vec<String> = history.iter().enumerate()
+                    .map(|(i, value)| format!("_{} = {}", i + 1, value.inspect()))
+                    .collect();
+                ReplOutput::Print(lines.join("\n"))
+            }
+            source => match self.evaluate(source) {
+                Ok(Value::Undefined) => ReplOutput::Silent,
+                Ok(value) => ReplOutput::Print(value.inspect()),
+                Err(e) => ReplOutput::Error(Diagnostic::from(e).to_string()),
+            },
+        }
+    }
+    
+    /// Run a line of source
+    ///
+    /// As in Node, a line starting with `{` that is a whole expression is
+    /// an object literal, not a block: `{ a: 1 }` shows the object. Any
+    /// other line runs as a program whose top-level declarations outlive
+    /// it (see `Engine::eval_repl_input`).
+    fn evaluate(&mut self, source: &str) -> Result<Value> {
+        if source.starts_with('{') {
+            let is_expression = Parser::from_source(Lexer::new(source)).parse_expression_only().is_ok();
+            if is_expression {
+                return self.engine.eval_expression(source);
+            }
+        }
+        self.engine.eval_repl_input(source)
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_underscore_holds_last_result() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval_line("41"), ReplOutput::Print("41".to_string()));
+        assert_eq!(repl.eval_line("_ + 1"), ReplOutput::Print("42".to_string()));
+        assert_eq!(repl.eval_line("_"), ReplOutput::Print("42".to_string()));
+        
+        // Undefined results are not stored
+        assert_eq!(repl.eval_line("undefined"), ReplOutput::Silent);
+        assert_eq!(repl.eval_line("_"), ReplOutput::Print("42".to_string()));
+    }
+    
+    #[test]
+    fn test_numbered_history() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval_line(".results"), ReplOutput::Print("No results yet".to_string()));
+        repl.eval_line("'a'");
+        repl.eval_line("[1, 2]");
+        assert_eq!(repl.eval_line("_1 + _2"), ReplOutput::Print("'a1,2'".to_string()));
+        assert_eq!(
+            repl.eval_line(".results"),
+            ReplOutput::Print("_1 = 'a'\n_2 = [ 1, 2 ]\n_3 = 'a1,2'".to_string())
+        );
+    }
+    
+    #[test]
+    fn test_assigning_underscore_disables_it() {
+        let mut repl = Repl::new();
+        repl.eval_line("1");
+        repl.eval_line("_ = 'mine'");
+        repl.eval_line("2");
+        assert_eq!(repl.eval_line("_"), ReplOutput::Print("'mine'".to_string()));
+        // Numbered history is unaffected
+        assert_eq!(repl.eval_line("_3"), ReplOutput::Print("2".to_string()));
+    }
+    
+    #[test]
+    fn test_assigning_underscore_its_own_value_disables_it() {
+        let mut repl = Repl::new();
+        repl.eval_line("1");
+        repl.eval_line("_ = 1");
+        repl.eval_line("2");
+        assert_eq!(repl.eval_line("_"), ReplOutput::Print("1".to_string()));
+    }
+    
+    #[test]
+    fn test_globals_persist_and_errors_are_reported() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval_line("x = 5"), ReplOutput::Print("5".to_string()));
+        assert_eq!(repl.eval_line("x * 2"), ReplOutput::Print("10".to_string()));
+        assert!(matches!(repl.eval_line("null.x"), ReplOutput::Error(_)));
+        assert_eq!(repl.eval_line("x"), ReplOutput::Print("5".to_string()));
+        assert_eq!(repl.eval_line(".exit"), ReplOutput::Exit);
+    }
+    
+    #[test]
+    fn test_leading_brace_is_an_object_when_it_can_be() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval_line("{ a: 1 }"), ReplOutput::Print("{ a: 1 }".to_string()));
+        assert_eq!(repl.eval_line("_.a"), ReplOutput::Print("1".to_string()));
+        // Anything that is not a single expression is still a block
+        assert_eq!(repl.eval_line("{ y = 2; }"), ReplOutput::Silent);
+        assert_eq!(repl.eval_line("y"), ReplOutput::Print("2".to_string()));
+    }
+    
+    #[test]
+    fn test_redeclaring_in_later_input_replaces_the_binding() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.eval_line("let x = 1"), ReplOutput::Silent);
+        assert_eq!(repl.eval_line("function f() { return x; }"), ReplOutput::Silent);
+        assert_eq!(repl.eval_line("let x = 2"), ReplOutput::Silent);
+        assert_eq!(repl.eval_line("x"), ReplOutput::Print("2".to_string()));
+        assert_eq!(repl.eval_line("f()"), ReplOutput::Print("2".to_string()));
+        
+        // `const` is kept across input until the name is declared again
+        repl.eval_line("const c = 1");
+        assert!(matches!(repl.eval_line("c = 2"), ReplOutput::Error(_)));
+        repl.eval_line("let c = 3");
+        assert_eq!(repl.eval_line("c = 4"), ReplOutput::Print("4".to_string()));
+        
+        // Functions, classes and even built-ins can be declared again
+        repl.eval_line("class A {}");
+        repl.eval_line("class A { constructor() { this.v = 1; } }");
+        assert_eq!(repl.eval_line("new A().v"), ReplOutput::Print("1".to_string()));
+        repl.eval_line("let parseInt = 5");
+        assert_eq!(repl.eval_line("parseInt"), ReplOutput::Print("5".to_string()));
+    }
+    
+    #[test]
+    fn test_redeclaring_in_one_input_is_an_error() {
+        let mut repl = Repl::new();
+        let output = repl.eval_line("let y = 1; let y = 2");
+        assert!(matches!(&output, ReplOutput::Error(message) if message.contains("already declared")), "{:?}", output);
+        // Nothing from the failed input was declared
+        assert!(matches!(repl.eval_line("y"), ReplOutput::Error(_)));
+        assert_eq!(repl.eval_line("let y = 3; y"), ReplOutput::Print("3".to_string()));
+    }
+}
@@ -0,0 +1,111 @@
+//! Standard streams of an engine
+//!
+//! An `EngineIo` is where an engine writes and reads instead of the
+//! process's own streams. The engine shares it with every VM it creates,
+//! and everything either prints goes through it:
+//!
+//! - stdout: `console.log`, `console.info` and `console.debug`, `print`,
+//!   the completion value `Engine::execute` prints, the AST and bytecode
+//!   of the debug modes, and the REPL's prompt and results;
+//! - stderr: `console.error` and `console.warn`, errors thrown by
+//!   callbacks and unhandled rejections without a handler, the REPL's
+//!   errors, and the instruction trace and `debugger` and NaN notes of a
+//!   VM with `VMConfig::trace_execution`, so they never mix with what the
+//!   script prints;
+//! - stdin: `readLine()` and the REPL's input.
+//!
+//! A console handler (`Engine::set_console_handler`) still takes every
+//! `console` line in place of either stream.
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, Cursor, Write};
+use std::rc::Rc;
+
+/// The streams an engine reads and writes
+pub struct EngineIo {
+    pub stdout: Box<dyn Write>,
+    pub stderr: Box<dyn Write>,
+    pub stdin: Box<dyn BufRead>,
+}
+
+/// An `EngineIo` shared by an engine and its VMs
+pub type SharedIo = Rc<RefCell<EngineIo>>;
+
+impl EngineIo {
+    /// The process's stdout, stderr and stdin
+    pub fn process() -> Self {
+        Self {
+            stdout: Box::new(io::stdout()),
+            stderr: Box::new(io::stderr()),
+            stdin: Box::new(io::BufReader::new(io::stdin())),
+        }
+    }
+
+    /// Streams that read `input` and keep what is written in memory, for
+    /// tests: the `CapturedOutput` shows what has been written so far
+    pub fn capture(input: &str) -> (Self, CapturedOutput) {
+        let output = CapturedOutput::default();
+        let io = Self {
+            stdout: Box::new(SharedBuffer(output.stdout.clone())),
+            stderr: Box::new(SharedBuffer(output.stderr.clone())),
+            stdin: Box::new(Cursor::new(input.as_bytes().to_vec())),
+        };
+        (io, output)
+    }
+
+    /// Share these streams with an engine or VM
+    pub fn shared(self) -> SharedIo {
+        Rc::new(RefCell::new(self))
+    }
+
+    /// Read a line from stdin without its line ending; `None` at the end
+    /// of input
+    pub fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.stdin.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let content = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(content);
+        Ok(Some(line))
+    }
+}
+
+impl Default for EngineIo {
+    fn default() -> Self {
+        Self::process()
+    }
+}
+
+/// What was written to the streams of `EngineIo::capture`
+#[derive(Debug, Clone, Default)]
+pub struct CapturedOutput {
+    stdout: Rc<RefCell<Vec<u8>>>,
+    stderr: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedOutput {
+    /// Everything written to stdout so far
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.stdout.borrow()).into_owned()
+    }
+
+    /// Everything written to stderr so far
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.stderr.borrow()).into_owned()
+    }
+}
+
+/// A stream appending to a buffer that is read elsewhere
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
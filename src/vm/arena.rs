@@ -0,0 +1,92 @@
+//! Frame arena: reusing the objects of literals that don't escape a call
+//!
+//! The compiler marks allocations whose object is only ever used through
+//! its properties (`BytecodeFunction::arena_sites`). With
+//! `VMConfig::frame_arena` on, each frame keeps the two most recent
+//! objects made at each such site. Running the site again reuses one of
+//! them in place once nothing but the arena refers to it, which in a loop
+//! is the object from two iterations back. When the frame returns, its
+//! unreferenced objects become spares for later calls (see
+//! `CallStack::recycle`).
+//!
+//! The reference count is the escape check. An object stored anywhere
+//! else — a global, another object, a closure, the return value — has
+//! more than one reference, so it is never reused: it simply lives on as
+//! an ordinary heap object. Nothing needs copying, and a site the
+//! compiler marked too eagerly only loses the reuse.
+
+use std::rc::Rc;
+use super::value::ObjectData;
+
+/// Objects made at a frame's arena sites, by site number
+#[derive(Debug, Clone, Default)]
+pub struct FrameArena {
+    /// Per site, the newest object first
+    sites: Vec<[Option<Rc<ObjectData>>; 2]>,
+}
+
+impl FrameArena {
+    /// An object made at `site` that nothing outside the arena refers to
+    pub fn reusable(&self, site: usize) -> Option<Rc<ObjectData>> {
+        self.sites.get(site)?.iter().flatten()
+            .find(|object| Rc::strong_count(object) == 1)
+            .cloned()
+    }
+    
+    /// Keep `object`, just made at `site`, letting go of the older of the
+    /// site's two objects
+    pub fn keep(&mut self, site: usize, object: Rc<ObjectData>) {
+        if self.sites.len() <= site {
+            self.sites.resize_with(site + 1, Default::default);
+        }
+        let slots = &mut self.sites[site];
+        slots[1] = slots[0].replace(object);
+    }
+    
+    /// Empty the arena, yielding the objects nothing outside it refers to
+    ///
+    /// The arena keeps its storage, ready for another frame.
+    pub fn drain_unreferenced(&mut self) -> impl Iterator<Item = Rc<ObjectData>> + '_ {
+        self.sites.drain(..).flatten().flatten().filter(|object| Rc::strong_count(object) == 1)
+    }
+    
+    /// Every object the arena holds
+    pub fn objects(&self) -> impl Iterator<Item = &Rc<ObjectData>> {
+        self.sites.iter().flatten().flatten()
+    }
+    
+    /// Whether the arena has room for sites without allocating
+    pub fn has_storage(&self) -> bool {
+        self.sites.capacity() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_objects_are_reusable_once_unreferenced() {
+        let mut arena = FrameArena::default();
+        assert!(arena.reusable(0).is_none());
+        
+        let first = Rc::new(ObjectData::new());
+        arena.keep(1, first.clone());
+        assert!(arena.reusable(0).is_none());
+        // Still held by `first`
+        assert!(arena.reusable(1).is_none());
+        
+        let second = Rc::new(ObjectData::new());
+        arena.keep(1, second.clone());
+        drop(first);
+        let reused = arena.reusable(1).unwrap();
+        assert!(!Rc::ptr_eq(&reused, &second));
+        drop(reused);
+        
+        // A third object pushes out the oldest
+        arena.keep(1, Rc::new(ObjectData::new()));
+        assert_eq!(arena.drain_unreferenced().count(), 1);
+        assert!(arena.reusable(1).is_none() && arena.has_storage());
+        assert_eq!(Rc::strong_count(&second), 1);
+    }
+}
@@ -0,0 +1,41 @@
+//! The Array Global
+//!
+//! `Array` as a namespace holding `Array.from`. `Array` itself is not
+//! callable yet; array literals make arrays.
+
+use std::ops::ControlFlow;
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::iteration::IterationConsumer;
+use super::object::methods_object;
+use super::value::{NativeMethod, Value};
+
+/// Create the `Array` object
+pub fn create_object() -> Value {
+    methods_object(&METHODS)
+}
+
+/// The methods of `Array`
+pub(crate) const METHODS: [(&str, NativeMethod); 1] = [("from", array_from)];
+
+/// `Array.from(items, mapFn)` - A new array of the elements of an iterable
+/// or array-like, each passed through `mapFn(element, index)` if given
+fn array_from(cx: &mut CallContext) -> Result<Value> {
+    let items = cx.arg(0);
+    let map = cx.arg(1);
+    if !matches!(map, Value::Undefined | Value::Function(_)) {
+        return Err(Error::runtime(ErrorCode::InvalidArgument, format!("TypeError: {} is not a function", map), None));
+    }
+    // Filled in place, so it holds what the map function has produced
+    let array = cx.new_array(Vec::new());
+    let mut length = 0;
+    cx.vm.iterate_value(&items, IterationConsumer::ArrayFrom, |vm, element| {
+        let element = match &map {
+            Value::Function(_) => vm.call_function(&map, Value::Undefined, &[element, Value::Number(length as f64)])?,
+            _ => element,
+        };
+        length = array.push_element(element);
+        Ok(ControlFlow::Continue(()))
+    })?;
+    Ok(array.get())
+}
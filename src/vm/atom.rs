@@ -0,0 +1,238 @@
+//! Interned Property Names
+//!
+//! Every string key of a `PropertyMap` is an [`Atom`]: the one shared
+//! copy of that name on this thread, compared and hashed by address. The
+//! VM turns a program's `StringTable` into atoms once, so a named access
+//! such as `o.length` finds its property without hashing the name's text.
+//! Keys that only exist as `&str`, as built-ins and computed members have
+//! them, are looked up in the interner first; a name it has never seen
+//! is no object's key.
+//!
+//! Values stay on the thread that made them, so the interner is kept per
+//! thread, like the well-known symbols. Names that no object or program
+//! uses any more are dropped from it as it grows.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use crate::bytecode::{NameId, StringTable};
+
+/// Fewest names the interner holds before it first drops unused ones
+const MIN_PRUNE_SIZE: usize = 1024;
+
+/// The names interned on this thread
+struct Interner {
+    names: HashSet<Rc<str>>,
+    /// Size at which unused names are next dropped, twice the size left
+    /// by the last pass so the passes cost O(1) per name interned
+    prune_at: usize,
+}
+
+thread_local! {
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner { names: HashSet::new(), prune_at: MIN_PRUNE_SIZE });
+}
+
+/// An interned property name; two atoms are equal exactly when their
+/// names are
+#[derive(Clone)]
+pub struct Atom(Rc<str>);
+
+impl Atom {
+    /// The atom for `name`, interning it if it is new
+    pub fn new(name: &str) -> Self {
+        let interned = INTERNER.try_with(|interner| {
+            let mut interner = interner.borrow_mut();
+            if let Some(name) = interner.names.get(name) {
+                return name.clone();
+            }
+            if interner.names.len() >= interner.prune_at {
+                // The interner's own reference is the only one left
+                interner.names.retain(|name| Rc::strong_count(name) > 1);
+                interner.prune_at = (2 * interner.names.len()).max(MIN_PRUNE_SIZE);
+            }
+            let name: Rc<str> = Rc::from(name);
+            interner.names.insert(name.clone());
+            name
+        });
+        // Only while the thread is being torn down, when no new property
+        // can meet an old one
+        Self(interned.unwrap_or_else(|_| Rc::from(name)))
+    }
+    
+    /// The atom for `name` if it has been interned
+    pub fn existing(name: &str) -> Option<Self> {
+        INTERNER.try_with(|interner| interner.borrow().names.get(name).cloned().map(Self)).ok().flatten()
+    }
+    
+    /// The name
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Atom {}
+
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (Rc::as_ptr(&self.0) as *const u8 as usize).hash(state);
+    }
+}
+
+impl Deref for Atom {
+    type Target = str;
+    
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Atom {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<String> for Atom {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl From<&String> for Atom {
+    fn from(name: &String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A property name as lookups take it: an [`Atom`] the VM already has,
+/// or text that has to be found in the interner
+pub trait AtomKey {
+    /// The name's text
+    fn as_str(&self) -> &str;
+    
+    /// The name's atom, `None` if it was never interned
+    fn atom(&self) -> Option<Cow<'_, Atom>>;
+    
+    /// The name's atom, interning it if needed
+    fn to_atom(&self) -> Atom;
+}
+
+impl AtomKey for Atom {
+    fn as_str(&self) -> &str {
+        self
+    }
+    
+    fn atom(&self) -> Option<Cow<'_, Atom>> {
+        Some(Cow::Borrowed(self))
+    }
+    
+    fn to_atom(&self) -> Atom {
+        self.clone()
+    }
+}
+
+impl AtomKey for str {
+    fn as_str(&self) -> &str {
+        self
+    }
+    
+    fn atom(&self) -> Option<Cow<'_, Atom>> {
+        Atom::existing(self).map(Cow::Owned)
+    }
+    
+    fn to_atom(&self) -> Atom {
+        Atom::new(self)
+    }
+}
+
+impl AtomKey for String {
+    fn as_str(&self) -> &str {
+        self
+    }
+    
+    fn atom(&self) -> Option<Cow<'_, Atom>> {
+        self.as_str().atom()
+    }
+    
+    fn to_atom(&self) -> Atom {
+        Atom::new(self)
+    }
+}
+
+/// The atoms of every string table a VM has run code from
+///
+/// The functions of one program share a table, so the one used last is
+/// checked before the map. The `Arc`s keep the tables, and so their
+/// addresses in `by_table`, alive.
+#[derive(Default)]
+pub(crate) struct TableAtoms {
+    tables: Vec<(Arc<StringTable>, Vec<Atom>)>,
+    by_table: HashMap<*const StringTable, usize>,
+    last: Option<usize>,
+}
+
+impl TableAtoms {
+    /// The atom for name `id` of `names`, interning the whole table the
+    /// first time it is seen
+    pub(crate) fn get(&mut self, names: &Arc<StringTable>, id: NameId) -> Option<Atom> {
+        let pointer = Arc::as_ptr(names);
+        let index = match self.last {
+            Some(index) if Arc::as_ptr(&self.tables[index].0) == pointer => index,
+            _ => *self.by_table.entry(pointer).or_insert_with(|| {
+                self.tables.push((names.clone(), names.iter().map(|(_, name)| Atom::new(name)).collect()));
+                self.tables.len() - 1
+            }),
+        };
+        self.last = Some(index);
+        self.tables[index].1.get(id.index()).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_atoms_are_shared_per_name() {
+        let length = Atom::new("length");
+        assert_eq!(length, Atom::from(String::from("length")));
+        assert_ne!(length, Atom::new("next"));
+        assert_eq!(Atom::existing("length"), Some(length.clone()));
+        assert_eq!(&*length, "length");
+        assert_eq!(Atom::existing("never interned by anything"), None);
+    }
+    
+    #[test]
+    fn test_unused_names_are_dropped() {
+        let kept = Atom::new("kept across pruning");
+        for i in 0..4 * MIN_PRUNE_SIZE {
+            Atom::new(&format!("temporary {}", i));
+        }
+        assert_eq!(Atom::existing("kept across pruning"), Some(kept));
+        assert!(INTERNER.with(|interner| interner.borrow().names.len()) < 3 * MIN_PRUNE_SIZE);
+    }
+}
@@ -3,33 +3,44 @@
 //! This module implements native JavaScript functions like print, console.log,
 //! and other essential runtime functions.
 
-use std::collections::HashMap;
-use super::value::{Value, NativeFunction};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::machine::VM;
+use super::object::{methods_object, ObjectBuilder};
+use super::tasks::Task;
+use super::value::{Value, NativeMethod, PropertyKey, check_dense_length, compare_code_units, js_pow};
 
 /// Registry of built-in functions
+///
+/// Kept sorted by name so everything derived from it, like the order
+/// globals are installed in, is the same on every run.
 pub struct Builtins {
-    functions: HashMap<String, NativeFunction>,
+    functions: BTreeMap<String, NativeMethod>,
 }
 
+/// The standard built-in functions, by registration name
+///
+/// None of them needs the VM; the functions they wrap also serve
+/// [`pure_builtin`](super::pure_builtin).
+pub(crate) const FUNCTIONS: [(&str, NativeMethod); 5] = [
+    ("typeof", |cx| Ok(typeof_fn(cx.args))),
+    ("isNaN", |cx| Ok(is_nan(cx.args))),
+    ("isFinite", |cx| Ok(is_finite(cx.args))),
+    ("parseInt", |cx| Ok(parse_int(cx.args))),
+    ("parseFloat", |cx| Ok(parse_float(cx.args))),
+];
+
 impl Builtins {
     /// Create a new builtins registry with all standard functions
     pub fn new() -> Self {
-        let mut functions = HashMap::new();
-        
-        // Register all built-in functions
-        functions.insert("print".to_string(), print as NativeFunction);
-        functions.insert("console.log".to_string(), console_log as NativeFunction);
-        functions.insert("typeof".to_string(), typeof_fn as NativeFunction);
-        functions.insert("isNaN".to_string(), is_nan as NativeFunction);
-        functions.insert("isFinite".to_string(), is_finite as NativeFunction);
-        functions.insert("parseInt".to_string(), parse_int as NativeFunction);
-        functions.insert("parseFloat".to_string(), parse_float as NativeFunction);
-        
+        let functions = FUNCTIONS.iter().map(|&(name, function)| (name.to_string(), function)).collect();
         Self { functions }
     }
     
     /// Get a built-in function by name
-    pub fn get(&self, name: &str) -> Option<NativeFunction> {
+    pub fn get(&self, name: &str) -> Option<NativeMethod> {
         self.functions.get(name).copied()
     }
     
@@ -38,7 +49,7 @@ impl Builtins {
         self.functions.contains_key(name)
     }
     
-    /// Get all built-in function names
+    /// Get all built-in function names, in sorted order
     pub fn names(&self) -> Vec<String> {
         self.functions.keys().cloned().collect()
     }
@@ -52,16 +63,41 @@ impl Default for Builtins {
 
 // === Built-in Function Implementations ===
 
-/// print(...args) - Print values to stdout
-fn print(args: &[Value]) -> Value {
-    let output: Vec<String> = args.iter().map(|v| v.to_string()).collect();
-    println!("{}", output.join(" "));
-    Value::Undefined
+/// print(...args) - Print values like `console.log`, without calling
+/// their own `toString`
+fn print(cx: &mut CallContext) -> Result<Value> {
+    let output: Vec<String> = cx.args.iter().map(|v| v.to_string()).collect();
+    cx.vm.write_console(&output.join(" "), false)?;
+    Ok(Value::Undefined)
+}
+
+/// readLine() - The next line of input without its line ending, or
+/// `null` once input has ended
+fn read_line(cx: &mut CallContext) -> Result<Value> {
+    Ok(cx.vm.read_line()?.map_or(Value::Null, Value::string))
+}
+
+/// Write `console` arguments as one line, to stderr if `error` is set
+///
+/// Unlike `print`, converts arguments with the VM so an object's own
+/// `toString` is honoured.
+fn write_console_args(cx: &mut CallContext, error: bool) -> Result<Value> {
+    let mut output = Vec::with_capacity(cx.args.len());
+    for arg in cx.args {
+        output.push(cx.vm.to_js_string(arg)?);
+    }
+    cx.vm.write_console(&output.join(" "), error)?;
+    Ok(Value::Undefined)
+}
+
+/// console.log(...args), and `info` and `debug` - Print to stdout
+fn console_log_method(cx: &mut CallContext) -> Result<Value> {
+    write_console_args(cx, false)
 }
 
-/// console.log(...args) - Print values to stdout (alias for print)
-fn console_log(args: &[Value]) -> Value {
-    print(args)
+/// console.error(...args), and `warn` - Print to stderr
+fn console_error_method(cx: &mut CallContext) -> Result<Value> {
+    write_console_args(cx, true)
 }
 
 /// typeof(value) - Return the type of a value
@@ -73,7 +109,7 @@ fn typeof_fn(args: &[Value]) -> Value {
 }
 
 /// isNaN(value) - Check if a value is NaN
-fn is_nan(args: &[Value]) -> Value {
+pub(crate) fn is_nan(args: &[Value]) -> Value {
     match args.first() {
         Some(value) => {
             let num = value.to_number();
@@ -84,7 +120,7 @@ fn is_nan(args: &[Value]) -> Value {
 }
 
 /// isFinite(value) - Check if a value is finite
-fn is_finite(args: &[Value]) -> Value {
+pub(crate) fn is_finite(args: &[Value]) -> Value {
     match args.first() {
         Some(value) => {
             let num = value.to_number();
@@ -95,7 +131,7 @@ fn is_finite(args: &[Value]) -> Value {
 }
 
 /// parseInt(string, radix) - Parse a string as an integer
-fn parse_int(args: &[Value]) -> Value {
+pub(crate) fn parse_int(args: &[Value]) -> Value {
     let string = match args.first() {
         Some(v) => v.to_string(),
         None => return Value::Number(f64::NAN),
@@ -114,7 +150,7 @@ fn parse_int(args: &[Value]) -> Value {
     };
     
     // Validate radix
-    if radix < 2 || radix > 36 {
+    if !(2..=36).contains(&radix) {
         return Value::Number(f64::NAN);
     }
     
@@ -158,7 +194,7 @@ fn parse_int(args: &[Value]) -> Value {
 }
 
 /// parseFloat(string) - Parse a string as a floating-point number
-fn parse_float(args: &[Value]) -> Value {
+pub(crate) fn parse_float(args: &[Value]) -> Value {
     match args.first() {
         Some(v) => {
             let s = v.to_string();
@@ -213,30 +249,389 @@ fn parse_float(args: &[Value]) -> Value {
     }
 }
 
+// === Scheduling ===
+
+/// Built-in globals that need the VM, installed next to the registry's
+pub fn global_methods() -> [(&'static str, NativeMethod); 5] {
+    [
+        ("print", print as NativeMethod),
+        ("readLine", read_line as NativeMethod),
+        ("setTimeout", set_timeout as NativeMethod),
+        ("clearTimeout", clear_timeout as NativeMethod),
+        ("queueMicrotask", queue_microtask as NativeMethod),
+    ]
+}
+
+/// The callback argument of a scheduling function, which must be callable
+fn scheduled_callback(name: &str, args: &[Value]) -> Result<Value> {
+    match args.first() {
+        Some(callback @ Value::Function(_)) => Ok(callback.clone()),
+        other => Err(Error::runtime(
+            ErrorCode::InvalidArgument,
+            format!(
+                "TypeError: The \"callback\" argument of {} must be of type function, received {}",
+                name,
+                other.map_or("undefined", |value| value.type_of()),
+            ),
+            None,
+        )),
+    }
+}
+
+/// setTimeout(callback, delay, ...args) - Run `callback` after `delay` ms
+///
+/// Returns the timer id for `clearTimeout`. A missing, negative or NaN
+/// delay counts as zero.
+fn set_timeout(cx: &mut CallContext) -> Result<Value> {
+    let callback = scheduled_callback("setTimeout", cx.args)?;
+    let delay = cx.args.get(1).map_or(0.0, |delay| delay.to_number());
+    let delay = if delay.is_nan() || delay < 0.0 { 0 } else { delay as u64 };
+    let task = Task { callback, args: cx.args.iter().skip(2).cloned().collect() };
+    Ok(Value::Number(cx.vm.tasks_mut().set_timeout(task, delay) as f64))
+}
+
+/// clearTimeout(id) - Cancel a pending timer
+fn clear_timeout(cx: &mut CallContext) -> Result<Value> {
+    if let Some(Value::Number(id)) = cx.args.first() {
+        if id.fract() == 0.0 && *id >= 0.0 && *id <= u32::MAX as f64 {
+            cx.vm.tasks_mut().clear_timeout(*id as u32);
+        }
+    }
+    Ok(Value::Undefined)
+}
+
+/// queueMicrotask(callback) - Run `callback` before the next timer
+fn queue_microtask(cx: &mut CallContext) -> Result<Value> {
+    let callback = scheduled_callback("queueMicrotask", cx.args)?;
+    cx.vm.tasks_mut().queue_microtask(Task { callback, args: Vec::new() });
+    Ok(Value::Undefined)
+}
+
+// === Built-in Methods ===
+
+/// Look up a method of Array.prototype
+pub fn array_method(name: &str) -> Option<NativeMethod> {
+    match name {
+        "push" => Some(array_push as NativeMethod),
+        "sort" => Some(array_sort as NativeMethod),
+        _ => None,
+    }
+}
+
+/// Look up a method of String.prototype
+pub fn string_method(name: &str) -> Option<NativeMethod> {
+    match name {
+        "indexOf" => Some(string_index_of as NativeMethod),
+        "localeCompare" => Some(string_locale_compare as NativeMethod),
+        "toUpperCase" => Some(string_to_upper_case as NativeMethod),
+        "toLowerCase" => Some(string_to_lower_case as NativeMethod),
+        "toLocaleUpperCase" => Some(string_to_locale_upper_case as NativeMethod),
+        "toLocaleLowerCase" => Some(string_to_locale_lower_case as NativeMethod),
+        _ => None,
+    }
+}
+
+/// Look up a method of Object.prototype, which every value inherits
+pub fn object_method(name: &str) -> Option<NativeMethod> {
+    match name {
+        "hasOwnProperty" => Some(object_has_own_property as NativeMethod),
+        _ => None,
+    }
+}
+
+/// value.hasOwnProperty(key) - Whether `key` is an own property of the receiver
+fn object_has_own_property(cx: &mut CallContext) -> Result<Value> {
+    let key = cx.vm.to_property_key(cx.args.first().unwrap_or(&Value::Undefined))?;
+    Ok(Value::Boolean(match &key {
+        PropertyKey::String(key) => cx.vm.has_own_property(cx.this, key),
+        PropertyKey::Symbol(symbol) => cx.this.own_symbol_property(symbol).is_some(),
+    }))
+}
+
+/// Look up a method of Generator.prototype
+///
+/// Only `next` and `return` exist; `throw` needs exceptions the VM
+/// can catch, which it does not have yet.
+pub fn generator_method(name: &str) -> Option<NativeMethod> {
+    match name {
+        "next" => Some(generator_next as NativeMethod),
+        "return" => Some(generator_return as NativeMethod),
+        _ => None,
+    }
+}
+
+/// generator.next(value) - Resume the generator; `value` becomes the
+/// result of the `yield` it is suspended at
+fn generator_next(cx: &mut CallContext) -> Result<Value> {
+    let sent = cx.arg(0);
+    let (value, done) = cx.vm.resume_generator(cx.this, sent)?;
+    Ok(iterator_result(value, done))
+}
+
+/// generator.return(value) - Finish the generator early, completing with `value`
+fn generator_return(cx: &mut CallContext) -> Result<Value> {
+    let is_generator = matches!(cx.this, Value::Object(object) if object.generator_state().is_some());
+    if !is_generator {
+        return Err(Error::runtime(
+            ErrorCode::IncompatibleReceiver,
+            format!("TypeError: return method called on incompatible receiver {}", cx.this),
+            None,
+        ));
+    }
+    cx.vm.close_generator(cx.this)?;
+    Ok(iterator_result(cx.arg(0), true))
+}
+
+/// An iterator result object: `{ value, done }`
+fn iterator_result(value: Value, done: bool) -> Value {
+    ObjectBuilder::new().prop("value", value).prop("done", Value::Boolean(done)).build()
+}
+
+/// array.push(...items) - Append `items`, returning the new length
+fn array_push(cx: &mut CallContext) -> Result<Value> {
+    let Value::Object(object) = cx.this else { return Ok(Value::Undefined) };
+    let Some(elements) = object.array_elements() else { return Ok(Value::Undefined) };
+    let before = cx.vm.heap_extent(object);
+    let length = {
+        let mut elements = elements.borrow_mut();
+        check_dense_length(elements.len() + cx.args.len())?;
+        elements.extend(cx.args.iter().cloned().map(Some));
+        elements.len()
+    };
+    cx.vm.charge_growth(object, before)?;
+    Ok(Value::Number(length as f64))
+}
+
+/// Array.prototype.sort(comparefn) - Stable in-place sort
+///
+/// Follows the spec's SortIndexedProperties: defined values are sorted,
+/// then undefined values follow, then holes. Without a comparator
+/// elements compare as strings by UTF-16 code units, so `[10, 9, 1]`
+/// sorts to `[1, 10, 9]`.
+fn array_sort(cx: &mut CallContext) -> Result<Value> {
+    let comparator = cx.arg(0);
+    if !matches!(comparator, Value::Undefined | Value::Function(_)) {
+        return Err(Error::runtime(
+            ErrorCode::InvalidArgument,
+            "The comparison function must be either a function or undefined",
+            None,
+        ));
+    }
+    
+    let elements = match cx.this {
+        Value::Object(object) => match object.array_elements() {
+            Some(elements) => elements,
+            None => return Ok(cx.this.clone()),
+        },
+        _ => return Ok(cx.this.clone()),
+    };
+    
+    // Work on a snapshot: the comparator may run arbitrary code
+    let snapshot = elements.borrow().clone();
+    let length = snapshot.len();
+    let mut values = Vec::with_capacity(length);
+    let mut undefined_count = 0;
+    for value in snapshot.into_iter().flatten() {
+        if matches!(value, Value::Undefined) {
+            undefined_count += 1;
+        } else {
+            values.push(value);
+        }
+    }
+    
+    // ...which may also remove them from the array
+    let sorted = cx.scope(|cx| {
+        for value in &values {
+            cx.root(value.clone());
+        }
+        merge_sort(values, &mut |x, y| compare_array_elements(cx.vm, &comparator, x, y))
+    })?;
+    let defined_count = sorted.len();
+    
+    let mut elements = elements.borrow_mut();
+    if elements.len() < length {
+        elements.resize(length, None);
+    }
+    for (slot, value) in elements.iter_mut().zip(sorted) {
+        *slot = Some(value);
+    }
+    for slot in elements.iter_mut().take(length).skip(defined_count) {
+        *slot = if undefined_count > 0 {
+            undefined_count -= 1;
+            Some(Value::Undefined)
+        } else {
+            None
+        };
+    }
+    
+    Ok(cx.this.clone())
+}
+
+/// Compare two defined array elements for sort (spec: CompareArrayElements)
+fn compare_array_elements(vm: &mut VM, comparator: &Value, x: &Value, y: &Value) -> Result<Ordering> {
+    if matches!(comparator, Value::Undefined) {
+        return Ok(compare_code_units(&vm.to_js_string(x)?, &vm.to_js_string(y)?));
+    }
+    
+    // Only the sign of the result matters; NaN counts as equal
+    let result = vm.call_function(comparator, Value::Undefined, &[x.clone(), y.clone()])?
+        .to_number();
+    Ok(if result < 0.0 {
+        Ordering::Less
+    } else if result > 0.0 {
+        Ordering::Greater
+    } else {
+        Ordering::Equal
+    })
+}
+
+/// Stable merge sort with a fallible comparator
+///
+/// `slice::sort_by` cannot propagate errors and may panic when a user
+/// comparator is inconsistent, so sorting is done by hand.
+fn merge_sort<F>(mut items: Vec<Value>, compare: &mut F) -> Result<Vec<Value>>
+where
+    F: FnMut(&Value, &Value) -> Result<Ordering>,
+{
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+    
+    let right = items.split_off(items.len() / 2);
+    let left = merge_sort(items, compare)?;
+    let right = merge_sort(right, compare)?;
+    
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        // Take from the right only if strictly smaller, keeping the sort stable
+        if compare(r, l)? == Ordering::Less {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    
+    Ok(merged)
+}
+
+/// String.prototype.localeCompare(that) - Locale-free code unit comparison
+fn string_locale_compare(cx: &mut CallContext) -> Result<Value> {
+    Ok(locale_compare(cx.this, &cx.arg(0)))
+}
+
+/// The `localeCompare` of two strings, as -1, 0 or 1
+pub(crate) fn locale_compare(this: &Value, that: &Value) -> Value {
+    let result = match compare_code_units(&this.to_string(), &that.to_string()) {
+        Ordering::Less => -1.0,
+        Ordering::Equal => 0.0,
+        Ordering::Greater => 1.0,
+    };
+    Value::Number(result)
+}
+
+/// str.toUpperCase() - The string in upper case (see `vm::case`)
+fn string_to_upper_case(cx: &mut CallContext) -> Result<Value> {
+    Ok(Value::string(cx.vm.case_mapper().to_upper(&cx.this.to_string())))
+}
+
+/// str.toLowerCase() - The string in lower case (see `vm::case`)
+fn string_to_lower_case(cx: &mut CallContext) -> Result<Value> {
+    Ok(Value::string(cx.vm.case_mapper().to_lower(&cx.this.to_string())))
+}
+
+/// str.toLocaleUpperCase(locale) - The string in upper case for `locale`,
+/// which is converted but doesn't change the result yet
+fn string_to_locale_upper_case(cx: &mut CallContext) -> Result<Value> {
+    let locale = requested_locale(cx)?;
+    Ok(Value::string(cx.vm.case_mapper().to_locale_upper(&cx.this.to_string(), locale.as_deref())))
+}
+
+/// str.toLocaleLowerCase(locale) - The string in lower case for `locale`,
+/// which is converted but doesn't change the result yet
+fn string_to_locale_lower_case(cx: &mut CallContext) -> Result<Value> {
+    let locale = requested_locale(cx)?;
+    Ok(Value::string(cx.vm.case_mapper().to_locale_lower(&cx.this.to_string(), locale.as_deref())))
+}
+
+/// The locale argument of a `toLocale*Case` method; `None` when omitted
+fn requested_locale(cx: &mut CallContext) -> Result<Option<String>> {
+    match cx.args.first() {
+        None | Some(Value::Undefined) => Ok(None),
+        Some(locale) => cx.vm.to_js_string(locale).map(Some),
+    }
+}
+
+/// str.indexOf(search, position) - The index of the first `search` at or
+/// after `position`, counted in UTF-16 code units, or -1
+fn string_index_of(cx: &mut CallContext) -> Result<Value> {
+    let search = cx.vm.to_js_string(cx.args.first().unwrap_or(&Value::Undefined))?;
+    Ok(index_of(&cx.this.to_string(), &search, cx.args.get(1)))
+}
+
+/// The `indexOf` of `search` in `string`, with `search` already converted
+pub(crate) fn index_of(string: &str, search: &str, position: Option<&Value>) -> Value {
+    let string: Vec<u16> = string.encode_utf16().collect();
+    let search: Vec<u16> = search.encode_utf16().collect();
+    let position = position.map_or(0.0, Value::to_number);
+    let start = if position.is_nan() { 0 } else { position.clamp(0.0, string.len() as f64) as usize };
+    let found = (start..=string.len()).find(|&i| string.get(i..i + search.len()) == Some(&search[..]));
+    Value::Number(found.map_or(-1.0, |i| i as f64))
+}
+
+/// Math.random() - A number in [0, 1) from the VM's seedable generator
+fn math_random(cx: &mut CallContext) -> Result<Value> {
+    Ok(Value::Number(cx.vm.rng_mut().next_f64()))
+}
+
+/// Math.pow(base, exponent) - `base ** exponent`, NaN for a negative base
+/// with a fractional exponent
+fn math_pow(cx: &mut CallContext) -> Result<Value> {
+    Ok(pow(cx.args))
+}
+
+/// Math.pow on its arguments; also serves the compiler's builtin folding
+pub(crate) fn pow(args: &[Value]) -> Value {
+    let number = |index: usize| args.get(index).map_or(f64::NAN, Value::to_number);
+    Value::Number(js_pow(number(0), number(1)))
+}
+
+/// The methods of `Math`
+pub(crate) const MATH_METHODS: [(&str, NativeMethod); 2] = [("pow", math_pow), ("random", math_random)];
+
+/// Math object implementation
+pub struct MathObject;
+
+impl MathObject {
+    /// Create a Math object value
+    pub fn create_object() -> Value {
+        methods_object(&MATH_METHODS)
+    }
+}
+
+/// The methods of `console`
+pub(crate) const CONSOLE_METHODS: [(&str, NativeMethod); 5] = [
+    ("log", console_log_method),
+    ("error", console_error_method),
+    ("warn", console_error_method),
+    ("info", console_log_method),
+    ("debug", console_log_method),
+];
+
 /// Console object implementation
 pub struct Console;
 
 impl Console {
     /// Create a console object value
     pub fn create_object() -> Value {
-        use std::rc::Rc;
-        use super::value::ObjectData;
-        
-        let mut properties = HashMap::new();
-        
-        // Add console methods as properties
-        // For now, we'll just store them as undefined placeholders
-        // In a full implementation, these would be function objects
-        properties.insert("log".to_string(), Value::Undefined);
-        properties.insert("error".to_string(), Value::Undefined);
-        properties.insert("warn".to_string(), Value::Undefined);
-        properties.insert("info".to_string(), Value::Undefined);
-        properties.insert("debug".to_string(), Value::Undefined);
-        
-        Value::Object(Rc::new(ObjectData { properties }))
+        methods_object(&CONSOLE_METHODS)
     }
 }
 
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -280,6 +675,7 @@ mod tests {
     }
     
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_parse_float() {
         assert_eq!(parse_float(&[Value::string("3.14")]), Value::Number(3.14));
         assert_eq!(parse_float(&[Value::string("  -123.456  ")]), Value::Number(-123.456));
@@ -0,0 +1,69 @@
+//! String case mapping
+//!
+//! `toUpperCase` and `toLowerCase` go through the VM's `CaseMapper`,
+//! chosen with `VMConfig::case_mapper`. The default applies Unicode's
+//! full mappings without regard to locale, as JavaScript does without
+//! ECMA-402: `ß` upper-cases to `SS`, so a result can be longer than its
+//! input, and `I` always lower-cases to `i`, never to Turkish dotless `ı`.
+//! Hosts that want neither the tables nor the surprises can map ASCII
+//! letters only.
+//!
+//! `toLocaleUpperCase` and `toLocaleLowerCase` take the requested locale
+//! but map as their locale-free twins for now. Locale-specific rules,
+//! such as Turkish and Azeri dotted and dotless `i` or Lithuanian dots
+//! over accented letters, belong in `CaseMapper::to_locale_upper` and
+//! `to_locale_lower`.
+
+/// How strings change case
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMapper {
+    /// Unicode's full default case mappings, special casing included
+    #[default]
+    Unicode,
+    /// `a`-`z` and `A`-`Z` only; every other character stays as it is
+    Ascii,
+}
+
+impl CaseMapper {
+    /// `string` in upper case, as `toUpperCase` returns it
+    pub fn to_upper(self, string: &str) -> String {
+        match self {
+            CaseMapper::Unicode => string.to_uppercase(),
+            CaseMapper::Ascii => string.to_ascii_uppercase(),
+        }
+    }
+    
+    /// `string` in lower case, as `toLowerCase` returns it
+    ///
+    /// A capital sigma that ends a word lower-cases to final `ς`.
+    pub fn to_lower(self, string: &str) -> String {
+        match self {
+            CaseMapper::Unicode => string.to_lowercase(),
+            CaseMapper::Ascii => string.to_ascii_lowercase(),
+        }
+    }
+    
+    /// `string` in upper case for `locale`, as `toLocaleUpperCase`
+    /// returns it; the same as `to_upper` whatever the locale for now
+    pub fn to_locale_upper(self, string: &str, _locale: Option<&str>) -> String {
+        self.to_upper(string)
+    }
+    
+    /// `string` in lower case for `locale`, as `toLocaleLowerCase`
+    /// returns it; the same as `to_lower` whatever the locale for now
+    pub fn to_locale_lower(self, string: &str, _locale: Option<&str>) -> String {
+        self.to_lower(string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_ascii_mapping_leaves_other_letters_alone() {
+        assert_eq!(CaseMapper::Ascii.to_upper("straße é"), "STRAßE é");
+        assert_eq!(CaseMapper::Ascii.to_lower("İSTANBUL É"), "İstanbul É");
+        assert_eq!(CaseMapper::Unicode.to_upper("straße é"), "STRASSE É");
+    }
+}
@@ -0,0 +1,129 @@
+//! What native methods are called with
+//!
+//! A native method receives one `CallContext` instead of the VM, `this`
+//! and the arguments separately. The context is also the call's handle
+//! scope (see `vm::handles`): `this`, the arguments and the values its
+//! allocation helpers make stay rooted until the call returns, and
+//! `root` adds any other value the native keeps across an allocation or
+//! a call back into JavaScript.
+
+use super::handles::{HandleScope, Local, Rootable};
+use super::machine::VM;
+use super::object::ObjectBuilder;
+use super::promise;
+use super::value::Value;
+
+/// The VM, receiver and arguments of one native call
+pub struct CallContext<'a> {
+    /// The calling VM, for calling back into JavaScript and for its state
+    pub vm: &'a mut VM,
+    /// The receiver; `undefined` for plain calls and constructors
+    pub this: &'a Value,
+    /// The arguments as passed, without padding
+    pub args: &'a [Value],
+    /// Roots `this`, the arguments and the handles made through the context
+    scope: HandleScope,
+}
+
+impl<'a> CallContext<'a> {
+    /// Open the handle scope of a call, with `this` and `args` rooted in it
+    pub fn new(vm: &'a mut VM, this: &'a Value, args: &'a [Value]) -> Self {
+        let scope = HandleScope::open(vm);
+        vm.push_handle(this.clone());
+        for arg in args {
+            vm.push_handle(arg.clone());
+        }
+        Self { vm, this, args, scope }
+    }
+    
+    /// Argument `index`, `undefined` when missing
+    pub fn arg(&self, index: usize) -> Value {
+        self.args.get(index).cloned().unwrap_or(Value::Undefined)
+    }
+    
+    /// Root `value` until the call returns
+    ///
+    /// Needed for a value the native made some other way, or took out of
+    /// an object JavaScript can change, when it keeps it across another
+    /// allocation or a call back into JavaScript.
+    pub fn root<T: Rootable>(&mut self, value: T) -> Local<'a, T> {
+        Local::new(self.vm, value)
+    }
+    
+    /// Run `body` in a nested handle scope, whose handles go when it
+    /// returns
+    ///
+    /// For loops that would otherwise root a value per iteration. What
+    /// `body` returns is not rooted.
+    pub fn scope<R>(&mut self, body: impl FnOnce(&mut CallContext<'_>) -> R) -> R {
+        let scope = HandleScope::open(self.vm);
+        body(&mut CallContext { vm: &mut *self.vm, this: self.this, args: self.args, scope })
+    }
+    
+    /// A new empty object
+    pub fn new_object(&mut self) -> Local<'a> {
+        let object = ObjectBuilder::new().build();
+        self.allocated(object)
+    }
+    
+    /// A new array holding `elements`
+    pub fn new_array(&mut self, elements: impl IntoIterator<Item = Value>) -> Local<'a> {
+        let array = Value::new_array(elements);
+        self.allocated(array)
+    }
+    
+    /// A new pending promise
+    pub fn new_promise(&mut self) -> Local<'a> {
+        let promise = promise::new_promise();
+        self.allocated(promise)
+    }
+    
+    /// Root `value`, just made, and give GC stress mode its collection
+    ///
+    /// The collection runs once the value is rooted, so what went into it
+    /// is reachable.
+    fn allocated(&mut self, value: Value) -> Local<'a> {
+        let local = self.root(value);
+        self.vm.stress_collect();
+        local
+    }
+}
+
+impl Drop for CallContext<'_> {
+    fn drop(&mut self) {
+        self.scope.close(self.vm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_missing_arguments_are_undefined() {
+        let mut vm = VM::new();
+        let args = [Value::Number(1.0)];
+        let cx = CallContext::new(&mut vm, &Value::Undefined, &args);
+        assert_eq!(cx.arg(0), Value::Number(1.0));
+        assert_eq!(cx.arg(1), Value::Undefined);
+    }
+    
+    #[test]
+    fn test_handles_go_with_their_scope() {
+        let mut vm = VM::new();
+        let args = [Value::Number(1.0), Value::Number(2.0)];
+        {
+            let mut cx = CallContext::new(&mut vm, &Value::Undefined, &args);
+            assert_eq!(cx.vm.handle_count(), 3);
+            let array = cx.new_array([Value::Null]);
+            cx.scope(|cx| {
+                cx.new_object();
+                cx.root(array.get());
+                assert_eq!(cx.vm.handle_count(), 6);
+            });
+            assert_eq!(cx.vm.handle_count(), 4);
+            assert_eq!(array.inspect(), "[ null ]");
+        }
+        assert_eq!(vm.handle_count(), 0);
+    }
+}
@@ -0,0 +1,196 @@
+//! Debugger Hook
+//!
+//! A host installs a `DebugHook` with `VM::set_debug_hook` to be called
+//! whenever execution pauses: at a `debugger;` statement, or at a source
+//! line given to `VM::set_breakpoint`. The VM is handed to the hook while
+//! the paused frame is still on the call stack, so the hook can inspect it
+//! and run code against it with `VM::eval_in_frame`, or with
+//! `VM::eval_source_in_frame`, which looks the frame's variable names up
+//! in its function's symbol table; execution resumes when the hook returns.
+//!
+//! Before returning, the hook can ask with `VM::step` to pause again at
+//! the next statement. The compiler records where each statement's code
+//! starts in `DebugInfo::statement_starts`, so stepping adds no
+//! instructions and costs nothing while no step is pending; a line
+//! holding three statements pauses three times, and a statement spread
+//! over several lines pauses once.
+//!
+//! With `VMConfig::trace_nan` the VM also records where NaN first appears
+//! (see `VM::nan_origins`).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::bytecode::{BytecodeFunction, LocalIndex};
+use crate::error::Result;
+use super::machine::VM;
+use super::value::Value;
+
+/// Why execution paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PauseReason {
+    /// A `debugger;` statement ran
+    DebuggerStatement,
+    /// Execution reached a breakpoint set with `VM::set_breakpoint`
+    Breakpoint(BreakpointId),
+    /// Execution reached the statement a `VM::step` asked for
+    Step,
+}
+
+/// Where `VM::step` pauses next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// At the next statement, inside a function called meanwhile if
+    /// there is one
+    Into,
+    /// At the next statement of the paused function, or of its caller
+    /// once it returns; calls made meanwhile run without pausing
+    Over,
+    /// At the next statement of the paused function's caller
+    Out,
+}
+
+/// Identifies a breakpoint for `VM::clear_breakpoint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BreakpointId(u32);
+
+/// A breakpoint as `VM::list_breakpoints` reports it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub id: BreakpointId,
+    /// Name of the function it is in; `main` (or `<main>`) for top-level code
+    pub function: String,
+    /// The source line it was set on
+    pub line: u32,
+}
+
+impl Breakpoint {
+    fn matches(&self, function: &BytecodeFunction) -> bool {
+        function.name == self.function || (self.function == "main" && function.name == "<main>")
+    }
+    
+    /// The first instruction of `function` on the nearest line at or
+    /// after this one
+    fn resolve(&self, function: &BytecodeFunction) -> Option<usize> {
+        let lines = &function.debug_info.line_numbers;
+        let line = lines.iter().flatten().filter(|&&line| line >= self.line).min()?;
+        lines.iter().position(|&at| at == Some(*line))
+    }
+}
+
+/// Breakpoint instructions of one function, sorted by instruction index
+type Sites = Vec<(usize, BreakpointId)>;
+
+/// The VM's breakpoints, with the instructions they resolve to in each
+/// function that has run since they last changed
+#[derive(Default)]
+pub(crate) struct Breakpoints {
+    next_id: u32,
+    list: Vec<Breakpoint>,
+    /// Breakpoint instructions by function. The `Arc` keeps the function
+    /// alive so its address isn't reused.
+    resolved: HashMap<*const BytecodeFunction, (Arc<BytecodeFunction>, Sites)>,
+}
+
+impl Breakpoints {
+    pub(crate) fn set(&mut self, function: &str, line: u32) -> BreakpointId {
+        let id = BreakpointId(self.next_id);
+        self.next_id += 1;
+        self.list.push(Breakpoint { id, function: function.to_string(), line });
+        self.resolved.clear();
+        id
+    }
+    
+    pub(crate) fn clear(&mut self, id: BreakpointId) -> bool {
+        let before = self.list.len();
+        self.list.retain(|breakpoint| breakpoint.id != id);
+        self.resolved.clear();
+        self.list.len() != before
+    }
+    
+    pub(crate) fn list(&self) -> &[Breakpoint] {
+        &self.list
+    }
+    
+    pub(crate) fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+    
+    /// The breakpoint at instruction `ip` of `function`, if any
+    pub(crate) fn hit(&mut self, function: &Arc<BytecodeFunction>, ip: usize) -> Option<BreakpointId> {
+        let list = &self.list;
+        let (_, sites) = self.resolved.entry(Arc::as_ptr(function)).or_insert_with(|| {
+            let mut sites: Vec<_> = list.iter()
+                .filter(|breakpoint| breakpoint.matches(function))
+                .filter_map(|breakpoint| Some((breakpoint.resolve(function)?, breakpoint.id)))
+                .collect();
+            sites.sort();
+            (function.clone(), sites)
+        });
+        let index = sites.partition_point(|&(at, _)| at < ip);
+        sites.get(index).filter(|&&(at, _)| at == ip).map(|&(_, id)| id)
+    }
+}
+
+/// Where execution paused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugContext {
+    /// Why the VM paused
+    pub reason: PauseReason,
+    /// Index of the paused frame in the call stack, 0 being the outermost
+    pub frame: usize,
+    /// Name of the paused function
+    pub function: String,
+    /// Index of the instruction that paused
+    pub ip: usize,
+    /// Source line of that instruction, when known
+    pub line: Option<u32>,
+    /// The variables in scope there and their slots, as
+    /// `Compiler::compile_expression_in_scope` takes them
+    pub locals: Vec<(String, LocalIndex)>,
+}
+
+/// Host callback for pauses
+pub trait DebugHook {
+    /// Called on each pause; returning an error aborts the script with it
+    fn on_pause(&mut self, vm: &mut VM, context: &DebugContext) -> Result<()>;
+}
+
+impl<F: FnMut(&mut VM, &DebugContext) -> Result<()>> DebugHook for F {
+    fn on_pause(&mut self, vm: &mut VM, context: &DebugContext) -> Result<()> {
+        self(vm, context)
+    }
+}
+
+/// An arithmetic instruction that made NaN out of operands that weren't
+#[derive(Debug, Clone, PartialEq)]
+pub struct NanOrigin {
+    /// Name of the function the instruction belongs to
+    pub function: String,
+    /// Index of the instruction
+    pub ip: usize,
+    /// Source line of the instruction, when known
+    pub line: Option<u32>,
+    /// The operator, such as `*` or `++`
+    pub operator: &'static str,
+    /// The operand values, left to right
+    pub operands: Vec<Value>,
+}
+
+/// `NaN from undefined * 2 at line 3 in area`, with each operand's type
+impl fmt::Display for NanOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operand = |value: &Value| format!("{} ({})", value.inspect(), value.type_of());
+        write!(f, "NaN from ")?;
+        match self.operands.as_slice() {
+            [left, right] => write!(f, "{} {} {}", operand(left), self.operator, operand(right))?,
+            [value] => write!(f, "{}{}", self.operator, operand(value))?,
+            _ => write!(f, "{}", self.operator)?,
+        }
+        if let Some(line) = self.line {
+            write!(f, " at line {}", line)?;
+        }
+        write!(f, " in {}", self.function)
+    }
+}
@@ -0,0 +1,178 @@
+//! Values detached from any engine
+//!
+//! `Value::deep_clone_detached` copies a value out of the engine by the
+//! core of the structured clone algorithm: primitives, plain objects and
+//! arrays are copied, and an object reached twice, through a cycle or
+//! otherwise, is copied once and shared. The copy holds nothing of the
+//! engine, so it outlives it and can be sent to another thread;
+//! `Engine::adopt` turns it back into a value, in the same engine or any
+//! other.
+//!
+//! As with `structuredClone`, objects keep their own enumerable
+//! string-keyed properties only, as plain writable data, and lose their
+//! prototypes. Functions, symbols, and objects tied to the VM that made
+//! them (generators, promises, iterators), cannot be copied.
+
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::error::{Error, ErrorCode, Result};
+use super::value::{ObjectData, ObjectKind, PropertyMap, Value};
+
+/// A value copied out of an engine (see the module docs)
+///
+/// Objects are kept in a table and refer to each other by index, which is
+/// how sharing and cycles survive the copy. Two detached values are equal
+/// when they have the same structure, sharing included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetachedValue {
+    root: Detached,
+    objects: Vec<DetachedObject>,
+}
+
+/// A primitive, or one of the objects of a `DetachedValue`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Detached {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    /// Index in `DetachedValue::objects`
+    Object(usize),
+}
+
+/// An object of a `DetachedValue`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DetachedObject {
+    /// The elements of an array, `None` for holes; `None` for a plain
+    /// object
+    pub elements: Option<Vec<Option<Detached>>>,
+    /// Own enumerable properties, in order
+    pub properties: Vec<(String, Detached)>,
+}
+
+impl DetachedValue {
+    /// The copied value itself
+    pub fn root(&self) -> &Detached {
+        &self.root
+    }
+    
+    /// Every object the value reaches, in the order they were first met;
+    /// the root, if it is an object, comes first
+    pub fn objects(&self) -> &[DetachedObject] {
+        &self.objects
+    }
+    
+    /// A fresh value with this structure, made of new objects
+    pub(crate) fn attach(&self) -> Value {
+        // Make every object first, so elements and properties can refer
+        // to any of them
+        let instances: Vec<Rc<ObjectData>> = self.objects.iter().map(|object| Rc::new(match &object.elements {
+            Some(elements) => ObjectData::new_array(Vec::with_capacity(elements.len())),
+            None => ObjectData::new(),
+        })).collect();
+        for (object, instance) in self.objects.iter().zip(&instances) {
+            if let (Some(elements), Some(storage)) = (&object.elements, instance.array_elements()) {
+                *storage.borrow_mut() = elements.iter()
+                    .map(|element| element.as_ref().map(|element| element.attach(&instances)))
+                    .collect();
+            }
+            let mut properties = PropertyMap::new();
+            for (key, value) in &object.properties {
+                properties.insert(key.clone(), value.attach(&instances));
+            }
+            *instance.properties.borrow_mut() = properties;
+        }
+        self.root.attach(&instances)
+    }
+}
+
+impl Detached {
+    fn attach(&self, instances: &[Rc<ObjectData>]) -> Value {
+        match self {
+            Detached::Undefined => Value::Undefined,
+            Detached::Null => Value::Null,
+            Detached::Boolean(b) => Value::Boolean(*b),
+            Detached::Number(n) => Value::Number(*n),
+            Detached::String(s) => Value::string(s.as_str()),
+            Detached::Object(index) => Value::Object(instances[*index].clone()),
+        }
+    }
+}
+
+impl Value {
+    /// Copy this value out of the engine (see the module docs)
+    ///
+    /// Fails with `ErrorCode::NotCloneable` on a function, or on a
+    /// generator, promise or iterator, anywhere in the value.
+    pub fn deep_clone_detached(&self) -> Result<DetachedValue> {
+        let mut clone = Cloner::default();
+        let root = clone.record(self)?;
+        // Objects are copied breadth first rather than by recursion, so
+        // nesting depth is not limited by the stack
+        let mut next = 0;
+        while let Some(object) = clone.pending.get(next).cloned() {
+            let copied = clone.copy(&object)?;
+            clone.objects.push(copied);
+            next += 1;
+        }
+        Ok(DetachedValue { root, objects: clone.objects })
+    }
+}
+
+/// State of one `deep_clone_detached`
+#[derive(Default)]
+struct Cloner {
+    /// Objects met so far, in index order
+    pending: Vec<Rc<ObjectData>>,
+    /// Index of each object met so far, by address
+    ids: HashMap<*const ObjectData, usize>,
+    /// Copies of `pending[..objects.len()]`
+    objects: Vec<DetachedObject>,
+}
+
+impl Cloner {
+    /// Record a value, giving an object met for the first time an index
+    fn record(&mut self, value: &Value) -> Result<Detached> {
+        Ok(match value {
+            Value::Undefined => Detached::Undefined,
+            Value::Null => Detached::Null,
+            Value::Boolean(b) => Detached::Boolean(*b),
+            Value::Number(n) => Detached::Number(*n),
+            Value::String(s) => Detached::String(s.to_string()),
+            Value::Object(object) => {
+                let next = self.pending.len();
+                let index = *self.ids.entry(Rc::as_ptr(object)).or_insert(next);
+                if index == next {
+                    self.pending.push(object.clone());
+                }
+                Detached::Object(index)
+            }
+            Value::Function(_) => return Err(not_cloneable("a function")),
+            Value::Symbol(_) => return Err(not_cloneable("a symbol")),
+        })
+    }
+    
+    /// Copy an object's elements and properties
+    fn copy(&mut self, object: &ObjectData) -> Result<DetachedObject> {
+        let elements = match &object.kind {
+            ObjectKind::Ordinary | ObjectKind::FunctionPrototype(_) => None,
+            ObjectKind::Array(elements) => Some(
+                elements.borrow().iter()
+                    .map(|element| element.as_ref().map(|element| self.record(element)).transpose())
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            ObjectKind::Generator(_) => return Err(not_cloneable("a generator")),
+            ObjectKind::Promise(_) => return Err(not_cloneable("a promise")),
+            ObjectKind::ListIterator { .. } | ObjectKind::IteratorRecord { .. } => return Err(not_cloneable("an iterator")),
+        };
+        let properties = object.properties.borrow().enumerable()
+            .map(|(key, value)| Ok((key.to_string(), self.record(value)?)))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(DetachedObject { elements, properties })
+    }
+}
+
+fn not_cloneable(what: &str) -> Error {
+    Error::runtime(ErrorCode::NotCloneable, format!("DataCloneError: {} could not be cloned", what), None)
+}
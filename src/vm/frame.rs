@@ -3,15 +3,115 @@
 //! This module implements call frames for the VM's call stack,
 //! managing local variables, return addresses, and function contexts.
 
+use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
-use super::value::Value;
-use crate::bytecode::BytecodeFunction;
+use std::sync::Arc;
+use super::arena::FrameArena;
+use super::value::{ObjectData, Value};
+use crate::bytecode::{BytecodeFunction, JumpOffset};
+
+/// Default maximum call stack depth
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// How many returned frames' slot storage a call stack keeps
+const MAX_SPARE_STORAGE: usize = 64;
+
+/// How many returned frames' arena objects a call stack keeps
+const MAX_SPARE_OBJECTS: usize = 64;
+
+/// Storage for a function invocation's local variables
+///
+/// Locals live on the heap rather than in the frame so that closures
+/// created during the call can keep them alive and share them. `parent`
+/// links to the environment the function was defined in.
+pub struct Environment {
+    /// Local variable slots
+    pub slots: RefCell<Vec<Value>>,
+    /// Enclosing function's environment
+    pub parent: Option<Rc<Environment>>,
+}
+
+impl Environment {
+    /// Create an environment with `size` undefined slots
+    pub fn new(size: usize, parent: Option<Rc<Environment>>) -> Self {
+        Self::with_storage(Vec::new(), size, parent)
+    }
+    
+    /// Create an environment with `size` undefined slots, reusing the
+    /// allocation of `storage`
+    pub fn with_storage(mut storage: Vec<Value>, size: usize, parent: Option<Rc<Environment>>) -> Self {
+        storage.clear();
+        storage.resize(size, Value::Undefined);
+        Self {
+            slots: RefCell::new(storage),
+            parent,
+        }
+    }
+    
+    /// Walk `depth` parents up the environment chain
+    pub fn ancestor(self: &Rc<Self>, depth: usize) -> Option<Rc<Environment>> {
+        let mut env = self.clone();
+        for _ in 0..depth {
+            env = env.parent.clone()?;
+        }
+        Some(env)
+    }
+    
+    /// Read a slot
+    pub fn get(&self, index: usize) -> Result<Value, String> {
+        self.slots.borrow()
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("Local variable index {} out of bounds", index))
+    }
+    
+    /// Read a slot whose index the verifier has accepted
+    ///
+    /// The index is still bounds-checked, but there is no error to build
+    /// or return: one out of range is a verifier bug and panics.
+    pub fn get_or_panic(&self, index: usize) -> Value {
+        self.slots.borrow()[index].clone()
+    }
+    
+    /// Write a slot whose index the verifier has accepted (see
+    /// `get_or_panic`)
+    pub fn set_or_panic(&self, index: usize, value: Value) {
+        self.slots.borrow_mut()[index] = value;
+    }
+    
+    /// Write a slot
+    pub fn set(&self, index: usize, value: Value) -> Result<(), String> {
+        let mut slots = self.slots.borrow_mut();
+        let slot = slots.get_mut(index)
+            .ok_or_else(|| format!("Local variable index {} out of bounds", index))?;
+        *slot = value;
+        Ok(())
+    }
+    
+    /// Number of slots
+    pub fn len(&self) -> usize {
+        self.slots.borrow().len()
+    }
+    
+    /// Check if the environment has no slots
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Slots may hold closures that point back here, so only the shape is printed
+impl fmt::Debug for Environment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Environment(slots: {}, has_parent: {})", self.len(), self.parent.is_some())
+    }
+}
 
 /// A call frame represents a function invocation on the call stack
 #[derive(Debug, Clone)]
 pub struct CallFrame {
     /// The function being executed
-    pub function: Rc<BytecodeFunction>,
+    pub function: Arc<BytecodeFunction>,
     
     /// Instruction pointer (current bytecode offset)
     pub ip: usize,
@@ -20,80 +120,125 @@ pub struct CallFrame {
     pub stack_base: usize,
     
     /// Local variables storage
-    pub locals: Vec<Value>,
+    pub env: Rc<Environment>,
+    
+    /// The `this` value of the invocation
+    pub this_value: Value,
     
     /// Return address (instruction pointer in calling function)
     pub return_address: Option<usize>,
     
     /// Calling frame's stack base (for restoring on return)
     pub caller_stack_base: Option<usize>,
+    
+    /// Objects made at the function's arena sites
+    pub arena: FrameArena,
+    
+    /// Whether `function` comes from a `VerifiedFunction`, so a local
+    /// index out of range is a bug rather than an error to report
+    pub verified: bool,
+    
+    /// Whether the frame runs a constructor for `new`, whose result is
+    /// `this_value` unless it returns an object
+    pub constructing: bool,
 }
 
 impl CallFrame {
     /// Create a new call frame for the main program
-    pub fn new_main(function: Rc<BytecodeFunction>) -> Self {
+    pub fn new_main(function: Arc<BytecodeFunction>) -> Self {
         let locals_count = function.locals_count as usize;
         Self {
             function,
             ip: 0,
             stack_base: 0,
-            locals: vec![Value::Undefined; locals_count],
+            env: Rc::new(Environment::new(locals_count, None)),
+            this_value: Value::Undefined,
             return_address: None,
             caller_stack_base: None,
+            arena: FrameArena::default(),
+            verified: false,
+            constructing: false,
         }
     }
     
     /// Create a new call frame for a function call
     pub fn new_call(
-        function: Rc<BytecodeFunction>,
+        function: Arc<BytecodeFunction>,
         arguments: Vec<Value>,
         return_address: usize,
         caller_stack_base: usize,
         new_stack_base: usize,
     ) -> Self {
-        let mut locals = vec![Value::Undefined; function.locals_count as usize];
-        
-        // Copy arguments to the first local slots
+        Self::new_closure_call(function, None, arguments, return_address, caller_stack_base, new_stack_base)
+    }
+    
+    /// Create a new call frame whose environment is nested in `parent_env`
+    pub fn new_closure_call(
+        function: Arc<BytecodeFunction>,
+        parent_env: Option<Rc<Environment>>,
+        arguments: Vec<Value>,
+        return_address: usize,
+        caller_stack_base: usize,
+        new_stack_base: usize,
+    ) -> Self {
+        let env = Environment::new(function.locals_count as usize, parent_env);
+        Self::new_in_environment(function, env, arguments, return_address, caller_stack_base, new_stack_base)
+    }
+    
+    /// Create a new call frame over a fresh environment, such as one
+    /// built on storage from `CallStack::take_storage`
+    pub fn new_in_environment(
+        function: Arc<BytecodeFunction>,
+        env: Environment,
+        arguments: Vec<Value>,
+        return_address: usize,
+        caller_stack_base: usize,
+        new_stack_base: usize,
+    ) -> Self {
+        // Copy arguments to the first local slots; missing arguments stay
+        // undefined and extra arguments are ignored
         let arity = function.arity as usize;
-        let arg_count = arguments.len();
-        
-        for (i, arg) in arguments.into_iter().enumerate() {
-            if i < arity {
-                locals[i] = arg;
-            } else {
-                break; // Ignore extra arguments
-            }
-        }
-        
-        // Fill missing arguments with undefined
-        for i in arg_count..arity {
-            locals[i] = Value::Undefined;
+        for (slot, arg) in env.slots.borrow_mut().iter_mut().take(arity).zip(arguments) {
+            *slot = arg;
         }
         
         Self {
             function,
             ip: 0,
             stack_base: new_stack_base,
-            locals,
+            env: Rc::new(env),
+            this_value: Value::Undefined,
             return_address: Some(return_address),
             caller_stack_base: Some(caller_stack_base),
+            arena: FrameArena::default(),
+            verified: false,
+            constructing: false,
         }
     }
     
+    /// Set the `this` value
+    pub fn with_this(self, this_value: Value) -> Self {
+        Self { this_value, ..self }
+    }
+    
+    /// Mark the function as verified (see `verified`)
+    pub fn with_verified(self, verified: bool) -> Self {
+        Self { verified, ..self }
+    }
+    
+    /// Mark the frame as running a constructor (see `constructing`)
+    pub fn with_constructing(self, constructing: bool) -> Self {
+        Self { constructing, ..self }
+    }
+    
     /// Get a local variable by index
-    pub fn get_local(&self, index: usize) -> Result<&Value, String> {
-        self.locals
-            .get(index)
-            .ok_or_else(|| format!("Local variable index {} out of bounds", index))
+    pub fn get_local(&self, index: usize) -> Result<Value, String> {
+        self.env.get(index)
     }
     
     /// Set a local variable by index
     pub fn set_local(&mut self, index: usize, value: Value) -> Result<(), String> {
-        if index >= self.locals.len() {
-            return Err(format!("Local variable index {} out of bounds", index));
-        }
-        self.locals[index] = value;
-        Ok(())
+        self.env.set(index, value)
     }
     
     /// Get the current instruction pointer
@@ -112,12 +257,15 @@ impl CallFrame {
     }
     
     /// Apply a relative jump offset
-    pub fn jump_relative(&mut self, offset: i16) {
-        if offset >= 0 {
-            self.ip = self.ip.saturating_add(offset as usize);
-        } else {
-            self.ip = self.ip.saturating_sub((-offset) as usize);
-        }
+    ///
+    /// The target may be one past the last instruction (the function's
+    /// end) but no further, and not before the first.
+    pub fn jump_relative(&mut self, offset: JumpOffset) -> Result<(), String> {
+        let target = self.ip.checked_add_signed(offset.0 as isize)
+            .filter(|&target| target <= self.function.bytecode.len())
+            .ok_or_else(|| format!("Jump target {} out of bounds", self.ip as isize + offset.0 as isize))?;
+        self.ip = target;
+        Ok(())
     }
     
     /// Check if we've reached the end of the bytecode
@@ -136,7 +284,7 @@ impl CallFrame {
             "{}[{}] (locals: {}, stack_base: {})",
             self.function_name(),
             self.ip,
-            self.locals.len(),
+            self.env.len(),
             self.stack_base
         )
     }
@@ -150,6 +298,12 @@ pub struct CallStack {
     
     /// Maximum allowed call stack depth (to prevent stack overflow)
     max_depth: usize,
+    /// Slot storage of returned frames, kept for the next calls
+    spare_storage: Vec<Vec<Value>>,
+    /// Arena objects of returned frames, kept for the next calls
+    spare_objects: Vec<Rc<ObjectData>>,
+    /// Emptied arenas of returned frames, kept for the next calls
+    spare_arenas: Vec<FrameArena>,
 }
 
 impl CallStack {
@@ -158,6 +312,9 @@ impl CallStack {
         Self {
             frames: Vec::new(),
             max_depth,
+            spare_storage: Vec::new(),
+            spare_objects: Vec::new(),
+            spare_arenas: Vec::new(),
         }
     }
     
@@ -175,6 +332,79 @@ impl CallStack {
         self.frames.pop()
     }
     
+    /// Keep a finished frame's slot storage and arena objects for reuse
+    ///
+    /// Storage still shared with a closure is left to the closure, and so
+    /// are arena objects that anything still refers to.
+    pub fn recycle(&mut self, frame: CallFrame) {
+        let CallFrame { env, mut arena, .. } = frame;
+        if let Ok(env) = Rc::try_unwrap(env) {
+            let mut storage = env.slots.into_inner();
+            storage.clear();
+            if self.spare_storage.len() < MAX_SPARE_STORAGE {
+                self.spare_storage.push(storage);
+            }
+        }
+        // With the locals gone, an object referred to elsewhere escaped
+        for object in arena.drain_unreferenced() {
+            if self.spare_objects.len() >= MAX_SPARE_OBJECTS {
+                break;
+            }
+            object.release_values();
+            self.spare_objects.push(object);
+        }
+        if arena.has_storage() && self.spare_arenas.len() < MAX_SPARE_STORAGE {
+            self.spare_arenas.push(arena);
+        }
+    }
+    
+    /// An object for arena site `site` of the current frame, as left by
+    /// its last use: one the frame made there before that nothing else
+    /// refers to, a spare from a returned frame, or a new one
+    ///
+    /// `array` is whether the site makes arrays. The caller resets the
+    /// object (see `ObjectData::reset`).
+    pub fn arena_object(&mut self, site: usize, array: bool) -> Option<Rc<ObjectData>> {
+        let frame = self.frames.last_mut()?;
+        if let Some(object) = frame.arena.reusable(site) {
+            return Some(object);
+        }
+        let spare = self.spare_objects.iter()
+            .rposition(|object| object.array_elements().is_some() == array)
+            .map(|index| self.spare_objects.swap_remove(index));
+        let object = spare.unwrap_or_else(|| Rc::new(match array {
+            true => ObjectData::new_array(Vec::new()),
+            false => ObjectData::new(),
+        }));
+        if !frame.arena.has_storage() {
+            if let Some(arena) = self.spare_arenas.pop() {
+                frame.arena = arena;
+            }
+        }
+        frame.arena.keep(site, object.clone());
+        Some(object)
+    }
+    
+    /// Slot storage for a new environment, recycled when possible
+    pub fn take_storage(&mut self) -> Vec<Value> {
+        self.spare_storage.pop().unwrap_or_default()
+    }
+    
+    /// Get the frame at `index`, 0 being the outermost
+    pub fn frame(&self, index: usize) -> Option<&CallFrame> {
+        self.frames.get(index)
+    }
+    
+    /// The frames, outermost first
+    pub(crate) fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+    
+    /// Arena objects kept from returned frames
+    pub(crate) fn spare_objects(&self) -> &[Rc<ObjectData>] {
+        &self.spare_objects
+    }
+    
     /// Get the current (top) frame
     pub fn current_frame(&self) -> Option<&CallFrame> {
         self.frames.last()
@@ -213,7 +443,7 @@ impl CallStack {
 
 impl Default for CallStack {
     fn default() -> Self {
-        Self::new(1000) // Default maximum recursion depth
+        Self::new(DEFAULT_MAX_CALL_DEPTH)
     }
 }
 
@@ -223,20 +453,20 @@ mod tests {
     
     #[test]
     fn test_frame_locals() {
-        let func = Rc::new(BytecodeFunction::new("test".to_string(), 2, 5, 0));
+        let func = Arc::new(BytecodeFunction::new("test".to_string(), 2, 5, 0));
         let args = vec![Value::Number(10.0), Value::Number(20.0)];
         let mut frame = CallFrame::new_call(func, args, 0, 0, 0);
         
         // Check arguments are properly set
-        assert_eq!(frame.get_local(0).unwrap(), &Value::Number(10.0));
-        assert_eq!(frame.get_local(1).unwrap(), &Value::Number(20.0));
+        assert_eq!(frame.get_local(0).unwrap(), Value::Number(10.0));
+        assert_eq!(frame.get_local(1).unwrap(), Value::Number(20.0));
         
         // Check other locals are undefined
-        assert_eq!(frame.get_local(2).unwrap(), &Value::Undefined);
+        assert_eq!(frame.get_local(2).unwrap(), Value::Undefined);
         
         // Test setting locals
         frame.set_local(2, Value::string("hello")).unwrap();
-        assert_eq!(frame.get_local(2).unwrap(), &Value::string("hello"));
+        assert_eq!(frame.get_local(2).unwrap(), Value::string("hello"));
     }
     
     #[test]
@@ -244,7 +474,7 @@ mod tests {
         let mut stack = CallStack::new(10);
         assert!(stack.is_empty());
         
-        let func = Rc::new(BytecodeFunction::new_main());
+        let func = Arc::new(BytecodeFunction::new_main());
         let frame = CallFrame::new_main(func);
         stack.push(frame).unwrap();
         
@@ -252,14 +482,52 @@ mod tests {
         assert!(!stack.is_empty());
         
         let popped = stack.pop().unwrap();
-        assert_eq!(popped.function_name(), "__main__");
+        assert_eq!(popped.function_name(), "<main>");
         assert!(stack.is_empty());
     }
     
+    #[test]
+    fn test_arena_objects_are_recycled_unless_referenced() {
+        let mut stack = CallStack::new(10);
+        let func = Arc::new(BytecodeFunction::new("f".to_string(), 0, 0, 0));
+        stack.push(CallFrame::new_call(func.clone(), vec![], 0, 0, 0)).unwrap();
+        let escaped = stack.arena_object(0, false).unwrap();
+        let unreferenced = Rc::as_ptr(&stack.arena_object(1, true).unwrap());
+        let frame = stack.pop().unwrap();
+        stack.recycle(frame);
+        
+        // Only the object nothing else holds is handed out again
+        stack.push(CallFrame::new_call(func, vec![], 0, 0, 0)).unwrap();
+        assert_eq!(Rc::as_ptr(&stack.arena_object(0, true).unwrap()), unreferenced);
+        assert!(!Rc::ptr_eq(&stack.arena_object(1, false).unwrap(), &escaped));
+    }
+    
+    #[test]
+    fn test_storage_is_recycled() {
+        let mut stack = CallStack::new(10);
+        let func = Arc::new(BytecodeFunction::new("f".to_string(), 0, 4, 0));
+        let mut frame = CallFrame::new_call(func.clone(), vec![], 0, 0, 0);
+        frame.set_local(0, Value::Number(1.0)).unwrap();
+        let storage_ptr = frame.env.slots.borrow().as_ptr();
+        stack.recycle(frame);
+        
+        let storage = stack.take_storage();
+        assert_eq!(storage.as_ptr(), storage_ptr);
+        let env = Environment::with_storage(storage, 4, None);
+        assert_eq!(env.get(0).unwrap(), Value::Undefined);
+        
+        // Storage a closure still shares is not reused
+        let frame = CallFrame::new_call(func, vec![], 0, 0, 0);
+        let captured = frame.env.clone();
+        stack.recycle(frame);
+        assert_eq!(stack.take_storage().capacity(), 0);
+        assert_eq!(captured.len(), 4);
+    }
+    
     #[test]
     fn test_max_call_depth() {
         let mut stack = CallStack::new(3);
-        let func = Rc::new(BytecodeFunction::new_main());
+        let func = Arc::new(BytecodeFunction::new_main());
         
         // Push 3 frames (should succeed)
         for _ in 0..3 {
@@ -0,0 +1,226 @@
+//! GC stress mode: checks the rooting rules of `vm::handles`
+//!
+//! There is no tracing collector yet; values are reference counted. With
+//! `VMConfig::gc_stress` the VM still runs a collection at every
+//! allocation: before each one a native makes through its `CallContext`,
+//! and after each instruction that allocated. A collection marks what the
+//! VM's roots reach and *poisons* each object made during the run that
+//! it didn't reach but that is still alive. Something outside the VM
+//! holds such an object without rooting it, and a tracing collector
+//! would have freed it. Poisoning empties the object and leaves its cells
+//! borrowed, so the next use panics with `already mutably borrowed`
+//! instead of quietly reading what a real collector would have reused.
+//!
+//! The roots are everything the VM holds (operand stack, accumulator,
+//! frames, globals, queued tasks, handles) plus the `Persistent`s of its
+//! `PersistentRoots`. Only objects made while the VM runs are checked;
+//! ones the host builds are reached through its handles anyway.
+//!
+//! A collection walks the whole heap, so stress runs are slow: it is for
+//! the test suite, not for production.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::rc::{Rc, Weak};
+use super::frame::{CallFrame, Environment};
+use super::promise::PromiseState;
+use super::value::{FunctionRef, GeneratorState, ObjectData, ObjectKind, PropertyMap, Value};
+
+thread_local! {
+    /// The heap of the stress-mode VM running on this thread, if any
+    static ACTIVE: RefCell<Option<Rc<StressHeap>>> = const { RefCell::new(None) };
+}
+
+/// Objects a stress-mode VM has made, for its collections to check
+#[derive(Debug, Default)]
+pub(crate) struct StressHeap {
+    objects: RefCell<Vec<Weak<ObjectData>>>,
+    /// Whether an object was made since the last collection
+    allocated: Cell<bool>,
+    collections: Cell<u64>,
+}
+
+impl StressHeap {
+    /// Record the objects made on this thread in this heap until the
+    /// guard is dropped
+    pub(crate) fn activate(self: &Rc<Self>) -> ActiveHeap {
+        let previous = ACTIVE.with(|active| active.replace(Some(self.clone())));
+        ActiveHeap { previous }
+    }
+    
+    /// Whether an object was made since the last call
+    pub(crate) fn take_allocated(&self) -> bool {
+        self.allocated.replace(false)
+    }
+    
+    /// Number of collections run so far
+    pub(crate) fn collections(&self) -> u64 {
+        self.collections.get()
+    }
+    
+    /// Poison the objects of this heap that `roots` don't reach
+    pub(crate) fn collect(&self, roots: Roots) {
+        self.collections.set(self.collections.get() + 1);
+        let marked = roots.marked;
+        let mut objects = self.objects.borrow_mut();
+        objects.retain(|object| object.strong_count() > 0);
+        for object in objects.iter().filter_map(Weak::upgrade) {
+            if !marked.contains(&Rc::as_ptr(&object)) {
+                object.poison();
+            }
+        }
+        objects.retain(|object| object.strong_count() > 0);
+    }
+}
+
+/// Restores the previously active heap when dropped
+pub(crate) struct ActiveHeap {
+    previous: Option<Rc<StressHeap>>,
+}
+
+impl Drop for ActiveHeap {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| *active.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Note a new object in the active stress heap, if there is one
+pub(crate) fn track(object: &Rc<ObjectData>) {
+    ACTIVE.with(|active| {
+        if let Some(heap) = &*active.borrow() {
+            heap.objects.borrow_mut().push(Rc::downgrade(object));
+            heap.allocated.set(true);
+        }
+    });
+}
+
+/// The objects reachable from a set of roots
+#[derive(Default)]
+pub(crate) struct Roots {
+    marked: HashSet<*const ObjectData>,
+    environments: HashSet<*const Environment>,
+    functions: HashSet<*const ()>,
+    pending: Vec<Value>,
+    pending_environments: Vec<Rc<Environment>>,
+}
+
+impl Roots {
+    /// Mark what `value` reaches
+    pub(crate) fn value(&mut self, value: &Value) {
+        self.pending.push(value.clone());
+        self.drain();
+    }
+    
+    /// Mark what `values` reach
+    pub(crate) fn values<'v>(&mut self, values: impl IntoIterator<Item = &'v Value>) {
+        self.pending.extend(values.into_iter().cloned());
+        self.drain();
+    }
+    
+    /// Mark what the locals, receiver and arena objects of `frame` reach
+    pub(crate) fn frame(&mut self, frame: &CallFrame) {
+        self.push_frame(frame);
+        self.drain();
+    }
+    
+    /// Mark what `objects` reach, themselves included
+    pub(crate) fn objects<'o>(&mut self, objects: impl IntoIterator<Item = &'o Rc<ObjectData>>) {
+        self.pending.extend(objects.into_iter().map(|object| Value::Object(object.clone())));
+        self.drain();
+    }
+    
+    fn push_frame(&mut self, frame: &CallFrame) {
+        self.pending_environments.push(frame.env.clone());
+        self.pending.push(frame.this_value.clone());
+        self.pending.extend(frame.arena.objects().map(|object| Value::Object(object.clone())));
+    }
+    
+    fn drain(&mut self) {
+        loop {
+            if let Some(value) = self.pending.pop() {
+                self.trace(value);
+            } else if let Some(environment) = self.pending_environments.pop() {
+                if self.environments.insert(Rc::as_ptr(&environment)) {
+                    self.pending.extend(environment.slots.borrow().iter().cloned());
+                    self.pending_environments.extend(environment.parent.clone());
+                }
+            } else {
+                return;
+            }
+        }
+    }
+    
+    fn trace(&mut self, value: Value) {
+        match value {
+            Value::Object(object) => self.trace_object(&object),
+            Value::Function(function) => self.trace_function(&function),
+            _ => {}
+        }
+    }
+    
+    fn trace_object(&mut self, object: &Rc<ObjectData>) {
+        if !self.marked.insert(Rc::as_ptr(object)) {
+            return;
+        }
+        self.properties(&object.properties.borrow());
+        if let Some(prototype) = object.prototype() {
+            self.pending.push(Value::Object(prototype));
+        }
+        match &object.kind {
+            ObjectKind::Ordinary | ObjectKind::FunctionPrototype(_) => {}
+            ObjectKind::Array(elements) => self.pending.extend(elements.borrow().iter().flatten().cloned()),
+            ObjectKind::Generator(state) => {
+                if let GeneratorState::Suspended { frame, operands } = &*state.borrow() {
+                    self.push_frame(frame);
+                    self.pending.extend(operands.iter().cloned());
+                }
+            }
+            ObjectKind::Promise(state) => match &*state.borrow() {
+                PromiseState::Pending(reactions) => {
+                    for reaction in reactions {
+                        self.pending.push(reaction.on_fulfilled.clone());
+                        self.pending.push(reaction.on_rejected.clone());
+                        self.pending.extend(reaction.derived.clone());
+                    }
+                }
+                PromiseState::Fulfilled(value) | PromiseState::Rejected { reason: value, .. } => {
+                    self.pending.push(value.clone());
+                }
+            },
+            ObjectKind::ListIterator { source, .. } => self.pending.push(source.clone()),
+            ObjectKind::IteratorRecord { iterator, next, .. } => {
+                self.pending.push(iterator.clone());
+                self.pending.push(next.clone());
+            }
+        }
+    }
+    
+    fn trace_function(&mut self, function: &FunctionRef) {
+        match function {
+            FunctionRef::Bytecode(_) | FunctionRef::NativeMethod(_) | FunctionRef::Host(_) => {}
+            FunctionRef::Closure(closure) => {
+                if self.functions.insert(Rc::as_ptr(closure).cast()) {
+                    self.properties(&closure.properties.borrow());
+                    self.pending.extend(closure.this.clone());
+                    self.pending_environments.push(closure.env.clone());
+                }
+            }
+            FunctionRef::Bound(bound) => {
+                if self.functions.insert(Rc::as_ptr(bound).cast()) {
+                    self.pending.push(bound.target.clone());
+                    self.pending.push(bound.this.clone());
+                    self.pending.extend(bound.args.iter().cloned());
+                }
+            }
+            FunctionRef::Builtin(builtin) => {
+                if self.functions.insert(Rc::as_ptr(builtin).cast()) {
+                    self.properties(&builtin.properties.borrow());
+                }
+            }
+        }
+    }
+    
+    fn properties(&mut self, properties: &PropertyMap) {
+        self.pending.extend(properties.values().cloned());
+    }
+}
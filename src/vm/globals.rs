@@ -0,0 +1,159 @@
+//! Global name resolution
+//!
+//! `LdaGlobal` and `StaGlobal` look names up in a [`GlobalScope`], a short
+//! chain of name→binding maps. The base holds the global object's
+//! bindings: built-ins, host globals and whatever scripts create. Layers
+//! pushed over it hold bindings for one evaluation, such as those given to
+//! `Engine::eval_with_globals`; they shadow base bindings of the same name
+//! until they are popped, and the base is never touched on their account.
+//! A layer also records which of its bindings scripts assigned, so the
+//! host can tell a value it put there from one a script wrote.
+//! A module environment would be one more layer.
+//!
+//! Most code runs with the base alone, and then every lookup is a single
+//! hash map probe, as it was before layers existed.
+
+use std::collections::{HashMap, HashSet};
+use super::machine::GlobalSlot;
+use super::value::Value;
+
+/// Global bindings: the base and the layers over it
+#[derive(Debug, Default)]
+pub(crate) struct GlobalScope {
+    base: HashMap<String, GlobalSlot>,
+    /// Layers over the base, the innermost last
+    layers: Vec<Layer>,
+}
+
+/// Bindings pushed over the base, and the names scripts assigned in them
+#[derive(Debug, Default)]
+struct Layer {
+    bindings: HashMap<String, GlobalSlot>,
+    written: HashSet<String>,
+}
+
+impl GlobalScope {
+    pub(crate) fn new(base: HashMap<String, GlobalSlot>) -> Self {
+        Self { base, layers: Vec::new() }
+    }
+    
+    /// The binding `name` resolves to: the innermost layer's that has one,
+    /// or the base's
+    #[inline]
+    pub(crate) fn get(&self, name: &str) -> Option<&GlobalSlot> {
+        if self.layers.is_empty() {
+            return self.base.get(name);
+        }
+        self.layers.iter().rev()
+            .find_map(|layer| layer.bindings.get(name))
+            .or_else(|| self.base.get(name))
+    }
+    
+    /// The binding `name` resolves to, for writing; a write changes the
+    /// layer that owns the binding
+    #[inline]
+    pub(crate) fn get_mut(&mut self, name: &str) -> Option<&mut GlobalSlot> {
+        match self.layers.iter().rposition(|layer| layer.bindings.contains_key(name)) {
+            Some(index) => self.layers[index].bindings.get_mut(name),
+            None => self.base.get_mut(name),
+        }
+    }
+    
+    /// Record that a script assigned the binding `name` resolves to, if a
+    /// layer owns it
+    #[inline]
+    pub(crate) fn mark_written(&mut self, name: &str) {
+        if self.layers.is_empty() {
+            return;
+        }
+        if let Some(layer) = self.layers.iter_mut().rev().find(|layer| layer.bindings.contains_key(name)) {
+            if !layer.written.contains(name) {
+                layer.written.insert(name.to_string());
+            }
+        }
+    }
+    
+    /// Whether a script has assigned `name` in the innermost layer since
+    /// it was pushed
+    pub(crate) fn is_written(&self, name: &str) -> bool {
+        self.layers.last().is_some_and(|layer| layer.written.contains(name))
+    }
+    
+    /// Define or replace a binding of the base
+    pub(crate) fn insert(&mut self, name: String, slot: GlobalSlot) {
+        self.base.insert(name, slot);
+    }
+    
+    /// Remove a binding of the base
+    pub(crate) fn remove(&mut self, name: &str) -> Option<GlobalSlot> {
+        self.base.remove(name)
+    }
+    
+    /// The global object's bindings, without any layer
+    pub(crate) fn base(&self) -> &HashMap<String, GlobalSlot> {
+        &self.base
+    }
+    
+    /// The values of the base's and the layers' bindings
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        let layers = self.layers.iter().flat_map(|layer| layer.bindings.values());
+        self.base.values().chain(layers).map(|slot| &slot.value)
+    }
+    
+    /// Every name that resolves to something, shadowed ones included
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().map(|layer| &layer.bindings).chain([&self.base]).flat_map(|bindings| bindings.keys().map(String::as_str))
+    }
+    
+    pub(crate) fn push_layer(&mut self, bindings: HashMap<String, GlobalSlot>) {
+        self.layers.push(Layer { bindings, written: HashSet::new() });
+    }
+    
+    /// Remove the innermost layer, returning its bindings as evaluation
+    /// left them
+    pub(crate) fn pop_layer(&mut self) -> Option<HashMap<String, GlobalSlot>> {
+        self.layers.pop().map(|layer| layer.bindings)
+    }
+    
+    /// Number of layers over the base
+    pub(crate) fn depth(&self) -> usize {
+        self.layers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Value;
+    
+    fn layer(bindings: &[(&str, f64)]) -> HashMap<String, GlobalSlot> {
+        bindings.iter().map(|&(name, n)| (name.to_string(), GlobalSlot::new(Value::Number(n)))).collect()
+    }
+    
+    fn value(scope: &GlobalScope, name: &str) -> Option<Value> {
+        scope.get(name).map(|slot| slot.value.clone())
+    }
+    
+    #[test]
+    fn test_innermost_layer_wins_and_writes_go_to_the_owner() {
+        let mut scope = GlobalScope::new(layer(&[("a", 1.0), ("b", 2.0)]));
+        scope.push_layer(layer(&[("a", 10.0)]));
+        scope.push_layer(layer(&[("c", 30.0)]));
+        assert_eq!(value(&scope, "a"), Some(Value::Number(10.0)));
+        assert_eq!(value(&scope, "c"), Some(Value::Number(30.0)));
+        
+        scope.get_mut("a").unwrap().value = Value::Number(11.0);
+        scope.mark_written("a");
+        scope.get_mut("b").unwrap().value = Value::Number(22.0);
+        scope.mark_written("b");
+        // Only the layer that owns a binding records the write
+        assert!(!scope.is_written("a"));
+        assert!(!scope.is_written("b"));
+        assert_eq!(scope.pop_layer(), Some(layer(&[("c", 30.0)])));
+        assert!(scope.is_written("a"));
+        assert_eq!(scope.pop_layer(), Some(layer(&[("a", 11.0)])));
+        assert_eq!(scope.depth(), 0);
+        assert_eq!(value(&scope, "a"), Some(Value::Number(1.0)));
+        assert_eq!(value(&scope, "b"), Some(Value::Number(22.0)));
+    }
+}
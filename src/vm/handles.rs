@@ -0,0 +1,223 @@
+//! Handles: how native and host code hold values so a collector can find
+//! them
+//!
+//! Values are reference counted, so today a `Value` kept in a Rust local
+//! keeps its object alive by itself. A tracing collector would only see
+//! what it can reach from the VM's roots, so code outside the
+//! interpreter holds values the way it will have to then:
+//!
+//! - Each native call runs in a `HandleScope` that its `CallContext`
+//!   opens. `this` and the arguments are rooted there, and so is what the
+//!   context's allocation helpers return, as `Local`s. Any other value a
+//!   native keeps across an allocation or a call back into JavaScript it
+//!   roots with `CallContext::root`. The handles go when the call
+//!   returns; `CallContext::scope` opens a nested scope for a loop that
+//!   would otherwise pile them up.
+//! - Host code that keeps a value between calls into the VM holds it in
+//!   a `Persistent`, which is a root until dropped.
+//!
+//! `VMConfig::gc_stress` checks that both rules are followed (see
+//! `vm::gc`).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::rc::Rc;
+use super::machine::VM;
+use super::value::{ObjectData, Value};
+
+/// Something a handle can root
+pub trait Rootable: Clone {
+    /// The value the collector traces for it
+    fn to_root(&self) -> Value;
+}
+
+impl Rootable for Value {
+    fn to_root(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl Rootable for Rc<ObjectData> {
+    fn to_root(&self) -> Value {
+        Value::Object(self.clone())
+    }
+}
+
+/// The handles of one native call, or of a nested scope inside one
+///
+/// Opening a scope marks the top of the VM's handle stack; closing it
+/// releases the handles made since.
+#[derive(Debug)]
+pub struct HandleScope {
+    base: usize,
+}
+
+impl HandleScope {
+    pub(crate) fn open(vm: &VM) -> Self {
+        Self { base: vm.handle_count() }
+    }
+    
+    pub(crate) fn close(&self, vm: &mut VM) {
+        vm.release_handles(self.base);
+    }
+}
+
+/// A value rooted in the handle scope of a native call
+///
+/// The lifetime ties it to the `CallContext` it came from, whose scope
+/// releases the root when the call returns. Return or store the plain
+/// value (`Local::get`) to keep it longer.
+#[derive(Clone)]
+pub struct Local<'s, T: Rootable = Value> {
+    value: T,
+    scope: PhantomData<&'s ()>,
+}
+
+impl<T: Rootable> Local<'_, T> {
+    /// Root `value` in the innermost scope of `vm`
+    pub(crate) fn new(vm: &mut VM, value: T) -> Self {
+        vm.push_handle(value.to_root());
+        Self { value, scope: PhantomData }
+    }
+    
+    /// The handled value
+    pub fn get(&self) -> T {
+        self.value.clone()
+    }
+}
+
+impl<T: Rootable> Deref for Local<'_, T> {
+    type Target = T;
+    
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Rootable + fmt::Debug> fmt::Debug for Local<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Local({:?})", self.value)
+    }
+}
+
+/// Values host code holds between calls into the VM, by slot
+///
+/// Shared by the `Persistent`s made from it and by the VMs that trace
+/// it, so a handle can outlive the VM it was made for; an `Engine` that
+/// makes a new VM for each script hands every one the same roots.
+#[derive(Clone, Default)]
+pub struct PersistentRoots {
+    slots: Rc<RefCell<Slots>>,
+}
+
+#[derive(Default)]
+struct Slots {
+    values: Vec<Option<Value>>,
+    free: Vec<usize>,
+}
+
+impl PersistentRoots {
+    /// Every value held, for the collector
+    pub(crate) fn values(&self) -> Vec<Value> {
+        self.slots.borrow().values.iter().flatten().cloned().collect()
+    }
+    
+    /// Number of `Persistent`s alive
+    pub fn len(&self) -> usize {
+        let slots = self.slots.borrow();
+        slots.values.len() - slots.free.len()
+    }
+    
+    /// Whether no `Persistent` is alive
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Debug for PersistentRoots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PersistentRoots(len: {})", self.len())
+    }
+}
+
+/// A value host code holds across calls into the VM
+///
+/// It is a root for as long as it exists: dropping it is what lets the
+/// value go. `set` replaces the value in place. Cloning makes a second,
+/// independent root.
+pub struct Persistent {
+    roots: PersistentRoots,
+    slot: usize,
+}
+
+impl Persistent {
+    /// Root `value` in `roots`
+    pub fn new(roots: &PersistentRoots, value: Value) -> Self {
+        let mut slots = roots.slots.borrow_mut();
+        let slot = match slots.free.pop() {
+            Some(slot) => {
+                slots.values[slot] = Some(value);
+                slot
+            }
+            None => {
+                slots.values.push(Some(value));
+                slots.values.len() - 1
+            }
+        };
+        drop(slots);
+        Self { roots: roots.clone(), slot }
+    }
+    
+    /// The held value
+    pub fn get(&self) -> Value {
+        self.roots.slots.borrow().values[self.slot].clone().unwrap_or_default()
+    }
+    
+    /// Hold `value` instead
+    pub fn set(&self, value: Value) {
+        self.roots.slots.borrow_mut().values[self.slot] = Some(value);
+    }
+}
+
+impl Clone for Persistent {
+    fn clone(&self) -> Self {
+        Self::new(&self.roots, self.get())
+    }
+}
+
+impl Drop for Persistent {
+    fn drop(&mut self) {
+        let mut slots = self.roots.slots.borrow_mut();
+        slots.values[self.slot] = None;
+        slots.free.push(self.slot);
+    }
+}
+
+impl fmt::Debug for Persistent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Persistent({:?})", self.get())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_persistent_releases_its_slot_when_dropped() {
+        let roots = PersistentRoots::default();
+        let first = Persistent::new(&roots, Value::Number(1.0));
+        let second = first.clone();
+        second.set(Value::Number(2.0));
+        assert_eq!(first.get(), Value::Number(1.0));
+        assert_eq!(roots.values(), vec![Value::Number(1.0), Value::Number(2.0)]);
+        
+        drop(first);
+        assert_eq!(roots.len(), 1);
+        let third = Persistent::new(&roots, Value::Null);
+        assert_eq!(third.slot, 0);
+        assert_eq!(second.get(), Value::Number(2.0));
+    }
+}
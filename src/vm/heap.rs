@@ -0,0 +1,234 @@
+//! Accounting for the memory script values take (`VMConfig::max_heap_bytes`)
+//!
+//! Values are reference counted, so there is no collector to ask how much
+//! is live. Instead the VM charges each string and object it makes to a
+//! `HeapAccount`, which keeps a `Weak` handle beside every charge. A
+//! charge that would pass the limit first releases the charges whose
+//! value has been dropped, and fails only if the live values alone leave
+//! no room. Releases also happen whenever the list of charges has doubled
+//! since the last one, so the list stays proportional to what is live.
+//!
+//! The figures are estimates from sizes and capacities, not measurements
+//! of the allocator:
+//!
+//! - a string counts its header, its capacity and the reference count box;
+//! - an object counts its fixed size, a fixed amount per property, which
+//!   assumes short keys, and its element capacity;
+//! - each block also counts what the allocator keeps beside it;
+//! - growth of an object already charged is charged as a second charge on
+//!   the same object, released with it;
+//! - a value a native call returns is charged only when nothing else holds
+//!   it yet, and only for itself, not for new values inside it;
+//! - closures, environments, generators, promises and the objects of
+//!   frame arenas are not charged.
+//!
+//! So `used` only grows while values are live and drops once they are
+//! gone, but it is an approximation: a limit is a ceiling on the bulk of
+//! what scripts allocate, not an exact bound on the process's memory.
+//! The charge for an allocation is made just after it, so one allocation
+//! can pass the limit by its own size before the error is raised; only
+//! writes to an array's `length` or past its end, which can ask for
+//! millions of elements at once, are checked before they allocate.
+
+use std::mem::size_of;
+use std::rc::{Rc, Weak};
+
+use crate::error::{Error, Result, RuntimeErrorKind};
+use super::value::{ObjectData, PropertyAttributes, Value};
+
+/// The reference counts `Rc` keeps before each value
+const RC_HEADER_BYTES: usize = 2 * size_of::<usize>();
+
+/// What the allocator keeps beside each block, and the least it hands out
+const BLOCK_BYTES: usize = 16;
+const MIN_BLOCK_BYTES: usize = 32;
+
+/// A property: its entry in the ordered list, about two buckets of the
+/// index, which is kept partly empty, and two copies of a short key
+const PROPERTY_BYTES: usize =
+    size_of::<(String, Value, PropertyAttributes)>() + 2 * size_of::<(String, usize)>() + 2 * MIN_BLOCK_BYTES;
+
+/// One array element slot
+const ELEMENT_BYTES: usize = size_of::<Option<Value>>();
+
+/// What a charge was made for, to tell when it has been dropped
+enum Held {
+    String(Weak<String>),
+    Object(Weak<ObjectData>),
+}
+
+impl Held {
+    fn is_live(&self) -> bool {
+        match self {
+            Held::String(string) => string.strong_count() > 0,
+            Held::Object(object) => object.strong_count() > 0,
+        }
+    }
+}
+
+/// How much of an object can grow after it is made
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Extent {
+    properties: usize,
+    elements: usize,
+}
+
+impl Extent {
+    pub(crate) fn of(object: &ObjectData) -> Self {
+        Self {
+            properties: object.properties.borrow().len(),
+            elements: object.array_elements().map_or(0, |elements| elements.borrow().capacity()),
+        }
+    }
+
+    /// Bytes added since `earlier`; nothing for shrinking, which keeps
+    /// its capacity
+    fn growth_since(self, earlier: Extent) -> usize {
+        self.properties.saturating_sub(earlier.properties) * PROPERTY_BYTES
+            + self.elements.saturating_sub(earlier.elements) * ELEMENT_BYTES
+    }
+}
+
+/// Bytes charged to the values a VM has made, and its limit
+pub(crate) struct HeapAccount {
+    limit: usize,
+    used: usize,
+    /// Charges not yet released, with what each was for
+    charges: Vec<(Held, usize)>,
+    /// How many charges were left after the last release
+    settled: usize,
+}
+
+impl HeapAccount {
+    /// Charges kept before the first routine release
+    const MIN_SETTLED: usize = 1024;
+
+    pub(crate) fn new(limit: usize) -> Self {
+        Self { limit, used: 0, charges: Vec::new(), settled: 0 }
+    }
+
+    /// Bytes charged to values that are still live
+    pub(crate) fn live(&mut self) -> usize {
+        self.release();
+        self.used
+    }
+
+    /// Charge a new string or object; other values take no heap memory
+    /// of their own
+    pub(crate) fn charge(&mut self, value: &Value) -> Result<()> {
+        let (held, bytes) = match value {
+            Value::String(string) => (Held::String(Rc::downgrade(string)), string_bytes(string)),
+            Value::Object(object) => (Held::Object(Rc::downgrade(object)), object_bytes(object)),
+            _ => return Ok(()),
+        };
+        self.add(held, bytes)
+    }
+
+    /// Charge what `object` grew by since its extent was `before`
+    pub(crate) fn charge_growth(&mut self, object: &Rc<ObjectData>, before: Extent) -> Result<()> {
+        let bytes = Extent::of(object).growth_since(before);
+        if bytes == 0 {
+            return Ok(());
+        }
+        self.add(Held::Object(Rc::downgrade(object)), bytes)
+    }
+
+    /// Fail if growing `object` to hold `length` elements would pass the
+    /// limit, before the storage for them is allocated
+    pub(crate) fn check_elements(&mut self, object: &ObjectData, length: usize) -> Result<()> {
+        let capacity = object.array_elements().map_or(0, |elements| elements.borrow().capacity());
+        self.make_room(length.saturating_sub(capacity) * ELEMENT_BYTES)
+    }
+
+    fn add(&mut self, held: Held, bytes: usize) -> Result<()> {
+        self.make_room(bytes)?;
+        self.used += bytes;
+        self.charges.push((held, bytes));
+        if self.charges.len() >= 2 * self.settled.max(Self::MIN_SETTLED) {
+            self.release();
+        }
+        Ok(())
+    }
+
+    /// Make sure `bytes` more fit under the limit, releasing what has been
+    /// dropped if they don't
+    fn make_room(&mut self, bytes: usize) -> Result<()> {
+        if self.used + bytes > self.limit {
+            self.release();
+            if self.used + bytes > self.limit {
+                return Err(Error::with_kind(RuntimeErrorKind::AllocationLimit { limit: self.limit }, None));
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop the charges of values that are gone
+    fn release(&mut self) {
+        let mut used = self.used;
+        self.charges.retain(|(held, bytes)| {
+            let live = held.is_live();
+            if !live {
+                used -= bytes;
+            }
+            live
+        });
+        self.used = used;
+        self.settled = self.charges.len();
+    }
+}
+
+fn string_bytes(string: &String) -> usize {
+    let text = if string.capacity() > 0 { BLOCK_BYTES + string.capacity() } else { 0 };
+    BLOCK_BYTES + RC_HEADER_BYTES + size_of::<String>() + text
+}
+
+fn object_bytes(object: &ObjectData) -> usize {
+    let extent = Extent::of(object);
+    // The property list and index, and the elements, are blocks of their own
+    let blocks = 1 + 2 * usize::from(extent.properties > 0) + usize::from(extent.elements > 0);
+    blocks * BLOCK_BYTES + RC_HEADER_BYTES + size_of::<ObjectData>() + extent.growth_since(Extent { properties: 0, elements: 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dropped_values_are_released_when_the_limit_is_near() {
+        let string = Value::string("x".repeat(100));
+        let bytes = match &string {
+            Value::String(s) => string_bytes(s),
+            _ => unreachable!(),
+        };
+        let mut heap = HeapAccount::new(bytes * 2);
+        heap.charge(&string).unwrap();
+        let kept = Value::string("y".repeat(100));
+        heap.charge(&kept).unwrap();
+        assert_eq!(heap.used, bytes * 2);
+
+        // No room until the first string goes
+        let third = Value::string("z".repeat(100));
+        assert!(heap.charge(&third).is_err());
+        drop(string);
+        heap.charge(&third).unwrap();
+        assert_eq!(heap.live(), bytes * 2);
+    }
+
+    #[test]
+    fn test_growth_is_released_with_its_object() {
+        let Value::Object(array) = Value::new_array([]) else { unreachable!() };
+        let mut heap = HeapAccount::new(1 << 20);
+        heap.charge(&Value::Object(array.clone())).unwrap();
+        let created = heap.used;
+
+        let before = Extent::of(&array);
+        array.array_elements().unwrap().borrow_mut().extend((0..10).map(|_| None));
+        heap.charge_growth(&array, before).unwrap();
+        assert!(heap.used >= created + 10 * ELEMENT_BYTES);
+
+        // Primitives are free
+        heap.charge(&Value::Number(1.0)).unwrap();
+        drop(array);
+        assert_eq!(heap.live(), 0);
+    }
+}
@@ -0,0 +1,59 @@
+//! Iterating over values
+//!
+//! Every feature that walks the elements of a value (for-of,
+//! `Array.from`, `Promise.all`) goes through `VM::iterate_value`, or for
+//! for-of's step-by-step loop the iterator it is built on, so they agree
+//! on what is iterable and in what order:
+//!
+//! - anything with a `[Symbol.iterator]` method, which is checked first:
+//!   the iterator it returns is stepped with its `next` method until a
+//!   result is `done`, and leaving early calls its `return` method;
+//! - arrays, index by index, reading holes as `undefined` and seeing
+//!   elements pushed while iterating;
+//! - strings, by code point rather than by UTF-16 unit;
+//! - generators, until they return.
+//!
+//! Array-likes (anything with a numeric `length`) are not iterable, as in
+//! the spec; only consumers that ask for them, like `Array.from`, read
+//! them, as elements `0..length`. Everything else fails with one
+//! TypeError naming the consumer.
+
+use std::fmt;
+use crate::error::{Error, ErrorCode};
+use super::value::Value;
+
+/// What is iterating, for the error a non-iterable value gets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterationConsumer {
+    ForOf,
+    ArrayFrom,
+    PromiseAll,
+}
+
+impl IterationConsumer {
+    /// Whether array-likes are read as well as iterables
+    pub fn accepts_array_likes(self) -> bool {
+        matches!(self, IterationConsumer::ArrayFrom)
+    }
+}
+
+impl fmt::Display for IterationConsumer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            IterationConsumer::ForOf => "for-of",
+            IterationConsumer::ArrayFrom => "Array.from",
+            IterationConsumer::PromiseAll => "Promise.all",
+        })
+    }
+}
+
+/// The TypeError for an iterator, or an iterator result, that is not an
+/// object
+pub(crate) fn invalid_iterator(message: String) -> Error {
+    Error::runtime(ErrorCode::InvalidIterator, format!("TypeError: {}", message), None)
+}
+
+/// The TypeError for iterating `value`, which can't be
+pub(crate) fn not_iterable(value: &Value, consumer: IterationConsumer) -> Error {
+    Error::runtime(ErrorCode::NotIterable, format!("TypeError: {} is not iterable (in {})", value, consumer), None)
+}
@@ -0,0 +1,413 @@
+//! The JSON Global
+//!
+//! `JSON.stringify` and `JSON.parse`, written against the object
+//! construction API in `object.rs`. Restrictions: no replacer or reviver
+//! (passing one is a TypeError), and nesting deeper than
+//! `MAX_NESTING_DEPTH` is a RangeError rather than a host stack overflow.
+
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::machine::VM;
+use super::object::methods_object;
+use super::value::{NativeMethod, Value};
+
+/// Deepest structure either direction handles
+const MAX_NESTING_DEPTH: usize = 512;
+
+/// Create the `JSON` object
+pub fn create_object() -> Value {
+    methods_object(&METHODS)
+}
+
+/// The methods of `JSON`
+pub(crate) const METHODS: [(&str, NativeMethod); 2] = [("parse", json_parse), ("stringify", json_stringify)];
+
+fn nesting_error() -> Error {
+    Error::runtime(ErrorCode::JsonNesting, "RangeError: Maximum JSON nesting depth exceeded", None)
+}
+
+// === JSON.stringify ===
+
+/// `JSON.stringify(value, replacer, space)`
+fn json_stringify(cx: &mut CallContext) -> Result<Value> {
+    let value = cx.arg(0);
+    if cx.args.get(1).is_some_and(|replacer| !matches!(replacer, Value::Undefined | Value::Null)) {
+        return Err(Error::runtime(ErrorCode::UnsupportedBuiltin, "TypeError: JSON.stringify replacer is not supported", None));
+    }
+    let indent = match cx.args.get(2) {
+        Some(Value::Number(n)) => " ".repeat(n.clamp(0.0, 10.0) as usize),
+        Some(Value::String(s)) => s.chars().take(10).collect(),
+        _ => String::new(),
+    };
+    
+    let mut serializer = Serializer { indent, stack: Vec::new() };
+    let mut out = String::new();
+    if serializer.write_value(cx.vm, &mut out, value, "")? {
+        Ok(Value::string(out))
+    } else {
+        Ok(Value::Undefined)
+    }
+}
+
+struct Serializer {
+    /// One level of indentation; empty for compact output
+    indent: String,
+    /// Composites being written, for cycle detection
+    stack: Vec<Value>,
+}
+
+impl Serializer {
+    /// Append `value`, returning false for values JSON has no form of
+    /// (`undefined`, functions and symbols), which the caller omits
+    fn write_value(&mut self, vm: &mut VM, out: &mut String, value: Value, current_indent: &str) -> Result<bool> {
+        let value = match value.get_property("toJSON") {
+            Some(to_json @ Value::Function(_)) => vm.call_function(&to_json, value, &[])?,
+            _ => value,
+        };
+        match &value {
+            Value::Null => out.push_str("null"),
+            Value::Boolean(b) => out.push_str(if *b { "true" } else { "false" }),
+            // Adding zero turns -0 into 0
+            Value::Number(n) if n.is_finite() => out.push_str(&Value::Number(n + 0.0).to_string()),
+            Value::Number(_) => out.push_str("null"),
+            Value::String(s) => write_quoted(out, s),
+            Value::Undefined | Value::Function(_) | Value::Symbol(_) => return Ok(false),
+            Value::Object(_) => {
+                if self.stack.iter().any(|open| open.strict_eq(&value)) {
+                    return Err(Error::runtime(ErrorCode::CircularJson, "TypeError: Converting circular structure to JSON", None));
+                }
+                if self.stack.len() >= MAX_NESTING_DEPTH {
+                    return Err(nesting_error());
+                }
+                self.stack.push(value.clone());
+                let inner_indent = format!("{}{}", current_indent, self.indent);
+                // A `toJSON` may remove what is still to be written, and
+                // nothing but this call may hold what one returned
+                let result = match value.array_values() {
+                    Some(elements) => vm.with_roots(std::iter::once(&value).chain(&elements), |vm| {
+                        self.write_array(vm, out, &elements, current_indent, &inner_indent)
+                    }),
+                    None => {
+                        let entries = value.own_entries();
+                        let values = entries.iter().map(|(_, value)| value);
+                        vm.with_roots(std::iter::once(&value).chain(values), |vm| {
+                            self.write_object(vm, out, &entries, current_indent, &inner_indent)
+                        })
+                    }
+                };
+                self.stack.pop();
+                result?;
+            }
+        }
+        Ok(true)
+    }
+    
+    fn write_array(&mut self, vm: &mut VM, out: &mut String, elements: &[Value], current_indent: &str, inner_indent: &str) -> Result<()> {
+        if elements.is_empty() {
+            out.push_str("[]");
+            return Ok(());
+        }
+        out.push('[');
+        for (index, element) in elements.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            self.write_separator(out, inner_indent);
+            if !self.write_value(vm, out, element.clone(), inner_indent)? {
+                out.push_str("null");
+            }
+        }
+        self.write_separator(out, current_indent);
+        out.push(']');
+        Ok(())
+    }
+    
+    fn write_object(&mut self, vm: &mut VM, out: &mut String, entries: &[(String, Value)], current_indent: &str, inner_indent: &str) -> Result<()> {
+        out.push('{');
+        let mut first = true;
+        for (key, value) in entries {
+            // Write into a scratch buffer so omitted members leave no trace
+            let mut member = String::new();
+            write_quoted(&mut member, key);
+            member.push(':');
+            if !self.indent.is_empty() {
+                member.push(' ');
+            }
+            if !self.write_value(vm, &mut member, value.clone(), inner_indent)? {
+                continue;
+            }
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            self.write_separator(out, inner_indent);
+            out.push_str(&member);
+        }
+        if !first {
+            self.write_separator(out, current_indent);
+        }
+        out.push('}');
+        Ok(())
+    }
+    
+    /// A line break and indentation, in pretty-printed output only
+    fn write_separator(&self, out: &mut String, indent: &str) {
+        if !self.indent.is_empty() {
+            out.push('\n');
+            out.push_str(indent);
+        }
+    }
+}
+
+/// Append `s` as a JSON string literal
+fn write_quoted(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// === JSON.parse ===
+
+/// `JSON.parse(text)`
+fn json_parse(cx: &mut CallContext) -> Result<Value> {
+    if cx.args.get(1).is_some_and(|reviver| matches!(reviver, Value::Function(_))) {
+        return Err(Error::runtime(ErrorCode::UnsupportedBuiltin, "TypeError: JSON.parse reviver is not supported", None));
+    }
+    let text = cx.vm.to_js_string(&cx.arg(0))?;
+    parse_text(&text)
+}
+
+/// Parse a whole JSON text, as `JSON.parse` does without a reviver
+pub(crate) fn parse_text(text: &str) -> Result<Value> {
+    let mut parser = JsonParser { chars: text.chars().collect(), position: 0, depth: 0 };
+    parser.skip_whitespace();
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return Err(parser.unexpected());
+    }
+    Ok(value)
+}
+
+struct JsonParser {
+    chars: Vec<char>,
+    position: usize,
+    depth: usize,
+}
+
+impl JsonParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.position += 1;
+        }
+    }
+    
+    /// A SyntaxError for the character at the current position
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Some(c) => Error::runtime(
+                ErrorCode::JsonSyntax,
+                format!("SyntaxError: Unexpected token {} in JSON at position {}", c, self.position),
+                None,
+            ),
+            None => Error::runtime(ErrorCode::JsonSyntax, "SyntaxError: Unexpected end of JSON input", None),
+        }
+    }
+    
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+    
+    fn parse_value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('{') => self.nested(Self::parse_object),
+            Some('[') => self.nested(Self::parse_array),
+            Some('"') => Ok(Value::string(self.parse_string()?)),
+            Some('-' | '0'..='9') => self.parse_number(),
+            Some('t') => self.parse_literal("true", Value::Boolean(true)),
+            Some('f') => self.parse_literal("false", Value::Boolean(false)),
+            Some('n') => self.parse_literal("null", Value::Null),
+            _ => Err(self.unexpected()),
+        }
+    }
+    
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<Value>) -> Result<Value> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            return Err(nesting_error());
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+    
+    fn parse_literal(&mut self, word: &str, value: Value) -> Result<Value> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+    
+    fn parse_object(&mut self) -> Result<Value> {
+        self.expect('{')?;
+        let object = Value::new_object();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position += 1;
+            return Ok(object);
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.unexpected());
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            let value = self.parse_value()?;
+            object.set_property(&key, value)?;
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some('}') => {
+                    self.position += 1;
+                    return Ok(object);
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+    
+    fn parse_array(&mut self) -> Result<Value> {
+        self.expect('[')?;
+        let mut elements = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position += 1;
+            return Ok(Value::new_array(elements));
+        }
+        loop {
+            self.skip_whitespace();
+            elements.push(self.parse_value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position += 1,
+                Some(']') => {
+                    self.position += 1;
+                    return Ok(Value::new_array(elements));
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+    }
+    
+    fn parse_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut units: Vec<u16> = Vec::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.unexpected());
+            };
+            match c {
+                '"' => {
+                    self.position += 1;
+                    return Ok(String::from_utf16_lossy(&units));
+                }
+                '\\' => {
+                    self.position += 1;
+                    let unit = match self.peek() {
+                        Some('"') => '"' as u16,
+                        Some('\\') => '\\' as u16,
+                        Some('/') => '/' as u16,
+                        Some('b') => 0x8,
+                        Some('f') => 0xc,
+                        Some('n') => '\n' as u16,
+                        Some('r') => '\r' as u16,
+                        Some('t') => '\t' as u16,
+                        Some('u') => {
+                            let digits: String = self.chars.iter().skip(self.position + 1).take(4).collect();
+                            if digits.len() != 4 || !digits.chars().all(|d| d.is_ascii_hexdigit()) {
+                                self.position += 1 + digits.chars().take_while(char::is_ascii_hexdigit).count();
+                                return Err(self.unexpected());
+                            }
+                            self.position += 4;
+                            u16::from_str_radix(&digits, 16).unwrap_or_default()
+                        }
+                        _ => return Err(self.unexpected()),
+                    };
+                    self.position += 1;
+                    units.push(unit);
+                }
+                c if (c as u32) < 0x20 => return Err(self.unexpected()),
+                c => {
+                    self.position += 1;
+                    let mut buffer = [0; 2];
+                    units.extend_from_slice(c.encode_utf16(&mut buffer));
+                }
+            }
+        }
+    }
+    
+    fn parse_number(&mut self) -> Result<Value> {
+        let start = self.position;
+        if self.peek() == Some('-') {
+            self.position += 1;
+        }
+        match self.peek() {
+            Some('0') => self.position += 1,
+            Some('1'..='9') => self.skip_digits(),
+            _ => return Err(self.unexpected()),
+        }
+        if self.peek() == Some('.') {
+            self.position += 1;
+            self.require_digits()?;
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.position += 1;
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.position += 1;
+            }
+            self.require_digits()?;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| Error::internal("Malformed JSON number"))
+    }
+    
+    fn skip_digits(&mut self) {
+        while matches!(self.peek(), Some('0'..='9')) {
+            self.position += 1;
+        }
+    }
+    
+    fn require_digits(&mut self) -> Result<()> {
+        if !matches!(self.peek(), Some('0'..='9')) {
+            return Err(self.unexpected());
+        }
+        self.skip_digits();
+        Ok(())
+    }
+}
@@ -3,16 +3,177 @@
 //! This module implements the stack-based virtual machine that executes
 //! JavaScript bytecode with an accumulator register.
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::panic::{self, AssertUnwindSafe};
 use std::rc::Rc;
+use std::sync::Arc;
 
-use crate::bytecode::{BytecodeFunction, Bytecode, ConstantValue};
-use crate::error::{Error, Result};
-use super::value::Value;
-use super::frame::{CallFrame, CallStack};
-use super::builtins::Builtins;
+use crate::bytecode::{BytecodeFunction, Bytecode, Compiler, ConstIndex, ConstantValue, VerifiedFunction};
+use crate::error::{panic_message, suggest, Error, ErrorCode, Result, RuntimeErrorKind, Span};
+use crate::lexer::UnicodeHelper;
+use crate::stdio::{EngineIo, SharedIo};
+use super::value::{
+    Value, FunctionRef, Closure, ObjectData, ObjectKind, GeneratorState, PreferredType, PropertyAttributes,
+    PropertyKey, js_pow, array_index, check_dense_length, function_source_text, MAX_DENSE_ARRAY_LENGTH,
+};
+use super::snapshot::Snapshot;
+use super::context::CallContext;
+use super::iteration::{invalid_iterator, not_iterable, IterationConsumer};
+use super::frame::{CallFrame, CallStack, Environment, DEFAULT_MAX_CALL_DEPTH};
+use super::builtins::{self, Builtins, Console, MathObject};
+use super::object::ObjectBuilder;
+use super::tasks::{Task, TaskQueue};
+use super::promise;
+use super::json;
+use super::object_global;
+use super::array_global;
+use super::number_global;
+use super::random::Rng;
+use super::case::CaseMapper;
+use super::symbol::{self, Symbol};
+use super::globals::GlobalScope;
+use super::heap::{Extent, HeapAccount};
+use super::profiler::{ProfileMode, ProfileReport, Profiler};
+use super::property_cache::{PropertyCacheStats, PropertyCaches};
+use super::atom::{Atom, AtomKey, TableAtoms};
+use super::gc::{ActiveHeap, Roots, StressHeap};
+use super::handles::{Persistent, PersistentRoots};
+use super::debug::{Breakpoint, BreakpointId, Breakpoints, DebugContext, DebugHook, NanOrigin, PauseReason, StepMode};
+
+/// A global variable binding
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalSlot {
+    /// Current value
+    pub value: Value,
+    /// Whether assignments may change the value
+    pub writable: bool,
+    /// Whether the binding may be deleted or redefined
+    pub configurable: bool,
+}
+
+impl GlobalSlot {
+    /// A binding created by script assignment: writable and configurable
+    pub fn new(value: Value) -> Self {
+        Self { value, writable: true, configurable: true }
+    }
+    
+    /// A binding that ignores (sloppy) or rejects (strict) assignments
+    pub fn read_only(value: Value) -> Self {
+        Self { value, writable: false, configurable: false }
+    }
+}
+
+/// Default maximum number of values on the operand stack
+///
+/// Room for ten pending operands in each of `DEFAULT_MAX_CALL_DEPTH`
+/// frames, so that recursion as deep as the call depth allows fails on
+/// the depth, not on the operands an expression like `n + (n + f(n - 1))`
+/// keeps in every frame.
+pub const DEFAULT_MAX_STACK_SIZE: usize = 10 * DEFAULT_MAX_CALL_DEPTH;
+
+/// How deeply arrays nested in arrays can be joined by ToString
+///
+/// Each level is a few native calls, several kilobytes of stack in a debug
+/// build, so past this it is a RangeError rather than a host stack
+/// overflow, with room to spare on a 2 MiB thread stack.
+const MAX_JOIN_DEPTH: usize = 128;
+
+/// Limits of a VM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VMConfig {
+    /// Maximum number of active calls; calls in tail position don't count
+    pub max_call_depth: usize,
+    /// Maximum number of values on the operand stack
+    pub max_stack_size: usize,
+    /// Maximum number of instructions one `execute` (or event loop task)
+    /// may run; `None` for no limit
+    pub max_instructions: Option<u64>,
+    /// Roughly how many bytes the strings and objects a VM has made may
+    /// take while live; `None` for no limit
+    ///
+    /// Past it, the allocation fails with an `AllocationLimit` RangeError
+    /// (see `vm::heap` for what is counted).
+    pub max_heap_bytes: Option<usize>,
+    /// Record where arithmetic first turns non-NaN operands into NaN
+    /// (see `VM::nan_origins`)
+    pub trace_nan: bool,
+    /// Write each instruction, with the accumulator and operand stack, to
+    /// the stderr of the VM's `EngineIo` before running it, along with
+    /// notes on `debugger` statements and NaN origins
+    ///
+    /// Tracing only reads the VM's state: a traced program prints and
+    /// returns exactly what an untraced one does, only much more slowly.
+    pub trace_execution: bool,
+    /// Make `obj.__proto__`, written as a dotted member, get and set the
+    /// prototype of `obj`
+    ///
+    /// Off by default: `__proto__` is then an ordinary property name, as
+    /// it always is in computed writes (`obj["__proto__"]`), object
+    /// literals and `JSON.parse`, so untrusted keys can't reach a
+    /// prototype. `Object.getPrototypeOf` and `Object.setPrototypeOf`
+    /// work either way.
+    pub proto_setter: bool,
+    /// Reuse the objects of literals that never leave their call instead
+    /// of allocating new ones (see `vm::arena`)
+    ///
+    /// Off by default until it has seen more use.
+    pub frame_arena: bool,
+    /// Cache where each `obj.name` read last found its property (see
+    /// `vm::property_cache`)
+    ///
+    /// Off by default until it has seen more use; `VM::set_property_caches`
+    /// switches it on a running VM.
+    pub property_caches: bool,
+    /// How `toUpperCase` and `toLowerCase` map characters (see `vm::case`)
+    pub case_mapper: CaseMapper,
+    /// Run a collection at every allocation and poison what it would
+    /// free, so that a value native or host code holds without a handle
+    /// panics at its next use (see `vm::gc`)
+    ///
+    /// For tests: it makes scripts many times slower. Defaults to whether
+    /// the `V8_GC_STRESS` environment variable is set, so a test suite can
+    /// be run under it unchanged.
+    pub gc_stress: bool,
+}
+
+impl Default for VMConfig {
+    fn default() -> Self {
+        Self {
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack_size: DEFAULT_MAX_STACK_SIZE,
+            max_instructions: None,
+            max_heap_bytes: None,
+            trace_nan: false,
+            trace_execution: false,
+            proto_setter: false,
+            frame_arena: false,
+            property_caches: false,
+            case_mapper: CaseMapper::Unicode,
+            gc_stress: std::env::var_os("V8_GC_STRESS").is_some(),
+        }
+    }
+}
+
+/// Counters a VM keeps once `VM::enable_metrics` is called
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct VmMetrics {
+    /// Instructions run since the counters were last taken
+    pub instructions_executed: u64,
+    /// Most values the operand stack held at once
+    pub peak_stack_depth: usize,
+}
+
+/// A host sink for `console` output, shared by the VMs an engine creates
+pub type ConsoleOutput = Rc<RefCell<dyn FnMut(&str)>>;
 
 /// Stack-based virtual machine with accumulator
+///
+/// A VM and the values it creates are confined to the thread that made
+/// them; only compiled `BytecodeFunction`s can cross threads.
 pub struct VM {
     /// The accumulator register
     accumulator: Value,
@@ -23,77 +184,1116 @@ pub struct VM {
     /// Call stack for function invocations
     call_stack: CallStack,
     
-    /// Global variables
-    globals: HashMap<String, Value>,
+    /// Global variables, and the layers pushed over them
+    globals: GlobalScope,
     
     /// Built-in functions registry
     builtins: Builtins,
     
     /// Bytecode functions (for function calls)
-    functions: Vec<Rc<BytecodeFunction>>,
+    functions: Vec<Arc<BytecodeFunction>>,
     
-    /// Debug mode flag
-    debug: bool,
+    /// Whether to trace each instruction (`VMConfig::trace_execution`)
+    trace_execution: bool,
     
     /// Maximum stack size to prevent overflow
     max_stack_size: usize,
+    
+    /// Instruction limit per run, and what is left of it in this run
+    max_instructions: Option<u64>,
+    instructions_left: u64,
+    
+    /// Bytes charged to live strings and objects, when there is a limit
+    heap: Option<HeapAccount>,
+    
+    /// Arrays currently being joined by ToString, to cut cycles
+    joining: HashSet<*const ObjectData>,
+    
+    /// Callbacks waiting for the host to pump the event loop
+    tasks: TaskQueue,
+    
+    /// Promises rejected while nothing handled them, oldest first
+    rejected_promises: Vec<Value>,
+    
+    /// Frame and operands of the generator that just yielded, until
+    /// `resume_generator` stores them back in the generator object
+    suspended: Option<(CallFrame, Vec<Value>)>,
+    
+    /// Source of `Math.random`
+    rng: Rng,
+    
+    /// Line-level profile, when enabled
+    profiler: Option<Box<Profiler>>,
+    
+    /// `LdaNamed` caches while `VMConfig::property_caches` is on
+    property_caches: Option<Box<PropertyCaches>>,
+    
+    /// Atoms of the string tables of the programs run so far
+    atoms: TableAtoms,
+    
+    /// Execution counters, when enabled
+    metrics: Option<VmMetrics>,
+    
+    /// Host callback for `debugger` statements and breakpoints
+    debug_hook: Option<Box<dyn DebugHook>>,
+    
+    /// Source-line breakpoints; the dispatch loop only looks when there
+    /// are some
+    breakpoints: Breakpoints,
+    
+    /// Deepest call stack depth the pending `step` pauses at, if one is
+    /// pending
+    stepping: Option<usize>,
+    
+    /// Receives `console` output instead of the streams, when set
+    console_output: Option<ConsoleOutput>,
+    
+    /// Where scripts print and read, and where debugging notes go
+    io: SharedIo,
+    
+    /// Whether to record NaN origins, and those recorded so far
+    trace_nan: bool,
+    nan_origins: Vec<NanOrigin>,
+    
+    /// Whether dotted `__proto__` members reach the prototype
+    /// (`VMConfig::proto_setter`)
+    proto_setter: bool,
+    
+    /// How strings change case (`VMConfig::case_mapper`)
+    case_mapper: CaseMapper,
+    
+    /// Whether arena sites reuse objects (`VMConfig::frame_arena`)
+    frame_arena: bool,
+    
+    /// Symbols handed out by `Symbol.for`, by key
+    symbol_registry: HashMap<String, Rc<Symbol>>,
+    
+    /// Values rooted by the handle scopes of the native calls running
+    handles: Vec<Value>,
+    
+    /// Values host code holds in `Persistent`s
+    persistents: PersistentRoots,
+    
+    /// Objects to check at each collection (`VMConfig::gc_stress`)
+    gc_stress: Option<Rc<StressHeap>>,
 }
 
 impl VM {
     /// Create a new VM instance
     pub fn new() -> Self {
+        Self::with_config(VMConfig::default())
+    }
+    
+    /// Create a VM with the given limits
+    pub fn with_config(config: VMConfig) -> Self {
+        let mut vm = Self::without_globals(config);
+        vm.install_globals(false);
+        vm
+    }
+    
+    /// Create a VM with the given limits whose globals are copied from
+    /// `snapshot` rather than built by the built-in modules
+    pub fn with_snapshot(config: VMConfig, snapshot: &Snapshot) -> Self {
+        let mut vm = Self::without_globals(config);
+        vm.globals = GlobalScope::new(snapshot.instantiate());
+        vm
+    }
+    
+    /// Record the globals, for `with_snapshot`
+    ///
+    /// Fails if a global holds something only this VM can run, like a
+    /// script function or a host function.
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::capture(self.globals.base())
+    }
+    
+    fn without_globals(config: VMConfig) -> Self {
         Self {
             accumulator: Value::Undefined,
             stack: Vec::with_capacity(256),
-            call_stack: CallStack::new(1000),
-            globals: HashMap::new(),
+            call_stack: CallStack::new(config.max_call_depth),
+            globals: GlobalScope::default(),
             builtins: Builtins::new(),
             functions: Vec::new(),
-            debug: false,
-            max_stack_size: 10000,
+            trace_execution: config.trace_execution,
+            max_stack_size: config.max_stack_size,
+            max_instructions: config.max_instructions,
+            instructions_left: 0,
+            heap: config.max_heap_bytes.map(HeapAccount::new),
+            joining: HashSet::new(),
+            tasks: TaskQueue::default(),
+            rejected_promises: Vec::new(),
+            suspended: None,
+            rng: Rng::from_entropy(),
+            profiler: None,
+            property_caches: config.property_caches.then(Box::default),
+            atoms: TableAtoms::default(),
+            metrics: None,
+            debug_hook: None,
+            breakpoints: Breakpoints::default(),
+            stepping: None,
+            console_output: None,
+            io: EngineIo::process().shared(),
+            trace_nan: config.trace_nan,
+            proto_setter: config.proto_setter,
+            case_mapper: config.case_mapper,
+            frame_arena: config.frame_arena,
+            nan_origins: Vec::new(),
+            symbol_registry: HashMap::new(),
+            handles: Vec::new(),
+            persistents: PersistentRoots::default(),
+            gc_stress: config.gc_stress.then(Rc::default),
         }
     }
     
-    /// Create a new VM with debug mode enabled
-    pub fn new_with_debug() -> Self {
-        let mut vm = Self::new();
-        vm.debug = true;
-        vm
+    /// Expose the built-in functions, the console object, and the global
+    /// value properties as globals
+    ///
+    /// `undefined`, `NaN` and `Infinity` are always read-only, as in the
+    /// spec; the other built-ins are read-only unless `writable_builtins`.
+    fn install_globals(&mut self, writable_builtins: bool) {
+        let builtin_slot = |value| GlobalSlot {
+            value,
+            writable: writable_builtins,
+            configurable: writable_builtins,
+        };
+        
+        for name in self.builtins.names() {
+            // Dotted names like "console.log" live on their objects instead
+            if name.contains('.') {
+                continue;
+            }
+            if let Some(function) = self.builtins.get(&name) {
+                self.globals.insert(name, builtin_slot(Value::Function(FunctionRef::NativeMethod(function))));
+            }
+        }
+        for (name, method) in builtins::global_methods() {
+            self.globals.insert(name.to_string(), builtin_slot(Value::Function(FunctionRef::NativeMethod(method))));
+        }
+        self.globals.insert("console".to_string(), builtin_slot(Console::create_object()));
+        self.globals.insert("Math".to_string(), builtin_slot(MathObject::create_object()));
+        self.globals.insert("Promise".to_string(), builtin_slot(promise::create_constructor()));
+        self.globals.insert("JSON".to_string(), builtin_slot(json::create_object()));
+        self.globals.insert("Object".to_string(), builtin_slot(object_global::create_object()));
+        self.globals.insert("Number".to_string(), builtin_slot(number_global::create_object()));
+        self.globals.insert("Array".to_string(), builtin_slot(array_global::create_object()));
+        self.globals.insert("Symbol".to_string(), builtin_slot(symbol::create_constructor()));
+        
+        self.globals.insert("undefined".to_string(), GlobalSlot::read_only(Value::Undefined));
+        self.globals.insert("NaN".to_string(), GlobalSlot::read_only(Value::Number(f64::NAN)));
+        self.globals.insert("Infinity".to_string(), GlobalSlot::read_only(Value::Number(f64::INFINITY)));
+    }
+    
+    /// Make engine-installed built-ins (`console`, `print`, ...) writable
+    ///
+    /// This reinstalls them, so call it before running any code.
+    pub fn set_builtins_writable(&mut self, writable: bool) {
+        self.install_globals(writable);
+    }
+    
+    /// Restart `Math.random` from `seed`, making its sequence reproducible
+    pub fn set_random_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+    
+    /// Send `console` output, one line per call, to `output` instead of
+    /// the VM's streams (or with `None`, back to them)
+    pub fn set_console_output(&mut self, output: Option<ConsoleOutput>) {
+        self.console_output = output;
+    }
+    
+    /// Print and read through `io` instead of the process's streams
+    pub fn set_io(&mut self, io: SharedIo) {
+        self.io = io;
+    }
+    
+    /// The streams the VM prints to and reads from
+    pub fn io(&self) -> SharedIo {
+        self.io.clone()
+    }
+    
+    /// Write one line of `console` output, to stderr if `error` is set
+    /// and to stdout otherwise
+    pub(crate) fn write_console(&mut self, line: &str, error: bool) -> Result<()> {
+        if let Some(output) = &self.console_output {
+            (output.borrow_mut())(line);
+            return Ok(());
+        }
+        let mut io = self.io.borrow_mut();
+        let stream = if error { &mut io.stderr } else { &mut io.stdout };
+        writeln!(stream, "{}", line)?;
+        Ok(())
+    }
+    
+    /// Read a line for `readLine()`, without its line ending; `None` at
+    /// the end of input
+    pub(crate) fn read_line(&mut self) -> Result<Option<String>> {
+        Ok(self.io.borrow_mut().read_line()?)
+    }
+    
+    /// Write a line of debugging output to stderr
+    ///
+    /// A failed write is dropped: notes must not change what the script
+    /// does.
+    fn debug_note(&self, note: std::fmt::Arguments) {
+        let _ = writeln!(self.io.borrow_mut().stderr, "{}", note);
+    }
+    
+    /// The generator behind `Math.random`
+    pub(crate) fn rng_mut(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+    
+    /// How the string case methods map characters
+    pub(crate) fn case_mapper(&self) -> CaseMapper {
+        self.case_mapper
+    }
+    
+    /// The symbol `Symbol.for(key)` returns, made on first request
+    pub(crate) fn registered_symbol(&mut self, key: String) -> Rc<Symbol> {
+        self.symbol_registry.entry(key)
+            .or_insert_with_key(|key| Symbol::new(Some(key.clone())))
+            .clone()
+    }
+    
+    /// The key `symbol` is registered under, if `Symbol.for` made it
+    pub(crate) fn symbol_registry_key(&self, symbol: &Symbol) -> Option<String> {
+        self.symbol_registry.iter()
+            .find(|(_, registered)| ***registered == *symbol)
+            .map(|(key, _)| key.clone())
+    }
+    
+    /// Start profiling by source line, discarding any earlier profile
+    pub fn enable_profiling(&mut self, mode: ProfileMode) {
+        self.profiler = Some(Box::new(Profiler::new(mode)));
+    }
+    
+    /// Record the active frame's current instruction in a sampling profile
+    ///
+    /// Does nothing unless sampling is enabled and code is running.
+    pub fn sample(&mut self) {
+        let Some(profiler) = &mut self.profiler else {
+            return;
+        };
+        if profiler.mode() != ProfileMode::Sampling {
+            return;
+        }
+        if let Some(frame) = self.call_stack.current_frame() {
+            profiler.record(&frame.function, frame.ip().saturating_sub(1));
+        }
+    }
+    
+    /// Switch the `LdaNamed` caches on or off (`VMConfig::property_caches`)
+    ///
+    /// Off, every named read looks its name up in the object's hash index,
+    /// the dictionary-mode path. Switching them on again starts from empty
+    /// caches.
+    pub fn set_property_caches(&mut self, enabled: bool) {
+        if enabled != self.property_caches.is_some() {
+            self.property_caches = enabled.then(Box::default);
+        }
+    }
+    
+    /// Empty the `LdaNamed` caches and zero their counters, as if no named
+    /// read had run; does nothing while they are off
+    pub fn reset_property_caches(&mut self) {
+        if let Some(caches) = &mut self.property_caches {
+            **caches = PropertyCaches::new();
+        }
+    }
+    
+    /// What the `LdaNamed` caches have seen; `None` while they are off
+    pub fn property_cache_stats(&self) -> Option<PropertyCacheStats> {
+        self.property_caches.as_ref().map(|caches| caches.stats())
+    }
+    
+    /// Count instructions and track the operand stack's peak from now on
+    pub fn enable_metrics(&mut self) {
+        self.metrics.get_or_insert_with(VmMetrics::default);
+    }
+    
+    /// The counters so far, starting them again from zero; all zero
+    /// unless `enable_metrics` was called
+    pub fn take_metrics(&mut self) -> VmMetrics {
+        match &mut self.metrics {
+            Some(metrics) => std::mem::take(metrics),
+            None => VmMetrics::default(),
+        }
+    }
+    
+    /// Hits per line and per function so far; `None` unless profiling
+    pub fn profile_report(&self) -> Option<ProfileReport> {
+        self.profiler.as_ref().map(|profiler| profiler.report())
+    }
+    
+    /// Arithmetic instructions that produced NaN from non-NaN operands,
+    /// each listed once, in the order they first did; empty unless
+    /// `VMConfig::trace_nan`
+    pub fn nan_origins(&self) -> &[NanOrigin] {
+        &self.nan_origins
+    }
+    
+    /// Record the accumulator as a NaN origin if the instruction just run
+    /// made it NaN from `operands`
+    ///
+    /// With `trace_execution` on, the origin is printed with the trace.
+    fn note_nan(&mut self, operator: &'static str, operands: Vec<Value>) {
+        let is_nan = |value: &Value| matches!(value, Value::Number(n) if n.is_nan());
+        if !is_nan(&self.accumulator) || operands.iter().any(is_nan) {
+            return;
+        }
+        let Some(frame) = self.call_stack.current_frame() else {
+            return;
+        };
+        let ip = frame.ip().saturating_sub(1);
+        let function = &frame.function.name;
+        if self.nan_origins.iter().any(|origin| origin.ip == ip && origin.function == *function) {
+            return;
+        }
+        let origin = NanOrigin {
+            function: function.clone(),
+            ip,
+            line: frame.function.debug_info.get_line_number(ip),
+            operator,
+            operands,
+        };
+        if self.trace_execution {
+            self.debug_note(format_args!("[nan] {}", origin));
+        }
+        self.nan_origins.push(origin);
+    }
+    
+    /// Install (or with `None`, remove) the callback run when execution pauses
+    pub fn set_debug_hook(&mut self, hook: Option<Box<dyn DebugHook>>) {
+        self.debug_hook = hook;
+    }
+    
+    /// Pause before the first instruction at or after `line` in every
+    /// function named `function` (`main` for top-level code)
+    ///
+    /// The debug hook is called each time execution gets there, with
+    /// `PauseReason::Breakpoint`; without a hook breakpoints do nothing.
+    /// A function without instructions on that line or after it never
+    /// hits the breakpoint.
+    pub fn set_breakpoint(&mut self, function: &str, line: u32) -> BreakpointId {
+        self.breakpoints.set(function, line)
+    }
+    
+    /// Remove a breakpoint, returning whether it was set
+    pub fn clear_breakpoint(&mut self, id: BreakpointId) -> bool {
+        self.breakpoints.clear(id)
+    }
+    
+    /// The breakpoints set, oldest first
+    pub fn list_breakpoints(&self) -> &[Breakpoint] {
+        self.breakpoints.list()
+    }
+    
+    /// Pause again at the next statement start `mode` allows, with
+    /// `PauseReason::Step`
+    ///
+    /// Meant for a `DebugHook` to call before it returns; modes are
+    /// relative to the innermost frame, the paused one. The request holds
+    /// until it pauses, whatever pauses first, so a breakpoint reached on
+    /// the way ends it too. Called with no code running, `StepMode::Into`
+    /// and `Over` pause at the first statement of the next script.
+    pub fn step(&mut self, mode: StepMode) {
+        let depth = self.call_stack.depth();
+        self.stepping = Some(match mode {
+            StepMode::Into => usize::MAX,
+            StepMode::Over if depth == 0 => usize::MAX,
+            StepMode::Over => depth,
+            StepMode::Out => depth.saturating_sub(1),
+        });
+    }
+    
+    /// Whether a pending `step` pauses at instruction `ip` of the
+    /// innermost frame
+    fn steps_to(&self, ip: usize) -> bool {
+        match (self.stepping, self.call_stack.current_frame()) {
+            (Some(deepest), Some(frame)) => {
+                self.call_stack.depth() <= deepest && frame.function.debug_info.is_statement_start(ip)
+            }
+            _ => false,
+        }
+    }
+    
+    /// Run `function`, compiled by `Compiler::compile_expression_in_scope`,
+    /// against the locals and `this` of call stack frame `frame_index`
+    ///
+    /// Meant for a `DebugHook` inspecting a paused frame. Assignments to
+    /// the frame's locals stick; the accumulator and operand stack are
+    /// left as they were, so the paused code resumes undisturbed.
+    pub fn eval_in_frame(&mut self, frame_index: usize, function: Arc<BytecodeFunction>) -> Result<Value> {
+        let frame = self.call_stack.frame(frame_index)
+            .ok_or_else(|| Error::runtime(ErrorCode::InvalidFrame, format!("No frame {} to evaluate in", frame_index), None))?;
+        let closure = Value::Function(FunctionRef::Closure(Rc::new(Closure::new(function, frame.env.clone()))));
+        let this = frame.this_value.clone();
+        
+        let accumulator = self.accumulator.clone();
+        let result = self.call_function(&closure, this, &[]);
+        self.accumulator = accumulator;
+        result
+    }
+    
+    /// Compile the expression `source` against the variables in scope
+    /// where call stack frame `frame_index` stopped, and run it there with
+    /// `eval_in_frame`
+    ///
+    /// The names come from the frame function's symbol table (see
+    /// `BytecodeFunction::scope_at`); a function without one, such as one
+    /// loaded from a dump, sees only globals.
+    pub fn eval_source_in_frame(&mut self, frame_index: usize, source: &str) -> Result<Value> {
+        let frame = self.call_stack.frame(frame_index)
+            .ok_or_else(|| Error::runtime(ErrorCode::InvalidFrame, format!("No frame {} to evaluate in", frame_index), None))?;
+        let scope = frame.function.scope_at(frame.ip().saturating_sub(1));
+        let function = Compiler::compile_expression_in_scope(source, &scope)?;
+        self.eval_in_frame(frame_index, Arc::new(function))
+    }
+    
+    /// Hand a pause to the debug hook, if there is one
+    fn pause(&mut self, reason: PauseReason) -> Result<()> {
+        let Some(mut hook) = self.debug_hook.take() else {
+            return Ok(());
+        };
+        let frame = self.call_stack.depth().saturating_sub(1);
+        let context = match self.call_stack.current_frame() {
+            Some(current) => {
+                let ip = current.ip().saturating_sub(1);
+                DebugContext {
+                    reason,
+                    frame,
+                    function: current.function.name.clone(),
+                    ip,
+                    line: current.function.debug_info.get_line_number(ip),
+                    locals: current.function.scope_at(ip),
+                }
+            }
+            None => return Ok(()),
+        };
+        self.stepping = None;
+        let result = hook.on_pause(self, &context);
+        // The hook may have installed a replacement for itself
+        if self.debug_hook.is_none() {
+            self.debug_hook = Some(hook);
+        }
+        result
+    }
+    
+    /// Define or replace a global binding from the host
+    pub fn define_global(&mut self, name: impl Into<String>, slot: GlobalSlot) {
+        self.globals.insert(name.into(), slot);
+    }
+    
+    /// Remove a global binding, returning it
+    pub fn remove_global(&mut self, name: &str) -> Option<GlobalSlot> {
+        self.globals.remove(name)
+    }
+    
+    /// Get the value of a global binding
+    ///
+    /// Bindings of pushed layers shadow the others.
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name).map(|slot| &slot.value)
+    }
+    
+    /// Shadow globals with `bindings` until `pop_global_layer`
+    ///
+    /// Scripts read and assign the layer's bindings in place of any
+    /// global of the same name; names it lacks resolve as before, and
+    /// globals scripts create still go to the global object. Layers
+    /// nest. `define_global` and `remove_global` ignore them.
+    pub fn push_global_layer(&mut self, bindings: HashMap<String, GlobalSlot>) {
+        self.globals.push_layer(bindings);
+    }
+    
+    /// Remove the layer pushed last, returning its bindings with the
+    /// values scripts left in them
+    pub fn pop_global_layer(&mut self) -> Option<HashMap<String, GlobalSlot>> {
+        self.globals.pop_layer()
+    }
+    
+    /// Whether a script has assigned `name` in the layer pushed last,
+    /// since it was pushed
+    ///
+    /// Writing the value it already held counts too, so the host can
+    /// tell a value it put in the layer from one a script wrote.
+    pub fn global_layer_written(&self, name: &str) -> bool {
+        self.globals.is_written(name)
+    }
+    
+    /// Number of layers pushed with `push_global_layer` and not popped
+    pub fn global_layer_depth(&self) -> usize {
+        self.globals.depth()
+    }
+    
+    /// Hold `value` across calls into this VM (see `vm::handles`)
+    pub fn persistent(&self, value: Value) -> Persistent {
+        Persistent::new(&self.persistents, value)
+    }
+    
+    /// The roots the VM's `Persistent`s live in
+    pub fn persistent_roots(&self) -> &PersistentRoots {
+        &self.persistents
+    }
+    
+    /// Treat the `Persistent`s of `roots` as this VM's own
+    ///
+    /// An engine that makes a VM for each script gives them all the same
+    /// roots, so a handle made while one runs stays a root in the next.
+    pub fn set_persistent_roots(&mut self, roots: PersistentRoots) {
+        self.persistents = roots;
+    }
+    
+    /// Number of collections `VMConfig::gc_stress` has run; always 0
+    /// without it
+    pub fn stress_collections(&self) -> u64 {
+        self.gc_stress.as_ref().map_or(0, |heap| heap.collections())
+    }
+    
+    /// Root `value` in the innermost handle scope
+    pub(crate) fn push_handle(&mut self, value: Value) {
+        self.handles.push(value);
+    }
+    
+    /// Number of values rooted by the open handle scopes
+    pub(crate) fn handle_count(&self) -> usize {
+        self.handles.len()
+    }
+    
+    /// Release the handles rooted since there were `count`
+    pub(crate) fn release_handles(&mut self, count: usize) {
+        self.handles.truncate(count);
+    }
+    
+    /// Run `body` with `values` rooted
+    ///
+    /// For VM code that holds values across a call back into JavaScript,
+    /// as natives do with `CallContext::root`.
+    pub(crate) fn with_roots<'v, R>(&mut self, values: impl IntoIterator<Item = &'v Value>, body: impl FnOnce(&mut Self) -> R) -> R {
+        let count = self.handles.len();
+        self.handles.extend(values.into_iter().cloned());
+        let result = body(self);
+        self.release_handles(count);
+        result
+    }
+    
+    /// Collect now if `VMConfig::gc_stress` is on: poison the objects
+    /// made while running that the roots don't reach
+    pub(crate) fn stress_collect(&mut self) {
+        let Some(heap) = &self.gc_stress else {
+            return;
+        };
+        let mut roots = Roots::default();
+        roots.value(&self.accumulator);
+        roots.values(&self.stack);
+        roots.values(&self.handles);
+        roots.values(&self.persistents.values());
+        roots.values(&self.rejected_promises);
+        roots.values(self.globals.values());
+        roots.values(self.tasks.values());
+        roots.values(self.nan_origins.iter().flat_map(|origin| &origin.operands));
+        for frame in self.call_stack.frames() {
+            roots.frame(frame);
+        }
+        roots.objects(self.call_stack.spare_objects());
+        if let Some((frame, operands)) = &self.suspended {
+            roots.frame(frame);
+            roots.values(operands);
+        }
+        heap.collect(roots);
+    }
+    
+    /// Count the objects made on this thread as this VM's until the guard
+    /// is dropped, if `VMConfig::gc_stress` is on
+    fn activate_stress_heap(&self) -> Option<ActiveHeap> {
+        self.gc_stress.as_ref().map(StressHeap::activate)
     }
     
     /// Execute a bytecode function
     pub fn execute(&mut self, function: BytecodeFunction) -> Result<Value> {
+        self.execute_shared(Arc::new(function))
+    }
+    
+    /// Execute a bytecode function that may be shared, e.g. from a
+    /// compilation cache; running it never modifies it
+    pub fn execute_shared(&mut self, main_func: Arc<BytecodeFunction>) -> Result<Value> {
+        self.execute_main(main_func, false)
+    }
+    
+    /// Execute a verified function
+    ///
+    /// It and the closures it creates treat a local index out of range as
+    /// a bug that panics, instead of an error other code gets back.
+    pub fn execute_verified(&mut self, main_func: &VerifiedFunction) -> Result<Value> {
+        self.execute_main(main_func.function().clone(), true)
+    }
+    
+    /// Run `main_func` as the main program; `verified` is whether it comes
+    /// from a `VerifiedFunction`
+    fn execute_main(&mut self, main_func: Arc<BytecodeFunction>, verified: bool) -> Result<Value> {
+        let _heap = self.activate_stress_heap();
         // Store the main function
-        let main_func = Rc::new(function);
         self.functions.push(main_func.clone());
+        self.reset_instruction_budget();
         
         // Create and push the main frame
-        let main_frame = CallFrame::new_main(main_func);
+        let main_frame = CallFrame::new_main(main_func).with_verified(verified);
+        let depth = self.call_stack.depth();
         self.call_stack.push(main_frame)
-            .map_err(|e| Error::Runtime { message: e, span: None })?;
+            .map_err(|_| Error::with_kind(RuntimeErrorKind::StackOverflow { depth }, None))?;
         
         // Run the interpreter loop
-        let result = self.run();
+        let result = self.run(0);
         
         // Return the final value (from accumulator or undefined)
         match result {
             Ok(_) => Ok(self.accumulator.clone()),
-            Err(e) => Err(e),
+            Err(e) => {
+                self.unwind_to(0);
+                Err(e)
+            }
+        }
+    }
+    
+    /// Call a JavaScript function value from native code
+    ///
+    /// Runs the interpreter re-entrantly until the callee returns, so
+    /// built-ins can invoke user callbacks (e.g. a sort comparator).
+    pub fn call_function(&mut self, callee: &Value, this: Value, args: &[Value]) -> Result<Value> {
+        let _heap = self.activate_stress_heap();
+        let depth = self.call_stack.depth();
+        if self.invoke(callee.clone(), this, args.to_vec())? {
+            if let Err(e) = self.run(depth) {
+                self.unwind_to(depth);
+                return Err(e);
+            }
+        }
+        Ok(self.accumulator.clone())
+    }
+    
+    /// Pump the event loop at host time `now_ms`
+    ///
+    /// Runs every queued microtask, then each timer that is due, draining
+    /// microtasks again after each one. Timers scheduled while pumping wait
+    /// for the next pump even if already due, so a callback that reschedules
+    /// itself can't stall the host. An error thrown by a callback goes to
+    /// `on_uncaught` and the pump carries on. Returns whether work remains.
+    pub fn run_pending(&mut self, now_ms: u64, on_uncaught: &mut dyn FnMut(Error)) -> bool {
+        let _heap = self.activate_stress_heap();
+        self.tasks.advance_to(now_ms);
+        let max_timer_id = self.tasks.last_timer_id();
+        
+        self.drain_microtasks(on_uncaught);
+        while let Some(timer) = self.tasks.next_due_timer(max_timer_id) {
+            self.run_task(timer, on_uncaught);
+            self.drain_microtasks(on_uncaught);
         }
+        self.tasks.has_pending()
     }
     
-    /// Main interpreter loop
-    fn run(&mut self) -> Result<()> {
-        loop {
-            // Check if we have an active frame
-            if self.call_stack.is_empty() {
-                break; // No more frames to execute
+    /// Whether any timer or microtask is waiting to run
+    pub fn has_pending_tasks(&self) -> bool {
+        self.tasks.has_pending()
+    }
+    
+    /// Reasons of the promises rejected without a handler that still have
+    /// none, each reported once
+    ///
+    /// Call after `run_pending`, so handlers attached by the microtasks it
+    /// ran count. Rejection reasons are plain values: there are no Error
+    /// objects to carry a creation-site stack yet.
+    pub fn take_unhandled_rejections(&mut self) -> Vec<Value> {
+        std::mem::take(&mut self.rejected_promises).iter()
+            .filter_map(promise::unhandled_reason)
+            .collect()
+    }
+    
+    /// Whether a rejected promise may still need reporting
+    pub fn has_unhandled_rejections(&self) -> bool {
+        !self.rejected_promises.is_empty()
+    }
+    
+    /// Remember a promise rejected while it had no handlers
+    pub(crate) fn track_rejection(&mut self, promise: Value) {
+        self.rejected_promises.push(promise);
+    }
+    
+    /// The timer and microtask queues, for the scheduling built-ins
+    pub(crate) fn tasks_mut(&mut self) -> &mut TaskQueue {
+        &mut self.tasks
+    }
+    
+    fn drain_microtasks(&mut self, on_uncaught: &mut dyn FnMut(Error)) {
+        while let Some(task) = self.tasks.next_microtask() {
+            self.run_task(task, on_uncaught);
+        }
+    }
+    
+    fn run_task(&mut self, task: Task, on_uncaught: &mut dyn FnMut(Error)) {
+        self.reset_instruction_budget();
+        if let Err(error) = self.call_function(&task.callback, Value::Undefined, &task.args) {
+            on_uncaught(error);
+        }
+    }
+    
+    /// Start a call: natives run immediately, bytecode functions get a new frame
+    ///
+    /// Returns true if a frame was pushed that the interpreter loop must run.
+    fn invoke(&mut self, callee: Value, this: Value, args: Vec<Value>) -> Result<bool> {
+        self.enter(callee, this, args, false)
+    }
+    
+    /// Like `invoke`, but a bytecode callee in tail position replaces the
+    /// current frame instead of being pushed on top of it
+    ///
+    /// The current frame's own `Return` would just hand the callee's
+    /// result to its caller, so the callee may return there directly.
+    fn enter(&mut self, callee: Value, mut this: Value, args: Vec<Value>, tail: bool) -> Result<bool> {
+        let (function, parent_env, verified) = match callee {
+            Value::Function(FunctionRef::NativeMethod(method)) => {
+                let result = method(&mut CallContext::new(self, &this, &args));
+                self.accumulator = result?;
+                self.charge_native_result()?;
+                return Ok(false);
+            }
+            Value::Function(FunctionRef::Host(host)) => {
+                let depth = self.call_stack.depth();
+                self.accumulator = Value::Undefined;
+                let result = catch_host_panic(&host.name, || (host.callback)(&mut CallContext::new(self, &this, &args)));
+                // Frames of a call back into the VM that panicked
+                self.unwind_to(depth);
+                self.accumulator = result?;
+                self.charge_native_result()?;
+                return Ok(false);
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => {
+                let result = (builtin.call)(&mut CallContext::new(self, &this, &args));
+                self.accumulator = result?;
+                self.charge_native_result()?;
+                return Ok(false);
+            }
+            Value::Function(FunctionRef::Bound(bound)) => {
+                let args = bound.args.iter().cloned().chain(args).collect();
+                return self.enter(bound.target.clone(), bound.this.clone(), args, tail);
+            }
+            Value::Function(FunctionRef::Closure(closure)) => {
+                if let Some(captured) = &closure.this {
+                    this = captured.clone();
+                }
+                (closure.function.clone(), Some(closure.env.clone()), closure.verified)
+            }
+            Value::Function(FunctionRef::Bytecode(index)) => {
+                let function = self.functions.get(index).cloned()
+                    .ok_or_else(|| Error::internal(format!("Invalid function index: {}", index)))?;
+                (function, None, false)
+            }
+            other => {
+                let kind = RuntimeErrorKind::NotCallable {
+                    callee: other.to_string(),
+                    type_of: other.type_of().to_string(),
+                    suggestion: None,
+                };
+                return Err(Error::with_kind(kind, None));
+            }
+        };
+        
+        if function.is_class_constructor {
+            return Err(Error::runtime(
+                ErrorCode::NotConstructor,
+                format!("TypeError: Class constructor {} cannot be invoked without 'new'", function.name),
+                None,
+            ));
+        }
+        
+        if tail && !function.is_resumable() {
+            let caller = self.call_stack.pop()
+                .ok_or_else(|| Error::internal("No frame to replace in tail call"))?;
+            self.stack.truncate(caller.stack_base);
+            self.call_stack.recycle(caller);
+        }
+        let frame = self.new_frame(function, parent_env, this, args).with_verified(verified);
+        
+        // Calling a generator function runs none of its body yet
+        if frame.function.is_generator {
+            self.accumulator = Value::Object(ObjectData::new_generator(frame).into_rc());
+            return Ok(false);
+        }
+        // An async body runs up to its first `await` before the call returns
+        if frame.function.is_async {
+            let generator = Value::Object(ObjectData::new_generator(frame).into_rc());
+            self.accumulator = promise::start_async(self, generator)?;
+            return Ok(false);
+        }
+        
+        self.push_frame(frame)?;
+        Ok(true)
+    }
+    
+    /// The TypeError for calling `callee` at the call instruction just read
+    ///
+    /// Names the callee as written in the source when the compiler
+    /// recorded it, and points at the call. A method `receiver` doesn't
+    /// have gets one of its keys with a similar name as a suggestion.
+    fn not_callable_at_call_site(&self, callee: &Value, receiver: Option<&Value>) -> Error {
+        let name = self.call_stack.current_frame().and_then(|frame| {
+            frame.function.debug_info.get_callee_name(frame.ip().saturating_sub(1))
+        });
+        // `obj.method` as written; computed keys aren't looked into
+        let method = name.and_then(|name| name.rsplit_once('.'))
+            .map(|(_, method)| method)
+            .filter(|method| UnicodeHelper::is_identifier_name(method));
+        let suggestion = match (callee, receiver, method) {
+            (Value::Undefined, Some(receiver @ Value::Object(_)), Some(method)) => {
+                suggest::closest(method, receiver.own_keys(true).iter().map(String::as_str))
+            }
+            _ => None,
+        };
+        let kind = RuntimeErrorKind::NotCallable {
+            callee: name.map_or_else(|| callee.to_string(), str::to_string),
+            type_of: callee.type_of().to_string(),
+            suggestion,
+        };
+        Error::with_kind(kind, self.current_span())
+    }
+    
+    /// The TypeError for `new` of `callee` at the current instruction,
+    /// naming the callee expression as written where debug info has it
+    fn not_constructor_at_call_site(&self, callee: &Value) -> Error {
+        let name = self.call_stack.current_frame().and_then(|frame| {
+            frame.function.debug_info.get_callee_name(frame.ip().saturating_sub(1))
+        });
+        not_constructor(&name.map_or_else(|| callee.to_string(), str::to_string), self.current_span())
+    }
+    
+    /// The ReferenceError for reading global `name`, which doesn't exist,
+    /// suggesting a global with a similar name
+    fn unknown_global(&self, name: &str) -> Error {
+        let mut names: Vec<&str> = self.globals.names().collect();
+        // Sorted, so that ties are broken the same way every run
+        names.sort_unstable();
+        let suggestion = suggest::closest(name, names);
+        Error::with_kind(RuntimeErrorKind::UnknownGlobal { name: name.to_string(), suggestion }, None)
+    }
+    
+    /// Whether the call just read is immediately followed by `Return`
+    ///
+    /// Frames without a return address (the main program, resumed
+    /// generators), generator bodies and constructors, whose result
+    /// `handle_return` may replace, are never replaced.
+    fn in_tail_position(&self) -> bool {
+        self.call_stack.current_frame().is_some_and(|frame| {
+            frame.return_address.is_some()
+                && !frame.function.is_resumable()
+                && !frame.constructing
+                && matches!(frame.function.bytecode.get(frame.ip()), Some(Bytecode::Return))
+        })
+    }
+    
+    /// Span of the instruction being executed, if the compiler recorded one
+    fn current_span(&self) -> Option<Span> {
+        let frame = self.call_stack.current_frame()?;
+        frame.function.debug_info.get_span(frame.ip().saturating_sub(1)).copied()
+    }
+    
+    /// An own property of an ordinary object read through the current
+    /// instruction's property cache; `None` when the caches are off or
+    /// the read needs the full lookup
+    fn cached_own_property(&mut self, object: &Value, name: &Atom) -> Option<Value> {
+        let caches = self.property_caches.as_deref_mut()?;
+        let Value::Object(data) = object else {
+            return None;
+        };
+        // Array elements and `length` are not in the property map
+        if data.array_elements().is_some() {
+            return None;
+        }
+        let frame = self.call_stack.current_frame()?;
+        caches.read(&frame.function, frame.ip().saturating_sub(1), &data.properties.borrow(), name)
+    }
+    
+    /// Point a runtime error without a location at the current instruction
+    fn at_current_instruction(&self, error: Error) -> Error {
+        match error {
+            Error::Runtime { kind, span: None } => Error::with_kind(kind, self.current_span()),
+            other => other,
+        }
+    }
+    
+    /// `new callee(...args)`: run the constructor on a fresh object that
+    /// inherits from `callee.prototype`
+    ///
+    /// The result is that object, unless the constructor returns an
+    /// object of its own.
+    pub fn construct(&mut self, callee: &Value, args: Vec<Value>) -> Result<Value> {
+        let _heap = self.activate_stress_heap();
+        let depth = self.call_stack.depth();
+        if let Some(result) = self.begin_construct(callee, args)? {
+            return Ok(result);
+        }
+        if let Err(e) = self.run(depth) {
+            self.unwind_to(depth);
+            return Err(e);
+        }
+        Ok(self.accumulator.clone())
+    }
+    
+    /// Start `new callee(...args)`
+    ///
+    /// A built-in constructor runs to its result here. A closure gets a
+    /// constructing frame pushed like any call, and `None` is returned;
+    /// `handle_return` then gives its result.
+    fn begin_construct(&mut self, callee: &Value, args: Vec<Value>) -> Result<Option<Value>> {
+        // Built-in constructors make their own objects
+        if let Value::Function(FunctionRef::Builtin(builtin)) = callee {
+            if let Some(construct) = builtin.construct {
+                return construct(&mut CallContext::new(self, &Value::Undefined, &args)).map(Some);
+            }
+        }
+        let closure = match callee {
+            Value::Function(FunctionRef::Closure(closure)) if is_constructor(callee) => closure.clone(),
+            other => return Err(not_constructor(&other.to_string(), None)),
+        };
+        let prototype = match self.get_property(callee, "prototype")? {
+            Value::Object(prototype) => Some(prototype),
+            _ => None,
+        };
+        let this = ObjectBuilder::new().prototype(prototype).build();
+        
+        let frame = self.new_frame(closure.function.clone(), Some(closure.env.clone()), this, args)
+            .with_verified(closure.verified)
+            .with_constructing(true);
+        self.push_frame(frame)?;
+        Ok(None)
+    }
+    
+    /// `object instanceof target`: whether `target.prototype` is on the
+    /// prototype chain of `object`
+    fn instance_of(&self, object: &Value, target: &Value) -> Result<bool> {
+        if !matches!(target, Value::Function(_)) {
+            return Err(Error::runtime(ErrorCode::InvalidInstanceof, "TypeError: Right-hand side of 'instanceof' is not callable", None));
+        }
+        let prototype = match self.get_property(target, "prototype")? {
+            Value::Object(prototype) => prototype,
+            other => {
+                return Err(Error::runtime(
+                    ErrorCode::InvalidInstanceof,
+                    format!("TypeError: Function has non-object prototype '{}' in instanceof check", other),
+                    None,
+                ));
+            }
+        };
+        Ok(match object {
+            Value::Object(data) => data.inherits_from(&prototype),
+            _ => false,
+        })
+    }
+    
+    /// A frame for calling `function`, returning to the current frame
+    fn new_frame(
+        &mut self,
+        function: Arc<BytecodeFunction>,
+        parent_env: Option<Rc<Environment>>,
+        this: Value,
+        args: Vec<Value>,
+    ) -> CallFrame {
+        let (return_address, caller_stack_base) = self.call_stack.current_frame()
+            .map(|frame| (frame.ip(), frame.stack_base))
+            .unwrap_or((0, 0));
+        let storage = self.call_stack.take_storage();
+        let env = Environment::with_storage(storage, function.locals_count as usize, parent_env);
+        CallFrame::new_in_environment(
+            function,
+            env,
+            args,
+            return_address,
+            caller_stack_base,
+            self.stack.len(),
+        ).with_this(this)
+    }
+    
+    /// Push a call frame, failing once the call stack is full
+    fn push_frame(&mut self, frame: CallFrame) -> Result<()> {
+        let depth = self.call_stack.depth();
+        self.call_stack.push(frame)
+            .map_err(|_| Error::with_kind(RuntimeErrorKind::StackOverflow { depth }, None))
+    }
+    
+    /// Drop frames above `depth` after an error, restoring the operand stack
+    fn unwind_to(&mut self, depth: usize) {
+        while self.call_stack.depth() > depth {
+            if let Some(frame) = self.call_stack.pop() {
+                self.stack.truncate(frame.stack_base);
+            }
+        }
+    }
+    
+    /// Bytes charged to the strings and objects that are still live, or
+    /// `None` without `VMConfig::max_heap_bytes`
+    pub fn heap_bytes(&mut self) -> Option<usize> {
+        self.heap.as_mut().map(HeapAccount::live)
+    }
+    
+    /// Charge a new string or object to the heap limit, if there is one
+    pub(crate) fn charge_allocation(&mut self, value: &Value) -> Result<()> {
+        match &mut self.heap {
+            Some(heap) => heap.charge(value),
+            None => Ok(()),
+        }
+    }
+    
+    /// Charge the accumulator when a native call has just made it: when
+    /// nothing else holds it yet
+    fn charge_native_result(&mut self) -> Result<()> {
+        let Some(heap) = &mut self.heap else { return Ok(()) };
+        let fresh = match &self.accumulator {
+            Value::String(string) => Rc::strong_count(string) == 1,
+            Value::Object(object) => Rc::strong_count(object) == 1,
+            _ => false,
+        };
+        if fresh { heap.charge(&self.accumulator) } else { Ok(()) }
+    }
+    
+    /// How far `object` has grown, to pass to `charge_growth` after
+    /// growing it; `None` without a heap limit
+    pub(crate) fn heap_extent(&self, object: &ObjectData) -> Option<Extent> {
+        self.heap.as_ref().map(|_| Extent::of(object))
+    }
+    
+    /// Charge what `object` grew by since `heap_extent` gave `before`
+    pub(crate) fn charge_growth(&mut self, object: &Rc<ObjectData>, before: Option<Extent>) -> Result<()> {
+        match (&mut self.heap, before) {
+            (Some(heap), Some(before)) => heap.charge_growth(object, before),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Give the next top-level run its full instruction limit
+    fn reset_instruction_budget(&mut self) {
+        self.instructions_left = self.max_instructions.unwrap_or(0);
+    }
+    
+    /// Main interpreter loop; runs until the call stack is back to `base_depth`
+    fn run(&mut self, base_depth: usize) -> Result<()> {
+        while self.call_stack.depth() > base_depth {
+            if self.gc_stress.as_ref().is_some_and(|heap| heap.take_allocated()) {
+                self.stress_collect();
+            }
+            if let Some(limit) = self.max_instructions {
+                if self.instructions_left == 0 {
+                    return Err(Error::with_kind(RuntimeErrorKind::InstructionLimit { limit }, None));
+                }
+                self.instructions_left -= 1;
+            }
+            if let Some(metrics) = &mut self.metrics {
+                metrics.instructions_executed += 1;
+                metrics.peak_stack_depth = metrics.peak_stack_depth.max(self.stack.len());
             }
             
             // Get frame info we need
             let (instruction, ip, at_end) = {
                 let frame = self.call_stack.current_frame_mut()
-                    .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("No active frame"))?;
                 
                 // Check if we've reached the end of the function
                 if frame.is_at_end() {
@@ -101,6 +1301,11 @@ impl VM {
                 } else {
                     let instruction = frame.function.bytecode[frame.ip()].clone();
                     let ip = frame.ip();
+                    if let Some(profiler) = &mut self.profiler {
+                        if profiler.mode() == ProfileMode::Counting {
+                            profiler.record(&frame.function, ip);
+                        }
+                    }
                     frame.advance_ip(1);
                     (Some(instruction), ip, false)
                 }
@@ -109,16 +1314,25 @@ impl VM {
             if at_end {
                 // Implicit return undefined
                 self.accumulator = Value::Undefined;
-                if !self.handle_return()? {
-                    break; // Main function returned
-                }
+                self.handle_return()?;
                 continue;
             }
             
             let instruction = instruction.unwrap();
             
-            if self.debug {
-                self.debug_instruction(&instruction, ip);
+            if self.trace_execution {
+                self.trace_instruction(&instruction, ip);
+            }
+            
+            let breakpoint = match self.call_stack.current_frame() {
+                Some(frame) if !self.breakpoints.is_empty() => self.breakpoints.hit(&frame.function, ip),
+                _ => None,
+            };
+            if let Some(id) = breakpoint {
+                self.pause(PauseReason::Breakpoint(id))?;
+            } else if self.stepping.is_some() && self.steps_to(ip) && instruction != Bytecode::Debugger {
+                // A `debugger` statement pauses by itself
+                self.pause(PauseReason::Step)?;
             }
             
             // Execute the instruction
@@ -135,185 +1349,322 @@ impl VM {
             Bytecode::LdaConst(idx) => {
                 let constant = self.get_constant(idx)?;
                 self.accumulator = self.constant_to_value(constant)?;
+                if let Some(heap) = &mut self.heap {
+                    heap.charge(&self.accumulator)?;
+                }
+            }
+            
+            Bytecode::LdaSmi(value) => {
+                self.accumulator = Value::Number(value as f64);
             }
             
             Bytecode::LdaLocal(idx) => {
                 let frame = self.call_stack.current_frame()
-                    .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                self.accumulator = frame.get_local(idx as usize)
-                    .map_err(|e| Error::Runtime { message: e, span: None })?
-                    .clone();
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                self.accumulator = if frame.verified {
+                    frame.env.get_or_panic(idx.index())
+                } else {
+                    frame.get_local(idx.index()).map_err(Error::internal)?
+                };
             }
             
             Bytecode::StaLocal(idx) => {
                 let frame = self.call_stack.current_frame_mut()
-                    .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                frame.set_local(idx as usize, self.accumulator.clone())
-                    .map_err(|e| Error::Runtime { message: e, span: None })?;
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                // Compiled code never does this, and the verifier rejects
+                // it; debug builds check anyway for bytecode built by hand
+                if cfg!(debug_assertions) && frame.function.immutable_locals.contains(idx) {
+                    return Err(self.at_current_instruction(Error::runtime(ErrorCode::ConstAssignment, "TypeError: Assignment to constant variable.", None)));
+                }
+                if frame.verified {
+                    frame.env.set_or_panic(idx.index(), self.accumulator.clone());
+                } else {
+                    frame.set_local(idx.index(), self.accumulator.clone())
+                        .map_err(Error::internal)?;
+                }
+            }
+            
+            Bytecode::InitLocal(idx) => {
+                let frame = self.call_stack.current_frame_mut()
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                if frame.verified {
+                    frame.env.set_or_panic(idx.index(), self.accumulator.clone());
+                } else {
+                    frame.set_local(idx.index(), self.accumulator.clone())
+                        .map_err(Error::internal)?;
+                }
             }
             
             Bytecode::LdaGlobal(idx) => {
                 let name = self.get_constant_string(idx)?;
-                self.accumulator = self.globals.get(&name)
-                    .cloned()
-                    .unwrap_or(Value::Undefined);
+                self.accumulator = match self.globals.get(&name) {
+                    Some(slot) => slot.value.clone(),
+                    None => return Err(self.unknown_global(&name)),
+                };
             }
             
-            Bytecode::StaGlobal(idx) => {
+            Bytecode::LdaGlobalInsideTypeof(idx) => {
                 let name = self.get_constant_string(idx)?;
-                self.globals.insert(name, self.accumulator.clone());
+                self.accumulator = self.globals.get(&name).map(|slot| slot.value.clone()).unwrap_or_default();
+            }
+            
+            Bytecode::StaGlobal(idx) | Bytecode::InitGlobal(idx) => {
+                let name = self.get_constant_string(idx)?;
+                match self.globals.get_mut(&name) {
+                    Some(slot) if !slot.writable => {
+                        // Sloppy mode silently ignores the write; strict mode throws
+                        if self.is_strict() {
+                            return Err(Error::runtime(
+                                ErrorCode::ReadOnlyProperty,
+                                format!("Cannot assign to read only property '{}' of the global object", &*name),
+                                None,
+                            ));
+                        }
+                    }
+                    Some(slot) => {
+                        slot.value = self.accumulator.clone();
+                        self.globals.mark_written(&name);
+                    }
+                    None => {
+                        // Strict mode code can't create globals by assigning,
+                        // only by declaring them
+                        if self.is_strict() && matches!(instruction, Bytecode::StaGlobal(_)) {
+                            return Err(self.unknown_global(&name));
+                        }
+                        self.globals.insert(name.to_string(), GlobalSlot::new(self.accumulator.clone()));
+                    }
+                }
+            }
+            
+            Bytecode::LdaContextSlot(depth, idx) => {
+                let env = self.context_env(depth)?;
+                self.accumulator = env.get(idx.index())
+                    .map_err(Error::internal)?;
+            }
+            
+            Bytecode::StaContextSlot(depth, idx) => {
+                let env = self.context_env(depth)?;
+                env.set(idx.index(), self.accumulator.clone())
+                    .map_err(Error::internal)?;
             }
             
             // === Stack Operations ===
             Bytecode::Push => {
                 if self.stack.len() >= self.max_stack_size {
-                    return Err(Error::Runtime { message: "Stack overflow".to_string(), span: None });
+                    return Err(Error::with_kind(
+                        RuntimeErrorKind::StackOverflow { depth: self.call_stack.depth() },
+                        None,
+                    ));
                 }
                 self.stack.push(self.accumulator.clone());
             }
             
             Bytecode::Pop => {
                 self.accumulator = self.stack.pop()
-                    .unwrap_or(Value::Undefined);
+                    .ok_or_else(|| Error::internal("Stack underflow in Pop"))?;
+            }
+            
+            Bytecode::Dup => {
+                let top = self.stack.last().cloned()
+                    .ok_or_else(|| Error::internal("Stack underflow in Dup"))?;
+                self.stack.push(top);
+            }
+            
+            Bytecode::Dup2 => {
+                let len = self.stack.len();
+                if len < 2 {
+                    return Err(Error::internal("Stack underflow in Dup2"));
+                }
+                self.stack.extend_from_within(len - 2..);
             }
             
             // === Arithmetic Operations ===
             Bytecode::Add => {
-                let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Add".to_string(), span: None })?;
-                let right = self.accumulator.clone();
+                let left_operand = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in Add"))?;
+                let right_operand = self.accumulator.clone();
                 
-                // JavaScript addition: string concatenation or numeric addition
+                // JavaScript addition: both sides become primitives first,
+                // then string concatenation or numeric addition
+                let (left, right) = self.with_roots([&left_operand, &right_operand], |vm| -> Result<_> {
+                    let left = vm.to_primitive(&left_operand, PreferredType::Default)?;
+                    Ok((left, vm.to_primitive(&right_operand, PreferredType::Default)?))
+                })?;
+                if matches!(left, Value::Symbol(_)) || matches!(right, Value::Symbol(_)) {
+                    let target = if matches!((&left, &right), (Value::String(_), _) | (_, Value::String(_))) { "string" } else { "number" };
+                    return Err(self.at_current_instruction(symbol::conversion_error(target)));
+                }
                 self.accumulator = match (&left, &right) {
                     (Value::String(s1), Value::String(s2)) => {
                         Value::string(format!("{}{}", s1, s2))
                     }
                     (Value::String(s), _) => {
-                        Value::string(format!("{}{}", s, right.to_string()))
+                        Value::string(format!("{}{}", s, right))
                     }
                     (_, Value::String(s)) => {
-                        Value::string(format!("{}{}", left.to_string(), s))
+                        Value::string(format!("{}{}", left, s))
                     }
                     _ => {
                         Value::Number(left.to_number() + right.to_number())
                     }
                 };
+                if let Some(heap) = &mut self.heap {
+                    heap.charge(&self.accumulator)?;
+                }
+                if self.trace_nan {
+                    self.note_nan("+", vec![left_operand, right_operand]);
+                }
             }
             
             Bytecode::Sub => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Sub".to_string(), span: None })?;
-                let right = self.accumulator.clone();
-                self.accumulator = Value::Number(
-                    left.to_number() - right.to_number()
-                );
+                    .ok_or_else(|| Error::internal("Stack underflow in Sub"))?;
+                let result = Value::Number(left.to_number() - self.accumulator.to_number());
+                let right = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("-", vec![left, right]);
+                }
             }
             
             Bytecode::Mul => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Mul".to_string(), span: None })?;
-                let right = self.accumulator.clone();
-                self.accumulator = Value::Number(
-                    left.to_number() * right.to_number()
-                );
+                    .ok_or_else(|| Error::internal("Stack underflow in Mul"))?;
+                let result = Value::Number(left.to_number() * self.accumulator.to_number());
+                let right = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("*", vec![left, right]);
+                }
             }
             
             Bytecode::Div => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Div".to_string(), span: None })?;
-                let right = self.accumulator.clone();
-                self.accumulator = Value::Number(
-                    left.to_number() / right.to_number()
-                );
+                    .ok_or_else(|| Error::internal("Stack underflow in Div"))?;
+                let result = Value::Number(left.to_number() / self.accumulator.to_number());
+                let right = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("/", vec![left, right]);
+                }
             }
             
             Bytecode::Mod => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Mod".to_string(), span: None })?;
-                let right = self.accumulator.clone();
-                self.accumulator = Value::Number(
-                    left.to_number() % right.to_number()
-                );
+                    .ok_or_else(|| Error::internal("Stack underflow in Mod"))?;
+                let result = Value::Number(left.to_number() % self.accumulator.to_number());
+                let right = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("%", vec![left, right]);
+                }
             }
             
             Bytecode::Pow => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Pow".to_string(), span: None })?;
-                let right = self.accumulator.clone();
-                self.accumulator = Value::Number(
-                    left.to_number().powf(right.to_number())
-                );
+                    .ok_or_else(|| Error::internal("Stack underflow in Pow"))?;
+                let result = Value::Number(js_pow(left.to_number(), self.accumulator.to_number()));
+                let right = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("**", vec![left, right]);
+                }
+            }
+            
+            Bytecode::Inc => {
+                let result = Value::Number(self.accumulator.to_number() + 1.0);
+                let operand = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("++", vec![operand]);
+                }
+            }
+            
+            Bytecode::Dec => {
+                let result = Value::Number(self.accumulator.to_number() - 1.0);
+                let operand = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("--", vec![operand]);
+                }
             }
             
             // === Comparison Operations ===
             Bytecode::Eq => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Eq".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Eq"))?;
                 let right = self.accumulator.clone();
                 self.accumulator = Value::Boolean(left.loose_eq(&right));
             }
             
             Bytecode::Ne => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Ne".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Ne"))?;
                 let right = self.accumulator.clone();
                 self.accumulator = Value::Boolean(!left.loose_eq(&right));
             }
             
             Bytecode::StrictEq => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in StrictEq".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in StrictEq"))?;
                 let right = self.accumulator.clone();
                 self.accumulator = Value::Boolean(left.strict_eq(&right));
             }
             
             Bytecode::StrictNe => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in StrictNe".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in StrictNe"))?;
                 let right = self.accumulator.clone();
                 self.accumulator = Value::Boolean(!left.strict_eq(&right));
             }
             
             Bytecode::Lt => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Lt".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Lt"))?;
                 let right = self.accumulator.clone();
-                self.accumulator = Value::Boolean(
-                    left.to_number() < right.to_number()
-                );
+                self.accumulator = Value::Boolean(matches!(left.compare(&right), Some(Ordering::Less)));
             }
             
             Bytecode::Gt => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Gt".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Gt"))?;
                 let right = self.accumulator.clone();
-                self.accumulator = Value::Boolean(
-                    left.to_number() > right.to_number()
-                );
+                self.accumulator = Value::Boolean(matches!(left.compare(&right), Some(Ordering::Greater)));
             }
             
             Bytecode::Le => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Le".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Le"))?;
                 let right = self.accumulator.clone();
-                self.accumulator = Value::Boolean(
-                    left.to_number() <= right.to_number()
-                );
+                self.accumulator = Value::Boolean(matches!(left.compare(&right), Some(Ordering::Less | Ordering::Equal)));
             }
             
             Bytecode::Ge => {
                 let left = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in Ge".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in Ge"))?;
                 let right = self.accumulator.clone();
-                self.accumulator = Value::Boolean(
-                    left.to_number() >= right.to_number()
-                );
+                self.accumulator = Value::Boolean(matches!(left.compare(&right), Some(Ordering::Greater | Ordering::Equal)));
+            }
+            
+            Bytecode::InstanceOf => {
+                let object = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in InstanceOf"))?;
+                let target = self.accumulator.clone();
+                self.accumulator = Value::Boolean(self.instance_of(&object, &target)?);
+            }
+            
+            Bytecode::In => {
+                let key = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in In"))?;
+                let object = self.accumulator.clone();
+                if !matches!(object, Value::Object(_) | Value::Function(_)) {
+                    return Err(self.at_current_instruction(Error::runtime(
+                        ErrorCode::InvalidInOperand,
+                        format!("TypeError: Cannot use 'in' operator to search for '{}' in {}", key, object),
+                        None,
+                    )));
+                }
+                let key = self.with_roots([&object], |vm| vm.to_property_key(&key))?;
+                self.accumulator = Value::Boolean(self.has_keyed_property(&object, &key));
             }
             
             // === Logical Operations ===
             Bytecode::LogicalAnd => {
                 let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in LogicalAnd".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in LogicalAnd"))?;
                 // JavaScript short-circuit: return first falsy or last value
                 if !self.accumulator.is_truthy() {
                     // accumulator is already the result (first falsy value)
@@ -324,7 +1675,7 @@ impl VM {
             
             Bytecode::LogicalOr => {
                 let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in LogicalOr".to_string(), span: None })?;
+                    .ok_or_else(|| Error::internal("Stack underflow in LogicalOr"))?;
                 // JavaScript short-circuit: return first truthy or last value
                 if self.accumulator.is_truthy() {
                     // accumulator is already the result (first truthy value)
@@ -337,101 +1688,113 @@ impl VM {
                 self.accumulator = Value::Boolean(!self.accumulator.is_truthy());
             }
             
+            Bytecode::ToBoolean => {
+                self.accumulator = Value::Boolean(self.accumulator.is_truthy());
+            }
+            
             // === Bitwise Operations ===
             Bytecode::BitwiseAnd => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in BitwiseAnd".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as i32;
-                let right = right.to_number() as i32;
-                self.accumulator = Value::Number((left & right) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in BitwiseAnd"))?;
+                let right = self.accumulator.to_int32();
+                self.accumulator = Value::Number((left.to_int32() & right) as f64);
             }
             
             Bytecode::BitwiseOr => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in BitwiseOr".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as i32;
-                let right = right.to_number() as i32;
-                self.accumulator = Value::Number((left | right) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in BitwiseOr"))?;
+                let right = self.accumulator.to_int32();
+                self.accumulator = Value::Number((left.to_int32() | right) as f64);
             }
             
             Bytecode::BitwiseXor => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in BitwiseXor".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as i32;
-                let right = right.to_number() as i32;
-                self.accumulator = Value::Number((left ^ right) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in BitwiseXor"))?;
+                let right = self.accumulator.to_int32();
+                self.accumulator = Value::Number((left.to_int32() ^ right) as f64);
             }
             
             Bytecode::BitwiseNot => {
-                let value = self.accumulator.to_number() as i32;
+                let value = self.accumulator.to_int32();
                 self.accumulator = Value::Number((!value) as f64);
             }
             
             Bytecode::LeftShift => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in LeftShift".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as i32;
-                let right = right.to_number() as u32;
-                self.accumulator = Value::Number((left << (right & 31)) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in LeftShift"))?;
+                let shift = self.accumulator.to_uint32() & 31;
+                self.accumulator = Value::Number(left.to_int32().wrapping_shl(shift) as f64);
             }
             
             Bytecode::RightShift => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in RightShift".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as i32;
-                let right = right.to_number() as u32;
-                self.accumulator = Value::Number((left >> (right & 31)) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in RightShift"))?;
+                let shift = self.accumulator.to_uint32() & 31;
+                self.accumulator = Value::Number((left.to_int32() >> shift) as f64);
             }
             
             Bytecode::UnsignedRightShift => {
-                let right = self.stack.pop()
-                    .ok_or_else(|| Error::Runtime { message: "Stack underflow in UnsignedRightShift".to_string(), span: None })?;
-                let left = self.accumulator.to_number() as u32;
-                let right = right.to_number() as u32;
-                self.accumulator = Value::Number((left >> (right & 31)) as f64);
+                let left = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in UnsignedRightShift"))?;
+                let shift = self.accumulator.to_uint32() & 31;
+                self.accumulator = Value::Number((left.to_uint32() >> shift) as f64);
             }
             
             // === Unary Operations ===
             Bytecode::UnaryPlus => {
-                self.accumulator = Value::Number(self.accumulator.to_number());
+                let result = Value::Number(self.accumulator.to_number());
+                let operand = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("+", vec![operand]);
+                }
             }
             
             Bytecode::UnaryMinus => {
-                self.accumulator = Value::Number(-self.accumulator.to_number());
+                let result = Value::Number(-self.accumulator.to_number());
+                let operand = std::mem::replace(&mut self.accumulator, result);
+                if self.trace_nan {
+                    self.note_nan("-", vec![operand]);
+                }
             }
             
             Bytecode::TypeOf => {
                 self.accumulator = Value::string(self.accumulator.type_of());
             }
             
+            Bytecode::LdaThis => {
+                let frame = self.call_stack.current_frame()
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                self.accumulator = frame.this_value.clone();
+            }
+            
             // === Control Flow ===
             Bytecode::Jump(offset) => {
                 let frame = self.call_stack.current_frame_mut()
-                    .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                frame.jump_relative(offset);
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                frame.jump_relative(offset).map_err(Error::internal)?;
             }
             
             Bytecode::JumpIfFalse(offset) => {
                 if !self.accumulator.is_truthy() {
                     let frame = self.call_stack.current_frame_mut()
-                        .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                    frame.jump_relative(offset);
+                        .ok_or_else(|| Error::internal("No active frame"))?;
+                    frame.jump_relative(offset).map_err(Error::internal)?;
                 }
             }
             
             Bytecode::JumpIfTrue(offset) => {
                 if self.accumulator.is_truthy() {
                     let frame = self.call_stack.current_frame_mut()
-                        .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                    frame.jump_relative(offset);
+                        .ok_or_else(|| Error::internal("No active frame"))?;
+                    frame.jump_relative(offset).map_err(Error::internal)?;
                 }
             }
             
             Bytecode::JumpIfNullish(offset) => {
                 if matches!(self.accumulator, Value::Null | Value::Undefined) {
                     let frame = self.call_stack.current_frame_mut()
-                        .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
-                    frame.jump_relative(offset);
+                        .ok_or_else(|| Error::internal("No active frame"))?;
+                    frame.jump_relative(offset).map_err(Error::internal)?;
                 }
             }
             
@@ -447,70 +1810,253 @@ impl VM {
             }
             
             Bytecode::Call(arg_count) => {
-                // For now, we only support built-in function calls
-                // The function name should be a string in the accumulator
-                if let Value::String(name) = &self.accumulator {
-                    if let Some(builtin_fn) = self.builtins.get(&**name) {
-                        // Pop arguments from stack
-                        let mut args = Vec::with_capacity(arg_count as usize);
-                        for _ in 0..arg_count {
-                            args.push(self.stack.pop()
-                                .unwrap_or(Value::Undefined));
-                        }
-                        args.reverse(); // Arguments were pushed in order
-                        
-                        // Call the built-in function
-                        self.accumulator = builtin_fn(&args);
-                    } else {
-                        return Err(Error::Runtime { message: format!("Unknown function: {}", name), span: None });
+                let args = self.pop_arguments(arg_count)?;
+                let callee = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in Call"))?;
+                if !matches!(callee, Value::Function(_)) {
+                    return Err(self.not_callable_at_call_site(&callee, None));
+                }
+                let tail = self.in_tail_position();
+                self.enter(callee, Value::Undefined, args, tail)?;
+            }
+            
+            Bytecode::CallMethod(arg_count) => {
+                let args = self.pop_arguments(arg_count)?;
+                let (callee, receiver) = match (self.stack.pop(), self.stack.pop()) {
+                    (Some(callee), Some(receiver)) => (callee, receiver),
+                    _ => return Err(Error::internal("Stack underflow in CallMethod")),
+                };
+                if !matches!(callee, Value::Function(_)) {
+                    return Err(self.not_callable_at_call_site(&callee, Some(&receiver)));
+                }
+                let tail = self.in_tail_position();
+                self.enter(callee, receiver, args, tail)?;
+            }
+            
+            Bytecode::Construct(arg_count) => {
+                let args = self.pop_arguments(arg_count)?;
+                let callee = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in Construct"))?;
+                if !is_constructor(&callee) {
+                    return Err(self.not_constructor_at_call_site(&callee));
+                }
+                if let Some(result) = self.begin_construct(&callee, args)? {
+                    self.accumulator = result;
+                }
+            }
+            
+            Bytecode::Yield => {
+                // Detach the frame and its operands; the interpreter loop
+                // then returns to `resume_generator`
+                let frame = self.call_stack.pop()
+                    .ok_or_else(|| Error::internal("No frame to yield from"))?;
+                if !frame.function.is_resumable() {
+                    return Err(Error::internal("Yield outside a generator"));
+                }
+                let operands = self.stack.split_off(frame.stack_base.min(self.stack.len()));
+                self.suspended = Some((frame, operands));
+            }
+            
+            // === Iteration ===
+            Bytecode::GetIterator => {
+                let iterable = self.accumulator.clone();
+                self.accumulator = self.get_iterator(&iterable, IterationConsumer::ForOf)?;
+            }
+            
+            Bytecode::IteratorNext(offset) => {
+                let iterator = self.stack.last().cloned()
+                    .ok_or_else(|| Error::internal("Stack underflow in IteratorNext"))?;
+                match self.iterator_step(&iterator)? {
+                    Some(value) => self.accumulator = value,
+                    None => {
+                        let frame = self.call_stack.current_frame_mut()
+                            .ok_or_else(|| Error::internal("No active frame"))?;
+                        frame.jump_relative(offset).map_err(Error::internal)?;
                     }
-                } else {
-                    return Err(Error::Runtime { message: "Can only call functions".to_string(), span: None });
                 }
             }
             
-            // === Object Operations (stubs for now) ===
+            Bytecode::IteratorClose => {
+                let iterator = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in IteratorClose"))?;
+                self.close_iterator(&iterator)?;
+            }
+            
+            // === Object Operations ===
             Bytecode::CreateObject => {
-                // Create an empty object
-                use std::rc::Rc;
-                use super::value::ObjectData;
-                self.accumulator = Value::Object(Rc::new(ObjectData {
-                    properties: HashMap::new(),
-                }));
+                self.accumulator = match self.arena_object(false) {
+                    Some(object) => {
+                        object.reset(std::iter::empty());
+                        Value::Object(object)
+                    }
+                    None => {
+                        let object = Value::new_object();
+                        self.charge_allocation(&object)?;
+                        object
+                    }
+                };
             }
             
-            Bytecode::CreateArray(_idx) => {
-                // Create an empty array (represented as object for now)
-                use std::rc::Rc;
-                use super::value::ObjectData;
-                self.accumulator = Value::Object(Rc::new(ObjectData {
-                    properties: HashMap::new(),
-                }));
+            Bytecode::CreateArray(length) => {
+                self.accumulator = match self.arena_object(true) {
+                    Some(object) => {
+                        object.reset(std::iter::empty());
+                        if let Some(elements) = object.array_elements() {
+                            elements.borrow_mut().resize(length as usize, None);
+                        }
+                        Value::Object(object)
+                    }
+                    None => {
+                        let array = Value::new_array_with_length(length as usize);
+                        self.charge_allocation(&array)?;
+                        array
+                    }
+                };
             }
             
-            Bytecode::CreateClosure(_idx) => {
-                // Create a closure (not fully implemented yet)
-                self.accumulator = Value::Undefined;
+            Bytecode::CreateFromTemplate(idx) => {
+                let template = self.get_constant(idx)?;
+                let is_array = match template {
+                    ConstantValue::ObjectTemplate(_) => false,
+                    ConstantValue::ArrayTemplate(_) => true,
+                    _ => return Err(Error::internal("CreateFromTemplate needs a template constant")),
+                };
+                self.accumulator = match self.arena_object(is_array) {
+                    Some(object) => {
+                        self.fill_from_template(&object, idx)?;
+                        Value::Object(object)
+                    }
+                    None => {
+                        let object = self.constant_to_value(self.get_constant(idx)?)?;
+                        self.charge_allocation(&object)?;
+                        object
+                    }
+                };
             }
             
-            Bytecode::LdaNamed(_idx) => {
-                // Load named property (not fully implemented)
-                self.accumulator = Value::Undefined;
+            Bytecode::StaArrayElement(index) => {
+                let elements = match self.stack.last() {
+                    Some(Value::Object(object)) => object.array_elements(),
+                    _ => None,
+                };
+                let elements = elements
+                    .ok_or_else(|| Error::internal("StaArrayElement without an array on the stack"))?;
+                let mut elements = elements.borrow_mut();
+                let index = index as usize;
+                if index >= elements.len() {
+                    elements.resize(index + 1, None);
+                }
+                elements[index] = Some(self.accumulator.clone());
+            }
+            
+            Bytecode::DefineNamedProperty(idx) => {
+                let name = self.get_constant_string(idx)?;
+                let target = self.stack.last().cloned()
+                    .ok_or_else(|| Error::internal("Stack underflow in DefineNamedProperty"))?;
+                self.set_property(&target, &name, self.accumulator.clone())?;
             }
             
-            Bytecode::StaNamed(_idx) => {
-                // Store named property (not fully implemented)
-                // For now, just consume the value
+            Bytecode::DefineKeyedProperty => {
+                let key = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in DefineKeyedProperty"))?;
+                let target = self.stack.last().cloned()
+                    .ok_or_else(|| Error::internal("Stack underflow in DefineKeyedProperty"))?;
+                let value = self.accumulator.clone();
+                let key = self.with_roots([&value], |vm| vm.to_property_key(&key))?;
+                self.set_keyed_property(&target, &key, value)?;
+            }
+            
+            Bytecode::CopyDataProperties => {
+                let target = self.stack.last().cloned()
+                    .ok_or_else(|| Error::internal("Stack underflow in CopyDataProperties"))?;
+                // null and undefined sources are skipped, not an error
+                for (key, value) in self.accumulator.own_entries() {
+                    self.set_property(&target, &key, value)?;
+                }
+            }
+            
+            Bytecode::CreateClosure(idx) => {
+                let frame = self.call_stack.current_frame()
+                    .ok_or_else(|| Error::internal("No active frame"))?;
+                let function = frame.function.functions.get(idx.index()).cloned()
+                    .ok_or_else(|| Error::internal(format!("Invalid function index: {}", idx)))?;
+                let mut closure = Closure::new(function, frame.env.clone());
+                // Nested functions were verified along with this one
+                closure.verified = frame.verified;
+                if closure.function.is_arrow {
+                    closure.this = Some(frame.this_value.clone());
+                }
+                self.accumulator = Value::Function(FunctionRef::Closure(Rc::new(closure)));
+            }
+            
+            Bytecode::LdaNamed(idx) => {
+                let name = self.get_constant_string(idx)?;
+                let object = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in LdaNamed"))?;
+                if let Some(data) = self.proto_accessor_target(&object, &name) {
+                    self.accumulator = data.prototype().map_or(Value::Null, Value::Object);
+                } else if let Some(value) = self.cached_own_property(&object, &name) {
+                    self.accumulator = value;
+                } else {
+                    self.accumulator = self.get_property(&object, &name)
+                        .map_err(|error| self.at_current_instruction(error))?;
+                }
+            }
+            
+            Bytecode::StaNamed(idx) => {
+                let name = self.get_constant_string(idx)?;
+                let object = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in StaNamed"))?;
+                if let Some(data) = self.proto_accessor_target(&object, &name) {
+                    // Values other than objects and null are ignored
+                    let prototype = match &self.accumulator {
+                        Value::Object(prototype) => Some(Some(prototype.clone())),
+                        Value::Null => Some(None),
+                        _ => None,
+                    };
+                    if prototype.is_some_and(|prototype| !data.set_prototype(prototype)) {
+                        return Err(self.at_current_instruction(object_global::cyclic_prototype_error()));
+                    }
+                } else {
+                    self.set_property(&object, &name, self.accumulator.clone())
+                        .map_err(|error| self.at_current_instruction(error))?;
+                }
             }
             
             Bytecode::LdaKeyed => {
-                // Load keyed property (not fully implemented)
-                self.accumulator = Value::Undefined;
+                let key = self.accumulator.clone();
+                let key = self.to_property_key(&key)?;
+                let object = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in LdaKeyed"))?;
+                self.accumulator = self.get_keyed_property(&object, &key)
+                    .map_err(|error| self.at_current_instruction(error))?;
             }
             
             Bytecode::StaKeyed => {
-                // Store keyed property (not fully implemented)
-                // For now, just consume the value
+                let (key, object) = match (self.stack.pop(), self.stack.pop()) {
+                    (Some(key), Some(object)) => (key, object),
+                    _ => return Err(Error::internal("Stack underflow in StaKeyed")),
+                };
+                let value = self.accumulator.clone();
+                let key = self.with_roots([&object, &value], |vm| vm.to_property_key(&key))?;
+                self.set_keyed_property(&object, &key, value)
+                    .map_err(|error| self.at_current_instruction(error))?;
+            }
+            
+            Bytecode::DeleteNamedProperty(idx) => {
+                let name = self.get_constant_string(idx)?;
+                let object = self.accumulator.clone();
+                self.accumulator = Value::Boolean(self.delete_property(&object, &name)
+                    .map_err(|error| self.at_current_instruction(error))?);
+            }
+            
+            Bytecode::DeleteKeyedProperty => {
+                let key = self.accumulator.clone();
+                let key = self.to_property_key(&key)?;
+                let object = self.stack.pop()
+                    .ok_or_else(|| Error::internal("Stack underflow in DeleteKeyedProperty"))?;
+                self.accumulator = Value::Boolean(self.delete_keyed_property(&object, &key)
+                    .map_err(|error| self.at_current_instruction(error))?);
             }
             
             // === Debugging Operations ===
@@ -519,52 +2065,720 @@ impl VM {
             }
             
             Bytecode::Debugger => {
-                // Debugger breakpoint - for now just continue
-                if self.debug {
+                if self.trace_execution {
                     if let Some(frame) = self.call_stack.current_frame() {
-                        println!("[DEBUGGER] Breakpoint hit at ip: {}", frame.ip());
+                        self.debug_note(format_args!("[DEBUGGER] Breakpoint hit at ip: {}", frame.ip()));
+                    }
+                }
+                self.pause(PauseReason::DebuggerStatement)?;
+            }
+        }
+        
+        Ok(())
+    }
+    
+    /// Run a generator until it yields or returns, sending `sent` as the
+    /// value of the `yield` it is suspended at
+    ///
+    /// Returns the yielded or returned value and whether the generator is
+    /// now done. A generator that threw is done; resuming a completed one
+    /// gives `(undefined, true)`.
+    pub fn resume_generator(&mut self, generator: &Value, sent: Value) -> Result<(Value, bool)> {
+        let state = generator_state(generator, "next")?;
+        let (mut frame, operands) = match state.replace(GeneratorState::Running) {
+            GeneratorState::Suspended { frame, operands } => (frame, operands),
+            GeneratorState::Running => {
+                return Err(Error::runtime(ErrorCode::GeneratorRunning, "TypeError: Generator is already running", None));
+            }
+            GeneratorState::Completed => {
+                state.replace(GeneratorState::Completed);
+                return Ok((Value::Undefined, true));
+            }
+        };
+        
+        // Re-attach the frame on top of whatever is running now; it returns
+        // to the native `next()` call rather than to a bytecode caller
+        frame.stack_base = self.stack.len();
+        frame.return_address = None;
+        frame.caller_stack_base = None;
+        self.stack.extend(operands);
+        let depth = self.call_stack.depth();
+        if self.call_stack.push(frame).is_err() {
+            state.replace(GeneratorState::Completed);
+            return Err(Error::with_kind(RuntimeErrorKind::StackOverflow { depth }, None));
+        }
+        
+        self.accumulator = sent;
+        if let Err(e) = self.run(depth) {
+            self.unwind_to(depth);
+            self.suspended = None;
+            state.replace(GeneratorState::Completed);
+            return Err(e);
+        }
+        match self.suspended.take() {
+            Some((frame, operands)) => {
+                state.replace(GeneratorState::Suspended { frame, operands });
+                Ok((self.accumulator.clone(), false))
+            }
+            None => {
+                state.replace(GeneratorState::Completed);
+                Ok((self.accumulator.clone(), true))
+            }
+        }
+    }
+    
+    /// Finish a generator without running any more of its body, as
+    /// `return()` does; other values are left alone
+    pub fn close_generator(&mut self, generator: &Value) -> Result<()> {
+        if let Value::Object(data) = generator {
+            if let Some(state) = data.generator_state() {
+                if matches!(*state.borrow(), GeneratorState::Running) {
+                    return Err(Error::runtime(ErrorCode::GeneratorRunning, "TypeError: Generator is already running", None));
+                }
+                state.replace(GeneratorState::Completed);
+            }
+        }
+        Ok(())
+    }
+    
+    /// The iterator to step through `value` with: a wrapper around what
+    /// `value[Symbol.iterator]()` returns when `value` has that method,
+    /// otherwise the generator itself or a list iterator
+    fn get_iterator(&mut self, value: &Value, consumer: IterationConsumer) -> Result<Value> {
+        let method = match value {
+            Value::Object(_) | Value::Function(_) => self.get_symbol_property(value, &Symbol::iterator())?,
+            _ => Value::Undefined,
+        };
+        match method {
+            Value::Undefined | Value::Null => return iterator_for(value, consumer),
+            Value::Function(_) => {}
+            _ => return Err(not_iterable(value, consumer)),
+        }
+        let saved = self.accumulator.clone();
+        let iterator = self.call_function(&method, value.clone(), &[]);
+        self.accumulator = saved;
+        let iterator = iterator?;
+        if !matches!(iterator, Value::Object(_) | Value::Function(_)) {
+            return Err(invalid_iterator("Result of the Symbol.iterator method is not an object".to_string()));
+        }
+        let next = self.get_property(&iterator, "next")?;
+        Ok(Value::Object(ObjectData::new_iterator_record(iterator, next).into_rc()))
+    }
+    
+    /// Leave an iterator before it is exhausted, as `break` out of for-of
+    /// does: a generator is closed, and an iterator from
+    /// `[Symbol.iterator]` that isn't done has its `return` method called
+    pub fn close_iterator(&mut self, iterator: &Value) -> Result<()> {
+        let Value::Object(data) = iterator else { return Ok(()) };
+        let (inner, done) = match &data.kind {
+            ObjectKind::Generator(_) => return self.close_generator(iterator),
+            ObjectKind::IteratorRecord { iterator, done, .. } => (iterator, done),
+            _ => return Ok(()),
+        };
+        if done.replace(true) {
+            return Ok(());
+        }
+        let method = self.get_property(inner, "return")?;
+        if matches!(method, Value::Undefined | Value::Null) {
+            return Ok(());
+        }
+        let saved = self.accumulator.clone();
+        let result = self.call_function(&method, inner.clone(), &[]);
+        self.accumulator = saved;
+        match result? {
+            Value::Object(_) | Value::Function(_) => Ok(()),
+            other => Err(invalid_iterator(format!("Iterator result {} is not an object", other))),
+        }
+    }
+    
+    /// Advance a for-of iterator; `None` once it is exhausted
+    fn iterator_step(&mut self, iterator: &Value) -> Result<Option<Value>> {
+        let data = match iterator {
+            Value::Object(data) => data,
+            _ => return Err(Error::internal("IteratorNext without an iterator")),
+        };
+        match &data.kind {
+            ObjectKind::Generator(_) => {
+                let (value, done) = self.resume_generator(iterator, Value::Undefined)?;
+                Ok(if done { None } else { Some(value) })
+            }
+            ObjectKind::ListIterator { source: Value::String(s), position } => {
+                // Strings iterate by code point, not by UTF-16 unit
+                let offset = position.get();
+                Ok(s[offset..].chars().next().map(|c| {
+                    position.set(offset + c.len_utf8());
+                    Value::string(c.to_string())
+                }))
+            }
+            ObjectKind::ListIterator { source: Value::Object(array), position } => {
+                // The length is read on every step, so pushes during the loop are seen
+                let index = position.get();
+                let elements = array.array_elements()
+                    .ok_or_else(|| Error::internal("List iterator over a non-array"))?;
+                let element = match elements.borrow().get(index) {
+                    Some(element) => element.clone().unwrap_or(Value::Undefined),
+                    None => return Ok(None),
+                };
+                position.set(index + 1);
+                Ok(Some(element))
+            }
+            ObjectKind::IteratorRecord { iterator, next, done } => {
+                if done.get() {
+                    return Ok(None);
+                }
+                // Until `next` returns a result that isn't done, the
+                // iterator counts as finished, so a failed step isn't closed
+                done.set(true);
+                let saved = self.accumulator.clone();
+                let result = self.call_function(next, iterator.clone(), &[]);
+                self.accumulator = saved;
+                let result = result?;
+                if !matches!(result, Value::Object(_) | Value::Function(_)) {
+                    return Err(invalid_iterator(format!("Iterator result {} is not an object", result)));
+                }
+                if self.get_property(&result, "done")?.is_truthy() {
+                    return Ok(None);
+                }
+                done.set(false);
+                self.get_property(&result, "value").map(Some)
+            }
+            _ => Err(Error::internal("IteratorNext without an iterator")),
+        }
+    }
+    
+    /// Run `f` on each element of `value`, in order, until it breaks
+    ///
+    /// See `iteration` for what counts as iterable; `consumer` names the
+    /// feature in the error for anything else. `f` gets the VM back, so it
+    /// can call into JavaScript. An iterator left early is closed.
+    pub fn iterate_value(
+        &mut self,
+        value: &Value,
+        consumer: IterationConsumer,
+        mut f: impl FnMut(&mut VM, Value) -> Result<ControlFlow<()>>,
+    ) -> Result<()> {
+        let iterator = match self.get_iterator(value, consumer) {
+            Ok(iterator) => iterator,
+            Err(error) if error.code() == ErrorCode::NotIterable && consumer.accepts_array_likes()
+                && !matches!(value, Value::Null | Value::Undefined) => {
+                return self.iterate_array_like(value, f);
+            }
+            Err(error) => return Err(error),
+        };
+        self.with_roots([&iterator], |vm| {
+            while let Some(element) = vm.iterator_step(&iterator)? {
+                if f(vm, element)?.is_break() {
+                    vm.close_iterator(&iterator)?;
+                    break;
+                }
+            }
+            Ok(())
+        })
+    }
+    
+    /// Elements `0..length` of an array-like; primitives have no length
+    fn iterate_array_like(
+        &mut self,
+        value: &Value,
+        mut f: impl FnMut(&mut VM, Value) -> Result<ControlFlow<()>>,
+    ) -> Result<()> {
+        let length = self.get_property(value, "length")?.to_number();
+        let length = if length.is_nan() || length <= 0.0 { 0 } else { length.min(usize::MAX as f64) as usize };
+        check_dense_length(length)?;
+        for index in 0..length {
+            let element = self.get_property(value, &index.to_string())?;
+            if f(self, element)?.is_break() {
+                break;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Every element of `value`, as `iterate_value` visits them
+    ///
+    /// Callers that keep the elements across an allocation root them.
+    pub fn collect_iterable(&mut self, value: &Value, consumer: IterationConsumer) -> Result<Vec<Value>> {
+        // Gathered in a rooted array, so a later step can't collect them
+        let array = Value::new_array([]);
+        self.with_roots([&array], |vm| {
+            vm.iterate_value(value, consumer, |_, element| {
+                check_dense_length(array.push_element(element))?;
+                Ok(ControlFlow::Continue(()))
+            })
+        })?;
+        Ok(array.array_values().unwrap_or_default())
+    }
+    
+    /// Handle function return
+    fn handle_return(&mut self) -> Result<bool> {
+        // Pop current frame, discarding anything it left on the operand stack
+        let current_frame = self.call_stack.pop()
+            .ok_or_else(|| Error::internal("No frame to return from"))?;
+        self.stack.truncate(current_frame.stack_base);
+        let return_address = current_frame.return_address;
+        // `new` gives the constructed object unless the constructor
+        // returned an object of its own
+        if current_frame.constructing && !matches!(self.accumulator, Value::Object(_) | Value::Function(_)) {
+            self.accumulator = current_frame.this_value.clone();
+        }
+        self.call_stack.recycle(current_frame);
+        
+        // Check if we're returning from main
+        if self.call_stack.is_empty() {
+            return Ok(false); // Signal to stop execution
+        }
+        
+        // Restore caller's context (if returning from a function call)
+        if let Some(return_address) = return_address {
+            let frame = self.call_stack.current_frame_mut()
+                .ok_or_else(|| Error::internal("No caller frame"))?;
+            frame.ip = return_address;
+        }
+        
+        Ok(true) // Continue execution
+    }
+    
+    /// Pop `count` call arguments, returning them in source order
+    ///
+    /// Only the current frame's own operands can be taken.
+    fn pop_arguments(&mut self, count: u8) -> Result<Vec<Value>> {
+        let count = count as usize;
+        let base = self.call_stack.current_frame().map_or(0, |frame| frame.stack_base);
+        if self.stack.len() < base + count {
+            return Err(Error::internal("Stack underflow in call arguments"));
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+    
+    /// Environment `depth` levels above the current frame's
+    fn context_env(&self, depth: u8) -> Result<Rc<Environment>> {
+        let frame = self.call_stack.current_frame()
+            .ok_or_else(|| Error::internal("No active frame"))?;
+        frame.env.ancestor(depth as usize)
+            .ok_or_else(|| Error::internal(format!("Invalid context depth: {}", depth)))
+    }
+    
+    /// Read a property, including array/string built-in methods
+    pub(crate) fn get_property<K: AtomKey + ?Sized>(&self, object: &Value, key: &K) -> Result<Value> {
+        let name = key.as_str();
+        match object {
+            Value::Object(data) => {
+                if let Some(value) = data.lookup(key) {
+                    return Ok(value);
+                }
+                let method = match &data.kind {
+                    ObjectKind::Array(_) => builtins::array_method(name),
+                    ObjectKind::Generator(_) => builtins::generator_method(name),
+                    ObjectKind::Promise(_) => promise::promise_method(name),
+                    _ => None,
+                };
+                Ok(method
+                    .or_else(|| builtins::object_method(name))
+                    .map(|method| Value::Function(FunctionRef::NativeMethod(method)))
+                    .unwrap_or(Value::Undefined))
+            }
+            Value::String(s) => {
+                if name == "length" {
+                    return Ok(Value::Number(s.encode_utf16().count() as f64));
+                }
+                if let Some(index) = array_index(name) {
+                    return Ok(s.encode_utf16().nth(index)
+                        .map(|unit| Value::string(String::from_utf16_lossy(&[unit])))
+                        .unwrap_or(Value::Undefined));
+                }
+                Ok(builtins::string_method(name)
+                    .or_else(|| builtins::object_method(name))
+                    .map(|method| Value::Function(FunctionRef::NativeMethod(method)))
+                    .unwrap_or(Value::Undefined))
+            }
+            Value::Function(FunctionRef::Closure(closure)) => {
+                if let Some(value) = closure.properties.borrow().get(key) {
+                    return Ok(value.clone());
+                }
+                // Constructible functions get their `prototype` object on first
+                // use, with the attributes the spec gives both properties; its
+                // `constructor` is a weak reference, or neither would be dropped
+                if name == "prototype" && !closure.function.is_resumable() {
+                    let prototype = Value::Object(ObjectData::new_function_prototype(closure).into_rc());
+                    let permanent = PropertyAttributes { writable: true, ..PropertyAttributes::NONE };
+                    closure.properties.borrow_mut().define(key.to_atom(), prototype.clone(), permanent);
+                    return Ok(prototype);
+                }
+                Ok(builtins::object_method(name)
+                    .map(|method| Value::Function(FunctionRef::NativeMethod(method)))
+                    .unwrap_or(Value::Undefined))
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => {
+                if let Some(value) = builtin.properties.borrow().get(key) {
+                    return Ok(value.clone());
+                }
+                Ok(builtins::object_method(name)
+                    .map(|method| Value::Function(FunctionRef::NativeMethod(method)))
+                    .unwrap_or(Value::Undefined))
+            }
+            Value::Null | Value::Undefined => Err(Error::with_kind(RuntimeErrorKind::NullishPropertyAccess {
+                base: object.to_string(),
+                property: name.to_string(),
+                is_write: false,
+            }, None)),
+            _ => Ok(builtins::object_method(name)
+                .map(|method| Value::Function(FunctionRef::NativeMethod(method)))
+                .unwrap_or(Value::Undefined)),
+        }
+    }
+    
+    /// Whether `key` is an own property of `object`, as `hasOwnProperty` sees it
+    pub fn has_own_property<K: AtomKey + ?Sized>(&self, object: &Value, key: &K) -> bool {
+        let name = key.as_str();
+        match object {
+            Value::Object(data) => data.has_own(key),
+            Value::String(s) => name == "length" || array_index(name).is_some_and(|index| index < s.encode_utf16().count()),
+            Value::Function(FunctionRef::Closure(closure)) => {
+                closure.properties.borrow().contains_key(key)
+                    || (name == "prototype" && !closure.function.is_resumable())
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => builtin.properties.borrow().contains_key(key),
+            _ => false,
+        }
+    }
+    
+    /// Whether `key` is an own or inherited property of `object` (the `in` operator)
+    ///
+    /// Built-in methods count as inherited, matching what `get_property` finds.
+    fn has_property<K: AtomKey + ?Sized>(&self, object: &Value, key: &K) -> bool {
+        let name = key.as_str();
+        if self.has_own_property(object, key) {
+            return true;
+        }
+        let method = match object {
+            Value::Object(data) => {
+                if data.has_property(key) {
+                    return true;
+                }
+                match &data.kind {
+                    ObjectKind::Array(_) => builtins::array_method(name),
+                    ObjectKind::Generator(_) => builtins::generator_method(name),
+                    ObjectKind::Promise(_) => promise::promise_method(name),
+                    _ => None,
+                }
+            }
+            Value::String(_) => builtins::string_method(name),
+            _ => None,
+        };
+        method.or_else(|| builtins::object_method(name)).is_some()
+    }
+    
+    /// The `delete` operator: remove an own property
+    fn delete_property<K: AtomKey + ?Sized>(&self, object: &Value, key: &K) -> Result<bool> {
+        let name = key.as_str();
+        let deleted = match object {
+            Value::Object(data) => data.delete(key),
+            Value::Function(FunctionRef::Closure(closure)) => {
+                if name == "prototype" && !closure.function.is_resumable() {
+                    false
+                } else {
+                    closure.properties.borrow_mut().delete(key)
+                }
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => builtin.properties.borrow_mut().delete(key),
+            Value::String(_) => !self.has_own_property(object, key),
+            Value::Null | Value::Undefined => {
+                return Err(Error::runtime(ErrorCode::NotObjectCoercible, "TypeError: Cannot convert undefined or null to object", None));
+            }
+            _ => true,
+        };
+        self.check_deletion(deleted, object, name)
+    }
+    
+    /// The result of `delete`: a property that could not be deleted makes
+    /// sloppy mode code return false and strict mode code throw
+    fn check_deletion(&self, deleted: bool, object: &Value, key: &str) -> Result<bool> {
+        if !deleted && self.is_strict() {
+            return Err(Error::runtime(
+                ErrorCode::CannotDelete,
+                format!("TypeError: Cannot delete property '{}' of {}", key, object_tag(object)),
+                None,
+            ));
+        }
+        Ok(deleted)
+    }
+    
+    /// Read a symbol-keyed property, own or inherited; primitives other
+    /// than null and undefined have none
+    pub(crate) fn get_symbol_property(&self, object: &Value, symbol: &Symbol) -> Result<Value> {
+        let value = match object {
+            Value::Object(data) => data.lookup_symbol(symbol),
+            Value::Null | Value::Undefined => return Err(Error::with_kind(RuntimeErrorKind::NullishPropertyAccess {
+                base: object.to_string(),
+                property: symbol.to_string(),
+                is_write: false,
+            }, None)),
+            _ => object.property_map().and_then(|properties| properties.borrow().get_symbol(symbol).cloned()),
+        };
+        Ok(value.unwrap_or(Value::Undefined))
+    }
+    
+    /// `get_property` for a computed key
+    fn get_keyed_property(&self, object: &Value, key: &PropertyKey) -> Result<Value> {
+        match key {
+            PropertyKey::String(key) => self.get_property(object, key),
+            PropertyKey::Symbol(symbol) => self.get_symbol_property(object, symbol),
+        }
+    }
+    
+    /// `set_property` for a computed key
+    fn set_keyed_property(&mut self, object: &Value, key: &PropertyKey, value: Value) -> Result<()> {
+        let symbol = match key {
+            PropertyKey::String(key) => return self.set_property(object, key, value),
+            PropertyKey::Symbol(symbol) => symbol,
+        };
+        let written = match object {
+            Value::Object(data) => data.set_symbol(symbol, value),
+            Value::Null | Value::Undefined => return Err(Error::with_kind(RuntimeErrorKind::NullishPropertyAccess {
+                base: object.to_string(),
+                property: symbol.to_string(),
+                is_write: true,
+            }, None)),
+            _ => match object.property_map() {
+                Some(properties) => properties.borrow_mut().assign_symbol(symbol, value),
+                None => return Ok(()),
+            },
+        };
+        self.check_assignment(written, object, &symbol.to_string())
+    }
+    
+    /// `has_property` for a computed key
+    fn has_keyed_property(&self, object: &Value, key: &PropertyKey) -> bool {
+        match (key, object) {
+            (PropertyKey::String(key), _) => self.has_property(object, key),
+            (PropertyKey::Symbol(symbol), Value::Object(data)) => data.has_symbol_property(symbol),
+            (PropertyKey::Symbol(symbol), _) => object.own_symbol_property(symbol).is_some(),
+        }
+    }
+    
+    /// `delete_property` for a computed key
+    fn delete_keyed_property(&self, object: &Value, key: &PropertyKey) -> Result<bool> {
+        let symbol = match key {
+            PropertyKey::String(key) => return self.delete_property(object, key),
+            PropertyKey::Symbol(symbol) => symbol,
+        };
+        if matches!(object, Value::Null | Value::Undefined) {
+            return Err(Error::runtime(ErrorCode::NotObjectCoercible, "TypeError: Cannot convert undefined or null to object", None));
+        }
+        let deleted = object.property_map().is_none_or(|properties| properties.borrow_mut().delete_symbol(symbol));
+        self.check_deletion(deleted, object, &symbol.to_string())
+    }
+    
+    /// JavaScript ToString, calling a user-defined `toString` where present
+    ///
+    /// Symbols are a TypeError; only their `Display` names them.
+    pub fn to_js_string(&mut self, value: &Value) -> Result<String> {
+        match value {
+            Value::Object(_) | Value::Function(_) => {
+                let primitive = self.to_primitive(value, PreferredType::String)?;
+                self.to_js_string(&primitive)
+            }
+            Value::Symbol(_) => Err(symbol::conversion_error("string")),
+            primitive => Ok(primitive.to_string()),
+        }
+    }
+    
+    /// JavaScript ToPropertyKey: the symbol or string a computed key names
+    ///
+    /// Every keyed access goes through here, so `obj[1]`, `obj[1.0]` and
+    /// `obj["1"]` reach the same property and `obj[0.1 + 0.2]` reaches
+    /// `"0.30000000000000004"`.
+    pub fn to_property_key(&mut self, key: &Value) -> Result<PropertyKey> {
+        match key {
+            Value::Symbol(symbol) => Ok(PropertyKey::Symbol(symbol.clone())),
+            key => match self.to_primitive(key, PreferredType::String)? {
+                Value::Symbol(symbol) => Ok(PropertyKey::Symbol(symbol)),
+                primitive => self.to_js_string(&primitive).map(PropertyKey::String),
+            },
+        }
+    }
+    
+    /// JavaScript ToPrimitive (spec: OrdinaryToPrimitive for objects)
+    ///
+    /// Tries `valueOf` and `toString` in the order given by `hint`. Own or
+    /// inherited properties shadow the built-in versions: the built-in `valueOf`
+    /// returns the object itself, so it never yields a primitive, and the
+    /// built-in `toString` joins arrays and names everything else.
+    pub fn to_primitive(&mut self, value: &Value, hint: PreferredType) -> Result<Value> {
+        if !matches!(value, Value::Object(_) | Value::Function(_)) {
+            return Ok(value.clone());
+        }
+        
+        let order = match hint {
+            PreferredType::String => ["toString", "valueOf"],
+            PreferredType::Default | PreferredType::Number => ["valueOf", "toString"],
+        };
+        for name in order {
+            let method = match value {
+                Value::Object(data) => data.lookup(name),
+                _ => None,
+            };
+            let result = match method {
+                Some(method @ Value::Function(_)) => {
+                    // Conversions happen mid-instruction, so keep the accumulator
+                    let saved = self.accumulator.clone();
+                    let result = self.call_function(&method, value.clone(), &[]);
+                    self.accumulator = saved;
+                    result?
+                }
+                // A non-callable property is skipped, as if absent
+                Some(_) => continue,
+                None if name == "toString" => Value::string(self.builtin_to_string(value)?),
+                None => continue,
+            };
+            if !matches!(result, Value::Object(_) | Value::Function(_)) {
+                return Ok(result);
+            }
+        }
+        Err(Error::runtime(ErrorCode::NotPrimitive, "TypeError: Cannot convert object to primitive value", None))
+    }
+    
+    /// The built-in `toString` of an object or function
+    fn builtin_to_string(&mut self, value: &Value) -> Result<String> {
+        let data = match value {
+            Value::Object(data) => data,
+            Value::Function(function) => return Ok(function_source_text(&self.function_name(function))),
+            primitive => return Ok(primitive.to_string()),
+        };
+        let elements = match data.array_elements() {
+            Some(elements) => elements.borrow().clone(),
+            None if data.generator_state().is_some() => return Ok("[object Generator]".to_string()),
+            None if data.promise_state().is_some() => return Ok("[object Promise]".to_string()),
+            None => return Ok("[object Object]".to_string()),
+        };
+        
+        // Array.prototype.join(","); a cyclic reference joins as ""
+        let ptr = Rc::as_ptr(data);
+        if self.joining.contains(&ptr) {
+            return Ok(String::new());
+        }
+        if self.joining.len() >= MAX_JOIN_DEPTH {
+            return Err(Error::runtime(ErrorCode::JoinNesting, "RangeError: Maximum array join depth exceeded", None));
+        }
+        self.joining.insert(ptr);
+        // A `toString` may remove later elements from the array
+        let parts: Result<Vec<String>> = self.with_roots(elements.iter().flatten(), |vm| {
+            elements.iter()
+                .map(|element| match element {
+                    None | Some(Value::Undefined) | Some(Value::Null) => Ok(String::new()),
+                    Some(element) => vm.to_js_string(element),
+                })
+                .collect()
+        });
+        self.joining.remove(&ptr);
+        Ok(parts?.join(","))
+    }
+    
+    /// Name of a function value, looking up bytecode functions by index
+    fn function_name(&self, function: &FunctionRef) -> String {
+        match function {
+            FunctionRef::Bytecode(index) => self.functions.get(*index)
+                .map(|function| function.name.clone())
+                .unwrap_or_default(),
+            other => other.name().to_string(),
+        }
+    }
+    
+    /// The object whose prototype `object.<name>` reads or writes: only
+    /// with `VMConfig::proto_setter`, only for `__proto__`, and only
+    /// where no own property of that name hides the accessor
+    fn proto_accessor_target(&self, object: &Value, name: &str) -> Option<Rc<ObjectData>> {
+        match object {
+            Value::Object(data) if self.proto_setter && name == "__proto__" && !data.has_own(name) => {
+                Some(data.clone())
+            }
+            _ => None,
+        }
+    }
+    
+    /// Write a property; writes to primitives are silently ignored
+    fn set_property<K: AtomKey + ?Sized>(&mut self, object: &Value, key: &K, value: Value) -> Result<()> {
+        let name = key.as_str();
+        match object {
+            Value::Object(data) => {
+                // An array length is converted with ToNumber, which may call `valueOf`
+                let value = if data.is_array() && name == "length" {
+                    self.with_roots([object], |vm| vm.to_primitive(&value, PreferredType::Number))?
+                } else {
+                    value
+                };
+                if let (Some(heap), Some(elements)) = (&mut self.heap, data.array_elements()) {
+                    let length = if name == "length" {
+                        Some(value.to_uint32() as usize).filter(|&length| length as f64 == value.to_number())
+                    } else {
+                        array_index(name).map(|index| index + 1)
+                    };
+                    let length = length.filter(|&length| length > elements.borrow().len() && length <= MAX_DENSE_ARRAY_LENGTH);
+                    if let Some(length) = length {
+                        heap.check_elements(data, length)?;
                     }
                 }
+                let before = self.heap_extent(data);
+                let written = data.set(key, value)?;
+                self.charge_growth(data, before)?;
+                self.check_assignment(written, object, name)
             }
+            Value::Function(FunctionRef::Closure(closure)) => {
+                let written = closure.properties.borrow_mut().assign(key, value);
+                self.check_assignment(written, object, name)
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => {
+                let written = builtin.properties.borrow_mut().assign(key, value);
+                self.check_assignment(written, object, name)
+            }
+            Value::Null | Value::Undefined => Err(Error::with_kind(RuntimeErrorKind::NullishPropertyAccess {
+                base: object.to_string(),
+                property: name.to_string(),
+                is_write: true,
+            }, None)),
+            _ => Ok(()),
         }
-        
-        Ok(())
     }
     
-    /// Handle function return
-    fn handle_return(&mut self) -> Result<bool> {
-        // Pop current frame
-        let current_frame = self.call_stack.pop()
-            .ok_or_else(|| Error::Runtime { message: "No frame to return from".to_string(), span: None })?;
-        
-        // Check if we're returning from main
-        if self.call_stack.is_empty() {
-            return Ok(false); // Signal to stop execution
-        }
-        
-        // Restore caller's context (if returning from a function call)
-        if let Some(return_address) = current_frame.return_address {
-            let frame = self.call_stack.current_frame_mut()
-                .ok_or_else(|| Error::Runtime { message: "No caller frame".to_string(), span: None })?;
-            frame.ip = return_address;
+    /// A write to a read-only property is ignored in sloppy mode code and
+    /// a TypeError in strict mode code
+    fn check_assignment(&self, written: bool, object: &Value, key: &str) -> Result<()> {
+        if !written && self.is_strict() {
+            return Err(Error::runtime(
+                ErrorCode::ReadOnlyProperty,
+                format!("TypeError: Cannot assign to read only property '{}' of {}", key, object_tag(object)),
+                None,
+            ));
         }
-        
-        Ok(true) // Continue execution
+        Ok(())
+    }
+    
+    /// Whether the running function is strict mode code
+    fn is_strict(&self) -> bool {
+        self.call_stack.current_frame().is_some_and(|frame| frame.function.is_strict)
     }
     
     /// Get a constant value from the current function's constant pool
-    fn get_constant(&self, idx: u16) -> Result<&ConstantValue> {
+    fn get_constant(&self, idx: ConstIndex) -> Result<&ConstantValue> {
         let frame = self.call_stack.current_frame()
-            .ok_or_else(|| Error::Runtime { message: "No active frame".to_string(), span: None })?;
+            .ok_or_else(|| Error::internal("No active frame"))?;
         frame.function.constants.get(idx)
-            .ok_or_else(|| Error::Runtime { message: format!("Invalid constant index: {}", idx), span: None })
+            .ok_or_else(|| Error::internal(format!("Invalid constant index: {}", idx)))
     }
     
-    /// Get a string constant from the pool
-    fn get_constant_string(&self, idx: u16) -> Result<String> {
-        match self.get_constant(idx)? {
-            ConstantValue::String(s) => Ok(s.clone()),
-            _ => Err(Error::Runtime { message: "Expected string constant".to_string(), span: None }),
+    /// Get a property name or string constant from the pool as an atom
+    ///
+    /// Property names resolve through the program's string table, which
+    /// is interned once per VM, so a named access does not hash its text.
+    fn get_constant_string(&mut self, idx: ConstIndex) -> Result<Atom> {
+        let frame = self.call_stack.current_frame()
+            .ok_or_else(|| Error::internal("No active frame"))?;
+        match frame.function.constants.get(idx) {
+            Some(ConstantValue::PropertyName(id)) => self.atoms.get(&frame.function.names, *id)
+                .ok_or_else(|| Error::internal(format!("Invalid property name: #{}", id))),
+            Some(ConstantValue::String(name)) => Ok(Atom::new(name)),
+            Some(_) => Err(Error::internal("Expected string constant")),
+            None => Err(Error::internal(format!("Invalid constant index: {}", idx))),
         }
     }
     
@@ -576,15 +2790,141 @@ impl VM {
             ConstantValue::Boolean(b) => Value::Boolean(*b),
             ConstantValue::Null => Value::Null,
             ConstantValue::Undefined => Value::Undefined,
+            ConstantValue::Regex { pattern, flags } => {
+                // No matcher yet: a regex literal evaluates to a fresh object
+                // carrying its source text and flags
+                ObjectBuilder::new()
+                    .prop("source", Value::string(pattern.clone()))
+                    .prop("flags", Value::string(flags.clone()))
+                    .build()
+            }
+            // Templates are copied all the way down, so no two evaluations
+            // of a literal share an object
+            ConstantValue::ObjectTemplate(properties) => {
+                let mut object = ObjectBuilder::new();
+                for (key, value) in properties {
+                    object = object.prop(key.clone(), self.constant_to_value(value)?);
+                }
+                object.build()
+            }
+            ConstantValue::ArrayTemplate(elements) => {
+                let elements = elements.iter()
+                    .map(|element| element.as_ref().map(|value| self.constant_to_value(value)).transpose())
+                    .collect::<Result<Vec<_>>>()?;
+                Value::Object(ObjectData::new_array(elements).into_rc())
+            }
             _ => Value::Undefined, // Other constant types not yet supported
         })
     }
     
-    /// Debug helper to print current instruction
-    fn debug_instruction(&self, instruction: &Bytecode, ip: usize) {
-        println!("[{:04}] {:?} | acc: {:?} | stack: {:?}", 
-                 ip, instruction, self.accumulator, self.stack);
+    /// The frame arena's object for the allocation being run, when it is
+    /// an arena site and `VMConfig::frame_arena` is on
+    ///
+    /// The object still holds whatever it held before; the caller resets it.
+    fn arena_object(&mut self, array: bool) -> Option<Rc<ObjectData>> {
+        if !self.frame_arena {
+            return None;
+        }
+        let frame = self.call_stack.current_frame()?;
+        let site = frame.function.arena_sites.binary_search(&frame.ip().checked_sub(1)?).ok()?;
+        self.call_stack.arena_object(site, array)
+    }
+    
+    /// Make `object` a fresh copy of template constant `idx`, as
+    /// `constant_to_value` would, reusing its storage
+    fn fill_from_template(&self, object: &ObjectData, idx: ConstIndex) -> Result<()> {
+        match self.get_constant(idx)? {
+            ConstantValue::ObjectTemplate(properties) => {
+                object.reset(properties.iter().map(|(key, _)| key.as_str()));
+                let mut map = object.properties.borrow_mut();
+                for (slot, (_, value)) in map.values_mut().zip(properties) {
+                    *slot = self.constant_to_value(value)?;
+                }
+            }
+            ConstantValue::ArrayTemplate(elements) => {
+                object.reset(std::iter::empty());
+                let storage = object.array_elements()
+                    .ok_or_else(|| Error::internal("Array arena site holds a non-array"))?;
+                let mut storage = storage.borrow_mut();
+                for element in elements {
+                    storage.push(element.as_ref().map(|value| self.constant_to_value(value)).transpose()?);
+                }
+            }
+            _ => return Err(Error::internal("CreateFromTemplate needs a template constant")),
+        }
+        Ok(())
+    }
+    
+    /// Write the trace line of the instruction about to run at `ip`
+    fn trace_instruction(&self, instruction: &Bytecode, ip: usize) {
+        self.debug_note(format_args!("[{:04}] {:?} | acc: {:?} | stack: {:?}",
+                 ip, instruction, self.accumulator, self.stack));
+    }
+}
+
+/// Run a host callback, turning a panic into a `NativeFunctionPanicked`
+/// error
+///
+/// The caller goes on as if the callback had returned the error, after
+/// unwinding any frames a call back into the VM left behind. The panic
+/// hook still runs and may print.
+fn catch_host_panic(name: &str, call: impl FnOnce() -> Result<Value>) -> Result<Value> {
+    panic::catch_unwind(AssertUnwindSafe(call)).unwrap_or_else(|payload| {
+        let kind = RuntimeErrorKind::NativeFunctionPanicked {
+            name: name.to_string(),
+            message: panic_message(payload.as_ref()).map(str::to_string),
+        };
+        Err(Error::with_kind(kind, None))
+    })
+}
+
+/// Whether `new` may be used on `value`: a built-in with a construct
+/// behaviour, or a closure that is not an arrow, generator or async
+/// function
+fn is_constructor(value: &Value) -> bool {
+    match value {
+        Value::Function(FunctionRef::Builtin(builtin)) => builtin.construct.is_some(),
+        Value::Function(FunctionRef::Closure(closure)) => !closure.function.is_resumable() && !closure.function.is_arrow,
+        _ => false,
+    }
+}
+
+/// The TypeError for `new` of something that is not a constructor,
+/// shown as `callee`
+fn not_constructor(callee: &str, span: Option<Span>) -> Error {
+    Error::runtime(ErrorCode::NotConstructor, format!("TypeError: {} is not a constructor", callee), span)
+}
+
+/// The `[object Tag]` form `Object.prototype.toString` gives a value
+fn object_tag(value: &Value) -> &'static str {
+    match value {
+        Value::Object(data) => match &data.kind {
+            ObjectKind::Array(_) => "[object Array]",
+            ObjectKind::Generator(_) => "[object Generator]",
+            ObjectKind::Promise(_) => "[object Promise]",
+            _ => "[object Object]",
+        },
+        Value::Function(_) => "[object Function]",
+        Value::Symbol(_) => "[object Symbol]",
+        Value::String(_) => "[object String]",
+        Value::Number(_) => "[object Number]",
+        Value::Boolean(_) => "[object Boolean]",
+        Value::Null => "[object Null]",
+        Value::Undefined => "[object Undefined]",
+    }
+}
+
+/// The state of a generator receiver, or a TypeError naming `method`
+fn generator_state<'a>(value: &'a Value, method: &str) -> Result<&'a RefCell<GeneratorState>> {
+    match value {
+        Value::Object(data) => data.generator_state(),
+        _ => None,
     }
+    .ok_or_else(|| Error::runtime(
+        ErrorCode::IncompatibleReceiver,
+        format!("TypeError: {} method called on incompatible receiver {}", method, value),
+        None,
+    ))
 }
 
 impl Default for VM {
@@ -593,12 +2933,25 @@ impl Default for VM {
     }
 }
 
+/// A for-of iterator over `value`: the generator itself, or a list
+/// iterator over an array or string
+fn iterator_for(value: &Value, consumer: IterationConsumer) -> Result<Value> {
+    match value {
+        Value::Object(data) if data.generator_state().is_some() => Ok(value.clone()),
+        Value::String(_) => Ok(Value::Object(ObjectData::new_list_iterator(value.clone()).into_rc())),
+        Value::Object(data) if data.is_array() => Ok(Value::Object(ObjectData::new_list_iterator(value.clone()).into_rc())),
+        _ => Err(not_iterable(value, consumer)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::bytecode::{Compiler};
     use crate::parser::{Parser};
     use crate::lexer::{Lexer};
+    use crate::vm::value::HostFunction;
+    use std::cell::Cell;
     
     fn compile_and_run(source: &str) -> Result<Value> {
         // Tokenize
@@ -618,6 +2971,38 @@ mod tests {
         vm.execute(bytecode)
     }
     
+    fn run_in(vm: &mut VM, source: &str) -> Result<Value> {
+        let tokens = Lexer::new(source).tokenize()?;
+        let ast = Parser::new(tokens).parse()?;
+        let bytecode = Compiler::new_main(source).compile(&ast)?;
+        vm.execute(bytecode)
+    }
+    
+    fn assert_number(source: &str, expected: f64) {
+        let result = compile_and_run(source).unwrap();
+        let expected = Value::Number(expected);
+        assert!(result.same_value(&expected), "{}: expected {}, got {:?}", source, expected, result);
+    }
+    
+    fn assert_number_in(vm: &mut VM, source: &str, expected: f64) {
+        let result = run_in(vm, source).unwrap();
+        assert!(result.same_value(&Value::Number(expected)), "{}: got {:?}", source, result);
+    }
+    
+    fn array_elements(value: &Value) -> Vec<Option<Value>> {
+        match value {
+            Value::Object(object) => object.array_elements()
+                .expect("expected an array")
+                .borrow()
+                .clone(),
+            other => panic!("expected an array, got {:?}", other),
+        }
+    }
+    
+    fn numbers(values: &[f64]) -> Vec<Option<Value>> {
+        values.iter().map(|&n| Some(Value::Number(n))).collect()
+    }
+    
     #[test]
     fn test_arithmetic() {
         assert_eq!(compile_and_run("2 + 3").unwrap(), Value::Number(5.0));
@@ -648,4 +3033,719 @@ mod tests {
         assert_eq!(compile_and_run("true && false").unwrap(), Value::Boolean(false));
         assert_eq!(compile_and_run("true || false").unwrap(), Value::Boolean(true));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_numeric_edge_cases() {
+        // Expected values verified against V8
+        let cases: &[(&str, f64)] = &[
+            ("1 % 0", f64::NAN),
+            ("-1 % 1", -0.0),
+            ("5.5 % 2", 1.5),
+            ("1 / 0", f64::INFINITY),
+            ("-1 / 0", f64::NEG_INFINITY),
+            ("0 / 0", f64::NAN),
+            ("(-1) ** 0.5", f64::NAN),
+            ("Math.pow(-1, 0.5)", f64::NAN),
+            ("Math.pow(0, 0)", 1.0),
+            ("Math.pow(2, -3)", 0.125),
+            ("Math.pow(1, 1 / 0)", f64::NAN),
+            ("Math.pow()", f64::NAN),
+            ("0 ** 0", 1.0),
+            ("2 ** 3 ** 2", 512.0),
+            ("(-2) ** 2", 4.0),
+            ("-(2 ** 2)", -4.0),
+            ("2 ** -3", 0.125),
+            ("1 ** (1 / 0)", f64::NAN),
+            ("(-1) ** (-1 / 0)", f64::NAN),
+            ("(0 / 0) ** 0", 1.0),
+            ("1 ** (0 / 0)", f64::NAN),
+            ("(-0) * 5", -0.0),
+            ("(1 << 31) | 0", -2147483648.0),
+            ("4294967295 >>> 0", 4294967295.0),
+            ("-1 >>> 0", 4294967295.0),
+            ("(0 / 0) | 0", 0.0),
+            ("(1 / 0) >> 1", 0.0),
+            ("4294967296 | 0", 0.0),
+            ("2147483648 | 0", -2147483648.0),
+            ("1e21 | 0", -559939584.0),
+            ("-2.7 | 0", -2.0),
+            ("1 << 32", 1.0),
+            ("-16 >> 2", -4.0),
+            ("6 & 3", 2.0),
+            ("6 ^ 3", 5.0),
+            ("~5", -6.0),
+        ];
+        
+        for (source, expected) in cases {
+            assert_number(source, *expected);
+        }
+    }
+    
+    #[test]
+    fn test_object_is_tells_zeros_apart() {
+        let cases = [
+            ("Object.is((-0) * 5, -0)", true),
+            ("Object.is((-0) * 5, 0)", false),
+            ("(-0) * 5 === 0", true),
+            ("Object.is(0 / 0, 0 / 0)", true),
+            ("Object.is('a', 'a')", true),
+            ("Object.is(1, '1')", false),
+            ("let o = {}; Object.is(o, o) && !Object.is(o, {})", true),
+            ("Object.is()", true),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(compile_and_run(source).unwrap(), Value::Boolean(expected), "{}", source);
+        }
+    }
+    
+    #[test]
+    fn test_completion_values() {
+        let prefix = "let a = 40; a + 2; ";
+        let cases: &[(&str, Value)] = &[
+            ("", Value::Number(42.0)),
+            ("a", Value::Number(40.0)),
+            ("a + 1;", Value::Number(41.0)),
+            ("let b = a;", Value::Undefined),
+            ("let b = a + 2;", Value::Undefined),
+            ("var c;", Value::Undefined),
+            ("const d = 'x';", Value::Undefined),
+            (";", Value::Undefined),
+            ("a; ;", Value::Undefined),
+            ("{ let e = 1; }", Value::Undefined),
+            ("function f() { return 1; }", Value::Undefined),
+            ("if (a) { a; }", Value::Undefined),
+            ("while (false) { a; }", Value::Undefined),
+            ("'done'", Value::string("done")),
+        ];
+        
+        for (suffix, expected) in cases {
+            let source = format!("{}{}", prefix, suffix);
+            assert_eq!(&compile_and_run(&source).unwrap(), expected, "program: {}", source);
+        }
+        
+        assert_eq!(compile_and_run("").unwrap(), Value::Undefined);
+        assert_eq!(compile_and_run("let x = 42;").unwrap(), Value::Undefined);
+    }
+    
+    #[test]
+    fn test_string_comparison() {
+        assert_eq!(compile_and_run("'a' < 'b'").unwrap(), Value::Boolean(true));
+        assert_eq!(compile_and_run("'10' < '9'").unwrap(), Value::Boolean(true));
+        assert_eq!(compile_and_run("'B' < 'a'").unwrap(), Value::Boolean(true));
+        assert_eq!(compile_and_run("'abc' <= 'ab'").unwrap(), Value::Boolean(false));
+        assert_eq!(compile_and_run("'ab' >= 'ab'").unwrap(), Value::Boolean(true));
+        assert_eq!(compile_and_run("'10' < 9").unwrap(), Value::Boolean(false));
+        assert_eq!(compile_and_run("'x' > 1").unwrap(), Value::Boolean(false));
+    }
+    
+    #[test]
+    fn test_functions_and_closures() {
+        assert_number("function add(a, b) { return a + b; } add(5, 3)", 8.0);
+        assert_number("let twice = function (x) { return x * 2; }; twice(21)", 42.0);
+        assert_number(
+            "function counter() { let n = 0; return function () { n = n + 1; return n; }; }
+             let c = counter(); c(); c()",
+            2.0,
+        );
+        assert!(compile_and_run("let x = 1; x()").is_err());
+    }
+    
+    #[test]
+    fn test_array_sort_default_order() {
+        // Default comparator compares string forms, not numbers
+        let result = compile_and_run("[10, 9, 1].sort()").unwrap();
+        assert_eq!(array_elements(&result), numbers(&[1.0, 10.0, 9.0]));
+        
+        let result = compile_and_run("['b', 'a', 'B', 'aa'].sort()").unwrap();
+        let expected: Vec<_> = ["B", "a", "aa", "b"].iter().map(|s| Some(Value::string(*s))).collect();
+        assert_eq!(array_elements(&result), expected);
+        
+        // Sorting is in place and returns the same array
+        assert_eq!(compile_and_run("let a = [2, 1]; a.sort() === a").unwrap(), Value::Boolean(true));
+    }
+    
+    #[test]
+    fn test_array_sort_with_comparator() {
+        let result = compile_and_run("[1, 3, 2].sort(function (a, b) { return b - a; })").unwrap();
+        assert_eq!(array_elements(&result), numbers(&[3.0, 2.0, 1.0]));
+        
+        let result = compile_and_run("[10, 9, 1].sort(function (a, b) { return a - b; })").unwrap();
+        assert_eq!(array_elements(&result), numbers(&[1.0, 9.0, 10.0]));
+        
+        // Stable: equal elements keep their relative order
+        let result = compile_and_run(
+            "['bb', 'a', 'cc', 'd'].sort(function (x, y) { return x.length - y.length; })"
+        ).unwrap();
+        let expected: Vec<_> = ["a", "d", "bb", "cc"].iter().map(|s| Some(Value::string(*s))).collect();
+        assert_eq!(array_elements(&result), expected);
+        
+        // NaN results are treated as "equal"
+        let result = compile_and_run("[2, 1, 3].sort(function () { return 0 / 0; })").unwrap();
+        assert_eq!(array_elements(&result), numbers(&[2.0, 1.0, 3.0]));
+        
+        assert!(compile_and_run("[2, 1].sort(1)").is_err());
+    }
+    
+    #[test]
+    fn test_array_sort_undefined_and_holes() {
+        let result = compile_and_run("[3, undefined, , 1].sort()").unwrap();
+        assert_eq!(
+            array_elements(&result),
+            vec![Some(Value::Number(1.0)), Some(Value::Number(3.0)), Some(Value::Undefined), None]
+        );
+        
+        // The comparator never sees undefined or holes
+        let result = compile_and_run(
+            "[, undefined, 2, 1].sort(function (a, b) { return a.length + b.length; })"
+        ).unwrap();
+        assert_eq!(array_elements(&result).len(), 4);
+    }
+    
+    #[test]
+    fn test_locale_compare() {
+        assert_number("'a'.localeCompare('b')", -1.0);
+        assert_number("'b'.localeCompare('a')", 1.0);
+        assert_number("'a'.localeCompare('a')", 0.0);
+        assert_number("'Z'.localeCompare('a')", -1.0);
+        
+        let result = compile_and_run(
+            "['pear', 'apple', 'fig'].sort(function (a, b) { return a.localeCompare(b); })"
+        ).unwrap();
+        let expected: Vec<_> = ["apple", "fig", "pear"].iter().map(|s| Some(Value::string(*s))).collect();
+        assert_eq!(array_elements(&result), expected);
+    }
+    
+    #[test]
+    fn test_read_only_globals_sloppy_mode() {
+        // Writes are silently ignored
+        assert_eq!(compile_and_run("undefined = 1; undefined").unwrap(), Value::Undefined);
+        assert_number("NaN = 1; NaN", f64::NAN);
+        assert_number("Infinity = 0; Infinity", f64::INFINITY);
+        assert_eq!(compile_and_run("console = null; console").unwrap().type_of(), "object");
+        
+        // Script-created globals stay writable
+        assert_number("x = 1; x = 2; x", 2.0);
+    }
+    
+    #[test]
+    fn test_read_only_globals_strict_mode() {
+        for target in ["undefined", "NaN", "console"] {
+            let source = format!("'use strict'; {} = 1;", target);
+            let err = compile_and_run(&source).unwrap_err();
+            assert_eq!(err.code(), ErrorCode::ReadOnlyProperty, "{}", source);
+            assert!(err.to_string().contains("read only"), "{}: {}", source, err);
+        }
+        
+        // Strictness comes from the function's own directive prologue too
+        assert!(compile_and_run("function f() { 'use strict'; NaN = 1; } f()").is_err());
+        assert!(compile_and_run("function f() { NaN = 1; } f()").is_ok());
+        // A directive only counts at the start
+        assert!(compile_and_run("let a = 1; 'use strict'; NaN = 1;").is_ok());
+    }
+    
+    #[test]
+    fn test_global_slot_flags() {
+        let mut vm = VM::new();
+        vm.set_builtins_writable(true);
+        assert_number_in(&mut vm, "'use strict'; console = 5; console", 5.0);
+        // Value properties stay read-only even when built-ins are relaxed
+        assert!(run_in(&mut vm, "'use strict'; NaN = 1;").is_err());
+        
+        let mut vm = VM::new();
+        vm.define_global("VERSION", GlobalSlot::read_only(Value::Number(3.0)));
+        vm.define_global("counter", GlobalSlot::new(Value::Number(0.0)));
+        assert!(run_in(&mut vm, "'use strict'; VERSION = 4;").is_err());
+        assert_number_in(&mut vm, "counter = counter + 1; counter", 1.0);
+        assert_eq!(vm.get_global("VERSION"), Some(&Value::Number(3.0)));
+    }
+    
+    #[test]
+    fn test_host_functions() {
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let add = HostFunction::new("add", move |cx: &mut CallContext| {
+            counter.set(counter.get() + 1);
+            match cx.args {
+                [Value::Number(a), Value::Number(b)] => Ok(Value::Number(a + b)),
+                _ => Err(Error::host("add expects two numbers")),
+            }
+        });
+        
+        let mut vm = VM::new();
+        vm.define_global("add", GlobalSlot::new(Value::Function(FunctionRef::Host(Rc::new(add)))));
+        assert_number_in(&mut vm, "add(1, add(2, 3))", 6.0);
+        assert_eq!(calls.get(), 2);
+        assert_eq!(run_in(&mut vm, "'' + add").unwrap(), Value::string("function add() { [native code] }"));
+        
+        let err = run_in(&mut vm, "add('1')").unwrap_err();
+        assert_eq!(err.code(), ErrorCode::HostError);
+        assert!(err.to_string().contains("add expects two numbers"), "{}", err);
+    }
+    
+    #[test]
+    fn test_host_functions_get_the_vm_and_this() {
+        let twice = HostFunction::new("twice", |cx: &mut CallContext| {
+            let callback = cx.arg(0);
+            let first = cx.vm.call_function(&callback, Value::Undefined, &[Value::Number(1.0)])?;
+            cx.vm.call_function(&callback, Value::Undefined, &[first])
+        });
+        let own_name = HostFunction::new("ownName", |cx: &mut CallContext| {
+            let this = cx.this.clone();
+            cx.vm.get_property(&this, "name")
+        });
+        
+        let mut vm = VM::new();
+        vm.define_global("twice", GlobalSlot::new(Value::Function(FunctionRef::Host(Rc::new(twice)))));
+        vm.define_global("ownName", GlobalSlot::new(Value::Function(FunctionRef::Host(Rc::new(own_name)))));
+        assert_number_in(&mut vm, "twice(x => x * 10)", 100.0);
+        assert_eq!(run_in(&mut vm, "({ name: 'box', ownName }).ownName()").unwrap(), Value::string("box"));
+        
+        // Errors thrown by the callback come back through the host function
+        let err = run_in(&mut vm, "twice(() => missing)").unwrap_err();
+        assert!(err.to_string().contains("missing is not defined"), "{}", err);
+        assert_number_in(&mut vm, "twice(x => x + 1)", 3.0);
+    }
+    
+    #[test]
+    fn test_sampling_profile() {
+        fn sample_now(cx: &mut CallContext) -> Result<Value> {
+            cx.vm.sample();
+            Ok(Value::Undefined)
+        }
+        
+        let mut vm = VM::new();
+        assert!(vm.profile_report().is_none());
+        vm.enable_profiling(ProfileMode::Sampling);
+        vm.define_global("sample", GlobalSlot::new(Value::Function(FunctionRef::NativeMethod(sample_now))));
+        run_in(&mut vm, "function f() {\n sample();\n}\nf(); f();\nsample();").unwrap();
+        
+        let report = vm.profile_report().unwrap();
+        assert_eq!(report.total_hits(), 3);
+        assert_eq!((report.lines[0].function.as_str(), report.lines[0].line, report.lines[0].hits), ("f", Some(2), 2));
+        assert_eq!((report.lines[1].function.as_str(), report.lines[1].line), ("<main>", Some(5)));
+    }
+    
+    fn own_properties(value: &Value) -> Vec<(String, Value)> {
+        match value {
+            Value::Object(object) => object.properties.borrow()
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+            other => panic!("expected an object, got {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_if_else() {
+        assert_number("let x = 0; if (1 < 2) { x = 1; } else { x = 2; } x", 1.0);
+        assert_number("let x = 0; if (1 > 2) { x = 1; } else { x = 2; } x", 2.0);
+        assert_number("let x = 5; if (x === 0) x = 1; x", 5.0);
+        assert_number("let f = function (n) { if (n < 0) return -1; else if (n > 0) return 1; return 0; }; f(-3) + f(0) * 10 + f(8) * 100", 99.0);
+    }
+    
+    #[test]
+    fn test_loops_with_break_and_continue() {
+        assert_number("let i = 0; let sum = 0; while (i < 10) { i = i + 1; if (i % 2 === 0) continue; sum = sum + i; } sum", 25.0);
+        assert_number("let n = 0; while (true) { n = n + 1; if (n === 7) break; } n", 7.0);
+        assert_number("let sum = 0; for (let i = 0; i < 5; i = i + 1) { if (i === 3) continue; sum = sum + i; } sum", 7.0);
+        assert_number("let sum = 0; for (let i = 0; ; i = i + 1) { if (i > 4) break; sum = sum + i; } sum", 10.0);
+        // Nested loops break out of the innermost one only
+        assert_number("let count = 0; for (let i = 0; i < 3; i = i + 1) { for (let j = 0; j < 10; j = j + 1) { if (j === 2) break; count = count + 1; } } count", 6.0);
+    }
+    
+    #[test]
+    fn test_nfc_equivalent_identifiers_share_a_binding() {
+        assert_number("let caf\u{e9} = 1; cafe\u{301} = cafe\u{301} + 1; caf\u{e9}", 2.0);
+    }
+    
+    #[test]
+    fn test_regex_literal_value() {
+        let result = compile_and_run("let r = /a+b/gi; r.source + ':' + r.flags").unwrap();
+        assert_eq!(result, Value::string("a+b:gi".to_string()));
+        
+        // Each evaluation creates a new object
+        let result = compile_and_run("let f = function () { return /x/; }; f() === f()").unwrap();
+        assert_eq!(result, Value::Boolean(false));
+    }
+    
+    #[test]
+    fn test_object_spread() {
+        let result = compile_and_run("let merged = { a: 1, ...{ a: 2, b: 3 }, c: 4 }; merged").unwrap();
+        assert_eq!(own_properties(&result), vec![
+            ("a".to_string(), Value::Number(2.0)),
+            ("b".to_string(), Value::Number(3.0)),
+            ("c".to_string(), Value::Number(4.0)),
+        ]);
+        
+        // Later literal properties override spread ones
+        assert_number("let defaults = { x: 1, y: 2 }; let o = { ...defaults, y: 5 }; o.x + o.y", 6.0);
+        
+        // null and undefined sources are ignored
+        let result = compile_and_run("({ ...null, a: 1, ...undefined })").unwrap();
+        assert_eq!(own_properties(&result), vec![("a".to_string(), Value::Number(1.0))]);
+        
+        // Spreading copies, it does not alias
+        assert_eq!(compile_and_run("let src = {}; let copy = { ...src }; copy === src").unwrap(), Value::Boolean(false));
+        
+        // Arrays and strings spread their indices
+        assert_eq!(compile_and_run("({ ...['x', 'y'] })[1]").unwrap(), Value::string("y"));
+        assert_eq!(compile_and_run("({ ...'hi' })[0]").unwrap(), Value::string("h"));
+    }
+    
+    #[test]
+    fn test_assignment_expressions() {
+        assert_number("a = b = 3; a + b", 6.0);
+        assert_number("let a; let b; a = b = 3; a + b", 6.0);
+        assert_number("let x; let y = (x = 5) + 1; y", 6.0);
+        assert_number("let y = (x = 5) + 1; y + x", 11.0);
+        assert_number("function f() { let n; return (n = 4) * n; } f()", 16.0);
+        assert_number("let c = 0; function inc() { return c = c + 1; } inc(); inc()", 2.0);
+        
+        // Member assignment leaves the assigned value too
+        assert_number("let o = {}; let v = (o.x = 7); v + o.x", 14.0);
+        assert_number("let o = {}; o['k'] = o.j = 2; o.k + o.j", 4.0);
+        assert_number("let arr = [1, 2]; arr[5] = 9; arr.length", 6.0);
+        assert_number("let o = { n: 1 }; o.n += 2; o.n", 3.0);
+    }
+    
+    #[test]
+    fn test_composite_to_string() {
+        let cases = [
+            // Arrays join with commas; null, undefined and holes are empty
+            ("'' + [1, 2, 3]", "1,2,3"),
+            ("'' + []", ""),
+            ("'' + [1, [2, 3]]", "1,2,3"),
+            ("'' + [null, undefined, 4]", ",,4"),
+            ("let a = [1]; a[2] = 3; '' + a", "1,,3"),
+            ("let a = [1]; a[1] = a; '' + a", "1,"),
+            ("[1, 2] + [3]", "1,23"),
+            ("[5] + 1", "51"),
+            // Plain objects
+            ("'' + {}", "[object Object]"),
+            ("({}) + 1", "[object Object]1"),
+            ("'' + [{}]", "[object Object]"),
+            // An own toString is called, valueOf takes precedence for +
+            ("let o = { toString: function () { return 'custom'; } }; 'x' + o", "xcustom"),
+            ("let o = { valueOf: function () { return 42; }, toString: function () { return 'no'; } }; '' + o", "42"),
+            ("let o = { toString: function () { return 'in'; } }; '' + [o, o]", "in,in"),
+            // Inherited methods count too
+            ("class A { toString() { return 'A'; } } '' + new A()", "A"),
+            ("let p = { toString: function () { return 'p'; } }; '' + Object.create(p)", "p"),
+            ("let p = { valueOf: function () { return 'v'; } }; Object.create(p) + 1", "v1"),
+            ("class K { toString() { return 'key'; } } let o = { key: 'v' }; o[new K()]", "v"),
+            // Functions render a name-based placeholder
+            ("function f() {} '' + f", "function f() { [native code] }"),
+            ("'' + function () {}", "function () { [native code] }"),
+            // Computed property keys
+            ("let o = {}; o[[1, 2]] = 'v'; o['1,2']", "v"),
+            ("let o = { '[object Object]': 'hit' }; o[{}]", "hit"),
+            ("let k = { toString: function () { return 'key'; } }; let o = { [k]: 'v' }; o.key", "v"),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(compile_and_run(source).unwrap(), Value::string(expected), "{}", source);
+        }
+        
+        // Composites that still add numerically
+        assert_number("let o = { valueOf: function () { return 2; } }; o + 1", 3.0);
+        assert_number("class V { valueOf() { return 42; } } new V() + 1", 43.0);
+        
+        // Without a callable that yields a primitive the conversion fails
+        for source in [
+            "let o = { toString: function () { return {}; } }; '' + o",
+            "let o = { toString: 7 }; '' + o",
+        ] {
+            let error = compile_and_run(source).unwrap_err();
+            assert_eq!(error.code(), ErrorCode::NotPrimitive, "{}", source);
+            assert!(error.to_string().contains("Cannot convert object to primitive value"), "{}", source);
+        }
+        
+        // Arrays nested past the join depth fail instead of overflowing
+        let nested = |depth: usize| format!("let a = [1]; for (let i = 0; i < {}; i++) {{ a = [a]; }} '' + a", depth);
+        assert_eq!(compile_and_run(&nested(MAX_JOIN_DEPTH - 1)).unwrap(), Value::string("1"));
+        let error = compile_and_run(&nested(20000)).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::JoinNesting);
+        assert!(error.to_string().contains("RangeError"), "{}", error);
+        
+        // Display never calls user code, so it joins any depth
+        let value = compile_and_run("let a = [1]; for (let i = 0; i < 20000; i++) { a = [a, []]; } a").unwrap();
+        assert_eq!(value.to_string(), format!("1{}", ",".repeat(20000)));
+    }
+    
+    #[test]
+    fn test_to_boolean() {
+        let cases = [
+            ("!!0", false), ("!!-0", false), ("!!NaN", false), ("!!''", false),
+            ("!!null", false), ("!!undefined", false), ("!!false", false),
+            ("!!'0'", true), ("!!' '", true), ("!!{}", true), ("!![]", true),
+            ("!!-1", true), ("!!Infinity", true), ("!!function () {}", true),
+            ("!!!0", true), ("!!!''", true), ("!!!!{}", true),
+        ];
+        for (source, expected) in cases {
+            assert_eq!(compile_and_run(source).unwrap(), Value::Boolean(expected), "{}", source);
+        }
+    }
+    
+    const RANGE: &str = "function* range(n) { for (let i = 0; i < n; i = i + 1) { yield i; } } ";
+    
+    #[test]
+    fn test_generator_suspends_across_loop_iterations() {
+        // Each next() resumes inside the loop with `i` intact
+        assert_number(&format!("{}let sum = 0; for (let x of range(5)) {{ sum = sum + x; }} sum", RANGE), 10.0);
+        assert_number(
+            &format!("{}let g = range(3); g.next().value * 100 + g.next().value * 10 + g.next().value", RANGE),
+            12.0,
+        );
+        
+        // The body does not start until the first next()
+        let mut vm = VM::new();
+        assert_number_in(&mut vm, "let log = []; function* g() { log[log.length] = 'ran'; yield 1; } let it = g(); log.length", 0.0);
+        
+        // Values sent to next() become the result of the pending yield
+        let source = "function* echo() { let a = yield 1; let b = yield a * 2; return a + b; } \
+            let e = echo(); [e.next().value, e.next(5).value, e.next(10).value, e.next().done]";
+        let result = compile_and_run(source).unwrap();
+        let expected = vec![
+            Some(Value::Number(1.0)), Some(Value::Number(10.0)),
+            Some(Value::Number(15.0)), Some(Value::Boolean(true)),
+        ];
+        assert_eq!(array_elements(&result), expected);
+    }
+    
+    #[test]
+    fn test_generator_next_results_and_early_return() {
+        let result = compile_and_run(&format!("{}let g = range(2); [g.next(), g.next(), g.next()]", RANGE)).unwrap();
+        let results: Vec<Vec<(String, Value)>> = array_elements(&result).iter()
+            .map(|element| own_properties(element.as_ref().unwrap()))
+            .collect();
+        let step = |value: Value, done: bool| vec![
+            ("value".to_string(), value),
+            ("done".to_string(), Value::Boolean(done)),
+        ];
+        assert_eq!(results, vec![
+            step(Value::Number(0.0), false),
+            step(Value::Number(1.0), false),
+            step(Value::Undefined, true),
+        ]);
+        
+        // return() finishes the generator without running the rest of the body
+        let source = format!("{}let g = range(10); g.next(); let r = g.return(42); \
+            [r.value, r.done, g.next().done, g.next().value]", RANGE);
+        let result = compile_and_run(&source).unwrap();
+        assert_eq!(array_elements(&result), vec![
+            Some(Value::Number(42.0)), Some(Value::Boolean(true)),
+            Some(Value::Boolean(true)), Some(Value::Undefined),
+        ]);
+        
+        // Breaking out of for-of closes the generator
+        assert_eq!(
+            compile_and_run(&format!("{}let g = range(10); for (let x of g) {{ if (x === 2) break; }} g.next().done", RANGE)).unwrap(),
+            Value::Boolean(true)
+        );
+        
+        // A generator that threw is finished
+        let mut vm = VM::new();
+        assert!(run_in(&mut vm, "function* bad() { yield 1; null.x; } b = bad(); b.next(); b.next()").is_err());
+        assert_eq!(run_in(&mut vm, "b.next().done").unwrap(), Value::Boolean(true));
+        
+        // Re-entering a running generator is a TypeError
+        let error = compile_and_run("let g; function* self() { g.next(); yield 1; } g = self(); g.next()").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::GeneratorRunning);
+        assert!(error.to_string().contains("Generator is already running"), "{}", error);
+    }
+    
+    #[test]
+    fn test_for_of_arrays_and_strings() {
+        assert_number("let sum = 0; for (const x of [1, 2, 3]) sum = sum + x; sum", 6.0);
+        assert_number("let n = 0; for (let x of [1, , 3]) { if (x === undefined) n = n + 1; } n", 1.0);
+        // Elements pushed during the loop are visited
+        assert_number("let a = [1]; let n = 0; for (let x of a) { n = n + 1; if (x < 3) a[a.length] = x + 1; } n", 3.0);
+        assert_number("let sum = 0; for (let x of [1, 2, 3, 4]) { if (x % 2) continue; sum = sum + x; } sum", 6.0);
+        
+        // Strings iterate by code point
+        assert_eq!(
+            compile_and_run("let s = ''; for (let c of 'a\u{1F600}b') s = s + '[' + c + ']'; s").unwrap(),
+            Value::string("[a][\u{1F600}][b]")
+        );
+        
+        // An existing variable can be the target
+        assert_number("let last = 0; for (last of [7, 8, 9]) {} last", 9.0);
+        
+        let error = compile_and_run("for (let x of 5) {}").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotIterable);
+        assert!(error.to_string().contains("5 is not iterable"), "{}", error);
+    }
+    
+    const POINT: &str = "class Point { \
+        constructor(x, y) { this.x = x; this.y = y; } \
+        sum() { return this.x + this.y; } \
+        static origin() { return new Point(0, 0); } \
+    } ";
+    
+    #[test]
+    fn test_class_instances_and_methods() {
+        assert_number(&format!("{}let p = new Point(3, 4); p.sum()", POINT), 7.0);
+        assert_number(&format!("{}Point.origin().sum()", POINT), 0.0);
+        let result = compile_and_run(&format!("{}let p = new Point(1, 2); [p instanceof Point, {{}} instanceof Point, p.constructor === Point]", POINT)).unwrap();
+        assert_eq!(array_elements(&result), vec![
+            Some(Value::Boolean(true)), Some(Value::Boolean(false)), Some(Value::Boolean(true)),
+        ]);
+        // Methods live on the prototype and are shared
+        assert_eq!(
+            compile_and_run(&format!("{}new Point(1, 2).sum === Point.prototype.sum", POINT)).unwrap(),
+            Value::Boolean(true)
+        );
+        
+        // Class expressions, and a constructor that returns an object
+        assert_number("const C = class Named { get() { return 7; } }; new C().get()", 7.0);
+        assert_number("class W { constructor() { return { w: 5 }; } } new W().w", 5.0);
+        
+        // Plain functions are constructors too
+        assert_number("function F(a) { this.a = a; } F.prototype.twice = function () { return this.a * 2; }; new F(21).twice()", 42.0);
+    }
+    
+    #[test]
+    fn test_class_errors() {
+        let error = compile_and_run(&format!("{}Point(1, 2)", POINT)).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotConstructor);
+        assert!(error.to_string().contains("Class constructor Point cannot be invoked without 'new'"), "{}", error);
+        
+        let error = compile_and_run("new 5").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotConstructor);
+        assert!(error.to_string().contains("5 is not a constructor"), "{}", error);
+        
+        let error = compile_and_run("({}) instanceof 1").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidInstanceof);
+        assert!(error.to_string().contains("Right-hand side of 'instanceof' is not callable"), "{}", error);
+        
+        // Class bodies are strict
+        assert!(compile_and_run("class S { m() { NaN = 1; } } new S().m()").is_err());
+        assert!(compile_and_run("function f() { NaN = 1; } f()").is_ok());
+    }
+    
+    #[test]
+    fn test_increment_and_decrement() {
+        // Postfix operators yield the old value, converted to a number
+        assert_number("let x = 5; let y = x++; y * 10 + x", 56.0);
+        assert_number("let x = 5; let y = x--; y * 10 + x", 54.0);
+        assert_number("let s = '7'; let old = s++; old + s", 15.0);
+        assert_number("let x = 0.1; x++; x--; x", 0.1 + 1.0 - 1.0);
+        assert_number("let x = 3; x -= 1; x", 2.0);
+        
+        // Captured and global variables
+        assert_number("let n = 0; function bump() { n++; } bump(); bump(); n", 2.0);
+        assert_number("g = 1; g++; g", 2.0);
+        
+        assert_eq!(compile_and_run("let s = 'a'; s += 1; s").unwrap(), Value::string("a1"));
+        assert!(compile_and_run("let o = {}; o.n++").is_err());
+    }
+    
+    #[test]
+    fn test_pop_on_an_empty_stack_is_an_error() {
+        // An unbalanced function must fail, not read undefined
+        let mut function = BytecodeFunction::new("unbalanced".to_string(), 0, 0, 0);
+        function.add_instruction(Bytecode::LdaSmi(1));
+        function.add_instruction(Bytecode::Pop);
+        function.add_instruction(Bytecode::Return);
+        let error = VM::new().execute(function).unwrap_err();
+        assert_eq!(error.kind(), Some(&RuntimeErrorKind::Internal { message: "Stack underflow in Pop".to_string() }));
+        assert_eq!(error.code(), ErrorCode::Internal);
+    }
+    
+    #[test]
+    fn test_call_arity_follows_js() {
+        // Missing arguments are undefined, extra ones are ignored
+        assert_eq!(compile_and_run("function f(a, b) { return b; } f(1)").unwrap(), Value::Undefined);
+        assert_number("function f(a, b) { return a + b; } f(1, 2, 3)", 3.0);
+        assert_number("let o = { m(a) { return a; } }; o.m(4, 5, 6)", 4.0);
+    }
+    
+    #[test]
+    fn test_delete_in_and_has_own_property() {
+        let is_true = |source: &str| {
+            assert_eq!(compile_and_run(source).unwrap(), Value::Boolean(true), "{}", source);
+        };
+        
+        // Deleting an own property removes it for reads, `in` and hasOwnProperty alike
+        is_true("let o = { a: 1 }; delete o.a && !('a' in o) && !o.hasOwnProperty('a') && o.a === undefined");
+        is_true("let o = { a: 1 }; let k = 'a'; delete o[k] && !('a' in o)");
+        is_true("let o = { a: 1 }; delete o.a; o.a = 2; 'a' in o && o.hasOwnProperty('a') && o.a === 2");
+        
+        // Missing and inherited properties delete trivially and stay inherited
+        is_true("let o = {}; delete o.missing");
+        is_true("class P { m() {} } let p = new P(); delete p.m && 'm' in p && !p.hasOwnProperty('m')");
+        is_true("let o = {}; 'hasOwnProperty' in o && !o.hasOwnProperty('hasOwnProperty')");
+        
+        // Array elements become holes; `length` cannot be deleted
+        is_true("let a = [1, 2, 3]; delete a[1] && !(1 in a) && a.length === 3 && a[1] === undefined");
+        is_true("let a = [1]; !(delete a.length) && 'length' in a && a.hasOwnProperty(0)");
+        is_true("'sort' in [] && ![].hasOwnProperty('sort')");
+        
+        // Primitives and other operands
+        is_true("'abc'.hasOwnProperty('length') && 'abc'.hasOwnProperty(2) && !'abc'.hasOwnProperty(3)");
+        is_true("delete 5 && !(delete 'abc'.length)");
+        is_true("function f() {} !(delete f.prototype) && 'prototype' in f && f.hasOwnProperty('prototype')");
+        
+        // Enumeration no longer yields deleted keys, and order is kept
+        let result = compile_and_run("let o = { a: 1, b: 2, c: 3 }; delete o.b; o.d = 4; delete o.a; o").unwrap();
+        let Value::Object(data) = result else { panic!("expected an object, got {:?}", result) };
+        let keys: Vec<String> = data.properties.borrow().keys().map(|key| key.to_string()).collect();
+        assert_eq!(keys, vec!["c", "d"]);
+        
+        let error = compile_and_run("'use strict'; delete [].length").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::CannotDelete);
+        assert!(error.to_string().contains("Cannot delete property 'length' of [object Array]"), "{}", error);
+        let error = compile_and_run("let u; delete u.x").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotObjectCoercible);
+        assert!(error.to_string().contains("Cannot convert undefined or null to object"), "{}", error);
+        let error = compile_and_run("'x' in 5").unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidInOperand);
+        assert!(error.to_string().contains("Cannot use 'in' operator to search for 'x' in 5"), "{}", error);
+        assert_eq!(compile_and_run("let v = 1; delete v").unwrap_err().code(), ErrorCode::UnsupportedSyntax);
+    }
+    
+    #[test]
+    fn test_computed_keys_convert_to_strings() {
+        let pairs = [
+            ("1", "'1'"), ("true", "'true'"), ("{}", "'[object Object]'"), ("1.0", "'1'"),
+            ("1.5", "'1.5'"), ("-1", "'-1'"), ("-0", "'0'"), ("null", "'null'"), ("1e21", "'1e+21'"),
+            ("0.1 + 0.2", "'0.30000000000000004'"),
+        ];
+        for (key, string) in pairs {
+            // Write through one form and read, test and delete through the other
+            for (write, read) in [(key, string), (string, key)] {
+                let source = format!(
+                    "let o = {{}}; o[{w}] = 7; o[{r}] === 7 && ({r}) in o && o.hasOwnProperty({r}) \
+                     && delete o[{r}] && !(({w}) in o)",
+                    w = write, r = read,
+                );
+                assert_eq!(compile_and_run(&source).unwrap(), Value::Boolean(true), "{}", source);
+            }
+            let source = format!("let o = {{ [{}]: 7 }}; o[{}]", key, string);
+            assert_eq!(compile_and_run(&source).unwrap(), Value::Number(7.0), "{}", source);
+        }
+        
+        // Array elements are found by the same strings
+        assert_number("let a = [1, 2, 3]; a['1'] + a[1.0] + a[2]", 7.0);
+        assert_eq!(compile_and_run("[1, 2][1.5]").unwrap(), Value::Undefined);
+    }
+    
+    #[test]
+    fn test_literal_templates_make_fresh_values() {
+        // Each evaluation copies the template, nested literals included
+        assert_eq!(
+            compile_and_run("function f() { return {a: [1, 2], b: {c: 3}}; } let x = f(); let y = f(); \
+                             x.a.push(9); x.b.c = 4; x !== y && x.a !== y.a && y.a.length === 2 && y.b.c === 3").unwrap(),
+            Value::Boolean(true),
+        );
+        assert_eq!(compile_and_run("let a = [1, , 3]; a.length === 3 && !(1 in a)").unwrap(), Value::Boolean(true));
+        
+        // Mixed literals keep source order for keys, values and side effects
+        assert_eq!(
+            compile_and_run("let log = ''; function f(v) { log = log + v; return v; } \
+                             let o = {a: f('a'), b: 2, a: 1, c: f('c'), d: 4}; \
+                             JSON.stringify(o) + log").unwrap(),
+            Value::string(r#"{"a":1,"b":2,"c":"c","d":4}ac"#),
+        );
+        assert_number("let x = 5; let a = [1, x, [x, 2]]; a[0] + a[1] + a[2][0] + a[2][1]", 13.0);
+    }
+}
@@ -4,15 +4,52 @@
 //! implementing a stack-based VM with accumulator register.
 
 pub mod value;
+pub mod atom;
+pub mod context;
+pub mod handles;
 pub mod frame;
+pub mod arena;
 pub mod builtins;
 pub mod machine;
+pub mod object;
+pub mod json;
+pub mod object_global;
+pub mod number_global;
+pub mod array_global;
+pub mod tasks;
+pub mod iteration;
+pub mod pure;
+pub mod promise;
+pub mod random;
+pub mod profiler;
+pub mod property_cache;
+pub mod debug;
+pub mod snapshot;
+pub mod detached;
+pub mod case;
+pub mod symbol;
+mod globals;
+mod heap;
+mod gc;
 
 // Re-export main types
-pub use value::{Value, FunctionRef, NativeFunction};
+pub use value::{Value, FunctionRef, HostFunction, NativeMethod, PreferredType, PropertyAttributes, PropertyKey};
+pub use context::CallContext;
+pub use handles::{HandleScope, Local, Persistent, PersistentRoots, Rootable};
+pub use iteration::IterationConsumer;
+pub use pure::{pure_builtin, PureFunction};
 pub use frame::{CallFrame, CallStack};
 pub use builtins::Builtins;
-pub use machine::VM;
+pub use object::ObjectBuilder;
+pub use machine::{VM, VMConfig, VmMetrics, GlobalSlot, ConsoleOutput};
+pub use profiler::{ProfileMode, ProfileReport};
+pub use property_cache::PropertyCacheStats;
+pub use debug::{Breakpoint, BreakpointId, DebugContext, DebugHook, NanOrigin, PauseReason, StepMode};
+pub use snapshot::Snapshot;
+pub use detached::{Detached, DetachedObject, DetachedValue};
+pub use case::CaseMapper;
+pub use symbol::Symbol;
+pub use atom::{Atom, AtomKey};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,83 @@
+//! The Number Global
+//!
+//! `Number` as a namespace: the constants `MAX_SAFE_INTEGER`,
+//! `MIN_SAFE_INTEGER` and `EPSILON`, and the predicates `Number.isInteger`,
+//! `Number.isSafeInteger` and `Number.isFinite`. Unlike the global
+//! `isFinite`, the predicates never convert their argument: anything that
+//! isn't a number is false. `Number` itself is not callable yet.
+//!
+//! Numbers are doubles throughout the VM, so arithmetic past
+//! `MAX_SAFE_INTEGER` rounds exactly as it does in other engines
+//! (`2 ** 53 + 1 === 2 ** 53`) rather than wrapping or failing.
+
+use crate::error::Result;
+use super::context::CallContext;
+use super::object::methods_object;
+use super::value::{NativeMethod, PropertyAttributes, Value};
+
+/// 2^53 - 1, the largest integer with no other integer rounding to it
+pub const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// Create the `Number` object
+pub fn create_object() -> Value {
+    let number = methods_object(&METHODS);
+    // The constants are read-only, hidden and permanent, as in the spec
+    for (name, value) in [
+        ("EPSILON", f64::EPSILON),
+        ("MAX_SAFE_INTEGER", MAX_SAFE_INTEGER),
+        ("MIN_SAFE_INTEGER", -MAX_SAFE_INTEGER),
+    ] {
+        number.define_own_property(name, Value::Number(value), PropertyAttributes::NONE)
+            .expect("plain objects take any attributes");
+    }
+    number
+}
+
+/// The methods of `Number`
+pub(crate) const METHODS: [(&str, NativeMethod); 3] = [
+    ("isFinite", number_is_finite),
+    ("isInteger", number_is_integer),
+    ("isSafeInteger", number_is_safe_integer),
+];
+
+/// The first argument when it is a number
+fn number_argument(args: &[Value]) -> Option<f64> {
+    match args.first() {
+        Some(Value::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn is_integral(n: f64) -> bool {
+    n.is_finite() && n.trunc() == n
+}
+
+/// `Number.isFinite(value)` - Whether `value` is a number other than
+/// NaN and the infinities
+fn number_is_finite(cx: &mut CallContext) -> Result<Value> {
+    Ok(is_finite(cx.args))
+}
+
+pub(crate) fn is_finite(args: &[Value]) -> Value {
+    Value::Boolean(number_argument(args).is_some_and(f64::is_finite))
+}
+
+/// `Number.isInteger(value)` - Whether `value` is a number with no
+/// fractional part
+fn number_is_integer(cx: &mut CallContext) -> Result<Value> {
+    Ok(is_integer(cx.args))
+}
+
+pub(crate) fn is_integer(args: &[Value]) -> Value {
+    Value::Boolean(number_argument(args).is_some_and(is_integral))
+}
+
+/// `Number.isSafeInteger(value)` - Whether `value` is an integer no
+/// further from zero than `MAX_SAFE_INTEGER`
+fn number_is_safe_integer(cx: &mut CallContext) -> Result<Value> {
+    Ok(is_safe_integer(cx.args))
+}
+
+pub(crate) fn is_safe_integer(args: &[Value]) -> Value {
+    Value::Boolean(number_argument(args).is_some_and(|n| is_integral(n) && n.abs() <= MAX_SAFE_INTEGER))
+}
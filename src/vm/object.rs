@@ -0,0 +1,349 @@
+//! Object Construction API
+//!
+//! Built-ins create, read and fill objects through these helpers rather
+//! than through `ObjectData`'s storage, so the object representation can
+//! change without touching them. Exotic objects the VM makes itself
+//! (generators, promises, iterators) keep their own constructors on
+//! `ObjectData`.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::Result;
+use super::symbol::Symbol;
+use super::value::{array_index, FunctionRef, NativeMethod, ObjectData, PropertyAttributes, PropertyMap, Value};
+
+impl Value {
+    /// A new empty object
+    pub fn new_object() -> Value {
+        ObjectBuilder::new().build()
+    }
+    
+    /// A new array holding `elements`
+    pub fn new_array(elements: impl IntoIterator<Item = Value>) -> Value {
+        Value::Object(ObjectData::new_array(elements.into_iter().map(Some).collect()).into_rc())
+    }
+    
+    /// A new array of `length` holes, as `new Array(length)` makes
+    pub fn new_array_with_length(length: usize) -> Value {
+        Value::Object(ObjectData::new_array(vec![None; length]).into_rc())
+    }
+    
+    /// Append `value` to an array, returning the new length; 0 for
+    /// anything else, which is left alone
+    pub fn push_element(&self, value: Value) -> usize {
+        match self {
+            Value::Object(object) => object.array_elements().map_or(0, |elements| {
+                let mut elements = elements.borrow_mut();
+                elements.push(Some(value));
+                elements.len()
+            }),
+            _ => 0,
+        }
+    }
+    
+    /// Read a property, own or inherited
+    ///
+    /// Built-in methods such as `push` are not seen; `None` for primitives
+    /// and missing keys.
+    pub fn get_property(&self, key: &str) -> Option<Value> {
+        match self {
+            Value::Object(object) => object.lookup(key),
+            Value::Function(FunctionRef::Closure(closure)) => closure.properties.borrow().get(key).cloned(),
+            Value::Function(FunctionRef::Builtin(builtin)) => builtin.properties.borrow().get(key).cloned(),
+            _ => None,
+        }
+    }
+    
+    /// Write an own property
+    ///
+    /// Arrays grow for index keys and truncate for `length`. Writes to
+    /// primitives and read-only properties are ignored, as in sloppy mode
+    /// code.
+    pub fn set_property(&self, key: &str, value: Value) -> Result<()> {
+        match self {
+            Value::Object(object) => object.set(key, value).map(|_| ()),
+            Value::Function(FunctionRef::Closure(closure)) => {
+                closure.properties.borrow_mut().assign(key, value);
+                Ok(())
+            }
+            Value::Function(FunctionRef::Builtin(builtin)) => {
+                builtin.properties.borrow_mut().assign(key, value);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// The elements of an array, holes read as `undefined`; `None` for
+    /// anything else
+    pub fn array_values(&self) -> Option<Vec<Value>> {
+        match self {
+            Value::Object(object) => object.array_elements().map(|elements| {
+                elements.borrow().iter()
+                    .map(|element| element.clone().unwrap_or(Value::Undefined))
+                    .collect()
+            }),
+            _ => None,
+        }
+    }
+    
+    /// Own enumerable string-keyed properties, in order
+    ///
+    /// Array elements and string characters come first by index, followed
+    /// by named properties in insertion order.
+    pub fn own_entries(&self) -> Vec<(String, Value)> {
+        match self {
+            Value::Object(object) => {
+                let mut entries = Vec::new();
+                if let Some(elements) = object.array_elements() {
+                    for (index, element) in elements.borrow().iter().enumerate() {
+                        if let Some(element) = element {
+                            entries.push((index.to_string(), element.clone()));
+                        }
+                    }
+                }
+                for (key, value) in object.properties.borrow().enumerable() {
+                    entries.push((key.to_string(), value.clone()));
+                }
+                entries
+            }
+            Value::String(s) => s.encode_utf16()
+                .enumerate()
+                .map(|(index, unit)| (index.to_string(), Value::string(String::from_utf16_lossy(&[unit]))))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// Own string keys, in the order `own_entries` uses
+    ///
+    /// With `include_hidden`, non-enumerable keys are listed too,
+    /// including the `length` of arrays and strings after their indices.
+    pub fn own_keys(&self, include_hidden: bool) -> Vec<String> {
+        let named = |properties: &PropertyMap| -> Vec<String> {
+            if include_hidden {
+                properties.keys().map(|key| key.to_string()).collect()
+            } else {
+                properties.enumerable().map(|(key, _)| key.to_string()).collect()
+            }
+        };
+        match self {
+            Value::Object(object) => {
+                let mut keys = Vec::new();
+                if let Some(elements) = object.array_elements() {
+                    let elements = elements.borrow();
+                    keys.extend((0..elements.len()).filter(|&index| elements[index].is_some()).map(|index| index.to_string()));
+                    if include_hidden {
+                        keys.push("length".to_string());
+                    }
+                }
+                if include_hidden && object.weak_constructor().is_some() {
+                    keys.push("constructor".to_string());
+                }
+                keys.extend(named(&object.properties.borrow()));
+                keys
+            }
+            Value::String(s) => {
+                let mut keys: Vec<String> = (0..s.encode_utf16().count()).map(|index| index.to_string()).collect();
+                if include_hidden {
+                    keys.push("length".to_string());
+                }
+                keys
+            }
+            Value::Function(FunctionRef::Closure(closure)) => named(&closure.properties.borrow()),
+            Value::Function(FunctionRef::Builtin(builtin)) => named(&builtin.properties.borrow()),
+            _ => Vec::new(),
+        }
+    }
+    
+    /// An own property's value and attributes
+    ///
+    /// Array elements are ordinary properties; `length` is writable but
+    /// neither enumerable nor configurable. String characters and
+    /// `length` are read-only.
+    pub fn own_property(&self, key: &str) -> Option<(Value, PropertyAttributes)> {
+        let map_property = |properties: &PropertyMap| {
+            properties.get(key).cloned().zip(properties.attributes(key))
+        };
+        match self {
+            Value::Object(object) => {
+                if let Some(elements) = object.array_elements() {
+                    if key == "length" {
+                        let length = Value::Number(elements.borrow().len() as f64);
+                        return Some((length, PropertyAttributes { writable: true, ..PropertyAttributes::NONE }));
+                    }
+                    if let Some(index) = array_index(key) {
+                        let element = elements.borrow().get(index).cloned().flatten()?;
+                        return Some((element, PropertyAttributes::DEFAULT));
+                    }
+                }
+                if key == "constructor" {
+                    if let Some(constructor) = object.weak_constructor() {
+                        return Some((constructor, PropertyAttributes { enumerable: false, ..PropertyAttributes::DEFAULT }));
+                    }
+                }
+                map_property(&object.properties.borrow())
+            }
+            Value::String(s) => {
+                let units: Vec<u16> = s.encode_utf16().collect();
+                if key == "length" {
+                    return Some((Value::Number(units.len() as f64), PropertyAttributes::NONE));
+                }
+                let unit = *units.get(array_index(key)?)?;
+                let character = Value::string(String::from_utf16_lossy(&[unit]));
+                Some((character, PropertyAttributes { enumerable: true, ..PropertyAttributes::NONE }))
+            }
+            Value::Function(FunctionRef::Closure(closure)) => map_property(&closure.properties.borrow()),
+            Value::Function(FunctionRef::Builtin(builtin)) => map_property(&builtin.properties.borrow()),
+            _ => None,
+        }
+    }
+    
+    /// Create or replace an own property with the given attributes
+    ///
+    /// No checks are made against the current attributes; that is up to
+    /// the caller, as `Object.defineProperty` does. Array elements and
+    /// `length` have nowhere to keep attributes, so they can only take
+    /// the ones `own_property` reports for them: false is returned for
+    /// anything else, and for primitives.
+    pub fn define_own_property(&self, key: &str, value: Value, attributes: PropertyAttributes) -> Result<bool> {
+        let properties = match self {
+            Value::Object(object) => {
+                if object.is_array() && (key == "length" || array_index(key).is_some()) {
+                    let expected = self.own_property(key).map_or(PropertyAttributes::DEFAULT, |(_, current)| current);
+                    if attributes != expected {
+                        return Ok(false);
+                    }
+                    return object.set(key, value);
+                }
+                object.settle_constructor(key);
+                &object.properties
+            }
+            Value::Function(FunctionRef::Closure(closure)) => &closure.properties,
+            Value::Function(FunctionRef::Builtin(builtin)) => &builtin.properties,
+            _ => return Ok(false),
+        };
+        properties.borrow_mut().define(key.to_string(), value, attributes);
+        Ok(true)
+    }
+    
+    /// An own symbol-keyed property's value and attributes
+    pub fn own_symbol_property(&self, symbol: &Symbol) -> Option<(Value, PropertyAttributes)> {
+        let properties = self.property_map()?.borrow();
+        properties.get_symbol(symbol).cloned().zip(properties.symbol_attributes(symbol))
+    }
+    
+    /// `define_own_property` for a symbol key; false for primitives
+    pub fn define_own_symbol_property(&self, symbol: &Rc<Symbol>, value: Value, attributes: PropertyAttributes) -> bool {
+        match self.property_map() {
+            Some(properties) => {
+                properties.borrow_mut().define_symbol(symbol.clone(), value, attributes);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Where an object or function keeps its own named properties
+    pub(crate) fn property_map(&self) -> Option<&RefCell<PropertyMap>> {
+        match self {
+            Value::Object(object) => Some(&object.properties),
+            Value::Function(FunctionRef::Closure(closure)) => Some(&closure.properties),
+            Value::Function(FunctionRef::Builtin(builtin)) => Some(&builtin.properties),
+            _ => None,
+        }
+    }
+}
+
+/// Builds an ordinary object property by property
+///
+/// ```
+/// use v8::vm::{ObjectBuilder, Value};
+///
+/// let point = ObjectBuilder::new().prop("x", Value::Number(1.0)).prop("y", Value::Number(2.0)).build();
+/// assert_eq!(point.get_property("y"), Some(Value::Number(2.0)));
+/// ```
+#[derive(Debug, Default)]
+pub struct ObjectBuilder {
+    properties: PropertyMap,
+    prototype: Option<Rc<ObjectData>>,
+}
+
+impl ObjectBuilder {
+    /// Start an empty object with no prototype
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a property; a repeated name keeps its first position and takes
+    /// the last value
+    pub fn prop(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.properties.insert(name.into(), value);
+        self
+    }
+    
+    /// Inherit from `prototype`
+    pub fn prototype(mut self, prototype: Option<Rc<ObjectData>>) -> Self {
+        self.prototype = prototype;
+        self
+    }
+    
+    /// Finish the object
+    pub fn build(self) -> Value {
+        let object = ObjectData::with_properties(self.properties);
+        object.set_prototype(self.prototype);
+        Value::Object(object.into_rc())
+    }
+}
+
+/// An object holding `methods`, in order
+pub(crate) fn methods_object(methods: &[(&str, NativeMethod)]) -> Value {
+    methods.iter()
+        .fold(ObjectBuilder::new(), |object, &(name, method)| {
+            object.prop(name, Value::Function(FunctionRef::NativeMethod(method)))
+        })
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_builder_and_accessors() {
+        let prototype = ObjectBuilder::new().prop("inherited", Value::Boolean(true)).build();
+        let Value::Object(prototype_data) = &prototype else { unreachable!() };
+        let object = ObjectBuilder::new()
+            .prop("a", Value::Number(1.0))
+            .prop("b", Value::Number(2.0))
+            .prop("a", Value::Number(3.0))
+            .prototype(Some(prototype_data.clone()))
+            .build();
+        
+        assert_eq!(object.get_property("a"), Some(Value::Number(3.0)));
+        assert_eq!(object.get_property("inherited"), Some(Value::Boolean(true)));
+        assert_eq!(object.get_property("missing"), None);
+        let keys: Vec<String> = object.own_entries().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(keys, vec!["a", "b"]);
+        
+        object.set_property("c", Value::Null).unwrap();
+        assert_eq!(object.get_property("c"), Some(Value::Null));
+        // Primitives ignore writes
+        Value::Number(1.0).set_property("x", Value::Null).unwrap();
+        assert_eq!(Value::Number(1.0).get_property("x"), None);
+    }
+    
+    #[test]
+    fn test_arrays() {
+        let array = Value::new_array([Value::Number(1.0), Value::string("two")]);
+        assert_eq!(array.get_property("length"), Some(Value::Number(2.0)));
+        array.set_property("3", Value::Boolean(true)).unwrap();
+        assert_eq!(
+            array.array_values(),
+            Some(vec![Value::Number(1.0), Value::string("two"), Value::Undefined, Value::Boolean(true)])
+        );
+        assert_eq!(Value::new_array_with_length(2).inspect(), "[ <2 empty items> ]");
+        assert_eq!(Value::new_object().array_values(), None);
+    }
+}
@@ -0,0 +1,255 @@
+//! The Object Global
+//!
+//! `Object.keys`, `Object.getOwnPropertyNames`, `Object.defineProperty`,
+//! `Object.getOwnPropertyDescriptor` (which take symbol keys too),
+//! `Object.is` and the prototype functions `Object.create`,
+//! `Object.getPrototypeOf` and `Object.setPrototypeOf`, written against
+//! the object construction API in `object.rs`. `Object` itself is not callable yet, and descriptors
+//! with `get` or `set` are a TypeError until accessor properties exist.
+//!
+//! There is no `Object.prototype` object: a plain object's prototype is
+//! `null` unless `new`, `Object.create` or `Object.setPrototypeOf` gave
+//! it one, and the methods every object has come from the VM instead.
+
+use std::rc::Rc;
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::machine::VM;
+use super::object::{methods_object, ObjectBuilder};
+use super::value::{NativeMethod, ObjectData, PropertyAttributes, PropertyKey, Value};
+
+/// Create the `Object` object
+pub fn create_object() -> Value {
+    methods_object(&METHODS)
+}
+
+/// The methods of `Object`
+pub(crate) const METHODS: [(&str, NativeMethod); 8] = [
+    ("create", object_create),
+    ("defineProperty", object_define_property),
+    ("getOwnPropertyDescriptor", object_get_own_property_descriptor),
+    ("getOwnPropertyNames", object_get_own_property_names),
+    ("getPrototypeOf", object_get_prototype_of),
+    ("is", object_is),
+    ("keys", object_keys),
+    ("setPrototypeOf", object_set_prototype_of),
+];
+
+/// ToObject's error for the functions that accept primitives
+fn require_object_coercible(value: &Value) -> Result<()> {
+    match value {
+        Value::Null | Value::Undefined => {
+            Err(Error::runtime(ErrorCode::NotObjectCoercible, "TypeError: Cannot convert undefined or null to object", None))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The TypeError for a prototype chain that would loop
+pub(crate) fn cyclic_prototype_error() -> Error {
+    Error::runtime(ErrorCode::InvalidPrototype, "TypeError: Cyclic __proto__ value", None)
+}
+
+/// A prototype argument: an object, or `None` for null
+fn prototype_argument(value: &Value) -> Result<Option<Rc<ObjectData>>> {
+    match value {
+        Value::Object(data) => Ok(Some(data.clone())),
+        Value::Null => Ok(None),
+        other => Err(Error::runtime(
+            ErrorCode::InvalidPrototype,
+            format!("TypeError: Object prototype may only be an Object or null: {}", other),
+            None,
+        )),
+    }
+}
+
+/// `Object.create(prototype)` - A new object inheriting from
+/// `prototype`, which may be null for an object with no inherited
+/// properties at all
+fn object_create(cx: &mut CallContext) -> Result<Value> {
+    let prototype = prototype_argument(&cx.arg(0))?;
+    if !matches!(cx.arg(1), Value::Undefined) {
+        return Err(Error::runtime(ErrorCode::UnsupportedBuiltin, "TypeError: Object.create does not support property descriptors", None));
+    }
+    Ok(ObjectBuilder::new().prototype(prototype).build())
+}
+
+/// `Object.getPrototypeOf(object)` - The prototype, or null
+///
+/// Primitives and functions have no prototype objects here, so they
+/// report null.
+fn object_get_prototype_of(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    require_object_coercible(&object)?;
+    Ok(match &object {
+        Value::Object(data) => data.prototype().map_or(Value::Null, Value::Object),
+        _ => Value::Null,
+    })
+}
+
+/// `Object.is(a, b)` - SameValue: `===`, except that NaN is itself and
+/// `0` and `-0` differ
+fn object_is(cx: &mut CallContext) -> Result<Value> {
+    Ok(Value::Boolean(cx.arg(0).same_value(&cx.arg(1))))
+}
+
+/// `Object.setPrototypeOf(object, prototype)` - Change what `object`
+/// inherits from, returning `object`
+///
+/// Primitives are returned unchanged. A prototype that would make the
+/// chain a cycle is a TypeError.
+fn object_set_prototype_of(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    if matches!(object, Value::Null | Value::Undefined) {
+        return Err(Error::runtime(ErrorCode::NotObjectCoercible, "TypeError: Object.setPrototypeOf called on null or undefined", None));
+    }
+    let prototype = prototype_argument(&cx.arg(1))?;
+    match &object {
+        Value::Object(data) if !data.set_prototype(prototype) => Err(cyclic_prototype_error()),
+        Value::Function(_) => Err(Error::runtime(ErrorCode::InvalidPrototype, "TypeError: Functions have no prototype to set", None)),
+        _ => Ok(object),
+    }
+}
+
+/// `Object.keys(object)` - The own enumerable string keys
+fn object_keys(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    require_object_coercible(&object)?;
+    Ok(cx.new_array(object.own_keys(false).into_iter().map(Value::string)).get())
+}
+
+/// `Object.getOwnPropertyNames(object)` - The own string keys, enumerable or not
+fn object_get_own_property_names(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    require_object_coercible(&object)?;
+    Ok(cx.new_array(object.own_keys(true).into_iter().map(Value::string)).get())
+}
+
+/// `Object.getOwnPropertyDescriptor(object, key)` - `{ value, writable,
+/// enumerable, configurable }` for an own property, otherwise `undefined`
+fn object_get_own_property_descriptor(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    require_object_coercible(&object)?;
+    let key = cx.vm.to_property_key(&cx.arg(1))?;
+    Ok(match own_property(cx.vm, &object, &key)? {
+        Some((value, attributes)) => ObjectBuilder::new()
+            .prop("value", value)
+            .prop("writable", Value::Boolean(attributes.writable))
+            .prop("enumerable", Value::Boolean(attributes.enumerable))
+            .prop("configurable", Value::Boolean(attributes.configurable))
+            .build(),
+        None => Value::Undefined,
+    })
+}
+
+/// `Object.defineProperty(object, key, descriptor)` - Create or change an
+/// own property, returning `object`
+///
+/// Fields missing from the descriptor keep their current values, or are
+/// `undefined` and false for a new property. A non-configurable property
+/// can only be made read-only, or given its current value again.
+fn object_define_property(cx: &mut CallContext) -> Result<Value> {
+    let object = cx.arg(0);
+    if !matches!(object, Value::Object(_) | Value::Function(_)) {
+        return Err(Error::runtime(ErrorCode::InvalidArgument, "TypeError: Object.defineProperty called on non-object", None));
+    }
+    let key = cx.vm.to_property_key(&cx.arg(1))?;
+    let descriptor = Descriptor::from_value(&cx.arg(2))?;
+    
+    let current = own_property(cx.vm, &object, &key)?;
+    let (value, attributes) = match current {
+        Some((current_value, current)) => {
+            if !descriptor.is_compatible(&current_value, current) {
+                return Err(redefine_error(&key));
+            }
+            (
+                descriptor.value.unwrap_or(current_value),
+                PropertyAttributes {
+                    writable: descriptor.writable.unwrap_or(current.writable),
+                    enumerable: descriptor.enumerable.unwrap_or(current.enumerable),
+                    configurable: descriptor.configurable.unwrap_or(current.configurable),
+                },
+            )
+        }
+        None => (
+            descriptor.value.unwrap_or_default(),
+            PropertyAttributes {
+                writable: descriptor.writable.unwrap_or(false),
+                enumerable: descriptor.enumerable.unwrap_or(false),
+                configurable: descriptor.configurable.unwrap_or(false),
+            },
+        ),
+    };
+    
+    let defined = match &key {
+        PropertyKey::String(name) => object.define_own_property(name, value, attributes)?,
+        PropertyKey::Symbol(symbol) => object.define_own_symbol_property(symbol, value, attributes),
+    };
+    if !defined {
+        return Err(redefine_error(&key));
+    }
+    Ok(object)
+}
+
+fn redefine_error(key: &PropertyKey) -> Error {
+    Error::runtime(ErrorCode::ReadOnlyProperty, format!("TypeError: Cannot redefine property: {}", key), None)
+}
+
+/// An own property of `object`, creating a function's lazy `prototype`
+/// first so that it can be seen
+fn own_property(vm: &mut VM, object: &Value, key: &PropertyKey) -> Result<Option<(Value, PropertyAttributes)>> {
+    let key = match key {
+        PropertyKey::String(key) => key,
+        PropertyKey::Symbol(symbol) => return Ok(object.own_symbol_property(symbol)),
+    };
+    if key == "prototype" && matches!(object, Value::Function(_)) && vm.has_own_property(object, key) {
+        vm.get_property(object, key)?;
+    }
+    Ok(object.own_property(key))
+}
+
+/// The fields of a data property descriptor that are present
+struct Descriptor {
+    value: Option<Value>,
+    writable: Option<bool>,
+    enumerable: Option<bool>,
+    configurable: Option<bool>,
+}
+
+impl Descriptor {
+    /// ToPropertyDescriptor: read the fields from an object, own or inherited
+    fn from_value(descriptor: &Value) -> Result<Self> {
+        if !matches!(descriptor, Value::Object(_) | Value::Function(_)) {
+            return Err(Error::runtime(
+                ErrorCode::InvalidArgument,
+                format!("TypeError: Property description must be an object: {}", descriptor),
+                None,
+            ));
+        }
+        if descriptor.get_property("get").is_some() || descriptor.get_property("set").is_some() {
+            return Err(Error::runtime(ErrorCode::UnsupportedBuiltin, "TypeError: Getters and setters are not supported in property descriptors", None));
+        }
+        let flag = |name| descriptor.get_property(name).map(|value| value.is_truthy());
+        Ok(Self {
+            value: descriptor.get_property("value"),
+            writable: flag("writable"),
+            enumerable: flag("enumerable"),
+            configurable: flag("configurable"),
+        })
+    }
+    
+    /// Whether this descriptor may be applied to a property that exists:
+    /// any change is allowed while it is configurable
+    fn is_compatible(&self, current_value: &Value, current: PropertyAttributes) -> bool {
+        if current.configurable {
+            return true;
+        }
+        if self.configurable == Some(true) || self.enumerable.is_some_and(|enumerable| enumerable != current.enumerable) {
+            return false;
+        }
+        if current.writable {
+            return true;
+        }
+        self.writable != Some(true) && self.value.as_ref().is_none_or(|value| value.same_value(current_value))
+    }
+}
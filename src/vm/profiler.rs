@@ -0,0 +1,173 @@
+//! Line-Level Profiler
+//!
+//! Attributes execution to source lines using the line table in each
+//! function's debug info. Counting mode tallies every instruction the VM
+//! runs; sampling mode only records the active instruction when the host
+//! calls `VM::sample`. The VM holds the profiler in an `Option`, so a VM
+//! without one pays a single branch per instruction.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::bytecode::BytecodeFunction;
+
+/// How the profiler collects hits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileMode {
+    /// Count every executed instruction
+    Counting,
+    /// Record the active instruction on each `VM::sample`
+    Sampling,
+}
+
+/// Hits per instruction, per function
+#[derive(Debug)]
+pub struct Profiler {
+    mode: ProfileMode,
+    /// Keyed by function identity; the `Arc` keeps the line table alive
+    functions: HashMap<*const BytecodeFunction, (Arc<BytecodeFunction>, Vec<u64>)>,
+}
+
+impl Profiler {
+    /// Create an empty profiler
+    pub fn new(mode: ProfileMode) -> Self {
+        Self { mode, functions: HashMap::new() }
+    }
+    
+    /// The collection mode
+    pub fn mode(&self) -> ProfileMode {
+        self.mode
+    }
+    
+    /// Record one hit on instruction `ip` of `function`
+    pub fn record(&mut self, function: &Arc<BytecodeFunction>, ip: usize) {
+        let (_, counts) = self.functions.entry(Arc::as_ptr(function))
+            .or_insert_with(|| (function.clone(), vec![0; function.bytecode.len()]));
+        if let Some(count) = counts.get_mut(ip) {
+            *count += 1;
+        }
+    }
+    
+    /// Bucket the hits by source line
+    pub fn report(&self) -> ProfileReport {
+        let mut lines = Vec::new();
+        let mut functions = Vec::new();
+        for (function, counts) in self.functions.values() {
+            let mut by_line: HashMap<Option<u32>, u64> = HashMap::new();
+            for (ip, &count) in counts.iter().enumerate().filter(|(_, &count)| count > 0) {
+                *by_line.entry(function.debug_info.get_line_number(ip)).or_default() += count;
+            }
+            let total = by_line.values().sum();
+            if total == 0 {
+                continue;
+            }
+            functions.push(FunctionHits { function: function.name.clone(), hits: total });
+            lines.extend(by_line.into_iter().map(|(line, hits)| LineHits {
+                function: function.name.clone(),
+                line,
+                hits,
+            }));
+        }
+        
+        // Hottest first; ties in a stable order so reports can be compared
+        lines.sort_by(|a, b| b.hits.cmp(&a.hits).then(a.line.cmp(&b.line)).then(a.function.cmp(&b.function)));
+        functions.sort_by(|a, b| b.hits.cmp(&a.hits).then(a.function.cmp(&b.function)));
+        ProfileReport { mode: self.mode, lines, functions }
+    }
+}
+
+/// Hits attributed to one source line of one function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineHits {
+    pub function: String,
+    /// `None` for instructions the compiler gave no location
+    pub line: Option<u32>,
+    pub hits: u64,
+}
+
+/// Hits attributed to one function, over all its lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionHits {
+    pub function: String,
+    pub hits: u64,
+}
+
+/// Result of `VM::profile_report`, hottest entries first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    pub mode: ProfileMode,
+    pub lines: Vec<LineHits>,
+    pub functions: Vec<FunctionHits>,
+}
+
+impl ProfileReport {
+    /// Hits over the whole run
+    pub fn total_hits(&self) -> u64 {
+        self.functions.iter().map(|function| function.hits).sum()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let unit = match self.mode {
+            ProfileMode::Counting => "instructions",
+            ProfileMode::Sampling => "samples",
+        };
+        let total = self.total_hits().max(1) as f64;
+        let percent = |hits: u64| hits as f64 * 100.0 / total;
+        
+        writeln!(f, "Profile ({} {}):", self.total_hits(), unit)?;
+        writeln!(f, "  By line:")?;
+        for entry in &self.lines {
+            let line = entry.line.map_or_else(|| "?".to_string(), |line| line.to_string());
+            writeln!(f, "  {:>10} {:>6.2}%  {}:{}", entry.hits, percent(entry.hits), entry.function, line)?;
+        }
+        writeln!(f, "  By function:")?;
+        for entry in &self.functions {
+            writeln!(f, "  {:>10} {:>6.2}%  {}", entry.hits, percent(entry.hits), entry.function)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Bytecode;
+    use crate::error::Span;
+    
+    fn function_with_lines(name: &str, lines: &[u32]) -> Arc<BytecodeFunction> {
+        let mut function = BytecodeFunction::new(name.to_string(), 0, 0, 0);
+        for &line in lines {
+            function.bytecode.push(Bytecode::LdaSmi(0));
+            function.debug_info.add_instruction(Some(Span::new(0, 0, line, 1)));
+        }
+        Arc::new(function)
+    }
+    
+    #[test]
+    fn test_hits_are_bucketed_by_line() {
+        let f = function_with_lines("f", &[1, 2, 2]);
+        let g = function_with_lines("g", &[7]);
+        let mut profiler = Profiler::new(ProfileMode::Counting);
+        for ip in [0, 1, 2, 1, 2] {
+            profiler.record(&f, ip);
+        }
+        profiler.record(&g, 0);
+        // Out-of-range instructions are ignored
+        profiler.record(&g, 5);
+        
+        let report = profiler.report();
+        assert_eq!(report.total_hits(), 6);
+        assert_eq!(report.lines[0], LineHits { function: "f".to_string(), line: Some(2), hits: 4 });
+        assert_eq!(
+            report.functions,
+            vec![
+                FunctionHits { function: "f".to_string(), hits: 5 },
+                FunctionHits { function: "g".to_string(), hits: 1 },
+            ]
+        );
+        assert!(report.to_string().contains("f:2"));
+    }
+}
@@ -0,0 +1,490 @@
+//! Promises and Async Functions
+//!
+//! A promise settles once and hands its result to the reactions registered
+//! with `then`, each run as a microtask. Resolving with a thenable (any
+//! object with a callable `then`) adopts its result, so user code can
+//! await and chain its own promise-like objects.
+//!
+//! A promise rejected while nothing handles it is remembered; the host
+//! collects the ones still unhandled after pumping the event loop with
+//! `VM::take_unhandled_rejections`.
+//!
+//! An async function's body runs as a generator: every `await` yields the
+//! awaited value, and the driver here resumes the frame from a microtask
+//! once that value settles. The function's promise settles when the body
+//! returns or fails. The VM cannot catch exceptions yet, so an awaited
+//! rejection is not thrown back into the body; it ends the body and rejects
+//! the function's promise with the same reason.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::iteration::IterationConsumer;
+use super::machine::VM;
+use super::object::ObjectBuilder;
+use super::tasks::Task;
+use super::value::{BoundFunction, BuiltinFunction, FunctionRef, NativeMethod, ObjectData, PropertyMap, Value};
+
+/// Where a promise is in its lifetime
+#[derive(Debug)]
+pub enum PromiseState {
+    /// Not settled yet; reactions run in registration order once it is
+    Pending(Vec<Reaction>),
+    Fulfilled(Value),
+    Rejected {
+        reason: Value,
+        /// Whether a reaction has been registered for the rejection
+        handled: bool,
+    },
+}
+
+/// Handlers registered by `then`
+#[derive(Debug, Clone)]
+pub struct Reaction {
+    pub on_fulfilled: Value,
+    pub on_rejected: Value,
+    /// Promise settled with the handler's result; `None` for the VM's own
+    /// reactions, whose results are not observable
+    pub derived: Option<Value>,
+}
+
+/// Create a pending promise
+pub fn new_promise() -> Value {
+    Value::Object(ObjectData::new_promise().into_rc())
+}
+
+/// Resolve `promise` with `value`: thenables are adopted from a microtask,
+/// anything else fulfills it
+pub fn resolve(vm: &mut VM, promise: &Value, value: Value) -> Result<()> {
+    if &value == promise {
+        let reason = Value::string("TypeError: Chaining cycle detected for promise");
+        return settle(vm, promise, reason, true);
+    }
+    if matches!(value, Value::Object(_) | Value::Function(_)) {
+        let then = match vm.get_property(&value, "then") {
+            Ok(then) => then,
+            Err(error) => return settle(vm, promise, rejection_reason(error), true),
+        };
+        if matches!(then, Value::Function(_)) {
+            let job = Value::Function(FunctionRef::NativeMethod(adopt_thenable_job));
+            vm.tasks_mut().queue_microtask(Task { callback: job, args: vec![promise.clone(), value, then] });
+            return Ok(());
+        }
+    }
+    settle(vm, promise, value, false)
+}
+
+/// Reject `promise` with `reason`
+pub fn reject(vm: &mut VM, promise: &Value, reason: Value) -> Result<()> {
+    settle(vm, promise, reason, true)
+}
+
+/// Register handlers on `promise`, returning the promise their result
+/// settles
+pub fn then(vm: &mut VM, promise: &Value, on_fulfilled: Value, on_rejected: Value) -> Result<Value> {
+    let derived = new_promise();
+    add_reaction(vm, promise, Reaction { on_fulfilled, on_rejected, derived: Some(derived.clone()) })?;
+    Ok(derived)
+}
+
+/// The value a failed call rejects a promise with
+///
+/// Errors don't carry a JavaScript value, so the reason is the message,
+/// e.g. `"TypeError: x is not a function"`.
+pub fn rejection_reason(error: Error) -> Value {
+    match error {
+        Error::Runtime { kind, .. } => Value::string(kind.to_string()),
+        other => Value::string(other.to_string()),
+    }
+}
+
+/// Resolve to `value` if it is a promise, else a new promise resolved with it
+pub fn promise_resolve(vm: &mut VM, value: Value) -> Result<Value> {
+    if matches!(&value, Value::Object(data) if data.promise_state().is_some()) {
+        return Ok(value);
+    }
+    let promise = new_promise();
+    resolve(vm, &promise, value)?;
+    Ok(promise)
+}
+
+/// The reason `promise` was rejected with, if it was and nothing handles it
+pub fn unhandled_reason(promise: &Value) -> Option<Value> {
+    let state = promise_state(promise).ok()?.borrow();
+    match &*state {
+        PromiseState::Rejected { reason, handled: false } => Some(reason.clone()),
+        _ => None,
+    }
+}
+
+/// Fulfill or reject a pending promise and queue its reactions; settling
+/// an already settled promise does nothing
+fn settle(vm: &mut VM, promise: &Value, value: Value, rejected: bool) -> Result<()> {
+    let mut state = promise_state(promise)?.borrow_mut();
+    let reactions = match &mut *state {
+        PromiseState::Pending(reactions) => std::mem::take(reactions),
+        _ => return Ok(()),
+    };
+    *state = if rejected {
+        PromiseState::Rejected { reason: value.clone(), handled: !reactions.is_empty() }
+    } else {
+        PromiseState::Fulfilled(value.clone())
+    };
+    drop(state);
+    if rejected && reactions.is_empty() {
+        vm.track_rejection(promise.clone());
+    }
+    for reaction in reactions {
+        queue_reaction(vm, reaction, value.clone(), rejected);
+    }
+    Ok(())
+}
+
+fn add_reaction(vm: &mut VM, promise: &Value, reaction: Reaction) -> Result<()> {
+    let state = promise_state(promise)?;
+    let settled = match &mut *state.borrow_mut() {
+        PromiseState::Pending(reactions) => {
+            reactions.push(reaction);
+            return Ok(());
+        }
+        PromiseState::Fulfilled(value) => (value.clone(), false),
+        PromiseState::Rejected { reason, handled } => {
+            *handled = true;
+            (reason.clone(), true)
+        }
+    };
+    queue_reaction(vm, reaction, settled.0, settled.1);
+    Ok(())
+}
+
+fn queue_reaction(vm: &mut VM, reaction: Reaction, value: Value, rejected: bool) {
+    let handler = if rejected { reaction.on_rejected } else { reaction.on_fulfilled };
+    let job = Value::Function(FunctionRef::NativeMethod(reaction_job));
+    let derived = reaction.derived.unwrap_or(Value::Undefined);
+    vm.tasks_mut().queue_microtask(Task { callback: job, args: vec![handler, derived, value, Value::Boolean(rejected)] });
+}
+
+/// Microtask `(handler, derived, value, rejected)`: run one reaction
+///
+/// A missing handler passes the result through to the derived promise.
+fn reaction_job(cx: &mut CallContext) -> Result<Value> {
+    let [handler, derived, value, rejected] = cx.args else {
+        return Err(Error::internal("Malformed promise reaction"));
+    };
+    let rejected = rejected.is_truthy();
+    if !matches!(handler, Value::Function(_)) {
+        if derived != &Value::Undefined {
+            settle(cx.vm, derived, value.clone(), rejected)?;
+        }
+        return Ok(Value::Undefined);
+    }
+    let result = cx.vm.call_function(handler, Value::Undefined, std::slice::from_ref(value));
+    if derived == &Value::Undefined {
+        return result;
+    }
+    match result {
+        Ok(result) => resolve(cx.vm, derived, result)?,
+        Err(error) => reject(cx.vm, derived, rejection_reason(error))?,
+    }
+    Ok(Value::Undefined)
+}
+
+/// Microtask `(promise, thenable, then)`: call `then` with functions that
+/// settle `promise`
+fn adopt_thenable_job(cx: &mut CallContext) -> Result<Value> {
+    let [promise, thenable, then] = cx.args else {
+        return Err(Error::internal("Malformed thenable job"));
+    };
+    let (resolve_function, reject_function) = resolving_functions(promise);
+    let reject_function = cx.root(reject_function);
+    if let Err(error) = cx.vm.call_function(then, thenable.clone(), &[resolve_function, reject_function.get()]) {
+        cx.vm.call_function(&reject_function, Value::Undefined, &[rejection_reason(error)])?;
+    }
+    Ok(Value::Undefined)
+}
+
+/// The `resolve` and `reject` functions for `promise`
+///
+/// They share a record, so only the first call of either has an effect.
+fn resolving_functions(promise: &Value) -> (Value, Value) {
+    let record = ObjectBuilder::new()
+        .prop("promise", promise.clone())
+        .prop("alreadyResolved", Value::Boolean(false))
+        .build();
+    (bound(resolve_function, vec![record.clone()]), bound(reject_function, vec![record]))
+}
+
+/// Mark a resolving-function record used, returning its promise unless it
+/// already was
+fn claim_record(record: &Value) -> Result<Option<Value>> {
+    let Some(resolved) = record.get_property("alreadyResolved") else {
+        return Err(Error::internal("Malformed resolving function"));
+    };
+    if resolved.is_truthy() {
+        return Ok(None);
+    }
+    record.set_property("alreadyResolved", Value::Boolean(true))?;
+    Ok(record.get_property("promise"))
+}
+
+/// `resolve(value)`, bound to a resolving-function record
+fn resolve_function(cx: &mut CallContext) -> Result<Value> {
+    if let Some(promise) = claim_record(&cx.args[0])? {
+        resolve(cx.vm, &promise, cx.arg(1))?;
+    }
+    Ok(Value::Undefined)
+}
+
+/// `reject(reason)`, bound to a resolving-function record
+fn reject_function(cx: &mut CallContext) -> Result<Value> {
+    if let Some(promise) = claim_record(&cx.args[0])? {
+        reject(cx.vm, &promise, cx.arg(1))?;
+    }
+    Ok(Value::Undefined)
+}
+
+// === Async Functions ===
+
+/// Start the body of an async function, suspended in `generator`, and
+/// return the promise its completion settles
+///
+/// The body runs synchronously up to its first `await`.
+pub fn start_async(vm: &mut VM, generator: Value) -> Result<Value> {
+    let promise = new_promise();
+    // Nothing else holds either until the body first awaits
+    vm.with_roots([&generator, &promise], |vm| step(vm, &generator, &promise, Value::Undefined))?;
+    Ok(promise)
+}
+
+/// Resume the body with `sent` as the value of the pending `await`
+fn step(vm: &mut VM, generator: &Value, promise: &Value, sent: Value) -> Result<()> {
+    match vm.resume_generator(generator, sent) {
+        Ok((value, true)) => resolve(vm, promise, value),
+        Ok((awaited, false)) => {
+            // Plain values go through a promise too, so the body always
+            // resumes from a later microtask
+            let settled = new_promise();
+            vm.with_roots([&settled], |vm| resolve(vm, &settled, awaited))?;
+            let resume = vec![generator.clone(), promise.clone()];
+            add_reaction(vm, &settled, Reaction {
+                on_fulfilled: bound(async_resume, resume.clone()),
+                on_rejected: bound(async_abort, resume),
+                derived: None,
+            })
+        }
+        Err(error) => reject(vm, promise, rejection_reason(error)),
+    }
+}
+
+/// Continue after an `await` fulfilled, bound to `(generator, promise)`
+fn async_resume(cx: &mut CallContext) -> Result<Value> {
+    let value = cx.arg(2);
+    step(cx.vm, &cx.args[0], &cx.args[1], value)?;
+    Ok(Value::Undefined)
+}
+
+/// End the body after an `await` rejected, bound to `(generator, promise)`
+fn async_abort(cx: &mut CallContext) -> Result<Value> {
+    let reason = cx.arg(2);
+    cx.vm.close_generator(&cx.args[0])?;
+    reject(cx.vm, &cx.args[1], reason)?;
+    Ok(Value::Undefined)
+}
+
+/// A native method with leading arguments bound
+fn bound(method: NativeMethod, args: Vec<Value>) -> Value {
+    let target = Value::Function(FunctionRef::NativeMethod(method));
+    Value::Function(FunctionRef::Bound(Rc::new(BoundFunction { target, this: Value::Undefined, args })))
+}
+
+/// The state of a promise, or a TypeError for any other value
+fn promise_state(value: &Value) -> Result<&RefCell<PromiseState>> {
+    match value {
+        Value::Object(data) => data.promise_state(),
+        _ => None,
+    }
+    .ok_or_else(|| Error::runtime(ErrorCode::IncompatibleReceiver, format!("TypeError: {} is not a promise", value), None))
+}
+
+// === The Promise Global ===
+
+/// Create the `Promise` constructor with its static methods
+pub fn create_constructor() -> Value {
+    let mut properties = PropertyMap::new();
+    for (name, method) in STATIC_METHODS {
+        properties.insert(name.to_string(), Value::Function(FunctionRef::NativeMethod(method)));
+    }
+    let (name, call, construct) = CONSTRUCTOR;
+    Value::Function(FunctionRef::Builtin(Rc::new(BuiltinFunction {
+        name,
+        call,
+        construct,
+        properties: RefCell::new(properties),
+    })))
+}
+
+/// `Promise` itself: its name, and what calling it and `new` run
+pub(crate) const CONSTRUCTOR: (&str, NativeMethod, Option<NativeMethod>) = ("Promise", promise_call, Some(promise_construct));
+
+/// The static methods of `Promise`
+pub(crate) const STATIC_METHODS: [(&str, NativeMethod); 3] = [
+    ("resolve", promise_static_resolve),
+    ("reject", promise_static_reject),
+    ("all", promise_all),
+];
+
+/// Promise(executor) without `new` - Always a TypeError
+fn promise_call(_cx: &mut CallContext) -> Result<Value> {
+    Err(Error::runtime(ErrorCode::NotConstructor, "TypeError: Promise constructor cannot be invoked without 'new'", None))
+}
+
+/// new Promise(executor) - Call `executor(resolve, reject)` right away; a
+/// failing executor rejects the promise
+fn promise_construct(cx: &mut CallContext) -> Result<Value> {
+    let executor = match cx.args.first() {
+        Some(executor @ Value::Function(_)) => executor.clone(),
+        other => {
+            let other = other.cloned().unwrap_or(Value::Undefined);
+            return Err(Error::runtime(ErrorCode::InvalidArgument, format!("TypeError: Promise resolver {} is not a function", other), None));
+        }
+    };
+    let promise = cx.new_promise();
+    let (resolve_function, reject_function) = resolving_functions(&promise);
+    let reject_function = cx.root(reject_function);
+    if let Err(error) = cx.vm.call_function(&executor, Value::Undefined, &[resolve_function, reject_function.get()]) {
+        cx.vm.call_function(&reject_function, Value::Undefined, &[rejection_reason(error)])?;
+    }
+    Ok(promise.get())
+}
+
+/// Promise.resolve(value) - `value` itself if it is a promise, else a
+/// promise resolved with it
+fn promise_static_resolve(cx: &mut CallContext) -> Result<Value> {
+    promise_resolve(cx.vm, cx.arg(0))
+}
+
+/// Promise.reject(reason) - A promise rejected with `reason`
+fn promise_static_reject(cx: &mut CallContext) -> Result<Value> {
+    let promise = cx.new_promise();
+    reject(cx.vm, &promise, cx.arg(0))?;
+    Ok(promise.get())
+}
+
+/// Promise.all(iterable) - A promise for the array of its elements'
+/// results, rejected with the first rejection
+///
+/// Elements that aren't promises count as already fulfilled.
+fn promise_all(cx: &mut CallContext) -> Result<Value> {
+    let items = cx.vm.collect_iterable(&cx.arg(0), IterationConsumer::PromiseAll)?;
+    for item in &items {
+        cx.root(item.clone());
+    }
+    let promise = cx.new_promise();
+    let values = Value::new_array_with_length(items.len());
+    if items.is_empty() {
+        resolve(cx.vm, &promise, values)?;
+        return Ok(promise.get());
+    }
+    
+    let state = ObjectBuilder::new()
+        .prop("promise", promise.get())
+        .prop("values", values)
+        .prop("remaining", Value::Number(items.len() as f64))
+        .build();
+    let state = cx.root(state);
+    let (_, reject_function) = resolving_functions(&promise);
+    let reject_function = cx.root(reject_function);
+    for (index, item) in items.into_iter().enumerate() {
+        let item = promise_resolve(cx.vm, item)?;
+        add_reaction(cx.vm, &item, Reaction {
+            on_fulfilled: bound(all_element_fulfilled, vec![state.get(), Value::Number(index as f64)]),
+            on_rejected: reject_function.get(),
+            derived: None,
+        })?;
+    }
+    Ok(promise.get())
+}
+
+/// Record one `Promise.all` result, bound to `(state, index)`; the last one
+/// resolves the combined promise
+fn all_element_fulfilled(cx: &mut CallContext) -> Result<Value> {
+    let [state, Value::Number(index), value] = cx.args else {
+        return Err(Error::internal("Malformed Promise.all element"));
+    };
+    let field = |key| state.get_property(key).ok_or_else(|| Error::internal("Malformed Promise.all state"));
+    let values = field("values")?;
+    values.set_property(&index.to_string(), value.clone())?;
+    let remaining = field("remaining")?.to_number() - 1.0;
+    state.set_property("remaining", Value::Number(remaining))?;
+    if remaining == 0.0 {
+        resolve(cx.vm, &field("promise")?, values)?;
+    }
+    Ok(Value::Undefined)
+}
+
+// === Promise.prototype ===
+
+/// Look up a method of Promise.prototype
+pub fn promise_method(name: &str) -> Option<NativeMethod> {
+    match name {
+        "then" => Some(promise_then as NativeMethod),
+        "catch" => Some(promise_catch as NativeMethod),
+        "finally" => Some(promise_finally as NativeMethod),
+        _ => None,
+    }
+}
+
+/// promise.then(onFulfilled, onRejected) - Register handlers; returns a
+/// promise for the handler's result
+fn promise_then(cx: &mut CallContext) -> Result<Value> {
+    let on_fulfilled = cx.arg(0);
+    let on_rejected = cx.arg(1);
+    then(cx.vm, cx.this, on_fulfilled, on_rejected)
+}
+
+/// promise.catch(onRejected) - Shorthand for `then(undefined, onRejected)`
+fn promise_catch(cx: &mut CallContext) -> Result<Value> {
+    let on_rejected = cx.arg(0);
+    then(cx.vm, cx.this, Value::Undefined, on_rejected)
+}
+
+/// promise.finally(onFinally) - Run `onFinally()` however the promise
+/// settles, then pass its result on unchanged
+///
+/// If `onFinally` fails or returns a promise that rejects, that rejection
+/// replaces the result.
+fn promise_finally(cx: &mut CallContext) -> Result<Value> {
+    let on_finally = match cx.args.first() {
+        Some(on_finally @ Value::Function(_)) => on_finally.clone(),
+        _ => return then(cx.vm, cx.this, Value::Undefined, Value::Undefined),
+    };
+    let on_fulfilled = bound(finally_handler, vec![on_finally.clone(), Value::Boolean(false)]);
+    let on_rejected = bound(finally_handler, vec![on_finally, Value::Boolean(true)]);
+    then(cx.vm, cx.this, on_fulfilled, on_rejected)
+}
+
+/// Reaction of `finally`, bound to `(onFinally, rejected)`: run
+/// `onFinally`, wait for its result, then settle like the original
+fn finally_handler(cx: &mut CallContext) -> Result<Value> {
+    let [on_finally, rejected, value] = cx.args else {
+        return Err(Error::internal("Malformed finally handler"));
+    };
+    let result = cx.vm.call_function(on_finally, Value::Undefined, &[])?;
+    let done = promise_resolve(cx.vm, result)?;
+    let restore = if rejected.is_truthy() { rejected_with } else { fulfilled_with };
+    then(cx.vm, &done, bound(restore, vec![value.clone()]), Value::Undefined)
+}
+
+/// Return the bound value, ignoring the argument
+fn fulfilled_with(cx: &mut CallContext) -> Result<Value> {
+    Ok(cx.args[0].clone())
+}
+
+/// Return a promise rejected with the bound reason, ignoring the argument
+fn rejected_with(cx: &mut CallContext) -> Result<Value> {
+    let promise = cx.new_promise();
+    reject(cx.vm, &promise, cx.args[0].clone())?;
+    Ok(promise.get())
+}
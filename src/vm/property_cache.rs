@@ -0,0 +1,144 @@
+//! Property Caches
+//!
+//! Caches for `LdaNamed`, one per instruction, kept while
+//! `VMConfig::property_caches` is on. Objects have no hidden classes, so
+//! a cache remembers the positions in an object's `PropertyMap` where
+//! its instruction last found the name. A read checks the key at each
+//! remembered position before trusting it, comparing atoms by address,
+//! so a stale cache only costs a miss, never a wrong value; a miss looks
+//! the name up in the map's hash index, the path every read takes with
+//! the caches off.
+//!
+//! A cache holding one position is monomorphic and one holding up to
+//! `POLYMORPHIC_LIMIT` polymorphic. A site that finds the name at more
+//! positions than that goes megamorphic: its reads skip the cache from
+//! then on.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::bytecode::BytecodeFunction;
+use super::atom::Atom;
+use super::value::{PropertyMap, Value};
+
+/// Most positions one cache remembers before it goes megamorphic
+pub const POLYMORPHIC_LIMIT: usize = 4;
+
+/// What the caches have seen since they were created or last reset
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PropertyCacheStats {
+    /// Reads that found the name at a remembered position
+    pub hits: u64,
+    /// Reads that looked the name up in the hash index
+    pub misses: u64,
+    /// Reads at megamorphic sites, which skip the cache
+    pub megamorphic_reads: u64,
+    /// Sites remembering one position
+    pub monomorphic_sites: usize,
+    /// Sites remembering two to `POLYMORPHIC_LIMIT` positions
+    pub polymorphic_sites: usize,
+    /// Sites that gave up caching
+    pub megamorphic_sites: usize,
+}
+
+/// The cache of one instruction
+#[derive(Debug, Clone, Copy)]
+enum Site {
+    /// Where the name was found, the first `len` of `positions`
+    Positions { len: usize, positions: [usize; POLYMORPHIC_LIMIT] },
+    Megamorphic,
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Site::Positions { len: 0, positions: [0; POLYMORPHIC_LIMIT] }
+    }
+}
+
+/// Caches for every `LdaNamed` a VM has run
+#[derive(Debug, Default)]
+pub struct PropertyCaches {
+    /// The sites of each function that has run a named read, indexed by
+    /// instruction offset; the `Arc` keeps the function, and so its
+    /// pointer in `by_function`, alive
+    functions: Vec<(Arc<BytecodeFunction>, Vec<Site>)>,
+    by_function: HashMap<*const BytecodeFunction, usize>,
+    /// Index of the function read from last, which is most often the
+    /// next one too
+    last: Option<usize>,
+    hits: u64,
+    misses: u64,
+    megamorphic_reads: u64,
+}
+
+impl PropertyCaches {
+    /// Create empty caches
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Read own property `name` from `properties` for instruction `ip` of
+    /// `function`, going through its cache
+    ///
+    /// `None` when the property is not an own one of the map, so the
+    /// caller looks further.
+    pub fn read(&mut self, function: &Arc<BytecodeFunction>, ip: usize, properties: &PropertyMap, name: &Atom) -> Option<Value> {
+        let index = self.function_index(function);
+        let sites = &mut self.functions[index].1;
+        let Some(Site::Positions { len, positions }) = sites.get_mut(ip) else {
+            self.megamorphic_reads += 1;
+            return properties.get(name).cloned();
+        };
+        for &position in &positions[..*len] {
+            if let Some((key, value)) = properties.entry_at(position) {
+                if key == name {
+                    self.hits += 1;
+                    return Some(value.clone());
+                }
+            }
+        }
+        
+        self.misses += 1;
+        let position = properties.position(name)?;
+        if *len == POLYMORPHIC_LIMIT {
+            sites[ip] = Site::Megamorphic;
+        } else {
+            positions[*len] = position;
+            *len += 1;
+        }
+        properties.entry_at(position).map(|(_, value)| value.clone())
+    }
+    
+    /// Where `function`'s sites are in `functions`, adding them if new
+    fn function_index(&mut self, function: &Arc<BytecodeFunction>) -> usize {
+        let pointer = Arc::as_ptr(function);
+        let index = match self.last {
+            Some(index) if Arc::as_ptr(&self.functions[index].0) == pointer => index,
+            _ => *self.by_function.entry(pointer).or_insert_with(|| {
+                self.functions.push((function.clone(), vec![Site::default(); function.bytecode.len()]));
+                self.functions.len() - 1
+            }),
+        };
+        self.last = Some(index);
+        index
+    }
+    
+    /// The counters so far, and how many sites are in each state
+    pub fn stats(&self) -> PropertyCacheStats {
+        let mut stats = PropertyCacheStats {
+            hits: self.hits,
+            misses: self.misses,
+            megamorphic_reads: self.megamorphic_reads,
+            ..PropertyCacheStats::default()
+        };
+        for site in self.functions.iter().flat_map(|(_, sites)| sites) {
+            match site {
+                Site::Positions { len: 0, .. } => {}
+                Site::Positions { len: 1, .. } => stats.monomorphic_sites += 1,
+                Site::Positions { .. } => stats.polymorphic_sites += 1,
+                Site::Megamorphic => stats.megamorphic_sites += 1,
+            }
+        }
+        stats
+    }
+}
@@ -0,0 +1,57 @@
+//! Built-ins that need no VM
+//!
+//! A pure built-in's result depends only on its receiver and arguments:
+//! it has no side effects and returns the same thing every time, which
+//! rules out `Math.random` and anything reading the clock. Of `Math`, only
+//! `Math.pow` qualifies; `Math.floor` and the other pure functions of the
+//! standard `Math` are not built in yet. Given primitive arguments these
+//! run without a VM, so the compiler can call them on constants (see
+//! `Compiler::with_builtin_folding`). The natives the VM installs call the
+//! same functions, so a folded call and a run-time call cannot disagree.
+
+use crate::error::Result;
+use super::value::Value;
+use super::{builtins, json, number_global};
+
+/// A pure built-in, called with its receiver and primitive arguments
+pub type PureFunction = fn(&Value, &[Value]) -> Result<Value>;
+
+/// The pure built-ins, by the path a script calls them through; string
+/// methods are under `String.prototype`
+pub(crate) const PURE_BUILTINS: [(&str, PureFunction); 11] = [
+    ("JSON.parse", |_, args| json::parse_text(&first(args).to_string())),
+    ("Math.pow", |_, args| Ok(builtins::pow(args))),
+    ("Number.isFinite", |_, args| Ok(number_global::is_finite(args))),
+    ("Number.isInteger", |_, args| Ok(number_global::is_integer(args))),
+    ("Number.isSafeInteger", |_, args| Ok(number_global::is_safe_integer(args))),
+    ("String.prototype.indexOf", |this, args| Ok(builtins::index_of(&this.to_string(), &first(args).to_string(), args.get(1)))),
+    ("String.prototype.localeCompare", |this, args| Ok(builtins::locale_compare(this, first(args)))),
+    ("isFinite", |_, args| Ok(builtins::is_finite(args))),
+    ("isNaN", |_, args| Ok(builtins::is_nan(args))),
+    ("parseFloat", |_, args| Ok(builtins::parse_float(args))),
+    ("parseInt", |_, args| Ok(builtins::parse_int(args))),
+];
+
+/// The pure built-in called through `path`, such as `Number.isInteger`
+pub fn pure_builtin(path: &str) -> Option<PureFunction> {
+    PURE_BUILTINS.iter().find(|(name, _)| *name == path).map(|&(_, function)| function)
+}
+
+fn first(args: &[Value]) -> &Value {
+    args.first().unwrap_or(&Value::Undefined)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_pure_builtins_run_without_a_vm() {
+        let call = |path: &str, this: Value, args: &[Value]| pure_builtin(path).unwrap()(&this, args);
+        assert_eq!(call("parseInt", Value::Undefined, &[Value::string("42px")]).unwrap(), Value::Number(42.0));
+        assert_eq!(call("String.prototype.indexOf", Value::string("abc"), &[Value::string("c")]).unwrap(), Value::Number(2.0));
+        assert!(call("JSON.parse", Value::Undefined, &[Value::string("{")]).is_err());
+        assert_eq!(call("Math.pow", Value::Undefined, &[Value::Number(2.0), Value::Number(10.0)]).unwrap(), Value::Number(1024.0));
+        assert!(pure_builtin("Math.random").is_none());
+    }
+}
@@ -0,0 +1,83 @@
+//! Pseudo-Random Numbers for `Math.random`
+//!
+//! A xoshiro256** generator seeded through SplitMix64, so a given seed
+//! produces the same sequence on every platform. Without a seed the VM
+//! draws one from the OS entropy behind `RandomState`.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// xoshiro256** state
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// A generator whose sequence is fixed by `seed`
+    pub fn new(seed: u64) -> Self {
+        // SplitMix64 spreads the seed over the whole state, which is never
+        // all zero
+        let mut x = seed;
+        let mut next = || {
+            x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = x;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        Self { state: [next(), next(), next(), next()] }
+    }
+    
+    /// A generator seeded from OS entropy
+    pub fn from_entropy() -> Self {
+        Self::new(RandomState::new().build_hasher().finish())
+    }
+    
+    /// The next 64 random bits
+    pub fn next_u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s1.wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+    
+    /// A number in [0, 1) with 53 random bits
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+        assert_ne!(Rng::new(1).next_u64(), Rng::new(2).next_u64());
+    }
+    
+    #[test]
+    fn test_known_sequence_and_range() {
+        // Pinned so the sequence for a seed never changes between releases
+        let mut rng = Rng::new(0);
+        let first: Vec<u64> = (0..2).map(|_| rng.next_u64()).collect();
+        assert_eq!(first, vec![0x99EC_5F36_CB75_F2B4, 0xBF6E_1F78_4956_452A]);
+        
+        for _ in 0..1000 {
+            let x = rng.next_f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+    }
+}
@@ -0,0 +1,605 @@
+//! Snapshots of the global environment
+//!
+//! A new VM builds its globals by running the setup code of every
+//! built-in module. A [`Snapshot`] records the result instead: each
+//! global with its flags and value, and the graph of built-in objects
+//! behind them. `VM::with_snapshot` then sets up a VM by copying it.
+//!
+//! Native functions are recorded by the stable name they are registered
+//! under (`Math.random`, `Promise.all`, ...; see `Natives`) and linked
+//! back by name when a snapshot is read, so a snapshot saved as bytes
+//! stays valid across builds as long as those names do.
+//!
+//! The engine embeds a snapshot of the standard globals in
+//! `src/vm/snapshot.bin`, read by [`Snapshot::standard`]. After changing
+//! the built-ins, regenerate it with
+//! `V8_UPDATE_SNAPSHOT=1 cargo test --test snapshot_tests`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
+use crate::error::{Error, ErrorCode, Result};
+use super::machine::GlobalSlot;
+use super::value::{
+    BuiltinFunction, FunctionRef, NativeMethod, ObjectData, ObjectKind, PropertyAttributes,
+    PropertyMap, Value,
+};
+use super::symbol::{self, Symbol};
+use super::{array_global, builtins, json, number_global, object_global, promise};
+
+/// Start of every snapshot
+const MAGIC: &[u8; 8] = b"v8rsglob";
+
+/// Version of the format below; bumped on any change to it
+const VERSION: u32 = 3;
+
+/// The snapshot of the standard globals
+static STANDARD: &[u8] = include_bytes!("snapshot.bin");
+
+/// The global environment of a VM, detached from it
+///
+/// Values are recorded rather than shared, so one snapshot can set up
+/// any number of VMs on any thread.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Objects referred to by `Record::Object`, by index
+    objects: Vec<ObjectRecord>,
+    /// Globals, sorted by name
+    globals: Vec<GlobalRecord>,
+}
+
+#[derive(Debug, Clone)]
+struct GlobalRecord {
+    name: String,
+    writable: bool,
+    configurable: bool,
+    value: Record,
+}
+
+#[derive(Debug, Clone)]
+struct ObjectRecord {
+    /// Set for a built-in function with properties of its own, like
+    /// `Promise`; such a function has no prototype
+    builtin: Option<BuiltinRecord>,
+    /// Index of the prototype in `Snapshot::objects`
+    prototype: Option<usize>,
+    properties: Vec<(String, PropertyAttributes, Record)>,
+}
+
+/// A registered built-in function: its name, and what calling it and
+/// `new` run
+type BuiltinRecord = (&'static str, NativeMethod, Option<NativeMethod>);
+
+/// A value in a snapshot
+#[derive(Debug, Clone)]
+enum Record {
+    Undefined,
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    /// Index in `Snapshot::objects`
+    Object(usize),
+    Method(NativeMethod),
+    /// A well-known symbol, by its name as a property of `Symbol`
+    Symbol(&'static str),
+}
+
+impl Snapshot {
+    /// The standard globals, as every new VM has them
+    ///
+    /// The embedded bytes are read once per process. Fails only if they
+    /// are stale, naming a native that is no longer registered.
+    pub fn standard() -> Result<Arc<Snapshot>> {
+        static DECODED: OnceLock<std::result::Result<Arc<Snapshot>, String>> = OnceLock::new();
+        DECODED
+            .get_or_init(|| Snapshot::from_bytes(STANDARD).map(Arc::new).map_err(|error| match std::error::Error::source(&error) {
+                Some(cause) => format!("{}: {}", error, cause),
+                None => error.to_string(),
+            }))
+            .clone()
+            .map_err(|message| Error::runtime(ErrorCode::InvalidSnapshot, message, None))
+    }
+    
+    /// The bytes [`Snapshot::standard`] reads, as compiled into the engine
+    pub fn standard_bytes() -> &'static [u8] {
+        STANDARD
+    }
+    
+    /// Record `globals`
+    ///
+    /// Only what the built-ins are made of can be recorded: primitives,
+    /// well-known symbols, ordinary objects, and registered native
+    /// functions. Anything else, such as a script's closures or a host
+    /// function, is an error.
+    pub(crate) fn capture<'a>(globals: impl IntoIterator<Item = (&'a String, &'a GlobalSlot)>) -> Result<Self> {
+        let natives = Natives::get();
+        let mut capture = Capture { natives, objects: Vec::new(), ids: HashMap::new() };
+        let mut globals: Vec<_> = globals.into_iter().collect();
+        globals.sort_by(|a, b| a.0.cmp(b.0));
+        let mut records = Vec::with_capacity(globals.len());
+        for (name, slot) in globals {
+            let value = capture.record(&slot.value).map_err(|message| cannot_capture(name, message))?;
+            records.push(GlobalRecord {
+                name: name.clone(),
+                writable: slot.writable,
+                configurable: slot.configurable,
+                value,
+            });
+        }
+        Ok(Snapshot { objects: capture.objects, globals: records })
+    }
+    
+    /// Fresh globals holding what was recorded
+    pub(crate) fn instantiate(&self) -> HashMap<String, GlobalSlot> {
+        // Make every object first, so properties can refer to any of them
+        let instances: Vec<Value> = self.objects.iter().map(|object| match object.builtin {
+            Some((name, call, construct)) => Value::Function(FunctionRef::Builtin(Rc::new(BuiltinFunction {
+                name,
+                call,
+                construct,
+                properties: RefCell::new(PropertyMap::new()),
+            }))),
+            None => Value::Object(Rc::new(ObjectData::new())),
+        }).collect();
+        for (object, instance) in self.objects.iter().zip(&instances) {
+            let mut properties = PropertyMap::new();
+            for (key, attributes, value) in &object.properties {
+                properties.define(key.clone(), value.instantiate(&instances), *attributes);
+            }
+            match instance {
+                Value::Function(FunctionRef::Builtin(function)) => *function.properties.borrow_mut() = properties,
+                Value::Object(data) => {
+                    *data.properties.borrow_mut() = properties;
+                    if let Some(Value::Object(prototype)) = object.prototype.map(|index| &instances[index]) {
+                        data.set_prototype(Some(prototype.clone()));
+                    }
+                }
+                _ => unreachable!("instances are objects or built-in functions"),
+            }
+        }
+        self.globals.iter().map(|global| {
+            let slot = GlobalSlot {
+                value: global.value.instantiate(&instances),
+                writable: global.writable,
+                configurable: global.configurable,
+            };
+            (global.name.clone(), slot)
+        }).collect()
+    }
+    
+    /// The snapshot as bytes, for `from_bytes`
+    ///
+    /// The same globals always give the same bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let natives = Natives::get();
+        let mut writer = Writer::default();
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.u32(VERSION);
+        writer.u32(self.objects.len() as u32);
+        for object in &self.objects {
+            match (object.builtin, object.prototype) {
+                (Some((name, _, _)), _) => {
+                    writer.u8(1);
+                    writer.str(name);
+                }
+                (None, prototype) => {
+                    writer.u8(0);
+                    writer.u32(prototype.map_or(0, |index| index as u32 + 1));
+                }
+            }
+            writer.u32(object.properties.len() as u32);
+            for (key, attributes, value) in &object.properties {
+                writer.str(key);
+                writer.u8(u8::from(attributes.writable) | u8::from(attributes.enumerable) << 1 | u8::from(attributes.configurable) << 2);
+                writer.record(value, natives);
+            }
+        }
+        writer.u32(self.globals.len() as u32);
+        for global in &self.globals {
+            writer.str(&global.name);
+            writer.u8(u8::from(global.writable) | u8::from(global.configurable) << 1);
+            writer.record(&global.value, natives);
+        }
+        writer.bytes
+    }
+    
+    /// Read a snapshot written by `to_bytes`, linking its natives by name
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let natives = Natives::get();
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_snapshot("not a global environment snapshot".to_string()));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(invalid_snapshot(format!("version {} is not {}", version, VERSION)));
+        }
+        
+        let object_count = reader.u32()? as usize;
+        let mut objects = Vec::with_capacity(object_count.min(bytes.len()));
+        for _ in 0..object_count {
+            let (builtin, prototype) = match reader.u8()? {
+                0 => (None, reader.u32()?.checked_sub(1).map(|index| index as usize)),
+                1 => {
+                    let name = reader.str()?;
+                    (Some(natives.builtin(&name).ok_or_else(|| unregistered(&name))?), None)
+                }
+                kind => return Err(invalid_snapshot(format!("unknown object kind {}", kind))),
+            };
+            let property_count = reader.u32()? as usize;
+            let mut properties = Vec::with_capacity(property_count.min(bytes.len()));
+            for _ in 0..property_count {
+                let key = reader.str()?;
+                let flags = reader.u8()?;
+                let attributes = PropertyAttributes {
+                    writable: flags & 1 != 0,
+                    enumerable: flags & 2 != 0,
+                    configurable: flags & 4 != 0,
+                };
+                properties.push((key, attributes, reader.record(natives)?));
+            }
+            objects.push(ObjectRecord { builtin, prototype, properties });
+        }
+        
+        let global_count = reader.u32()? as usize;
+        let mut globals = Vec::with_capacity(global_count.min(bytes.len()));
+        for _ in 0..global_count {
+            let name = reader.str()?;
+            let flags = reader.u8()?;
+            let value = reader.record(natives)?;
+            globals.push(GlobalRecord { name, writable: flags & 1 != 0, configurable: flags & 2 != 0, value });
+        }
+        if reader.position != bytes.len() {
+            return Err(invalid_snapshot(format!("{} bytes left over", bytes.len() - reader.position)));
+        }
+        
+        let snapshot = Snapshot { objects, globals };
+        snapshot.check()?;
+        Ok(snapshot)
+    }
+    
+    /// Check that every object index is in range, and that prototypes
+    /// are ordinary objects
+    fn check(&self) -> Result<()> {
+        let in_range = |index: usize| -> Result<()> {
+            match self.objects.get(index) {
+                Some(_) => Ok(()),
+                None => Err(invalid_snapshot(format!("object {} of {} does not exist", index, self.objects.len()))),
+            }
+        };
+        let values = self.objects.iter()
+            .flat_map(|object| object.properties.iter().map(|(_, _, value)| value))
+            .chain(self.globals.iter().map(|global| &global.value));
+        for value in values {
+            if let Record::Object(index) = value {
+                in_range(*index)?;
+            }
+        }
+        for object in &self.objects {
+            if let Some(index) = object.prototype {
+                in_range(index)?;
+                if self.objects[index].builtin.is_some() {
+                    return Err(invalid_snapshot(format!("prototype {} is a function", index)));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Record {
+    fn instantiate(&self, instances: &[Value]) -> Value {
+        match self {
+            Record::Undefined => Value::Undefined,
+            Record::Null => Value::Null,
+            Record::Boolean(b) => Value::Boolean(*b),
+            Record::Number(n) => Value::Number(*n),
+            Record::String(s) => Value::string(s.as_str()),
+            Record::Object(index) => instances[*index].clone(),
+            Record::Method(method) => Value::Function(FunctionRef::NativeMethod(*method)),
+            Record::Symbol(name) => Value::Symbol(Symbol::well_known(name).expect("recorded symbols are well-known")),
+        }
+    }
+}
+
+/// Every native the standard globals refer to, by registration name
+struct Natives {
+    methods: Vec<(String, NativeMethod)>,
+    builtins: Vec<BuiltinRecord>,
+}
+
+impl Natives {
+    /// The registry, built once per process
+    fn get() -> &'static Natives {
+        static NATIVES: OnceLock<Natives> = OnceLock::new();
+        NATIVES.get_or_init(|| {
+            let mut methods: Vec<(String, NativeMethod)> = builtins::FUNCTIONS.iter()
+                .chain(&builtins::global_methods())
+                .map(|&(name, method)| (name.to_string(), method))
+                .collect();
+            let namespaces: [(&str, &[(&str, NativeMethod)]); 8] = [
+                ("console", &builtins::CONSOLE_METHODS),
+                ("Math", &builtins::MATH_METHODS),
+                ("JSON", &json::METHODS),
+                ("Object", &object_global::METHODS),
+                ("Number", &number_global::METHODS),
+                ("Array", &array_global::METHODS),
+                ("Promise", &promise::STATIC_METHODS),
+                ("Symbol", &symbol::STATIC_METHODS),
+            ];
+            for (namespace, table) in namespaces {
+                methods.extend(table.iter().map(|&(name, method)| (format!("{}.{}", namespace, name), method)));
+            }
+            Natives {
+                methods,
+                builtins: vec![promise::CONSTRUCTOR, symbol::CONSTRUCTOR],
+            }
+        })
+    }
+    
+    fn builtin(&self, name: &str) -> Option<BuiltinRecord> {
+        self.builtins.iter().find(|builtin| builtin.0 == name).copied()
+    }
+    
+    /// The first name `method` is registered under; methods shared by
+    /// several properties, like `console.log` and `console.warn`, are all
+    /// recorded under one of them
+    fn method_name(&self, method: NativeMethod) -> Option<&str> {
+        self.methods.iter().find(|(_, m)| std::ptr::fn_addr_eq(*m, method)).map(|(name, _)| name.as_str())
+    }
+}
+
+/// State of `Snapshot::capture`
+struct Capture {
+    natives: &'static Natives,
+    objects: Vec<ObjectRecord>,
+    /// Index of each object recorded so far, by address
+    ids: HashMap<*const (), usize>,
+}
+
+impl Capture {
+    /// Record `value`; the error says what couldn't be
+    fn record(&mut self, value: &Value) -> std::result::Result<Record, String> {
+        Ok(match value {
+            Value::Undefined => Record::Undefined,
+            Value::Null => Record::Null,
+            Value::Boolean(b) => Record::Boolean(*b),
+            Value::Number(n) => Record::Number(*n),
+            Value::String(s) => Record::String(s.to_string()),
+            Value::Object(data) => Record::Object(self.object(data)?),
+            Value::Function(FunctionRef::NativeMethod(method)) => {
+                self.natives.method_name(*method).ok_or("an unregistered native method")?;
+                Record::Method(*method)
+            }
+            Value::Function(FunctionRef::Builtin(function)) => Record::Object(self.builtin(function)?),
+            Value::Function(FunctionRef::Closure(_) | FunctionRef::Bytecode(_)) => return Err("a script function".to_string()),
+            Value::Function(FunctionRef::Host(_)) => return Err("a host function".to_string()),
+            Value::Function(FunctionRef::Bound(_)) => return Err("a bound function".to_string()),
+            Value::Symbol(symbol) => Record::Symbol(symbol.well_known_name().ok_or("a symbol")?),
+        })
+    }
+    
+    /// Reserve an index for the object at `address`; `None` if it already
+    /// has one
+    fn reserve(&mut self, address: *const ()) -> std::result::Result<usize, usize> {
+        if let Some(&index) = self.ids.get(&address) {
+            return Err(index);
+        }
+        let index = self.objects.len();
+        self.ids.insert(address, index);
+        self.objects.push(ObjectRecord { builtin: None, prototype: None, properties: Vec::new() });
+        Ok(index)
+    }
+    
+    fn object(&mut self, data: &Rc<ObjectData>) -> std::result::Result<usize, String> {
+        let index = match self.reserve(Rc::as_ptr(data).cast()) {
+            Ok(index) => index,
+            Err(index) => return Ok(index),
+        };
+        match data.kind {
+            ObjectKind::Ordinary => {}
+            // Its `constructor` is not in the property map
+            ObjectKind::FunctionPrototype(_) if data.weak_constructor().is_some() => return Err("a script function".to_string()),
+            ObjectKind::FunctionPrototype(_) => {}
+            ObjectKind::Array(_) => return Err("an array".to_string()),
+            ObjectKind::Generator(_) => return Err("a generator".to_string()),
+            ObjectKind::Promise(_) => return Err("a promise".to_string()),
+            ObjectKind::ListIterator { .. } | ObjectKind::IteratorRecord { .. } => return Err("an iterator".to_string()),
+        }
+        let prototype = match data.prototype() {
+            Some(prototype) => Some(self.object(&prototype)?),
+            None => None,
+        };
+        let properties = self.properties(&data.properties.borrow())?;
+        self.objects[index].prototype = prototype;
+        self.objects[index].properties = properties;
+        Ok(index)
+    }
+    
+    fn builtin(&mut self, function: &Rc<BuiltinFunction>) -> std::result::Result<usize, String> {
+        let index = match self.reserve(Rc::as_ptr(function).cast()) {
+            Ok(index) => index,
+            Err(index) => return Ok(index),
+        };
+        let registered = self.natives.builtin(function.name).filter(|&(_, call, construct)| {
+            std::ptr::fn_addr_eq(call, function.call)
+                && match (construct, function.construct) {
+                    (Some(a), Some(b)) => std::ptr::fn_addr_eq(a, b),
+                    (a, b) => a.is_none() && b.is_none(),
+                }
+        });
+        let builtin = registered.ok_or_else(|| format!("the unregistered built-in function {}", function.name))?;
+        let properties = self.properties(&function.properties.borrow())?;
+        self.objects[index].builtin = Some(builtin);
+        self.objects[index].properties = properties;
+        Ok(index)
+    }
+    
+    fn properties(&mut self, properties: &PropertyMap) -> std::result::Result<Vec<(String, PropertyAttributes, Record)>, String> {
+        if let Some((symbol, _, _)) = properties.symbols_with_attributes().next() {
+            return Err(format!("a property keyed by {}", symbol));
+        }
+        properties.iter_with_attributes()
+            .map(|(key, value, attributes)| {
+                let value = self.record(value).map_err(|message| format!("{} (property '{}')", message, key))?;
+                Ok((key.to_string(), attributes, value))
+            })
+            .collect()
+    }
+}
+
+fn cannot_capture(global: &str, what: String) -> Error {
+    Error::runtime(ErrorCode::SnapshotCapture, format!("Cannot snapshot global '{}': it holds {}", global, what), None)
+}
+
+fn invalid_snapshot(message: String) -> Error {
+    Error::from(io::Error::new(io::ErrorKind::InvalidData, message)).context("Invalid global environment snapshot")
+}
+
+fn unregistered(name: &str) -> Error {
+    invalid_snapshot(format!("no native is registered as '{}'", name))
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+    
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    
+    fn str(&mut self, value: &str) {
+        self.u32(value.len() as u32);
+        self.bytes.extend_from_slice(value.as_bytes());
+    }
+    
+    fn record(&mut self, record: &Record, natives: &Natives) {
+        match record {
+            Record::Undefined => self.u8(0),
+            Record::Null => self.u8(1),
+            Record::Boolean(b) => self.u8(2 + u8::from(*b)),
+            Record::Number(n) => {
+                self.u8(4);
+                self.bytes.extend_from_slice(&n.to_bits().to_le_bytes());
+            }
+            Record::String(s) => {
+                self.u8(5);
+                self.str(s);
+            }
+            Record::Object(index) => {
+                self.u8(6);
+                self.u32(*index as u32);
+            }
+            // Captured records only hold registered natives
+            Record::Method(method) => {
+                self.u8(8);
+                self.str(natives.method_name(*method).expect("recorded methods are registered"));
+            }
+            Record::Symbol(name) => {
+                self.u8(9);
+                self.str(name);
+            }
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8]> {
+        let end = self.position.checked_add(count).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid_snapshot(format!("truncated at byte {}", self.position)))?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+    
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+    
+    fn u32(&mut self) -> Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+    
+    fn str(&mut self) -> Result<String> {
+        let length = self.u32()? as usize;
+        let position = self.position;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| invalid_snapshot(format!("invalid UTF-8 at byte {}", position)))
+    }
+    
+    fn record(&mut self, natives: &Natives) -> Result<Record> {
+        Ok(match self.u8()? {
+            0 => Record::Undefined,
+            1 => Record::Null,
+            2 => Record::Boolean(false),
+            3 => Record::Boolean(true),
+            4 => {
+                let bytes = self.take(8)?;
+                Record::Number(f64::from_bits(u64::from_le_bytes(bytes.try_into().expect("took 8 bytes"))))
+            }
+            5 => Record::String(self.str()?),
+            6 => Record::Object(self.u32()? as usize),
+            8 => {
+                let name = self.str()?;
+                let method = natives.methods.iter().find(|(registered, _)| *registered == name);
+                Record::Method(method.ok_or_else(|| unregistered(&name))?.1)
+            }
+            9 => {
+                let name = self.str()?;
+                let symbol = symbol::WELL_KNOWN.into_iter().find(|known| *known == name);
+                Record::Symbol(symbol.ok_or_else(|| invalid_snapshot(format!("no well-known symbol is named '{}'", name)))?)
+            }
+            tag => return Err(invalid_snapshot(format!("unknown value tag {} at byte {}", tag, self.position - 1))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    
+    #[test]
+    fn test_registration_names_are_unique() {
+        let natives = Natives::get();
+        let tables: [Vec<&str>; 2] = [
+            natives.methods.iter().map(|(name, _)| name.as_str()).collect(),
+            natives.builtins.iter().map(|builtin| builtin.0).collect(),
+        ];
+        for names in tables {
+            let unique: HashSet<&str> = names.iter().copied().collect();
+            assert_eq!(unique.len(), names.len(), "{:?}", names);
+        }
+    }
+    
+    #[test]
+    fn test_shared_objects_are_recorded_once() {
+        let shared = Value::Object(Rc::new(ObjectData::new()));
+        let globals: HashMap<String, GlobalSlot> = ["a", "b"].into_iter()
+            .map(|name| (name.to_string(), GlobalSlot::new(shared.clone())))
+            .collect();
+        let snapshot = Snapshot::capture(&globals).unwrap();
+        assert_eq!(snapshot.objects.len(), 1);
+        
+        let restored = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap().instantiate();
+        match (&restored["a"].value, &restored["b"].value) {
+            (Value::Object(a), Value::Object(b)) => assert!(Rc::ptr_eq(a, b)),
+            other => panic!("expected objects, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Symbols and the Symbol Global
+//!
+//! A symbol is a primitive with identity: `Symbol('a') !== Symbol('a')`,
+//! the description being only a label for display. Symbols key
+//! properties as strings do, but `PropertyMap` keeps them apart from the
+//! string-keyed ones, so `Object.keys`, object spread and
+//! `JSON.stringify` never see them.
+//!
+//! `Symbol.for` hands out symbols from a registry kept per VM. The
+//! well-known symbols, of which only `Symbol.iterator` exists so far, are
+//! shared by every VM on a thread instead, so the VM can look them up
+//! without going through the `Symbol` global and snapshots can record
+//! them by name.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+use crate::error::{Error, ErrorCode, Result};
+use super::context::CallContext;
+use super::value::{BuiltinFunction, FunctionRef, NativeMethod, PropertyAttributes, PropertyMap, Value};
+
+/// A symbol; compare with `==`, which is identity
+pub struct Symbol {
+    description: Option<String>,
+}
+
+/// Names of the well-known symbols, as properties of `Symbol`
+pub(crate) const WELL_KNOWN: [&str; 1] = ["iterator"];
+
+thread_local! {
+    static ITERATOR: Rc<Symbol> = Symbol::new(Some("Symbol.iterator".to_string()));
+}
+
+impl Symbol {
+    /// A new symbol, unequal to every other
+    pub fn new(description: Option<String>) -> Rc<Self> {
+        Rc::new(Self { description })
+    }
+
+    /// The description given when the symbol was made, if any
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// `Symbol.iterator`: the method iteration calls for an iterator
+    pub fn iterator() -> Rc<Self> {
+        ITERATOR.with(Rc::clone)
+    }
+
+    /// The well-known symbol `Symbol.<name>`
+    pub(crate) fn well_known(name: &str) -> Option<Rc<Self>> {
+        match name {
+            "iterator" => Some(Self::iterator()),
+            _ => None,
+        }
+    }
+
+    /// The name this symbol has as a property of `Symbol`, if it is a
+    /// well-known one
+    pub(crate) fn well_known_name(&self) -> Option<&'static str> {
+        WELL_KNOWN.into_iter().find(|name| Self::well_known(name).is_some_and(|symbol| std::ptr::eq(&*symbol, self)))
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// `Symbol(description)`, as the console shows it
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Symbol({})", self.description().unwrap_or(""))
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+/// The TypeError for a symbol used where a string or number is needed
+pub(crate) fn conversion_error(target: &str) -> Error {
+    Error::runtime(ErrorCode::SymbolConversion, format!("TypeError: Cannot convert a Symbol value to a {}", target), None)
+}
+
+// === The Symbol Global ===
+
+/// Create the `Symbol` function with its static methods and the
+/// well-known symbols
+pub fn create_constructor() -> Value {
+    let mut properties = PropertyMap::new();
+    for (name, method) in STATIC_METHODS {
+        properties.insert(name.to_string(), Value::Function(FunctionRef::NativeMethod(method)));
+    }
+    for name in WELL_KNOWN {
+        let symbol = Symbol::well_known(name).expect("well-known names have symbols");
+        properties.define(name.to_string(), Value::Symbol(symbol), PropertyAttributes::NONE);
+    }
+    let (name, call, construct) = CONSTRUCTOR;
+    Value::Function(FunctionRef::Builtin(Rc::new(BuiltinFunction {
+        name,
+        call,
+        construct,
+        properties: RefCell::new(properties),
+    })))
+}
+
+/// `Symbol` itself: its name, and what calling it and `new` run
+pub(crate) const CONSTRUCTOR: (&str, NativeMethod, Option<NativeMethod>) = ("Symbol", symbol_call, Some(symbol_construct));
+
+/// The static methods of `Symbol`
+pub(crate) const STATIC_METHODS: [(&str, NativeMethod); 2] = [
+    ("for", symbol_for),
+    ("keyFor", symbol_key_for),
+];
+
+/// Symbol(description) - A new symbol; the description is converted to
+/// a string unless it is undefined
+fn symbol_call(cx: &mut CallContext) -> Result<Value> {
+    let description = match cx.arg(0) {
+        Value::Undefined => None,
+        description => Some(cx.vm.to_js_string(&description)?),
+    };
+    Ok(Value::Symbol(Symbol::new(description)))
+}
+
+/// new Symbol() - Always a TypeError; symbols are primitives
+fn symbol_construct(_cx: &mut CallContext) -> Result<Value> {
+    Err(Error::runtime(ErrorCode::NotConstructor, "TypeError: Symbol is not a constructor", None))
+}
+
+/// Symbol.for(key) - The symbol registered under `key`, registering a
+/// new one the first time
+fn symbol_for(cx: &mut CallContext) -> Result<Value> {
+    let key = cx.vm.to_js_string(&cx.arg(0))?;
+    Ok(Value::Symbol(cx.vm.registered_symbol(key)))
+}
+
+/// Symbol.keyFor(symbol) - The key a symbol is registered under, or
+/// undefined for one `Symbol.for` didn't make
+fn symbol_key_for(cx: &mut CallContext) -> Result<Value> {
+    match cx.arg(0) {
+        Value::Symbol(symbol) => Ok(cx.vm.symbol_registry_key(&symbol).map_or(Value::Undefined, Value::string)),
+        other => Err(Error::runtime(ErrorCode::InvalidArgument, format!("TypeError: {} is not a symbol", other), None)),
+    }
+}
@@ -0,0 +1,133 @@
+//! Timer and Microtask Queues
+//!
+//! Callbacks scheduled by `setTimeout` and `queueMicrotask` wait here until
+//! the host pumps the loop with `VM::run_pending`. Time is whatever the
+//! host says it is: the VM never reads a clock.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use super::value::Value;
+
+/// A scheduled callback and the arguments to call it with
+#[derive(Debug, Clone)]
+pub struct Task {
+    pub callback: Value,
+    pub args: Vec<Value>,
+}
+
+/// Pending microtasks and timers
+#[derive(Debug, Default)]
+pub struct TaskQueue {
+    /// Microtasks in the order they were queued
+    microtasks: VecDeque<Task>,
+    /// Timers keyed by (due time, id); ids grow, so equal due times fire
+    /// in the order they were scheduled
+    timers: BTreeMap<(u64, u32), Task>,
+    /// Id of the most recently scheduled timer
+    last_timer_id: u32,
+    /// Host time of the most recent pump, in milliseconds
+    now: u64,
+}
+
+impl TaskQueue {
+    /// Queue a microtask
+    pub fn queue_microtask(&mut self, task: Task) {
+        self.microtasks.push_back(task);
+    }
+    
+    /// Schedule a timer `delay` milliseconds after the current time
+    ///
+    /// Returns the timer id, starting from 1.
+    pub fn set_timeout(&mut self, task: Task, delay: u64) -> u32 {
+        self.last_timer_id += 1;
+        self.timers.insert((self.now.saturating_add(delay), self.last_timer_id), task);
+        self.last_timer_id
+    }
+    
+    /// Cancel a timer; unknown and already-fired ids are ignored
+    pub fn clear_timeout(&mut self, id: u32) {
+        self.timers.retain(|&(_, timer_id), _| timer_id != id);
+    }
+    
+    /// Advance the current time; the clock never moves backwards
+    pub fn advance_to(&mut self, now: u64) {
+        self.now = self.now.max(now);
+    }
+    
+    /// The current time as of the last `advance_to`
+    pub fn now(&self) -> u64 {
+        self.now
+    }
+    
+    /// Take the oldest microtask
+    pub fn next_microtask(&mut self) -> Option<Task> {
+        self.microtasks.pop_front()
+    }
+    
+    /// Take the earliest timer that is due and was scheduled with an id
+    /// up to `max_id`
+    pub fn next_due_timer(&mut self, max_id: u32) -> Option<Task> {
+        let key = self.timers.keys()
+            .take_while(|&&(due, _)| due <= self.now)
+            .find(|&&(_, id)| id <= max_id)
+            .copied()?;
+        self.timers.remove(&key)
+    }
+    
+    /// Id of the most recently scheduled timer
+    pub fn last_timer_id(&self) -> u32 {
+        self.last_timer_id
+    }
+    
+    /// Whether any microtask or timer is waiting
+    pub fn has_pending(&self) -> bool {
+        !self.microtasks.is_empty() || !self.timers.is_empty()
+    }
+    
+    /// The callbacks and arguments of every waiting task
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        self.microtasks.iter().chain(self.timers.values())
+            .flat_map(|task| std::iter::once(&task.callback).chain(&task.args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn task(n: f64) -> Task {
+        Task { callback: Value::Number(n), args: Vec::new() }
+    }
+    
+    #[test]
+    fn test_timers_fire_by_due_time_then_insertion() {
+        let mut queue = TaskQueue::default();
+        queue.set_timeout(task(1.0), 20);
+        queue.set_timeout(task(2.0), 10);
+        queue.set_timeout(task(3.0), 10);
+        
+        queue.advance_to(15);
+        let max_id = queue.last_timer_id();
+        assert_eq!(queue.next_due_timer(max_id).unwrap().callback, Value::Number(2.0));
+        assert_eq!(queue.next_due_timer(max_id).unwrap().callback, Value::Number(3.0));
+        assert!(queue.next_due_timer(max_id).is_none());
+        assert!(queue.has_pending());
+        
+        queue.advance_to(20);
+        assert_eq!(queue.next_due_timer(max_id).unwrap().callback, Value::Number(1.0));
+        assert!(!queue.has_pending());
+    }
+    
+    #[test]
+    fn test_clear_timeout_and_monotonic_clock() {
+        let mut queue = TaskQueue::default();
+        let id = queue.set_timeout(task(1.0), 0);
+        queue.clear_timeout(id);
+        queue.clear_timeout(99);
+        assert!(!queue.has_pending());
+        
+        queue.advance_to(50);
+        queue.advance_to(10);
+        assert_eq!(queue.now(), 50);
+    }
+}
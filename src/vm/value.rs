@@ -3,12 +3,25 @@
 //! This module implements the dynamic value system for JavaScript,
 //! supporting all primitive types and type coercion rules.
 
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
 use std::fmt;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+use crate::bytecode::BytecodeFunction;
+use crate::error::{Error, ErrorCode, Result};
+use crate::lexer::{EscapeValidator, UnicodeHelper};
+use super::frame::{CallFrame, Environment};
+use super::context::CallContext;
+use super::gc;
+use super::promise::PromiseState;
+use super::symbol::Symbol;
+use super::atom::{Atom, AtomKey};
 
 /// JavaScript value types
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Default)]
 pub enum Value {
     /// JavaScript number (always f64)
     Number(f64),
@@ -19,30 +32,1033 @@ pub enum Value {
     /// JavaScript null
     Null,
     /// JavaScript undefined
+    #[default]
     Undefined,
-    /// JavaScript object (placeholder for now)
+    /// JavaScript symbol; equal only to itself
+    Symbol(Rc<Symbol>),
+    /// JavaScript object (ordinary object or array)
     Object(Rc<ObjectData>),
     /// JavaScript function reference
     Function(FunctionRef),
 }
 
-/// Object data (simplified for now)
+/// What a computed member names: ToPropertyKey's result
 #[derive(Debug, Clone, PartialEq)]
+pub enum PropertyKey {
+    String(String),
+    Symbol(Rc<Symbol>),
+}
+
+impl fmt::Display for PropertyKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyKey::String(key) => write!(f, "{}", key),
+            PropertyKey::Symbol(symbol) => write!(f, "{}", symbol),
+        }
+    }
+}
+
+/// The flags of an own data property
+///
+/// Properties created by assignment or an object literal have all three;
+/// `Object.defineProperty` can clear them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropertyAttributes {
+    /// Assignment can change the value
+    pub writable: bool,
+    /// `Object.keys`, spread and `JSON.stringify` see the property
+    pub enumerable: bool,
+    /// `delete` can remove the property and `defineProperty` can change it
+    pub configurable: bool,
+}
+
+impl PropertyAttributes {
+    /// The attributes of an ordinary assigned property
+    pub const DEFAULT: Self = Self { writable: true, enumerable: true, configurable: true };
+    
+    /// Read-only, hidden and permanent, as `defineProperty` makes by default
+    pub const NONE: Self = Self { writable: false, enumerable: false, configurable: false };
+}
+
+impl Default for PropertyAttributes {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Own properties of an object, kept in insertion order
+///
+/// Property enumeration order is observable in JavaScript, so a plain
+/// `HashMap` is not enough; the index map keeps lookups O(1). String keys
+/// are [`Atom`]s, so the index hashes addresses, not names.
+///
+/// Symbol-keyed properties are kept apart, and only the `symbol`
+/// methods reach them: everything that lists properties lists string
+/// keys only.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PropertyMap {
+    entries: Vec<(Atom, Value, PropertyAttributes)>,
+    index: HashMap<Atom, usize>,
+    /// Symbol-keyed properties in insertion order; objects rarely have
+    /// more than a few, so they are searched linearly
+    symbols: Vec<(Rc<Symbol>, Value, PropertyAttributes)>,
+}
+
+impl PropertyMap {
+    /// Create an empty property map
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Where `key` sits in insertion order
+    ///
+    /// An empty map needs no lookup, and a name that was never interned
+    /// is in no map.
+    fn find<K: AtomKey + ?Sized>(&self, key: &K) -> Option<usize> {
+        if self.index.is_empty() {
+            return None;
+        }
+        self.index.get(&*key.atom()?).copied()
+    }
+    
+    /// Get a property value by key
+    pub fn get<K: AtomKey + ?Sized>(&self, key: &K) -> Option<&Value> {
+        self.find(key).map(|i| &self.entries[i].1)
+    }
+    
+    /// Where a property sits in insertion order, for `vm::property_cache`
+    pub(crate) fn position<K: AtomKey + ?Sized>(&self, key: &K) -> Option<usize> {
+        self.find(key)
+    }
+    
+    /// The key and value at a position given by `position`
+    pub(crate) fn entry_at(&self, position: usize) -> Option<(&Atom, &Value)> {
+        self.entries.get(position).map(|(key, value, _)| (key, value))
+    }
+    
+    /// Attributes of a property, `None` if it does not exist
+    pub fn attributes<K: AtomKey + ?Sized>(&self, key: &K) -> Option<PropertyAttributes> {
+        self.find(key).map(|i| self.entries[i].2)
+    }
+    
+    /// Set a property, keeping the original position of existing keys
+    ///
+    /// An existing property keeps its attributes, even if it is read-only;
+    /// a new one gets the default attributes. Checking `writable` is up to
+    /// the caller.
+    pub fn insert(&mut self, key: impl Into<Atom>, value: Value) {
+        let key = key.into();
+        if let Some(&i) = self.index.get(&key) {
+            self.entries[i].1 = value;
+        } else {
+            self.index.insert(key.clone(), self.entries.len());
+            self.entries.push((key, value, PropertyAttributes::DEFAULT));
+        }
+    }
+    
+    /// Assign a property as `insert` does, unless it exists and is
+    /// read-only; returns whether the value was written
+    pub fn assign<K: AtomKey + ?Sized>(&mut self, key: &K, value: Value) -> bool {
+        match self.find(key) {
+            Some(i) if !self.entries[i].2.writable => false,
+            Some(i) => {
+                self.entries[i].1 = value;
+                true
+            }
+            None => {
+                self.insert(key.to_atom(), value);
+                true
+            }
+        }
+    }
+    
+    /// Set a property together with its attributes, keeping the original
+    /// position of an existing key
+    pub fn define(&mut self, key: impl Into<Atom>, value: Value, attributes: PropertyAttributes) {
+        let key = key.into();
+        if let Some(&i) = self.index.get(&key) {
+            self.entries[i].1 = value;
+            self.entries[i].2 = attributes;
+        } else {
+            self.index.insert(key.clone(), self.entries.len());
+            self.entries.push((key, value, attributes));
+        }
+    }
+    
+    /// Leave exactly the properties `keys`, in that order, all undefined
+    /// with the default attributes
+    ///
+    /// When the map already has those keys in that order it keeps its
+    /// index and only resets the values, so refilling an object of the
+    /// same shape allocates nothing.
+    pub(crate) fn reset_to_keys<'k>(&mut self, keys: impl ExactSizeIterator<Item = &'k str> + Clone) {
+        self.symbols.clear();
+        let same_keys = self.entries.len() == keys.len()
+            && self.entries.iter().zip(keys.clone()).all(|(entry, key)| &*entry.0 == key);
+        if same_keys {
+            for entry in &mut self.entries {
+                entry.1 = Value::Undefined;
+                entry.2 = PropertyAttributes::DEFAULT;
+            }
+        } else {
+            self.entries.clear();
+            self.index.clear();
+            for key in keys {
+                self.insert(key, Value::Undefined);
+            }
+        }
+    }
+    
+    /// Set every property to undefined, dropping the values but keeping
+    /// the string keys for `reset_to_keys`; symbol keys go too
+    pub(crate) fn clear_values(&mut self) {
+        for entry in &mut self.entries {
+            entry.1 = Value::Undefined;
+        }
+        self.symbols.clear();
+    }
+    
+    /// Values of the string- and symbol-keyed properties
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        let named = self.entries.iter().map(|(_, value, _)| value);
+        named.chain(self.symbols.iter().map(|(_, value, _)| value))
+    }
+    
+    /// Property values in insertion order
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries.iter_mut().map(|(_, value, _)| value)
+    }
+    
+    /// Check whether a property exists
+    pub fn contains_key<K: AtomKey + ?Sized>(&self, key: &K) -> bool {
+        self.find(key).is_some()
+    }
+    
+    /// Remove a property, keeping the order of the remaining keys
+    pub fn remove<K: AtomKey + ?Sized>(&mut self, key: &K) -> Option<Value> {
+        let i = self.find(key)?;
+        let (key, value, _) = self.entries.remove(i);
+        self.index.remove(&key);
+        for position in self.index.values_mut().filter(|position| **position > i) {
+            *position -= 1;
+        }
+        Some(value)
+    }
+    
+    /// The `delete` operator on this map: remove a property unless it is
+    /// non-configurable, returning whether it is gone
+    pub fn delete<K: AtomKey + ?Sized>(&mut self, key: &K) -> bool {
+        if self.attributes(key).is_some_and(|attributes| !attributes.configurable) {
+            return false;
+        }
+        self.remove(key);
+        true
+    }
+    
+    /// Iterate over properties in insertion order, enumerable or not
+    pub fn iter(&self) -> impl Iterator<Item = (&Atom, &Value)> {
+        self.entries.iter().map(|(k, v, _)| (k, v))
+    }
+    
+    /// Iterate over properties and their attributes in insertion order
+    pub fn iter_with_attributes(&self) -> impl Iterator<Item = (&Atom, &Value, PropertyAttributes)> {
+        self.entries.iter().map(|(k, v, attributes)| (k, v, *attributes))
+    }
+    
+    /// Iterate over the enumerable properties in insertion order
+    pub fn enumerable(&self) -> impl Iterator<Item = (&Atom, &Value)> {
+        self.entries.iter()
+            .filter(|(_, _, attributes)| attributes.enumerable)
+            .map(|(k, v, _)| (k, v))
+    }
+    
+    /// Property keys in insertion order, enumerable or not
+    pub fn keys(&self) -> impl Iterator<Item = &Atom> {
+        self.entries.iter().map(|(k, _, _)| k)
+    }
+    
+    /// Get a symbol-keyed property value
+    pub fn get_symbol(&self, symbol: &Symbol) -> Option<&Value> {
+        self.symbol_entry(symbol).map(|(_, value, _)| value)
+    }
+    
+    /// Attributes of a symbol-keyed property, `None` if it does not exist
+    pub fn symbol_attributes(&self, symbol: &Symbol) -> Option<PropertyAttributes> {
+        self.symbol_entry(symbol).map(|(_, _, attributes)| *attributes)
+    }
+    
+    /// `assign` for a symbol key
+    pub fn assign_symbol(&mut self, symbol: &Rc<Symbol>, value: Value) -> bool {
+        match self.symbols.iter_mut().find(|(key, _, _)| **key == **symbol) {
+            Some((_, _, attributes)) if !attributes.writable => false,
+            Some((_, current, _)) => {
+                *current = value;
+                true
+            }
+            None => {
+                self.symbols.push((symbol.clone(), value, PropertyAttributes::DEFAULT));
+                true
+            }
+        }
+    }
+    
+    /// `define` for a symbol key
+    pub fn define_symbol(&mut self, symbol: Rc<Symbol>, value: Value, attributes: PropertyAttributes) {
+        match self.symbols.iter_mut().find(|(key, _, _)| *key == symbol) {
+            Some(entry) => {
+                entry.1 = value;
+                entry.2 = attributes;
+            }
+            None => self.symbols.push((symbol, value, attributes)),
+        }
+    }
+    
+    /// `delete` for a symbol key
+    pub fn delete_symbol(&mut self, symbol: &Symbol) -> bool {
+        match self.symbols.iter().position(|(key, _, _)| **key == *symbol) {
+            Some(i) if !self.symbols[i].2.configurable => false,
+            Some(i) => {
+                self.symbols.remove(i);
+                true
+            }
+            None => true,
+        }
+    }
+    
+    /// Symbol-keyed properties and their attributes in insertion order
+    pub fn symbols_with_attributes(&self) -> impl Iterator<Item = (&Rc<Symbol>, &Value, PropertyAttributes)> {
+        self.symbols.iter().map(|(k, v, attributes)| (k, v, *attributes))
+    }
+    
+    fn symbol_entry(&self, symbol: &Symbol) -> Option<&(Rc<Symbol>, Value, PropertyAttributes)> {
+        self.symbols.iter().find(|(key, _, _)| **key == *symbol)
+    }
+    
+    /// Number of properties
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    /// Check if there are no properties
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Object data shared by reference between values
+///
+/// Objects are mutated through `&self` (they live behind an `Rc`), so the
+/// storage uses `RefCell`. Equality is identity, as in JavaScript.
 pub struct ObjectData {
-    pub properties: HashMap<String, Value>,
+    /// Own named properties
+    pub properties: RefCell<PropertyMap>,
+    /// What kind of object this is
+    pub kind: ObjectKind,
+    /// Object that property lookups fall back to, as set up by `new`,
+    /// `Object.create` or `Object.setPrototypeOf`
+    prototype: RefCell<Option<Rc<ObjectData>>>,
+}
+
+/// Exotic behaviour attached to an object
+pub enum ObjectKind {
+    /// Ordinary object
+    Ordinary,
+    /// Array with dense element storage; `None` is a hole
+    Array(RefCell<Vec<Option<Value>>>),
+    /// Generator object returned by calling a `function*`
+    Generator(RefCell<GeneratorState>),
+    /// Promise returned by calling an `async function`
+    Promise(RefCell<PromiseState>),
+    /// Iterator over an array's elements or a string's code points
+    ListIterator {
+        /// The array or string being iterated
+        source: Value,
+        /// Next element index (arrays) or byte offset (strings)
+        position: Cell<usize>,
+    },
+    /// An iterator returned by a `[Symbol.iterator]` method, with the
+    /// `next` method read from it once, as the spec's Iterator Record
+    /// holds them
+    IteratorRecord {
+        iterator: Value,
+        next: Value,
+        /// Set once `next` reports done or fails, or the loop is left
+        done: Cell<bool>,
+    },
+    /// The `prototype` object of a constructible function, whose hidden
+    /// `constructor` property refers back to the function weakly, so the
+    /// two don't keep each other alive
+    ///
+    /// Once the function is dropped, `constructor` is gone. Writing,
+    /// deleting or redefining it first makes it an ordinary property.
+    FunctionPrototype(RefCell<Weak<Closure>>),
+}
+
+/// Where a generator is in its lifetime
+pub enum GeneratorState {
+    /// Waiting for `next()`: the frame and its operand stack, detached
+    /// from the VM. A new generator is suspended at its first instruction.
+    Suspended {
+        frame: CallFrame,
+        operands: Vec<Value>,
+    },
+    /// Inside a `next()` call
+    Running,
+    /// Returned, threw, or closed by `return()`
+    Completed,
+}
+
+impl ObjectData {
+    /// Create an empty ordinary object
+    pub fn new() -> Self {
+        Self::with_properties(PropertyMap::new())
+    }
+    
+    /// Move the object to the heap
+    ///
+    /// Objects made this way while a VM runs are the ones GC stress mode
+    /// checks (see `vm::gc`).
+    pub fn into_rc(self) -> Rc<Self> {
+        let object = Rc::new(self);
+        gc::track(&object);
+        object
+    }
+    
+    /// Create an ordinary object with the given properties
+    pub fn with_properties(properties: PropertyMap) -> Self {
+        Self {
+            properties: RefCell::new(properties),
+            kind: ObjectKind::Ordinary,
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Create an empty ordinary object inheriting from `prototype`
+    pub fn with_prototype(prototype: Option<Rc<ObjectData>>) -> Self {
+        let object = Self::new();
+        *object.prototype.borrow_mut() = prototype;
+        object
+    }
+    
+    /// Create an array from its elements (`None` for holes)
+    pub fn new_array(elements: Vec<Option<Value>>) -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::Array(RefCell::new(elements)),
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Create a generator suspended before the first instruction of `frame`
+    pub fn new_generator(frame: CallFrame) -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::Generator(RefCell::new(GeneratorState::Suspended { frame, operands: Vec::new() })),
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Create a pending promise
+    pub fn new_promise() -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::Promise(RefCell::new(PromiseState::Pending(Vec::new()))),
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Create an iterator over an array or string
+    pub fn new_list_iterator(source: Value) -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::ListIterator { source, position: Cell::new(0) },
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Wrap an iterator a `[Symbol.iterator]` method returned
+    pub fn new_iterator_record(iterator: Value, next: Value) -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::IteratorRecord { iterator, next, done: Cell::new(false) },
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// Create the `prototype` object of `constructor`
+    pub fn new_function_prototype(constructor: &Rc<Closure>) -> Self {
+        Self {
+            properties: RefCell::new(PropertyMap::new()),
+            kind: ObjectKind::FunctionPrototype(RefCell::new(Rc::downgrade(constructor))),
+            prototype: RefCell::new(None),
+        }
+    }
+    
+    /// The function a `prototype` object still refers back to weakly
+    pub(crate) fn weak_constructor(&self) -> Option<Value> {
+        match &self.kind {
+            ObjectKind::FunctionPrototype(constructor) => constructor.borrow().upgrade()
+                .map(|closure| Value::Function(FunctionRef::Closure(closure))),
+            _ => None,
+        }
+    }
+    
+    /// Before `key` is written, deleted or redefined, turn a weak
+    /// `constructor` into an ordinary hidden property
+    pub(crate) fn settle_constructor<K: AtomKey + ?Sized>(&self, key: &K) {
+        let ObjectKind::FunctionPrototype(constructor) = &self.kind else { return };
+        if key.as_str() != "constructor" {
+            return;
+        }
+        if let Some(closure) = constructor.replace(Weak::new()).upgrade() {
+            let hidden = PropertyAttributes { enumerable: false, ..PropertyAttributes::DEFAULT };
+            self.properties.borrow_mut().define(key.to_atom(), Value::Function(FunctionRef::Closure(closure)), hidden);
+        }
+    }
+    
+    /// Turn this object back into a fresh one of its kind with only the
+    /// properties `keys` (see `PropertyMap::reset_to_keys`), no elements
+    /// and no prototype, reusing its storage
+    ///
+    /// Only for objects nothing else can see, such as the frame arena's.
+    pub(crate) fn reset<'k>(&self, keys: impl ExactSizeIterator<Item = &'k str> + Clone) {
+        self.properties.borrow_mut().reset_to_keys(keys);
+        *self.prototype.borrow_mut() = None;
+        if let ObjectKind::Array(elements) = &self.kind {
+            elements.borrow_mut().clear();
+        }
+    }
+    
+    /// Drop everything this object refers to, keeping its storage and
+    /// property keys for a later `reset`
+    pub(crate) fn release_values(&self) {
+        self.properties.borrow_mut().clear_values();
+        *self.prototype.borrow_mut() = None;
+        if let ObjectKind::Array(elements) = &self.kind {
+            elements.borrow_mut().clear();
+        }
+    }
+    
+    /// Drop everything this object refers to and leave its cells
+    /// borrowed, so that any later use panics; false if a cell is in use
+    ///
+    /// For GC stress mode, on objects a collector would have freed.
+    pub(crate) fn poison(&self) -> bool {
+        let (Ok(mut properties), Ok(mut prototype)) = (self.properties.try_borrow_mut(), self.prototype.try_borrow_mut()) else {
+            return false;
+        };
+        *properties = PropertyMap::new();
+        *prototype = None;
+        std::mem::forget(properties);
+        std::mem::forget(prototype);
+        match &self.kind {
+            ObjectKind::Array(elements) => {
+                if let Ok(mut elements) = elements.try_borrow_mut() {
+                    elements.clear();
+                    std::mem::forget(elements);
+                }
+            }
+            ObjectKind::Generator(state) => {
+                if let Ok(mut state) = state.try_borrow_mut() {
+                    *state = GeneratorState::Completed;
+                    std::mem::forget(state);
+                }
+            }
+            ObjectKind::Promise(state) => {
+                if let Ok(mut state) = state.try_borrow_mut() {
+                    *state = PromiseState::Pending(Vec::new());
+                    std::mem::forget(state);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+    
+    /// Element storage if this object is an array
+    pub fn array_elements(&self) -> Option<&RefCell<Vec<Option<Value>>>> {
+        match &self.kind {
+            ObjectKind::Array(elements) => Some(elements),
+            _ => None,
+        }
+    }
+    
+    /// Generator state if this object is a generator
+    pub fn generator_state(&self) -> Option<&RefCell<GeneratorState>> {
+        match &self.kind {
+            ObjectKind::Generator(state) => Some(state),
+            _ => None,
+        }
+    }
+    
+    /// Promise state if this object is a promise
+    pub fn promise_state(&self) -> Option<&RefCell<PromiseState>> {
+        match &self.kind {
+            ObjectKind::Promise(state) => Some(state),
+            _ => None,
+        }
+    }
+    
+    /// Check whether this object is an array
+    pub fn is_array(&self) -> bool {
+        self.array_elements().is_some()
+    }
+    
+    /// Get an own property (array elements and `length` included)
+    pub fn get<K: AtomKey + ?Sized>(&self, key: &K) -> Option<Value> {
+        if let Some(elements) = self.array_elements() {
+            if key.as_str() == "length" {
+                return Some(Value::Number(elements.borrow().len() as f64));
+            }
+            if let Some(index) = array_index(key.as_str()) {
+                return elements.borrow().get(index).cloned().flatten();
+            }
+        }
+        let value = self.properties.borrow().get(key).cloned();
+        value.or_else(|| if key.as_str() == "constructor" { self.weak_constructor() } else { None })
+    }
+    
+    /// Whether `key` is an own property (array elements and `length` included)
+    pub fn has_own<K: AtomKey + ?Sized>(&self, key: &K) -> bool {
+        if let Some(elements) = self.array_elements() {
+            if key.as_str() == "length" {
+                return true;
+            }
+            if let Some(index) = array_index(key.as_str()) {
+                return elements.borrow().get(index).is_some_and(Option::is_some);
+            }
+        }
+        self.properties.borrow().contains_key(key)
+            || (key.as_str() == "constructor" && self.weak_constructor().is_some())
+    }
+    
+    /// The object this one inherits from
+    pub fn prototype(&self) -> Option<Rc<ObjectData>> {
+        self.prototype.borrow().clone()
+    }
+    
+    /// Make this object inherit from `prototype`
+    ///
+    /// Returns false, changing nothing, when that would make the
+    /// prototype chain a cycle.
+    pub fn set_prototype(&self, prototype: Option<Rc<ObjectData>>) -> bool {
+        if let Some(prototype) = &prototype {
+            if std::ptr::eq(&**prototype, self) || prototype.inherits_from(self) {
+                return false;
+            }
+        }
+        *self.prototype.borrow_mut() = prototype;
+        true
+    }
+    
+    /// Whether `key` is an own or inherited property
+    pub fn has_property<K: AtomKey + ?Sized>(&self, key: &K) -> bool {
+        if self.has_own(key) {
+            return true;
+        }
+        let mut object = self.prototype();
+        while let Some(current) = object {
+            if current.has_own(key) {
+                return true;
+            }
+            object = current.prototype();
+        }
+        false
+    }
+    
+    /// Delete an own property, leaving a hole for array elements
+    ///
+    /// Returns false only for a property that cannot be deleted: a
+    /// non-configurable one or an array's `length`. Missing and inherited
+    /// keys delete trivially.
+    pub fn delete<K: AtomKey + ?Sized>(&self, key: &K) -> bool {
+        if let Some(elements) = self.array_elements() {
+            if key.as_str() == "length" {
+                return false;
+            }
+            if let Some(index) = array_index(key.as_str()) {
+                if let Some(element) = elements.borrow_mut().get_mut(index) {
+                    *element = None;
+                }
+                return true;
+            }
+        }
+        self.settle_constructor(key);
+        self.properties.borrow_mut().delete(key)
+    }
+    
+    /// Get a property, own or inherited through the prototype chain
+    pub fn lookup<K: AtomKey + ?Sized>(&self, key: &K) -> Option<Value> {
+        if let Some(value) = self.get(key) {
+            return Some(value);
+        }
+        let mut object = self.prototype()?;
+        loop {
+            if let Some(value) = object.get(key) {
+                return Some(value);
+            }
+            object = object.prototype()?;
+        }
+    }
+    
+    /// Whether `prototype` appears anywhere on this object's prototype chain
+    pub fn inherits_from(&self, prototype: &ObjectData) -> bool {
+        let mut current = self.prototype();
+        while let Some(object) = current {
+            if std::ptr::eq(&*object, prototype) {
+                return true;
+            }
+            current = object.prototype();
+        }
+        false
+    }
+    
+    /// Set an own property by assignment, growing arrays with holes as
+    /// needed
+    ///
+    /// On an array, `length` is set as by `set_array_length`. Keys that
+    /// are not array indices (`-1`, `1.5`, `4294967295`, `02`) are plain
+    /// properties and leave the length alone. Returns false, writing
+    /// nothing, when the property or the one it would shadow is read-only.
+    pub fn set<K: AtomKey + ?Sized>(&self, key: &K, value: Value) -> Result<bool> {
+        if let Some(elements) = self.array_elements() {
+            if key.as_str() == "length" {
+                return self.set_array_length(&value).map(|()| true);
+            }
+            if let Some(index) = array_index(key.as_str()) {
+                let mut elements = elements.borrow_mut();
+                if index >= elements.len() {
+                    check_dense_length(index + 1)?;
+                    elements.resize(index + 1, None);
+                }
+                elements[index] = Some(value);
+                return Ok(true);
+            }
+        }
+        self.settle_constructor(key);
+        let mut properties = self.properties.borrow_mut();
+        if properties.contains_key(key) {
+            return Ok(properties.assign(key, value));
+        }
+        let writable = self.prototype().is_none_or(|prototype| prototype.is_writable(key));
+        if writable {
+            properties.insert(key.to_atom(), value);
+        }
+        Ok(writable)
+    }
+    
+    /// Whether assignment could change `key`: false when the nearest
+    /// property of that name, own or inherited, is read-only
+    pub fn is_writable<K: AtomKey + ?Sized>(&self, key: &K) -> bool {
+        if let Some(attributes) = self.properties.borrow().attributes(key) {
+            return attributes.writable;
+        }
+        if self.has_own(key) {
+            // Array elements and lengths are always writable
+            return true;
+        }
+        let mut object = self.prototype();
+        while let Some(current) = object {
+            if let Some(attributes) = current.properties.borrow().attributes(key) {
+                return attributes.writable;
+            }
+            if current.has_own(key) {
+                return true;
+            }
+            object = current.prototype();
+        }
+        true
+    }
+    
+    /// Get a symbol-keyed property, own or inherited
+    pub fn lookup_symbol(&self, symbol: &Symbol) -> Option<Value> {
+        if let Some(value) = self.properties.borrow().get_symbol(symbol) {
+            return Some(value.clone());
+        }
+        let mut object = self.prototype()?;
+        loop {
+            if let Some(value) = object.properties.borrow().get_symbol(symbol) {
+                return Some(value.clone());
+            }
+            object = object.prototype()?;
+        }
+    }
+    
+    /// Whether `symbol` keys an own or inherited property
+    pub fn has_symbol_property(&self, symbol: &Symbol) -> bool {
+        self.lookup_symbol(symbol).is_some()
+    }
+    
+    /// `set` for a symbol key: returns false, writing nothing, when the
+    /// property or the one it would shadow is read-only
+    pub fn set_symbol(&self, symbol: &Rc<Symbol>, value: Value) -> bool {
+        let mut properties = self.properties.borrow_mut();
+        if properties.symbol_attributes(symbol).is_some() {
+            return properties.assign_symbol(symbol, value);
+        }
+        let mut object = self.prototype();
+        while let Some(current) = object {
+            if let Some(attributes) = current.properties.borrow().symbol_attributes(symbol) {
+                if !attributes.writable {
+                    return false;
+                }
+                break;
+            }
+            object = current.prototype();
+        }
+        properties.assign_symbol(symbol, value)
+    }
+    
+    /// Assign an array's `length`: truncating deletes the elements above
+    /// it, growing adds holes
+    ///
+    /// `length` is converted with ToNumber, so objects must already be
+    /// primitives. A value that is not an integer in 0..2^32 is a
+    /// RangeError. Does nothing on a non-array.
+    pub fn set_array_length(&self, length: &Value) -> Result<()> {
+        let Some(elements) = self.array_elements() else { return Ok(()) };
+        let number = length.to_number();
+        let new_length = length.to_uint32();
+        if new_length as f64 != number {
+            return Err(Error::runtime(ErrorCode::InvalidArrayLength, "RangeError: Invalid array length", None));
+        }
+        let new_length = new_length as usize;
+        check_dense_length(new_length)?;
+        elements.borrow_mut().resize(new_length, None);
+        Ok(())
+    }
+}
+
+/// Longest array the engine stores
+///
+/// Elements live in one dense vector, so an index or `length` beyond this
+/// is a RangeError instead of an allocation of gigabytes, even though the
+/// language allows lengths up to 2^32 - 1.
+pub const MAX_DENSE_ARRAY_LENGTH: usize = 1 << 24;
+
+/// RangeError for array lengths past `MAX_DENSE_ARRAY_LENGTH`
+pub fn check_dense_length(length: usize) -> Result<()> {
+    if length > MAX_DENSE_ARRAY_LENGTH {
+        return Err(Error::runtime(
+            ErrorCode::InvalidArrayLength,
+            format!("RangeError: Array length {} exceeds the supported maximum of {}", length, MAX_DENSE_ARRAY_LENGTH),
+            None,
+        ));
+    }
+    Ok(())
+}
+
+impl Default for ObjectData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ObjectData {
+    /// Move the objects this one refers to into `out`, leaving
+    /// `undefined` in their place
+    fn take_children(&mut self, out: &mut Vec<Rc<ObjectData>>) {
+        let mut take = |value: &mut Value| {
+            if let Value::Object(_) = value {
+                if let Value::Object(child) = std::mem::take(value) {
+                    out.push(child);
+                }
+            }
+        };
+        let properties = self.properties.get_mut();
+        properties.entries.iter_mut().for_each(|(_, value, _)| take(value));
+        properties.symbols.iter_mut().for_each(|(_, value, _)| take(value));
+        match &mut self.kind {
+            ObjectKind::Array(elements) => elements.get_mut().iter_mut().flatten().for_each(take),
+            ObjectKind::ListIterator { source, .. } => take(source),
+            ObjectKind::IteratorRecord { iterator, next, .. } => {
+                take(iterator);
+                take(next);
+            }
+            _ => {}
+        }
+        out.extend(self.prototype.get_mut().take());
+    }
+}
+
+// Dropping the last reference to the head of a long chain (`{n: {n: ...}}`)
+// would otherwise drop one level per native stack frame; children that
+// only this object holds are unlinked into a worklist first
+impl Drop for ObjectData {
+    fn drop(&mut self) {
+        let mut pending = Vec::new();
+        self.take_children(&mut pending);
+        while let Some(child) = pending.pop() {
+            if let Ok(mut child) = Rc::try_unwrap(child) {
+                child.take_children(&mut pending);
+            }
+        }
+    }
+}
+
+impl PartialEq for ObjectData {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+// Objects can be cyclic, so Debug lists keys only instead of recursing
+impl fmt::Debug for ObjectData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let properties = self.properties.borrow();
+        let keys: Vec<&Atom> = properties.keys().collect();
+        match &self.kind {
+            ObjectKind::Ordinary | ObjectKind::FunctionPrototype(_) => write!(f, "Object {:?}", keys),
+            ObjectKind::Array(elements) => {
+                write!(f, "Array(len: {}) {:?}", elements.borrow().len(), keys)
+            }
+            ObjectKind::Generator(_) => write!(f, "Generator {:?}", keys),
+            ObjectKind::Promise(_) => write!(f, "Promise {:?}", keys),
+            ObjectKind::ListIterator { position, .. } => {
+                write!(f, "ListIterator(at: {})", position.get())
+            }
+            ObjectKind::IteratorRecord { done, .. } => write!(f, "IteratorRecord(done: {})", done.get()),
+        }
+    }
+}
+
+/// Parse a property key as an array index (canonical numeric string below 2^32 - 1)
+pub fn array_index(key: &str) -> Option<usize> {
+    if key.is_empty() || (key.len() > 1 && key.starts_with('0')) {
+        return None;
+    }
+    if !key.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    key.parse::<u32>().ok()
+        .filter(|&n| n != u32::MAX)
+        .map(|n| n as usize)
 }
 
 /// Function reference
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum FunctionRef {
     /// Bytecode function index
     Bytecode(usize),
-    /// Built-in function
-    Native(NativeFunction),
+    /// Built-in function, called with the VM, `this` and the arguments
+    NativeMethod(NativeMethod),
+    /// Compiled function together with its captured environment
+    Closure(Rc<Closure>),
+    /// Function registered by the embedder (see `Engine::register_function`)
+    Host(Rc<HostFunction>),
+    /// Function with its `this` and leading arguments fixed
+    Bound(Rc<BoundFunction>),
+    /// Built-in function with properties of its own, such as `Promise`
+    Builtin(Rc<BuiltinFunction>),
+}
+
+impl PartialEq for FunctionRef {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FunctionRef::Bytecode(a), FunctionRef::Bytecode(b)) => a == b,
+            (FunctionRef::NativeMethod(a), FunctionRef::NativeMethod(b)) => std::ptr::fn_addr_eq(*a, *b),
+            (FunctionRef::Closure(a), FunctionRef::Closure(b)) => Rc::ptr_eq(a, b),
+            (FunctionRef::Host(a), FunctionRef::Host(b)) => Rc::ptr_eq(a, b),
+            (FunctionRef::Bound(a), FunctionRef::Bound(b)) => Rc::ptr_eq(a, b),
+            (FunctionRef::Builtin(a), FunctionRef::Builtin(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
 /// Native function signature
-pub type NativeFunction = fn(&[Value]) -> Value;
+///
+/// Natives get the VM through the context so they can call back into
+/// JavaScript (e.g. a sort comparator), and can fail with a runtime error.
+pub type NativeMethod = fn(&mut CallContext) -> Result<Value>;
+
+/// Host callback signature, the same context as natives get
+pub type HostCallback = dyn Fn(&mut CallContext) -> Result<Value>;
+
+/// A function implemented by the embedder
+///
+/// Unlike natives, host functions may capture state, so they are boxed
+/// closures rather than plain function pointers. A callback that panics
+/// fails the call with `RuntimeErrorKind::NativeFunctionPanicked`, but
+/// returning an error is the way to fail.
+pub struct HostFunction {
+    /// Name shown when the function is printed
+    pub name: String,
+    pub callback: Box<HostCallback>,
+}
+
+impl HostFunction {
+    /// Wrap a callback under a name
+    pub fn new(name: impl Into<String>, callback: impl Fn(&mut CallContext) -> Result<Value> + 'static) -> Self {
+        Self { name: name.into(), callback: Box::new(callback) }
+    }
+}
+
+impl fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostFunction({})", self.name)
+    }
+}
+
+/// A function that calls `target` with a fixed `this` and `args`
+/// prepended to the arguments it is called with
+#[derive(Debug)]
+pub struct BoundFunction {
+    pub target: Value,
+    pub this: Value,
+    pub args: Vec<Value>,
+}
+
+/// A built-in function that carries properties, e.g. `Promise` with
+/// `Promise.resolve`
+pub struct BuiltinFunction {
+    pub name: &'static str,
+    /// Runs when the function is called
+    pub call: NativeMethod,
+    /// Runs for `new`; `None` if the function is not a constructor
+    pub construct: Option<NativeMethod>,
+    pub properties: RefCell<PropertyMap>,
+}
+
+impl fmt::Debug for BuiltinFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BuiltinFunction({})", self.name)
+    }
+}
+
+/// A function value created by `CreateClosure`
+pub struct Closure {
+    /// The compiled function body
+    pub function: Arc<BytecodeFunction>,
+    /// Environment of the enclosing function at creation time
+    pub env: Rc<Environment>,
+    /// Properties set on the function object, such as `prototype`
+    pub properties: RefCell<PropertyMap>,
+    /// For an arrow function, the `this` where it was created, used in
+    /// place of the receiver of every call
+    pub this: Option<Value>,
+    /// Whether `function` is nested in a `VerifiedFunction`
+    pub verified: bool,
+}
+
+impl Closure {
+    /// Create a closure with no properties yet
+    pub fn new(function: Arc<BytecodeFunction>, env: Rc<Environment>) -> Self {
+        Self { function, env, properties: RefCell::new(PropertyMap::new()), this: None, verified: false }
+    }
+}
+
+// The environment may contain this closure, so Debug must not recurse into it
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Closure({})", self.function.name)
+    }
+}
+
+/// Compare two strings by UTF-16 code units, as JavaScript does
+pub fn compare_code_units(a: &str, b: &str) -> Ordering {
+    a.encode_utf16().cmp(b.encode_utf16())
+}
 
 impl Value {
     /// Create a new number value
@@ -60,22 +1076,20 @@ impl Value {
         Value::Boolean(b)
     }
     
-    /// Check if value is truthy (JavaScript truthiness rules)
+    /// Check if value is truthy (JavaScript ToBoolean)
+    ///
+    /// The falsy values are exactly `false`, `0`, `-0`, `NaN`, `""`, `null`
+    /// and `undefined`; every object, even an empty one, is truthy.
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Null | Value::Undefined => false,
             Value::Number(n) => !n.is_nan() && *n != 0.0,
             Value::String(s) => !s.is_empty(),
-            Value::Object(_) | Value::Function(_) => true,
+            Value::Object(_) | Value::Function(_) | Value::Symbol(_) => true,
         }
     }
     
-    /// Convert value to boolean (JavaScript ToBoolean)
-    pub fn to_boolean(&self) -> bool {
-        self.is_truthy()
-    }
-    
     /// Convert value to number (JavaScript ToNumber)
     pub fn to_number(&self) -> f64 {
         match self {
@@ -88,36 +1102,30 @@ impl Value {
                 // Try to parse as number
                 s.trim().parse::<f64>().unwrap_or(f64::NAN)
             }
-            Value::Object(_) | Value::Function(_) => f64::NAN,
+            Value::Object(_) | Value::Function(_) | Value::Symbol(_) => f64::NAN,
         }
     }
     
-    /// Convert value to string (JavaScript ToString)
-    pub fn to_string(&self) -> String {
-        match self {
-            Value::Number(n) => {
-                if n.is_nan() {
-                    "NaN".to_string()
-                } else if n.is_infinite() {
-                    if n.is_sign_negative() {
-                        "-Infinity".to_string()
-                    } else {
-                        "Infinity".to_string()
-                    }
-                } else if n.fract() == 0.0 && n.abs() < 1e10 {
-                    // Display integers without decimal point
-                    format!("{:.0}", n)
-                } else {
-                    n.to_string()
-                }
-            }
-            Value::String(s) => s.to_string(),
-            Value::Boolean(b) => b.to_string(),
-            Value::Null => "null".to_string(),
-            Value::Undefined => "undefined".to_string(),
-            Value::Object(_) => "[object Object]".to_string(),
-            Value::Function(_) => "[object Function]".to_string(),
+    /// Convert value to a 32-bit signed integer (JavaScript ToInt32)
+    ///
+    /// Follows the spec's modular arithmetic: NaN and infinities map to 0,
+    /// everything else is truncated and wrapped modulo 2^32. A bare `as i32`
+    /// cast saturates instead of wrapping, so it cannot be used here.
+    pub fn to_int32(&self) -> i32 {
+        self.to_uint32() as i32
+    }
+    
+    /// Convert value to a 32-bit unsigned integer (JavaScript ToUint32)
+    pub fn to_uint32(&self) -> u32 {
+        let n = self.to_number();
+        if !n.is_finite() {
+            return 0;
         }
+        
+        const TWO_32: f64 = 4294967296.0;
+        let wrapped = n.trunc() % TWO_32;
+        let wrapped = if wrapped < 0.0 { wrapped + TWO_32 } else { wrapped };
+        wrapped as u32
     }
     
     /// Get the JavaScript typeof string
@@ -130,6 +1138,7 @@ impl Value {
             Value::Undefined => "undefined",
             Value::Object(_) => "object",
             Value::Function(_) => "function",
+            Value::Symbol(_) => "symbol",
         }
     }
     
@@ -148,10 +1157,52 @@ impl Value {
             (Value::Boolean(a), Value::Boolean(b)) => a == b,
             (Value::Null, Value::Null) => true,
             (Value::Undefined, Value::Undefined) => true,
+            (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+            (Value::Function(a), Value::Function(b)) => a == b,
+            (Value::Symbol(a), Value::Symbol(b)) => Rc::ptr_eq(a, b),
             _ => false, // Different types are never strictly equal
         }
     }
     
+    /// Abstract relational comparison (`<`, `>`, `<=`, `>=`)
+    ///
+    /// Two strings compare by UTF-16 code units; anything else compares
+    /// numerically. `None` means the operands are unordered (a NaN was
+    /// involved), which makes every relational operator return false.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(a), Value::String(b)) => Some(compare_code_units(a, b)),
+            _ => self.to_number().partial_cmp(&other.to_number()),
+        }
+    }
+    
+    /// Check for SameValue equality (Object.is)
+    ///
+    /// Unlike `===`, NaN is equal to itself and +0 is distinct from -0.
+    pub fn same_value(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => {
+                if a.is_nan() && b.is_nan() {
+                    true
+                } else {
+                    a == b && a.is_sign_negative() == b.is_sign_negative()
+                }
+            }
+            _ => self.strict_eq(other),
+        }
+    }
+    
+    /// Render the value for a REPL, like Node's `util.inspect`
+    ///
+    /// Unlike ToString, strings are quoted and objects show their
+    /// contents: `[ 1, 'a', <1 empty item> ]`, `{ x: 1, 'a-b': [] }`.
+    /// A reference back to an object being rendered shows `[Circular]`,
+    /// and objects nested deeper than `INSPECT_DEPTH` show as `[Array]`
+    /// or `[Object]`.
+    pub fn inspect(&self) -> String {
+        inspect_value(self, 0, &mut HashSet::new())
+    }
+    
     /// Check for loose equality (==)
     pub fn loose_eq(&self, other: &Value) -> bool {
         // First check strict equality
@@ -180,16 +1231,248 @@ impl Value {
     }
 }
 
+/// How many levels of nested objects `Value::inspect` expands, as in Node
+const INSPECT_DEPTH: usize = 2;
+
+/// Render a value the way a REPL echoes it (see `Value::inspect`)
+///
+/// `depth` is how many objects enclose `value`; `seen` holds the ones
+/// being rendered.
+fn inspect_value(value: &Value, depth: usize, seen: &mut HashSet<*const ObjectData>) -> String {
+    let data = match value {
+        Value::String(s) => return format!("'{}'", EscapeValidator::escape_quoted(s, '\'')),
+        Value::Number(n) if *n == 0.0 && n.is_sign_negative() => return "-0".to_string(),
+        Value::Function(FunctionRef::Closure(closure)) if closure.function.is_class_constructor => {
+            return match closure.function.name.as_str() {
+                "" | "<anonymous>" => "[class (anonymous)]".to_string(),
+                name => format!("[class {}]", name),
+            };
+        }
+        Value::Function(function) => {
+            return match function.name() {
+                "" | "<anonymous>" => "[Function (anonymous)]".to_string(),
+                name => format!("[Function: {}]", name),
+            };
+        }
+        Value::Object(data) => data,
+        primitive => return primitive.to_string(),
+    };
+    
+    let ptr = Rc::as_ptr(data);
+    if seen.contains(&ptr) {
+        return "[Circular]".to_string();
+    }
+    // Instances are prefixed with their constructor's name, like `Point { x: 1 }`
+    let constructor = match data.prototype().and_then(|prototype| prototype.get("constructor")) {
+        Some(Value::Function(function)) if !matches!(function.name(), "" | "<anonymous>") => {
+            Some(function.name().to_string())
+        }
+        _ => None,
+    };
+    if depth > INSPECT_DEPTH {
+        let kind = if data.is_array() { "Array" } else { "Object" };
+        return format!("[{}]", constructor.as_deref().unwrap_or(kind));
+    }
+    seen.insert(ptr);
+    
+    let mut parts = Vec::new();
+    if let Some(state) = data.promise_state() {
+        parts.push(match &*state.borrow() {
+            PromiseState::Pending(_) => "<pending>".to_string(),
+            PromiseState::Fulfilled(value) => inspect_value(value, depth + 1, seen),
+            PromiseState::Rejected { reason, .. } => format!("<rejected> {}", inspect_value(reason, depth + 1, seen)),
+        });
+    }
+    if let Some(elements) = data.array_elements() {
+        let elements = elements.borrow().clone();
+        let mut holes = 0;
+        for element in elements.iter().map(Some).chain([None]) {
+            if let Some(None) = element {
+                holes += 1;
+                continue;
+            }
+            if holes > 0 {
+                let plural = if holes == 1 { "" } else { "s" };
+                parts.push(format!("<{} empty item{}>", holes, plural));
+                holes = 0;
+            }
+            if let Some(Some(element)) = element {
+                parts.push(inspect_value(element, depth + 1, seen));
+            }
+        }
+    }
+    let properties = data.properties.borrow().clone();
+    for (key, value) in properties.enumerable() {
+        let key = if UnicodeHelper::is_identifier_name(key) {
+            key.to_string()
+        } else {
+            format!("'{}'", EscapeValidator::escape_quoted(key, '\''))
+        };
+        parts.push(format!("{}: {}", key, inspect_value(value, depth + 1, seen)));
+    }
+    seen.remove(&ptr);
+    
+    let (open, close) = if data.is_array() { ("[", "]") } else { ("{", "}") };
+    let open = if data.generator_state().is_some() { "Object [Generator] {" } else { open };
+    let open = if data.promise_state().is_some() { "Promise {" } else { open };
+    let open = match constructor {
+        Some(name) => format!("{} {}", name, open),
+        None => open.to_string(),
+    };
+    if parts.is_empty() {
+        format!("{}{}", open, close)
+    } else {
+        format!("{} {} {}", open, parts.join(", "), close)
+    }
+}
+
+/// Conversion hint for ToPrimitive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreferredType {
+    /// No preference (`+`, `==`); ordinary objects treat it as `Number`
+    Default,
+    /// Try `valueOf` before `toString`
+    Number,
+    /// Try `toString` before `valueOf`
+    String,
+}
+
+/// Source text shown for a function converted to a string
+///
+/// Function source is not kept, so every function renders like a native one.
+pub fn function_source_text(name: &str) -> String {
+    let name = if name == "<anonymous>" { "" } else { name };
+    format!("function {}() {{ [native code] }}", name)
+}
+
+impl FunctionRef {
+    /// The function's name, if it is known without the VM
+    pub fn name(&self) -> &str {
+        match self {
+            FunctionRef::Closure(closure) => &closure.function.name,
+            FunctionRef::Host(host) => &host.name,
+            FunctionRef::Builtin(builtin) => builtin.name,
+            _ => "",
+        }
+    }
+}
+
+/// Built-in ToString of a composite value, without calling user code
+///
+/// Arrays join their elements with commas; an array reached again while
+/// it is being joined renders as the empty string. Nested arrays are
+/// walked with an explicit stack, so any depth can be displayed.
+fn write_composite(f: &mut fmt::Formatter, value: &Value) -> fmt::Result {
+    let data = match value {
+        Value::Object(data) => data,
+        Value::Function(function) => return write!(f, "{}", function_source_text(function.name())),
+        primitive => return write!(f, "{}", primitive),
+    };
+    let elements = match data.array_elements() {
+        Some(elements) => elements.borrow().clone(),
+        None if data.generator_state().is_some() => return write!(f, "[object Generator]"),
+        None if data.promise_state().is_some() => return write!(f, "[object Promise]"),
+        None => return write!(f, "[object Object]"),
+    };
+    
+    // Arrays being joined, each with the index of its next element
+    let mut stack = vec![(Rc::as_ptr(data), elements, 0)];
+    let mut joining = HashSet::from([Rc::as_ptr(data)]);
+    while let Some((ptr, elements, next)) = stack.last_mut() {
+        let Some(element) = elements.get(*next).cloned() else {
+            joining.remove(ptr);
+            stack.pop();
+            continue;
+        };
+        if *next > 0 {
+            write!(f, ",")?;
+        }
+        *next += 1;
+        match element {
+            None | Some(Value::Undefined) | Some(Value::Null) => {}
+            Some(Value::Object(child)) if child.is_array() => {
+                let ptr = Rc::as_ptr(&child);
+                if joining.insert(ptr) {
+                    let elements = child.array_elements().map_or_else(Vec::new, |elements| elements.borrow().clone());
+                    stack.push((ptr, elements, 0));
+                }
+            }
+            Some(element) => write_composite(f, &element)?,
+        }
+    }
+    Ok(())
+}
+
+/// JavaScript ToString
+///
+/// Composites use the built-in conversions; see `VM::to_js_string` for
+/// the version that honours a user-defined `toString`.
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        match self {
+            Value::Number(n) => write!(f, "{}", number_to_string(*n)),
+            Value::String(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Null => write!(f, "null"),
+            Value::Undefined => write!(f, "undefined"),
+            Value::Symbol(symbol) => write!(f, "{}", symbol),
+            Value::Object(_) | Value::Function(_) => write_composite(f, self),
+        }
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Value::Undefined
+/// Number::toString from the spec, for radix 10
+///
+/// Uses the shortest digits that round-trip, as V8 does, and switches to
+/// exponent form outside `1e-7 < |n| < 1e21`. `-0` converts to `"0"`.
+pub fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        return "NaN".to_string();
+    }
+    if n == 0.0 {
+        return "0".to_string();
+    }
+    if n.is_infinite() {
+        return if n < 0.0 { "-Infinity" } else { "Infinity" }.to_string();
+    }
+    
+    // Rust's `{:e}` gives the shortest round-tripping digits as `d.ddde±x`
+    let scientific = format!("{:e}", n.abs());
+    let (mantissa, exponent) = scientific.split_once('e').expect("`{:e}` always has an exponent");
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // The spec's `n`: the decimal point sits after this many digits
+    let point = exponent.parse::<i32>().expect("`{:e}` exponent is an integer") + 1;
+    
+    let sign = if n < 0.0 { "-" } else { "" };
+    let body = if k <= point && point <= 21 {
+        format!("{}{}", digits, "0".repeat((point - k) as usize))
+    } else if 0 < point && point <= 21 {
+        format!("{}.{}", &digits[..point as usize], &digits[point as usize..])
+    } else if -6 < point && point <= 0 {
+        format!("0.{}{}", "0".repeat(-point as usize), digits)
+    } else {
+        let exponent = point - 1;
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        let (first, rest) = digits.split_at(1);
+        let fraction = if rest.is_empty() { String::new() } else { format!(".{}", rest) };
+        format!("{}{}e{}{}", first, fraction, exponent_sign, exponent.abs())
+    };
+    format!("{}{}", sign, body)
+}
+
+/// Exponentiation with JavaScript semantics (the `**` operator and `Math.pow`)
+///
+/// Differs from `f64::powf` where IEEE 754 and ECMAScript disagree: a NaN
+/// exponent always yields NaN, and `(±1) ** ±Infinity` is NaN rather than 1.
+pub fn js_pow(base: f64, exponent: f64) -> f64 {
+    if exponent.is_nan() {
+        return f64::NAN;
+    }
+    if exponent.is_infinite() && base.abs() == 1.0 {
+        return f64::NAN;
     }
+    base.powf(exponent)
 }
 
 #[cfg(test)]
@@ -207,6 +1490,13 @@ mod tests {
         assert!(!Value::Number(f64::NAN).is_truthy());
         assert!(Value::string("hello").is_truthy());
         assert!(!Value::string("").is_truthy());
+        assert!(!Value::Number(-0.0).is_truthy());
+        assert!(Value::Number(f64::INFINITY).is_truthy());
+        assert!(Value::string("0").is_truthy());
+        assert!(Value::string("false").is_truthy());
+        assert!(Value::Object(Rc::new(ObjectData::new())).is_truthy());
+        assert!(Value::Object(Rc::new(ObjectData::new_array(vec![]))).is_truthy());
+        assert!(Value::Function(FunctionRef::Bytecode(0)).is_truthy());
     }
     
     #[test]
@@ -222,6 +1512,7 @@ mod tests {
     }
     
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_to_string() {
         assert_eq!(Value::Number(42.0).to_string(), "42");
         assert_eq!(Value::Number(3.14).to_string(), "3.14");
@@ -233,6 +1524,72 @@ mod tests {
         assert_eq!(Value::Null.to_string(), "null");
         assert_eq!(Value::Undefined.to_string(), "undefined");
         assert_eq!(Value::string("hello").to_string(), "hello");
+        
+        // Composites use the built-in conversions
+        let inner = Value::Object(Rc::new(ObjectData::new_array(vec![Some(Value::Number(2.0)), None])));
+        let array = Rc::new(ObjectData::new_array(vec![Some(Value::Number(1.0)), Some(Value::Null), Some(inner)]));
+        assert_eq!(Value::Object(array.clone()).to_string(), "1,,2,");
+        array.array_elements().unwrap().borrow_mut().push(Some(Value::Object(array.clone())));
+        assert_eq!(Value::Object(array).to_string(), "1,,2,,");
+        assert_eq!(Value::Object(Rc::new(ObjectData::new())).to_string(), "[object Object]");
+    }
+    
+    #[test]
+    fn test_number_to_string() {
+        let cases = [
+            (0.0, "0"), (-0.0, "0"), (1.0, "1"), (-1.5, "-1.5"), (0.1 + 0.2, "0.30000000000000004"),
+            (123456789012345680000.0, "123456789012345680000"), (1e21, "1e+21"), (1.5e300, "1.5e+300"),
+            (0.000001, "0.000001"), (1e-7, "1e-7"), (-2.5e-10, "-2.5e-10"), (5e-324, "5e-324"),
+            (f64::MAX, "1.7976931348623157e+308"), (f64::NAN, "NaN"), (f64::NEG_INFINITY, "-Infinity"),
+        ];
+        for (n, expected) in cases {
+            assert_eq!(number_to_string(n), expected, "{:?}", n);
+        }
+    }
+    
+    #[test]
+    fn test_inspect() {
+        assert_eq!(Value::string("it's").inspect(), "'it\\'s'");
+        assert_eq!(Value::Number(-0.0).inspect(), "-0");
+        assert_eq!(Value::Undefined.inspect(), "undefined");
+        
+        let array = Rc::new(ObjectData::new_array(vec![
+            Some(Value::Number(1.0)),
+            None,
+            None,
+            Some(Value::string("a")),
+        ]));
+        assert_eq!(Value::Object(array.clone()).inspect(), "[ 1, <2 empty items>, 'a' ]");
+        assert_eq!(Value::Object(Rc::new(ObjectData::new_array(vec![]))).inspect(), "[]");
+        
+        let mut properties = PropertyMap::new();
+        properties.insert("x".to_string(), Value::Object(array));
+        properties.insert("a-b".to_string(), Value::Null);
+        let object = Rc::new(ObjectData::with_properties(properties));
+        object.set("self", Value::Object(object.clone())).unwrap();
+        assert_eq!(
+            Value::Object(object).inspect(),
+            "{ x: [ 1, <2 empty items>, 'a' ], 'a-b': null, self: [Circular] }"
+        );
+        assert_eq!(Value::Object(Rc::new(ObjectData::new())).inspect(), "{}");
+    }
+    
+    #[test]
+    fn test_inspect_escapes_and_stops_at_depth() {
+        assert_eq!(Value::string("a\nb").inspect(), "'a\\nb'");
+        
+        let mut properties = PropertyMap::new();
+        properties.insert("it's\n".to_string(), Value::Null);
+        let object = Value::Object(Rc::new(ObjectData::with_properties(properties)));
+        assert_eq!(object.inspect(), "{ 'it\\'s\\n': null }");
+        
+        let nest = |value: Value| Value::Object(Rc::new(ObjectData::new_array(vec![Some(value)])));
+        let deep = (0..5).fold(object, |value, _| nest(value));
+        assert_eq!(deep.inspect(), "[ [ [ [Array] ] ] ]");
+        let mut properties = PropertyMap::new();
+        properties.insert("x".to_string(), Value::Object(Rc::new(ObjectData::new())));
+        let object = Value::Object(Rc::new(ObjectData::with_properties(properties)));
+        assert_eq!(nest(nest(nest(object))).inspect(), "[ [ [ [Object] ] ] ]");
     }
     
     #[test]
@@ -267,4 +1624,34 @@ mod tests {
         assert!(Value::Number(123.0).loose_eq(&Value::string("123")));
         assert!(!Value::Number(123.0).loose_eq(&Value::string("456")));
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_to_int32_and_uint32() {
+        assert_eq!(Value::Number(2147483648.0).to_int32(), -2147483648);
+        assert_eq!(Value::Number(4294967295.0).to_int32(), -1);
+        assert_eq!(Value::Number(4294967296.0).to_int32(), 0);
+        assert_eq!(Value::Number(-1.0).to_uint32(), 4294967295);
+        assert_eq!(Value::Number(-2.7).to_int32(), -2);
+        assert_eq!(Value::Number(1e21).to_int32(), -559939584);
+        assert_eq!(Value::Number(f64::NAN).to_int32(), 0);
+        assert_eq!(Value::Number(f64::INFINITY).to_uint32(), 0);
+        assert_eq!(Value::string("12").to_int32(), 12);
+    }
+    
+    #[test]
+    fn test_same_value() {
+        assert!(Value::Number(f64::NAN).same_value(&Value::Number(f64::NAN)));
+        assert!(!Value::Number(0.0).same_value(&Value::Number(-0.0)));
+        assert!(Value::Number(-0.0).same_value(&Value::Number(-0.0)));
+        assert!(Value::string("a").same_value(&Value::string("a")));
+    }
+    
+    #[test]
+    fn test_js_pow() {
+        assert!(js_pow(-1.0, 0.5).is_nan());
+        assert!(js_pow(1.0, f64::INFINITY).is_nan());
+        assert!(js_pow(1.0, f64::NAN).is_nan());
+        assert_eq!(js_pow(f64::NAN, 0.0), 1.0);
+        assert_eq!(js_pow(0.0, 0.0), 1.0);
+    }
+}
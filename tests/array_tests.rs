@@ -0,0 +1,79 @@
+//! Tests for array indices and `length`
+//!
+//! Only canonical numeric strings below 2^32 - 1 are indices; every other
+//! key is a plain property. Index writes grow `length`, and assigning
+//! `length` truncates or pads with holes.
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{eval, eval_error};
+
+#[test]
+fn test_index_writes_track_length() {
+    let cases = [
+        // Writes at or past the end grow the array
+        ("let a = [0, 1]; a[2] = 'x'; a.length", 3.0),
+        ("let a = [0, 1]; a[5] = 'x'; a.length", 6.0),
+        ("let a = []; a['3'] = 1; a.length", 4.0),
+        ("let a = [0, 1, 2]; a[1] = 'x'; a.length", 3.0),
+        // Keys that are not canonical indices do not
+        ("let a = [0, 1]; a['02'] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a[-1] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a[1.5] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a['+1'] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a[' 1'] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a[4294967295] = 'x'; a.length", 2.0),
+        // 1.0 converts to the key '1', which is an index
+        ("let a = []; a[1.0] = 'x'; a.length", 2.0),
+        ("let a = [0, 1]; a.push(2, 3)", 4.0),
+        ("let a = [0, 1]; a.push(); a.length", 2.0),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(eval(source), Value::Number(expected), "{}", source);
+    }
+}
+
+#[test]
+fn test_non_index_keys_are_properties() {
+    assert_eq!(eval("let a = []; a[-1] = 'neg'; a['-1']"), Value::string("neg"));
+    assert_eq!(eval("let a = []; a[1.5] = 'half'; a['1.5']"), Value::string("half"));
+    assert_eq!(eval("let a = [0, 1]; a['02'] = 'x'; a[2]"), Value::Undefined);
+    assert_eq!(eval("let a = [0, 1]; a['02'] = 'x'; a['02']"), Value::string("x"));
+    assert_eq!(eval("let a = []; a[4294967295] = 'max'; a[4294967295]"), Value::string("max"));
+    assert_eq!(eval("let a = [0, 1]; a[-1] = 'x'; a").inspect(), "[ 0, 1, '-1': 'x' ]");
+}
+
+#[test]
+fn test_length_assignment() {
+    // Truncation deletes the elements above the new length
+    assert_eq!(eval("let a = [0, 1, 2, 3]; a.length = 2; a").inspect(), "[ 0, 1 ]");
+    assert_eq!(eval("let a = [0, 1, 2, 3]; a.length = 2; a.length = 4; a[3]"), Value::Undefined);
+    assert_eq!(eval("let a = [0, 1, 2]; a.length = 0; 0 in a"), Value::Boolean(false));
+    // Growing adds holes
+    assert_eq!(eval("let a = [0]; a.length = 3; a").inspect(), "[ 0, <2 empty items> ]");
+    // The value is converted with ToNumber
+    assert_eq!(eval("let a = [0, 1, 2, 3]; a.length = '3'; a.length"), Value::Number(3.0));
+    assert_eq!(eval("let a = [0, 1]; a.length = true; a").inspect(), "[ 0 ]");
+    assert_eq!(eval("let a = [0, 1]; a.length = null; a.length"), Value::Number(0.0));
+    assert_eq!(eval("let a = [0, 1, 2]; a.length = { valueOf() { return 1; } }; a.length"), Value::Number(1.0));
+    // The assignment evaluates to the assigned value
+    assert_eq!(eval("let a = [0, 1, 2]; a.length = '1'"), Value::string("1"));
+    // Assigning `length` on an ordinary object is a plain property write
+    assert_eq!(eval("let o = {}; o.length = 1.5; o.length"), Value::Number(1.5));
+}
+
+#[test]
+fn test_invalid_lengths_are_range_errors() {
+    for length in ["1.5", "-1", "'abc'", "undefined", "4294967296", "NaN", "Infinity", "{}"] {
+        let source = format!("let a = [0, 1]; a.length = {};", length);
+        assert_eq!(Engine::new().execute(&source).unwrap_err().code(), ErrorCode::InvalidArrayLength, "{}", source);
+    }
+}
+
+#[test]
+fn test_lengths_past_dense_storage_are_range_errors() {
+    assert!(eval_error("let a = []; a[4294967294] = 1", ErrorCode::InvalidArrayLength).contains("exceeds the supported maximum"));
+    assert!(eval_error("let a = []; a.length = 4294967295", ErrorCode::InvalidArrayLength).contains("exceeds the supported maximum"));
+}
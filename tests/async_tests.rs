@@ -0,0 +1,126 @@
+//! Tests for `async function` and `await`
+//!
+//! Async bodies resume from microtasks, so every test pumps the event loop
+//! with `Engine::run_pending` and checks the order events were recorded in.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{engine_with, log};
+
+#[test]
+fn test_code_after_await_runs_asynchronously() {
+    let mut engine = engine_with(
+        "async function f() {
+             record('a');
+             let x = await 1;
+             record('c' + x);
+         }
+         f();
+         record('b');",
+    );
+    assert_eq!(log(&mut engine), Value::string("ab"));
+    
+    assert!(!engine.run_pending(0).unwrap());
+    assert_eq!(log(&mut engine), Value::string("abc1"));
+}
+
+#[test]
+fn test_sequential_awaits_resolve_in_order() {
+    let mut engine = engine_with(
+        "async function step(name) {
+             record(name + '1');
+             await 0;
+             record(name + '2');
+             await 0;
+             record(name + '3');
+             return name;
+         }
+         step('x').then(function (v) { record('[' + v + ']'); });
+         step('y');",
+    );
+    assert_eq!(log(&mut engine), Value::string("x1y1"));
+    
+    engine.run_pending(0).unwrap();
+    assert_eq!(log(&mut engine), Value::string("x1y1x2y2x3y3[x]"));
+}
+
+#[test]
+fn test_await_adopts_thenables_and_async_results() {
+    let mut engine = engine_with(
+        "let later = { then: function (resolve) { setTimeout(function () { resolve(20); }, 10); } };
+         async function inner() { return (await later) + 1; }
+         async function outer() { record('' + (await inner()) * 2); }
+         outer();",
+    );
+    engine.run_pending(0).unwrap();
+    assert_eq!(log(&mut engine), Value::string(""));
+    
+    assert!(!engine.run_pending(10).unwrap());
+    assert_eq!(log(&mut engine), Value::string("42"));
+}
+
+#[test]
+fn test_rejected_await_is_catchable() {
+    // There is no try/catch yet, so the rejection ends the body and is
+    // caught on the function's promise. `g` fails before its first await,
+    // so its handler runs first; adopting the thenable takes extra ticks.
+    let mut engine = engine_with(
+        "let failing = { then: function (resolve, reject) { reject('boom'); } };
+         async function f() {
+             record('a');
+             await failing;
+             record('never');
+         }
+         f().then(function () { record('fulfilled'); }, function (e) { record('caught ' + e); });
+         async function g() { undefinedFunction(); }
+         g().catch(function (e) { record('; ' + e); });",
+    );
+    engine.run_pending(0).unwrap();
+    assert_eq!(
+        log(&mut engine),
+        Value::string("a; ReferenceError: undefinedFunction is not definedcaught boom")
+    );
+}
+
+#[test]
+fn test_async_results_chain_and_failures_reject() {
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let rejections = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = engine_with(
+        "async function two() { return 2; }
+         two()
+             .then(function (v) { return v * 10; })
+             .then(undefined, function () { record('skipped'); })
+             .then(function (v) { record('' + v); });
+         async function fails() { null.x; }
+         fails();",
+    );
+    let sink = errors.clone();
+    engine.set_uncaught_exception_handler(move |error| sink.borrow_mut().push(error.to_string()));
+    let sink = rejections.clone();
+    engine.set_unhandled_rejection_handler(move |reason| sink.borrow_mut().push(reason));
+    
+    assert!(!engine.run_pending(0).unwrap());
+    assert_eq!(log(&mut engine), Value::string("20"));
+    // A failing body rejects its promise rather than throwing
+    assert!(errors.borrow().is_empty(), "{:?}", errors.borrow());
+    assert_eq!(rejections.borrow().len(), 1);
+}
+
+#[test]
+fn test_await_outside_async_function_is_a_syntax_error() {
+    // Outside async functions `await` is an ordinary identifier
+    let mut engine = Engine::new();
+    assert!(engine.execute("function f() { await 1; }").is_err());
+    assert!(engine.execute("async function f() { function g() { await 1; } }").is_err());
+    assert_eq!(engine.execute("let await = 2; await").unwrap(), Value::Number(2.0));
+    
+    let error = engine.execute("async function* f() {}").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UnsupportedSyntax);
+    assert!(error.to_string().contains("Async generators are not supported"), "{}", error);
+}
@@ -0,0 +1,128 @@
+//! Tests for folding calls of pure built-ins at compile time: folded calls
+//! become constants, and optimized programs behave exactly like
+//! unoptimized ones
+
+use v8::bytecode::{self, Bytecode};
+use v8::{Disassembler, Engine, ErrorCode};
+
+mod common;
+
+use common::optimizing;
+
+fn disassemble(engine: &mut Engine, source: &str) -> String {
+    Disassembler::quick_disassemble(&engine.compile_cached(source).unwrap())
+}
+
+/// The result of `source` as text, or its error's code and message
+fn outcome(engine: &mut Engine, source: &str) -> String {
+    match engine.execute(source) {
+        Ok(value) => format!("{:?}", value),
+        Err(error) => format!("{}: {}", error.code(), error),
+    }
+}
+
+#[test]
+fn test_folded_calls_become_constants() {
+    for (source, constant) in [
+        ("parseFloat('2.5e3x')", "2500"),
+        ("Number.isSafeInteger(9007199254740993)", "false"),
+        ("'héllo😀'.length", "LdaSmi 7"),
+        ("'abcabc'.indexOf('c', 3)", "LdaSmi 5"),
+        ("JSON.parse('\"config\"')", "\"config\""),
+        ("parseInt(JSON.parse('\"0x1f\"'), 16)", "LdaSmi 31"),
+        ("Math.pow(2, 10)", "LdaSmi 1024"),
+        ("Math.pow(2, 0.5)", "1.4142135623730951"),
+    ] {
+        let disassembly = disassemble(&mut optimizing(), source);
+        assert!(disassembly.contains(constant), "{}:\n{}", source, disassembly);
+        assert!(!disassembly.contains("Call") && !disassembly.contains("LdaNamed"), "{}:\n{}", source, disassembly);
+    }
+    
+    // Without the flag nothing is folded
+    let disassembly = disassemble(&mut Engine::new(), "parseInt('42')");
+    assert!(disassembly.contains("Call"), "{}", disassembly);
+}
+
+#[test]
+fn test_only_pure_builtins_on_constants_are_folded() {
+    for source in [
+        "Math.random()",
+        "let s = '42'; parseInt(s)",
+        "JSON.parse('[1, 2]')",
+        "JSON.parse('{')",
+        "let parseInt = function (s) { return s; }; parseInt('42')",
+        "function f(Number) { return Number.isInteger(1); } f(Number)",
+        "console.log('x')",
+    ] {
+        let mut engine = optimizing();
+        let unfolded = disassemble(&mut Engine::new(), source);
+        assert_eq!(disassemble(&mut engine, source), unfolded, "{}", source);
+    }
+}
+
+#[test]
+fn test_optimized_programs_behave_the_same() {
+    let deep_json = format!("JSON.parse('{}')", "[".repeat(10_000));
+    let mut corpus: Vec<String> = [
+        "parseInt('42')",
+        "parseInt('z', 36) + parseInt('  12px')",
+        "parseInt('abc')",
+        "parseInt('7', 1)",
+        "parseFloat('.5') + parseFloat('-Infinityx')",
+        "Math.pow(-1, 0.5)",
+        "1 / Math.pow(-0, 3) + Math.pow(2, -1) + Math.pow('3', '2')",
+        "Math.pow(1, 1 / 0) + Math.pow()",
+        "isNaN('x') && isFinite('1e308') && !isFinite('1e309')",
+        "Number.isInteger(5.0) && !Number.isInteger('5') && Number.isFinite(-0)",
+        "1 / parseFloat('-0')",
+        "'😀'.length + ''.length",
+        "'abc'.indexOf('') + 'abc'.indexOf('d') + 'abc'.indexOf('c', NaN)",
+        "'b'.localeCompare('a') - 'a'.localeCompare('b')",
+        "JSON.parse(' null ')",
+        "JSON.parse('[1, 2]').length",
+        "JSON.parse('{')",
+        "JSON.parse('1', function (k, v) { return v; })",
+        "let parseInt = function () { return 'shadowed'; }; parseInt('42')",
+        "function f(Number) { return Number.isInteger(1); } f({ isInteger: function () { return 'mine'; } })",
+        "let total = 0; for (let i = 0; i < 3; i++) { total += parseInt('1' + i); } total",
+        "(function () { return Number.isSafeInteger(9007199254740991); })()",
+    ].iter().map(|source| source.to_string()).collect();
+    corpus.push(deep_json);
+    
+    for source in &corpus {
+        let expected = outcome(&mut Engine::new(), source);
+        assert_eq!(outcome(&mut optimizing(), source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn test_folding_only_replaces_calls_with_constants() {
+    let source = "let n = parseInt('42'); function f() { return Number.isInteger(n) + 'abc'.length; } f()";
+    let unfolded = Engine::new().compile_cached(source).unwrap();
+    let folded = optimizing().compile_cached(source).unwrap();
+    let difference = bytecode::diff(&unfolded, &folded);
+    
+    // Only constant loads come in, and every call that goes out had
+    // constant arguments
+    assert!(difference.added().iter().all(|op| matches!(op, Bytecode::LdaSmi(_) | Bytecode::LdaConst(_))), "{}", difference);
+    assert_eq!(difference.removed().iter().filter(|op| matches!(op, Bytecode::Call(_))).count(), 1, "{}", difference);
+    assert!(difference.functions[0].changes().next().is_some(), "{}", difference);
+    assert!(difference.functions[0].removed().iter().all(|op| !matches!(op, Bytecode::Call(_))), "{}", difference);
+}
+
+#[test]
+fn test_throwing_calls_are_not_compile_errors() {
+    let deep_json = format!("JSON.parse('{}')", "[".repeat(10_000));
+    for (source, code) in [("JSON.parse('{')", ErrorCode::JsonSyntax), (deep_json.as_str(), ErrorCode::JsonNesting)] {
+        let mut engine = optimizing();
+        let function = engine.compile_cached(source).unwrap();
+        assert_eq!(engine.execute_compiled(&function).unwrap_err().code(), code, "{}", source);
+    }
+}
+
+#[test]
+fn test_writable_builtins_turn_folding_off() {
+    let mut engine = Engine::builder().optimize(true).writable_builtins(true).build();
+    assert_eq!(engine.execute("parseInt = function () { return 'mine'; }; parseInt('42')").unwrap(), v8::Value::string("mine"));
+    assert!(disassemble(&mut engine, "parseInt('42')").contains("Call"));
+}
@@ -0,0 +1,74 @@
+//! Tests for the JSON bytecode dump: what is written reads back the same,
+//! and rebuilds a program that runs
+
+use std::sync::Arc;
+use v8::bytecode::{BytecodeDump, DUMP_FORMAT_VERSION};
+use v8::{Engine, Value};
+
+const PROGRAM: &str = "\
+function counter(start) {
+    let n = start;
+    return function () { n += 1; return n; };
+}
+let next = counter(10);
+next();
+let data = { list: [1, , 'two'], pattern: /a+/g, big: 1e400 };
+next() + data.list.length + data.pattern.source.length
+";
+
+#[test]
+fn test_json_round_trip() {
+    let function = Engine::new().compile_cached(PROGRAM).unwrap();
+    let dump = BytecodeDump::new(&function);
+    assert_eq!(dump.version, DUMP_FORMAT_VERSION);
+    assert_eq!(dump.main.functions[0].functions.len(), 1);
+    
+    let json = dump.to_json();
+    let read = BytecodeDump::from_json(&json).unwrap();
+    assert_eq!(read, dump);
+    assert_eq!(read.to_json(), json);
+    
+    let rebuilt = read.to_function().unwrap();
+    assert_eq!(rebuilt.bytecode, function.bytecode);
+    assert_eq!(rebuilt.constants, function.constants);
+    assert_eq!(rebuilt.names, function.names);
+    assert!(Arc::ptr_eq(&rebuilt.names, &rebuilt.functions[0].functions[0].names));
+    assert_eq!(rebuilt.functions[0].functions[0].bytecode, function.functions[0].functions[0].bytecode);
+    assert_eq!(BytecodeDump::new(&rebuilt), dump);
+}
+
+#[test]
+fn test_rebuilt_program_runs() {
+    let function = Engine::new().compile_cached(PROGRAM).unwrap();
+    let json = BytecodeDump::new(&function).to_json();
+    let rebuilt = BytecodeDump::from_json(&json).unwrap().to_function().unwrap();
+    assert_eq!(Engine::new().execute_compiled(&Arc::new(rebuilt)).unwrap(), Value::Number(17.0));
+}
+
+#[test]
+fn test_instructions_are_typed() {
+    let function = Engine::new().compile_cached("let s = 'abc'; s.length").unwrap();
+    let json = BytecodeDump::new(&function).to_json();
+    assert!(json.contains("\"op\": {\n          \"LdaNamed\": 1\n        },\n        \"text\": \"LdaNamed #1 (.length)\""), "{}", json);
+    assert!(json.contains("\"op\": \"Return\""), "{}", json);
+    
+    let error = BytecodeDump::from_json(&json.replace("LdaNamed", "LoadNamed")).unwrap_err();
+    assert!(format!("{:?}", error).contains("unknown variant `LoadNamed`"), "{:?}", error);
+}
+
+#[test]
+fn test_property_names_are_written_once() {
+    let function = Engine::new().compile_cached("let o = { length: 1 }; function f() { return o.length; } f()").unwrap();
+    let dump = BytecodeDump::new(&function);
+    assert_eq!(dump.names, ["length"]);
+    
+    let mut unknown = dump.clone();
+    unknown.names.clear();
+    let error = unknown.to_function().unwrap_err();
+    assert!(format!("{:?}", error).contains("names property #0 of 0"), "{:?}", error);
+    
+    let mut repeated = dump;
+    repeated.names.push("length".to_string());
+    let error = repeated.to_function().unwrap_err();
+    assert!(format!("{:?}", error).contains("property name #1 repeats an earlier one"), "{:?}", error);
+}
@@ -0,0 +1,178 @@
+//! Random bytecode must never crash the VM
+//!
+//! Bytecode will be loadable from disk, so the interpreter treats it as
+//! untrusted: any instruction sequence, operand or constant pool has to
+//! end in `Ok` or `Err`, never a panic or a hang. The instruction limit
+//! bounds the runs; the `fuzz/` crate runs the same check under
+//! libFuzzer.
+
+use std::sync::Arc;
+
+use proptest::prelude::*;
+use v8::bytecode::{ConstIndex, ConstantValue, JumpOffset, LocalIndex};
+use v8::{Bytecode, BytecodeFunction, VMConfig, VM};
+
+/// Operands near the range real code uses, plus a few far outside it
+fn small_u16() -> impl Strategy<Value = u16> {
+    prop_oneof![8 => 0u16..8, 1 => any::<u16>()]
+}
+
+fn small_u8() -> impl Strategy<Value = u8> {
+    prop_oneof![8 => 0u8..4, 1 => any::<u8>()]
+}
+
+fn const_index() -> impl Strategy<Value = ConstIndex> {
+    small_u16().prop_map(ConstIndex)
+}
+
+fn local_index() -> impl Strategy<Value = LocalIndex> {
+    small_u16().prop_map(LocalIndex)
+}
+
+fn offset() -> impl Strategy<Value = JumpOffset> {
+    prop_oneof![8 => -12i16..12, 1 => any::<i16>()].prop_map(JumpOffset)
+}
+
+fn instruction() -> impl Strategy<Value = Bytecode> {
+    prop_oneof![
+        const_index().prop_map(Bytecode::LdaConst),
+        any::<i16>().prop_map(Bytecode::LdaSmi),
+        local_index().prop_map(Bytecode::LdaLocal),
+        local_index().prop_map(Bytecode::StaLocal),
+        local_index().prop_map(Bytecode::InitLocal),
+        const_index().prop_map(Bytecode::LdaGlobal),
+        const_index().prop_map(Bytecode::StaGlobal),
+        const_index().prop_map(Bytecode::InitGlobal),
+        const_index().prop_map(Bytecode::LdaGlobalInsideTypeof),
+        (small_u8(), local_index()).prop_map(|(depth, index)| Bytecode::LdaContextSlot(depth, index)),
+        (small_u8(), local_index()).prop_map(|(depth, index)| Bytecode::StaContextSlot(depth, index)),
+        prop::sample::select(vec![
+            Bytecode::Push, Bytecode::Pop, Bytecode::Dup, Bytecode::Dup2,
+            Bytecode::Add, Bytecode::Sub, Bytecode::Mul, Bytecode::Div, Bytecode::Mod, Bytecode::Pow,
+            Bytecode::Inc, Bytecode::Dec,
+            Bytecode::Eq, Bytecode::Ne, Bytecode::StrictEq, Bytecode::StrictNe,
+            Bytecode::Lt, Bytecode::Gt, Bytecode::Le, Bytecode::Ge, Bytecode::InstanceOf, Bytecode::In,
+            Bytecode::LogicalAnd, Bytecode::LogicalOr, Bytecode::LogicalNot, Bytecode::ToBoolean,
+            Bytecode::BitwiseAnd, Bytecode::BitwiseOr, Bytecode::BitwiseXor, Bytecode::LeftShift,
+            Bytecode::RightShift, Bytecode::UnsignedRightShift, Bytecode::BitwiseNot,
+            Bytecode::UnaryPlus, Bytecode::UnaryMinus, Bytecode::TypeOf, Bytecode::LdaThis,
+            Bytecode::LdaKeyed, Bytecode::StaKeyed, Bytecode::DeleteKeyedProperty,
+            Bytecode::Return, Bytecode::ReturnUndefined, Bytecode::Yield,
+            Bytecode::GetIterator, Bytecode::IteratorClose,
+            Bytecode::CreateObject, Bytecode::DefineKeyedProperty, Bytecode::CopyDataProperties,
+            Bytecode::Nop, Bytecode::Debugger,
+        ]),
+        const_index().prop_map(Bytecode::LdaNamed),
+        const_index().prop_map(Bytecode::StaNamed),
+        const_index().prop_map(Bytecode::DeleteNamedProperty),
+        small_u8().prop_map(Bytecode::Call),
+        small_u8().prop_map(Bytecode::CallMethod),
+        small_u8().prop_map(Bytecode::Construct),
+        offset().prop_map(Bytecode::Jump),
+        offset().prop_map(Bytecode::JumpIfFalse),
+        offset().prop_map(Bytecode::JumpIfTrue),
+        offset().prop_map(Bytecode::JumpIfNullish),
+        offset().prop_map(Bytecode::IteratorNext),
+        small_u16().prop_map(Bytecode::CreateArray),
+        small_u16().prop_map(Bytecode::StaArrayElement),
+        const_index().prop_map(Bytecode::DefineNamedProperty),
+        const_index().prop_map(Bytecode::CreateClosure),
+        const_index().prop_map(Bytecode::CreateFromTemplate),
+    ]
+}
+
+/// A function with random code, locals and flags, nesting `depth` more
+/// levels of functions for `CreateClosure`
+fn function(depth: u32) -> BoxedStrategy<BytecodeFunction> {
+    let nested = if depth == 0 {
+        Just(Vec::new()).boxed()
+    } else {
+        prop::collection::vec(function(depth - 1), 0..3).boxed()
+    };
+    (
+        prop::collection::vec(instruction(), 0..40),
+        0u8..3,
+        0u16..6,
+        any::<bool>(),
+        any::<bool>(),
+        nested,
+    )
+        .prop_map(|(bytecode, arity, extra_locals, is_generator, is_strict, functions)| {
+            let mut function = BytecodeFunction::new("fuzz".to_string(), arity, arity as u16 + extra_locals, 0);
+            function.constants.add_number(1.5);
+            function.constants.add_string("text".to_string());
+            function.add_property_name("length");
+            function.add_property_name("prototype");
+            function.add_property_name("next");
+            let constants = &mut function.constants;
+            constants.add_boolean(true);
+            constants.add_null();
+            constants.add_regex("a+".to_string(), "g".to_string());
+            constants.add_object_template(vec![
+                ("a".to_string(), ConstantValue::Boolean(false)),
+                ("b".to_string(), ConstantValue::ArrayTemplate(vec![None, Some(ConstantValue::Null)])),
+            ]);
+            for instruction in bytecode {
+                function.add_instruction(instruction);
+            }
+            function.is_generator = is_generator;
+            function.is_strict = is_strict;
+            function.functions = functions.into_iter().map(Arc::new).collect();
+            function
+        })
+        .boxed()
+}
+
+fn run(function: BytecodeFunction) {
+    let config = VMConfig { max_instructions: Some(10_000), max_call_depth: 200, ..VMConfig::default() };
+    let mut vm = VM::with_config(config);
+    // Either outcome is fine; getting here without a panic is the test
+    let _ = vm.execute(function);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig { cases: 256, ..ProptestConfig::default() })]
+    
+    #[test]
+    fn test_random_bytecode_never_panics(main in function(2)) {
+        run(main);
+    }
+}
+
+#[test]
+fn test_known_malformed_programs_fail_cleanly() {
+    let cases: Vec<Vec<Bytecode>> = vec![
+        vec![Bytecode::LdaLocal(LocalIndex(500))],
+        vec![Bytecode::StaLocal(LocalIndex(u16::MAX))],
+        vec![Bytecode::LdaContextSlot(3, LocalIndex(0))],
+        vec![Bytecode::Jump(JumpOffset(i16::MAX))],
+        vec![Bytecode::Jump(JumpOffset(-100))],
+        vec![Bytecode::Call(200)],
+        vec![Bytecode::LdaConst(ConstIndex(999))],
+        vec![Bytecode::CreateClosure(ConstIndex(7))],
+        vec![Bytecode::Yield],
+        vec![Bytecode::IteratorNext(JumpOffset(1))],
+        vec![Bytecode::StaArrayElement(3)],
+        // Loops forever without the instruction limit
+        vec![Bytecode::Jump(JumpOffset(-1))],
+        vec![Bytecode::Dup, Bytecode::Push, Bytecode::Jump(JumpOffset(-3))],
+    ];
+    for code in cases {
+        let mut function = BytecodeFunction::new_main();
+        for instruction in code.iter().cloned() {
+            function.add_instruction(instruction);
+        }
+        let config = VMConfig { max_instructions: Some(10_000), ..VMConfig::default() };
+        let result = VM::with_config(config).execute(function);
+        assert!(result.is_err(), "{:?} should fail, got {:?}", code, result);
+    }
+}
+
+#[test]
+fn test_instruction_limit() {
+    let mut function = BytecodeFunction::new_main();
+    function.add_instruction(Bytecode::Jump(JumpOffset(-1)));
+    let config = VMConfig { max_instructions: Some(50), ..VMConfig::default() };
+    let error = VM::with_config(config).execute(function).unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: RangeError: Instruction limit of 50 exceeded");
+}
@@ -0,0 +1,55 @@
+//! Tests for `Engine::capabilities`; the conformance suite checks the
+//! feature list against its skipped cases
+
+use v8::{Capabilities, Engine, Feature, FeatureFlags, VMConfig, VERSION};
+
+#[test]
+fn test_limits_follow_the_configuration() {
+    let defaults = Engine::new().capabilities();
+    assert_eq!(defaults.version, VERSION);
+    assert_eq!(defaults.limits.max_call_depth, VMConfig::default().max_call_depth);
+    assert_eq!(defaults.limits.max_instructions, None);
+    assert_eq!(defaults.limits.max_heap_bytes, None);
+    assert_eq!(defaults.limits.max_constants, 65_536);
+    assert_eq!(defaults.limits.max_locals, 65_535);
+    assert_eq!(defaults.limits.max_array_length, 1 << 24);
+    
+    let config = VMConfig {
+        max_call_depth: 50,
+        max_stack_size: 1_000,
+        max_instructions: Some(10),
+        max_heap_bytes: Some(1 << 20),
+        ..VMConfig::default()
+    };
+    let engine = Engine::builder().vm_config(config).max_nesting_depth(32).build();
+    let limits = engine.capabilities().limits;
+    assert_eq!((limits.max_call_depth, limits.max_stack_size, limits.max_instructions), (50, 1_000, Some(10)));
+    assert_eq!(limits.max_heap_bytes, Some(1 << 20));
+    assert_eq!(limits.max_nesting_depth, 32);
+}
+
+#[test]
+fn test_lexer_features_are_reported_as_configured() {
+    assert!(Engine::new().capabilities().supports(Feature::NumericSeparators));
+    let engine = Engine::builder().lexer_features(FeatureFlags { numeric_separators: false, ..FeatureFlags::ALL }).build();
+    let capabilities = engine.capabilities();
+    assert!(!capabilities.supports(Feature::NumericSeparators));
+    assert!(capabilities.supports(Feature::Classes));
+}
+
+#[test]
+fn test_capabilities_serialize_with_feature_names() {
+    let capabilities = Engine::new().capabilities();
+    let json = serde_json::to_value(&capabilities).unwrap();
+    assert_eq!(json["version"], VERSION);
+    assert!(json["features"].as_array().unwrap().contains(&"arrow-functions".into()));
+    assert_eq!(json["limits"]["max_string_length"], serde_json::Value::Null);
+    
+    let back: Capabilities = serde_json::from_value(json).unwrap();
+    assert_eq!(back, capabilities);
+    
+    for feature in Feature::ALL {
+        assert_eq!(serde_json::to_value(feature).unwrap(), feature.name());
+        assert_eq!(Feature::from_name(feature.name()), Some(feature));
+    }
+}
@@ -0,0 +1,243 @@
+//! Tests for the command-line tool's arguments and output handling
+//!
+//! Output piped into a reader that stops early, like `v8 script.js | head`,
+//! must end the process quietly instead of panicking in `println!`, and
+//! scripts too large to read must be refused with a clear message.
+
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use assert_cmd::cargo::CommandCargoExt;
+use assert_cmd::Command as AssertCommand;
+
+/// Write `contents` to a fresh file in the temp directory
+fn script(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("v8-cli-{}-{}.js", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_closed_pipe_exits_cleanly() {
+    let path = script("pipe", "for (let i = 0; i < 200000; i++) { console.log('line ' + i); print(i); }");
+    let mut child = Command::cargo_bin("v8").unwrap()
+        .arg(&path)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    
+    // Read a little, then hang up like `head` does
+    let mut stdout = child.stdout.take().unwrap();
+    let mut start = [0u8; 64];
+    stdout.read_exact(&mut start).unwrap();
+    drop(stdout);
+    
+    let output = child.wait_with_output().unwrap();
+    fs::remove_file(&path).unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(String::from_utf8_lossy(&start).starts_with("line 0\n0\nline 1\n"));
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+    assert!(stderr.is_empty(), "{}", stderr);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_output_and_errors() {
+    let path = script("output", "console.log('hello'); print(1.5, 'x'); 6 * 7");
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg(&path)
+        .assert()
+        .success()
+        .stdout("hello\n1.5 x\n42\n")
+        .stderr("");
+    fs::remove_file(&path).unwrap();
+    
+    let path = script("error", "console.log('before'); null.x;");
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg(&path).assert().code(1).stdout("before\n");
+    fs::remove_file(&path).unwrap();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert_eq!(stderr, "error[E4003]: TypeError: Cannot read properties of null (reading 'x') (line 1, column 28)\n");
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn test_timing_table_goes_to_stderr() {
+    let path = script("timing", "let n = 0; for (let i = 0; i < 10; i++) { n += i; } console.log(n);");
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--timing").arg(&path).assert().success().stdout("45\n");
+    fs::remove_file(&path).unwrap();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.contains("execute") && stderr.contains("instructions executed"), "{}", stderr);
+}
+
+#[test]
+fn test_large_scripts_run_or_hit_the_size_limit() {
+    // A generated bundle: a 25MB comment, then a 25MB string literal
+    let source = format!("// {}\nlet s = '{}'; s.length", "c".repeat(25_000_000), "a".repeat(25_000_000));
+    let path = script("large", &source);
+    drop(source);
+    
+    let start = Instant::now();
+    let assert = AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--max-source-size").arg("1000000").arg(&path)
+        .assert().code(1).stdout("");
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.contains("the script is 50000024 bytes, over the limit of 1000000"), "{}", stderr);
+    assert!(start.elapsed() < Duration::from_secs(5));
+    
+    let start = Instant::now();
+    AssertCommand::cargo_bin("v8").unwrap().arg(&path).assert().success().stdout("25000000\n");
+    fs::remove_file(&path).unwrap();
+    assert!(start.elapsed() < Duration::from_secs(120), "took {:?}", start.elapsed());
+}
+
+#[test]
+fn test_invalid_max_source_size() {
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--max-source-size=lots").arg("script.js")
+        .assert().code(1).stderr("--max-source-size needs a size in bytes, not 'lots'\n");
+}
+
+#[test]
+fn test_bytecode_dumps_stay_off_stdout() {
+    let path = script("dump", "function twice(n) { return n * 2; } console.log(twice(21));");
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--dump-bytecode").arg(&path).assert().success().stdout("42\n");
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.starts_with("format v8-rs bytecode text\nversion 2\n"), "{}", stderr);
+    assert!(stderr.contains("\nfunction main/0\nsignature function twice(arg0)\n"), "{}", stderr);
+    
+    let json = std::env::temp_dir().join(format!("v8-cli-{}-dump.json", std::process::id()));
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--dump-bytecode=json").arg("--output").arg(&json).arg(&path)
+        .assert().success().stdout("42\n").stderr("");
+    fs::remove_file(&path).unwrap();
+    let dump = v8::bytecode::BytecodeDump::from_json(&fs::read_to_string(&json).unwrap()).unwrap();
+    fs::remove_file(&json).unwrap();
+    assert_eq!(dump.main.functions[0].name, "twice");
+    
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--dump-bytecode=yaml").arg("script.js")
+        .assert().code(1).stderr("--dump-bytecode writes text or json, not 'yaml'\n");
+}
+
+#[test]
+fn test_ast_dump_does_not_run_the_script() {
+    let path = script("ast", "console.log(1);");
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--dump-ast=json").arg(&path).assert().success().stderr("");
+    fs::remove_file(&path).unwrap();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    let program = v8::parser::estree::Node::from_json(&stdout).unwrap();
+    assert_eq!(program.kind.type_name(), "Program");
+    assert_eq!(program.end, Some(15));
+}
+
+#[test]
+fn test_debug_modes_report_errors_with_their_codes() {
+    let path = script("debug", "let x = 1;\nlet x = 2;");
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--debug-bytecode").arg(&path).assert();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.contains("error[E3001]: Variable 'x' already declared in this scope (line 2, column 1)"), "{}", stderr);
+    
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--debug-ast").write_stdin("1 +\n").assert();
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.starts_with("error[E2"), "{}", stderr);
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_script_from_stdin() {
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg("-").write_stdin("console.log('from stdin'); 1 + 1")
+        .assert().success().stdout("from stdin\n2\n").stderr("");
+    
+    // Other modes read stdin the same way, and the size limit holds
+    let assert = AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--dump-ast=json").arg("-").write_stdin("1;")
+        .assert().success();
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    assert!(stdout.contains("\"Program\""), "{}", stdout);
+    let assert = AssertCommand::cargo_bin("v8").unwrap()
+        .args(["--max-source-size", "4", "-"]).write_stdin("1 + 1 + 1")
+        .assert().code(1).stdout("");
+    let stderr = String::from_utf8_lossy(&assert.get_output().stderr).into_owned();
+    assert!(stderr.contains("over the limit of 4"), "{}", stderr);
+}
+
+#[test]
+fn test_eval_flags_run_in_order_in_one_vm() {
+    // Unlike a script, -e prints nothing of its own
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-e", "console.log(6 * 7)"])
+        .assert().success().stdout("42\n").stderr("");
+    
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-e", "count = 1", "-e", "count += 1; console.log('second')", "-e", "console.log('count', count)"])
+        .assert().success().stdout("second\ncount 2\n");
+    
+    // Declarations are seen by later snippets
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-e", "let a = 1", "-e", "const b = a + 1; function twice(n) { return n * 2; }", "-p", "twice(a + b)"])
+        .assert().success().stdout("6\n");
+    
+    // A failing snippet stops the ones after it
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-e", "console.log('a')", "-e", "missing()", "-e", "console.log('c')"])
+        .assert().code(1).stdout("a\n");
+}
+
+#[test]
+fn test_eval_flag_runs_like_a_script() {
+    // Declarations bind globals in -e code, but a name read before its
+    // `let`, `const` or `class` must still be an error
+    for (index, source) in [
+        "x; let x = 1",
+        "console.log(typeof x); let x = 1",
+        "new C(); class C {}",
+        "'use strict'; w = 1; let w = 2",
+        "'use strict'; const y = 2; console.log(y)",
+        "console.log(z); var z = 1",
+        "console.log(f()); function f() { return 3; }",
+    ].into_iter().enumerate() {
+        let path = script(&format!("snippet-{}", index), source);
+        let file = Command::cargo_bin("v8").unwrap().arg(&path).output().unwrap();
+        fs::remove_file(&path).unwrap();
+        let snippet = Command::cargo_bin("v8").unwrap().args(["-e", source]).output().unwrap();
+        assert_eq!(
+            (snippet.status.code(), String::from_utf8_lossy(&snippet.stdout), String::from_utf8_lossy(&snippet.stderr)),
+            (file.status.code(), String::from_utf8_lossy(&file.stdout), String::from_utf8_lossy(&file.stderr)),
+            "{}", source
+        );
+    }
+}
+
+#[test]
+fn test_print_flag_prints_every_completion_value() {
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-p", "6 * 7", "-p", "'plain ' + 'string'", "-p", "undefined", "-e", "1", "-p", "[1, 2]"])
+        .assert().success().stdout("42\nplain string\nundefined\n[ 1, 2 ]\n");
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-p", "({ list: ['a', 1] })"])
+        .assert().success().stdout("{ list: [ 'a', 1 ] }\n");
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-p", "let a = []; for (let i = 0; i < 100000; i++) a = [a]; a"])
+        .assert().success().stdout("[ [ [ [Array] ] ] ]\n");
+}
+
+#[test]
+fn test_help_and_bad_arguments() {
+    let assert = AssertCommand::cargo_bin("v8").unwrap().arg("--help").assert().success().stderr("");
+    let stdout = String::from_utf8_lossy(&assert.get_output().stdout).into_owned();
+    for flag in ["-e CODE", "-p CODE", "--debug-tokens", "--debug-ast", "--debug-bytecode", "--dump-ast=json",
+                 "--dump-bytecode", "--output", "--profile", "--timing", "--max-source-size", "--help"] {
+        assert!(stdout.contains(flag), "{} missing from\n{}", flag, stdout);
+    }
+    
+    AssertCommand::cargo_bin("v8").unwrap()
+        .arg("--frobnicate").assert().code(1).stdout("").stderr("Unknown option '--frobnicate' (see --help)\n");
+    AssertCommand::cargo_bin("v8").unwrap()
+        .args(["-e", "1", "script.js"]).assert().code(1)
+        .stderr("-e and -p cannot be combined with the script 'script.js'\n");
+}
@@ -0,0 +1,59 @@
+//! Helpers shared by the integration tests
+//!
+//! Each test binary that uses them declares `mod common;`. Not every
+//! binary uses every helper.
+#![allow(dead_code)]
+
+use v8::{BytecodeFunction, Compiler, Engine, Error, ErrorCode, Lexer, Parser, Value};
+
+/// The value `source` evaluates to on a fresh engine
+pub fn eval(source: &str) -> Value {
+    Engine::new().execute_detailed(source).result
+        .unwrap_or_else(|error| panic!("{}: {}", source, error))
+}
+
+/// The message of the error `source` fails with, checking its code
+pub fn eval_error(source: &str, code: ErrorCode) -> String {
+    match Engine::new().execute_detailed(source).result {
+        Ok(value) => panic!("{}: expected an error, got {}", source, value),
+        Err(error) => {
+            assert_eq!(error.code(), code, "{}", source);
+            error.to_string()
+        }
+    }
+}
+
+/// The first error `source` raises on a fresh engine, through every phase
+pub fn error_of(source: &str) -> Error {
+    Engine::new().execute(source).unwrap_err()
+}
+
+/// An engine that runs the bytecode optimizer
+pub fn optimizing() -> Engine {
+    Engine::builder().optimize(true).build()
+}
+
+/// `source` compiled as a main script, hoisting up to `max_constants`
+/// loop constants into slots when given a limit
+pub fn compile(source: &str, max_constants: Option<usize>) -> BytecodeFunction {
+    let program = Parser::from_source(Lexer::new(source)).parse().unwrap();
+    let compiler = Compiler::new_main(source);
+    let compiler = match max_constants {
+        Some(max_constants) => compiler.with_constant_hoisting(max_constants),
+        None => compiler,
+    };
+    compiler.compile(&program).unwrap()
+}
+
+/// Run `source` on a persistent engine that records events in `log`
+pub fn engine_with(source: &str) -> Engine {
+    let mut engine = Engine::new_persistent();
+    let setup = format!("log = ''; function record(s) {{ log = log + s; }} {}", source);
+    engine.execute_detailed(&setup).result.unwrap();
+    engine
+}
+
+/// What the events recorded so far spell out
+pub fn log(engine: &mut Engine) -> Value {
+    engine.execute_detailed("log").result.unwrap()
+}
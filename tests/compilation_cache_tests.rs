@@ -0,0 +1,63 @@
+//! Tests for the engine's compilation cache
+
+use v8::{CacheStats, Engine, Phase, Value};
+
+#[test]
+fn test_second_execution_skips_compilation() {
+    let mut engine = Engine::builder().compilation_cache(8).build();
+    let source = "let x = 40; x + 2";
+    
+    let first = engine.execute_detailed(source);
+    assert_eq!(first.result.unwrap(), Value::Number(42.0));
+    assert!(first.ast.is_some());
+    assert_eq!(engine.cache_stats(), CacheStats { hits: 0, misses: 1, entries: 1 });
+    
+    let second = engine.execute_detailed(source);
+    assert_eq!(second.result.unwrap(), Value::Number(42.0));
+    assert_eq!(second.phase, Phase::Execute);
+    // Nothing was lexed, parsed or compiled
+    assert!(second.tokens.is_none() && second.ast.is_none());
+    assert!(second.timings.compile.is_zero());
+    assert_eq!(engine.cache_stats(), CacheStats { hits: 1, misses: 1, entries: 1 });
+    
+    // Both runs executed the very same function
+    assert!(std::sync::Arc::ptr_eq(&first.bytecode.unwrap(), &second.bytecode.unwrap()));
+}
+
+#[test]
+fn test_cached_script_sees_current_globals() {
+    let mut engine = Engine::builder().compilation_cache(8).persistent(true).build();
+    let bump = "counter = counter + 1; counter";
+    engine.execute_detailed("counter = 0").result.unwrap();
+    assert_eq!(engine.execute_detailed(bump).result.unwrap(), Value::Number(1.0));
+    assert_eq!(engine.execute_detailed(bump).result.unwrap(), Value::Number(2.0));
+    assert_eq!(engine.cache_stats().hits, 1);
+}
+
+#[test]
+fn test_compile_cached_and_clear_cache() {
+    let mut engine = Engine::builder().compilation_cache(1).build();
+    let function = engine.compile_cached("6 * 7").unwrap();
+    assert_eq!(engine.execute_compiled(&function).unwrap(), Value::Number(42.0));
+    assert!(std::sync::Arc::ptr_eq(&function, &engine.compile_cached("6 * 7").unwrap()));
+    
+    // A capacity of one keeps only the latest script
+    engine.compile_cached("1").unwrap();
+    assert_eq!(engine.cache_stats(), CacheStats { hits: 1, misses: 2, entries: 1 });
+    
+    engine.clear_cache();
+    assert_eq!(engine.cache_stats().entries, 0);
+    
+    // Errors are reported and never cached
+    assert!(engine.compile_cached("let = 1").is_err());
+    assert_eq!(engine.cache_stats().entries, 0);
+}
+
+#[test]
+fn test_cache_is_off_by_default() {
+    let mut engine = Engine::new();
+    engine.execute_detailed("1").result.unwrap();
+    let report = engine.execute_detailed("1");
+    assert!(report.ast.is_some());
+    assert_eq!(engine.cache_stats(), CacheStats::default());
+}
@@ -0,0 +1,3 @@
+// description: bitwise operators work on 32-bit integers
+// expect-output: 1 7 6 -6
+console.log(5 & 3, 5 | 3, 5 ^ 3, ~5)
@@ -0,0 +1,3 @@
+// description: division by zero gives infinities and NaN
+// expect-output: Infinity -Infinity NaN
+console.log(1 / 0, -1 / 0, 0 / 0)
@@ -0,0 +1,8 @@
+// description: ** is right-associative
+// feature: exponentiation
+// expect-output: 1024
+// expect-output: 4
+// expect-output: 512
+console.log(2 ** 10)
+console.log((-2) ** 2)
+console.log(2 ** 3 ** 2)
@@ -0,0 +1,5 @@
+// description: % takes the sign of the dividend
+// expect-output: 1
+// expect-output: -1
+console.log(7 % -3)
+console.log(-7 % 3)
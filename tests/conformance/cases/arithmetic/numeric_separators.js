@@ -0,0 +1,4 @@
+// description: underscores separate digits in every radix
+// feature: numeric-separators
+// expect-value: 1000170
+1_000_000 + 0b1010_1010
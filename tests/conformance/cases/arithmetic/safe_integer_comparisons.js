@@ -0,0 +1,6 @@
+// description: comparisons around 2^53 see the rounded doubles
+// expect-output: true true false
+// expect-output: true true true
+const big = 2 ** 53;
+console.log(big + 1 === big, big - 1 < big, big + 2 === big)
+console.log(Number.MAX_SAFE_INTEGER + 2 === big, big + 2 > big + 1, Number.MIN_SAFE_INTEGER - 2 === -big)
@@ -0,0 +1,14 @@
+// description: += and -= at the edge of the safe integers lose precision like doubles
+// expect-output: 9007199254740992 9007199254740992 9007199254740994
+// expect-output: 9007199254740990 9007199254740992
+let a = Number.MAX_SAFE_INTEGER;
+a += 1;
+let b = a;
+b += 1;
+let c = a;
+c += 2;
+console.log(a, b, c)
+let d = 2 ** 53 - 2;
+let e = 2 ** 53 + 2;
+e -= 2;
+console.log(d, e)
@@ -0,0 +1,20 @@
+// description: ++ and -- past 2^53 round like doubles instead of wrapping
+// expect-output: 9007199254740992 9007199254740992 9007199254740992
+// expect-output: 9007199254740996 9007199254740998
+// expect-output: -9007199254740992 -9007199254740992
+let x = Number.MAX_SAFE_INTEGER;
+x++;
+let y = x;
+y++;
+let z = 2 ** 53;
+z++;
+console.log(x, y, z)
+// 2^53 + 3 is halfway between doubles and rounds to the even one
+let up = 2 ** 53 + 2;
+up++;
+let up2 = up + 2;
+console.log(up, up2)
+let down = Number.MIN_SAFE_INTEGER;
+down--;
+down--;
+console.log(down, 2 ** 53 * -1)
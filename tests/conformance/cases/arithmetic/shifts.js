@@ -0,0 +1,7 @@
+// description: shifts wrap at 32 bits; >>> is unsigned
+// expect-output: -2147483648
+// expect-output: 15
+// expect-output: -4
+console.log(1 << 31)
+console.log(-1 >>> 28)
+console.log(-16 >> 2)
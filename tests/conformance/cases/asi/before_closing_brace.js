@@ -0,0 +1,4 @@
+// description: a semicolon is inserted before }
+// expect-value: 1
+function f() { return 1 }
+f()
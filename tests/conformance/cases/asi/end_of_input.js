@@ -0,0 +1,4 @@
+// description: a semicolon is inserted at the end of the input
+// expect-value: 3
+let a = 3
+a
\ No newline at end of file
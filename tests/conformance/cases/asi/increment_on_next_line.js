@@ -0,0 +1,7 @@
+// description: ++ after a line break applies to the following operand
+// skip: ASI before a prefix ++ is not implemented
+// expect-value: [ 1, 2 ]
+let x = 1
+let y = x
+++y
+;[x, y]
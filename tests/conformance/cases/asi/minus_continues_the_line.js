@@ -0,0 +1,5 @@
+// description: a line starting with - continues the previous expression
+// expect-value: 4
+let z = 5
+-1
+z
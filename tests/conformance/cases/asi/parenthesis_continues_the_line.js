@@ -0,0 +1,5 @@
+// description: a line starting with ( calls the previous line's value
+// expect-error: TypeError
+let a = 1
+let b = a
+(function () {})
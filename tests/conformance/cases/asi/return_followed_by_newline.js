@@ -0,0 +1,7 @@
+// description: a line break after return ends the statement
+// expect-value: undefined
+function f() {
+  return
+    42
+}
+f()
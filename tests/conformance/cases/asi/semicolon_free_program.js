@@ -0,0 +1,8 @@
+// description: a program without semicolons runs like one with them
+// expect-value: 10
+let total = 0
+for (let i = 0; i < 5; i++) {
+  total += i
+}
+total = total
+total
@@ -0,0 +1,3 @@
+// description: no semicolon is inserted between statements on one line
+// expect-error: SyntaxError
+let a = 1 let b = 2
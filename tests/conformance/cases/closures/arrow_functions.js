@@ -0,0 +1,16 @@
+// description: arrow functions take `this` from where they were made
+// feature: arrow-functions
+// expect-output: 5 16 10 7
+// expect-output: async 2
+let add = (a, b) => a + b;
+let square = x => x * x;
+let counter = {
+  n: 5,
+  scaled: function () {
+    let times = v => v * this.n;
+    return times(2);
+  }
+};
+let increment = async x => x + 1;
+increment(1).then(v => console.log('async', v));
+console.log(add(2, 3), square(4), counter.scaled(), (() => 7)());
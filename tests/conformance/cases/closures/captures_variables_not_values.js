@@ -0,0 +1,6 @@
+// description: closures see later assignments to captured variables
+// expect-value: 2
+let x = 1
+function read() { return x }
+x = 2
+read()
@@ -0,0 +1,10 @@
+// description: a closure keeps its own copy of the enclosing variables
+// expect-value: 3
+function counter() {
+  let n = 0
+  return function () { n++; return n }
+}
+let c = counter()
+c()
+c()
+c()
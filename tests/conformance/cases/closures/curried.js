@@ -0,0 +1,8 @@
+// description: closures nest
+// expect-value: 6
+function add(a) {
+  return function (b) {
+    return function (c) { return a + b + c }
+  }
+}
+add(1)(2)(3)
@@ -0,0 +1,6 @@
+// description: defaults replace missing and undefined arguments
+// feature: default-parameters
+// skip: default parameters are not implemented
+// expect-output: 1 2
+function f(a = 1) { return a }
+console.log(f(), f(2))
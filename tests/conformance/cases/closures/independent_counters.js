@@ -0,0 +1,9 @@
+// description: each call of the outer function makes new variables
+// expect-output: 1 2 1
+function counter() {
+  let n = 0
+  return function () { n++; return n }
+}
+let a = counter()
+let b = counter()
+console.log(a(), a(), b())
@@ -0,0 +1,8 @@
+// description: each iteration of a for-let loop has its own binding
+// skip: known bug, closures made in the loop share one binding
+// expect-value: 3
+let fs = []
+for (let i = 0; i < 3; i++) {
+  fs.push(function () { return i })
+}
+fs[0]() + fs[1]() + fs[2]()
@@ -0,0 +1,7 @@
+// description: var loop variables are shared by every iteration
+// expect-value: 9
+let fs = []
+for (var i = 0; i < 3; i++) {
+  fs.push(function () { return i })
+}
+fs[0]() + fs[1]() + fs[2]()
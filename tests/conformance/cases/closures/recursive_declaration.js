@@ -0,0 +1,7 @@
+// description: a function declaration can call itself through its name
+// expect-value: 55
+function fib(n) {
+  if (n < 2) return n
+  return fib(n - 1) + fib(n - 2)
+}
+fib(10)
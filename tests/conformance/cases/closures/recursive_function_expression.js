@@ -0,0 +1,5 @@
+// description: a named function expression can call itself
+// skip: named function expressions don't bind their own name
+// expect-value: 55
+let fib = function f(n) { if (n < 2) return n; return f(n - 1) + f(n - 2) }
+fib(10)
@@ -0,0 +1,6 @@
+// description: a rest parameter collects the remaining arguments
+// feature: rest-parameters
+// skip: rest parameters are not implemented
+// expect-value: 2
+function f(first, ...rest) { return rest.length }
+f(1, 2, 3)
@@ -0,0 +1,15 @@
+// description: functions naming the same globals and properties all see one binding
+// expect-output: 1 1 2
+// expect-output: 3 3 3
+// expect-output: 6
+let count = 0;
+function bump() { count = count + 1; return count; }
+function read() { return count; }
+let o = { length: 3 };
+function viaA(x) { return x.length; }
+function viaB(x) { return x.length; }
+console.log(bump(), read(), (function () { count = count + 1; return count; })());
+console.log(viaA(o), viaB(o), viaA([1, 2, 3]));
+total = 6;
+function readTotal() { return total; }
+console.log(readTotal());
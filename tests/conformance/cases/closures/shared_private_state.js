@@ -0,0 +1,15 @@
+// description: methods closing over one variable share it
+// expect-output: 5
+// expect-output: 3
+function account(balance) {
+  return {
+    deposit: function (n) { balance += n },
+    withdraw: function (n) { balance -= n },
+    balance: function () { return balance },
+  }
+}
+let acc = account(0)
+acc.deposit(5)
+console.log(acc.balance())
+acc.withdraw(2)
+console.log(acc.balance())
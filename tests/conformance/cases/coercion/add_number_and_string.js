@@ -0,0 +1,3 @@
+// description: + concatenates when either operand is a string
+// expect-value: '12'
+1 + '2'
@@ -0,0 +1,7 @@
+// description: + converts arrays and objects to primitives first
+// expect-output: ''
+// expect-output: '1,2'
+// expect-output: '[object Object]'
+console.log("'" + ([] + []) + "'")
+console.log("'" + ([1, 2] + '') + "'")
+console.log("'" + ({} + '') + "'")
@@ -0,0 +1,7 @@
+// description: + converts booleans, null and undefined to numbers
+// expect-output: 2
+// expect-output: 1
+// expect-output: NaN
+console.log(true + 1)
+console.log(null + 1)
+console.log(undefined + 1)
@@ -0,0 +1,3 @@
+// description: arrays join their elements, nested ones included
+// expect-value: '1,2,3|null|undefined'
+'' + [1, [2, 3]] + '|' + null + '|' + undefined
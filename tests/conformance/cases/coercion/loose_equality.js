@@ -0,0 +1,9 @@
+// description: == coerces across types
+// expect-output: true
+// expect-output: true
+// expect-output: false
+// expect-output: true
+console.log(1 == '1')
+console.log(null == undefined)
+console.log(NaN == NaN)
+console.log('0' == false)
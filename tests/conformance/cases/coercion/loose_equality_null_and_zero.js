@@ -0,0 +1,4 @@
+// description: null is only loosely equal to undefined
+// skip: known bug, null == 0 is true
+// expect-value: false
+null == 0
@@ -0,0 +1,3 @@
+// description: * converts both string operands to numbers
+// expect-value: 12
+'3' * '4'
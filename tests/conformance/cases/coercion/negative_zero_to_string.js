@@ -0,0 +1,3 @@
+// description: -0 converts to '0'
+// expect-value: '0'
+-0 + ''
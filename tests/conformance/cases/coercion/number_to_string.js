@@ -0,0 +1,7 @@
+// description: numbers convert to their shortest round-tripping form
+// expect-output: 0.30000000000000004
+// expect-output: 1.5
+// expect-output: -12
+console.log((0.1 + 0.2) + '')
+console.log(1.5 + '')
+console.log(-12 + '')
@@ -0,0 +1,3 @@
+// description: large numbers convert in exponent form
+// expect-value: '1e+21'
+1e21 + ''
@@ -0,0 +1,7 @@
+// description: parseInt and parseFloat stop at the first invalid character
+// expect-output: 42
+// expect-output: 35
+// expect-output: NaN
+console.log(parseInt('42px'))
+console.log(parseFloat('3.5e1x'))
+console.log(parseInt('abc'))
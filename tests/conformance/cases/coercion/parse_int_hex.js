@@ -0,0 +1,4 @@
+// description: parseInt reads a 0x prefix as base 16
+// skip: known bug, parseInt stops at the x
+// expect-value: 31
+parseInt('0x1f')
@@ -0,0 +1,9 @@
+// description: two strings compare by code units, otherwise as numbers
+// expect-output: true
+// expect-output: true
+// expect-output: false
+// expect-output: false
+console.log('b' > 'a')
+console.log('10' < '9')
+console.log(10 < 9)
+console.log('10' < 9)
@@ -0,0 +1,7 @@
+// description: null compares as 0, undefined as NaN
+// expect-output: true
+// expect-output: false
+// expect-output: false
+console.log(null >= 0)
+console.log(undefined >= 0)
+console.log(undefined == 0)
@@ -0,0 +1,7 @@
+// description: === never coerces; 0 and -0 are equal, NaN is not
+// expect-output: false
+// expect-output: true
+// expect-output: false
+console.log(1 === '1')
+console.log(0 === -0)
+console.log(NaN === NaN)
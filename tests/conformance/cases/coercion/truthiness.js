@@ -0,0 +1,3 @@
+// description: only false, 0, -0, NaN, '', null and undefined are falsy
+// expect-output: false true true true false false
+console.log(!!'', !!'0', !![], !!{}, !!NaN, !!-0)
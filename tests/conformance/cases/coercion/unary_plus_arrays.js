@@ -0,0 +1,8 @@
+// description: arrays convert through their string form
+// skip: known bug, arrays convert to NaN
+// expect-output: 0
+// expect-output: 5
+// expect-output: NaN
+console.log(+[])
+console.log(+[5])
+console.log(+[1, 2])
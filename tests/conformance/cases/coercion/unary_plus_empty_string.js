@@ -0,0 +1,4 @@
+// description: the empty string converts to 0
+// skip: known bug, the empty string converts to NaN
+// expect-value: 0
++''
@@ -0,0 +1,4 @@
+// description: hex strings convert to numbers
+// skip: known bug, string to number conversion rejects 0x prefixes
+// expect-value: 16
++'0x10'
@@ -0,0 +1,7 @@
+// description: unary + trims whitespace and accepts exponents
+// expect-output: 12
+// expect-output: 1000
+// expect-output: NaN
+console.log(+' 12 ')
+console.log(+'1e3')
+console.log(+'abc')
@@ -0,0 +1,4 @@
+// description: calling a non-function throws
+// expect-error: TypeError
+let n = 1
+n()
@@ -0,0 +1,4 @@
+// description: strict mode code may not repeat a parameter name
+// expect-error: SyntaxError
+console.log('never');
+function f(a, a) { 'use strict'; }
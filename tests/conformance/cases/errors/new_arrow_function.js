@@ -0,0 +1,4 @@
+// description: arrow functions are not constructors
+// expect-error: TypeError
+let make = () => {};
+new make();
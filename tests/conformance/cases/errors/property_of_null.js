@@ -0,0 +1,4 @@
+// description: reading a property of null throws
+// expect-error: TypeError
+let o = null
+o.x
@@ -0,0 +1,4 @@
+// description: unbounded recursion throws a RangeError
+// expect-error: RangeError
+function f() { return 1 + f() }
+f()
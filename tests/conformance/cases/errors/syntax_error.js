@@ -0,0 +1,4 @@
+// description: malformed source is rejected before running
+// expect-error: SyntaxError
+console.log('never')
+let = ;
@@ -0,0 +1,4 @@
+// description: a unary expression can't be the base of ** without parentheses
+// expect-error: SyntaxError
+console.log('never');
+let x = -2 ** 2;
@@ -0,0 +1,3 @@
+// description: reading an undeclared variable throws
+// expect-error: ReferenceError
+missing + 1
@@ -0,0 +1,8 @@
+// description: && gives the first falsy operand or the last one
+// skip: known bug, && always gives its left operand
+// expect-output: 2
+// expect-output: 0
+// expect-output: b
+console.log(1 && 2)
+console.log(0 && 2)
+console.log('a' && 'b')
@@ -0,0 +1,8 @@
+// description: ?: evaluates only the chosen branch
+// feature: conditional-operator
+// skip: the conditional operator is not implemented
+// expect-value: 1
+let calls = 0
+function f() { calls++ }
+true ? f() : f()
+calls
@@ -0,0 +1,7 @@
+// description: ?? only replaces null and undefined
+// feature: nullish-coalescing
+// skip: ?? is not implemented
+// expect-output: d
+// expect-output: 0
+console.log(null ?? 'd')
+console.log(0 ?? 'd')
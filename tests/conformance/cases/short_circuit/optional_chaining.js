@@ -0,0 +1,6 @@
+// description: ?. stops at null and undefined
+// feature: optional-chaining
+// skip: ?. is not implemented
+// expect-output: undefined 1
+let o = { a: { b: 1 } }
+console.log(o.missing?.b, o.a?.b)
@@ -0,0 +1,4 @@
+// description: || gives the right operand even when it is falsy
+// skip: known bug, || gives its left operand when both are falsy
+// expect-value: 0
+null || 0
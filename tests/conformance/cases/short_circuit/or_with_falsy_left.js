@@ -0,0 +1,5 @@
+// description: || gives its right operand when the left is falsy
+// expect-output: x
+// expect-output: e
+console.log(0 || 'x')
+console.log('' || 'e')
@@ -0,0 +1,8 @@
+// description: the right operand of && and || runs only when needed
+// skip: known bug, both operands are always evaluated
+// expect-value: 0
+let calls = 0
+function f() { calls++; return true }
+false && f()
+true || f()
+calls
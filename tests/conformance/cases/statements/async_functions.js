@@ -0,0 +1,7 @@
+// description: async functions resume after await, once synchronous code is done
+// feature: async-functions
+// expect-output: before
+// expect-output: after 2
+async function f(x) { let y = await x; console.log('after', y + 1) }
+f(1)
+console.log('before')
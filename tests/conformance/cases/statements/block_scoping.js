@@ -0,0 +1,11 @@
+// description: let and const are scoped to their block
+// feature: block-scoping
+// expect-output: 2 3
+// expect-value: 1
+let x = 1
+{
+  let x = 2
+  const y = 3
+  console.log(x, y)
+}
+x
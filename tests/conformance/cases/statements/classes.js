@@ -0,0 +1,8 @@
+// description: class instances get prototype methods
+// feature: classes
+// expect-output: 8 true
+class A {
+  constructor(x) { this.x = x }
+  double() { return this.x * 2 }
+}
+console.log(new A(4).double(), new A(1) instanceof A)
@@ -0,0 +1,5 @@
+// description: a trailing declaration keeps the completion value of the statement before it
+// skip: known bug, a trailing declaration resets the completion value to undefined
+// expect-value: 1
+1
+function f() {}
@@ -0,0 +1,7 @@
+// description: array and object patterns bind parts of a value
+// feature: destructuring
+// skip: destructuring is not implemented
+// expect-value: 6
+let [a, b] = [1, 2]
+let { c } = { c: 3 }
+a + b + c
@@ -0,0 +1,7 @@
+// description: do-while runs its body at least once
+// feature: do-while
+// skip: do-while is not implemented
+// expect-value: 1
+let k = 0
+do { k++ } while (false)
+k
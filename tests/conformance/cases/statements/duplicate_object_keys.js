@@ -0,0 +1,5 @@
+// description: a repeated object key keeps its first position and its last value
+// expect-output: a,b 3 2
+'use strict'
+let o = { a: 1, b: 2, a: 3 }
+console.log(Object.keys(o), o.a, o.b)
@@ -0,0 +1,7 @@
+// description: synchronous code, then microtasks, then timers
+// expect-output: sync
+// expect-output: micro
+// expect-output: timer
+setTimeout(function () { console.log('timer') }, 0)
+Promise.resolve().then(function () { console.log('micro') })
+console.log('sync')
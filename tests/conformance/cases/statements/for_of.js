@@ -0,0 +1,7 @@
+// description: for-of walks strings by code point and arrays by element
+// feature: for-of
+// expect-value: 'a😀12'
+let s = ''
+for (const c of 'a😀') s += c
+for (const n of [1, 2]) s += n
+s
@@ -0,0 +1,6 @@
+// description: function declarations can be called before they appear
+// feature: function-hoisting
+// expect-value: 'h'
+let result = hoisted()
+function hoisted() { return 'h' }
+result
@@ -0,0 +1,7 @@
+// description: for-of drives a generator to completion
+// feature: generators
+// expect-value: 6
+function* numbers() { yield 1; yield 2; yield 3 }
+let total = 0
+for (const n of numbers()) total += n
+total
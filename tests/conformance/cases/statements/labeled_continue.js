@@ -0,0 +1,13 @@
+// description: continue and break can target an outer loop
+// feature: labels
+// skip: labels are not implemented
+// expect-value: '0010'
+let s = ''
+outer: for (let i = 0; i < 3; i++) {
+  for (let j = 0; j < 3; j++) {
+    if (j == 1) continue outer
+    if (i == 2) break outer
+    s += i + '' + j
+  }
+}
+s
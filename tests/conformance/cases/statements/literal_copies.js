@@ -0,0 +1,15 @@
+// description: every evaluation of a constant literal makes new objects, nested ones included
+// expect-output: false false
+// expect-output: 2 3
+// expect-output: {"x":1,"y":2,"z":"z"} xz
+function config() { return { list: [1, 2], inner: { n: 3 } }; }
+let first = config();
+let second = config();
+first.list.push(9);
+first.inner.n = 4;
+console.log(first === second, first.list === second.list);
+console.log(second.list.length, second.inner.n);
+let log = '';
+function note(v) { log = log + v; return v; }
+let mixed = { x: note('x'), y: 2, x: 1, z: note('z') };
+console.log(JSON.stringify(mixed), log);
@@ -0,0 +1,9 @@
+// description: the Number namespace's constants and predicates
+// expect-output: 9007199254740991 -9007199254740991 true
+// expect-output: true false false false
+// expect-output: true false false true
+// expect-output: true false false false
+console.log(Number.MAX_SAFE_INTEGER, Number.MIN_SAFE_INTEGER, Number.EPSILON === 2 ** -52)
+console.log(Number.isInteger(5), Number.isInteger(5.5), Number.isInteger('5'), Number.isInteger(Infinity))
+console.log(Number.isSafeInteger(2 ** 53 - 1), Number.isSafeInteger(2 ** 53), Number.isSafeInteger(1.5), Number.isSafeInteger(-(2 ** 53 - 1)))
+console.log(Number.isFinite(1e308), Number.isFinite(NaN), Number.isFinite('1'), Number.isFinite(-Infinity))
@@ -0,0 +1,6 @@
+// description: spread copies own properties, and later keys win
+// feature: object-spread
+// expect-output: 1 3 4
+let a = { x: 1, y: 2 }
+let b = { ...a, y: 3, z: 4 }
+console.log(b.x, b.y, b.z)
@@ -0,0 +1,11 @@
+// description: switch falls through until break
+// feature: switch
+// skip: switch is not implemented
+// expect-output: three
+// expect-output: four
+switch (3) {
+  case 1: console.log('one')
+  case 3: console.log('three')
+  case 4: console.log('four'); break
+  default: console.log('default')
+}
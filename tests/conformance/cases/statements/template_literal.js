@@ -0,0 +1,5 @@
+// description: template literals interpolate expressions
+// feature: template-literals
+// skip: template literals are not implemented
+// expect-value: 't2x'
+`t${1 + 1}x`
@@ -0,0 +1,13 @@
+// description: trailing commas in literals, calls, parameters and arrow parameters
+// feature: trailing-commas
+// expect-output: 3 2 1
+// expect-output: 2
+// expect-output: 3
+// expect-output: 6
+function add(a, b,) { return a + b; }
+let times = (x, y,) => x * y;
+let o = { a: 1, b: 2, };
+console.log([1, 2, 3,].length, [1,,].length, [,].length);
+console.log(o.b);
+console.log(add(1, 2,));
+console.log(times(2, 3,));
@@ -0,0 +1,6 @@
+// description: catch receives the thrown value and finally always runs
+// feature: try-catch
+// skip: try, catch and throw are not implemented
+// expect-output: caught 1
+// expect-output: finally
+try { throw 1 } catch (e) { console.log('caught', e) } finally { console.log('finally') }
@@ -0,0 +1,3 @@
+// description: typeof names a value's type
+// expect-value: 'number'
+typeof 1
@@ -0,0 +1,426 @@
+//! Conformance suite
+//!
+//! Runs every `.js` fixture under `tests/conformance/cases` through an
+//! `Engine` and checks it against the expectations in its front matter,
+//! the `//` comment lines at the top of the file:
+//!
+//! ```text
+//! // description: what the case covers
+//! // expect-value: 42            completion value, as `Value::inspect` shows it
+//! // expect-error: TypeError     the error's JavaScript name
+//! // expect-output: first line   one directive per console line, in order
+//! // skip: why it can't pass yet
+//! // feature: template-literals  the syntax feature the case exercises
+//! ```
+//!
+//! A case needs at least one expectation. `Engine::capabilities` must
+//! report a feature exactly when none of the cases naming it are skipped. Skipped cases still run, so the
+//! report can point out the ones that started passing. Set
+//! `CONFORMANCE_FILTER` to run only the cases whose path contains it.
+//!
+//! The suite runs four times: as is, with `VMConfig::frame_arena` on,
+//! since reused objects must behave exactly like fresh ones, with
+//! globals restored from `Snapshot::standard`, which must match the ones
+//! the built-ins set up, and with `VMConfig::gc_stress` on, which fails a
+//! case that keeps a value the collector can't see. Every case, skipped or not, also runs with and
+//! without `VMConfig::trace_execution`, which must not change what it
+//! prints or returns.
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use v8::{Engine, EngineIo, Error, Feature, Snapshot, VMConfig};
+
+/// Instructions one case may run, so a hanging case fails instead
+const MAX_INSTRUCTIONS: u64 = 10_000_000;
+
+/// How the engine running each fixture is set up
+#[derive(Debug, Clone, Copy)]
+enum Variant {
+    Default,
+    FrameArena,
+    Snapshot,
+    GcStress,
+}
+
+/// Expectations and directives read from a fixture's front matter
+#[derive(Debug, Default, PartialEq)]
+struct FrontMatter {
+    description: Option<String>,
+    expect_value: Option<String>,
+    expect_error: Option<String>,
+    expect_output: Vec<String>,
+    skip: Option<String>,
+    features: Vec<Feature>,
+}
+
+impl FrontMatter {
+    /// Parse the leading comment block of `source`
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut front_matter = FrontMatter::default();
+        for line in source.lines().map(str::trim) {
+            let Some(comment) = line.strip_prefix("//") else {
+                if line.is_empty() {
+                    continue;
+                }
+                break;
+            };
+            let Some((key, value)) = comment.split_once(':') else {
+                continue;
+            };
+            let value = value.strip_prefix(' ').unwrap_or(value).to_string();
+            match key.trim() {
+                "description" => front_matter.description = Some(value),
+                "expect-value" => front_matter.expect_value = Some(value),
+                "expect-error" => front_matter.expect_error = Some(value),
+                "expect-output" => front_matter.expect_output.push(value),
+                "skip" => front_matter.skip = Some(value),
+                "feature" => match Feature::from_name(&value) {
+                    Some(feature) => front_matter.features.push(feature),
+                    None => return Err(format!("unknown feature '{}'", value)),
+                },
+                other => return Err(format!("unknown directive '{}'", other)),
+            }
+        }
+        if front_matter.expect_value.is_some() && front_matter.expect_error.is_some() {
+            return Err("expect-value and expect-error exclude each other".to_string());
+        }
+        if front_matter.expect_value.is_none() && front_matter.expect_error.is_none() && front_matter.expect_output.is_empty() {
+            return Err("no expect-value, expect-error or expect-output directive".to_string());
+        }
+        Ok(front_matter)
+    }
+}
+
+/// What running a fixture produced
+struct Outcome {
+    completion: Result<String, String>,
+    output: Vec<String>,
+}
+
+/// Run `source` in a fresh engine, pumping the event loop on a virtual
+/// clock until nothing is pending
+fn run(source: &str, variant: Variant) -> Outcome {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let frame_arena = matches!(variant, Variant::FrameArena);
+    let gc_stress = matches!(variant, Variant::GcStress);
+    let config = VMConfig { max_instructions: Some(MAX_INSTRUCTIONS), frame_arena, gc_stress, ..VMConfig::default() };
+    let mut builder = Engine::builder().random_seed(0).vm_config(config);
+    if let Variant::Snapshot = variant {
+        builder = builder.snapshot(Snapshot::standard().expect("embedded snapshot is current"));
+    }
+    let mut engine = builder.build();
+    let sink = output.clone();
+    engine.set_console_handler(move |line| sink.borrow_mut().extend(line.lines().map(String::from)));
+    let sink = output.clone();
+    engine.set_uncaught_exception_handler(move |error| sink.borrow_mut().push(format!("Uncaught {}", error_name(&error))));
+    let sink = output.clone();
+    engine.set_unhandled_rejection_handler(move |reason| sink.borrow_mut().push(format!("Unhandled rejection {}", reason.inspect())));
+    
+    let completion = match engine.execute_detailed(source).result {
+        Ok(value) => Ok(value.inspect()),
+        Err(error) => Err(error_name(&error)),
+    };
+    let mut now = 0;
+    while matches!(engine.run_pending(now), Ok(true)) && now < 60_000 {
+        now += 10;
+    }
+    let output = output.borrow().clone();
+    Outcome { completion, output }
+}
+
+/// What a case shows the world when run with its streams captured: the
+/// completion, stdout, and the errors and rejections left uncaught
+#[derive(Debug, PartialEq)]
+struct Observed {
+    completion: Result<String, String>,
+    stdout: String,
+    uncaught: Vec<String>,
+}
+
+/// Counts the bytes written to it, standing in for a trace that may run
+/// to gigabytes
+#[derive(Clone, Default)]
+struct CountingSink(Rc<Cell<usize>>);
+
+impl io::Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.set(self.0.get() + buf.len());
+        Ok(buf.len())
+    }
+    
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Run `source` as `run` does, but printing to captured streams, with or
+/// without tracing; also returns how many bytes went to stderr
+fn run_observed(source: &str, trace_execution: bool) -> (Observed, usize) {
+    let config = VMConfig { max_instructions: Some(MAX_INSTRUCTIONS), ..VMConfig::default() };
+    let mut engine = Engine::builder().random_seed(0).vm_config(config).trace_execution(trace_execution).build();
+    let (mut io, output) = EngineIo::capture("");
+    let stderr = CountingSink::default();
+    io.stderr = Box::new(stderr.clone());
+    engine.set_io(io);
+    let uncaught = Rc::new(RefCell::new(Vec::new()));
+    let sink = uncaught.clone();
+    engine.set_uncaught_exception_handler(move |error| sink.borrow_mut().push(error.to_string()));
+    let sink = uncaught.clone();
+    engine.set_unhandled_rejection_handler(move |reason| sink.borrow_mut().push(reason.inspect()));
+    
+    let completion = engine.execute_detailed(source).result.map(|value| value.inspect()).map_err(|error| error.to_string());
+    let mut now = 0;
+    while matches!(engine.run_pending(now), Ok(true)) && now < 60_000 {
+        now += 10;
+    }
+    let uncaught = uncaught.borrow().clone();
+    (Observed { completion, stdout: output.stdout(), uncaught }, stderr.0.get())
+}
+
+/// The JavaScript name of an error: `SyntaxError` for lexer, parser and
+/// compile errors, otherwise the prefix of the runtime message
+fn error_name(error: &Error) -> String {
+    match error {
+        Error::Lexer { .. } | Error::Parser { .. } | Error::Compile { .. } => "SyntaxError".to_string(),
+        Error::Runtime { kind, .. } => {
+            let message = kind.to_string();
+            match message.split_once(':') {
+                Some((name, _)) if name.ends_with("Error") => name.to_string(),
+                _ => "Error".to_string(),
+            }
+        }
+        Error::Context { source, .. } | Error::Noted { source, .. } => error_name(source),
+        Error::Io(_) => "Error".to_string(),
+    }
+}
+
+/// Compare an outcome with the expectations, describing every mismatch
+fn check(front_matter: &FrontMatter, outcome: &Outcome) -> Option<String> {
+    let mut report = String::new();
+    match (&front_matter.expect_value, &front_matter.expect_error, &outcome.completion) {
+        (Some(expected), _, Ok(actual)) | (_, Some(expected), Err(actual)) if expected == actual => {}
+        (None, None, Ok(_)) => {}
+        (Some(expected), _, actual) | (_, Some(expected), actual) => {
+            let actual = match actual {
+                Ok(value) => format!("value {}", value),
+                Err(name) => format!("error {}", name),
+            };
+            let kind = if front_matter.expect_value.is_some() { "value" } else { "error" };
+            let _ = writeln!(report, "  expected {} {}, got {}", kind, expected, actual);
+        }
+        (None, None, Err(name)) => {
+            let _ = writeln!(report, "  unexpected error {}", name);
+        }
+    }
+    if !front_matter.expect_output.is_empty() && front_matter.expect_output != outcome.output {
+        let _ = writeln!(report, "  output differs (- expected, + actual):");
+        report.push_str(&diff_lines(&front_matter.expect_output, &outcome.output));
+    }
+    (!report.is_empty()).then_some(report)
+}
+
+/// A line diff, pairing lines by position
+fn diff_lines(expected: &[String], actual: &[String]) -> String {
+    let mut diff = String::new();
+    for index in 0..expected.len().max(actual.len()) {
+        match (expected.get(index), actual.get(index)) {
+            (Some(e), Some(a)) if e == a => {
+                let _ = writeln!(diff, "      {}", e);
+            }
+            (e, a) => {
+                if let Some(e) = e {
+                    let _ = writeln!(diff, "    - {}", e);
+                }
+                if let Some(a) = a {
+                    let _ = writeln!(diff, "    + {}", a);
+                }
+            }
+        }
+    }
+    diff
+}
+
+/// Every `.js` file under `dir`, sorted
+fn fixtures(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).expect("readable fixture directory") {
+            let path = entry.expect("readable fixture entry").path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "js") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+#[test]
+fn conformance_suite() {
+    run_suite(Variant::Default);
+}
+
+#[test]
+fn conformance_suite_with_frame_arena() {
+    run_suite(Variant::FrameArena);
+}
+
+#[test]
+fn conformance_suite_from_snapshot() {
+    run_suite(Variant::Snapshot);
+}
+
+#[test]
+fn conformance_suite_under_gc_stress() {
+    run_suite(Variant::GcStress);
+}
+
+#[test]
+fn conformance_suite_traced_matches_plain() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/cases");
+    let filter = std::env::var("CONFORMANCE_FILTER").unwrap_or_default();
+    let mut differences = String::new();
+    let mut compared = 0;
+    for path in fixtures(&root) {
+        let name = path.strip_prefix(&root).unwrap_or(&path).display().to_string();
+        if !name.contains(&filter) {
+            continue;
+        }
+        let source = fs::read_to_string(&path).expect("readable fixture");
+        let (plain, plain_trace) = run_observed(&source, false);
+        let (traced, trace) = run_observed(&source, true);
+        compared += 1;
+        if plain != traced {
+            let _ = writeln!(differences, "DIFF {}
+  plain:  {:?}
+  traced: {:?}", name, plain, traced);
+        }
+        // Only the traced run writes to stderr, and one that completed ran
+        // at least one instruction
+        if plain_trace != 0 || (trace == 0 && plain.completion.is_ok()) {
+            let _ = writeln!(differences, "TRACE {}
+  {} bytes untraced, {} traced", name, plain_trace, trace);
+        }
+    }
+    assert!(differences.is_empty(), "\n{}tracing changed {} case(s)", differences, differences.matches('\n').count());
+    assert!(compared > 0 || !filter.is_empty(), "no conformance cases found in {}", root.display());
+}
+
+/// Run every fixture, failing with a report of each mismatch
+fn run_suite(variant: Variant) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/cases");
+    let filter = std::env::var("CONFORMANCE_FILTER").unwrap_or_default();
+    let (mut passed, mut failed, mut skipped) = (0, 0, 0);
+    let mut failures = String::new();
+    let mut newly_passing = Vec::new();
+    
+    for path in fixtures(&root) {
+        let name = path.strip_prefix(&root).unwrap_or(&path).display().to_string();
+        if !name.contains(&filter) {
+            continue;
+        }
+        let source = fs::read_to_string(&path).expect("readable fixture");
+        let front_matter = match FrontMatter::parse(&source) {
+            Ok(front_matter) => front_matter,
+            Err(problem) => {
+                failed += 1;
+                let _ = writeln!(failures, "FAIL {}\n  bad front matter: {}", name, problem);
+                continue;
+            }
+        };
+        let mismatch = check(&front_matter, &run(&source, variant));
+        match (&front_matter.skip, mismatch) {
+            (Some(_), mismatch) => {
+                skipped += 1;
+                if mismatch.is_none() {
+                    newly_passing.push(name);
+                }
+            }
+            (None, None) => passed += 1,
+            (None, Some(report)) => {
+                failed += 1;
+                let _ = write!(failures, "FAIL {}\n{}", name, report);
+            }
+        }
+    }
+    
+    println!("conformance: {} passed, {} failed, {} skipped", passed, failed, skipped);
+    for name in &newly_passing {
+        println!("  now passing, remove its skip directive: {}", name);
+    }
+    assert!(failures.is_empty(), "\n{}conformance: {} passed, {} failed, {} skipped", failures, passed, failed, skipped);
+    assert!(passed + skipped > 0 || !filter.is_empty(), "no conformance cases found in {}", root.display());
+}
+
+#[test]
+fn test_capabilities_match_the_skip_list() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/cases");
+    let capabilities = Engine::new().capabilities();
+    let cases: Vec<(String, FrontMatter)> = fixtures(&root).into_iter().filter_map(|path| {
+        let front_matter = FrontMatter::parse(&fs::read_to_string(&path).expect("readable fixture")).ok()?;
+        Some((path.strip_prefix(&root).unwrap_or(&path).display().to_string(), front_matter))
+    }).collect();
+    let mut problems = String::new();
+    for feature in Feature::ALL {
+        let cases: Vec<&(String, FrontMatter)> = cases.iter().filter(|(_, front_matter)| front_matter.features.contains(&feature)).collect();
+        let skipped: Vec<&str> = cases.iter().filter(|(_, front_matter)| front_matter.skip.is_some()).map(|(name, _)| name.as_str()).collect();
+        match (capabilities.supports(feature), cases.is_empty(), skipped.is_empty()) {
+            (_, true, _) => {
+                let _ = writeln!(problems, "{}: no case has a '// feature: {}' directive", feature, feature);
+            }
+            (true, false, false) => {
+                let _ = writeln!(problems, "{}: reported as supported, but skipped in {}", feature, skipped.join(", "));
+            }
+            (false, false, true) => {
+                let _ = writeln!(problems, "{}: every case passes, so `Engine::capabilities` should report it", feature);
+            }
+            _ => {}
+        }
+    }
+    assert!(problems.is_empty(), "\n{}", problems);
+}
+
+#[test]
+fn test_front_matter_parsing() {
+    let source = "// description: sums\n//expect-output: 1\n// expect-output:  two\n\n// expect-value: 3\nlet x; // expect-value: ignored\n";
+    assert_eq!(FrontMatter::parse(source), Ok(FrontMatter {
+        description: Some("sums".to_string()),
+        expect_value: Some("3".to_string()),
+        expect_output: vec!["1".to_string(), " two".to_string()],
+        ..FrontMatter::default()
+    }));
+    
+    assert!(FrontMatter::parse("// expect-valu: 1\n").unwrap_err().contains("unknown directive"));
+    assert!(FrontMatter::parse("// description: nothing to check\n1;").is_err());
+    assert!(FrontMatter::parse("// expect-value: 1\n// expect-error: TypeError\n").is_err());
+    
+    let front_matter = FrontMatter::parse("// feature: for-of\n// feature: generators\n// expect-value: 1\n").unwrap();
+    assert_eq!(front_matter.features, [Feature::ForOf, Feature::Generators]);
+    assert!(FrontMatter::parse("// feature: teleportation\n// expect-value: 1\n").unwrap_err().contains("unknown feature"));
+}
+
+#[test]
+fn test_mismatches_are_reported_with_a_diff() {
+    let front_matter = FrontMatter {
+        expect_value: Some("1".to_string()),
+        expect_output: vec!["a".to_string(), "b".to_string()],
+        ..FrontMatter::default()
+    };
+    let outcome = Outcome { completion: Ok("2".to_string()), output: vec!["a".to_string(), "c".to_string()] };
+    assert_eq!(
+        check(&front_matter, &outcome).unwrap(),
+        "  expected value 1, got value 2\n  output differs (- expected, + actual):\n      a\n    - b\n    + c\n"
+    );
+    
+    let outcome = Outcome { completion: Err("TypeError".to_string()), output: Vec::new() };
+    let front_matter = FrontMatter { expect_error: Some("TypeError".to_string()), ..FrontMatter::default() };
+    assert_eq!(check(&front_matter, &outcome), None);
+}
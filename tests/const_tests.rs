@@ -0,0 +1,116 @@
+//! Tests for `const` locals: compile-time checks, and the verifier and VM
+//! checks that hold for bytecode the compiler didn't write
+
+use std::sync::Arc;
+use v8::bytecode::{verify, Bytecode, BytecodeDump, BytecodeFunction, LocalIndex};
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{eval, eval_error};
+
+/// `const x = 1; x = 2; return x` with the assignment compiled as a store
+fn reassigning_function() -> BytecodeFunction {
+    let mut function = BytecodeFunction::new("reassign".to_string(), 0, 1, 1);
+    function.immutable_locals.insert(LocalIndex(0));
+    for instruction in [
+        Bytecode::LdaSmi(1),
+        Bytecode::InitLocal(LocalIndex(0)),
+        Bytecode::LdaSmi(2),
+        Bytecode::StaLocal(LocalIndex(0)),
+        Bytecode::LdaLocal(LocalIndex(0)),
+        Bytecode::Return,
+    ] {
+        function.add_instruction(instruction);
+    }
+    function
+}
+
+#[test]
+fn test_const_declarations_run() {
+    assert_eq!(eval("const a = 1; const b = a + 1; a + b"), Value::Number(3.0));
+    assert_eq!(eval("let sum = 0; for (const n of [1, 2, 3]) { sum += n; } sum"), Value::Number(6.0));
+    assert_eq!(eval("let sum = 0; let i = 0; while (i < 3) { const d = i * 2; sum += d; i++; } sum"), Value::Number(6.0));
+    assert_eq!(eval("const base = 10; function add(n) { return base + n; } add(5)"), Value::Number(15.0));
+    // A const in an inner scope doesn't freeze an outer variable of the same name
+    assert_eq!(eval("let x = 1; { const x = 2; } x = 3; x"), Value::Number(3.0));
+}
+
+#[test]
+fn test_const_assignments_fail_to_compile() {
+    let message = "Runtime error: TypeError: Assignment to constant variable.";
+    for source in [
+        "const a = 1; a = 2;",
+        "const a = 1; a += 2;",
+        "const a = 1; a -= 1;",
+        "const a = 1; a++;",
+        "const a = 1; function f() { a = 2; }",
+        "const a = 1; let f = () => { a--; };",
+        "for (const n of [1]) { n = 2; }",
+        "let n = 0; const m = 1; for (m of [1]) {}",
+    ] {
+        assert_eq!(eval_error(source, ErrorCode::ConstAssignment), message, "{}", source);
+    }
+}
+
+#[test]
+fn test_compiler_marks_const_locals() {
+    let function = Engine::new().compile_cached("let a = 1; const b = 2; a + b").unwrap();
+    assert!(!function.immutable_locals.contains(LocalIndex(0)));
+    assert!(function.immutable_locals.contains(LocalIndex(1)));
+    assert!(function.bytecode.contains(&Bytecode::StaLocal(LocalIndex(0))));
+    assert!(function.bytecode.contains(&Bytecode::InitLocal(LocalIndex(1))));
+    assert!(verify(&function).is_ok());
+}
+
+#[test]
+fn test_verifier_rejects_stores_to_immutable_locals() {
+    let error = verify(&reassigning_function()).unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: Invalid bytecode in main at offset 3: StaLocal to immutable local 0");
+    
+    // A closure writing its parent's const through the context
+    let mut inner = BytecodeFunction::new("inner".to_string(), 0, 0, 1);
+    inner.add_instruction(Bytecode::LdaSmi(2));
+    inner.add_instruction(Bytecode::StaContextSlot(1, LocalIndex(0)));
+    inner.add_instruction(Bytecode::ReturnUndefined);
+    let mut outer = BytecodeFunction::new("outer".to_string(), 0, 1, 1);
+    outer.immutable_locals.insert(LocalIndex(0));
+    outer.functions.push(Arc::new(inner));
+    let error = verify(&outer).unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: Invalid bytecode in main/0 at offset 1: StaContextSlot to immutable local 0 of outer");
+    
+    let mut function = BytecodeFunction::new("f".to_string(), 0, 1, 1);
+    function.immutable_locals.insert(LocalIndex(4));
+    let error = verify(&function).unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: Invalid bytecode in main: immutable local 4 is outside its 1 locals");
+}
+
+#[test]
+fn test_vm_rejects_stores_to_immutable_locals() {
+    let result = Engine::new().execute_compiled(&Arc::new(reassigning_function()));
+    if cfg!(debug_assertions) {
+        assert_eq!(result.unwrap_err().to_string(), "Runtime error: TypeError: Assignment to constant variable.");
+    } else {
+        // Release builds leave the check to the verifier
+        assert_eq!(result.unwrap(), Value::Number(2.0));
+    }
+}
+
+#[test]
+fn test_const_survives_the_dump() {
+    let function = Engine::new().compile_cached("const a = 1; function f() { const b = a; return b; } f()").unwrap();
+    let mut dump = BytecodeDump::new(&function);
+    assert_eq!(dump.main.immutable_locals, [0]);
+    assert_eq!(dump.main.functions[0].immutable_locals, [0]);
+    
+    let read = BytecodeDump::from_json(&dump.to_json()).unwrap().to_function().unwrap();
+    assert_eq!(read.immutable_locals, function.immutable_locals);
+    assert_eq!(Engine::new().execute_compiled(&Arc::new(read)).unwrap(), Value::Number(1.0));
+    
+    // A dump edited to reassign the const doesn't load
+    let init = dump.main.instructions.iter().position(|instruction| instruction.op == Bytecode::InitLocal(LocalIndex(0))).unwrap();
+    dump.main.instructions.insert(init + 1, dump.main.instructions[init].clone());
+    dump.main.instructions[init + 1].op = Bytecode::StaLocal(LocalIndex(0));
+    let error = BytecodeDump::from_json(&dump.to_json()).unwrap().to_function().unwrap_err();
+    assert!(format!("{:?}", error).contains("StaLocal to immutable local 0"), "{:?}", error);
+}
@@ -0,0 +1,350 @@
+//! Tests for the debug hook and evaluating expressions in a paused frame
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use v8::bytecode::LocalIndex;
+use v8::{Compiler, DebugContext, Disassembler, ErrorCode, PauseReason, StepMode, VMConfig, Value, VM};
+
+mod common;
+
+use common::compile;
+
+fn layout(names: &[(&str, u16)]) -> Vec<(String, LocalIndex)> {
+    names.iter().map(|&(name, index)| (name.to_string(), LocalIndex(index))).collect()
+}
+
+const PAUSING: &str = "\
+function f(x, y) {
+    let z = x * y;
+    debugger;
+    return x + z;
+}
+f(2, 3);
+";
+
+#[test]
+fn test_eval_reads_and_writes_paused_locals() {
+    let seen: Rc<RefCell<Vec<Value>>> = Rc::default();
+    let contexts: Rc<RefCell<Vec<DebugContext>>> = Rc::default();
+    
+    let mut vm = VM::new();
+    let (seen_in_hook, contexts_in_hook) = (seen.clone(), contexts.clone());
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        contexts_in_hook.borrow_mut().push(context.clone());
+        let scope = layout(&[("x", 0), ("y", 1), ("z", 2)]);
+        for expr in ["x + y", "z", "x = 99", "x"] {
+            let function = Compiler::compile_expression_in_scope(expr, &scope)?;
+            let value = vm.eval_in_frame(context.frame, Arc::new(function))?;
+            seen_in_hook.borrow_mut().push(value);
+        }
+        Ok(())
+    })));
+    
+    // After resuming, `f` sees the 99 written while paused
+    let result = vm.execute(compile(PAUSING, None)).unwrap();
+    assert_eq!(result, Value::Number(105.0));
+    assert_eq!(
+        *seen.borrow(),
+        vec![Value::Number(5.0), Value::Number(6.0), Value::Number(99.0), Value::Number(99.0)]
+    );
+    
+    let contexts = contexts.borrow();
+    assert_eq!(contexts.len(), 1);
+    assert_eq!(contexts[0].reason, PauseReason::DebuggerStatement);
+    assert_eq!((contexts[0].function.as_str(), contexts[0].frame, contexts[0].line), ("f", 1, Some(3)));
+}
+
+#[test]
+fn test_eval_sees_this_globals_and_closures() {
+    let seen: Rc<RefCell<Vec<Value>>> = Rc::default();
+    let mut vm = VM::new();
+    let seen_in_hook = seen.clone();
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        let scope = layout(&[("n", 0)]);
+        for expr in ["this.label", "limit - n", "(function (k) { return k * n; })(2)"] {
+            let function = Compiler::compile_expression_in_scope(expr, &scope)?;
+            seen_in_hook.borrow_mut().push(vm.eval_in_frame(context.frame, Arc::new(function))?);
+        }
+        Ok(())
+    })));
+    
+    let source = "\
+limit = 10;
+let counter = { label: 'c', step(n) { debugger; return n; } };
+counter.step(4);
+";
+    assert_eq!(vm.execute(compile(source, None)).unwrap(), Value::Number(4.0));
+    assert_eq!(
+        *seen.borrow(),
+        vec![Value::string("c"), Value::Number(6.0), Value::Number(8.0)]
+    );
+}
+
+#[test]
+fn test_eval_errors_leave_the_paused_frame_intact() {
+    let errors: Rc<RefCell<Vec<String>>> = Rc::default();
+    let mut vm = VM::new();
+    let errors_in_hook = errors.clone();
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        let scope = layout(&[("x", 0)]);
+        let function = Compiler::compile_expression_in_scope("x.missing.deeper", &scope)?;
+        if let Err(error) = vm.eval_in_frame(context.frame, Arc::new(function)) {
+            errors_in_hook.borrow_mut().push(error.to_string());
+        }
+        if let Err(error) = Compiler::compile_expression_in_scope("x; x", &scope) {
+            errors_in_hook.borrow_mut().push(error.to_string());
+        }
+        Ok(())
+    })));
+    
+    let source = "function g(x) { let before = x + 1; debugger; return before * 2; } g(1) + 1;";
+    assert_eq!(vm.execute(compile(source, None)).unwrap(), Value::Number(5.0));
+    let errors = errors.borrow();
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+    assert!(errors[0].contains("TypeError"), "{}", errors[0]);
+    assert!(errors[1].contains("Expected a single expression"), "{}", errors[1]);
+}
+
+#[test]
+fn test_hook_sees_the_names_in_scope_where_it_paused() {
+    let seen: Collected<(Vec<(String, LocalIndex)>, Value)> = Rc::default();
+    let mut vm = VM::new();
+    let seen_in_hook = seen.clone();
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        // The innermost name, looked up in the symbol table again
+        let (name, _) = context.locals.last().unwrap();
+        let value = vm.eval_source_in_frame(context.frame, &format!("{} + n", name))?;
+        seen_in_hook.borrow_mut().push((context.locals.clone(), value));
+        Ok(())
+    })));
+    
+    // `a` and `b` share a slot, one block after the other
+    let source = "\
+function f(n) {
+    { let a = n + 1; debugger; }
+    { let b = n * 10; debugger; }
+    return n;
+}
+f(2);
+";
+    vm.execute(compile(source, None)).unwrap();
+    let seen = seen.borrow();
+    assert_eq!(seen[0], (layout(&[("n", 0), ("a", 1)]), Value::Number(5.0)));
+    assert_eq!(seen[1], (layout(&[("n", 0), ("b", 1)]), Value::Number(22.0)));
+}
+
+#[test]
+fn test_hook_errors_abort_and_debugger_is_a_no_op_without_hook() {
+    let mut vm = VM::new();
+    assert_eq!(vm.execute(compile("let a = 1; debugger; a + 1;", None)).unwrap(), Value::Number(2.0));
+    
+    vm.set_debug_hook(Some(Box::new(|_: &mut VM, _: &DebugContext| {
+        Err(v8::Error::host("stopped by debugger"))
+    })));
+    let error = vm.execute(compile("let a = 1; debugger; a + 1;", None)).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::HostError);
+    assert!(error.to_string().contains("stopped by debugger"), "{}", error);
+}
+
+const NAN_DEEP: &str = "\
+function area(shape) {
+    return shape.width * shape.height;
+}
+function total(shapes) {
+    let sum = 0;
+    for (const s of shapes) { sum = sum + area(s); }
+    return sum;
+}
+function report(shapes) {
+    return total(shapes) / 2;
+}
+report([{ width: 2, height: 3 }, { width: 4 }, { width: 1, height: 1 }]);
+";
+
+#[test]
+fn test_nan_tracing_finds_where_nan_started() {
+    let mut vm = VM::with_config(VMConfig { trace_nan: true, ..VMConfig::default() });
+    let result = vm.execute(compile(NAN_DEEP, None)).unwrap();
+    assert!(matches!(result, Value::Number(n) if n.is_nan()));
+    
+    // Only the multiplication made NaN; the additions and the division
+    // downstream merely passed it on
+    let origins = vm.nan_origins();
+    assert_eq!(origins.len(), 1, "{:?}", origins);
+    let origin = &origins[0];
+    assert_eq!((origin.function.as_str(), origin.line, origin.operator), ("area", Some(2), "*"));
+    assert_eq!(origin.operands, vec![Value::Number(4.0), Value::Undefined]);
+    assert_eq!(origin.to_string(), "NaN from 4 (number) * undefined (undefined) at line 2 in area");
+    
+    // Off by default
+    let mut vm = VM::new();
+    vm.execute(compile(NAN_DEEP, None)).unwrap();
+    assert!(vm.nan_origins().is_empty());
+}
+
+#[test]
+fn test_nan_tracing_reports_each_instruction_once() {
+    let mut vm = VM::with_config(VMConfig { trace_nan: true, ..VMConfig::default() });
+    let source = "let x;\nfor (let i = 0; i < 3; i++) { x = -'a'; }\nlet y = x - 1;\nlet z = 'b' % 2;";
+    vm.execute(compile(source, None)).unwrap();
+    let lines: Vec<_> = vm.nan_origins().iter().map(|origin| (origin.operator, origin.line)).collect();
+    assert_eq!(lines, vec![("-", Some(2)), ("%", Some(4))]);
+}
+
+/// What a hook collects across pauses
+type Collected<T> = Rc<RefCell<Vec<T>>>;
+
+fn breakpoint_fixture() -> String {
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/breakpoints.js");
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[test]
+fn test_breakpoint_pauses_on_a_source_line() {
+    let hits: Collected<(DebugContext, Vec<Value>)> = Rc::default();
+    let mut vm = VM::new();
+    let id = vm.set_breakpoint("area", 3);
+    let hits_in_hook = hits.clone();
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        let scope = layout(&[("width", 0), ("height", 1), ("scale", 2), ("result", 3)]);
+        let mut values = Vec::new();
+        for expr in ["width", "height", "scale", "result"] {
+            let function = Compiler::compile_expression_in_scope(expr, &scope)?;
+            values.push(vm.eval_in_frame(context.frame, Arc::new(function))?);
+        }
+        hits_in_hook.borrow_mut().push((context.clone(), values));
+        Ok(())
+    })));
+    
+    assert_eq!(vm.execute(compile(&breakpoint_fixture(), None)).unwrap(), Value::Number(26.0));
+    let hits = hits.borrow();
+    assert_eq!(hits.len(), 2);
+    for (context, _) in hits.iter() {
+        assert_eq!(context.reason, PauseReason::Breakpoint(id));
+        assert_eq!((context.function.as_str(), context.frame, context.line), ("area", 1, Some(3)));
+    }
+    // Line 3 hasn't run yet when it pauses
+    assert_eq!(hits[0].1, [Value::Number(3.0), Value::Number(4.0), Value::Number(2.0), Value::Undefined]);
+    assert_eq!(hits[1].1, [Value::Number(1.0), Value::Number(1.0), Value::Number(2.0), Value::Undefined]);
+}
+
+#[test]
+fn test_breakpoints_resolve_to_the_next_line_with_code() {
+    let lines: Collected<(String, Option<u32>)> = Rc::default();
+    let mut vm = VM::new();
+    let lines_in_hook = lines.clone();
+    vm.set_debug_hook(Some(Box::new(move |_: &mut VM, context: &DebugContext| {
+        lines_in_hook.borrow_mut().push((context.function.clone(), context.line));
+        Ok(())
+    })));
+    // Top-level code has nothing on lines 2 to 5; they belong to `area`
+    vm.set_breakpoint("main", 2);
+    // Nothing in `area` is on line 9 or after
+    vm.set_breakpoint("area", 9);
+    vm.execute(compile(&breakpoint_fixture(), None)).unwrap();
+    assert_eq!(*lines.borrow(), [("<main>".to_string(), Some(6))]);
+}
+
+#[test]
+fn test_clear_and_list_breakpoints() {
+    let count = Rc::new(RefCell::new(0));
+    let mut vm = VM::new();
+    let count_in_hook = count.clone();
+    vm.set_debug_hook(Some(Box::new(move |_: &mut VM, _: &DebugContext| {
+        *count_in_hook.borrow_mut() += 1;
+        Ok(())
+    })));
+    let first = vm.set_breakpoint("area", 2);
+    let second = vm.set_breakpoint("main", 7);
+    assert_ne!(first, second);
+    let listed: Vec<_> = vm.list_breakpoints().iter().map(|breakpoint| (breakpoint.id, breakpoint.function.as_str(), breakpoint.line)).collect();
+    assert_eq!(listed, [(first, "area", 2), (second, "main", 7)]);
+    vm.execute(compile(&breakpoint_fixture(), None)).unwrap();
+    assert_eq!(*count.borrow(), 3);
+    
+    assert!(vm.clear_breakpoint(first));
+    assert!(!vm.clear_breakpoint(first));
+    assert_eq!(vm.list_breakpoints().len(), 1);
+    vm.execute(compile(&breakpoint_fixture(), None)).unwrap();
+    assert_eq!(*count.borrow(), 4);
+}
+
+/// Run `source` under a hook that answers each pause with the next of
+/// `modes`, stepping until they run out, and collect where it paused
+fn step_through(source: &str, start: Option<StepMode>, modes: Vec<StepMode>) -> Vec<(PauseReason, String, Option<u32>)> {
+    let pauses: Collected<(PauseReason, String, Option<u32>)> = Rc::default();
+    let mut vm = VM::new();
+    let pauses_in_hook = pauses.clone();
+    let mut modes = modes.into_iter();
+    vm.set_debug_hook(Some(Box::new(move |vm: &mut VM, context: &DebugContext| {
+        pauses_in_hook.borrow_mut().push((context.reason, context.function.clone(), context.line));
+        if let Some(mode) = modes.next() {
+            vm.step(mode);
+        }
+        Ok(())
+    })));
+    if let Some(mode) = start {
+        vm.step(mode);
+    }
+    vm.execute(compile(source, None)).unwrap();
+    let pauses = pauses.borrow().clone();
+    pauses
+}
+
+#[test]
+fn test_stepping_pauses_at_each_statement_not_each_line() {
+    // Three statements on the first line, then one over four lines
+    let source = "\
+let a = 1; let b = 2; let c = a + b;
+let d = [
+    a,
+    b,
+];
+c + d.length;
+";
+    let pauses = step_through(source, Some(StepMode::Into), vec![StepMode::Into; 8]);
+    let lines: Vec<_> = pauses.iter().map(|(_, _, line)| *line).collect();
+    assert_eq!(lines, [Some(1), Some(1), Some(1), Some(2), Some(6)]);
+    assert!(pauses.iter().all(|(reason, function, _)| *reason == PauseReason::Step && function == "<main>"));
+    
+    // The boundaries are a side table the disassembler marks
+    let function = compile(source, None);
+    assert_eq!(function.debug_info.statement_starts.len(), 5);
+    let marked = Disassembler::quick_disassemble(&function).lines().filter(|line| line.contains(":>")).count();
+    assert_eq!(marked, 5);
+}
+
+const STEPPING: &str = "\
+function twice(n) {
+    let m = n * 2;
+    return m;
+}
+debugger;
+let x = twice(1);
+x;
+";
+
+#[test]
+fn test_step_into_over_and_out() {
+    use PauseReason::{DebuggerStatement, Step};
+    let at = |reason, function: &str, line| (reason, function.to_string(), Some(line));
+    
+    // Into enters the call and comes back out to the caller's next statement
+    let pauses = step_through(STEPPING, None, vec![StepMode::Into; 4]);
+    assert_eq!(pauses, [
+        at(DebuggerStatement, "<main>", 5), at(Step, "<main>", 6),
+        at(Step, "twice", 2), at(Step, "twice", 3), at(Step, "<main>", 7),
+    ]);
+    
+    // Over runs the call without pausing in it
+    let pauses = step_through(STEPPING, None, vec![StepMode::Over; 2]);
+    assert_eq!(pauses, [at(DebuggerStatement, "<main>", 5), at(Step, "<main>", 6), at(Step, "<main>", 7)]);
+    
+    // Out finishes the call and pauses back in the caller
+    let pauses = step_through(STEPPING, None, vec![StepMode::Over, StepMode::Into, StepMode::Out]);
+    assert_eq!(pauses, [
+        at(DebuggerStatement, "<main>", 5), at(Step, "<main>", 6), at(Step, "twice", 2), at(Step, "<main>", 7),
+    ]);
+}
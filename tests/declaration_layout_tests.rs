@@ -0,0 +1,93 @@
+//! Tests for the compiler's declaration pass: every declaration of a
+//! function is laid out before its code is compiled, so functions can be
+//! called above their declaration, `var` is visible function-wide, and
+//! `locals_count` is the most slots in use at once
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{compile, eval};
+
+#[test]
+fn test_forward_function_references() {
+    assert_eq!(eval("function a() { return b() + 1; } function b() { return 41; } a()"), Value::Number(42.0));
+    assert_eq!(eval("let r = twice(4); function twice(n) { return n * 2; } r"), Value::Number(8.0));
+    assert_eq!(eval("function outer() { return inner(); function inner() { return 'in'; } } outer()"), Value::string("in"));
+    // Mutual recursion between nested functions
+    assert_eq!(
+        eval("function parity(n) {
+                  function even(k) { if (k === 0) { return true; } return odd(k - 1); }
+                  function odd(k) { if (k === 0) { return false; } return even(k - 1); }
+                  return even(n);
+              }
+              parity(7)"),
+        Value::Boolean(false),
+    );
+    // Block-level functions are hoisted within their block
+    assert_eq!(eval("let r; { r = f(); function f() { return 3; } } r"), Value::Number(3.0));
+}
+
+#[test]
+fn test_var_in_a_nested_block_is_visible_function_wide() {
+    assert_eq!(eval("function f() { { { var x = 5; } } return x; } f()"), Value::Number(5.0));
+    assert_eq!(eval("function f() { let seen = typeof x; if (true) { var x = 1; } return seen + x; } f()"), Value::string("undefined1"));
+    assert_eq!(eval("function f() { for (var i = 0; i < 3; i++) {} return i; } f()"), Value::Number(3.0));
+    assert_eq!(eval("function f(x) { { var x = 2; } return x; } f(1)"), Value::Number(2.0));
+    assert_eq!(eval("{ var y = 7; } y"), Value::Number(7.0));
+    // A closure created before the declaration sees the same variable
+    assert_eq!(eval("function f() { let get = () => v; { var v = 'late'; } return get(); } f()"), Value::string("late"));
+}
+
+#[test]
+fn test_var_may_not_share_a_name_with_a_lexical_declaration() {
+    for source in ["let x; { var x; }", "{ let x; { var x; } }", "var x; let x;", "function f(a) { let a; }"] {
+        let error = Engine::new().execute(source).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::DuplicateDeclaration, "{}", source);
+    }
+    assert_eq!(eval("var x = 1; var x = 2; x"), Value::Number(2.0));
+    assert_eq!(eval("{ let y = 1; } { var y = 2; } y"), Value::Number(2.0));
+}
+
+#[test]
+fn test_locals_count_is_the_most_slots_live_at_once() {
+    // Sibling blocks share slots
+    assert_eq!(compile("let a; { let b; let c; } { let d; }", None).locals_count, 3);
+    assert_eq!(compile("{ let a; { let b; } { let c; let d; } } { let e; }", None).locals_count, 3);
+    // A `var` lives for the whole function, wherever it is declared
+    assert_eq!(compile("{ let a; let b; } { var c; }", None).locals_count, 3);
+    // A block that creates a closure keeps its slots
+    assert_eq!(compile("let f; { let a; f = () => a; } { let b; }", None).locals_count, 3);
+    let main = compile("function g(p, q) { { let a; } { let b; { let c; } } var d; }", None);
+    assert_eq!(main.functions[0].locals_count, 5);
+    assert_eq!(main.locals_count, 1);
+}
+
+#[test]
+fn test_layout_keeps_results_unchanged() {
+    for (source, expected) in [
+        ("let x = 1; { let x = 2; } x", Value::Number(1.0)),
+        ("let total = 0; for (let i = 0; i < 4; i++) { let sq = i * i; total += sq; } total", Value::Number(14.0)),
+        ("let fs = []; for (var i = 0; i < 3; i++) { fs.push(() => i); } fs[0]() + fs[2]()", Value::Number(6.0)),
+        ("const k = (class K { static name2() { return K; } }); k.name2() === k", Value::Boolean(true)),
+        ("let s = 0; for (const n of [1, 2, 3]) { const d = n * 2; s += d; } s", Value::Number(12.0)),
+    ] {
+        assert_eq!(eval(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn test_lexical_names_are_unbound_before_their_declaration() {
+    // The block's `b` takes the slot the first block's `a` used, and must
+    // not read the value left in it
+    let error = Engine::new().execute("{ let a = 'stale'; } { let seen = b; let b = 1; }").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UnknownGlobal);
+    assert_eq!(error.to_string(), "Runtime error: ReferenceError: b is not defined");
+    assert!(Engine::new().execute("{ const a = 'stale'; } { b; const b = 1; }").is_err());
+
+    // Until an inner declaration, the name still means the outer variable
+    assert_eq!(eval("let x = 1; let seen; { seen = x; let x = 2; } seen"), Value::Number(1.0));
+    assert_eq!(eval("function f() { let y = 'outer'; { let r = y; let y = 'inner'; return r + y; } } f()"), Value::string("outerinner"));
+    // Functions and `var`s are still bound from the start of their scope
+    assert_eq!(eval("{ let a = 5; } let r; { r = typeof v + f(); var v; function f() { return 1; } } r"), Value::string("undefined1"));
+}
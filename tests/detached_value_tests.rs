@@ -0,0 +1,82 @@
+//! Tests for copying values out of one engine and into another with
+//! `Value::deep_clone_detached` and `Engine::adopt`
+
+use std::thread;
+use v8::{Detached, Engine, ErrorCode, GlobalSlot, Value};
+
+#[test]
+fn test_cyclic_structure_round_trips_between_engines() {
+    let mut source = Engine::new();
+    let value = source.execute("
+        let o = { name: 'root', list: [1, , 'two'], nested: { n: -0 } };
+        o.self = o;
+        o.alias = o.list;
+        o.list.push(o);
+        o
+    ").unwrap();
+    let detached = value.deep_clone_detached().unwrap();
+    drop(value);
+    drop(source);
+    
+    // Another engine, on another thread
+    let copied = detached.clone();
+    let (checks, back) = thread::spawn(move || {
+        let mut target = Engine::new_persistent();
+        target.define_global("o", GlobalSlot::new(target.adopt(&copied)));
+        let checks = target.execute("
+            o.self === o && o.alias === o.list && o.list[3] === o
+                && o.list.length === 4 && o.list[1] === undefined && 1 / o.nested.n === -Infinity
+                && o.name === 'root' && o.list[2] === 'two'
+        ").unwrap();
+        let back = target.execute("o").unwrap().deep_clone_detached().unwrap();
+        (checks == Value::Boolean(true), back)
+    }).join().unwrap();
+    
+    assert!(checks);
+    assert_eq!(back, detached);
+    
+    // The root comes first, and each object is copied once
+    assert_eq!(detached.root(), &Detached::Object(0));
+    assert_eq!(detached.objects().len(), 3);
+}
+
+#[test]
+fn test_copies_are_independent_of_the_original() {
+    let mut engine = Engine::new_persistent();
+    let original = engine.execute("let shared = [1]; pair = { a: shared, b: shared }; pair").unwrap();
+    let detached = original.deep_clone_detached().unwrap();
+    let copy = engine.adopt(&detached);
+    engine.define_global("copy", GlobalSlot::new(copy));
+    assert_eq!(engine.execute("copy.a.push(2); copy.b.length").unwrap(), Value::Number(2.0));
+    assert_eq!(engine.execute("pair.a.length").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_only_enumerable_data_is_copied() {
+    let mut engine = Engine::new();
+    let value = engine.execute("
+        let o = { visible: 1 };
+        Object.defineProperty(o, 'hidden', { value: 2 });
+        o
+    ").unwrap();
+    let detached = value.deep_clone_detached().unwrap();
+    assert_eq!(detached.objects()[0].properties, vec![("visible".to_string(), Detached::Number(1.0))]);
+    
+    assert_eq!(Value::string("text").deep_clone_detached().unwrap().root(), &Detached::String("text".to_string()));
+}
+
+#[test]
+fn test_functions_and_engine_objects_cannot_be_detached() {
+    let mut engine = Engine::new();
+    for source in [
+        "({ nested: [function () {}] })",
+        "parseInt",
+        "(function* () {})()",
+        "(async function () {})()",
+    ] {
+        let value = engine.execute(source).unwrap();
+        let error = value.deep_clone_detached().unwrap_err();
+        assert_eq!(error.code(), ErrorCode::NotCloneable, "{}", source);
+        assert!(error.to_string().contains("DataCloneError"), "{}", error);
+    }
+}
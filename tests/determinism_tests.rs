@@ -0,0 +1,46 @@
+//! Tests for reproducible script runs
+//!
+//! With a fixed seed, nothing a script can observe should change between
+//! runs or engines: not `Math.random`, not property order.
+
+use v8::{Engine, Value};
+
+/// Run `source` on a fresh engine seeded with `seed`
+fn run_seeded(seed: u64, source: &str) -> Value {
+    Engine::builder().random_seed(seed).build().execute_detailed(source).result.unwrap()
+}
+
+const THOUSAND_RANDOMS: &str = "let a = []; for (let i = 0; i < 1000; i++) a[i] = Math.random(); a";
+
+fn numbers(value: &Value) -> Vec<f64> {
+    let text = value.to_string();
+    text.split(',').map(|n| n.parse().unwrap()).collect()
+}
+
+#[test]
+fn test_same_seed_same_random_sequence() {
+    let first = numbers(&run_seeded(1234, THOUSAND_RANDOMS));
+    let second = numbers(&run_seeded(1234, THOUSAND_RANDOMS));
+    assert_eq!(first.len(), 1000);
+    assert_eq!(first, second);
+    assert!(first.iter().all(|x| (0.0..1.0).contains(x)));
+    
+    assert_ne!(first, numbers(&run_seeded(4321, THOUSAND_RANDOMS)));
+}
+
+#[test]
+fn test_persistent_engine_continues_the_sequence() {
+    let mut engine = Engine::builder().random_seed(9).persistent(true).build();
+    let first = engine.execute_detailed("Math.random()").result.unwrap();
+    let second = engine.execute_detailed("Math.random()").result.unwrap();
+    assert_ne!(first, second);
+    assert_eq!(first, run_seeded(9, "Math.random()"));
+}
+
+#[test]
+fn test_property_order_is_stable() {
+    let source = "let o = { zeta: 1, alpha: 2 }; o.mid = 3; o.beta = 4; o";
+    let inspected: Vec<String> = (0..5).map(|seed| run_seeded(seed, source).inspect()).collect();
+    assert!(inspected.iter().all(|text| *text == inspected[0]), "{:?}", inspected);
+    assert_eq!(inspected[0], "{ zeta: 1, alpha: 2, mid: 3, beta: 4 }");
+}
@@ -0,0 +1,95 @@
+//! Tests for names given twice: duplicate parameters, duplicate
+//! declarations in one scope, and duplicate object literal keys, which
+//! are allowed
+
+use v8::{Diagnostic, Error, ErrorCode, Value};
+
+mod common;
+
+use common::{error_of, eval};
+
+/// The line and column of the error and of its note
+fn locations(error: &Error) -> ((u32, u32), (u32, u32)) {
+    let span = error.span().expect("error span");
+    let note = error.note().expect("note on the first declaration");
+    ((span.line, span.column), (note.span.line, note.span.column))
+}
+
+#[test]
+fn test_sloppy_functions_may_repeat_parameters() {
+    assert_eq!(eval("function f(a, a) { return a; } f(1, 2)"), Value::Number(2.0));
+    assert_eq!(eval("function f(a, b, a) { return typeof a + b; } f(1, 2)"), Value::string("undefined2"));
+}
+
+#[test]
+fn test_strict_functions_arrows_and_methods_may_not() {
+    for source in [
+        "'use strict'; function f(a, a) {}",
+        "function f(a, a) { 'use strict'; }",
+        "let f = (a, a) => 1;",
+        "let f = async (a, a) => 1;",
+        "class C { constructor(a, a) {} }",
+        "let o = { m(a, a) {} };",
+    ] {
+        let error = error_of(source);
+        assert_eq!(error.code(), ErrorCode::DuplicateParameter, "{}", source);
+    }
+    let error = error_of("'use strict';\nfunction f(first,\n    second, first) {}");
+    assert_eq!(locations(&error), ((3, 13), (2, 12)));
+}
+
+#[test]
+fn test_noted_errors_keep_their_variant() {
+    let error = error_of("'use strict'; function f(a, a) {}");
+    assert!(matches!(error, Error::Noted { .. }));
+    assert!(matches!(error.inner(), Error::Parser { .. }));
+    let error = error.context("While running a.js");
+    assert!(matches!(error.inner(), Error::Parser { code: ErrorCode::DuplicateParameter, .. }));
+}
+
+#[test]
+fn test_duplicate_lexical_declarations_name_both_places() {
+    let error = error_of("let x = 1;\n{ let y; }\nlet x = 2;");
+    assert_eq!(error.code(), ErrorCode::DuplicateDeclaration);
+    assert!(matches!(error.inner(), Error::Compile { .. }));
+    assert_eq!(error.to_string(), "Compile error: Variable 'x' already declared in this scope");
+    assert_eq!(locations(&error), ((3, 1), (1, 1)));
+    
+    let error = error_of("function f() {\n  const k = 1;\n  { var k; }\n}");
+    assert_eq!(locations(&error), ((3, 5), (2, 3)));
+    
+    let error = error_of("{ var v; }\nclass v {}");
+    assert_eq!(locations(&error), ((2, 1), (1, 3)));
+    
+    let error = error_of("\nfunction f(a) { let a; }");
+    assert_eq!(locations(&error), ((2, 17), (2, 12)));
+}
+
+#[test]
+fn test_diagnostics_show_the_note() {
+    let source = "let x = 1;\nlet x = 2;";
+    let diagnostic = Diagnostic::from(error_of(source));
+    assert_eq!(diagnostic.notes.len(), 1);
+    assert_eq!(diagnostic.to_string(), "error[E3001]: Variable 'x' already declared in this scope (line 2, column 1)\nnote: 'x' first declared here (line 1, column 1)");
+    
+    let rendered = diagnostic.format_with_source(source);
+    let note = rendered.find("note:").expect(&rendered);
+    assert!(rendered[..note].contains("2 | let x = 2;"), "{}", rendered);
+    assert!(rendered[note..].contains("1 | let x = 1;"), "{}", rendered);
+    
+    // Context keeps the note
+    let diagnostic = Diagnostic::from(error_of(source).context("While running a.js"));
+    assert_eq!(diagnostic.notes.len(), 1);
+}
+
+#[test]
+fn test_duplicate_object_keys_are_allowed_and_the_last_wins() {
+    // The value is the last one, at the position of the first
+    assert_eq!(eval("JSON.stringify({ a: 1, b: 2, a: 3 })"), Value::string("{\"a\":3,\"b\":2}"));
+    assert_eq!(eval("Object.keys({ b: 1, a: 2, b: 3 }).length"), Value::Number(2.0));
+    assert_eq!(eval("'use strict'; ({ a: 1, 'a': 2, ['a']: 3 }).a"), Value::Number(3.0));
+    assert_eq!(eval("({ a: 1, ...{ a: 2 } }).a"), Value::Number(2.0));
+    assert_eq!(eval("({ ...{ a: 2 }, a: 1 }).a"), Value::Number(1.0));
+    assert_eq!(eval("let a = 5; ({ a: 1, a }).a"), Value::Number(5.0));
+    assert_eq!(eval("({ m() { return 1; }, m() { return 2; } }).m()"), Value::Number(2.0));
+}
@@ -0,0 +1,99 @@
+//! Tests for `EngineIo`: everything an engine prints or reads goes
+//! through its own streams, so a program's whole output can be checked
+//! in memory
+
+use v8::repl::Repl;
+use v8::{CapturedOutput, Engine, EngineIo, Value};
+
+/// An engine reading `input`, and what it writes
+fn captured_engine(input: &str) -> (Engine, CapturedOutput) {
+    let (io, output) = EngineIo::capture(input);
+    let mut engine = Engine::new();
+    engine.set_io(io);
+    (engine, output)
+}
+
+#[test]
+fn test_program_output_is_captured() {
+    let (mut engine, output) = captured_engine("");
+    let result = engine.execute("
+        console.log('log', 1, [2, 3]);
+        console.info('info');
+        console.debug('debug');
+        console.warn('warn');
+        console.error('error', { toString() { return 'custom'; } });
+        print('print');
+        'done'
+    ").unwrap();
+    assert_eq!(result, Value::string("done"));
+    assert_eq!(output.stdout(), "log 1 2,3\ninfo\ndebug\nprint\ndone\n");
+    assert_eq!(output.stderr(), "warn\nerror custom\n");
+}
+
+#[test]
+fn test_read_line_reads_the_engine_stdin() {
+    let (mut engine, output) = captured_engine("first\r\nsecond\n\nlast");
+    engine.execute("
+        let line;
+        while ((line = readLine()) !== null) { console.log('[' + line + ']'); }
+        console.log(readLine());
+    ").unwrap();
+    assert_eq!(output.stdout(), "[first]\n[second]\n[]\n[last]\nnull\n");
+}
+
+#[test]
+fn test_errors_after_the_program_go_to_stderr() {
+    let (mut engine, output) = captured_engine("");
+    engine.execute("
+        setTimeout(function () { null.late; }, 0);
+        Promise.reject('ignored'); undefined
+    ").unwrap();
+    engine.run_pending(10).unwrap();
+    assert_eq!(output.stdout(), "");
+    assert!(output.stderr().starts_with("error[E4003]: Uncaught TypeError"), "{}", output.stderr());
+    assert!(output.stderr().contains("Cannot read properties of null"), "{}", output.stderr());
+    assert!(output.stderr().contains("Warning: Unhandled promise rejection: 'ignored'"), "{}", output.stderr());
+}
+
+#[test]
+fn test_console_handler_takes_precedence() {
+    let (mut engine, output) = captured_engine("");
+    engine.set_console_handler(|_| {});
+    engine.execute("console.log('a'); console.error('b'); 1").unwrap();
+    assert_eq!(output.stdout(), "1\n");
+    assert_eq!(output.stderr(), "");
+}
+
+#[test]
+fn test_debug_output_uses_the_engine_streams() {
+    let (io, output) = EngineIo::capture("");
+    let mut engine = Engine::new_with_all_debug();
+    engine.set_io(io);
+    engine.execute("console.log('hi')").unwrap();
+    let stdout = output.stdout();
+    assert!(stdout.starts_with("AST (detailed tree):\n"), "{}", stdout);
+    assert!(stdout.contains("Bytecode:\n"), "{}", stdout);
+    assert!(stdout.ends_with("hi\n"), "{}", stdout);
+    assert!(!stdout.contains("acc:"), "{}", stdout);
+    assert!(output.stderr().contains("acc:"), "{}", output.stderr());
+}
+
+#[test]
+fn test_repl_session_over_captured_streams() {
+    let (io, output) = EngineIo::capture("let x = 2\nx * 21\nconsole.log('hi')\nnope\n.exit\n1\n");
+    let mut repl = Repl::new();
+    repl.engine_mut().set_io(io);
+    repl.run("> ").unwrap();
+    assert_eq!(output.stdout(), "> > 42\n> hi\n> > ");
+    assert!(output.stderr().starts_with("error[E4001]: "), "{}", output.stderr());
+    assert!(output.stderr().contains("nope"), "{}", output.stderr());
+}
+
+#[test]
+fn test_repl_ends_with_its_input() {
+    let (io, output) = EngineIo::capture("1 + 1\n");
+    let mut repl = Repl::new();
+    repl.engine_mut().set_io(io);
+    repl.run("> ").unwrap();
+    assert_eq!(output.stdout(), "> 2\n> \nGoodbye!\n");
+}
@@ -0,0 +1,159 @@
+//! Tests for error codes: every error carries a registered code, the
+//! table in specs/ERROR_CODES.md matches the registry, and no error is
+//! constructed without naming its code
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use v8::{Diagnostic, Engine, Error, ErrorCode};
+
+mod common;
+
+use common::error_of;
+
+const TABLE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/specs/ERROR_CODES.md");
+
+/// Render the code table as specs/ERROR_CODES.md holds it
+fn render_table() -> String {
+    let mut table = String::from(
+        "# Error Codes\n\
+         \n\
+         Every error the engine reports carries one of these codes, shown in\n\
+         output as `error[E1001]: message` and available to hosts as\n\
+         `Error::code()`. Match on codes rather than messages: wording may\n\
+         change, codes do not.\n\
+         \n\
+         Generated from `src/error/code.rs`; regenerate with\n\
+         `V8_UPDATE_ERROR_CODES=1 cargo test --test error_code_tests`.\n",
+    );
+    let sections = [('1', "Lexer"), ('2', "Parser"), ('3', "Compiler"), ('4', "Runtime"), ('5', "Host and embedding"), ('9', "Engine bugs")];
+    for (digit, title) in sections {
+        table.push_str(&format!("\n## {}\n\n| Code | Name | Meaning |\n| --- | --- | --- |\n", title));
+        for code in ErrorCode::ALL.iter().filter(|code| code.as_str()[1..].starts_with(digit)) {
+            table.push_str(&format!("| {} | `{}` | {} |\n", code, code.name(), code.summary()));
+        }
+    }
+    table
+}
+
+/// Every Rust source file under `dir`
+fn rust_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            rust_files(&path, files);
+        } else if path.extension().is_some_and(|extension| extension == "rs") {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_codes_are_unique_and_well_formed() {
+    let mut seen = HashSet::new();
+    for &code in ErrorCode::ALL {
+        let text = code.as_str();
+        assert!(text.len() == 5 && text.starts_with('E') && text[1..].bytes().all(|b| b.is_ascii_digit()), "{}", text);
+        assert!(seen.insert(text), "{} is registered twice", text);
+        assert_eq!(ErrorCode::from_code(text), Some(code));
+    }
+    assert_eq!(ErrorCode::from_code("E0000"), None);
+}
+
+#[test]
+fn test_code_table_is_current() {
+    let table = render_table();
+    if std::env::var_os("V8_UPDATE_ERROR_CODES").is_some() {
+        fs::write(TABLE_PATH, &table).unwrap();
+        return;
+    }
+    let current = fs::read_to_string(TABLE_PATH).unwrap_or_default();
+    assert!(
+        current == table,
+        "specs/ERROR_CODES.md is stale; regenerate it with V8_UPDATE_ERROR_CODES=1 cargo test --test error_code_tests"
+    );
+}
+
+/// A lint: every `Error::lexer`, `Error::parser` and `Error::runtime`
+/// call names its code right there, and every registered code is raised
+/// somewhere
+#[test]
+fn test_error_sites_name_their_code() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut files = Vec::new();
+    rust_files(&src, &mut files);
+    
+    let mut uncoded = Vec::new();
+    let mut sources = String::new();
+    for path in &files {
+        let text = fs::read_to_string(path).unwrap();
+        if !path.ends_with("error/code.rs") {
+            sources.push_str(&text);
+        }
+        for constructor in ["Error::lexer(", "Error::parser(", "Error::runtime("] {
+            for (offset, _) in text.match_indices(constructor) {
+                let rest = text[offset + constructor.len()..].trim_start();
+                // The definitions themselves take the code as a parameter
+                if !rest.starts_with("ErrorCode::") && !rest.starts_with("code: ErrorCode") {
+                    let line = text[..offset].lines().count();
+                    uncoded.push(format!("{}:{}", path.strip_prefix(&src).unwrap().display(), line));
+                }
+            }
+        }
+    }
+    assert!(uncoded.is_empty(), "errors constructed without an ErrorCode:\n{}", uncoded.join("\n"));
+    
+    let unused: Vec<_> = ErrorCode::ALL.iter()
+        .filter(|code| !sources.contains(&format!("ErrorCode::{}", code.name())))
+        .collect();
+    assert!(unused.is_empty(), "registered codes that nothing raises: {:?}", unused);
+}
+
+#[test]
+fn test_errors_carry_codes_from_every_phase() {
+    let cases = [
+        ("let a = #;", ErrorCode::UnexpectedCharacter),
+        ("'abc", ErrorCode::UnterminatedString),
+        ("`abc", ErrorCode::UnterminatedTemplate),
+        ("`abc${x}`", ErrorCode::UnsupportedSyntax),
+        ("f(1, 2", ErrorCode::ExpectedToken),
+        ("let x = 1 let y = 2", ErrorCode::MissingSemicolon),
+        ("const c;", ErrorCode::MissingInitializer),
+        ("-2 ** 2", ErrorCode::AmbiguousExponentiation),
+        ("break;", ErrorCode::IllegalBreak),
+        ("function f() { let a = 1; let a = 2; }", ErrorCode::DuplicateDeclaration),
+        ("let f = (a, a) => a;", ErrorCode::DuplicateParameter),
+        ("const k = 1; k = 2;", ErrorCode::ConstAssignment),
+        ("missing + 1", ErrorCode::UnknownGlobal),
+        ("null.x", ErrorCode::NullishPropertyAccess),
+        ("let n = 5; n()", ErrorCode::NotCallable),
+        ("for (const x of 5) {}", ErrorCode::NotIterable),
+        ("JSON.parse('{')", ErrorCode::JsonSyntax),
+        ("let o = {}; o.self = o; JSON.stringify(o)", ErrorCode::CircularJson),
+    ];
+    for (source, code) in cases {
+        let error = error_of(source);
+        assert_eq!(error.code(), code, "{}: {}", source, error);
+    }
+}
+
+#[test]
+fn test_host_errors_and_context_keep_their_code() {
+    let mut engine = Engine::new();
+    engine.register_function("fail", |_| Err(Error::host("no network")));
+    assert_eq!(engine.execute("fail()").unwrap_err().code(), ErrorCode::HostError);
+    
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+    assert_eq!(Error::from(io).context("Cannot read 'a.js'").code(), ErrorCode::Io);
+    assert_eq!(error_of("null.x").context("While running a.js").code(), ErrorCode::NullishPropertyAccess);
+}
+
+#[test]
+fn test_diagnostics_show_the_code() {
+    let diagnostic = Diagnostic::from(error_of("let x = 1 let y = 2"));
+    assert_eq!(diagnostic.code, Some(ErrorCode::MissingSemicolon));
+    assert!(diagnostic.to_string().starts_with("error[E2003]: Unexpected token"), "{}", diagnostic);
+    
+    let rendered = diagnostic.format_with_source("let x = 1 let y = 2");
+    assert!(rendered.contains("error[E2003]:"), "{}", rendered);
+}
@@ -0,0 +1,231 @@
+//! Tests for ESTree conversion
+//!
+//! Files in `tests/golden/estree` are compared as JSON; regenerate one
+//! with `v8 --dump-ast=json <name>.js > <name>.json`.
+
+use std::fs;
+use std::path::Path;
+use serde_json::Value as Json;
+use v8::parser::estree::{self, Node};
+use v8::ast::Program;
+use std::sync::Arc;
+use v8::{Compiler, Engine, ErrorCode, Lexer, Parser, Result, Value};
+
+const GOLDEN: [&str; 3] = ["declarations", "expressions", "statements"];
+
+fn parse(source: &str) -> Result<Program> {
+    Parser::new(Lexer::new(source).tokenize()?).parse()
+}
+
+/// Compile and run a program that has no source text
+fn run(program: &Program) -> Result<Value> {
+    let function = Compiler::new_main("").compile(program)?;
+    Engine::new().execute_compiled(&Arc::new(function))
+}
+
+fn golden_source(name: &str) -> Result<String> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/estree");
+    Ok(fs::read_to_string(dir.join(format!("{}.js", name)))?)
+}
+
+fn estree_json(source: &str) -> Result<Json> {
+    let json = estree::from_program(&parse(source)?, source).to_json();
+    Ok(serde_json::from_str(&json).unwrap())
+}
+
+#[test]
+fn test_golden_files() -> Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/estree");
+    for name in GOLDEN {
+        let expected: Json = serde_json::from_str(&fs::read_to_string(dir.join(format!("{}.json", name)))?).unwrap();
+        let actual = estree_json(&golden_source(name)?)?;
+        assert_eq!(actual, expected, "ESTree of {}.js differs:\n{:#}", name, actual);
+    }
+    Ok(())
+}
+
+/// The fields each node type must have, and what they hold
+///
+/// `node` is a node object, `node?` a node or null, `nodes` an array of
+/// nodes and `nodes?` one that may have holes.
+const SHAPES: &[(&str, &[(&str, &str)])] = &[
+    ("Program", &[("body", "nodes"), ("sourceType", "string")]),
+    ("ExpressionStatement", &[("expression", "node")]),
+    ("VariableDeclaration", &[("declarations", "nodes"), ("kind", "string")]),
+    ("VariableDeclarator", &[("id", "node"), ("init", "node?")]),
+    ("FunctionDeclaration", &[("id", "node?"), ("params", "nodes"), ("body", "node"), ("generator", "bool"), ("async", "bool")]),
+    ("FunctionExpression", &[("id", "node?"), ("params", "nodes"), ("body", "node"), ("generator", "bool"), ("async", "bool")]),
+    ("ArrowFunctionExpression", &[("params", "nodes"), ("body", "node"), ("expression", "bool"), ("async", "bool")]),
+    ("ClassDeclaration", &[("id", "node?"), ("superClass", "node?"), ("body", "node")]),
+    ("ClassBody", &[("body", "nodes")]),
+    ("MethodDefinition", &[("key", "node"), ("value", "node"), ("kind", "string"), ("computed", "bool"), ("static", "bool")]),
+    ("IfStatement", &[("test", "node"), ("consequent", "node"), ("alternate", "node?")]),
+    ("WhileStatement", &[("test", "node"), ("body", "node")]),
+    ("ForStatement", &[("init", "node?"), ("test", "node?"), ("update", "node?"), ("body", "node")]),
+    ("ForOfStatement", &[("left", "node"), ("right", "node"), ("body", "node"), ("await", "bool")]),
+    ("BlockStatement", &[("body", "nodes")]),
+    ("ReturnStatement", &[("argument", "node?")]),
+    ("BreakStatement", &[("label", "node?")]),
+    ("ContinueStatement", &[("label", "node?")]),
+    ("DebuggerStatement", &[]),
+    ("Literal", &[("value", "value"), ("raw", "string")]),
+    ("Identifier", &[("name", "string")]),
+    ("ThisExpression", &[]),
+    ("BinaryExpression", &[("operator", "string"), ("left", "node"), ("right", "node")]),
+    ("LogicalExpression", &[("operator", "string"), ("left", "node"), ("right", "node")]),
+    ("UnaryExpression", &[("operator", "string"), ("prefix", "bool"), ("argument", "node")]),
+    ("UpdateExpression", &[("operator", "string"), ("prefix", "bool"), ("argument", "node")]),
+    ("AssignmentExpression", &[("operator", "string"), ("left", "node"), ("right", "node")]),
+    ("CallExpression", &[("callee", "node"), ("arguments", "nodes"), ("optional", "bool")]),
+    ("NewExpression", &[("callee", "node"), ("arguments", "nodes")]),
+    ("MemberExpression", &[("object", "node"), ("property", "node"), ("computed", "bool"), ("optional", "bool")]),
+    ("ObjectExpression", &[("properties", "nodes")]),
+    ("Property", &[("key", "node"), ("value", "node"), ("kind", "string"), ("method", "bool"), ("shorthand", "bool"), ("computed", "bool")]),
+    ("SpreadElement", &[("argument", "node")]),
+    ("ArrayExpression", &[("elements", "nodes?")]),
+];
+
+/// Check that `json` is a node whose fields have the ESTree shape,
+/// returning the node types seen
+fn check_shape(json: &Json, path: &str, seen: &mut Vec<String>) {
+    let node = json.as_object().unwrap_or_else(|| panic!("{} is not an object", path));
+    let node_type = node["type"].as_str().unwrap_or_else(|| panic!("{} has no type", path));
+    seen.push(node_type.to_string());
+    let (_, fields) = SHAPES.iter().find(|(name, _)| *name == node_type)
+        .unwrap_or_else(|| panic!("{} has unexpected type {}", path, node_type));
+    
+    if let Some(start) = node.get("start") {
+        let (start, end) = (start.as_u64().unwrap(), node["end"].as_u64().unwrap());
+        assert!(start <= end, "{} ends before it starts", path);
+        for edge in ["start", "end"] {
+            let position = &node["loc"][edge];
+            assert!(position["line"].as_u64().unwrap() >= 1 && position["column"].is_u64(), "{}.loc.{}", path, edge);
+        }
+    }
+    for (field, kind) in *fields {
+        let path = format!("{}.{}", path, field);
+        let value = node.get(*field).unwrap_or_else(|| panic!("{} is missing", path));
+        match *kind {
+            "node" => check_shape(value, &path, seen),
+            "node?" if value.is_null() => {}
+            "node?" => check_shape(value, &path, seen),
+            "nodes" | "nodes?" => {
+                let items = value.as_array().unwrap_or_else(|| panic!("{} is not an array", path));
+                for (index, item) in items.iter().enumerate() {
+                    if !(item.is_null() && *kind == "nodes?") {
+                        check_shape(item, &format!("{}[{}]", path, index), seen);
+                    }
+                }
+            }
+            "string" => assert!(value.is_string(), "{} is not a string", path),
+            "bool" => assert!(value.is_boolean(), "{} is not a boolean", path),
+            "value" => assert!(!value.is_array() && !value.is_object(), "{} is not a literal value", path),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn test_estree_shape() -> Result<()> {
+    let mut seen = Vec::new();
+    for name in GOLDEN {
+        check_shape(&estree_json(&golden_source(name)?)?, name, &mut seen);
+    }
+    // The fixtures cover most of the node types
+    seen.sort();
+    seen.dedup();
+    assert!(seen.len() >= 30, "only {:?}", seen);
+    Ok(())
+}
+
+#[test]
+fn test_round_trip() -> Result<()> {
+    for name in GOLDEN {
+        let source = golden_source(name)?;
+        let program = parse(&source)?;
+        let node = Node::from_json(&estree::from_program(&program, &source).to_json())?;
+        assert_eq!(estree::to_program(&node)?.to_string(), program.to_string(), "{}.js", name);
+    }
+    Ok(())
+}
+
+/// ESTree written by other tools, with extra fields and expression arrows
+#[test]
+fn test_foreign_estree_runs() -> Result<()> {
+    let json = r#"{
+        "type": "Program", "sourceType": "script", "range": [0, 40],
+        "body": [
+            { "type": "VariableDeclaration", "kind": "const", "declarations": [{
+                "type": "VariableDeclarator",
+                "id": { "type": "Identifier", "name": "double" },
+                "init": { "type": "ArrowFunctionExpression", "id": null, "expression": true,
+                          "generator": false, "async": false,
+                          "params": [{ "type": "Identifier", "name": "n" }],
+                          "body": { "type": "BinaryExpression", "operator": "*",
+                                    "left": { "type": "Identifier", "name": "n" },
+                                    "right": { "type": "Literal", "value": 2, "raw": "2" } } }
+            }] },
+            { "type": "ExpressionStatement", "expression": {
+                "type": "CallExpression", "optional": false,
+                "callee": { "type": "Identifier", "name": "double" },
+                "arguments": [{ "type": "Literal", "value": 21, "raw": "21" }]
+            } }
+        ]
+    }"#;
+    let program = estree::to_program(&Node::from_json(json)?)?;
+    assert_eq!(run(&program)?, Value::Number(42.0));
+    Ok(())
+}
+
+#[test]
+fn test_compound_assignments_round_trip() -> Result<()> {
+    let source = "let n = 5; n -= 2; n *= 3; n %= 4; n";
+    let node = estree::from_program(&parse(source)?, source);
+    let json = node.to_json();
+    for operator in ["-=", "*=", "%="] {
+        assert!(json.contains(&format!("\"operator\": \"{}\"", operator)), "{}", json);
+    }
+    let program = estree::to_program(&Node::from_json(&json)?)?;
+    assert_eq!(run(&program)?, Value::Number(1.0));
+    Ok(())
+}
+
+#[test]
+fn test_positions_count_utf16() -> Result<()> {
+    let source = "let s = \"😀\";\nlet t = s;";
+    let json = estree_json(source)?;
+    let init = &json["body"][1]["declarations"][0]["init"];
+    assert_eq!(init["start"], 22);
+    assert_eq!(init["loc"]["start"], serde_json::json!({ "line": 2, "column": 8 }));
+    Ok(())
+}
+
+#[test]
+fn test_unsupported_nodes_are_errors() {
+    let template = r#"{ "type": "Program", "sourceType": "script", "body": [
+        { "type": "ExpressionStatement", "expression": { "type": "TemplateLiteral", "quasis": [], "expressions": [] } }
+    ] }"#;
+    let error = Node::from_json(template).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidEstree);
+    assert!(error.to_string().contains("unknown variant `TemplateLiteral`"), "{}", error);
+    
+    let unsupported = [
+        (r#"{ "type": "VariableDeclaration", "kind": "let", "start": 4, "end": 16, "declarations": [
+            { "type": "VariableDeclarator", "id": { "type": "Identifier", "name": "a" }, "init": null },
+            { "type": "VariableDeclarator", "id": { "type": "Identifier", "name": "b" }, "init": null }
+        ] }"#, ErrorCode::UnsupportedEstree, "Unsupported ESTree VariableDeclaration: only one declarator per declaration"),
+        (r#"{ "type": "ExpressionStatement", "expression": { "type": "LogicalExpression", "operator": "??",
+            "left": { "type": "Identifier", "name": "a" }, "right": { "type": "Identifier", "name": "b" } } }"#,
+         ErrorCode::UnsupportedEstree, "Unsupported ESTree LogicalExpression: operator ??"),
+        (r#"{ "type": "ClassDeclaration", "id": { "type": "Identifier", "name": "A" },
+            "superClass": { "type": "Identifier", "name": "B" }, "body": { "type": "ClassBody", "body": [] } }"#,
+         ErrorCode::UnsupportedEstree, "Unsupported ESTree ClassDeclaration: superClass"),
+        (r#"{ "type": "ClassBody", "body": [] }"#, ErrorCode::InvalidEstree, "Expected a statement in ESTree input, found ClassBody"),
+    ];
+    for (statement, code, message) in unsupported {
+        let json = format!(r#"{{ "type": "Program", "sourceType": "script", "body": [{}] }}"#, statement);
+        let error = estree::to_program(&Node::from_json(&json).unwrap()).unwrap_err();
+        assert_eq!(error.code(), code, "{}", error);
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+}
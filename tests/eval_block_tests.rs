@@ -0,0 +1,57 @@
+//! Tests for `Engine::eval_block` and its completion-value rules
+
+use v8::{Engine, ErrorCode, Value};
+
+fn eval_block(source: &str) -> Value {
+    Engine::new().eval_block(source).unwrap()
+}
+
+#[test]
+fn test_if_else_chains() {
+    let template = |role: &str| format!(
+        "let user = {{ role: '{}' }};
+         if (user.role === 'admin') {{ 'Admin' }} else if (user.role === 'guest') {{ 'Guest' }} else {{ 'User' }}",
+        role
+    );
+    assert_eq!(eval_block(&template("admin")), Value::string("Admin"));
+    assert_eq!(eval_block(&template("guest")), Value::string("Guest"));
+    assert_eq!(eval_block(&template("other")), Value::string("User"));
+    
+    // No branch taken completes with undefined, not the earlier value
+    assert_eq!(eval_block("1; if (false) { 2 }"), Value::Undefined);
+    assert_eq!(eval_block("if (true) { 'a'; let x = 1; x; } else { 'b' }"), Value::Number(1.0));
+}
+
+#[test]
+fn test_loops() {
+    assert_eq!(eval_block("'before'; for (let i = 0; i < 0; i++) { i }"), Value::Undefined);
+    assert_eq!(eval_block("let n = 0; while (n < 3) { n = n + 1; n * 10 }"), Value::Number(30.0));
+    assert_eq!(eval_block("let out = ''; for (let c of ['a', 'b']) { out = out + c; }"), Value::string("ab"));
+    assert_eq!(eval_block("let i = 0; while (true) { i = i + 1; if (i === 2) { 'stopped'; break; } }"), Value::string("stopped"));
+}
+
+#[test]
+fn test_blocks_and_declarations() {
+    assert_eq!(eval_block("{ 'value'; let x = 1; }"), Value::Undefined);
+    assert_eq!(eval_block("'value'; function f() { return 1; }"), Value::Undefined);
+    assert_eq!(eval_block("'value'; {} ;"), Value::string("value"));
+    assert_eq!(eval_block("{ 1; { 2; } }"), Value::Number(2.0));
+    assert_eq!(eval_block(""), Value::Undefined);
+}
+
+#[test]
+fn test_functions_are_unaffected() {
+    // Statements inside a function body have no completion value to leak
+    assert_eq!(eval_block("function f() { 'inner'; } f()"), Value::Undefined);
+    // Plain execution keeps its own rules
+    assert_eq!(Engine::new().execute("if (true) { 'Admin' }").unwrap(), Value::Undefined);
+}
+
+#[test]
+fn test_errors() {
+    assert_eq!(Engine::new().eval_block("let 1 = 2;").unwrap_err().code(), ErrorCode::ExpectedToken);
+    assert_eq!(
+        Engine::new().eval_block("null.x").unwrap_err().to_string(),
+        Engine::new().execute("null.x").unwrap_err().to_string()
+    );
+}
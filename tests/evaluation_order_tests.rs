@@ -0,0 +1,106 @@
+//! Tests for the order in which calls and assignments evaluate their parts
+//!
+//! In `o.m(f(), g())` the object is evaluated once, `m` is looked up, and
+//! then the arguments run left to right. In `o[k] += v` the object and key
+//! are evaluated once, before the value. Each case logs its steps to a
+//! global array and checks the exact sequence. Accessors don't exist yet,
+//! so the lookup is observed through an argument that replaces the method.
+
+use v8::{Engine, ErrorCode, Value};
+
+/// Engine with a global `log` and helpers that append to it
+fn engine() -> Engine {
+    let mut engine = Engine::new_persistent();
+    engine.execute("
+        log = [];
+        step = function (name, value) { log.push(name); return value; };
+        fail = function (name) { log.push(name); return null.missing; };
+        o = { m: function (a, b) { log.push('m(' + a + ',' + b + ')'); return this === o; } };
+    ").unwrap();
+    engine
+}
+
+fn log(engine: &mut Engine) -> Value {
+    engine.execute("JSON.stringify(log)").unwrap()
+}
+
+#[test]
+fn test_object_then_arguments_then_call() {
+    let mut engine = engine();
+    let result = engine.execute("step('object', o).m(step('f', 1), step('g', 2))").unwrap();
+    assert_eq!(result, Value::Boolean(true));
+    assert_eq!(log(&mut engine), Value::string(r#"["object","f","g","m(1,2)"]"#));
+}
+
+#[test]
+fn test_method_is_looked_up_before_the_arguments() {
+    let mut engine = engine();
+    // The argument replaces `o.m`, but the original was already loaded
+    engine.execute("
+        replace = function () { o.m = function () { log.push('replacement'); }; return 2; };
+        o.m(step('f', 1), replace());
+        o.m();
+    ").unwrap();
+    assert_eq!(log(&mut engine), Value::string(r#"["f","m(1,2)","replacement"]"#));
+}
+
+#[test]
+fn test_computed_key_is_evaluated_after_the_object_and_once() {
+    let mut engine = engine();
+    engine.execute("step('object', o)[step('key', 'm')](step('f', 1), step('g', 2))").unwrap();
+    assert_eq!(log(&mut engine), Value::string(r#"["object","key","f","g","m(1,2)"]"#));
+}
+
+#[test]
+fn test_throwing_argument_keeps_earlier_effects() {
+    let mut engine = engine();
+    let error = engine.execute("step('object', o).m(step('f', 1), fail('g'), step('h', 3))").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::NullishPropertyAccess, "{}", error);
+    // The method was never called and later arguments never ran
+    assert_eq!(log(&mut engine), Value::string(r#"["object","f","g"]"#));
+}
+
+#[test]
+fn test_plain_calls_evaluate_the_callee_first() {
+    let mut engine = engine();
+    engine.execute("
+        pick = function () { log.push('callee'); return function (a, b) { log.push('call'); }; };
+        pick()(step('f', 1), step('g', 2));
+    ").unwrap();
+    assert_eq!(log(&mut engine), Value::string(r#"["callee","f","g","call"]"#));
+}
+
+#[test]
+fn test_compound_assignment_evaluates_the_target_once() {
+    let mut engine = engine();
+    let result = engine.execute("
+        o.n = 1;
+        step('object', o)[step('key', 'n')] += step('value', 2);
+    ").unwrap();
+    assert_eq!(result, Value::Number(3.0));
+    assert_eq!(engine.execute("o.n").unwrap(), Value::Number(3.0));
+    assert_eq!(log(&mut engine), Value::string(r#"["object","key","value"]"#));
+    
+    let result = engine.execute("
+        log = [];
+        step('object', o).n *= step('value', 2);
+    ").unwrap();
+    assert_eq!(result, Value::Number(6.0));
+    assert_eq!(log(&mut engine), Value::string(r#"["object","value"]"#));
+}
+
+#[test]
+fn test_compound_assignment_runs_side_effects_once() {
+    let mut engine = engine();
+    let result = engine.execute("
+        a = [1, 2, 3];
+        i = 0;
+        a[i++] += 5;
+        calls = 0;
+        box = { x: 1 };
+        f = function () { calls++; return box; };
+        f().x += 1;
+        JSON.stringify([a, i, calls, box.x]);
+    ").unwrap();
+    assert_eq!(result, Value::string("[[6,2,3],1,1,2]"));
+}
@@ -0,0 +1,115 @@
+//! Tests for `setTimeout`, `queueMicrotask` and `Engine::run_pending`
+//!
+//! The host supplies the time, so every test pumps with synthetic clocks.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use v8::{Engine, Value};
+
+mod common;
+
+use common::{engine_with, log};
+
+#[test]
+fn test_timers_fire_in_due_order() {
+    let mut engine = engine_with(
+        "setTimeout(function () { record('c'); }, 30);
+         setTimeout(function () { record('a'); }, 10);
+         setTimeout(function () { record('b'); }, 10);",
+    );
+    
+    assert!(engine.run_pending(5).unwrap());
+    assert_eq!(log(&mut engine), Value::string(""));
+    
+    assert!(engine.run_pending(10).unwrap());
+    assert_eq!(log(&mut engine), Value::string("ab"));
+    
+    assert!(!engine.run_pending(100).unwrap());
+    assert_eq!(log(&mut engine), Value::string("abc"));
+}
+
+#[test]
+fn test_microtasks_run_before_timers_and_after_each_timer() {
+    let mut engine = engine_with(
+        "setTimeout(function () {
+             record('t1');
+             queueMicrotask(function () { record('m2'); });
+         }, 0);
+         setTimeout(function () { record('t2'); }, 0);
+         queueMicrotask(function () { record('m1'); });",
+    );
+    
+    assert!(!engine.run_pending(0).unwrap());
+    assert_eq!(log(&mut engine), Value::string("m1t1m2t2"));
+}
+
+#[test]
+fn test_clear_timeout_and_arguments() {
+    let mut engine = engine_with(
+        "let id = setTimeout(function () { record('never'); }, 10);
+         setTimeout(function (a, b) { record(a + b); }, 10, 'x', 'y');
+         clearTimeout(id);",
+    );
+    
+    assert!(!engine.run_pending(10).unwrap());
+    assert_eq!(log(&mut engine), Value::string("xy"));
+}
+
+#[test]
+fn test_timers_scheduled_while_pumping_wait_for_the_next_pump() {
+    let mut engine = engine_with(
+        "function tick() { record('.'); setTimeout(tick, 0); }
+         setTimeout(tick, 0);",
+    );
+    
+    assert!(engine.run_pending(0).unwrap());
+    assert!(engine.run_pending(0).unwrap());
+    assert_eq!(log(&mut engine), Value::string(".."));
+}
+
+#[test]
+fn test_uncaught_errors_go_to_the_handler() {
+    let mut engine = engine_with(
+        "setTimeout(function () { null.x; }, 0);
+         setTimeout(function () { record('still ran'); }, 0);",
+    );
+    let errors = Rc::new(RefCell::new(Vec::new()));
+    let sink = errors.clone();
+    engine.set_uncaught_exception_handler(move |error| sink.borrow_mut().push(error.to_string()));
+    
+    assert!(!engine.run_pending(0).unwrap());
+    assert_eq!(errors.borrow().len(), 1);
+    assert!(errors.borrow()[0].contains("Cannot read properties of null"));
+    assert_eq!(log(&mut engine), Value::string("still ran"));
+}
+
+#[test]
+fn test_non_persistent_engine_keeps_pending_work() {
+    let mut engine = Engine::new();
+    let result = engine.execute_detailed("setTimeout(function () {}, 5)").result.unwrap();
+    assert_eq!(result, Value::Number(1.0));
+    
+    assert!(engine.run_pending(0).unwrap());
+    assert!(!engine.run_pending(5).unwrap());
+    
+    // Scheduling requires a function
+    assert!(engine.execute_detailed("setTimeout(5)").result.is_err());
+}
+
+#[test]
+fn test_console_handler_captures_script_and_callback_output() {
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = Engine::new();
+    let sink = lines.clone();
+    engine.set_console_handler(move |line| sink.borrow_mut().push(line.to_string()));
+    
+    engine.execute_detailed(
+        "console.log('a', 1);
+         setTimeout(function () { console.error('late'); }, 5);",
+    ).result.unwrap();
+    assert_eq!(*lines.borrow(), ["a 1"]);
+    
+    engine.run_pending(5).unwrap();
+    assert_eq!(*lines.borrow(), ["a 1", "late"]);
+}
@@ -0,0 +1,83 @@
+//! Tests for `Engine::execute_detailed`
+//!
+//! Each test fails the pipeline at one phase and checks that the report
+//! carries exactly the artifacts of the phases before it.
+
+use v8::{Engine, Error, Phase, Value};
+
+#[test]
+fn test_lex_failure_has_no_artifacts() {
+    let report = Engine::new().execute_detailed("let s = \"unterminated");
+    
+    assert_eq!(report.phase, Phase::Lex);
+    assert!(matches!(report.result, Err(Error::Lexer { .. })));
+    assert!(report.tokens.is_none());
+    assert!(report.ast.is_none());
+    assert!(report.bytecode.is_none());
+}
+
+#[test]
+fn test_parse_failure_keeps_tokens() {
+    let report = Engine::new().execute_detailed("let = 1; let after = 2;");
+    
+    assert_eq!(report.phase, Phase::Parse);
+    assert!(matches!(report.result, Err(Error::Parser { .. })));
+    // The whole source was lexed, including what follows the parse error
+    let tokens = report.tokens.expect("tokens from a completed lex");
+    assert_eq!(tokens.len(), 10);
+    assert!(report.ast.is_none());
+    assert!(report.bytecode.is_none());
+}
+
+#[test]
+fn test_parse_failure_before_lex_error_has_no_tokens() {
+    let report = Engine::new().execute_detailed("let = 1; \"unterminated");
+    
+    assert_eq!(report.phase, Phase::Parse);
+    assert!(matches!(report.result, Err(Error::Parser { .. })));
+    assert!(report.tokens.is_none());
+}
+
+#[test]
+fn test_compile_failure_keeps_ast() {
+    let report = Engine::new().execute_detailed("break;");
+    
+    assert_eq!(report.phase, Phase::Compile);
+    assert!(report.result.is_err());
+    assert!(report.tokens.is_some());
+    assert_eq!(report.ast.expect("ast from a completed parse").statements.len(), 1);
+    assert!(report.bytecode.is_none());
+}
+
+#[test]
+fn test_execute_failure_keeps_bytecode() {
+    let report = Engine::new().execute_detailed("let o = null; o.x");
+    
+    assert_eq!(report.phase, Phase::Execute);
+    assert!(matches!(report.result, Err(Error::Runtime { .. })));
+    assert!(report.tokens.is_some());
+    assert!(report.ast.is_some());
+    assert!(report.bytecode.is_some());
+}
+
+#[test]
+fn test_success_reports_value_and_tokens() {
+    let source = "let a = /x/g; 1 + 2";
+    let report = Engine::new().execute_detailed(source);
+    
+    assert_eq!(report.phase, Phase::Execute);
+    assert_eq!(report.result.unwrap(), Value::Number(3.0));
+    let tokens = report.tokens.unwrap();
+    // The regex was re-scanned as one token, not recorded as `/` `x` `/` `g`
+    assert_eq!(tokens.iter().map(|t| t.text_in(source)).collect::<Vec<_>>(),
+               vec!["let", "a", "=", "/x/g", ";", "1", "+", "2", ""]);
+}
+
+#[test]
+fn test_report_collects_compiler_warnings() {
+    let report = Engine::new().execute_detailed("let unused = 1;\nif (0) { unused; }");
+    
+    assert!(report.result.is_ok());
+    let messages: Vec<&str> = report.diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(messages, vec!["condition is always falsy"]);
+}
@@ -0,0 +1,100 @@
+/* Drives the C interface end to end; run by tests/ffi_tests.rs */
+
+#include <stdio.h>
+#include <string.h>
+
+#include "v8rs.h"
+
+#define CHECK(cond)                                                   \
+    do {                                                              \
+        if (!(cond)) {                                                \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,    \
+                    __LINE__, #cond);                                 \
+            return 1;                                                 \
+        }                                                             \
+    } while (0)
+
+static int run(V8rsEngine *engine, const char *source, V8rsValue *out) {
+    return v8rs_execute(engine, source, strlen(source), out);
+}
+
+/* Sums its numeric arguments and counts calls in user_data */
+static int add(void *user_data, const V8rsValue *args, size_t argc, V8rsValue *out) {
+    int *calls = user_data;
+    double sum = 0;
+    ++*calls;
+    for (size_t i = 0; i < argc; i++) {
+        if (args[i].tag != V8RS_VALUE_TAG_NUMBER) {
+            out->tag = V8RS_VALUE_TAG_STRING;
+            out->string = "add expects numbers";
+            return 1;
+        }
+        sum += args[i].number;
+    }
+    out->tag = V8RS_VALUE_TAG_NUMBER;
+    out->number = sum;
+    return 0;
+}
+
+/* Returns the JSON of its first argument as a string */
+static int echo_json(void *user_data, const V8rsValue *args, size_t argc, V8rsValue *out) {
+    (void)user_data;
+    if (argc > 0 && args[0].tag == V8RS_VALUE_TAG_JSON) {
+        out->tag = V8RS_VALUE_TAG_STRING;
+        out->string = args[0].string;
+    }
+    return 0;
+}
+
+int main(void) {
+    V8rsEngine *engine = v8rs_engine_new();
+    V8rsValue value;
+    int calls = 0;
+    CHECK(engine != NULL);
+
+    CHECK(run(engine, "6 * 7", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_NUMBER && value.number == 42);
+
+    CHECK(run(engine, "'héllo'", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_STRING && strcmp(value.string, "héllo") == 0);
+    v8rs_value_free(&value);
+    CHECK(value.tag == V8RS_VALUE_TAG_UNDEFINED && value.string == NULL);
+
+    CHECK(run(engine, "Symbol('tag')", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_STRING && strcmp(value.string, "Symbol(tag)") == 0);
+    v8rs_value_free(&value);
+
+    /* Symbol-valued properties have no JSON form, as in JSON.stringify */
+    CHECK(run(engine, "({ a: 1, s: Symbol('x') })", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_JSON && strcmp(value.string, "{\"a\":1}") == 0);
+    v8rs_value_free(&value);
+
+    CHECK(run(engine, "[1, { a: 'b' }, true]", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_JSON);
+    CHECK(strcmp(value.string, "[1,{\"a\":\"b\"},true]") == 0);
+    v8rs_value_free(&value);
+
+    CHECK(v8rs_register_function(engine, "add", add, &calls) == 0);
+    CHECK(v8rs_register_function(engine, "echoJson", echo_json, NULL) == 0);
+    CHECK(run(engine, "add(1, 2, add(3, 4))", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_NUMBER && value.number == 10);
+    CHECK(calls == 2);
+
+    CHECK(run(engine, "echoJson({ x: [null] })", &value) == 0);
+    CHECK(value.tag == V8RS_VALUE_TAG_STRING && strcmp(value.string, "{\"x\":[null]}") == 0);
+    v8rs_value_free(&value);
+
+    CHECK(run(engine, "add('x')", &value) == -1);
+    CHECK(value.tag == V8RS_VALUE_TAG_UNDEFINED);
+    CHECK(strstr(v8rs_last_error_message(), "add expects numbers") != NULL);
+
+    CHECK(run(engine, "let = ;", NULL) == -1);
+    CHECK(v8rs_last_error_message() != NULL);
+    CHECK(v8rs_register_function(engine, "f", NULL, NULL) == -1);
+    CHECK(strstr(v8rs_last_error_message(), "Callback is null") != NULL);
+
+    v8rs_engine_free(engine);
+    v8rs_engine_free(NULL);
+    puts("ok");
+    return 0;
+}
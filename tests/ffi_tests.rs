@@ -0,0 +1,59 @@
+//! C interface tests
+//!
+//! Compiles `tests/ffi/smoke.c` against the generated header and the
+//! cdylib with the system C compiler, then runs it.
+
+#![cfg(all(feature = "ffi", unix))]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Build the cdylib with the `ffi` feature and return its directory
+///
+/// The cdylib's file name doesn't depend on the enabled features, so the
+/// copy next to this test may be from a build without them. A separate
+/// target directory keeps the two apart.
+fn library_dir() -> PathBuf {
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi");
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args(["build", "--lib", "--features", "ffi", "--target-dir"])
+        .arg(&target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success(), "Building the cdylib failed");
+    target_dir.join("debug")
+}
+
+#[test]
+fn test_c_program_drives_the_engine() {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let library_dir = library_dir();
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("ffi_smoke");
+    let compiler = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    
+    let status = Command::new(&compiler)
+        .arg("-std=c99")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I").arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/ffi/smoke.c"))
+        .arg("-L").arg(&library_dir)
+        .arg("-lv8")
+        .arg("-o").arg(&program)
+        .status()
+        .unwrap_or_else(|e| panic!("Cannot run C compiler '{}': {}", compiler, e));
+    assert!(status.success(), "Compiling smoke.c failed");
+    
+    // Cargo puts its own deps/ on the search path, which holds a cdylib
+    // that may have been built without the feature
+    let output = Command::new(&program)
+        .env("LD_LIBRARY_PATH", &library_dir)
+        .env("DYLD_LIBRARY_PATH", &library_dir)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "smoke.c failed:\n{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
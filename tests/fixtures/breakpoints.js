@@ -0,0 +1,7 @@
+function area(width, height) {
+    let scale = 2;
+    let result = width * height * scale;
+    return result;
+}
+let total = area(3, 4);
+total + area(1, 1);
@@ -0,0 +1,36 @@
+void 0 //=> undefined
+void 0 === undefined //=> true
+void 0 == null //=> true
+void f() //=> undefined
+typeof void 0 //=> undefined
+typeof undeclaredThing //=> undefined
+typeof undeclaredThing === "undefined" //=> true
+typeof undeclaredThing == typeof void 0 //=> true
+typeof f //=> function
+typeof f() //=> string
+typeof o.y //=> number
+typeof o.missing //=> undefined
+typeof o.g() //=> number
+typeof typeof 1 //=> string
+typeof null //=> object
+typeof /re/g //=> object
+typeof function(){} //=> function
+"" + typeof str.length //=> number
+!function(){}() //=> true
+!function(){return 1}() //=> false
+!function(n){return n>1}(2)===false //=> true
+!!~str.indexOf("world") //=> true
+!!~str.indexOf("xyz") //=> false
+~str.indexOf("xyz") //=> 0
+~~3.7 //=> 3
+!o.y //=> true
+!o.s.length //=> false
+!!o.g //=> true
+-o.s.length //=> -3
++"42"+1 //=> 43
++!0 //=> 1
+1+-+-1 //=> 2
+- -1 //=> 1
+void 0||"d" //=> d
+"a\u00e9b".indexOf("b",1)+"ab".indexOf("") //=> 2
+delete o.y //=> true
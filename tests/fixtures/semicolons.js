@@ -0,0 +1,80 @@
+// The semicolon-terminated twin of semicolon_free.js; both must produce the same log.
+
+let log = '';
+function note(value) {
+  log = log + value + ' ';
+}
+
+function sign(n) {
+  if (n < 0) return -1;
+  else if (n > 0) return 1;
+  else return 0;
+}
+note(sign(-5));
+note(sign(0));
+note(sign(7));
+
+let i = 0;
+while (i < 3) i++;
+note(i);
+
+let total = 0;
+for (let j = 0; j < 4; j++) total += j;
+note(total);
+
+for (const c of 'ab') note(c);
+
+let y;
+if (i) y = 'yes';
+else y = 'no';
+note(y);
+
+if (!i)
+  note('unreachable');
+else
+  note('else on its own line');
+
+function early() {
+  return;
+  42;
+}
+note(early());
+
+let k = 0;
+while (true) {
+  k++;
+  if (k < 3) continue;
+  break;
+}
+note(k);
+
+let n = 1;
+let m = n;
+n++;
+note(m + ',' + n);
+
+class Counter {
+  constructor() {
+    this.count = 0;
+  }
+  tick() {
+    this.count += 1;
+    return this;
+  }
+}
+note(new Counter().tick().tick().count);
+
+function* pair() {
+  yield 'x';
+  yield 'y';
+}
+for (const v of pair()) note(v);
+
+const point = {
+  x: 1,
+  y: 2
+};
+note(point.x +
+  point.y);
+
+log;
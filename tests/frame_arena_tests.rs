@@ -0,0 +1,92 @@
+//! Tests for reusing non-escaping literals from the frame arena
+
+use v8::bytecode::{verify, Bytecode};
+use v8::{Compiler, Engine, ErrorCode, Lexer, Parser, Value};
+
+fn eval(source: &str, frame_arena: bool) -> Value {
+    Engine::builder().frame_arena(frame_arena).build().execute(source).unwrap()
+}
+
+/// Scripts must give the same value whether or not the arena is on
+fn eval_both(source: &str) -> Value {
+    let value = eval(source, false);
+    assert_eq!(eval(source, true), value, "{}", source);
+    value
+}
+
+fn arena_sites(source: &str) -> (Vec<usize>, Vec<usize>) {
+    let program = Parser::from_source(Lexer::new(source)).parse().unwrap();
+    let main = Compiler::new_main(source).compile(&program).unwrap();
+    let nested = main.functions.first().map(|function| function.arena_sites.clone()).unwrap_or_default();
+    (main.arena_sites, nested)
+}
+
+#[test]
+fn test_only_non_escaping_literals_are_sites() {
+    let (main, nested) = arena_sites("function f(i) { let o = { a: i }; return o.a; } let p = [1, 2]; p[0]");
+    assert_eq!((main.len(), nested.len()), (1, 1));
+    
+    let (main, nested) = arena_sites("function f(i) { let o = { a: i }; return o; } let p = [1, 2]; g(p);");
+    assert!(main.is_empty() && nested.is_empty());
+}
+
+#[test]
+fn test_verifier_checks_arena_sites() {
+    let source = "let o = { a: 1 }; o.a";
+    let program = Parser::from_source(Lexer::new(source)).parse().unwrap();
+    let mut function = Compiler::new_main(source).compile(&program).unwrap();
+    assert!(matches!(function.bytecode[function.arena_sites[0]], Bytecode::CreateFromTemplate(_)));
+    assert!(verify(&function).is_ok());
+    
+    function.arena_sites.push(function.bytecode.len() - 1);
+    let error = verify(&function).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidBytecode);
+    assert!(error.to_string().contains("is not an allocation"), "{}", error);
+}
+
+#[test]
+fn test_reused_objects_start_fresh() {
+    // Each iteration sees only its own properties
+    assert_eq!(eval_both("
+        function f(i) {
+            let o = { a: i };
+            if (i % 2 == 0) { o.extra = 1; }
+            return o.extra === undefined;
+        }
+        let fresh = 0;
+        for (let i = 0; i < 10; i++) { if (f(i)) { fresh++; } }
+        fresh"), Value::Number(5.0));
+    assert_eq!(eval_both("
+        let sum = 0;
+        for (let i = 0; i < 10; i++) {
+            let list = [i, i];
+            list[2] = i;
+            let o = {};
+            if (o.x === undefined) { sum++; }
+            sum = sum + list[0] + list[2];
+        }
+        sum"), Value::Number(100.0));
+}
+
+#[test]
+fn test_escaped_objects_are_left_alone() {
+    // Stored into another object by way of a property the analysis can't see
+    assert_eq!(eval_both("
+        let kept = [];
+        function f(i) {
+            let o = { value: i };
+            let holder = { o: 0 };
+            holder.o = kept;
+            kept[i] = { from: o.value };
+            return holder.o.length;
+        }
+        for (let i = 0; i < 5; i++) { f(i); }
+        kept[0].from + kept[4].from"), Value::Number(4.0));
+    // Captured and returned objects survive their call
+    assert_eq!(eval_both("
+        function make(i) { let o = { value: i }; return () => o.value; }
+        function pass(i) { let o = { value: i }; return o; }
+        let getters = [make(1), make(2)];
+        let objects = [pass(3), pass(4)];
+        getters[0]() + getters[1]() + objects[0].value + objects[1].value"), Value::Number(10.0));
+}
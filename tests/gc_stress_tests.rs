@@ -0,0 +1,126 @@
+//! Tests for `VMConfig::gc_stress` and the rooting rules it checks
+//!
+//! The built-in test suites also run under it, through the
+//! `V8_GC_STRESS` environment variable.
+
+use std::cell::RefCell;
+use std::process::Command;
+use std::rc::Rc;
+
+use v8::{Engine, ErrorCode, Persistent, VMConfig, Value};
+
+/// The test binaries that exercise the built-ins
+const BUILTIN_SUITES: [&str; 14] = [
+    "array_tests",
+    "async_tests",
+    "detached_value_tests",
+    "evaluation_order_tests",
+    "event_loop_tests",
+    "global_layer_tests",
+    "iteration_tests",
+    "json_tests",
+    "promise_tests",
+    "property_attribute_tests",
+    "prototype_tests",
+    "runtime_error_tests",
+    "string_case_tests",
+    "symbol_tests",
+];
+
+/// A persistent engine that collects at every allocation
+fn stress_engine() -> Engine {
+    let config = VMConfig { gc_stress: true, ..VMConfig::default() };
+    Engine::builder().persistent(true).vm_config(config).build()
+}
+
+#[test]
+fn test_builtin_suites_pass_under_gc_stress() {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let mut command = Command::new(cargo);
+    command.arg("test");
+    for suite in BUILTIN_SUITES {
+        command.args(["--test", suite]);
+    }
+    let output = command
+        .env("V8_GC_STRESS", "1")
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "Built-in suites failed under V8_GC_STRESS:\n{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr),
+    );
+}
+
+#[test]
+fn test_collections_run_while_scripts_allocate() {
+    let mut engine = stress_engine();
+    let collections = Rc::new(RefCell::new(0));
+    let seen = collections.clone();
+    engine.register_function("collections", move |cx| {
+        *seen.borrow_mut() = cx.vm.stress_collections();
+        Ok(Value::Undefined)
+    });
+    
+    let result = engine.execute_detailed(
+        "let list = [];
+         for (let i = 0; i < 5; i++) list.push({ i: i, pair: [i, i] });
+         collections();
+         JSON.stringify(list[4])",
+    ).result;
+    assert_eq!(result.unwrap(), Value::string("{\"i\":4,\"pair\":[4,4]}"));
+    assert!(*collections.borrow() >= 5);
+}
+
+#[test]
+fn test_unrooted_locals_are_reported() {
+    let mut engine = stress_engine();
+    engine.register_function("unrooted", |cx| {
+        let first = Value::new_object();
+        first.set_property("x", Value::Number(1.0))?;
+        let _second = cx.new_object();
+        Ok(first.get_property("x").unwrap_or_default())
+    });
+    engine.register_function("rooted", |cx| {
+        let first = Value::new_object();
+        first.set_property("x", Value::Number(1.0))?;
+        let first = cx.root(first);
+        let _second = cx.new_object();
+        Ok(first.get_property("x").unwrap_or_default())
+    });
+    
+    assert_eq!(engine.execute_detailed("rooted()").result.unwrap(), Value::Number(1.0));
+    let error = engine.execute_detailed("unrooted()").result.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::NativeFunctionPanicked);
+    assert!(error.to_string().contains("already mutably borrowed"), "{}", error);
+}
+
+#[test]
+fn test_persistents_outlive_the_call_that_made_them() {
+    let mut engine = stress_engine();
+    let kept: Rc<RefCell<Option<(Value, Persistent)>>> = Rc::default();
+    let sink = kept.clone();
+    engine.register_function("keep", move |cx| {
+        let persistent = cx.vm.persistent(cx.arg(1));
+        *sink.borrow_mut() = Some((cx.arg(0), persistent));
+        Ok(Value::Undefined)
+    });
+    let held = kept.clone();
+    engine.register_function("persistent", move |_| {
+        Ok(held.borrow().as_ref().map_or(Value::Undefined, |(_, persistent)| persistent.get()))
+    });
+    engine.register_function("plain", move |_| {
+        let kept = kept.borrow();
+        let plain = kept.as_ref().map_or(Value::Undefined, |(plain, _)| plain.clone());
+        Ok(Value::string(plain.inspect()))
+    });
+    
+    engine.execute_detailed("keep({ answer: 1 }, { answer: 2 })").result.unwrap();
+    let result = engine.execute_detailed("let garbage = [[], {}]; persistent().answer").result;
+    assert_eq!(result.unwrap(), Value::Number(2.0));
+    let error = engine.execute_detailed("plain()").result.unwrap_err();
+    assert_eq!(error.code(), ErrorCode::NativeFunctionPanicked);
+    assert!(error.to_string().contains("already mutably borrowed"), "{}", error);
+}
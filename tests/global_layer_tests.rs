@@ -0,0 +1,111 @@
+//! Tests for globals injected for one evaluation: they shadow real
+//! globals, take the writes aimed at them, and are gone afterwards
+
+use v8::{Engine, ErrorCode, GlobalSlot, Value};
+
+fn bindings(pairs: &[(&str, Value)]) -> Vec<(String, Value)> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.clone())).collect()
+}
+
+#[test]
+fn test_injected_bindings_shadow_globals() {
+    let mut engine = Engine::new_persistent();
+    engine.define_global("limit", GlobalSlot::read_only(Value::Number(10.0)));
+    engine.execute("total = 1").unwrap();
+    
+    let injected = bindings(&[("limit", Value::Number(3.0)), ("extra", Value::string("x"))]);
+    assert_eq!(engine.eval_with_globals("limit * total + extra", injected).unwrap(), Value::string("3x"));
+    // Functions that read a global see the injected binding too
+    let injected = bindings(&[("total", Value::Number(5.0))]);
+    assert_eq!(engine.eval_with_globals("function f() { return total; } f()", injected).unwrap(), Value::Number(5.0));
+    
+    assert_eq!(engine.eval_expression("limit * total").unwrap(), Value::Number(10.0));
+    assert_eq!(engine.eval_expression("typeof extra").unwrap(), Value::string("undefined"));
+}
+
+#[test]
+fn test_writes_go_to_the_binding_owner() {
+    for strict in ["", "'use strict'; "] {
+        let mut engine = Engine::new_persistent();
+        engine.execute("shared = 1").unwrap();
+        
+        let source = format!("{}shadowed = shadowed + 1; shared = shared + 1; shadowed", strict);
+        let injected = bindings(&[("shadowed", Value::Number(41.0))]);
+        assert_eq!(engine.eval_with_globals(&source, injected).unwrap(), Value::Number(42.0), "{}", strict);
+        // The injected binding took the write and went away with it; the
+        // real global kept its own
+        assert_eq!(engine.eval_expression("typeof shadowed").unwrap(), Value::string("undefined"), "{}", strict);
+        assert_eq!(engine.eval_expression("shared").unwrap(), Value::Number(2.0), "{}", strict);
+    }
+    
+    // A name no layer has goes to the global object in sloppy mode...
+    let mut engine = Engine::new_persistent();
+    engine.eval_with_globals("created = 7", bindings(&[("other", Value::Null)])).unwrap();
+    assert_eq!(engine.eval_expression("created").unwrap(), Value::Number(7.0));
+    // ...and is an error in strict mode
+    let error = engine.eval_with_globals("'use strict'; missing = 7", Vec::new()).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::UnknownGlobal);
+    assert_eq!(engine.eval_expression("typeof missing").unwrap(), Value::string("undefined"));
+}
+
+#[test]
+fn test_read_only_bindings_behave_like_read_only_globals() {
+    let mut engine = Engine::new_persistent();
+    let slots = || vec![("limit".to_string(), GlobalSlot::read_only(Value::Number(3.0)))];
+    assert_eq!(engine.eval_with_global_slots("limit = 4; limit", slots()).unwrap(), Value::Number(3.0));
+    let error = engine.eval_with_global_slots("'use strict'; limit = 4", slots()).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ReadOnlyProperty);
+    assert_eq!(engine.eval_expression("typeof limit").unwrap(), Value::string("undefined"));
+    
+    // Writable ones take the write, as with `eval_with_globals`
+    let slots = vec![("limit".to_string(), GlobalSlot::new(Value::Number(3.0)))];
+    assert_eq!(engine.eval_with_global_slots("'use strict'; limit = 4; limit", slots).unwrap(), Value::Number(4.0));
+}
+
+#[test]
+fn test_result_history_is_a_layer() {
+    let mut engine = Engine::new_persistent();
+    engine.execute("41").unwrap();
+    assert_eq!(engine.execute("_ + 1").unwrap(), Value::Number(42.0));
+    
+    // Numbered results are read-only
+    assert_eq!(engine.execute("_1 = 0; _1").unwrap(), Value::Number(41.0));
+    let error = engine.execute("'use strict'; _1 = 0").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::ReadOnlyProperty);
+    
+    // They are not globals, so callbacks left for later don't see them
+    engine.execute("setTimeout(function () { seen = typeof _1; }, 0)").unwrap();
+    while engine.run_pending(1).unwrap() {}
+    assert_eq!(engine.execute("seen").unwrap(), Value::string("undefined"));
+    
+    // A declared `_` takes over from the history
+    engine.eval_repl_input("let _ = 'mine'").unwrap();
+    engine.execute("5").unwrap();
+    assert_eq!(engine.execute("_").unwrap(), Value::string("mine"));
+    assert_eq!(engine.execute("_2").unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn test_layer_is_popped_when_evaluation_fails() {
+    let mut engine = Engine::new_persistent();
+    engine.execute("value = 'real'").unwrap();
+    
+    for source in ["throw new Error('boom')", "value.missing.property", "function f() { value(); } f()"] {
+        let injected = bindings(&[("value", Value::string("injected"))]);
+        assert!(engine.eval_with_globals(source, injected).is_err(), "{}", source);
+        assert_eq!(engine.eval_expression("value").unwrap(), Value::string("real"), "{}", source);
+    }
+    
+    // Callbacks left for later see the real globals
+    let injected = bindings(&[("value", Value::string("injected"))]);
+    engine.eval_with_globals("setTimeout(function () { seen = value; }, 0)", injected).unwrap();
+    while engine.run_pending(1).unwrap() {}
+    assert_eq!(engine.eval_expression("seen").unwrap(), Value::string("real"));
+}
+
+#[test]
+fn test_repl_declarations_work_in_strict_mode() {
+    let mut engine = Engine::new_persistent();
+    assert_eq!(engine.eval_repl_input("'use strict'; let x = 1; x").unwrap(), Value::Number(1.0));
+    assert_eq!(engine.eval_repl_input("'use strict'; const x = 2; x + 1").unwrap(), Value::Number(3.0));
+}
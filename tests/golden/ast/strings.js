@@ -0,0 +1,7 @@
+let quote = "say \"hi\"";
+let path = 'C:\\temp\\new';
+let lines = "one\ntwo\tthree";
+let table = { "a\"b": 'it\'s', plain: "\u2028" };
+table["a\"b"] + table.plain;
+table[quote].length;
+({ x: 1 }).x;
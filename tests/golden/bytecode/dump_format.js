@@ -0,0 +1,10 @@
+'use strict';
+const greeting = { text: "hi", count: 2 };
+let scale = n => n * 1.5;
+function* pairs(limit) {
+    for (let i = 0; i < limit; i++) yield [i, greeting.text];
+}
+async function later() {
+    return await scale(greeting.count);
+}
+pairs(2).next().value;
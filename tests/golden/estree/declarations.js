@@ -0,0 +1,5 @@
+let total = 0;
+function add(a, b) {
+    return a + b;
+}
+total += add(1, 2.5);
@@ -0,0 +1,5 @@
+let o = { x, y: "é", [k]: null, m() { return this; }, ...rest };
+o.list = [1, , /ab+c/g];
+let f = (n) => n * 2;
+typeof o.x === "undefined" && !f(void 0);
+i++;
@@ -0,0 +1,9 @@
+class Point {
+    constructor(x) { this.x = x; }
+    static origin() { return new Point(0); }
+}
+for (let i = 0; i < 3; i++) {
+    if (i == 1) continue; else break;
+}
+for (const p of []) {}
+while (false) debugger;
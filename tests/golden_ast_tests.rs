@@ -0,0 +1,37 @@
+//! Golden tests for printed ASTs
+//!
+//! Each `tests/golden/ast/*.js` file is parsed and its `Display` output
+//! compared with the `.txt` file next to it.
+
+use std::fs;
+use std::path::Path;
+
+use v8::ast::{PrettyPrint, Program};
+use v8::{Lexer, Parser, Result};
+
+fn parse(source: &str) -> Result<Program> {
+    Parser::new(Lexer::new(source).tokenize()?).parse()
+}
+
+/// Compare the printed AST of `tests/golden/ast/<name>.js` with `<name>.txt`
+fn assert_golden(name: &str) -> Result<()> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden/ast");
+    let source = fs::read_to_string(dir.join(format!("{}.js", name)))?;
+    let expected = fs::read_to_string(dir.join(format!("{}.txt", name)))?;
+    let actual = format!("{}\n", parse(&source)?);
+    assert_eq!(actual, expected, "printed AST of {}.js differs", name);
+    Ok(())
+}
+
+#[test]
+fn test_string_literals_print_escaped() -> Result<()> {
+    assert_golden("strings")
+}
+
+#[test]
+fn test_pretty_print_escapes_strings() -> Result<()> {
+    let program = parse("'a\"\\\\\\n'")?;
+    let printed = program.statements[0].pretty_print(0);
+    assert!(printed.contains("Literal(String(\"a\\\"\\\\\\n\"))"), "{}", printed);
+    Ok(())
+}
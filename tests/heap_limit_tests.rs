@@ -0,0 +1,115 @@
+//! Tests for `VMConfig::max_heap_bytes`: scripts that keep allocating stop
+//! with an `AllocationLimit` RangeError before they take much more than
+//! the limit, and programs within it run as before
+
+use v8::{Engine, EngineIo, Error, ErrorCode, RuntimeErrorKind, VMConfig, Value, VM};
+
+const LIMIT: usize = 32 << 20;
+
+fn limited(max_heap_bytes: usize) -> VMConfig {
+    VMConfig { max_heap_bytes: Some(max_heap_bytes), ..VMConfig::default() }
+}
+
+/// Run `source` on a fresh VM with `config`, returning the VM too
+fn run(source: &str, config: VMConfig) -> (VM, v8::Result<Value>) {
+    let function = Engine::new().compile_cached(source).unwrap();
+    let mut vm = VM::with_config(config);
+    let result = vm.execute_shared(function);
+    (vm, result)
+}
+
+/// The process's resident and peak resident memory, in bytes
+#[cfg(target_os = "linux")]
+fn resident_memory() -> (usize, usize) {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    let field = |name: &str| {
+        let line = status.lines().find(|line| line.starts_with(name)).unwrap();
+        line.split_whitespace().nth(1).unwrap().parse::<usize>().unwrap() * 1024
+    };
+    (field("VmRSS:"), field("VmHWM:"))
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_unbounded_arrays_stop_before_the_limit_is_resident() {
+    let (resident_before, _) = resident_memory();
+    let (mut vm, result) = run("let keep = []; while (true) { keep.push([1, 2, 3, 4, 5, 6, 7, 8]); }", limited(LIMIT));
+    let Error::Runtime { kind, .. } = result.unwrap_err() else { panic!("not a runtime error") };
+    assert_eq!(kind, RuntimeErrorKind::AllocationLimit { limit: LIMIT });
+    assert_eq!(kind.to_string(), format!("RangeError: Allocation limit of {} bytes exceeded", LIMIT));
+    assert!(vm.heap_bytes().unwrap() <= LIMIT);
+    
+    // The peak since the process started bounds what the script took.
+    // The charges are estimates, and a vector that doubles holds its old
+    // and new storage at once, so allow twice the limit
+    let (_, peak) = resident_memory();
+    assert!(peak.saturating_sub(resident_before) < 2 * LIMIT, "grew by {} bytes", peak - resident_before);
+}
+
+#[test]
+fn test_every_kind_of_growth_is_limited() {
+    for source in [
+        "let keep = []; let i = 0; while (true) { keep[i] = { index: i, name: 'item' + i }; i++; }",
+        "let s = 'x'; let all = []; while (true) { s = s + s; all.push(s); }",
+        "let a = []; while (true) { a.push(a.length); }",
+        "let o = {}; let i = 0; while (true) { o['key' + i] = i; i++; }",
+        "let a = []; a.length = 1 << 24; a.push(1, 2, 3)",
+    ] {
+        let (mut vm, result) = run(source, limited(4 << 20));
+        assert_eq!(result.unwrap_err().code(), ErrorCode::AllocationLimit, "{}", source);
+        assert!(vm.heap_bytes().unwrap() <= 4 << 20, "{}", source);
+    }
+}
+
+#[test]
+fn test_dropped_values_make_room_again() {
+    // Each string is dropped by the next iteration, so what is live stays
+    // far below the limit although the total allocated passes it many
+    // times
+    let source = "let total = 0; for (let i = 0; i < 20000; i++) { let s = 'chunk of text ' + i; total = total + s.length; } total";
+    let (mut vm, result) = run(source, limited(256 << 10));
+    assert_eq!(result.unwrap(), Value::Number(20000.0 * 14.0 + 88890.0));
+    assert!(vm.heap_bytes().unwrap() < 4096);
+
+    let source = "let live = []; for (let i = 0; i < 1000; i++) { live = [i, i, i, i]; } live[0]";
+    assert_eq!(run(source, limited(64 << 10)).1.unwrap(), Value::Number(999.0));
+}
+
+#[test]
+fn test_programs_within_the_limit_are_unaffected() {
+    for source in [
+        "let o = { a: 1, b: [1, 2, 3] }; o.c = o.b.length; JSON.stringify(o)",
+        "let parts = []; for (let i = 0; i < 100; i++) { parts.push('p' + i); } parts[99] + parts.length",
+        "class Point { constructor(x) { this.x = x; } } let ps = []; for (let i = 0; i < 50; i++) { ps.push(new Point(i)); } ps[49].x",
+        "Object.keys({ x: 1, y: 2 }).length + 'abc'.toUpperCase()",
+        "function* g() { yield 'a'; yield 'b'; } let r = ''; for (const v of g()) { r = r + v; } r",
+    ] {
+        let expected = Engine::new().execute(source).unwrap();
+        let (mut vm, result) = run(source, limited(LIMIT));
+        assert_eq!(result.unwrap(), expected, "{}", source);
+        assert!(vm.heap_bytes().unwrap() > 0, "{}", source);
+    }
+    let (mut vm, result) = run("1 + 2", VMConfig::default());
+    assert_eq!(result.unwrap(), Value::Number(3.0));
+    assert_eq!(vm.heap_bytes(), None);
+}
+
+#[test]
+fn test_the_error_can_be_caught() {
+    // A persistent engine keeps running after a script hits the limit
+    let mut engine = Engine::builder().persistent(true).vm_config(limited(1 << 20)).build();
+    let error = engine.execute("let hog = []; while (true) { hog.push('#' + hog.length); }").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::AllocationLimit);
+    assert_eq!(engine.execute("hog = null; [1, 2, 3].length").unwrap(), Value::Number(3.0));
+
+    // In an async function it rejects the function's promise
+    let (io, output) = EngineIo::capture("");
+    let mut engine = Engine::builder().persistent(true).vm_config(limited(1 << 20)).build();
+    engine.set_io(io);
+    engine.execute("
+        async function fill() { let items = []; while (true) { items.push([items.length]); } }
+        let handled = fill().catch(reason => console.log('caught', reason));
+    ").unwrap();
+    engine.run_pending(0).unwrap();
+    assert_eq!(output.stdout(), "caught RangeError: Allocation limit of 1048576 bytes exceeded\n");
+}
@@ -0,0 +1,108 @@
+//! Tests for iteration: for-of, `Array.from` and `Promise.all` see the
+//! same elements of every iterable, and fail the same way on the rest
+
+use std::cell::RefCell;
+use std::ops::ControlFlow;
+use std::rc::Rc;
+
+use v8::vm::IterationConsumer;
+use v8::{Engine, ErrorCode, Value, VM};
+
+/// Iterables and the elements every consumer must see, as JSON
+const ITERABLES: [(&str, &str); 6] = [
+    ("[1, 2, 3]", "[1,2,3]"),
+    ("[1, , 3]", "[1,null,3]"),
+    ("[]", "[]"),
+    ("'a😀b'", "[\"a\",\"😀\",\"b\"]"),
+    ("(function* () { yield 'x'; yield 'y'; })()", "[\"x\",\"y\"]"),
+    ("Array.from({ length: 2, 0: 'p', 1: 'q' })", "[\"p\",\"q\"]"),
+];
+
+/// Values no consumer can iterate
+const NOT_ITERABLE: [&str; 4] = ["5", "null", "true", "{ length: 1, 0: 'z' }"];
+
+/// Scripts that log the elements of `(VALUE)` as JSON, one per consumer
+const CONSUMERS: [(&str, &str); 3] = [
+    ("for-of", "let out = []; for (const x of (VALUE)) { out.push(x); } console.log(JSON.stringify(out));"),
+    ("Array.from", "console.log(JSON.stringify(Array.from((VALUE))));"),
+    ("Promise.all", "Promise.all((VALUE)).then(function (out) { console.log(JSON.stringify(out)); });"),
+];
+
+/// Run `source` to completion; the console lines, or the error
+fn run(source: &str) -> Result<Vec<String>, String> {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let sink = output.clone();
+    let mut engine = Engine::new();
+    engine.set_console_handler(move |line| sink.borrow_mut().push(line.to_string()));
+    engine.execute(source).map_err(|error| error.to_string())?;
+    engine.run_pending(0).map_err(|error| error.to_string())?;
+    let lines = output.borrow().clone();
+    Ok(lines)
+}
+
+#[test]
+fn test_consumers_see_the_same_elements() {
+    for (value, expected) in ITERABLES {
+        for (consumer, template) in CONSUMERS {
+            let lines = run(&template.replace("VALUE", value)).unwrap();
+            assert_eq!(lines, [expected], "{} over {}", consumer, value);
+        }
+    }
+}
+
+#[test]
+fn test_consumers_reject_the_same_values() {
+    for value in NOT_ITERABLE {
+        for (consumer, template) in CONSUMERS {
+            let source = template.replace("VALUE", value);
+            if consumer == "Array.from" && value != "null" {
+                // Array.from also reads array-likes; primitives have no length
+                continue;
+            }
+            let error = run(&source).unwrap_err();
+            let shown = if value.starts_with('{') { "[object Object]" } else { value };
+            assert!(
+                error.contains(&format!("TypeError: {} is not iterable (in {})", shown, consumer)),
+                "{} over {}: {}",
+                consumer,
+                value,
+                error
+            );
+        }
+    }
+}
+
+#[test]
+fn test_array_from() {
+    assert_eq!(run("console.log(JSON.stringify(Array.from(5)), JSON.stringify(Array.from({ length: 2 })))").unwrap(), ["[] [null,null]"]);
+    assert_eq!(
+        run("console.log(JSON.stringify(Array.from('abc', function (c, i) { return c + i; })))").unwrap(),
+        ["[\"a0\",\"b1\",\"c2\"]"]
+    );
+    
+    // A copy, not the same array
+    assert_eq!(run("let a = [1]; let b = Array.from(a); b.push(2); console.log(a.length, b.length)").unwrap(), ["1 2"]);
+    
+    let error = Engine::new().execute("Array.from([1], 7)").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidArgument, "{}", error);
+}
+
+#[test]
+fn test_for_of_sees_elements_pushed_while_iterating() {
+    let source = "let a = [1, 2]; let seen = 0; for (const x of a) { seen++; if (a.length < 4) { a.push(x); } } console.log(seen);";
+    assert_eq!(run(source).unwrap(), ["4"]);
+}
+
+#[test]
+fn test_breaking_early_closes_generators() {
+    let mut vm = VM::new();
+    let source = "gen = (function* () { yield 1; yield 2; })(); gen";
+    let generator = vm.execute_shared(Engine::new().compile_cached(source).unwrap()).unwrap();
+    let mut seen = Vec::new();
+    vm.iterate_value(&generator, IterationConsumer::ForOf, |_, element| {
+        seen.push(element);
+        Ok(ControlFlow::Break(()))
+    }).unwrap();
+    assert_eq!(seen, [Value::Number(1.0)]);
+    assert!(vm.collect_iterable(&generator, IterationConsumer::ForOf).unwrap().is_empty());
+}
@@ -0,0 +1,74 @@
+//! Tests for the `JSON` global
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{eval, eval_error};
+
+#[test]
+fn test_stringify_values() {
+    assert_eq!(
+        eval("JSON.stringify({ a: 1, b: [true, null, 'x'], c: { d: -0 } })"),
+        Value::string(r#"{"a":1,"b":[true,null,"x"],"c":{"d":0}}"#)
+    );
+    assert_eq!(eval("JSON.stringify('say \"hi\"\\n')"), Value::string(r#""say \"hi\"\n""#));
+    assert_eq!(eval("JSON.stringify(1 / 0)"), Value::string("null"));
+    assert_eq!(eval("JSON.stringify(undefined)"), Value::Undefined);
+}
+
+#[test]
+fn test_stringify_omits_unrepresentable_members() {
+    assert_eq!(
+        eval("JSON.stringify({ a: undefined, f: function () {}, b: 2 })"),
+        Value::string(r#"{"b":2}"#)
+    );
+    assert_eq!(eval("JSON.stringify([undefined, function () {}])"), Value::string("[null,null]"));
+}
+
+#[test]
+fn test_stringify_indentation_and_to_json() {
+    assert_eq!(
+        eval("JSON.stringify({ a: [1, 2], b: {} }, null, 2)"),
+        Value::string("{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}")
+    );
+    assert_eq!(
+        eval("JSON.stringify({ when: { toJSON: function () { return 'later'; } } })"),
+        Value::string(r#"{"when":"later"}"#)
+    );
+}
+
+#[test]
+fn test_stringify_rejects_cycles() {
+    let error = eval_error("let o = {}; o.self = o; JSON.stringify(o)", ErrorCode::CircularJson);
+    assert!(error.contains("Converting circular structure to JSON"), "{}", error);
+}
+
+#[test]
+fn test_parse_round_trips() {
+    assert_eq!(
+        eval(r#"let o = JSON.parse(' { "a": [1, 2.5e1, -3], "b": { "c": "A\\n" }, "d": null } ');
+                o.a[1] + o.a.length + o.b.c + o.d"#),
+        Value::string("28A\nnull")
+    );
+    assert_eq!(
+        eval(r#"JSON.stringify(JSON.parse('{"x":[{"y":false}],"z":"é"}'))"#),
+        Value::string(r#"{"x":[{"y":false}],"z":"é"}"#)
+    );
+}
+
+#[test]
+fn test_parse_errors() {
+    for (source, expected) in [
+        ("JSON.parse('{\"a\":1,}')", "Unexpected token } in JSON at position 7"),
+        ("JSON.parse('[1, 2')", "Unexpected end of JSON input"),
+        ("JSON.parse('01')", "Unexpected token 1 in JSON at position 1"),
+        ("JSON.parse(\"{'a': 1}\")", "Unexpected token ' in JSON at position 1"),
+    ] {
+        let error = Engine::new().execute(source).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::JsonSyntax, "{}: {}", source, error);
+        assert!(error.to_string().contains(expected), "{}: {}", source, error);
+    }
+    let deep = format!("JSON.parse('{}')", "[".repeat(10_000));
+    assert_eq!(Engine::new().execute(&deep).unwrap_err().code(), ErrorCode::JsonNesting);
+}
@@ -0,0 +1,27 @@
+//! Tests for gating optional syntax through `EngineBuilder::lexer_features`
+
+use v8::{Engine, ErrorCode, FeatureFlags, Value};
+
+#[test]
+fn test_engine_accepts_enabled_features() {
+    let mut engine = Engine::builder().build();
+    let result = engine.eval_block("#!/usr/bin/env v8\n1_000 + 1").unwrap();
+    assert_eq!(result, Value::Number(1001.0));
+}
+
+#[test]
+fn test_engine_rejects_disabled_features() {
+    let features = FeatureFlags { numeric_separators: false, ..FeatureFlags::ALL };
+    let mut engine = Engine::builder().lexer_features(features).build();
+    let error = engine.execute("let n = 1_000;").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::DisabledSyntax);
+    assert!(error.to_string().contains("feature 'numeric_separators'"), "{}", error);
+    assert!(engine.execute("#!/usr/bin/env v8\nlet n = 1000;").is_ok());
+    
+    let features = FeatureFlags { hashbang: false, ..FeatureFlags::ALL };
+    let mut engine = Engine::builder().lexer_features(features).build();
+    let error = engine.eval_block("#!/usr/bin/env v8\n1").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::DisabledSyntax);
+    assert!(error.to_string().contains("feature 'hashbang'"), "{}", error);
+    assert_eq!(engine.eval_block("1_000").unwrap(), Value::Number(1000.0));
+}
@@ -0,0 +1,163 @@
+//! Tests for hoisting constant loads out of loops: the loads move to the
+//! start of the function, loops read local slots instead, and optimized
+//! programs behave exactly like unoptimized ones
+
+use v8::bytecode::{self, find_loops, Bytecode, ConstIndex, LocalIndex, Loop};
+use v8::{Disassembler, Engine};
+
+mod common;
+
+use common::{compile, optimizing};
+
+const APPEND_LOOP: &str = "let s = '';\nfor (let i = 0; i < 1e6; i++) { s = s + ','; }\ns.length";
+
+/// Each instruction of `function` on a line of its own, without offsets
+fn instructions(function: &v8::BytecodeFunction) -> Vec<String> {
+    function.bytecode.iter().map(Bytecode::to_string).collect()
+}
+
+#[test]
+fn test_append_loop_reads_its_constants_from_slots() {
+    let function = optimizing().compile_cached(APPEND_LOOP).unwrap();
+    let disassembly = Disassembler::minimal_disassemble(&function);
+    let expected = "\
+Bytecode:
+     0: LdaConst #1         
+     1: InitLocal 2         
+     2: LdaConst #2         
+     3: InitLocal 3         
+     4: LdaConst #0         
+     5: StaLocal 0          
+     6: LdaSmi 0            
+     7: StaLocal 1          
+     8: LdaLocal 1          
+     9: Push                
+    10: LdaLocal 2          
+    11: Lt                  
+    12: JumpIfFalse 9       
+    13: LdaLocal 0          
+    14: Push                
+    15: LdaLocal 3          
+    16: Add                 
+    17: StaLocal 0          
+    18: LdaLocal 1          
+    19: Inc                 
+    20: StaLocal 1          
+    21: Jump -14            
+";
+    assert!(disassembly.starts_with(expected), "{}", disassembly);
+    
+    // The full listing shows which constants the slots hold and that the
+    // statement starts and local names moved with the code
+    let disassembly = Disassembler::quick_disassemble(&function);
+    assert!(disassembly.contains("Locals: 4 (const 2, 3)"), "{}", disassembly);
+    assert!(disassembly.contains("     0:  LdaConst #1 (1000000)"), "{}", disassembly);
+    assert!(disassembly.contains("     2:  LdaConst #2 (\",\")"), "{}", disassembly);
+    assert!(disassembly.contains("     4:> LdaConst #0 (\"\")     ; line 1"), "{}", disassembly);
+    assert!(disassembly.contains("    13:> LdaLocal 0           ; s, line 2"), "{}", disassembly);
+    bytecode::verify(&function).unwrap();
+}
+
+#[test]
+fn test_without_optimizing_loops_load_constants() {
+    let function = Engine::new().compile_cached(APPEND_LOOP).unwrap();
+    let code = instructions(&function);
+    assert_eq!(code[0], "LdaConst #0");
+    assert_eq!(function.locals_count, 2);
+    let loop_body = find_loops(&function)[0].body();
+    assert!(code[loop_body].contains(&"LdaConst #1".to_string()));
+}
+
+#[test]
+fn test_only_constants_in_loops_are_hoisted() {
+    // The constant before the loop stays where it is, and one used both
+    // outside and inside a loop is only replaced inside
+    let function = compile("let a = 'x'; while (a.length < 3) { a = a + 'x'; } a", Some(8));
+    let code = instructions(&function);
+    assert_eq!(&code[..3], ["LdaConst #0", "InitLocal 1", "LdaConst #0"]);
+    assert_eq!(code.iter().filter(|instruction| *instruction == "LdaLocal 1").count(), 1);
+    
+    // Loops without constants, and code without loops, are left alone
+    for source in ["let n = 0; while (n < 3) { n++; } n", "let s = 'a' + 'b'; s"] {
+        assert_eq!(compile(source, Some(8)), compile(source, Some(0)), "{}", source);
+    }
+}
+
+#[test]
+fn test_object_constants_are_not_hoisted() {
+    // Each of these makes a new object on every evaluation
+    for source in [
+        "let a = []; for (let i = 0; i < 2; i++) { a.push({ x: 1 }); } a[0] === a[1]",
+        "let a = []; for (let i = 0; i < 2; i++) { a.push([1, 2]); } a[0] === a[1]",
+        "let a = []; for (let i = 0; i < 2; i++) { a.push(/x/g); } a[0] === a[1]",
+    ] {
+        let function = compile(source, Some(8));
+        assert!(!function.bytecode.iter().any(|instruction| matches!(instruction, Bytecode::InitLocal(_))), "{}", source);
+        assert_eq!(optimizing().execute(source).unwrap(), v8::Value::Boolean(false), "{}", source);
+    }
+}
+
+#[test]
+fn test_the_limit_caps_the_constants_hoisted() {
+    let source = "let s = ''; for (let i = 0; i < 3; i++) { s = s + 'a' + 'b' + 'c'; } s";
+    let hoisted = |max| compile(source, Some(max)).bytecode.iter().filter(|instruction| matches!(instruction, Bytecode::InitLocal(_))).count();
+    // The loop's constants are 3, 'a', 'b' and 'c', unless adjacent
+    // strings were folded into one
+    let all = hoisted(8);
+    assert!(all >= 2, "{}", all);
+    assert_eq!(hoisted(1), 1);
+    assert_eq!(hoisted(0), 0);
+    
+    let mut engine = Engine::builder().optimize(true).max_hoisted_constants(1).build();
+    let function = engine.compile_cached(source).unwrap();
+    assert_eq!(function.bytecode[..2], [Bytecode::LdaConst(ConstIndex(1)), Bytecode::InitLocal(LocalIndex(2))]);
+    let mut engine = Engine::builder().optimize(true).max_hoisted_constants(0).build();
+    assert_eq!(*engine.compile_cached(source).unwrap(), *Engine::new().compile_cached(source).unwrap());
+}
+
+#[test]
+fn test_nested_functions_hoist_into_their_own_slots() {
+    let source = "function f(n) { let s = ''; for (let i = 0; i < n; i++) { s = s + '-'; } return s; } f(3)";
+    let main = compile(source, Some(8));
+    let inner = &main.functions[0];
+    assert!(matches!(inner.bytecode[..2], [Bytecode::LdaConst(_), Bytecode::InitLocal(LocalIndex(3))]), "{:?}", inner.bytecode);
+    assert!(inner.immutable_locals.contains(LocalIndex(3)));
+    assert_eq!(inner.locals_count, 4);
+    // Parameters still count as live from the start
+    assert_eq!(inner.debug_info.locals[0].live.start, 0);
+}
+
+#[test]
+fn test_loops_are_found_from_back_edges() {
+    let loops = find_loops(&compile("let n = 0; while (n < 3) { n++; }", Some(0)));
+    assert_eq!(loops.len(), 1);
+    let Loop { header, back_edge } = loops[0];
+    assert!(header < back_edge);
+    
+    // Nested loops come out outer first; `continue` adds no loop
+    let function = compile("for (let i = 0; i < 2; i++) { for (let j = 0; j < 2; j++) { if (j) { continue; } } }", Some(0));
+    let loops = find_loops(&function);
+    assert_eq!(loops.len(), 2);
+    assert!(loops[0].contains(loops[1].header) && loops[0].contains(loops[1].back_edge));
+    let function = compile("let n = 0; while (n < 3) { n++; if (n < 2) { continue; } }", Some(0));
+    assert_eq!(find_loops(&function).len(), 1);
+    
+    assert!(find_loops(&compile("let n = 1; if (n) { n = 2; }", Some(0))).is_empty());
+}
+
+#[test]
+fn test_optimized_programs_behave_the_same() {
+    for source in [
+        "let s = ''; for (let i = 0; i < 100; i++) { s = s + ','; } s.length",
+        "let total = 0; for (let i = 0; i < 10; i++) { total = total + 2.5; } total",
+        "let s = ''; let n = 0; while (true) { n++; if (n % 2) { continue; } s = s + 'ab'; if (n > 5) { break; } } s",
+        "let out = ''; for (const w of ['a', 'b']) { out = out + w + '!'; } out",
+        "let fs = []; for (let i = 0; i < 3; i++) { fs.push(() => 'v' + i); } fs[0]() + fs[2]()",
+        "function* g() { for (let i = 0; i < 3; i++) { yield 'step' + i; } } let r = ''; for (const v of g()) { r = r + v; } r",
+        "let hits = 0; for (let i = 0; i < 5; i++) { if (i === 2) { continue; } if (i === 4) { break; } hits = hits + 1; } hits",
+        "let t = 0; while (true) { t = t + 1; if (t > 3) { break; } } t === 4 && null === null",
+    ] {
+        let plain = Engine::new().execute(source).unwrap();
+        assert_eq!(optimizing().execute(source).unwrap(), plain, "{}", source);
+    }
+}
@@ -0,0 +1,66 @@
+//! Tests for the execution metrics collected with `EngineBuilder::metrics`
+
+use std::fs;
+
+use v8::{Engine, VmMetrics, VM};
+
+fn fixture() -> String {
+    fs::read_to_string("tests/fixtures/semicolons.js").unwrap()
+}
+
+#[test]
+fn test_metrics_are_collected_and_consistent() {
+    let mut engine = Engine::builder().metrics(true).build();
+    let report = engine.execute_detailed(&fixture());
+    assert!(report.result.is_ok());
+    let metrics = *engine.last_metrics().expect("metrics are enabled");
+    
+    assert_eq!(metrics.timings, report.timings);
+    assert_eq!(metrics.statements, report.ast.as_ref().unwrap().statements.len());
+    assert!(metrics.statements > 0);
+    assert!(metrics.instructions_compiled > 0);
+    assert!(metrics.constants > 0);
+    assert!(metrics.peak_stack_depth > 0);
+    // Every statement runs at least one instruction, and the loops run
+    // more than the whole program holds
+    assert!(metrics.instructions_executed >= metrics.statements as u64);
+    assert!(metrics.instructions_executed > metrics.instructions_compiled as u64);
+    
+    // Nested functions count towards the compiled totals
+    let main = report.bytecode.unwrap();
+    assert!(metrics.instructions_compiled > main.bytecode.len());
+    
+    let table = metrics.to_string();
+    for row in ["lex", "parse", "compile", "execute", "total", "instructions executed", "peak stack depth"] {
+        assert!(table.contains(row), "{}", table);
+    }
+}
+
+#[test]
+fn test_each_execution_replaces_the_metrics() {
+    let mut engine = Engine::builder().metrics(true).persistent(true).build();
+    engine.execute_detailed("let i = 0; while (i < 100) { i++; }");
+    let looped = engine.last_metrics().unwrap().instructions_executed;
+    engine.execute_detailed("i");
+    let single = engine.last_metrics().unwrap().instructions_executed;
+    assert!(single < looped, "{} < {}", single, looped);
+    
+    // A failed compilation still reports the phases that ran
+    engine.execute_detailed("let = 1;");
+    let metrics = engine.last_metrics().unwrap();
+    assert_eq!(metrics.instructions_executed, 0);
+    assert_eq!(metrics.instructions_compiled, 0);
+}
+
+#[test]
+fn test_disabled_metrics_cost_nothing() {
+    let mut engine = Engine::new();
+    engine.execute_detailed(&fixture()).result.unwrap();
+    assert!(engine.last_metrics().is_none());
+    
+    // Without `enable_metrics` the VM counts nothing at all
+    let mut vm = VM::new();
+    let function = Engine::new().compile_cached(&fixture()).unwrap();
+    vm.execute_shared(function).unwrap();
+    assert_eq!(vm.take_metrics(), VmMetrics::default());
+}
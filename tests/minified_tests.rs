@@ -0,0 +1,48 @@
+//! Expressions in the style of minified code
+//!
+//! Each line of `tests/fixtures/minified.js` is an expression and, after
+//! `//=>`, what it evaluates to. They lean on prefix operators: `void 0`,
+//! `typeof` on undeclared names, calls and members, and `!`/`~` on calls.
+
+use std::fs;
+
+use v8::{Engine, RuntimeErrorKind};
+
+/// Declarations the fixture's expressions use
+const PRELUDE: &str = "\
+function f() { return 'r'; }
+let o = { y: 0, s: 'abc', g: function () { return this.y; } };
+let str = 'hello world';
+";
+
+#[test]
+fn test_minified_fixture() {
+    let fixture = fs::read_to_string("tests/fixtures/minified.js").unwrap();
+    let mut failures = Vec::new();
+    for line in fixture.lines().filter(|line| !line.trim().is_empty()) {
+        let (expression, expected) = line.split_once(" //=> ").unwrap_or_else(|| panic!("no //=> in: {}", line));
+        match Engine::new().execute(&format!("{}{}", PRELUDE, expression)) {
+            Ok(value) if value.to_string() == expected => {}
+            Ok(value) => failures.push(format!("{} gave {}, expected {}", expression, value, expected)),
+            Err(error) => failures.push(format!("{} failed: {}", expression, error)),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn test_typeof_only_excuses_the_undeclared_name_itself() {
+    let error = Engine::new().execute("typeof undeclaredThing.x").unwrap_err();
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::UnknownGlobal { name: "undeclaredThing".to_string(), suggestion: None }));
+    let error = Engine::new().execute("typeof undeclaredThing()").unwrap_err();
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::UnknownGlobal { name: "undeclaredThing".to_string(), suggestion: None }));
+    
+    // Declared later in the program is still declared
+    assert_eq!(Engine::new().execute("let t = typeof later; var later = 1; t").unwrap().to_string(), "undefined");
+}
+
+#[test]
+fn test_void_evaluates_its_operand() {
+    let value = Engine::new().execute("let calls = 0; function tick() { calls++; } void tick(); void tick(); calls").unwrap();
+    assert_eq!(value.to_string(), "2");
+}
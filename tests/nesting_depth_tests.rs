@@ -0,0 +1,80 @@
+//! Tests for the nesting depth limit shared by the parser and the compiler
+
+use v8::ast::{BinaryOp, Expr, Literal, Program, Stmt, UnaryOp};
+use v8::error::Span;
+use v8::{Compiler, Engine, Error, ErrorCode, Value};
+
+const DEEP: usize = 10_000;
+const SPAN: Span = Span { start: 0, end: 1, line: 1, column: 1 };
+
+fn number(value: f64) -> Expr {
+    Expr::Literal(Literal::Number(value))
+}
+
+fn program(statement: Stmt) -> Program {
+    Program { statements: vec![statement] }
+}
+
+fn compile_error(program: Program) -> Error {
+    let error = Compiler::new_main("").compile(&program).unwrap_err();
+    // Dropping the tree recurses too; a test thread has no stack to spare
+    std::mem::forget(program);
+    error
+}
+
+/// Run `test` on a thread with room for the default limit in a debug
+/// build, whose frames are many times larger than in release
+fn with_large_stack(test: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new().stack_size(64 << 20).spawn(test).unwrap().join().unwrap();
+}
+
+#[test]
+fn test_deep_asts_fail_cleanly() {
+    with_large_stack(deep_asts_fail_cleanly);
+}
+
+fn deep_asts_fail_cleanly() {
+    // 1 - (1 - (1 - ...))
+    let mut expr = number(1.0);
+    for _ in 0..DEEP {
+        expr = Expr::Binary { op: BinaryOp::Subtract, left: Box::new(number(1.0)), right: Box::new(expr), span: SPAN };
+    }
+    let error = compile_error(program(Stmt::Expression(expr)));
+    assert_eq!(error.code(), ErrorCode::TooMuchNesting);
+    assert!(error.to_string().contains("more than 256 levels"), "{}", error);
+    
+    let mut expr = number(1.0);
+    for _ in 0..DEEP {
+        expr = Expr::Unary { op: UnaryOp::Minus, operand: Box::new(expr), span: SPAN };
+    }
+    assert_eq!(compile_error(program(Stmt::Expression(expr))).code(), ErrorCode::TooMuchNesting);
+    
+    let mut statement = Stmt::Empty { span: SPAN };
+    for _ in 0..DEEP {
+        statement = Stmt::Block { statements: vec![statement], span: SPAN };
+    }
+    assert_eq!(compile_error(program(statement)).code(), ErrorCode::TooMuchNesting);
+}
+
+#[test]
+fn test_long_operator_chains_do_not_nest() {
+    let source = vec!["1"; DEEP].join(" - ");
+    assert_eq!(Engine::new().execute(&source).unwrap(), Value::Number(2.0 - DEEP as f64));
+    
+    let source = format!("let x = 0; x{}", " + 1".repeat(DEEP));
+    assert_eq!(Engine::new().execute(&source).unwrap(), Value::Number(DEEP as f64));
+}
+
+#[test]
+fn test_engine_limit_applies_to_parsing() {
+    let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+    let mut engine = Engine::builder().max_nesting_depth(40).build();
+    assert!(engine.execute(&nested(10)).is_ok());
+    
+    let error = engine.execute(&nested(50)).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::TooMuchNesting);
+    assert!(error.to_string().contains("more than 40 levels"), "{}", error);
+    for source in [format!("{}1{}", "(".repeat(50), ")".repeat(50)), format!("{}{}", "{".repeat(50), "}".repeat(50))] {
+        assert_eq!(engine.execute(&source).unwrap_err().code(), ErrorCode::TooMuchNesting);
+    }
+}
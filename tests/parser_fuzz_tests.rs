@@ -0,0 +1,181 @@
+//! Random and mutated source must never crash or hang the parser
+//!
+//! Every input has to end in `Ok` or `Err` within a time limit, and an
+//! `Ok` program has to pass `ast::validate_source`. Inputs are random
+//! text, or conformance cases with their tokens deleted, repeated,
+//! swapped or interleaved with stray ones; the `fuzz/` crate runs the
+//! same check under libFuzzer.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+
+use proptest::prelude::*;
+use v8::ast::validate_source;
+use v8::{Lexer, Parser};
+
+/// How long one input may take to parse before it counts as a hang
+const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+/// Inputs that once crashed the parser or produced an invalid tree
+const CRASHERS: [&str; 4] = [
+    // At the end of input the parser read `new` again as its own callee,
+    // recursing until the stack overflowed
+    "let make = () => {}; 1e3; make()\nnew\n",
+    "new",
+    "(",
+    "new new new",
+];
+
+/// Tokens worth splicing into a program
+const STRAY_TOKENS: [&str; 40] = [
+    "(", ")", "{", "}", "[", "]", ";", ",", ".", "...", "=>", "=", "+=", "?", ":", "?.", "??",
+    "+", "-", "**", "!", "++", "--", "\n", "/a+/g", "`t${", "}`", "'s'", "0x1F", "1e3",
+    "function", "function*", "async", "await", "yield", "class", "let", "const", "for", "of",
+];
+
+/// Parse `source` on its own thread and validate the result
+///
+/// Panics if the parser panicked, overran `TIME_LIMIT`, or returned a
+/// program with violations.
+fn check(source: &str) {
+    let (sender, receiver) = mpsc::channel();
+    let input = source.to_string();
+    let worker = thread::Builder::new().stack_size(16 * 1024 * 1024).spawn(move || {
+        let violations = match Parser::from_source(Lexer::new(&input)).parse() {
+            Ok(program) => validate_source(&program, &input).iter().map(ToString::to_string).collect(),
+            Err(_) => Vec::new(),
+        };
+        let _ = sender.send(violations);
+    }).unwrap();
+    match receiver.recv_timeout(TIME_LIMIT) {
+        Ok(violations) => {
+            worker.join().unwrap();
+            assert!(violations.is_empty(), "invalid AST for {:?}: {:?}", source, violations);
+        }
+        Err(mpsc::RecvTimeoutError::Disconnected) => panic!("parser panicked on {:?}", source),
+        Err(mpsc::RecvTimeoutError::Timeout) => panic!("parser took over {:?} on {:?}", TIME_LIMIT, source),
+    }
+}
+
+fn fixtures(dir: &Path, found: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            fixtures(&path, found);
+        } else if path.extension().is_some_and(|extension| extension == "js") {
+            found.push(path);
+        }
+    }
+}
+
+/// The conformance cases, each split into pieces of a token and the
+/// whitespace before it, so that joining the pieces gives back the case
+fn corpus() -> &'static [Vec<String>] {
+    static CORPUS: OnceLock<Vec<Vec<String>>> = OnceLock::new();
+    CORPUS.get_or_init(|| {
+        let mut paths = Vec::new();
+        fixtures(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance/cases"), &mut paths);
+        paths.sort();
+        paths.iter().map(|path| pieces(&fs::read_to_string(path).unwrap())).collect()
+    })
+}
+
+/// `source` as token-sized pieces; a case the lexer rejects is split
+/// into lines instead
+fn pieces(source: &str) -> Vec<String> {
+    let Ok(tokens) = Lexer::new(source).tokenize() else {
+        return source.split_inclusive('\n').map(String::from).collect();
+    };
+    let mut pieces = Vec::new();
+    let mut end = 0;
+    for token in tokens {
+        pieces.push(source[end..token.span.end].to_string());
+        end = token.span.end;
+    }
+    pieces.push(source[end..].to_string());
+    pieces
+}
+
+/// A change to a list of pieces, at positions taken modulo its length
+#[derive(Debug, Clone)]
+enum Mutation {
+    Delete(usize),
+    Repeat(usize),
+    Swap(usize, usize),
+    Insert(usize, &'static str),
+}
+
+impl Mutation {
+    fn apply(&self, pieces: &mut Vec<String>) {
+        if pieces.is_empty() {
+            return;
+        }
+        let len = pieces.len();
+        match *self {
+            Mutation::Delete(at) => {
+                pieces.remove(at % len);
+            }
+            Mutation::Repeat(at) => pieces.insert(at % len, pieces[at % len].clone()),
+            Mutation::Swap(a, b) => pieces.swap(a % len, b % len),
+            Mutation::Insert(at, token) => pieces.insert(at % len, format!(" {}", token)),
+        }
+    }
+}
+
+fn mutation() -> impl Strategy<Value = Mutation> {
+    prop_oneof![
+        any::<usize>().prop_map(Mutation::Delete),
+        any::<usize>().prop_map(Mutation::Repeat),
+        (any::<usize>(), any::<usize>()).prop_map(|(a, b)| Mutation::Swap(a, b)),
+        (any::<usize>(), prop::sample::select(STRAY_TOKENS.to_vec())).prop_map(|(at, token)| Mutation::Insert(at, token)),
+    ]
+}
+
+/// A conformance case with a few mutations applied
+fn mutated_case() -> impl Strategy<Value = String> {
+    (any::<prop::sample::Index>(), prop::collection::vec(mutation(), 1..6)).prop_map(|(case, mutations)| {
+        let corpus = corpus();
+        let mut pieces = corpus[case.index(corpus.len())].clone();
+        for mutation in &mutations {
+            mutation.apply(&mut pieces);
+        }
+        pieces.concat()
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig { cases: 512, ..ProptestConfig::default() })]
+
+    #[test]
+    fn test_mutated_cases_parse_to_valid_trees(source in mutated_case()) {
+        check(&source);
+    }
+
+    #[test]
+    fn test_random_text_never_panics(source in "[a-z0-9 (){}\\[\\];,.=+*/<>!?:'\"`$\\\\\n-]{0,80}") {
+        check(&source);
+    }
+}
+
+#[test]
+fn test_conformance_cases_parse_to_valid_trees() {
+    for pieces in corpus() {
+        check(&pieces.concat());
+    }
+}
+
+#[test]
+fn test_known_crashers() {
+    for source in CRASHERS {
+        check(source);
+    }
+}
+
+#[test]
+fn test_new_chains_count_towards_the_nesting_limit() {
+    check(&format!("{}x", "new ".repeat(100_000)));
+}
@@ -0,0 +1,42 @@
+//! Tests for the line-level profiler
+
+use v8::{Engine, ProfileMode};
+
+const HOT_LOOP: &str = "\
+function work(n) {
+    let total = 0;
+    for (let i = 0; i < n; i++) {
+        total = total + (i * 3 + 1) * (i % 7) - (i % 5) * 2;
+    }
+    return total;
+}
+work(500);
+";
+
+#[test]
+fn test_hot_loop_line_dominates() {
+    let mut engine = Engine::builder().profile(ProfileMode::Counting).build();
+    engine.execute_detailed(HOT_LOOP).result.unwrap();
+    let report = engine.profile_report().unwrap();
+    
+    let hottest = &report.lines[0];
+    assert_eq!((hottest.function.as_str(), hottest.line), ("work", Some(4)));
+    assert!(hottest.hits * 2 > report.total_hits(), "{}", report);
+    
+    assert_eq!(report.functions[0].function, "work");
+    assert_eq!(report.functions.iter().map(|f| f.hits).sum::<u64>(), report.total_hits());
+    let printed = report.to_string();
+    assert!(printed.contains("work:4"), "{}", printed);
+}
+
+#[test]
+fn test_profiling_is_off_by_default() {
+    let mut engine = Engine::new();
+    engine.execute_detailed(HOT_LOOP).result.unwrap();
+    assert!(engine.profile_report().is_none());
+    
+    // Sampling records nothing unless the host samples
+    let mut engine = Engine::builder().profile(ProfileMode::Sampling).build();
+    engine.execute_detailed(HOT_LOOP).result.unwrap();
+    assert_eq!(engine.profile_report().unwrap().total_hits(), 0);
+}
@@ -0,0 +1,133 @@
+//! Tests for the `Promise` built-in
+//!
+//! Reactions run as microtasks, so every test pumps the event loop with
+//! `Engine::run_pending` and checks the order events were recorded in.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{engine_with, log};
+
+#[test]
+fn test_reactions_run_as_microtasks_in_chain_order() {
+    let mut engine = engine_with(
+        "Promise.resolve(1)
+             .then(function (v) { record(' a' + v); return v + 1; })
+             .then(function (v) { record(' b' + v); });
+         Promise.resolve(10).then(function (v) { record(' c' + v); });
+         let p = new Promise(function (resolve, reject) {
+             record('executor');
+             resolve('first');
+             reject('ignored');
+             resolve('ignored');
+         });
+         p.then(function (v) { record(' ' + v); });
+         record(' sync');",
+    );
+    assert_eq!(log(&mut engine), Value::string("executor sync"));
+    
+    assert!(!engine.run_pending(0).unwrap());
+    assert_eq!(log(&mut engine), Value::string("executor sync a1 c10 first b2"));
+}
+
+#[test]
+fn test_chaining_adopts_thenables() {
+    let mut engine = engine_with(
+        "let later = { then: function (resolve) { setTimeout(function () { resolve('late'); }, 10); } };
+         Promise.resolve(1)
+             .then(function () { return later; })
+             .then(function (v) { record(v); return new Promise(function (r) { r(' inner'); }); })
+             .then(function (v) { record(v); });",
+    );
+    engine.run_pending(0).unwrap();
+    assert_eq!(log(&mut engine), Value::string(""));
+    
+    assert!(!engine.run_pending(10).unwrap());
+    assert_eq!(log(&mut engine), Value::string("late inner"));
+}
+
+#[test]
+fn test_errors_propagate_through_a_chain() {
+    // Each step of a chain is one microtask, so the shorter chains finish first
+    let mut engine = engine_with(
+        "Promise.reject('bad')
+             .then(function () { record('skipped'); })
+             .then(function () { record('skipped'); })
+             .catch(function (e) { record('caught ' + e); return 'recovered'; })
+             .finally(function () { record(', finally'); return 'ignored'; })
+             .then(function (v) { record(', ' + v); });
+         new Promise(function () { null.x; })
+             .finally(function () { record(', cleanup'); })
+             .then(undefined, function (e) { record(', ' + e); });
+         Promise.resolve(1)
+             .then(function () { undefinedFunction(); })
+             .catch(function (e) { record(', ' + e); });",
+    );
+    engine.run_pending(0).unwrap();
+    assert_eq!(
+        log(&mut engine),
+        Value::string(
+            ", cleanup, ReferenceError: undefinedFunction is not definedcaught bad, finally, \
+             TypeError: Cannot read properties of null (reading 'x'), recovered"
+        )
+    );
+}
+
+#[test]
+fn test_promise_all_mixes_values_and_promises() {
+    let mut engine = engine_with(
+        "let slow = new Promise(function (resolve) { setTimeout(function () { resolve('slow'); }, 10); });
+         Promise.all([1, Promise.resolve(2), slow, { then: function (r) { r(4); } }])
+             .then(function (values) { record('all ' + values + ' (' + values.length + ')'); });
+         Promise.all([]).then(function (values) { record('empty ' + values.length + '; '); });
+         Promise.all([slow, Promise.reject('no'), Promise.reject('later')])
+             .catch(function (e) { record('rejected ' + e + '; '); });",
+    );
+    engine.run_pending(0).unwrap();
+    assert_eq!(log(&mut engine), Value::string("empty 0; rejected no; "));
+    
+    assert!(!engine.run_pending(10).unwrap());
+    assert_eq!(log(&mut engine), Value::string("empty 0; rejected no; all 1,2,slow,4 (4)"));
+}
+
+#[test]
+fn test_unhandled_rejection_hook_fires_once() {
+    let reasons = Rc::new(RefCell::new(Vec::new()));
+    let mut engine = engine_with(
+        "Promise.reject('lost');
+         Promise.reject('kept').catch(function () {});
+         let handledLater = Promise.reject('handled later');
+         queueMicrotask(function () { handledLater.then(undefined, function () {}); });
+         new Promise(function (resolve, reject) { setTimeout(function () { reject('late'); }, 5); });",
+    );
+    let sink = reasons.clone();
+    engine.set_unhandled_rejection_handler(move |reason| sink.borrow_mut().push(reason));
+    
+    engine.run_pending(0).unwrap();
+    assert_eq!(*reasons.borrow(), vec![Value::string("lost")]);
+    
+    engine.run_pending(5).unwrap();
+    engine.run_pending(10).unwrap();
+    assert_eq!(*reasons.borrow(), vec![Value::string("lost"), Value::string("late")]);
+}
+
+#[test]
+fn test_constructor_errors_and_inspection() {
+    let mut engine = Engine::new_persistent();
+    let error = engine.execute("Promise(function () {})").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::NotConstructor);
+    assert!(error.to_string().contains("Promise constructor cannot be invoked without 'new'"), "{}", error);
+    let error = engine.execute("new Promise(1)").unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidArgument);
+    assert!(error.to_string().contains("Promise resolver 1 is not a function"), "{}", error);
+    
+    let value = engine.execute("Promise.resolve([1])").unwrap();
+    assert_eq!(value.inspect(), "Promise { [ 1 ] }");
+    let value = engine.execute("new Promise(function () {})").unwrap();
+    assert_eq!(value.inspect(), "Promise { <pending> }");
+    assert_eq!(engine.execute("'' + Promise.resolve(1)").unwrap(), Value::string("[object Promise]"));
+}
@@ -0,0 +1,155 @@
+//! Tests for property attributes and the `Object` global
+
+use v8::{ErrorCode, Value};
+
+mod common;
+
+use common::{eval, eval_error};
+
+fn json(source: &str) -> Value {
+    eval(&format!("JSON.stringify({})", source))
+}
+
+#[test]
+fn test_assigned_properties_have_every_attribute() {
+    assert_eq!(
+        json("Object.getOwnPropertyDescriptor({ a: 1 }, 'a')"),
+        Value::string(r#"{"value":1,"writable":true,"enumerable":true,"configurable":true}"#)
+    );
+    assert_eq!(
+        eval("let o = {}; o.x = 1; o.x = 2; delete o.x && !('x' in o)"),
+        Value::Boolean(true)
+    );
+    assert_eq!(eval("Object.getOwnPropertyDescriptor({}, 'missing')"), Value::Undefined);
+}
+
+#[test]
+fn test_define_property_defaults_to_no_attributes() {
+    assert_eq!(
+        json("Object.getOwnPropertyDescriptor(Object.defineProperty({}, 'x', { value: 1 }), 'x')"),
+        Value::string(r#"{"value":1,"writable":false,"enumerable":false,"configurable":false}"#)
+    );
+    // Missing fields keep the current values of an existing property
+    assert_eq!(
+        json("Object.getOwnPropertyDescriptor(Object.defineProperty({ x: 1 }, 'x', { enumerable: false }), 'x')"),
+        Value::string(r#"{"value":1,"writable":true,"enumerable":false,"configurable":true}"#)
+    );
+}
+
+#[test]
+fn test_writable() {
+    let setup = "let o = {}; Object.defineProperty(o, 'x', { value: 1, configurable: true });";
+    assert_eq!(eval(&format!("{} o.x = 2; o.x", setup)), Value::Number(1.0));
+    assert_eq!(
+        eval_error(&format!("{} (function () {{ 'use strict'; o.x = 2; }})()", setup), ErrorCode::ReadOnlyProperty),
+        "Runtime error: TypeError: Cannot assign to read only property 'x' of [object Object]"
+    );
+    assert_eq!(eval(&format!("{} o['x'] = 2; o.x += 1; o.x", setup)), Value::Number(1.0));
+    // Redefining still works while the property is configurable
+    assert_eq!(
+        eval(&format!("{} Object.defineProperty(o, 'x', {{ value: 3 }}); o.x", setup)),
+        Value::Number(3.0)
+    );
+    
+    // A read-only inherited property cannot be shadowed by assignment
+    assert_eq!(
+        eval("function P() {} Object.defineProperty(P.prototype, 'x', { value: 1 }); \
+              let p = new P(); p.x = 2; p.x + Object.keys(p).length"),
+        Value::Number(1.0)
+    );
+}
+
+#[test]
+fn test_enumerable() {
+    let setup = "let o = { a: 1 }; Object.defineProperty(o, 'hidden', { value: 2, writable: true }); o.b = 3;";
+    assert_eq!(json(&format!("(function () {{ {} return Object.keys(o); }})()", setup)), Value::string(r#"["a","b"]"#));
+    assert_eq!(
+        json(&format!("(function () {{ {} return Object.getOwnPropertyNames(o); }})()", setup)),
+        Value::string(r#"["a","hidden","b"]"#)
+    );
+    assert_eq!(json(&format!("(function () {{ {} return o; }})()", setup)), Value::string(r#"{"a":1,"b":3}"#));
+    assert_eq!(
+        json(&format!("(function () {{ {} return {{ ...o }}; }})()", setup)),
+        Value::string(r#"{"a":1,"b":3}"#)
+    );
+    // Hidden properties are still there to read, write and test for
+    assert_eq!(eval(&format!("{} o.hidden = 4; o.hidden", setup)), Value::Number(4.0));
+    assert_eq!(eval(&format!("{} 'hidden' in o && o.hasOwnProperty('hidden')", setup)), Value::Boolean(true));
+}
+
+#[test]
+fn test_configurable() {
+    let setup = "let o = {}; Object.defineProperty(o, 'x', { value: 1, writable: true, enumerable: true });";
+    assert_eq!(eval(&format!("{} delete o.x", setup)), Value::Boolean(false));
+    assert_eq!(eval(&format!("{} delete o['x']; o.x", setup)), Value::Number(1.0));
+    assert_eq!(
+        eval_error(&format!("{} (function () {{ 'use strict'; delete o.x; }})()", setup), ErrorCode::CannotDelete),
+        "Runtime error: TypeError: Cannot delete property 'x' of [object Object]"
+    );
+    
+    // Only a switch to read-only, or the same value again, is allowed
+    assert_eq!(
+        eval(&format!("{} Object.defineProperty(o, 'x', {{ value: 2, writable: false }}); \
+                       Object.defineProperty(o, 'x', {{ value: 2 }}); o.x", setup)),
+        Value::Number(2.0)
+    );
+    for change in ["{ configurable: true }", "{ enumerable: false }"] {
+        assert_eq!(
+            eval_error(&format!("{} Object.defineProperty(o, 'x', {})", setup, change), ErrorCode::ReadOnlyProperty),
+            "Runtime error: TypeError: Cannot redefine property: x",
+            "{}",
+            change
+        );
+    }
+    assert_eq!(
+        eval_error(&format!("{} Object.defineProperty(o, 'x', {{ writable: false }}); \
+                             Object.defineProperty(o, 'x', {{ value: 5 }})", setup), ErrorCode::ReadOnlyProperty),
+        "Runtime error: TypeError: Cannot redefine property: x"
+    );
+}
+
+#[test]
+fn test_array_and_string_properties() {
+    assert_eq!(json("Object.getOwnPropertyNames([1, , 3])"), Value::string(r#"["0","2","length"]"#));
+    assert_eq!(json("Object.keys('ab')"), Value::string(r#"["0","1"]"#));
+    assert_eq!(
+        json("Object.getOwnPropertyDescriptor([5], 'length')"),
+        Value::string(r#"{"value":1,"writable":true,"enumerable":false,"configurable":false}"#)
+    );
+    assert_eq!(
+        json("Object.getOwnPropertyDescriptor('ab', 1)"),
+        Value::string(r#"{"value":"b","writable":false,"enumerable":true,"configurable":false}"#)
+    );
+    // Elements have nowhere to keep other attributes
+    assert_eq!(eval("let a = [1]; Object.defineProperty(a, 1, { value: 2, writable: true, enumerable: true, configurable: true }); a[1]"), Value::Number(2.0));
+    assert_eq!(eval_error("Object.defineProperty([1], 1, { value: 2 })", ErrorCode::ReadOnlyProperty), "Runtime error: TypeError: Cannot redefine property: 1");
+}
+
+#[test]
+fn test_function_properties() {
+    assert_eq!(
+        json("(function () { function f() {} return Object.getOwnPropertyDescriptor(f, 'prototype'); })()"),
+        Value::string(r#"{"value":{},"writable":true,"enumerable":false,"configurable":false}"#)
+    );
+    assert_eq!(
+        eval("function f() {} Object.defineProperty(f, 'tag', { value: 'x' }); f.tag = 'y'; f.tag"),
+        Value::string("x")
+    );
+}
+
+#[test]
+fn test_invalid_arguments() {
+    assert_eq!(
+        eval_error("Object.defineProperty(1, 'x', { value: 1 })", ErrorCode::InvalidArgument),
+        "Runtime error: TypeError: Object.defineProperty called on non-object"
+    );
+    assert_eq!(
+        eval_error("Object.defineProperty({}, 'x', 1)", ErrorCode::InvalidArgument),
+        "Runtime error: TypeError: Property description must be an object: 1"
+    );
+    assert_eq!(
+        eval_error("Object.defineProperty({}, 'x', { get: function () { return 1; } })", ErrorCode::UnsupportedBuiltin),
+        "Runtime error: TypeError: Getters and setters are not supported in property descriptors"
+    );
+    assert_eq!(eval_error("Object.keys(null)", ErrorCode::NotObjectCoercible), "Runtime error: TypeError: Cannot convert undefined or null to object");
+}
@@ -0,0 +1,86 @@
+//! Tests for the `LdaNamed` property caches (`VMConfig::property_caches`)
+
+use v8::{Engine, PropertyCacheStats, Value, VMConfig, VM};
+
+fn cached_vm() -> VM {
+    VM::with_config(VMConfig { property_caches: true, ..VMConfig::default() })
+}
+
+/// Run `source` on `vm`, returning its value
+fn run(vm: &mut VM, source: &str) -> Value {
+    let function = Engine::new().compile_cached(source).unwrap();
+    vm.execute_shared(function).unwrap()
+}
+
+/// A loop reading `o.x` from objects in `layouts` layouts, with `x` at a
+/// different position in each
+fn reads(layouts: usize) -> String {
+    format!("
+        let items = [];
+        for (let i = 0; i < 20; i++) {{
+            let o = {{}};
+            for (let p = 0; p < i % {layouts}; p++) {{ o['pad' + p] = 0; }}
+            o.x = i;
+            items.push(o);
+        }}
+        let total = 0;
+        for (let j = 0; j < items.length; j++) {{ total = total + items[j].x; }}
+        total")
+}
+
+#[test]
+fn test_sites_go_from_monomorphic_to_megamorphic() {
+    let mut vm = cached_vm();
+    assert_eq!(run(&mut vm, &reads(1)), Value::Number(190.0));
+    let stats = vm.property_cache_stats().unwrap();
+    assert_eq!(stats.misses, 1);
+    assert_eq!(stats.hits, 19);
+    assert!(stats.monomorphic_sites >= 1);
+    assert_eq!(stats.polymorphic_sites + stats.megamorphic_sites, 0);
+    
+    vm.reset_property_caches();
+    assert_eq!(vm.property_cache_stats(), Some(PropertyCacheStats::default()));
+    assert_eq!(run(&mut vm, &reads(3)), Value::Number(190.0));
+    let stats = vm.property_cache_stats().unwrap();
+    assert_eq!((stats.misses, stats.hits), (3, 17));
+    assert_eq!(stats.polymorphic_sites, 1);
+    
+    vm.reset_property_caches();
+    assert_eq!(run(&mut vm, &reads(10)), Value::Number(190.0));
+    let stats = vm.property_cache_stats().unwrap();
+    assert_eq!(stats.megamorphic_sites, 1);
+    assert!(stats.megamorphic_reads > 0);
+}
+
+#[test]
+fn test_stale_positions_are_never_trusted() {
+    let source = "
+        function get(o) { return o.x; }
+        let o = { a: 1, x: 2 };
+        let first = get(o);
+        delete o.a;
+        let moved = get(o);
+        o.a = 3;
+        let proto = get(Object.create({ x: 4 }));
+        first + ',' + moved + ',' + proto + ',' + get({ x: 5 }) + ',' + get([1])";
+    let mut vm = cached_vm();
+    assert_eq!(run(&mut vm, source), Value::string("2,2,4,5,undefined"));
+    assert_eq!(run(&mut VM::new(), source), Value::string("2,2,4,5,undefined"));
+}
+
+#[test]
+fn test_caches_can_be_switched_off() {
+    let mut vm = VM::new();
+    assert!(vm.property_cache_stats().is_none());
+    run(&mut vm, &reads(1));
+    vm.reset_property_caches();
+    assert!(vm.property_cache_stats().is_none());
+    
+    vm.set_property_caches(true);
+    run(&mut vm, &reads(1));
+    assert!(vm.property_cache_stats().unwrap().hits > 0);
+    // The dictionary-mode path, with nothing recorded
+    vm.set_property_caches(false);
+    assert_eq!(run(&mut vm, &reads(4)), Value::Number(190.0));
+    assert!(vm.property_cache_stats().is_none());
+}
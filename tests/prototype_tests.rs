@@ -0,0 +1,153 @@
+//! Tests for prototype access and the `__proto__` policy
+
+use std::rc::Rc;
+
+use v8::vm::FunctionRef;
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::{eval, eval_error};
+
+fn eval_with_proto_setter(source: &str) -> Value {
+    Engine::builder().proto_setter(true).build().execute(source).unwrap()
+}
+
+/// Scripts that must behave the same whether or not `proto_setter` is on
+fn eval_both(source: &str) -> Value {
+    let value = eval(source);
+    assert_eq!(eval_with_proto_setter(source), value, "{}", source);
+    value
+}
+
+#[test]
+fn test_prototype_functions() {
+    assert_eq!(eval_both("let p = { greet: 'hi' }; let o = Object.create(p); o.greet"), Value::string("hi"));
+    assert_eq!(eval_both("let p = {}; Object.getPrototypeOf(Object.create(p)) === p"), Value::Boolean(true));
+    assert_eq!(eval_both("function F() {} Object.getPrototypeOf(new F()) === F.prototype"), Value::Boolean(true));
+    assert_eq!(eval_both("Object.getPrototypeOf(Object.create(null))"), Value::Null);
+    assert_eq!(eval_both("Object.getPrototypeOf({})"), Value::Null);
+    assert_eq!(
+        eval_both("let o = { a: 1 }; let r = Object.setPrototypeOf(o, { b: 2 }); r === o && o.a + o.b == 3"),
+        Value::Boolean(true)
+    );
+    assert_eq!(eval_both("let o = Object.create({ x: 1 }); Object.setPrototypeOf(o, null); o.x"), Value::Undefined);
+    assert_eq!(eval_both("Object.setPrototypeOf(1, {})"), Value::Number(1.0));
+    // Inherited properties are found but aren't own
+    assert_eq!(
+        eval_both("let o = Object.create({ x: 1 }); 'x' in o && !o.hasOwnProperty('x') && Object.keys(o).length == 0"),
+        Value::Boolean(true)
+    );
+}
+
+#[test]
+fn test_prototype_errors() {
+    assert_eq!(
+        eval_error("Object.create(1)", ErrorCode::InvalidPrototype),
+        "Runtime error: TypeError: Object prototype may only be an Object or null: 1"
+    );
+    assert_eq!(
+        eval_error("Object.setPrototypeOf({}, 'p')", ErrorCode::InvalidPrototype),
+        "Runtime error: TypeError: Object prototype may only be an Object or null: p"
+    );
+    assert_eq!(
+        eval_error("Object.setPrototypeOf(null, {})", ErrorCode::NotObjectCoercible),
+        "Runtime error: TypeError: Object.setPrototypeOf called on null or undefined"
+    );
+    assert_eq!(eval_error("Object.getPrototypeOf(undefined)", ErrorCode::NotObjectCoercible), "Runtime error: TypeError: Cannot convert undefined or null to object");
+    assert_eq!(
+        eval_error("let a = {}; let b = Object.create(a); Object.setPrototypeOf(a, b)", ErrorCode::InvalidPrototype),
+        "Runtime error: TypeError: Cyclic __proto__ value"
+    );
+    assert_eq!(eval_error("let a = {}; Object.setPrototypeOf(a, a)", ErrorCode::InvalidPrototype), "Runtime error: TypeError: Cyclic __proto__ value");
+}
+
+#[test]
+fn test_proto_is_an_ordinary_key_by_default() {
+    assert_eq!(
+        eval("let o = {}; o.__proto__ = { polluted: true }; o.polluted === undefined && Object.getPrototypeOf(o) === null"),
+        Value::Boolean(true)
+    );
+    assert_eq!(
+        eval("let o = {}; o.__proto__ = 1; JSON.stringify(Object.keys(o)) + o.__proto__"),
+        Value::string(r#"["__proto__"]1"#)
+    );
+    assert_eq!(eval("({}).__proto__"), Value::Undefined);
+}
+
+#[test]
+fn test_computed_proto_writes_stay_own_properties() {
+    let source = "let o = {}; let key = '__proto__'; o[key] = { polluted: true }; o['__proto__'].polluted = 1; \
+                  o.polluted === undefined && Object.getPrototypeOf(o) === null && o.hasOwnProperty('__proto__')";
+    assert_eq!(eval_both(source), Value::Boolean(true));
+    // An object literal key is an own property too
+    assert_eq!(
+        eval_both("let o = { __proto__: { polluted: true } }; o.polluted === undefined && Object.keys(o).length == 1"),
+        Value::Boolean(true)
+    );
+}
+
+#[test]
+fn test_json_parse_never_sets_prototypes() {
+    let source = r#"let o = JSON.parse('{"__proto__": {"isAdmin": true}, "name": "x"}');
+                    o.isAdmin === undefined && Object.getPrototypeOf(o) === null && JSON.stringify(Object.keys(o)) == '["__proto__","name"]'"#;
+    assert_eq!(eval_both(source), Value::Boolean(true));
+    // Nor does copying the parsed data around
+    let source = r#"let o = JSON.parse('{"__proto__": {"isAdmin": true}}'); let copy = { ...o };
+                    let target = {}; target['__proto__'] = o['__proto__']; copy.isAdmin === undefined && target.isAdmin === undefined"#;
+    assert_eq!(eval_both(source), Value::Boolean(true));
+}
+
+#[test]
+fn test_proto_setter_option() {
+    assert_eq!(
+        eval_with_proto_setter("let p = { greet: 'hi' }; let o = {}; o.__proto__ = p; o.greet + (o.__proto__ === p) + Object.keys(o).length"),
+        Value::string("hitrue0")
+    );
+    assert_eq!(eval_with_proto_setter("let o = Object.create({}); o.__proto__ = null; Object.getPrototypeOf(o)"), Value::Null);
+    assert_eq!(eval_with_proto_setter("({}).__proto__"), Value::Null);
+    // Other values are ignored
+    assert_eq!(
+        eval_with_proto_setter("let p = {}; let o = Object.create(p); o.__proto__ = 5; Object.getPrototypeOf(o) === p"),
+        Value::Boolean(true)
+    );
+    // An own `__proto__` property hides the accessor
+    assert_eq!(
+        eval_with_proto_setter("let o = JSON.parse('{\"__proto__\": 1}'); o.__proto__ = { x: 1 }; o.x === undefined && o.__proto__.x == 1"),
+        Value::Boolean(true)
+    );
+    let error = Engine::builder().proto_setter(true).build()
+        .execute("let a = {}; let b = Object.create(a); a.__proto__ = b").unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: TypeError: Cyclic __proto__ value");
+}
+
+#[test]
+fn test_constructor_and_prototype_are_dropped() {
+    // `constructor` on the prototype refers back to the function weakly
+    let mut engine = Engine::new();
+    // The function is bound only in the arrow's scope, which it can't see
+    let source = "(F => {
+        let o = new F();
+        F.prototype.y = 2;
+        return [F, o.constructor === F, o.y];
+    })(function () { this.x = 1; })";
+    let Value::Object(result) = engine.execute(source).unwrap() else { panic!("expected an array") };
+    let elements = result.array_elements().unwrap().borrow().clone();
+    assert_eq!(elements[1], Some(Value::Boolean(true)));
+    assert_eq!(elements[2], Some(Value::Number(2.0)));
+    let Some(Value::Function(FunctionRef::Closure(constructor))) = &elements[0] else { panic!("expected a closure") };
+    let constructor = Rc::downgrade(constructor);
+    drop((elements, result, engine));
+    assert!(constructor.upgrade().is_none());
+}
+
+#[test]
+fn test_prototype_constructor_property() {
+    assert_eq!(eval("function F() {} F.prototype.hasOwnProperty('constructor')"), Value::Boolean(true));
+    assert_eq!(eval("function F() {} Object.keys(F.prototype).length"), Value::Number(0.0));
+    assert_eq!(eval("function F() {} Object.getOwnPropertyNames(F.prototype)[0]"), Value::string("constructor"));
+    assert_eq!(eval("function F() {} F.prototype.constructor = 1; F.prototype.constructor"), Value::Number(1.0));
+    assert_eq!(eval("function F() {} F.prototype.constructor = 1; Object.keys(F.prototype).length"), Value::Number(0.0));
+    assert_eq!(eval("function F() {} delete F.prototype.constructor; new F().constructor === F"), Value::Boolean(false));
+    assert_eq!(eval("class A {} new A().constructor === A"), Value::Boolean(true));
+}
@@ -0,0 +1,91 @@
+//! Tests for deep recursion and tail calls
+
+use v8::{Engine, RuntimeErrorKind, VMConfig, Value};
+
+fn run(engine: &mut Engine, source: &str) -> v8::Result<Value> {
+    engine.execute_detailed(source).result
+}
+
+const TAIL_SUM: &str = "\
+function sum(n, acc) {
+    if (n === 0) return acc;
+    return sum(n - 1, acc + n);
+}
+";
+
+#[test]
+fn test_tail_recursion_runs_in_constant_frame_space() {
+    let mut engine = Engine::new();
+    let source = format!("{}sum(1000000, 0)", TAIL_SUM);
+    assert_eq!(run(&mut engine, &source).unwrap(), Value::Number(500000500000.0));
+    
+    // Method calls in tail position count too, even under a tiny limit
+    let config = VMConfig { max_call_depth: 10, ..VMConfig::default() };
+    let mut engine = Engine::builder().vm_config(config).build();
+    let source = "let o = { down: function (n) { if (n === 0) return 'done'; return this.down(n - 1); } }; o.down(1000)";
+    assert_eq!(run(&mut engine, source).unwrap(), Value::string("done"));
+}
+
+#[test]
+fn test_call_depth_limit() {
+    const DEPTH: &str = "function depth(n) { if (n === 0) return 0; return 1 + depth(n - 1); }";
+    let config = VMConfig { max_call_depth: 2000, ..VMConfig::default() };
+    let mut engine = Engine::builder().vm_config(config).build();
+    
+    // The main program takes one frame, and depth(n) takes n + 1
+    let source = format!("{} depth(1998)", DEPTH);
+    assert_eq!(run(&mut engine, &source).unwrap(), Value::Number(1998.0));
+    
+    let source = format!("{} depth(1999)", DEPTH);
+    let error = run(&mut engine, &source).unwrap_err();
+    assert!(matches!(error.kind(), Some(RuntimeErrorKind::StackOverflow { .. })), "{:?}", error);
+    
+    // The engine is still usable afterwards
+    assert_eq!(run(&mut engine, "1 + 1").unwrap(), Value::Number(2.0));
+}
+
+#[test]
+fn test_default_depth_allows_deep_recursion() {
+    let mut engine = Engine::new();
+    let source = "function depth(n) { if (n === 0) return 0; return 1 + depth(n - 1); } depth(9000)";
+    assert_eq!(run(&mut engine, source).unwrap(), Value::Number(9000.0));
+    
+    // Operands pending in every frame don't run out before the depth does
+    let source = "function sums(n) { if (n === 0) return 0; return n + (n + (n + sums(n - 1))); } sums(9000)";
+    assert_eq!(run(&mut engine, source).unwrap(), Value::Number(121513500.0));
+}
+
+#[test]
+fn test_constructors_count_against_the_depth_limit() {
+    const NESTED: &str = "function F(n) { if (n > 0) this.inner = new F(n - 1); }";
+    let config = VMConfig { max_call_depth: 50, ..VMConfig::default() };
+    let mut engine = Engine::builder().vm_config(config).build();
+    let source = format!("{} new F(48).inner instanceof F", NESTED);
+    assert_eq!(run(&mut engine, &source).unwrap(), Value::Boolean(true));
+    
+    let source = format!("{} new F(49)", NESTED);
+    let error = run(&mut engine, &source).unwrap_err();
+    assert!(matches!(error.kind(), Some(RuntimeErrorKind::StackOverflow { .. })), "{:?}", error);
+    
+    // With the default limit, as deep as plain calls go
+    let source = format!("{} new F(9000) instanceof F", NESTED);
+    assert_eq!(run(&mut Engine::new(), &source).unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn test_dropping_long_object_chains() {
+    // Each chain is freed when the engine drops it, without a native
+    // stack frame per level
+    let source = "let h = null; for (let i = 0; i < 100000; i++) { h = { n: h }; } 1";
+    assert_eq!(run(&mut Engine::new(), source).unwrap(), Value::Number(1.0));
+    
+    let source = "let a = []; for (let i = 0; i < 100000; i++) { a = [a]; } 1";
+    assert_eq!(run(&mut Engine::new(), source).unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_calls_in_constructors_are_not_tail_calls() {
+    // A constructor's result may be replaced by `this`, so its frame stays
+    let source = "function one() { return 1; } function F() { return one(); } new F() instanceof F";
+    assert_eq!(run(&mut Engine::new(), source).unwrap(), Value::Boolean(true));
+}
@@ -0,0 +1,172 @@
+//! Tests for structured runtime errors
+//!
+//! Hosts should be able to tell errors apart by matching on
+//! `RuntimeErrorKind`, never by parsing messages.
+
+use std::error::Error as _;
+
+use v8::{Engine, Error, ErrorCode, RuntimeErrorKind, Value};
+
+fn run(source: &str) -> Error {
+    Engine::new().execute_detailed(source).result.unwrap_err()
+}
+
+#[test]
+fn test_unknown_global() {
+    let error = run("let x = 1; x + missing");
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::UnknownGlobal { name: "missing".to_string(), suggestion: None }));
+    assert_eq!(error.to_string(), "Runtime error: ReferenceError: missing is not defined");
+}
+
+#[test]
+fn test_misspelled_global_suggests_a_builtin() {
+    let error = run("let x = 1; Mth.floor(x)");
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::UnknownGlobal {
+        name: "Mth".to_string(),
+        suggestion: Some("Math".into()),
+    }));
+    assert_eq!(error.to_string(), "Runtime error: ReferenceError: Mth is not defined (did you mean 'Math'?)");
+    
+    // Host globals are candidates too
+    let mut engine = Engine::new();
+    engine.register_function("fetchUser", |_| Ok(Value::Undefined));
+    let error = engine.execute("fetchUsr(1)").unwrap_err();
+    assert!(matches!(error.kind(), Some(RuntimeErrorKind::UnknownGlobal { suggestion: Some(name), .. }) if &**name == "fetchUser"));
+}
+
+#[test]
+fn test_misspelled_method_suggests_an_own_key() {
+    let error = run("let o = { reset() {}, count: 0 }; o.rest()");
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::NotCallable {
+        callee: "o.rest".to_string(),
+        type_of: "undefined".to_string(),
+        suggestion: Some("reset".into()),
+    }));
+    assert_eq!(error.to_string(), "Runtime error: TypeError: o.rest is not a function (did you mean 'reset'?)");
+    
+    // Nothing close enough, or a callee that isn't a missing method
+    for source in ["let o = { reset() {} }; o.launch()", "let o = { rest: 1 }; o.rest()", "let rest = 1; rest()"] {
+        assert!(matches!(run(source).kind(), Some(RuntimeErrorKind::NotCallable { suggestion: None, .. })), "{}", source);
+    }
+}
+
+#[test]
+fn test_not_callable() {
+    let error = run("let n = 5; n()");
+    match error.kind() {
+        Some(RuntimeErrorKind::NotCallable { type_of, .. }) => assert_eq!(type_of, "number"),
+        other => panic!("expected NotCallable, got {:?}", other),
+    }
+    
+    let error = run("let o = {}; o.method()");
+    assert!(matches!(
+        error.kind(),
+        Some(RuntimeErrorKind::NotCallable { type_of, .. }) if type_of == "undefined"
+    ));
+}
+
+#[test]
+fn test_not_callable_names_the_callee() {
+    let message = |source: &str| run(source).to_string();
+    assert_eq!(message("let n = 5; n()"), "Runtime error: TypeError: n is not a function");
+    assert_eq!(message("undefined()"), "Runtime error: TypeError: undefined is not a function");
+    assert_eq!(message("let s = 'abc'; s.foo(1)"), "Runtime error: TypeError: s.foo is not a function");
+    assert_eq!(message("let o = { a: [1] }; o.a[0]()"), "Runtime error: TypeError: o.a[0] is not a function");
+    assert_eq!(message("function f() { return 1; } f()()"), "Runtime error: TypeError: f(...) is not a function");
+    
+    // Callees without a simple name fall back to their value
+    assert_eq!(message("(1 + 2)()"), "Runtime error: TypeError: 3 is not a function");
+    
+    // The error points at the call
+    let error = run("let x = 1;\nx();");
+    assert_eq!(error.span().map(|span| span.line), Some(2));
+}
+
+#[test]
+fn test_not_constructor_names_the_callee() {
+    let message = |source: &str| run(source).to_string();
+    assert_eq!(message("let n = 5; new n()"), "Runtime error: TypeError: n is not a constructor");
+    assert_eq!(message("let o = { make: () => 1 }; new o.make(1)"), "Runtime error: TypeError: o.make is not a constructor");
+    assert_eq!(message("new (1 + 2)()"), "Runtime error: TypeError: 3 is not a constructor");
+    
+    let error = run("let f = function* () {};
+new f();");
+    assert_eq!(error.code(), ErrorCode::NotConstructor);
+    assert_eq!(error.span().map(|span| (span.line, span.column)), Some((2, 1)));
+}
+
+#[test]
+fn test_nullish_property_access() {
+    let error = run("let o = null;\no.foo");
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::NullishPropertyAccess {
+        base: "null".to_string(),
+        property: "foo".to_string(),
+        is_write: false,
+    }));
+    assert_eq!(error.to_string(), "Runtime error: TypeError: Cannot read properties of null (reading 'foo')");
+    assert_eq!(error.span().map(|span| span.line), Some(2));
+    
+    let message = |source: &str| run(source).to_string();
+    assert_eq!(message("undefined.bar"), "Runtime error: TypeError: Cannot read properties of undefined (reading 'bar')");
+    assert_eq!(message("let o = null; o.foo = 1"), "Runtime error: TypeError: Cannot set properties of null (setting 'foo')");
+    assert_eq!(message("let u; u.bar = 1"), "Runtime error: TypeError: Cannot set properties of undefined (setting 'bar')");
+    
+    // Computed keys are named by their string form
+    assert_eq!(message("let o = null; o[1 + 1]"), "Runtime error: TypeError: Cannot read properties of null (reading '2')");
+    assert_eq!(message("let u; let k = 'k'; u[k + 1] = 0"), "Runtime error: TypeError: Cannot set properties of undefined (setting 'k1')");
+}
+
+#[test]
+fn test_stack_overflow() {
+    // Not a tail call, which would run in constant frame space
+    let error = run("function f() { return 1 + f(); } f()");
+    match error.kind() {
+        Some(RuntimeErrorKind::StackOverflow { depth }) => assert!(*depth > 0),
+        other => panic!("expected StackOverflow, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_panicking_host_function() {
+    let mut engine = Engine::new_persistent();
+    engine.register_function("explode", |cx| panic!("cannot handle {}", cx.arg(0)));
+    engine.register_function("throwNumber", |_| std::panic::panic_any(7));
+    
+    let error = engine.execute_detailed("kept = 1; function f(x) { return explode(x) + 1; } f('this')").result.unwrap_err();
+    assert_eq!(error.kind(), Some(&RuntimeErrorKind::NativeFunctionPanicked {
+        name: "explode".to_string(),
+        message: Some("cannot handle this".to_string()),
+    }));
+    assert_eq!(error.code(), ErrorCode::NativeFunctionPanicked);
+    assert_eq!(error.to_string(), "Runtime error: Error: explode panicked: cannot handle this");
+    
+    let error = engine.execute_detailed("throwNumber()").result.unwrap_err();
+    assert_eq!(error.to_string(), "Runtime error: Error: throwNumber panicked");
+    
+    // The engine carries on, with what ran before the panic kept
+    assert_eq!(engine.execute_detailed("kept + [1, 2].length").result.unwrap(), Value::Number(3.0));
+}
+
+#[test]
+fn test_other_errors_are_custom() {
+    let error = run("for (let x of 5) {}");
+    assert!(matches!(error.kind(), Some(RuntimeErrorKind::Custom { code: ErrorCode::NotIterable, .. })));
+    
+    // Only runtime errors have a kind
+    assert!(run("let = 1").kind().is_none());
+}
+
+#[test]
+fn test_context_preserves_the_source() {
+    let io = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+    let error = Error::from(io).context("Cannot read 'missing.js'");
+    
+    assert_eq!(error.to_string(), "Cannot read 'missing.js'");
+    let source = error.source().expect("context keeps its cause");
+    assert_eq!(source.to_string(), "IO error: no such file");
+    assert!(source.source().is_some(), "the io::Error itself is chained too");
+    
+    // Kinds and spans are visible through context
+    let error = run("missing").context("While running the script");
+    assert!(matches!(error.kind(), Some(RuntimeErrorKind::UnknownGlobal { .. })));
+}
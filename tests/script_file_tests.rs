@@ -0,0 +1,46 @@
+//! Tests for running script files
+//!
+//! Scripts meant to be executed directly start with a `#!` line and may
+//! carry a byte order mark from the editor that saved them.
+
+use std::fs;
+use std::path::PathBuf;
+
+use v8::{Engine, Value};
+
+/// Write `contents` to a fresh file in the temp directory
+fn script(name: &str, contents: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("v8-script-{}-{}.js", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn test_shebang_script() {
+    let path = script("shebang", b"#!/usr/bin/env v8\nlet x = 20;\nx + 22\n");
+    let result = Engine::new().execute_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(result.unwrap(), Value::Number(42.0));
+}
+
+#[test]
+fn test_bom_prefixed_script() {
+    let path = script("bom", "\u{FEFF}#!/usr/bin/env v8\n'ok'".as_bytes());
+    let result = Engine::new().execute_file(&path);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(result.unwrap(), Value::string("ok"));
+}
+
+#[test]
+fn test_line_numbers_count_the_shebang() {
+    let path = script("lines", b"#!/usr/bin/env v8\nlet = 1;\n");
+    let error = Engine::new().execute_file(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(error.span().map(|span| span.line), Some(2));
+}
+
+#[test]
+fn test_missing_file() {
+    let error = Engine::new().execute_file("/nonexistent/script.js").unwrap_err();
+    assert_eq!(error.to_string(), "Cannot read '/nonexistent/script.js'");
+}
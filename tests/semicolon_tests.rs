@@ -0,0 +1,55 @@
+//! Tests for automatic semicolon insertion
+//!
+//! A statement may end without a `;` before a `}`, at the end of input, or
+//! before a token on a new line. The fixtures are one program written in
+//! both styles.
+
+use std::fs;
+
+use v8::{Engine, ErrorCode, Value};
+
+fn run_fixture(name: &str) -> Value {
+    let source = fs::read_to_string(format!("tests/fixtures/{}", name)).unwrap();
+    Engine::new().execute(&source).unwrap_or_else(|error| panic!("{}: {}", name, error))
+}
+
+#[test]
+fn test_semicolon_free_fixture_matches_its_twin() {
+    let without = run_fixture("semicolon_free.js");
+    let with = run_fixture("semicolons.js");
+    assert_eq!(without, with);
+    assert_eq!(without, Value::string("-1 0 1 3 6 a b yes else on its own line undefined 3 1,2 2 x y 3 "));
+}
+
+#[test]
+fn test_embedded_bodies_end_at_line_breaks() {
+    let mut engine = Engine::new();
+    let cases = [
+        ("let y\nif (false) y = 1\nelse y = 2\ny", Value::Number(2.0)),
+        ("let i = 0\nwhile (i < 5) i++\ni", Value::Number(5.0)),
+        ("let s = 0\nfor (let j = 1; j <= 3; j++) s += j\ns", Value::Number(6.0)),
+        ("let a = 1\nif (a) { a = 2 } else { a = 3 }\na", Value::Number(2.0)),
+        ("function f(x) { if (x) return 'a'\n else return 'b' }\nf(0)", Value::string("b")),
+        // A line break does not end a statement the next line continues
+        ("let t = 1\n+ 2\nt", Value::Number(3.0)),
+    ];
+    for (source, expected) in cases {
+        assert_eq!(engine.execute(source).unwrap(), expected, "{:?}", source);
+    }
+}
+
+#[test]
+fn test_restricted_productions() {
+    let mut engine = Engine::new();
+    // `return` and postfix `++` do not reach across a line break
+    assert_eq!(engine.execute("function f() {\n  return\n  1\n}\nf()").unwrap(), Value::Undefined);
+    assert_eq!(engine.execute("let a = 1\nlet b = a\na++\nb").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_same_line_statements_need_a_semicolon() {
+    for source in ["let a = 1 let b = 2", "if (true) x = 1 else x = 2", "let i = 0; while (i < 1) i++ i"] {
+        let error = Engine::new().execute(source).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::MissingSemicolon, "{}: {}", source, error);
+    }
+}
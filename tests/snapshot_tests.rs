@@ -0,0 +1,121 @@
+//! Tests for `Snapshot`: VMs restored from one behave like VMs that built
+//! their globals, and the embedded snapshot matches the built-ins
+
+use std::error::Error as _;
+use std::rc::Rc;
+use v8::vm::{FunctionRef, HostFunction};
+use v8::{Engine, Error, ErrorCode, GlobalSlot, Snapshot, Value, VMConfig, VM};
+
+/// A VM whose globals are copied from a fresh snapshot of a new VM
+fn restored_vm() -> VM {
+    let snapshot = VM::new().snapshot().unwrap();
+    let snapshot = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+    VM::with_snapshot(VMConfig::default(), &snapshot)
+}
+
+/// The error with its cause, if it has one
+fn describe(error: &Error) -> String {
+    match error.source() {
+        Some(source) => format!("{}: {}", error, source),
+        None => error.to_string(),
+    }
+}
+
+fn eval(vm: &mut VM, source: &str) -> Value {
+    let function = Engine::new().compile_cached(source).unwrap();
+    vm.execute_shared(function).unwrap()
+}
+
+#[test]
+fn test_embedded_snapshot_is_current() {
+    let bytes = VM::new().snapshot().unwrap().to_bytes();
+    if std::env::var_os("V8_UPDATE_SNAPSHOT").is_some() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/vm/snapshot.bin");
+        std::fs::write(path, &bytes).unwrap();
+        return;
+    }
+    assert!(
+        Snapshot::standard_bytes() == bytes,
+        "src/vm/snapshot.bin is stale; regenerate it with V8_UPDATE_SNAPSHOT=1 cargo test --test snapshot_tests"
+    );
+    Snapshot::standard().unwrap();
+}
+
+#[test]
+fn test_same_globals_give_same_bytes() {
+    assert_eq!(VM::new().snapshot().unwrap().to_bytes(), restored_vm().snapshot().unwrap().to_bytes());
+}
+
+#[test]
+fn test_restored_builtins_work() {
+    let mut vm = restored_vm();
+    assert_eq!(eval(&mut vm, "parseInt('7') + Number.isInteger(5)"), Value::Number(8.0));
+    assert_eq!(eval(&mut vm, "JSON.stringify(Object.keys({ a: 1, b: 2 }))"), Value::string("[\"a\",\"b\"]"));
+    assert_eq!(eval(&mut vm, "typeof Promise.resolve(1)"), Value::string("object"));
+    assert_eq!(eval(&mut vm, "isNaN(NaN) && typeof console.log === 'function'"), Value::Boolean(true));
+    
+    // Attributes survive: constants stay read-only, and so do the globals
+    assert_eq!(eval(&mut vm, "Number.MAX_SAFE_INTEGER = 1; Number.MAX_SAFE_INTEGER"), Value::Number(9007199254740991.0));
+    assert_eq!(eval(&mut vm, "Math = 1; typeof Math"), Value::string("object"));
+}
+
+#[test]
+fn test_restored_vms_do_not_share_objects() {
+    let snapshot = VM::new().snapshot().unwrap();
+    let mut first = VM::with_snapshot(VMConfig::default(), &snapshot);
+    let mut second = VM::with_snapshot(VMConfig::default(), &snapshot);
+    eval(&mut first, "Math.answer = 42");
+    assert_eq!(eval(&mut second, "Math.answer"), Value::Undefined);
+}
+
+#[test]
+fn test_engine_from_snapshot() {
+    let mut engine = Engine::from_snapshot().unwrap();
+    assert_eq!(engine.execute("parseFloat('2.5') * 2").unwrap(), Value::Number(5.0));
+    
+    let mut engine = Engine::builder().snapshot(Snapshot::standard().unwrap()).writable_builtins(true).build();
+    assert_eq!(engine.execute("console = 1; console").unwrap(), Value::Number(1.0));
+}
+
+#[test]
+fn test_invalid_bytes_are_rejected() {
+    let bytes = VM::new().snapshot().unwrap().to_bytes();
+    for (bytes, message) in [
+        (&b"not a snapshot"[..], "not a global environment snapshot"),
+        (&bytes[..bytes.len() - 1], "truncated at byte"),
+        (&[&bytes[..], &[0]].concat()[..], "1 bytes left over"),
+    ] {
+        let error = describe(&Snapshot::from_bytes(bytes).unwrap_err());
+        assert!(error.contains(message), "{}", error);
+    }
+    
+    // Natives are linked by name, so a renamed one is caught
+    let mut renamed = bytes.clone();
+    let last = renamed.windows(10).rposition(|window| window == b"parseFloat").unwrap();
+    renamed[last..last + 10].copy_from_slice(b"parseFlaot");
+    let error = describe(&Snapshot::from_bytes(&renamed).unwrap_err());
+    assert!(error.contains("no native is registered as 'parseFlaot'"), "{}", error);
+}
+
+#[test]
+fn test_script_globals_cannot_be_captured() {
+    let mut vm = VM::new();
+    let function = HostFunction::new("fetchUser".to_string(), |_| Ok(Value::Null));
+    vm.define_global("fetchUser".to_string(), GlobalSlot::new(Value::Function(FunctionRef::Host(Rc::new(function)))));
+    let error = vm.snapshot().unwrap_err();
+    assert_eq!(error.code(), ErrorCode::SnapshotCapture);
+    assert!(error.to_string().contains("Cannot snapshot global 'fetchUser': it holds a host function"), "{}", error);
+    
+    let mut vm = VM::new();
+    eval(&mut vm, "counter = function () { return 1; }");
+    let error = vm.snapshot().unwrap_err();
+    assert_eq!(error.code(), ErrorCode::SnapshotCapture);
+    assert!(error.to_string().contains("'counter': it holds a script function"), "{}", error);
+    
+    // Plain data is fine, and comes back
+    let mut vm = VM::new();
+    eval(&mut vm, "settings = { depth: 2, name: 'x', nested: { on: true } }");
+    let snapshot = vm.snapshot().unwrap();
+    let mut restored = VM::with_snapshot(VMConfig::default(), &snapshot);
+    assert_eq!(eval(&mut restored, "settings.name + settings.depth + settings.nested.on"), Value::string("x2true"));
+}
@@ -0,0 +1,62 @@
+//! Tests for the string case methods and the `CaseMapper` behind them
+
+use v8::{CaseMapper, Engine, VMConfig, Value};
+
+mod common;
+
+use common::eval;
+
+fn eval_ascii(source: &str) -> Value {
+    let config = VMConfig { case_mapper: CaseMapper::Ascii, ..VMConfig::default() };
+    Engine::builder().vm_config(config).build().execute(source).unwrap()
+}
+
+#[test]
+fn test_sharp_s_upper_cases_to_two_letters() {
+    assert_eq!(eval("'straße'.toUpperCase()"), Value::string("STRASSE"));
+    assert_eq!(eval("'ß'.toUpperCase().length"), Value::Number(2.0));
+    // There is no way back
+    assert_eq!(eval("'ß'.toUpperCase().toLowerCase()"), Value::string("ss"));
+}
+
+#[test]
+fn test_length_changing_mappings_show_in_length() {
+    // İ lower-cases to i and a combining dot above; ŉ upper-cases to ʼN;
+    // ﬃ upper-cases to FFI
+    assert_eq!(eval("'İ'.toLowerCase()"), Value::string("i\u{307}"));
+    assert_eq!(eval("'İ'.toLowerCase().length"), Value::Number(2.0));
+    assert_eq!(eval("'ŉ'.toUpperCase().length"), Value::Number(2.0));
+    assert_eq!(eval("'oﬃce'.toUpperCase()"), Value::string("OFFICE"));
+    assert_eq!(eval("'oﬃce'.toUpperCase().length"), Value::Number(6.0));
+    // Outside the basic plane, one letter stays two code units
+    assert_eq!(eval("'𐐨'.toUpperCase()"), Value::string("𐐀"));
+    assert_eq!(eval("'𐐨'.toUpperCase().length"), Value::Number(2.0));
+}
+
+#[test]
+fn test_turkish_rules_are_not_applied() {
+    // Turkish maps i to İ and I to ı; the default maps them as English does
+    assert_eq!(eval("'i'.toUpperCase()"), Value::string("I"));
+    assert_eq!(eval("'I'.toLowerCase()"), Value::string("i"));
+    assert_eq!(eval("'ı'.toUpperCase()"), Value::string("I"));
+    assert_eq!(eval("'istanbul'.toLocaleUpperCase('tr')"), Value::string("ISTANBUL"));
+    assert_eq!(eval("'ISPARTA'.toLocaleLowerCase('tr-TR')"), Value::string("isparta"));
+    assert_eq!(eval("'İ'.toLocaleLowerCase('tr')"), Value::string("i\u{307}"));
+}
+
+#[test]
+fn test_final_sigma_and_plain_cases() {
+    assert_eq!(eval("'ΟΔΟΣ'.toLowerCase()"), Value::string("οδος"));
+    assert_eq!(eval("'Hello, World!'.toUpperCase()"), Value::string("HELLO, WORLD!"));
+    assert_eq!(eval("'Hello, World!'.toLowerCase()"), Value::string("hello, world!"));
+    assert_eq!(eval("''.toUpperCase()"), Value::string(""));
+    assert_eq!(eval("'abc'.toLocaleUpperCase()"), Value::string("ABC"));
+}
+
+#[test]
+fn test_ascii_mode_maps_only_ascii_letters() {
+    assert_eq!(eval_ascii("'straße'.toUpperCase()"), Value::string("STRAßE"));
+    assert_eq!(eval_ascii("'ÉCOLE École'.toLowerCase()"), Value::string("École École"));
+    assert_eq!(eval_ascii("'İI'.toLowerCase().length"), Value::Number(2.0));
+    assert_eq!(eval_ascii("'ß'.toLocaleUpperCase('de')"), Value::string("ß"));
+}
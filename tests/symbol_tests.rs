@@ -0,0 +1,132 @@
+//! Tests for symbols: identity, symbol-keyed properties, and
+//! `Symbol.iterator` making user objects iterable
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use v8::{Engine, ErrorCode, Value};
+
+mod common;
+
+use common::eval;
+
+/// Run `source`; the console lines it logged
+fn console_lines(source: &str) -> Vec<String> {
+    let output = Rc::new(RefCell::new(Vec::new()));
+    let sink = output.clone();
+    let mut engine = Engine::new();
+    engine.set_console_handler(move |line| sink.borrow_mut().push(line.to_string()));
+    engine.execute(source).unwrap();
+    let lines = output.borrow().clone();
+    lines
+}
+
+fn error_code(source: &str) -> ErrorCode {
+    Engine::new().execute(source).unwrap_err().code()
+}
+
+/// An object counting from 1 to 3 through `[Symbol.iterator]`, logging
+/// when it is closed
+const COUNTER: &str = r#"
+    let counter = {};
+    counter[Symbol.iterator] = function () {
+        let n = 0;
+        return {
+            next: function () { n++; return { value: n, done: n > 3 }; },
+            return: function () { console.log('closed at ' + n); return {}; },
+        };
+    };
+"#;
+
+#[test]
+fn test_symbols_are_equal_only_to_themselves() {
+    assert_eq!(eval("Symbol('a') === Symbol('a')"), Value::Boolean(false));
+    assert_eq!(eval("Symbol('a') == Symbol('a')"), Value::Boolean(false));
+    assert_eq!(eval("let s = Symbol('a'); s === s"), Value::Boolean(true));
+    assert_eq!(eval("typeof Symbol('a')"), Value::string("symbol"));
+    assert_eq!(eval("typeof Symbol.iterator"), Value::string("symbol"));
+    assert_eq!(eval("Symbol('desc')").to_string(), "Symbol(desc)");
+    assert_eq!(eval("Symbol()").to_string(), "Symbol()");
+}
+
+#[test]
+fn test_symbol_registry() {
+    assert_eq!(eval("Symbol.for('app') === Symbol.for('app')"), Value::Boolean(true));
+    assert_eq!(eval("Symbol.for('app') === Symbol('app')"), Value::Boolean(false));
+    assert_eq!(eval("Symbol.keyFor(Symbol.for('app'))"), Value::string("app"));
+    assert_eq!(eval("Symbol.keyFor(Symbol('app'))"), Value::Undefined);
+    assert_eq!(eval("Symbol.keyFor(Symbol.iterator)"), Value::Undefined);
+}
+
+#[test]
+fn test_symbol_keyed_properties() {
+    assert_eq!(eval("let s = Symbol('k'); let o = {}; o[s] = 5; o[s]"), Value::Number(5.0));
+    // A symbol never reaches the property named by its description
+    assert_eq!(eval("let s = Symbol('k'); let o = { k: 1 }; o[s] = 5; o.k"), Value::Number(1.0));
+    assert_eq!(eval("let s = Symbol('k'); let o = {}; o[s] = 5; s in o"), Value::Boolean(true));
+    assert_eq!(eval("let s = Symbol('k'); let o = {}; o[s] = 5; delete o[s]; s in o"), Value::Boolean(false));
+    assert_eq!(eval("let s = Symbol('k'); let o = {}; o[s] = 5; o.hasOwnProperty(s)"), Value::Boolean(true));
+    assert_eq!(
+        eval("let s = Symbol(); let o = {}; Object.defineProperty(o, s, { value: 7 }); o[s] = 8; o[s]"),
+        Value::Number(7.0),
+    );
+}
+
+#[test]
+fn test_symbol_keyed_properties_are_not_enumerated() {
+    let source = "
+        let o = { a: 1 };
+        o[Symbol('hidden')] = 2;
+        console.log(JSON.stringify(Object.keys(o)));
+        console.log(JSON.stringify(Object.getOwnPropertyNames(o)));
+        console.log(JSON.stringify(o));
+        console.log(JSON.stringify({ ...o }));
+        console.log(JSON.stringify({ s: Symbol('x') }));
+    ";
+    assert_eq!(console_lines(source), ["[\"a\"]", "[\"a\"]", "{\"a\":1}", "{\"a\":1}", "{}"]);
+}
+
+#[test]
+fn test_custom_iterable_in_for_of_and_array_from() {
+    let source = format!("{}
+        let seen = [];
+        for (const n of counter) {{ seen.push(n); }}
+        console.log(JSON.stringify(seen));
+        console.log(JSON.stringify(Array.from(counter)));
+    ", COUNTER);
+    assert_eq!(console_lines(&source), ["[1,2,3]", "[1,2,3]"]);
+}
+
+#[test]
+fn test_leaving_a_custom_iterable_early_closes_it() {
+    let source = format!("{}
+        for (const n of counter) {{ if (n === 2) {{ break; }} }}
+        for (const n of counter) {{}}
+        console.log('done');
+    ", COUNTER);
+    assert_eq!(console_lines(&source), ["closed at 2", "done"]);
+}
+
+#[test]
+fn test_symbol_iterator_overrides_built_in_iteration() {
+    let source = "
+        let array = [1, 2];
+        array[Symbol.iterator] = function () {
+            let done = false;
+            return { next: function () { let result = { value: 'own', done: done }; done = true; return result; } };
+        };
+        let seen = [];
+        for (const x of array) { seen.push(x); }
+        console.log(JSON.stringify(seen));
+    ";
+    assert_eq!(console_lines(source), ["[\"own\"]"]);
+}
+
+#[test]
+fn test_symbol_errors() {
+    assert_eq!(error_code("'a' + Symbol('b')"), ErrorCode::SymbolConversion);
+    assert_eq!(error_code("Symbol('b') * 1 + Symbol()"), ErrorCode::SymbolConversion);
+    assert_eq!(error_code("new Symbol()"), ErrorCode::NotConstructor);
+    assert_eq!(error_code("let o = {}; o[Symbol.iterator] = function () { return 1; }; for (const x of o) {}"), ErrorCode::InvalidIterator);
+    assert_eq!(error_code("let o = {}; o[Symbol.iterator] = 5; for (const x of o) {}"), ErrorCode::NotIterable);
+}
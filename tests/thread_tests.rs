@@ -0,0 +1,55 @@
+//! Tests for sharing compiled code between threads
+//!
+//! Values and VMs stay on the thread that made them; compiled functions
+//! are plain data that any number of threads can run at once.
+
+use std::sync::Arc;
+use std::thread;
+
+use v8::{BytecodeFunction, Engine, Value, VM};
+
+const PROGRAM: &str = "\
+function fib(n) {
+    if (n < 2) {
+        return n;
+    }
+    return fib(n - 1) + fib(n - 2);
+}
+let greeting = 'fib ' + 15;
+greeting + ' = ' + fib(15);
+";
+
+#[test]
+fn test_compiled_functions_are_send_and_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<BytecodeFunction>();
+    assert_send_sync::<Arc<BytecodeFunction>>();
+}
+
+#[test]
+fn test_one_compiled_program_runs_on_eight_threads() {
+    let function = Engine::builder().compilation_cache(1).build().compile_cached(PROGRAM).unwrap();
+    
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let function = Arc::clone(&function);
+            thread::spawn(move || {
+                // Each thread runs it several times on its own VM
+                let mut vm = VM::new();
+                (0..5)
+                    .map(|_| match vm.execute_shared(function.clone()).unwrap() {
+                        Value::String(text) => text.to_string(),
+                        other => panic!("Expected a string, got {:?}", other),
+                    })
+                    .collect::<Vec<String>>()
+            })
+        })
+        .collect();
+    
+    for handle in handles {
+        assert_eq!(handle.join().unwrap(), vec!["fib 15 = 610".to_string(); 5]);
+    }
+    
+    // Running it never changed it, and the threads' references are gone
+    assert_eq!(Arc::strong_count(&function), 1);
+}
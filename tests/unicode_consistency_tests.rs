@@ -0,0 +1,73 @@
+//! The lexer and everything that prints names and strings back out must
+//! agree on what an identifier is and how a string is escaped
+//!
+//! Each case runs one input through every path and checks they all reach
+//! the same verdict; the inputs are ones the paths used to disagree on.
+
+use v8::lexer::{EscapeValidator, UnicodeHelper};
+use v8::{Disassembler, Engine, GlobalSlot, Lexer, TokenKind, Value};
+
+/// Names that aren't keywords, some of them not identifiers at all
+const NAMES: &[&str] = &[
+    "plain", "_$x1", "café", "\u{2118}", "x\u{200C}y", "a\u{00B2}", "\u{24B6}", "\u{0345}a", "1st", "has space", "",
+];
+
+/// Whether the lexer reads `name` as exactly one identifier
+fn lexes_as_identifier(name: &str) -> bool {
+    match Lexer::new(name).tokenize() {
+        Ok(tokens) => matches!(
+            tokens.iter().map(|token| &token.kind).collect::<Vec<_>>().as_slice(),
+            [TokenKind::Identifier(lexed), TokenKind::Eof] if lexed == name
+        ),
+        Err(_) => false,
+    }
+}
+
+/// Whether `Value::inspect` prints `name` as a key without quotes
+fn inspected_without_quotes(name: &str) -> bool {
+    let mut engine = Engine::new();
+    engine.define_global("key", GlobalSlot::new(Value::string(name)));
+    let object = engine.execute("let o = {}; o[key] = 1; o").unwrap();
+    object.inspect() == format!("{{ {}: 1 }}", name)
+}
+
+/// Whether the disassembly of an object literal prints `name` as a key
+/// without quotes
+fn disassembled_without_quotes(name: &str) -> bool {
+    let source = format!("({{ \"{}\": 1 }})", EscapeValidator::escape_string(name));
+    let function = Engine::new().compile_cached(&source).unwrap();
+    Disassembler::quick_disassemble(&function).contains(&format!("{{ {}: 1 }}", name))
+}
+
+#[test]
+fn test_identifier_classification_agrees_everywhere() {
+    for &name in NAMES {
+        let expected = UnicodeHelper::is_identifier_name(name);
+        assert_eq!(lexes_as_identifier(name), expected, "lexer on {:?}", name);
+        assert_eq!(inspected_without_quotes(name), expected, "inspect on {:?}", name);
+        assert_eq!(disassembled_without_quotes(name), expected, "disassembly on {:?}", name);
+    }
+    // Both kinds of divergence the old checks had are covered
+    assert!(UnicodeHelper::is_identifier_name("\u{2118}"));
+    assert!(!UnicodeHelper::is_identifier_name("a\u{00B2}"));
+}
+
+#[test]
+fn test_escaped_strings_lex_back_to_themselves() {
+    for value in ["plain", "quote \" and \\", "line\nbreak\r\t", "\u{0}\u{8}\u{B}", "bidi\u{202E}\u{2066}", "\u{2028}\u{2029}\u{FEFF}", "😀é"] {
+        let escaped = EscapeValidator::escape_string(value);
+        for source in [format!("\"{}\"", escaped), format!("`{}`", escaped.replace('`', "\\`"))] {
+            let tokens = Lexer::new(&source).tokenize().unwrap();
+            let lexed = match &tokens[0].kind {
+                TokenKind::String(lexed) | TokenKind::NoSubstitutionTemplate(lexed) => lexed.clone(),
+                other => panic!("{}: {:?}", source, other),
+            };
+            assert_eq!(lexed, value, "{}", source);
+        }
+        
+        // The constant pool shows a string as the AST printer writes it
+        let source = format!("\"{}\"", escaped);
+        let function = Engine::new().compile_cached(&format!("{};", source)).unwrap();
+        assert!(Disassembler::quick_disassemble(&function).contains(&source), "{}", source);
+    }
+}
@@ -0,0 +1,110 @@
+//! Tests for `VerifiedFunction`: verified code runs its locals unchecked,
+//! and code that wasn't verified keeps every check
+
+use std::sync::Arc;
+use v8::bytecode::{Bytecode, BytecodeFunction, ConstIndex, JumpOffset, LocalIndex};
+use v8::{Engine, ErrorCode, Value, VerifiedFunction, VM};
+
+/// A function reading local `index` of its `locals`
+fn reading_local(index: u16, locals: u16) -> Arc<BytecodeFunction> {
+    let mut function = BytecodeFunction::new("read".to_string(), 0, locals, 1);
+    function.add_instruction(Bytecode::LdaLocal(LocalIndex(index)));
+    function.add_instruction(Bytecode::Return);
+    Arc::new(function)
+}
+
+#[test]
+fn test_verified_functions_run() {
+    let source = "\
+        function counter() { let n = 0; return () => { n = n + 1; return n; }; }
+        let next = counter();
+        let total = 0;
+        for (let i = 0; i < 10; i++) { const step = next(); total += step; }
+        total";
+    let function = VerifiedFunction::new(Engine::new().compile_cached(source).unwrap()).unwrap();
+    assert_eq!(VM::new().execute_verified(&function).unwrap(), Value::Number(55.0));
+    assert_eq!(VM::new().execute_shared(function.function().clone()).unwrap(), Value::Number(55.0));
+}
+
+#[test]
+fn test_out_of_range_locals_are_caught() {
+    let error = VerifiedFunction::new(reading_local(3, 1)).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidBytecode);
+    assert!(error.to_string().contains("local 3"), "{}", error);
+    
+    // Without verification the VM itself reports the bad index
+    let error = VM::new().execute_shared(reading_local(3, 1)).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::Internal);
+    assert!(error.to_string().contains('3'), "{}", error);
+    
+    let function = VerifiedFunction::new(reading_local(0, 1)).unwrap();
+    assert_eq!(VM::new().execute_verified(&function).unwrap(), Value::Undefined);
+}
+
+/// A function running `instructions`
+fn running(instructions: &[Bytecode]) -> Arc<BytecodeFunction> {
+    let mut function = BytecodeFunction::new("stack".to_string(), 0, 0, 0);
+    for instruction in instructions {
+        function.add_instruction(instruction.clone());
+    }
+    Arc::new(function)
+}
+
+#[test]
+fn test_stack_underflow_is_caught() {
+    for (instructions, message) in [
+        (vec![Bytecode::Pop, Bytecode::Return], "the stack is 0 deep, but Pop needs 1"),
+        (vec![Bytecode::Push, Bytecode::CallMethod(0)], "the stack is 1 deep, but CallMethod 0 needs 2"),
+        (vec![Bytecode::CreateObject, Bytecode::DefineNamedProperty(ConstIndex(0))], "but DefineNamedProperty #0 needs 1"),
+    ] {
+        let error = VerifiedFunction::new(running(&instructions)).unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidBytecode);
+        assert!(error.to_string().contains(message), "{}", error);
+    }
+    
+    // Run anyway, a Pop on an empty stack is an error, not undefined
+    let error = VM::new().execute_shared(running(&[Bytecode::Pop, Bytecode::Return])).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::Internal);
+}
+
+#[test]
+fn test_paths_must_meet_with_one_stack_depth() {
+    // One branch pushes and the other doesn't
+    let error = VerifiedFunction::new(running(&[
+        Bytecode::LdaSmi(1),
+        Bytecode::JumpIfFalse(JumpOffset(1)),
+        Bytecode::Push,
+        Bytecode::Return,
+    ])).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidBytecode);
+    assert!(error.to_string().contains("at offset 3: reached with stack depths 0 and 1"), "{}", error);
+    
+    // A loop that pushes on every iteration
+    let error = VerifiedFunction::new(running(&[Bytecode::Push, Bytecode::Jump(JumpOffset(-2))])).unwrap_err();
+    assert_eq!(error.code(), ErrorCode::InvalidBytecode);
+    assert!(error.to_string().contains("reached with stack depths 0 and 1"), "{}", error);
+    
+    // Balanced branches are fine
+    VerifiedFunction::new(running(&[
+        Bytecode::LdaSmi(1),
+        Bytecode::Push,
+        Bytecode::JumpIfFalse(JumpOffset(2)),
+        Bytecode::Pop,
+        Bytecode::Return,
+        Bytecode::Pop,
+        Bytecode::Return,
+    ])).unwrap();
+}
+
+#[test]
+fn test_compiled_code_is_stack_balanced() {
+    let source = "
+        function* g() { for (const x of [1, 2]) { if (x > 1) break; yield { x, ...{ y: [x] } }; } }
+        class C { m(a, b) { return a && b || !a; } static s() { return new C(); } }
+        let total = 0;
+        for (const v of g()) { for (const w of 'ab') { if (w == 'b') continue; total += v.y[0]; } }
+        while (total < 10) { total++; }
+        C.s().m(total, [1, , 3]);
+    ";
+    VerifiedFunction::new(Engine::new().compile_cached(source).unwrap()).unwrap();
+}